@@ -0,0 +1,15 @@
+//! Aegis-Q Protocol Conformance
+//!
+//! Golden transcripts recorded from the reference implementation.
+//! Replaying them asserts byte-exact wire output, so any change to the
+//! handshake, framing or data path shows up as a fixture mismatch.
+//!
+//! `spec` is a slow, obviously-correct reference implementation that the
+//! production code is differentially tested against.
+
+pub mod spec;
+pub mod transcript;
+pub mod vectors;
+
+pub use transcript::{Record, Transcript};
+pub use vectors::{compute, record, replay, Kind};
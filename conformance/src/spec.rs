@@ -0,0 +1,348 @@
+//! Aegis-Q Reference Implementation ("mini spec")
+//!
+//! A deliberately simple, slow transcription of SPEC.md: the round function,
+//! state initialization, keystream, tag and the versioned ciphertext and
+//! state encodings. Nothing here is optimized, shared with the production
+//! crates or wiped; every layer is written out as its defining formula so it
+//! can be checked by reading. `tests/differential.rs` runs the production
+//! code against it on random inputs.
+//!
+//! ```text
+//! KDF(domain, key, info, len) = SHAKE256("aegis-q-kdf" || domain || key || info)[..len]
+//! state  = lattice (n LE u32) || code (n LE u32) || zk (64) || mask (64)
+//! round  = LatticeMix, CodeMix, ZKMix, MaskMix on the four layers
+//! output = (plaintext XOR SHAKE256(state)) || tag(state, ciphertext)
+//! ```
+//!
+//! Dimensions and round counts are parameters, so the layers can be
+//! compared at any size; the full transform is only practical with the
+//! `small_params` presets.
+
+use hkdf::Hkdf;
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+use sha3::{Digest, Sha3_256, Sha3_512, Shake256};
+
+/// LatticeMix modulus (`0xFFFFFFFF - 5`)
+pub const Q: u64 = 0xFFFF_FFFF - 5;
+
+/// Transform base of LatticeMix
+pub const OMEGA: u64 = 5;
+
+/// ZKMix layer size
+pub const ZK_SIZE: usize = 64;
+
+/// MaskMix layer size
+pub const MASK_SIZE: usize = 64;
+
+/// Dimensions and round count of one parameter set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpecParams {
+    pub lattice_n: usize,
+    pub code_n: usize,
+    pub rounds: usize,
+}
+
+/// Four-layer state
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpecState {
+    pub lattice: Vec<u32>,
+    pub code: Vec<u32>,
+    pub zk: Vec<u8>,
+    pub mask: Vec<u8>,
+}
+
+impl SpecState {
+    /// `lattice || code || zk || mask`, words little-endian
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for word in self.lattice.iter().chain(&self.code) {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.zk);
+        bytes.extend_from_slice(&self.mask);
+        bytes
+    }
+}
+
+/// SHAKE-256 of the concatenated `parts`
+pub fn shake(parts: &[&[u8]], len: usize) -> Vec<u8> {
+    let mut hasher = Shake256::default();
+    for part in parts {
+        hasher.update(part);
+    }
+    let mut out = vec![0u8; len];
+    hasher.finalize_xof().read(&mut out);
+    out
+}
+
+/// Domain-separated KDF
+pub fn kdf(domain: &[u8], key: &[u8], info: &[u8], len: usize) -> Vec<u8> {
+    shake(&[b"aegis-q-kdf", domain, key, info], len)
+}
+
+/// Little-endian u32 words of `bytes`
+fn words(bytes: &[u8]) -> Vec<u32> {
+    bytes
+        .chunks(4)
+        .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
+/// `base^exp mod Q` by repeated squaring
+fn pow_mod(base: u64, exp: u64) -> u64 {
+    let mut result = 1u64;
+    let mut base = base % Q;
+    let mut exp = exp;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = result * base % Q;
+        }
+        base = base * base % Q;
+        exp >>= 1;
+    }
+    result
+}
+
+/// Initial state: each layer is KDF output under its own domain label
+pub fn init_state(key: &[u8], nonce: &[u8], params: SpecParams) -> SpecState {
+    SpecState {
+        lattice: words(&kdf(b"aegis-q-state-lattice", key, nonce, params.lattice_n * 4)),
+        code: words(&kdf(b"aegis-q-state-code", key, nonce, params.code_n * 4)),
+        zk: kdf(b"aegis-q-state-zk", key, nonce, ZK_SIZE),
+        mask: kdf(b"aegis-q-state-mask", key, nonce, MASK_SIZE),
+    }
+}
+
+/// Round keys: HKDF-SHA3-512(salt = nonce, ikm = key), info
+/// `"aegis-q-round-key-{i}"`, 64 bytes each
+pub fn round_keys(key: &[u8], nonce: &[u8], rounds: usize) -> Vec<Vec<u8>> {
+    let hkdf = Hkdf::<Sha3_512>::new(Some(nonce), key);
+    (0..rounds)
+        .map(|i| {
+            let mut round_key = vec![0u8; 64];
+            hkdf.expand(format!("aegis-q-round-key-{}", i).as_bytes(), &mut round_key)
+                .expect("64 bytes is a valid HKDF length");
+            round_key
+        })
+        .collect()
+}
+
+/// LatticeMix: `T⁻¹(T(a) · T(s)) + b` over Z_Q, coefficient-wise
+///
+/// `T(x)_i = Σ_j x_j · ω^(i·j mod n)` and
+/// `T⁻¹(y)_i = n^(Q-2) · Σ_j y_j · ω^((Q - 1 - (i·j mod n)) mod n)`, with
+/// `a`, `b` = KDF("aegis-q-lattice-a" / "-b", round key, nonce) mod Q.
+/// Q is not prime, so `n^(Q-2)` is the defined scaling constant rather
+/// than a true inverse of `n`; the reference follows the definition.
+pub fn lattice_mix(lattice: &[u32], round_key: &[u8], nonce: &[u8]) -> Vec<u32> {
+    let n = lattice.len();
+    let reduce = |bytes: Vec<u8>| -> Vec<u64> { words(&bytes).into_iter().map(|word| word as u64 % Q).collect() };
+    let a = reduce(kdf(b"aegis-q-lattice-a", round_key, nonce, n * 4));
+    let b = reduce(kdf(b"aegis-q-lattice-b", round_key, nonce, n * 4));
+    let s: Vec<u64> = lattice.iter().map(|&word| word as u64).collect();
+
+    let forward = |x: &[u64]| -> Vec<u64> {
+        (0..n)
+            .map(|i| {
+                let mut sum = 0u64;
+                for (j, &coefficient) in x.iter().enumerate() {
+                    let exponent = ((i * j) % n) as u64;
+                    sum = (sum + coefficient * pow_mod(OMEGA, exponent) % Q) % Q;
+                }
+                sum
+            })
+            .collect()
+    };
+    let n_inverse = pow_mod(n as u64, Q - 2);
+    let inverse = |y: &[u64]| -> Vec<u64> {
+        (0..n)
+            .map(|i| {
+                let mut sum = 0u64;
+                for (j, &coefficient) in y.iter().enumerate() {
+                    let exponent = (Q - 1 - ((i * j) % n) as u64) % n as u64;
+                    sum = (sum + coefficient * pow_mod(OMEGA, exponent) % Q) % Q;
+                }
+                sum * n_inverse % Q
+            })
+            .collect()
+    };
+
+    let (a_hat, s_hat) = (forward(&a), forward(&s));
+    let product: Vec<u64> = a_hat.iter().zip(&s_hat).map(|(a, s)| a * s % Q).collect();
+    inverse(&product)
+        .iter()
+        .zip(&b)
+        .map(|(coefficient, b)| ((coefficient + b) % Q) as u32)
+        .collect()
+}
+
+/// Seed hash of one CodeMix entry: SHA3-512(KDF(domain, round key, label, 64))
+fn code_hash(domain: &[u8], round_key: &[u8], label: String) -> Vec<u8> {
+    Sha3_512::digest(kdf(domain, round_key, label.as_bytes(), 64)).to_vec()
+}
+
+/// CodeMix: `P · (G · s)` with wrapping u32 arithmetic
+///
+/// `G[row][col]` is non-zero when `h[0] < 25`, where `h` is the seed hash
+/// of `"{row}-{col}"`; its value is `h[1..5]` as a LE u32. `P` is a
+/// Fisher–Yates shuffle: for `i = n-1 .. 1`, swap `i` with
+/// `LE u64(h[0..8]) mod (i + 1)`, `h` the seed hash of `"{i}"`; then
+/// `out[i] = (G·s)[P[i]]`.
+pub fn code_mix(code: &[u32], round_key: &[u8]) -> Vec<u32> {
+    let n = code.len();
+    let mut matrix = vec![vec![0u32; n]; n];
+    for (row, entries) in matrix.iter_mut().enumerate() {
+        for (col, entry) in entries.iter_mut().enumerate() {
+            let hash = code_hash(b"aegis-q-codemix-matrix", round_key, format!("{}-{}", row, col));
+            if hash[0] < 25 {
+                *entry = u32::from_le_bytes([hash[1], hash[2], hash[3], hash[4]]);
+            }
+        }
+    }
+    let product: Vec<u32> = matrix
+        .iter()
+        .map(|entries| {
+            entries
+                .iter()
+                .zip(code)
+                .fold(0u32, |sum, (entry, word)| sum.wrapping_add(entry.wrapping_mul(*word)))
+        })
+        .collect();
+
+    let mut permutation: Vec<usize> = (0..n).collect();
+    for i in (1..n).rev() {
+        let hash = code_hash(b"aegis-q-codemix-perm", round_key, format!("{}", i));
+        let draw = u64::from_le_bytes(hash[0..8].try_into().expect("8 bytes"));
+        permutation.swap(i, (draw % (i as u64 + 1)) as usize);
+    }
+    permutation.iter().map(|&source| product[source]).collect()
+}
+
+/// ZKMix: `z XOR SHA3-512(nonce || z)`
+pub fn zk_mix(zk: &[u8], nonce: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha3_512::new();
+    Digest::update(&mut hasher, nonce);
+    Digest::update(&mut hasher, zk);
+    let hash = hasher.finalize();
+    zk.iter().zip(hash.iter()).map(|(z, h)| z ^ h).collect()
+}
+
+/// MaskMix: `m XOR SHAKE256(round key || nonce || counter LE u64)`
+pub fn mask_mix(mask: &[u8], round_key: &[u8], nonce: &[u8], counter: u64) -> Vec<u8> {
+    let stream = shake(&[round_key, nonce, &counter.to_le_bytes()], mask.len());
+    mask.iter().zip(&stream).map(|(m, k)| m ^ k).collect()
+}
+
+/// One round; all four layers read the state from before the round
+pub fn round(state: &SpecState, round_key: &[u8], nonce: &[u8], counter: u64) -> SpecState {
+    SpecState {
+        lattice: lattice_mix(&state.lattice, round_key, nonce),
+        code: code_mix(&state.code, round_key),
+        zk: zk_mix(&state.zk, nonce),
+        mask: mask_mix(&state.mask, round_key, nonce, counter),
+    }
+}
+
+/// Initial state after all rounds
+pub fn keyed_state(key: &[u8], nonce: &[u8], params: SpecParams) -> SpecState {
+    let mut state = init_state(key, nonce, params);
+    for (counter, round_key) in round_keys(key, nonce, params.rounds).iter().enumerate() {
+        state = round(&state, round_key, nonce, counter as u64);
+    }
+    state
+}
+
+/// Tag over the ciphertext: SHA3-256(state || ciphertext) for 32 bytes,
+/// SHAKE256("aegis-q-tag" || len || state || ciphertext) otherwise
+pub fn tag(state: &SpecState, ciphertext: &[u8], tag_len: usize) -> Vec<u8> {
+    let state = state.to_bytes();
+    if tag_len == 32 {
+        let mut hasher = Sha3_256::new();
+        Digest::update(&mut hasher, &state);
+        Digest::update(&mut hasher, ciphertext);
+        return hasher.finalize().to_vec();
+    }
+    shake(&[b"aegis-q-tag", &[tag_len as u8], &state, ciphertext], tag_len)
+}
+
+/// `ciphertext || tag`, the ciphertext being the plaintext XOR SHAKE256(state)
+pub fn encrypt(key: &[u8], nonce: &[u8], plaintext: &[u8], params: SpecParams, tag_len: usize) -> Vec<u8> {
+    let state = keyed_state(key, nonce, params);
+    let keystream = shake(&[&state.to_bytes()], plaintext.len());
+    let mut output: Vec<u8> = plaintext.iter().zip(&keystream).map(|(p, k)| p ^ k).collect();
+    let tag = tag(&state, &output, tag_len);
+    output.extend_from_slice(&tag);
+    output
+}
+
+/// Inverse of `encrypt`; `None` on a short input or a wrong tag
+pub fn decrypt(key: &[u8], nonce: &[u8], ciphertext: &[u8], params: SpecParams, tag_len: usize) -> Option<Vec<u8>> {
+    let body_len = ciphertext.len().checked_sub(tag_len)?;
+    let state = keyed_state(key, nonce, params);
+    if tag(&state, &ciphertext[..body_len], tag_len) != ciphertext[body_len..] {
+        return None;
+    }
+    let keystream = shake(&[&state.to_bytes()], body_len);
+    Some(ciphertext[..body_len].iter().zip(&keystream).map(|(c, k)| c ^ k).collect())
+}
+
+/// Ciphertext header of a versioned ciphertext: `"AQCT" || version 2 ||
+/// mode 0 || flags || 0`, flags = tag code << 2 | preset code << 4 (tag
+/// code 0 = 32 bytes, 1 = 16, 2 = 64)
+pub fn versioned_header(preset_code: u8, tag_len: usize) -> Vec<u8> {
+    let tag_code = match tag_len {
+        32 => 0,
+        16 => 1,
+        _ => 2,
+    };
+    let mut header = b"AQCT".to_vec();
+    header.extend_from_slice(&[2, 0, tag_code << 2 | preset_code << 4, 0]);
+    header
+}
+
+/// Versioned ciphertext: `header || encrypt(key, len(nonce) (BE u16) || nonce || header, ...)`
+pub fn encrypt_versioned(
+    key: &[u8],
+    nonce: &[u8],
+    plaintext: &[u8],
+    preset_code: u8,
+    params: SpecParams,
+    tag_len: usize,
+) -> Vec<u8> {
+    let header = versioned_header(preset_code, tag_len);
+    let bound_nonce = [&(nonce.len() as u16).to_be_bytes()[..], nonce, &header].concat();
+    [header, encrypt(key, &bound_nonce, plaintext, params, tag_len)].concat()
+}
+
+/// Persistent state encoding: version 1, then the lattice, code, zk and mask
+/// lengths as BE u16, then `to_bytes()`
+pub fn encode_state(state: &SpecState) -> Vec<u8> {
+    let mut encoded = vec![1u8];
+    for len in [state.lattice.len(), state.code.len(), state.zk.len(), state.mask.len()] {
+        encoded.extend_from_slice(&(len as u16).to_be_bytes());
+    }
+    encoded.extend_from_slice(&state.to_bytes());
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TINY: SpecParams = SpecParams { lattice_n: 4, code_n: 4, rounds: 2 };
+
+    #[test]
+    fn test_spec_round_trip() {
+        let ciphertext = encrypt(b"key", b"nonce", b"reference", TINY, 32);
+        assert_eq!(ciphertext.len(), 9 + 32);
+        assert_eq!(decrypt(b"key", b"nonce", &ciphertext, TINY, 32).unwrap(), b"reference");
+
+        let mut forged = ciphertext.clone();
+        forged[0] ^= 1;
+        assert_eq!(decrypt(b"key", b"nonce", &forged, TINY, 32), None);
+        assert_eq!(decrypt(b"key", b"other", &ciphertext, TINY, 32), None);
+
+        let short = encrypt(b"key", b"nonce", b"reference", TINY, 16);
+        assert_eq!(decrypt(b"key", b"nonce", &short, TINY, 16).unwrap(), b"reference");
+    }
+}
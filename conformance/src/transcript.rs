@@ -0,0 +1,156 @@
+//! Hex transcript fixture format
+//!
+//! One `name = hex` field per line, records separated by blank lines.
+//! Lines starting with `#` are comments. A field name may repeat inside
+//! a record (e.g. several `plaintext` lines for a multi-message session).
+
+/// Single transcript record: ordered list of named byte fields
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Record {
+    pub fields: Vec<(String, Vec<u8>)>,
+}
+
+impl Record {
+    /// Create empty record
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a field
+    pub fn push(&mut self, name: &str, value: &[u8]) {
+        self.fields.push((name.to_string(), value.to_vec()));
+    }
+
+    /// First value of a field
+    pub fn get(&self, name: &str) -> Result<&[u8], String> {
+        self.fields
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_slice())
+            .ok_or_else(|| format!("missing field `{}`", name))
+    }
+
+    /// All values of a (repeated) field, in order
+    pub fn get_all(&self, name: &str) -> Vec<&[u8]> {
+        self.fields
+            .iter()
+            .filter(|(n, _)| n == name)
+            .map(|(_, v)| v.as_slice())
+            .collect()
+    }
+}
+
+/// Parsed transcript file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Transcript {
+    pub records: Vec<Record>,
+}
+
+impl Transcript {
+    /// Parse transcript text
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut records = Vec::new();
+        let mut current = Record::new();
+
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.starts_with('#') {
+                continue;
+            }
+            if line.is_empty() {
+                if !current.fields.is_empty() {
+                    records.push(std::mem::take(&mut current));
+                }
+                continue;
+            }
+
+            let (name, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected `name = hex`", line_no + 1))?;
+            let value = hex_decode(value.trim())
+                .map_err(|e| format!("line {}: {}", line_no + 1, e))?;
+            current.push(name.trim(), &value);
+        }
+
+        if !current.fields.is_empty() {
+            records.push(current);
+        }
+
+        Ok(Self { records })
+    }
+
+    /// Render transcript text (inverse of `parse`, plus a header comment)
+    pub fn render(&self, header: &str) -> String {
+        let mut out = String::new();
+        for line in header.lines() {
+            out.push_str("# ");
+            out.push_str(line);
+            out.push('\n');
+        }
+
+        for record in &self.records {
+            out.push('\n');
+            for (name, value) in &record.fields {
+                out.push_str(name);
+                out.push_str(" =");
+                if !value.is_empty() {
+                    out.push(' ');
+                    out.push_str(&hex_encode(value));
+                }
+                out.push('\n');
+            }
+        }
+
+        out
+    }
+}
+
+/// Lowercase hex encoding
+pub fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Hex decoding (case-insensitive)
+pub fn hex_decode(text: &str) -> Result<Vec<u8>, String> {
+    if !text.len().is_multiple_of(2) {
+        return Err("odd-length hex".to_string());
+    }
+
+    text.as_bytes()
+        .chunks(2)
+        .enumerate()
+        .map(|(i, pair)| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|s| u8::from_str_radix(s, 16).ok())
+                .ok_or_else(|| format!("invalid hex at offset {}", i * 2))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transcript_roundtrip() {
+        let mut record = Record::new();
+        record.push("key", b"k");
+        record.push("plaintext", b"");
+        record.push("plaintext", &[0x00, 0xff]);
+
+        let transcript = Transcript { records: vec![record.clone(), record] };
+        let text = transcript.render("header");
+        let parsed = Transcript::parse(&text).unwrap();
+
+        assert_eq!(transcript, parsed);
+        assert_eq!(parsed.records[0].get_all("plaintext").len(), 2);
+    }
+
+    #[test]
+    fn test_hex_decode_rejects_garbage() {
+        assert!(hex_decode("abc").is_err());
+        assert!(hex_decode("zz").is_err());
+        assert_eq!(hex_decode("0A").unwrap(), vec![0x0a]);
+    }
+}
@@ -0,0 +1,256 @@
+//! Transcript kinds and their reference computations
+//!
+//! Every kind maps a set of input fields to the output fields produced by
+//! the implementation. Recording and replay share the same computation:
+//! a record conforms iff recomputing it from its inputs is byte-identical.
+
+use aegis_q_core::{aegis_q_decrypt, aegis_q_encrypt};
+use messenger::ratchet::RatchetState;
+use utils::keys::RootKey;
+use transport::framing::{Frame, FrameType};
+use transport::quic::QuicSession;
+use transport::vpn::{Handshake, VpnSession};
+
+use crate::transcript::{hex_encode, Record, Transcript};
+
+/// Transcript kind (one fixture file each)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// Raw `aegis_q_encrypt` vectors
+    Core,
+    /// Frame header encoding
+    Frame,
+    /// Handshake messages and shared secret
+    Handshake,
+    /// VPN data path: sequence of frames on one session
+    Vpn,
+    /// QUIC stream encryption
+    Quic,
+    /// Messenger ratchet: sequence of messages on one chain
+    Ratchet,
+}
+
+impl Kind {
+    /// All kinds, in fixture order
+    pub const ALL: [Kind; 6] = [
+        Kind::Core,
+        Kind::Frame,
+        Kind::Handshake,
+        Kind::Vpn,
+        Kind::Quic,
+        Kind::Ratchet,
+    ];
+
+    /// Fixture file name
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            Kind::Core => "core.hex",
+            Kind::Frame => "frame.hex",
+            Kind::Handshake => "handshake.hex",
+            Kind::Vpn => "vpn.hex",
+            Kind::Quic => "quic.hex",
+            Kind::Ratchet => "ratchet.hex",
+        }
+    }
+
+    /// Input field names (everything else in a record is output)
+    pub fn inputs(&self) -> &'static [&'static str] {
+        match self {
+            Kind::Core => &["key", "nonce", "plaintext"],
+            Kind::Frame => &["frame_type", "sequence", "payload"],
+            Kind::Handshake => &["client_key", "server_key"],
+            Kind::Vpn => &["shared_secret", "nonce", "plaintext"],
+            Kind::Quic => &["session_key", "session_nonce", "stream_id", "sequence", "data"],
+            Kind::Ratchet => &["root_key", "plaintext"],
+        }
+    }
+
+    /// Fixed inputs used when (re-)recording fixtures
+    pub fn default_inputs(&self) -> Vec<Record> {
+        let mut records = Vec::new();
+        match self {
+            Kind::Core => {
+                for (key, nonce, plaintext) in [
+                    (&b"00000000000000000000000000000000"[..], &b"0000000000000000"[..], &b""[..]),
+                    (b"00000000000000000000000000000000", b"0000000000000000", b"Hello, Aegis-Q!"),
+                    (
+                        b"0123456789abcdef0123456789abcdef",
+                        b"fedcba9876543210",
+                        b"The quick brown fox jumps over the lazy dog",
+                    ),
+                ] {
+                    let mut r = Record::new();
+                    r.push("key", key);
+                    r.push("nonce", nonce);
+                    r.push("plaintext", plaintext);
+                    records.push(r);
+                }
+            }
+            Kind::Frame => {
+                for (frame_type, sequence, payload) in [
+                    (FrameType::Handshake, 0u64, &b"CLIENT_HELLO"[..]),
+                    (FrameType::Data, 12345, b"Hello, World!"),
+                    (FrameType::Close, u64::MAX, b""),
+                    (FrameType::Heartbeat, 7, b"\x00\x01\x02"),
+                ] {
+                    let mut r = Record::new();
+                    r.push("frame_type", &[frame_type as u8]);
+                    r.push("sequence", &sequence.to_le_bytes());
+                    r.push("payload", payload);
+                    records.push(r);
+                }
+            }
+            Kind::Handshake => {
+                let mut r = Record::new();
+                r.push("client_key", b"client-key-0123456789abcdef");
+                r.push("server_key", b"server-key-0123456789abcdef");
+                records.push(r);
+            }
+            Kind::Vpn => {
+                let mut r = Record::new();
+                r.push("shared_secret", b"shared-secret-123456789012345678901234567890");
+                r.push("nonce", b"vpn-nonce-123456");
+                r.push("plaintext", b"Hello, VPN!");
+                r.push("plaintext", b"");
+                r.push("plaintext", b"third frame");
+                records.push(r);
+            }
+            Kind::Quic => {
+                for (stream_id, sequence) in [(0u32, 0u64), (1, 0), (1, 1)] {
+                    let mut r = Record::new();
+                    r.push("session_key", b"session-key-123456789012345678901234567890");
+                    r.push("session_nonce", b"session-nonce-123456");
+                    r.push("stream_id", &stream_id.to_le_bytes());
+                    r.push("sequence", &sequence.to_le_bytes());
+                    r.push("data", b"Hello, QUIC!");
+                    records.push(r);
+                }
+            }
+            Kind::Ratchet => {
+                let mut r = Record::new();
+                r.push("root_key", b"root-key-123456789012345678901234567890");
+                r.push("plaintext", b"first");
+                r.push("plaintext", b"second");
+                records.push(r);
+            }
+        }
+        records
+    }
+}
+
+/// Recompute a record from its input fields
+///
+/// Returns the inputs (in canonical order) followed by the outputs.
+pub fn compute(kind: Kind, record: &Record) -> Result<Record, String> {
+    let mut out = Record::new();
+    for name in kind.inputs() {
+        for value in record.get_all(name) {
+            out.push(name, value);
+        }
+    }
+
+    match kind {
+        Kind::Core => {
+            let key = record.get("key")?;
+            let nonce = record.get("nonce")?;
+            let plaintext = record.get("plaintext")?;
+
+            let ciphertext = aegis_q_encrypt(key, nonce, plaintext);
+            let decrypted = aegis_q_decrypt(key, nonce, &ciphertext).map_err(|e| e.to_string())?;
+            if decrypted != plaintext {
+                return Err("decrypt(encrypt(p)) != p".to_string());
+            }
+            out.push("ciphertext", &ciphertext);
+        }
+        Kind::Frame => {
+            let frame_type = FrameType::from(*record.get("frame_type")?.first().ok_or("empty frame_type")?);
+            let sequence = u64::from_le_bytes(fixed(record.get("sequence")?)?);
+            let payload = record.get("payload")?;
+
+            let encoded = Frame::new(frame_type, payload.to_vec(), sequence).encode();
+            let decoded = Frame::decode(&encoded).map_err(|e| e.to_string())?;
+            if decoded.frame_type != frame_type || decoded.sequence != sequence || decoded.payload != payload {
+                return Err("decode(encode(frame)) != frame".to_string());
+            }
+            out.push("encoded", &encoded);
+        }
+        Kind::Handshake => {
+            let handshake = Handshake::perform(record.get("client_key")?, record.get("server_key")?);
+            out.push("client_hello", &handshake.client_hello);
+            out.push("server_hello", &handshake.server_hello);
+            out.push("shared_secret", &handshake.shared_secret);
+        }
+        Kind::Vpn => {
+            let mut session = VpnSession::from_handshake(record.get("shared_secret")?, record.get("nonce")?);
+            for plaintext in record.get_all("plaintext") {
+                out.push("frame", &session.encrypt_data(plaintext));
+            }
+        }
+        Kind::Quic => {
+            let session = QuicSession::new(
+                record.get("session_key")?.to_vec(),
+                record.get("session_nonce")?.to_vec(),
+            );
+            let stream_id = u32::from_le_bytes(fixed(record.get("stream_id")?)?);
+            let sequence = u64::from_le_bytes(fixed(record.get("sequence")?)?);
+            let data = record.get("data")?;
+
+            let ciphertext = session.encrypt_stream(stream_id, data, sequence);
+            if session.decrypt_stream(stream_id, &ciphertext, sequence).map_err(|e| e.to_string())? != data {
+                return Err("decrypt_stream(encrypt_stream(d)) != d".to_string());
+            }
+            out.push("ciphertext", &ciphertext);
+        }
+        Kind::Ratchet => {
+            let mut ratchet = RatchetState::new(RootKey::from_bytes(record.get("root_key")?));
+            for plaintext in record.get_all("plaintext") {
+                out.push("ciphertext", &ratchet.encrypt(plaintext));
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// Replay a recorded record and check byte-exact conformance
+pub fn replay(kind: Kind, record: &Record) -> Result<(), String> {
+    let computed = compute(kind, record)?;
+    if computed.fields.len() != record.fields.len() {
+        return Err(format!(
+            "{}: expected {} fields, computed {}",
+            kind.file_name(),
+            record.fields.len(),
+            computed.fields.len()
+        ));
+    }
+
+    for ((name, expected), (_, actual)) in record.fields.iter().zip(computed.fields.iter()) {
+        if expected != actual {
+            return Err(format!(
+                "{}: field `{}` drifted\n  recorded: {}\n  computed: {}",
+                kind.file_name(),
+                name,
+                hex_encode(expected),
+                hex_encode(actual)
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Record a fresh transcript for `kind` from its default inputs
+pub fn record(kind: Kind) -> Result<Transcript, String> {
+    let records = kind
+        .default_inputs()
+        .iter()
+        .map(|r| compute(kind, r))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Transcript { records })
+}
+
+fn fixed<const N: usize>(bytes: &[u8]) -> Result<[u8; N], String> {
+    bytes
+        .try_into()
+        .map_err(|_| format!("expected {} bytes, got {}", N, bytes.len()))
+}
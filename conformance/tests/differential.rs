@@ -0,0 +1,122 @@
+//! Differential tests against the reference implementation
+//!
+//! Random keys, nonces, states and plaintexts go through both the
+//! production code and `conformance::spec`; any divergence (e.g. from an
+//! optimization of a layer) fails with the shrunk input. Single layers and
+//! rounds run at tiny dimensions; whole encryptions only with
+//! `small_params`:
+//!
+//! `cargo test --release -p conformance --features small_params --test differential`
+
+use aegis_q_core::round::{derive_round_keys, round};
+use aegis_q_core::versioned::aegis_q_encrypt_versioned_with;
+use aegis_q_core::{aegis_q_decrypt, aegis_q_encrypt, aegis_q_encrypt_with_tag, AeadId, EncryptOptions, Preset, State, TagSize};
+use conformance::spec::{self, SpecParams, SpecState};
+use pq_primitives::eccodes::{code_mix, GeneratorMatrix, Permutation};
+use pq_primitives::lattice::{derive_lattice_params_n, lattice_mix};
+use pq_primitives::zk::zk_mix;
+use proptest::prelude::*;
+
+fn bytes(max: usize) -> impl Strategy<Value = Vec<u8>> {
+    prop::collection::vec(any::<u8>(), 0..max)
+}
+
+/// State with random layers of `1..max_n` words
+fn tiny_state(max_n: usize) -> impl Strategy<Value = SpecState> {
+    (1..max_n, 1..max_n).prop_flat_map(|(lattice_n, code_n)| {
+        (
+            prop::collection::vec(any::<u32>(), lattice_n),
+            prop::collection::vec(any::<u32>(), code_n),
+            prop::collection::vec(any::<u8>(), spec::ZK_SIZE),
+            prop::collection::vec(any::<u8>(), spec::MASK_SIZE),
+        )
+            .prop_map(|(lattice, code, zk, mask)| SpecState { lattice, code, zk, mask })
+    })
+}
+
+fn production(state: &SpecState) -> State {
+    State {
+        lattice: state.lattice.clone(),
+        code: state.code.clone(),
+        zk: state.zk.clone(),
+        mask: state.mask.clone(),
+    }
+}
+
+fn params(preset: Preset) -> SpecParams {
+    let params = preset.params();
+    SpecParams {
+        lattice_n: params.lattice_n,
+        code_n: params.code_n,
+        rounds: params.rounds,
+    }
+}
+
+proptest! {
+    #[test]
+    fn layers_match(state in tiny_state(24), round_key in bytes(80), nonce in bytes(40)) {
+        let n = state.lattice.len();
+        let (a, b) = derive_lattice_params_n(&round_key, &nonce, n);
+        prop_assert_eq!(lattice_mix(&state.lattice, &a, &b), spec::lattice_mix(&state.lattice, &round_key, &nonce));
+
+        let n = state.code.len();
+        let generator = GeneratorMatrix::from_key_n(&round_key, &nonce, n);
+        let permutation = Permutation::from_key_n(&round_key, &nonce, n);
+        prop_assert_eq!(code_mix(&state.code, &generator, &permutation), spec::code_mix(&state.code, &round_key));
+
+        prop_assert_eq!(zk_mix(&state.zk, &nonce), spec::zk_mix(&state.zk, &nonce));
+    }
+
+    #[test]
+    fn rounds_match(state in tiny_state(16), key in bytes(64), nonce in bytes(32), counter in any::<u64>()) {
+        let round_keys = derive_round_keys(&key, &nonce, 3);
+        prop_assert_eq!(&round_keys, &spec::round_keys(&key, &nonce, 3));
+
+        let mut actual = production(&state);
+        round(&mut actual, &round_keys[0], &nonce, counter);
+        let expected = spec::round(&state, &round_keys[0], &nonce, counter);
+        prop_assert_eq!(actual.to_bytes(), expected.to_bytes());
+    }
+
+    #[test]
+    fn state_init_and_encoding_match(key in bytes(64), nonce in bytes(32)) {
+        let state = State::from_key(&key, &nonce);
+        let expected = spec::init_state(&key, &nonce, params(Preset::default()));
+        prop_assert_eq!(state.to_bytes(), expected.to_bytes());
+        prop_assert_eq!(state.encode(), spec::encode_state(&expected));
+    }
+}
+
+proptest! {
+    // Each case is a full encryption on both sides
+    #![proptest_config(ProptestConfig::with_cases(8))]
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn encryption_matches(key in bytes(64), nonce in bytes(32), plaintext in bytes(600)) {
+        let params = params(Preset::default());
+        let ciphertext = aegis_q_encrypt(&key, &nonce, &plaintext);
+        prop_assert_eq!(&ciphertext, &spec::encrypt(&key, &nonce, &plaintext, params, 32));
+        prop_assert_eq!(spec::decrypt(&key, &nonce, &ciphertext, params, 32), Some(plaintext.clone()));
+        prop_assert_eq!(aegis_q_decrypt(&key, &nonce, &ciphertext).unwrap(), plaintext.clone());
+
+        for tag_size in [TagSize::Bytes16, TagSize::Bytes64] {
+            let tag_len = tag_size.bytes();
+            prop_assert_eq!(
+                aegis_q_encrypt_with_tag(&key, &nonce, &plaintext, tag_size),
+                spec::encrypt(&key, &nonce, &plaintext, params, tag_len)
+            );
+        }
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn versioned_format_matches(key in bytes(64), nonce in bytes(32), plaintext in bytes(200)) {
+        let header = EncryptOptions::new().preset(Preset::AegisQ128).tag_size(TagSize::Bytes16).header();
+        prop_assert_eq!(&header.encode()[..], &spec::versioned_header(1, 16)[..]);
+        prop_assert_eq!(
+            aegis_q_encrypt_versioned_with(&key, &nonce, &plaintext, AeadId::AegisQ128, TagSize::Bytes16).unwrap(),
+            spec::encrypt_versioned(&key, &nonce, &plaintext, 1, params(Preset::AegisQ128), 16)
+        );
+    }
+}
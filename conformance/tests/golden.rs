@@ -0,0 +1,85 @@
+//! Golden transcript replay
+//!
+//! Fixtures are recorded with `small_params` (a single full-parameter
+//! encryption takes minutes), so replay only runs with that feature:
+//!
+//! `cargo test -p conformance --features small_params`
+//!
+//! After an intentional wire-format change, re-record with:
+//!
+//! `cargo test -p conformance --features small_params -- --ignored record_fixtures`
+
+use std::fs;
+use std::path::PathBuf;
+
+use conformance::{replay, Kind, Transcript};
+
+fn fixture_path(kind: Kind) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures")
+        .join("small_params")
+        .join(kind.file_name())
+}
+
+fn replay_fixture(kind: Kind) {
+    let text = fs::read_to_string(fixture_path(kind)).expect("fixture missing");
+    let transcript = Transcript::parse(&text).unwrap();
+    assert!(!transcript.records.is_empty());
+
+    for record in &transcript.records {
+        if let Err(e) = replay(kind, record) {
+            panic!("{}", e);
+        }
+    }
+}
+
+#[test]
+#[cfg_attr(not(feature = "small_params"), ignore)]
+fn golden_core() {
+    replay_fixture(Kind::Core);
+}
+
+#[test]
+#[cfg_attr(not(feature = "small_params"), ignore)]
+fn golden_frame() {
+    replay_fixture(Kind::Frame);
+}
+
+#[test]
+#[cfg_attr(not(feature = "small_params"), ignore)]
+fn golden_handshake() {
+    replay_fixture(Kind::Handshake);
+}
+
+#[test]
+#[cfg_attr(not(feature = "small_params"), ignore)]
+fn golden_vpn() {
+    replay_fixture(Kind::Vpn);
+}
+
+#[test]
+#[cfg_attr(not(feature = "small_params"), ignore)]
+fn golden_quic() {
+    replay_fixture(Kind::Quic);
+}
+
+#[test]
+#[cfg_attr(not(feature = "small_params"), ignore)]
+fn golden_ratchet() {
+    replay_fixture(Kind::Ratchet);
+}
+
+#[test]
+#[ignore]
+#[cfg(feature = "small_params")]
+fn record_fixtures() {
+    for kind in Kind::ALL {
+        let transcript = conformance::record(kind).unwrap();
+        let header = format!(
+            "Aegis-Q golden transcript: {}\nparameter set: small_params\ninputs: {}",
+            kind.file_name(),
+            kind.inputs().join(", ")
+        );
+        fs::write(fixture_path(kind), transcript.render(&header)).unwrap();
+    }
+}
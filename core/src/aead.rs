@@ -0,0 +1,92 @@
+//! RustCrypto `aead` trait implementations (feature `aead`)
+//!
+//! Lets `AegisQCipher` plug into code that is generic over `aead::Aead`
+//! and `aead::AeadInPlace`. Keys are 32 bytes, nonces 16 bytes, tags 32
+//! bytes. Associated data is bound into the nonce (`nonce || ad`); the
+//! nonce has a fixed size, so it needs no length prefix, and with empty
+//! associated data the ciphertext equals `AegisQCipher::encrypt(nonce, plaintext)`.
+
+use aead::consts::{U0, U16, U32};
+use aead::{AeadCore, AeadInPlace, Error, Key, KeyInit, KeySizeUser, Nonce, Tag};
+
+use crate::cipher::AegisQCipher;
+
+/// Nonce with the associated data appended
+fn bound_nonce(nonce: &[u8], associated_data: &[u8]) -> Vec<u8> {
+    let mut bound = nonce.to_vec();
+    bound.extend_from_slice(associated_data);
+    bound
+}
+
+impl KeySizeUser for AegisQCipher {
+    type KeySize = U32;
+}
+
+impl KeyInit for AegisQCipher {
+    fn new(key: &Key<Self>) -> Self {
+        AegisQCipher::new(key.as_slice())
+    }
+}
+
+impl AeadCore for AegisQCipher {
+    type NonceSize = U16;
+    type TagSize = U32;
+    type CiphertextOverhead = U0;
+}
+
+impl AeadInPlace for AegisQCipher {
+    fn encrypt_in_place_detached(
+        &self,
+        nonce: &Nonce<Self>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<Tag<Self>, Error> {
+        let tag = self.seal_in_place(&bound_nonce(nonce, associated_data), buffer);
+        Ok(Tag::<Self>::clone_from_slice(&tag))
+    }
+
+    fn decrypt_in_place_detached(
+        &self,
+        nonce: &Nonce<Self>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+        tag: &Tag<Self>,
+    ) -> Result<(), Error> {
+        self.open_in_place(&bound_nonce(nonce, associated_data), buffer, tag)
+            .map_err(|_| Error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aead::{Aead, Payload};
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_aead_matches_inherent_methods() {
+        let key = Key::<AegisQCipher>::from_slice(b"aead-key-0123456789abcdef0123456");
+        let nonce = Nonce::<AegisQCipher>::from_slice(b"aead-nonce-01234");
+        let cipher = <AegisQCipher as KeyInit>::new(key);
+
+        let ciphertext = Aead::encrypt(&cipher, nonce, &b"generic frame"[..]).unwrap();
+        assert_eq!(ciphertext, AegisQCipher::new(key).encrypt(nonce, b"generic frame"));
+        assert_eq!(Aead::decrypt(&cipher, nonce, ciphertext.as_slice()).unwrap(), b"generic frame");
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_aead_binds_associated_data() {
+        let cipher = <AegisQCipher as KeyInit>::new(Key::<AegisQCipher>::from_slice(b"aead-key-0123456789abcdef0123456"));
+        let nonce = Nonce::<AegisQCipher>::from_slice(b"aead-nonce-01234");
+
+        let mut buffer = b"in place".to_vec();
+        cipher.encrypt_in_place(nonce, b"header", &mut buffer).unwrap();
+        assert_eq!(buffer.len(), 8 + 32);
+
+        let wrong_ad = Payload { msg: buffer.as_slice(), aad: b"other" };
+        assert_eq!(Aead::decrypt(&cipher, nonce, wrong_ad), Err(Error));
+        cipher.decrypt_in_place(nonce, b"header", &mut buffer).unwrap();
+        assert_eq!(buffer, b"in place");
+    }
+}
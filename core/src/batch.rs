@@ -0,0 +1,69 @@
+//! Batch Encryption (feature `parallel`)
+//!
+//! Encrypts or decrypts many independent messages under one key, spread
+//! across the rayon thread pool. Each message is processed exactly as by
+//! `aegis_q_encrypt`/`aegis_q_decrypt`, so batch output is interchangeable
+//! with the single-message functions. Results keep the input order.
+
+use rayon::prelude::*;
+
+use crate::encrypt::{aegis_q_decrypt, aegis_q_encrypt};
+use crate::error::AegisQError;
+
+/// Encrypt `(nonce, plaintext)` pairs in parallel
+///
+/// Every nonce must be unique under `key`, as for `aegis_q_encrypt`.
+pub fn aegis_q_encrypt_batch(key: &[u8], messages: &[(&[u8], &[u8])]) -> Vec<Vec<u8>> {
+    messages
+        .par_iter()
+        .map(|(nonce, plaintext)| aegis_q_encrypt(key, nonce, plaintext))
+        .collect()
+}
+
+/// Decrypt `(nonce, ciphertext)` pairs in parallel
+///
+/// One result per message: a forged message does not fail the batch.
+pub fn aegis_q_decrypt_batch(key: &[u8], messages: &[(&[u8], &[u8])]) -> Vec<Result<Vec<u8>, AegisQError>> {
+    messages
+        .par_iter()
+        .map(|(nonce, ciphertext)| aegis_q_decrypt(key, nonce, ciphertext))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_batch_matches_single_message() {
+        let key = b"batch-key-0123456789abcdef012345";
+        let nonces: Vec<[u8; 16]> = (0u128..8).map(u128::to_be_bytes).collect();
+        let plaintexts: Vec<Vec<u8>> = (0..8).map(|i| vec![i as u8; i * 10]).collect();
+        let messages: Vec<(&[u8], &[u8])> = nonces.iter().zip(&plaintexts).map(|(n, p)| (&n[..], &p[..])).collect();
+
+        let ciphertexts = aegis_q_encrypt_batch(key, &messages);
+        for ((nonce, plaintext), ciphertext) in messages.iter().zip(&ciphertexts) {
+            assert_eq!(*ciphertext, aegis_q_encrypt(key, nonce, plaintext));
+        }
+
+        let sealed: Vec<(&[u8], &[u8])> = nonces.iter().zip(&ciphertexts).map(|(n, c)| (&n[..], &c[..])).collect();
+        let opened: Vec<Vec<u8>> = aegis_q_decrypt_batch(key, &sealed).into_iter().map(Result::unwrap).collect();
+        assert_eq!(opened, plaintexts);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_batch_reports_failures_per_message() {
+        let key = b"batch-key-0123456789abcdef012345";
+        let good = aegis_q_encrypt(key, b"nonce-a", b"envelope");
+        let mut bad = aegis_q_encrypt(key, b"nonce-b", b"envelope");
+        bad[0] ^= 1;
+
+        let results = aegis_q_decrypt_batch(key, &[(b"nonce-a", &good), (b"nonce-b", &bad), (b"nonce-c", b"short")]);
+        assert_eq!(results[0].as_deref(), Ok(&b"envelope"[..]));
+        assert_eq!(results[1], Err(AegisQError::AuthenticationFailed));
+        assert!(results[2].is_err());
+        assert!(aegis_q_encrypt_batch(key, &[]).is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+//! Aegis-Q Cipher API
+//!
+//! Cipher object with a precomputed key schedule, for code that keeps a
+//! key for many messages (`SessionCipher`, the `aead` traits).
+//!
+//! `new` absorbs the key into the state-initialization KDF once and
+//! derives every round's key-only layers (LatticeMix parameters, CodeMix
+//! matrix and permutation) from round keys that do not depend on the
+//! nonce. A message then clones the keyed KDF states for its nonce and
+//! runs the rounds with the stored layers; only ZKMix and MaskMix depend
+//! on the nonce. The schedule holds one CodeMix matrix per round.
+//!
+//! Because the round keys are key-only, this is its own construction:
+//! ciphertexts are not interchangeable with `aegis_q_encrypt`, and one
+//! never verifies as the other.
+
+use utils::kdf::KeyedKdf;
+
+use crate::encrypt::{open_with_state, seal_with_state, TAG_SIZE};
+use crate::error::AegisQError;
+use crate::options::TagSize;
+use crate::params::Preset;
+use crate::round::{derive_round_keys, RoundSchedule};
+use crate::state::{State, DOMAIN_CODE, DOMAIN_LATTICE, DOMAIN_MASK, DOMAIN_ZK};
+
+/// HKDF salt of the key-only round keys
+const SCHEDULE_SALT: &[u8] = b"aegis-q-cipher-schedule";
+
+/// Aegis-Q cipher bound to one key
+pub struct AegisQCipher {
+    rounds: Vec<RoundSchedule>,
+    lattice: KeyedKdf,
+    code: KeyedKdf,
+    zk: KeyedKdf,
+    mask: KeyedKdf,
+}
+
+impl AegisQCipher {
+    /// Create cipher from key, precomputing its schedule
+    pub fn new(key: &[u8]) -> Self {
+        let params = Preset::default().params();
+        let rounds = derive_round_keys(key, SCHEDULE_SALT, params.rounds)
+            .into_iter()
+            .map(|round_key| RoundSchedule::new(round_key, params.lattice_n, params.code_n))
+            .collect();
+        Self {
+            rounds,
+            lattice: KeyedKdf::new(DOMAIN_LATTICE, key),
+            code: KeyedKdf::new(DOMAIN_CODE, key),
+            zk: KeyedKdf::new(DOMAIN_ZK, key),
+            mask: KeyedKdf::new(DOMAIN_MASK, key),
+        }
+    }
+
+    /// Encrypt plaintext; returns ciphertext followed by a 32-byte tag
+    pub fn encrypt(&self, nonce: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        let mut ciphertext = Vec::with_capacity(plaintext.len() + TAG_SIZE);
+        ciphertext.extend_from_slice(plaintext);
+        let tag = self.seal_in_place(nonce, &mut ciphertext);
+        ciphertext.extend_from_slice(&tag);
+        ciphertext
+    }
+
+    /// Decrypt ciphertext produced by `encrypt`
+    pub fn decrypt(&self, nonce: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        if ciphertext.len() < TAG_SIZE {
+            return Err(AegisQError::InvalidLength("Ciphertext too short"));
+        }
+        let (data, tag) = ciphertext.split_at(ciphertext.len() - TAG_SIZE);
+        let mut plaintext = data.to_vec();
+        self.open_in_place(nonce, &mut plaintext, tag)?;
+        Ok(plaintext)
+    }
+
+    /// Encrypt `data` in place and return its 32-byte tag
+    pub(crate) fn seal_in_place(&self, nonce: &[u8], data: &mut [u8]) -> Vec<u8> {
+        seal_with_state(&self.state(nonce), data, TagSize::Bytes32)
+    }
+
+    /// Verify the 32-byte `tag`, then decrypt `data` in place
+    pub(crate) fn open_in_place(&self, nonce: &[u8], data: &mut [u8], tag: &[u8]) -> Result<(), AegisQError> {
+        if tag.len() != TAG_SIZE {
+            return Err(AegisQError::InvalidLength("Invalid tag length"));
+        }
+        open_with_state(&self.state(nonce), data, tag, TagSize::Bytes32)
+    }
+
+    /// Initialize the state for `nonce` and apply all rounds of the schedule
+    fn state(&self, nonce: &[u8]) -> State {
+        let mut state = State::from_kdf(|domain, out| {
+            let kdf = match domain {
+                DOMAIN_LATTICE => &self.lattice,
+                DOMAIN_CODE => &self.code,
+                DOMAIN_ZK => &self.zk,
+                _ => &self.mask,
+            };
+            kdf.fill(nonce, out);
+        });
+        for (i, round) in self.rounds.iter().enumerate() {
+            round.apply(&mut state, nonce, i as u64);
+        }
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encrypt::{aegis_q_decrypt, aegis_q_encrypt};
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_cipher_roundtrip() {
+        let key = b"cipher-key-0123456789abcdef01234";
+        let sender = AegisQCipher::new(key);
+        let receiver = AegisQCipher::new(key);
+
+        for (nonce, plaintext) in [(&b"nonce-0"[..], &b"first frame"[..]), (b"nonce-1", b""), (b"nonce-2", &[0x5a; 300][..])] {
+            let ciphertext = sender.encrypt(nonce, plaintext);
+            assert_eq!(ciphertext.len(), plaintext.len() + TAG_SIZE);
+            assert_eq!(ciphertext, sender.encrypt(nonce, plaintext));
+            assert_eq!(receiver.decrypt(nonce, &ciphertext).unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_cipher_is_separate_from_free_functions() {
+        let key = b"cipher-key-0123456789abcdef01234";
+        let cipher = AegisQCipher::new(key);
+
+        let ciphertext = cipher.encrypt(b"nonce", b"frame");
+        assert_ne!(ciphertext, aegis_q_encrypt(key, b"nonce", b"frame"));
+        assert_eq!(aegis_q_decrypt(key, b"nonce", &ciphertext), Err(AegisQError::AuthenticationFailed));
+        assert_eq!(
+            cipher.decrypt(b"nonce", &aegis_q_encrypt(key, b"nonce", b"frame")),
+            Err(AegisQError::AuthenticationFailed)
+        );
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_cipher_rejects_tampering() {
+        let cipher = AegisQCipher::new(b"cipher-key-0123456789abcdef01234");
+        let mut ciphertext = cipher.encrypt(b"nonce", b"data");
+
+        assert_eq!(cipher.decrypt(b"other", &ciphertext), Err(AegisQError::AuthenticationFailed));
+        ciphertext[0] ^= 1;
+        assert_eq!(cipher.decrypt(b"nonce", &ciphertext), Err(AegisQError::AuthenticationFailed));
+        assert_eq!(
+            cipher.decrypt(b"nonce", &ciphertext[..TAG_SIZE - 1]),
+            Err(AegisQError::InvalidLength("Ciphertext too short"))
+        );
+    }
+}
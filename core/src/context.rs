@@ -0,0 +1,175 @@
+//! Aegis-Q Context API
+//!
+//! Keyed context with option-driven encryption. The ciphertext is
+//! self-describing: header || [key commitment] || Aegis-Q ciphertext.
+//! The header is bound into the nonce (`utils::wire::BoundNonceWire`, the
+//! nonce length-prefixed so nonce and header cannot trade bytes), so it
+//! cannot be altered without failing authentication.
+
+use utils::kdf::kdf_shake256;
+use utils::wire::{BoundNonceWire, Wire};
+
+use crate::error::AegisQError;
+use crate::encrypt::{check_plaintext_len, constant_time_eq, open_with_mode, seal_with_mode};
+use crate::options::{pad, unpad, DecryptOptions, EncryptOptions, Header, HEADER_SIZE};
+
+/// Key commitment size
+pub const COMMITMENT_SIZE: usize = 32;
+
+/// Keyed Aegis-Q context
+pub struct AegisQContext {
+    key: Vec<u8>,
+}
+
+impl AegisQContext {
+    /// Create context from key
+    pub fn new(key: &[u8]) -> Self {
+        Self { key: key.to_vec() }
+    }
+
+    fn commitment(&self, nonce: &[u8], header: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        Ok(kdf_shake256(b"aegis-q-key-commitment", &self.key, &bound_nonce(nonce, header)?, COMMITMENT_SIZE))
+    }
+
+    /// Encrypt with options
+    pub fn encrypt(&self, nonce: &[u8], plaintext: &[u8], options: &EncryptOptions) -> Result<Vec<u8>, AegisQError> {
+        options.validate()?;
+
+        let header = options.header();
+        let encoded = header.encode();
+
+        let mut output = encoded.to_vec();
+        if header.commitment {
+            output.extend_from_slice(&self.commitment(nonce, &encoded)?);
+        }
+
+        let start = output.len();
+        check_plaintext_len(plaintext.len())?;
+        output.extend_from_slice(&pad(plaintext, header.padding));
+        check_plaintext_len(output.len() - start)?;
+        let state = header.preset.keyed_state(&self.key, &bound_nonce(nonce, &encoded)?);
+        let tag = seal_with_mode(&state, &mut output[start..], header.tag_size, header.mode);
+        output.extend_from_slice(&tag);
+        Ok(output)
+    }
+
+    /// Decrypt with options
+    pub fn decrypt(&self, nonce: &[u8], ciphertext: &[u8], options: &DecryptOptions) -> Result<Vec<u8>, AegisQError> {
+        let header = Header::decode(ciphertext)?;
+        options.check(&header)?;
+
+        let encoded = &ciphertext[..HEADER_SIZE];
+        let mut body = &ciphertext[HEADER_SIZE..];
+
+        if header.commitment {
+            if body.len() < COMMITMENT_SIZE {
+                return Err(AegisQError::InvalidLength("Ciphertext too short"));
+            }
+            let (commitment, rest) = body.split_at(COMMITMENT_SIZE);
+            if !constant_time_eq(commitment, &self.commitment(nonce, encoded)?) {
+                return Err(AegisQError::KeyCommitmentMismatch);
+            }
+            body = rest;
+        }
+
+        if body.len() < header.tag_size.bytes() {
+            return Err(AegisQError::InvalidLength("Ciphertext too short"));
+        }
+        let (data, tag) = body.split_at(body.len() - header.tag_size.bytes());
+        let mut padded = data.to_vec();
+        let state = header.preset.keyed_state(&self.key, &bound_nonce(nonce, encoded)?);
+        open_with_mode(&state, &mut padded, tag, header.tag_size, header.mode)?;
+        unpad(padded, header.padding)
+    }
+}
+
+/// Nonce with the encoded header bound in
+pub(crate) fn bound_nonce(nonce: &[u8], header: &[u8]) -> Result<Vec<u8>, AegisQError> {
+    BoundNonceWire {
+        nonce: nonce.to_vec(),
+        header: header.to_vec(),
+    }
+    .to_wire()
+    .map_err(AegisQError::Serialization)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::{Mode, Padding, TagSize};
+    use crate::params::Preset;
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_context_options_roundtrip() {
+        let ctx = AegisQContext::new(b"context-key-0123456789abcdef0123");
+        let nonce = b"context-nonce-01";
+        let options = EncryptOptions::new().padding(Padding::Block(32)).commitment(true);
+
+        let ciphertext = ctx.encrypt(nonce, b"Hello, options!", &options).unwrap();
+        let decrypted = ctx
+            .decrypt(nonce, &ciphertext, &DecryptOptions::new().require_commitment(true))
+            .unwrap();
+        assert_eq!(decrypted, b"Hello, options!");
+
+        // Header is authenticated: flipping the padding flag must fail
+        let mut tampered = ciphertext.clone();
+        tampered[6] &= !0x02;
+        tampered[7] = 0;
+        assert!(ctx.decrypt(nonce, &tampered, &DecryptOptions::new()).is_err());
+    }
+
+    #[test]
+    fn test_bound_nonce_is_length_prefixed() {
+        assert_eq!(bound_nonce(b"ab", b"c").unwrap(), [0, 2, b'a', b'b', b'c']);
+        assert_ne!(bound_nonce(b"ab", b"c").unwrap(), bound_nonce(b"a", b"bc").unwrap());
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_context_policy_rejects() {
+        let ctx = AegisQContext::new(b"context-key-0123456789abcdef0123");
+        let ciphertext = ctx.encrypt(b"nonce", b"data", &EncryptOptions::new()).unwrap();
+
+        assert_eq!(
+            ctx.decrypt(b"nonce", &ciphertext, &DecryptOptions::new().require_commitment(true)),
+            Err(AegisQError::Policy("Key commitment required"))
+        );
+        assert!(matches!(
+            ctx.decrypt(b"nonce", &ciphertext, &DecryptOptions::new().require_padding(true)),
+            Err(AegisQError::Policy(_))
+        ));
+        assert_eq!(ctx.decrypt(b"nonce", &ciphertext, &DecryptOptions::new()).unwrap(), b"data");
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_context_tag_size_from_header() {
+        let ctx = AegisQContext::new(b"context-key-0123456789abcdef0123");
+        let options = EncryptOptions::new().tag_size(TagSize::Bytes64);
+        let ciphertext = ctx.encrypt(b"nonce", b"data", &options).unwrap();
+        assert_eq!(ciphertext.len(), HEADER_SIZE + 4 + 64);
+        assert_eq!(ctx.decrypt(b"nonce", &ciphertext, &DecryptOptions::new()).unwrap(), b"data");
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_context_preset_and_mode_from_header() {
+        let ctx = AegisQContext::new(b"context-key-0123456789abcdef0123");
+        let options = EncryptOptions::new().preset(Preset::AegisQ128);
+        let ciphertext = ctx.encrypt(b"nonce", b"data", &options).unwrap();
+        assert_eq!(Header::decode(&ciphertext).unwrap().preset, Preset::AegisQ128);
+        assert_eq!(ctx.decrypt(b"nonce", &ciphertext, &DecryptOptions::new()).unwrap(), b"data");
+
+        let segmented = ctx.encrypt(b"nonce", b"data", &EncryptOptions::new().mode(Mode::Segmented)).unwrap();
+        assert_eq!(ctx.decrypt(b"nonce", &segmented, &DecryptOptions::new()).unwrap(), b"data");
+        assert!(matches!(
+            ctx.decrypt(b"nonce", &segmented, &DecryptOptions::new().mode(Mode::Standard)),
+            Err(AegisQError::Policy(_))
+        ));
+
+        // Ciphertexts differ from the default preset
+        let default = ctx.encrypt(b"nonce", b"data", &EncryptOptions::new()).unwrap();
+        assert_ne!(ciphertext[HEADER_SIZE..], default[HEADER_SIZE..]);
+    }
+}
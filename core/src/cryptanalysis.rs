@@ -0,0 +1,155 @@
+//! Reduced-Round Research Mode (feature `cryptanalysis`)
+//!
+//! Weakened Aegis-Q variants for differential and statistical analysis: a
+//! chosen round count and any of the four round layers switched off. A
+//! disabled layer leaves its state component unchanged in every round.
+//!
+//! Ciphertexts of a weakened variant do not interoperate with the regular
+//! API and offer no security; never use them for real data. The feature is
+//! off by default and nothing outside this module can select a variant, so
+//! the regular `aegis_q_*` functions, presets and ciphers always run the
+//! full round function.
+
+use crate::encrypt::{apply_rounds_with_layers, open_with_state, seal_with_state, TAG_SIZE};
+use crate::error::AegisQError;
+use crate::options::TagSize;
+use crate::params::{Params, Preset};
+use crate::round::Layers;
+use crate::state::State;
+
+/// One layer of the round function
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Layer {
+    LatticeMix,
+    CodeMix,
+    ZKMix,
+    MaskMix,
+}
+
+/// Aegis-Q with a chosen round count and layer selection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResearchCipher {
+    params: Params,
+    layers: Layers,
+}
+
+impl ResearchCipher {
+    /// Full-strength variant of `preset`; weaken it with `rounds`/`without`
+    pub fn new(preset: Preset) -> Self {
+        Self {
+            params: preset.params(),
+            layers: Layers::ALL,
+        }
+    }
+
+    /// Use `rounds` rounds (0 leaves the initial state)
+    pub fn rounds(mut self, rounds: usize) -> Self {
+        self.params.rounds = rounds;
+        self
+    }
+
+    /// Skip `layer` in every round
+    pub fn without(mut self, layer: Layer) -> Self {
+        self.set_layer(layer, false);
+        self
+    }
+
+    /// Apply `layer` again after `without`
+    pub fn with(mut self, layer: Layer) -> Self {
+        self.set_layer(layer, true);
+        self
+    }
+
+    /// Whether rounds apply `layer`
+    pub fn has_layer(&self, layer: Layer) -> bool {
+        match layer {
+            Layer::LatticeMix => self.layers.lattice,
+            Layer::CodeMix => self.layers.code,
+            Layer::ZKMix => self.layers.zk,
+            Layer::MaskMix => self.layers.mask,
+        }
+    }
+
+    /// Dimensions and round count of the variant
+    pub fn params(&self) -> Params {
+        self.params
+    }
+
+    /// State after initialization and all rounds, for analysing the state
+    /// directly
+    pub fn keyed_state(&self, key: &[u8], nonce: &[u8]) -> State {
+        let mut state = State::from_key_with(key, nonce, self.params);
+        apply_rounds_with_layers(&mut state, key, nonce, self.params.rounds, self.layers);
+        state
+    }
+
+    /// Encrypt (ciphertext || 32-byte tag)
+    pub fn encrypt(&self, key: &[u8], nonce: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        let mut ciphertext = plaintext.to_vec();
+        let tag = seal_with_state(&self.keyed_state(key, nonce), &mut ciphertext, TagSize::Bytes32);
+        ciphertext.extend_from_slice(&tag);
+        ciphertext
+    }
+
+    /// Decrypt a ciphertext of `encrypt` with the same variant
+    pub fn decrypt(&self, key: &[u8], nonce: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        if ciphertext.len() < TAG_SIZE {
+            return Err(AegisQError::InvalidLength("Ciphertext too short"));
+        }
+        let (data, tag) = ciphertext.split_at(ciphertext.len() - TAG_SIZE);
+        let mut plaintext = data.to_vec();
+        open_with_state(&self.keyed_state(key, nonce), &mut plaintext, tag, TagSize::Bytes32)?;
+        Ok(plaintext)
+    }
+
+    fn set_layer(&mut self, layer: Layer, enabled: bool) {
+        match layer {
+            Layer::LatticeMix => self.layers.lattice = enabled,
+            Layer::CodeMix => self.layers.code = enabled,
+            Layer::ZKMix => self.layers.zk = enabled,
+            Layer::MaskMix => self.layers.mask = enabled,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encrypt::aegis_q_encrypt;
+    use crate::params::{AegisQ128, ParameterSet};
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_full_variant_matches_regular_api() {
+        let key = b"research key";
+        let nonce = b"research nonce";
+        let plaintext = b"differential input";
+        let full = ResearchCipher::new(Preset::default());
+        assert_eq!(full.encrypt(key, nonce, plaintext), aegis_q_encrypt(key, nonce, plaintext));
+        assert_eq!(ResearchCipher::new(Preset::AegisQ128).encrypt(key, nonce, plaintext), AegisQ128::encrypt(key, nonce, plaintext));
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_weakened_variants() {
+        let key = b"research key";
+        let nonce = b"research nonce";
+        let full = ResearchCipher::new(Preset::AegisQ128);
+        let reduced = full.rounds(1);
+        let no_code = full.without(Layer::CodeMix);
+        assert!(!no_code.has_layer(Layer::CodeMix));
+        assert_eq!(no_code.with(Layer::CodeMix), full);
+
+        for variant in [reduced, no_code, full.rounds(0).without(Layer::LatticeMix).without(Layer::MaskMix)] {
+            let ciphertext = variant.encrypt(key, nonce, b"message");
+            assert_ne!(ciphertext, full.encrypt(key, nonce, b"message"));
+            assert_eq!(variant.decrypt(key, nonce, &ciphertext).unwrap(), b"message");
+            assert!(full.decrypt(key, nonce, &ciphertext).is_err());
+        }
+
+        // Without CodeMix the code component only depends on initialization
+        let initial = ResearchCipher::new(Preset::AegisQ128).rounds(0).keyed_state(key, nonce);
+        assert_eq!(no_code.keyed_state(key, nonce).code, initial.code);
+        assert_ne!(no_code.keyed_state(key, nonce).lattice, initial.lattice);
+    }
+}
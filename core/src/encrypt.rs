@@ -2,10 +2,36 @@
 //! 
 //! High-level API for encrypting and decrypting data using Aegis-Q
 
+use crate::error::AegisQError;
 use crate::state::State;
-use crate::round::{round, derive_round_keys, ROUNDS};
+use crate::round::{round_with_layers, derive_round_keys, Layers, ROUNDS};
+use crate::options::{Mode, TagSize};
+use crate::keystream::{apply_segmented_keystream, segmented_tag};
+use utils::memory::Wipe;
 use sha3::{Digest, Shake256, digest::{Update, ExtendableOutput, XofReader}};
 
+/// Default authentication tag size (256-bit tag)
+pub const TAG_SIZE: usize = 32;
+
+/// Largest plaintext accepted by one encryption (2 GiB)
+///
+/// Encryption panics above it (`aegis_q_try_encrypt` fails with
+/// `LimitExceeded` instead) and decryption fails with `LimitExceeded`.
+/// Larger data goes through `AegisQStreamEncryptor` or the segmented API
+/// in bounded chunks.
+pub const MAX_PLAINTEXT_SIZE: usize = 1 << 31;
+
+/// Ciphertext length for a plaintext of `plaintext_len` bytes (default tag)
+pub const fn ciphertext_len(plaintext_len: usize) -> usize {
+    TagSize::Bytes32.ciphertext_len(plaintext_len)
+}
+
+/// Plaintext length of a ciphertext of `ciphertext_len` bytes (default
+/// tag); `None` if it is shorter than the tag
+pub const fn plaintext_len(ciphertext_len: usize) -> Option<usize> {
+    TagSize::Bytes32.plaintext_len(ciphertext_len)
+}
+
 /// Initialize Aegis-Q state from key and nonce
 pub fn aegis_q_init(key: &[u8], nonce: &[u8]) -> State {
     State::from_key(key, nonce)
@@ -13,6 +39,9 @@ pub fn aegis_q_init(key: &[u8], nonce: &[u8]) -> State {
 
 /// Encrypt plaintext using Aegis-Q
 /// 
+/// # Panics
+/// If `plaintext` is longer than `MAX_PLAINTEXT_SIZE`
+/// 
 /// # Arguments
 /// * `key` - Encryption key (recommended: 32-64 bytes)
 /// * `nonce` - Nonce (recommended: 16-32 bytes)
@@ -21,35 +50,171 @@ pub fn aegis_q_init(key: &[u8], nonce: &[u8]) -> State {
 /// # Returns
 /// Ciphertext (same length as plaintext + authentication tag)
 pub fn aegis_q_encrypt(key: &[u8], nonce: &[u8], plaintext: &[u8]) -> Vec<u8> {
-    // Initialize state
-    let mut state = aegis_q_init(key, nonce);
-    
-    // Derive round keys
-    let round_keys = derive_round_keys(key, nonce, ROUNDS);
-    
-    // Apply rounds
-    for i in 0..ROUNDS {
-        round(&mut state, &round_keys[i], nonce, i as u64);
+    aegis_q_encrypt_with_tag(key, nonce, plaintext, TagSize::Bytes32)
+}
+
+/// Encrypt plaintext, failing instead of panicking on oversized input
+///
+/// For plaintext whose size the caller does not bound (stored records,
+/// serialized application data); otherwise the same as `aegis_q_encrypt`.
+pub fn aegis_q_try_encrypt(key: &[u8], nonce: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, AegisQError> {
+    aegis_q_try_encrypt_with_tag(key, nonce, plaintext, TagSize::Bytes32)
+}
+
+/// Fallible variant of `aegis_q_encrypt_with_tag`
+pub fn aegis_q_try_encrypt_with_tag(key: &[u8], nonce: &[u8], plaintext: &[u8], tag_size: TagSize) -> Result<Vec<u8>, AegisQError> {
+    check_plaintext_len(plaintext.len())?;
+    Ok(aegis_q_encrypt_with_tag(key, nonce, plaintext, tag_size))
+}
+
+/// `LimitExceeded` for plaintexts longer than `MAX_PLAINTEXT_SIZE`
+pub(crate) fn check_plaintext_len(len: usize) -> Result<(), AegisQError> {
+    if len > MAX_PLAINTEXT_SIZE {
+        return Err(AegisQError::LimitExceeded("Plaintext exceeds maximum message size"));
     }
+    Ok(())
+}
+
+/// Nonce length used by `aegis_q_encrypt_auto`
+pub const AUTO_NONCE_SIZE: usize = 16;
+
+/// Encrypt under a fresh random nonce
+/// 
+/// The nonce comes from `utils::rng` and is returned with the ciphertext;
+/// store or send it alongside and decrypt with `aegis_q_decrypt`. Prefer
+/// this over choosing nonces by hand unless the protocol derives them
+/// (e.g. from a sequence number).
+/// 
+/// # Returns
+/// `(nonce, ciphertext)`
+pub fn aegis_q_encrypt_auto(key: &[u8], plaintext: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let nonce = utils::rng::random_bytes(AUTO_NONCE_SIZE);
+    let ciphertext = aegis_q_encrypt(key, &nonce, plaintext);
+    (nonce, ciphertext)
+}
+
+/// Encrypt plaintext using Aegis-Q with a chosen tag size
+/// 
+/// # Returns
+/// Ciphertext (same length as plaintext + `tag_size` bytes)
+pub fn aegis_q_encrypt_with_tag(key: &[u8], nonce: &[u8], plaintext: &[u8], tag_size: TagSize) -> Vec<u8> {
+    let mut ciphertext = Vec::with_capacity(plaintext.len() + tag_size.bytes());
+    let tag = seal_into(key, nonce, plaintext, tag_size, &mut ciphertext);
     
-    // Generate keystream using KDF
-    let keystream = kdf(&state, plaintext.len());
+    // Append tag to ciphertext
+    ciphertext.extend_from_slice(&tag);
     
-    // XOR with plaintext
+    ciphertext
+}
+
+/// Encrypt plaintext, returning the ciphertext and tag separately
+/// 
+/// For protocols that carry the tag in its own field. The ciphertext has
+/// the same length as the plaintext.
+pub fn aegis_q_encrypt_detached(key: &[u8], nonce: &[u8], plaintext: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    aegis_q_encrypt_detached_with_tag(key, nonce, plaintext, TagSize::Bytes32)
+}
+
+/// Detached variant of `aegis_q_encrypt_with_tag`
+pub fn aegis_q_encrypt_detached_with_tag(key: &[u8], nonce: &[u8], plaintext: &[u8], tag_size: TagSize) -> (Vec<u8>, Vec<u8>) {
     let mut ciphertext = Vec::with_capacity(plaintext.len());
-    for i in 0..plaintext.len() {
-        ciphertext.push(plaintext[i] ^ keystream[i]);
+    let tag = seal_into(key, nonce, plaintext, tag_size, &mut ciphertext);
+    (ciphertext, tag)
+}
+
+/// Encrypt plaintext in place within the caller's buffer
+/// 
+/// The keystream is XORed straight into `buffer` and the tag appended, so
+/// the only allocation is reserving room for the tag. The result is the
+/// same as `aegis_q_encrypt`.
+pub fn aegis_q_encrypt_in_place(key: &[u8], nonce: &[u8], buffer: &mut Vec<u8>) {
+    aegis_q_encrypt_in_place_with_tag(key, nonce, buffer, TagSize::Bytes32)
+}
+
+/// In-place variant of `aegis_q_encrypt_with_tag`
+pub fn aegis_q_encrypt_in_place_with_tag(key: &[u8], nonce: &[u8], buffer: &mut Vec<u8>, tag_size: TagSize) {
+    let tag = seal_in_place(key, nonce, buffer, tag_size);
+    buffer.extend_from_slice(&tag);
+}
+
+/// Encrypt a fixed-size buffer in place and return the tag
+/// 
+/// For callers that cannot grow their buffer (embedded, preallocated
+/// packet slots); pairs with `aegis_q_decrypt_detached`.
+pub fn aegis_q_encrypt_in_place_detached(key: &[u8], nonce: &[u8], buffer: &mut [u8]) -> Vec<u8> {
+    seal_in_place(key, nonce, buffer, TagSize::Bytes32)
+}
+
+/// Append the encrypted plaintext to `ciphertext` and return its tag
+fn seal_into(key: &[u8], nonce: &[u8], plaintext: &[u8], tag_size: TagSize, ciphertext: &mut Vec<u8>) -> Vec<u8> {
+    let start = ciphertext.len();
+    ciphertext.extend_from_slice(plaintext);
+    seal_in_place(key, nonce, &mut ciphertext[start..], tag_size)
+}
+
+/// Encrypt `data` in place and return its tag
+fn seal_in_place(key: &[u8], nonce: &[u8], data: &mut [u8], tag_size: TagSize) -> Vec<u8> {
+    // Initialize state and apply rounds
+    let state = keyed_state(key, nonce);
+    seal_with_state(&state, data, tag_size)
+}
+
+/// Encrypt `data` in place under an already keyed state and return its tag
+pub(crate) fn seal_with_state(state: &State, data: &mut [u8], tag_size: TagSize) -> Vec<u8> {
+    seal_with_mode(state, data, tag_size, Mode::Standard)
+}
+
+/// `seal_with_state` with the keystream and tag construction of `mode`
+pub(crate) fn seal_with_mode(state: &State, data: &mut [u8], tag_size: TagSize, mode: Mode) -> Vec<u8> {
+    assert!(data.len() <= MAX_PLAINTEXT_SIZE, "plaintext exceeds MAX_PLAINTEXT_SIZE");
+    match mode {
+        Mode::Standard => {
+            apply_keystream(state, data);
+            generate_tag(state, data, tag_size)
+        }
+        Mode::Segmented => {
+            apply_segmented_keystream(state, data);
+            segmented_tag(state, data, tag_size)
+        }
     }
-    
-    // Generate authentication tag
-    let tag = generate_tag(&state, &ciphertext);
-    
-    // Append tag to ciphertext
+}
+
+/// Encrypt a large plaintext with the segmented keystream
+/// 
+/// Same layout as `aegis_q_encrypt` (ciphertext || 32-byte tag), but the
+/// keystream is expanded per 64 KiB segment (see `keystream`), in parallel
+/// with the `parallel` feature. Open with `aegis_q_decrypt_segmented`.
+///
+/// # Panics
+/// If `plaintext` is longer than `MAX_PLAINTEXT_SIZE`
+/// (`aegis_q_try_encrypt_segmented` fails instead)
+pub fn aegis_q_encrypt_segmented(key: &[u8], nonce: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    let state = keyed_state(key, nonce);
+    let mut ciphertext = Vec::with_capacity(plaintext.len() + TAG_SIZE);
+    ciphertext.extend_from_slice(plaintext);
+    let tag = seal_with_mode(&state, &mut ciphertext, TagSize::Bytes32, Mode::Segmented);
     ciphertext.extend_from_slice(&tag);
-    
     ciphertext
 }
 
+/// Fallible variant of `aegis_q_encrypt_segmented`
+pub fn aegis_q_try_encrypt_segmented(key: &[u8], nonce: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, AegisQError> {
+    check_plaintext_len(plaintext.len())?;
+    Ok(aegis_q_encrypt_segmented(key, nonce, plaintext))
+}
+
+/// Decrypt ciphertext produced by `aegis_q_encrypt_segmented`
+pub fn aegis_q_decrypt_segmented(key: &[u8], nonce: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AegisQError> {
+    if ciphertext.len() < TAG_SIZE {
+        return Err(AegisQError::InvalidLength("Ciphertext too short"));
+    }
+    let (data, tag) = ciphertext.split_at(ciphertext.len() - TAG_SIZE);
+    let state = keyed_state(key, nonce);
+    let mut plaintext = data.to_vec();
+    open_with_mode(&state, &mut plaintext, tag, TagSize::Bytes32, Mode::Segmented)?;
+    Ok(plaintext)
+}
+
 /// Decrypt ciphertext using Aegis-Q
 /// 
 /// # Arguments
@@ -59,69 +224,258 @@ pub fn aegis_q_encrypt(key: &[u8], nonce: &[u8], plaintext: &[u8]) -> Vec<u8> {
 /// 
 /// # Returns
 /// Plaintext or error if authentication fails
-pub fn aegis_q_decrypt(key: &[u8], nonce: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, &'static str> {
-    const TAG_SIZE: usize = 32; // 256-bit tag
-    
-    if ciphertext.len() < TAG_SIZE {
-        return Err("Ciphertext too short");
+pub fn aegis_q_decrypt(key: &[u8], nonce: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AegisQError> {
+    aegis_q_decrypt_with_tag(key, nonce, ciphertext, TagSize::Bytes32)
+}
+
+/// Decrypt ciphertext produced with `aegis_q_encrypt_with_tag`
+pub fn aegis_q_decrypt_with_tag(key: &[u8], nonce: &[u8], ciphertext: &[u8], tag_size: TagSize) -> Result<Vec<u8>, AegisQError> {
+    let mut buffer = ciphertext.to_vec();
+    let plaintext_len = aegis_q_decrypt_in_place_with_tag(key, nonce, &mut buffer, tag_size)?.len();
+    buffer.truncate(plaintext_len);
+    Ok(buffer)
+}
+
+/// Decrypt ciphertext in place within the caller's buffer
+/// 
+/// # Arguments
+/// * `key` - Decryption key (must match encryption key)
+/// * `nonce` - Nonce (must match encryption nonce)
+/// * `buffer` - Ciphertext including authentication tag; overwritten with plaintext
+/// 
+/// # Returns
+/// View of the plaintext at the start of `buffer`, or error if authentication
+/// fails (the buffer is left untouched in that case)
+pub fn aegis_q_decrypt_in_place<'a>(key: &[u8], nonce: &[u8], buffer: &'a mut [u8]) -> Result<&'a mut [u8], AegisQError> {
+    aegis_q_decrypt_in_place_with_tag(key, nonce, buffer, TagSize::Bytes32)
+}
+
+/// In-place variant of `aegis_q_decrypt_with_tag`
+pub fn aegis_q_decrypt_in_place_with_tag<'a>(
+    key: &[u8],
+    nonce: &[u8],
+    buffer: &'a mut [u8],
+    tag_size: TagSize,
+) -> Result<&'a mut [u8], AegisQError> {
+    if buffer.len() < tag_size.bytes() {
+        return Err(AegisQError::InvalidLength("Ciphertext too short"));
     }
     
-    let (encrypted_data, tag) = ciphertext.split_at(ciphertext.len() - TAG_SIZE);
+    let data_len = buffer.len() - tag_size.bytes();
+    let (data, tag) = buffer.split_at_mut(data_len);
+    open_in_place(key, nonce, data, tag, tag_size)?;
+    Ok(data)
+}
+
+/// Decrypt a ciphertext whose tag travels separately
+/// 
+/// # Returns
+/// Plaintext or error if `tag` has the wrong length or authentication fails
+pub fn aegis_q_decrypt_detached(key: &[u8], nonce: &[u8], ciphertext: &[u8], tag: &[u8]) -> Result<Vec<u8>, AegisQError> {
+    aegis_q_decrypt_detached_with_tag(key, nonce, ciphertext, tag, TagSize::Bytes32)
+}
+
+/// Detached variant of `aegis_q_decrypt_with_tag`
+pub fn aegis_q_decrypt_detached_with_tag(
+    key: &[u8],
+    nonce: &[u8],
+    ciphertext: &[u8],
+    tag: &[u8],
+    tag_size: TagSize,
+) -> Result<Vec<u8>, AegisQError> {
+    let mut plaintext = ciphertext.to_vec();
+    open_in_place(key, nonce, &mut plaintext, tag, tag_size)?;
+    Ok(plaintext)
+}
+
+/// Verify `tag` over `data`, then decrypt `data` in place
+/// 
+/// `data` is left untouched if verification fails.
+fn open_in_place(key: &[u8], nonce: &[u8], data: &mut [u8], tag: &[u8], tag_size: TagSize) -> Result<(), AegisQError> {
+    if tag.len() != tag_size.bytes() {
+        return Err(AegisQError::InvalidLength("Invalid tag length"));
+    }
+    let state = keyed_state(key, nonce);
+    open_with_state(&state, data, tag, tag_size)
+}
+
+/// Verify and decrypt `data` in place under an already keyed state
+pub(crate) fn open_with_state(state: &State, data: &mut [u8], tag: &[u8], tag_size: TagSize) -> Result<(), AegisQError> {
+    open_with_mode(state, data, tag, tag_size, Mode::Standard)
+}
+
+/// `open_with_state` with the keystream and tag construction of `mode`
+pub(crate) fn open_with_mode(state: &State, data: &mut [u8], tag: &[u8], tag_size: TagSize, mode: Mode) -> Result<(), AegisQError> {
+    if data.len() > MAX_PLAINTEXT_SIZE {
+        return Err(AegisQError::LimitExceeded("Ciphertext exceeds maximum message size"));
+    }
+    // Verify tag (constant-time comparison)
+    let computed_tag = match mode {
+        Mode::Standard => generate_tag(state, data, tag_size),
+        Mode::Segmented => segmented_tag(state, data, tag_size),
+    };
+    if !constant_time_eq(&computed_tag, tag) {
+        return Err(AegisQError::AuthenticationFailed);
+    }
     
-    // Initialize state (same as encryption)
-    let mut state = aegis_q_init(key, nonce);
+    match mode {
+        Mode::Standard => apply_keystream(state, data),
+        Mode::Segmented => apply_segmented_keystream(state, data),
+    }
     
-    // Derive round keys
-    let round_keys = derive_round_keys(key, nonce, ROUNDS);
+    Ok(())
+}
+
+/// Size of the key commitment absorbed into committing tags
+pub const KEY_COMMITMENT_SIZE: usize = 32;
+
+/// Encrypt with a key-committing tag
+/// 
+/// The 32-byte tag is `SHA3-256(label || commitment || state || ct)` with
+/// `commitment = KDF(key, nonce)`, so a ciphertext authenticates under one
+/// key only: finding a second key that opens it means a collision in the
+/// commitment or the tag hash. Use where a recipient tries several keys
+/// (multi-recipient envelopes, licensing) and a partitioning oracle must
+/// not exist. Not interchangeable with `aegis_q_encrypt` ciphertexts.
+pub fn aegis_q_encrypt_committing(key: &[u8], nonce: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    assert!(plaintext.len() <= MAX_PLAINTEXT_SIZE, "plaintext exceeds MAX_PLAINTEXT_SIZE");
+    let state = keyed_state(key, nonce);
+    let mut ciphertext = Vec::with_capacity(plaintext.len() + TAG_SIZE);
+    ciphertext.extend_from_slice(plaintext);
+    apply_keystream(&state, &mut ciphertext);
     
-    // Apply rounds
-    for i in 0..ROUNDS {
-        round(&mut state, &round_keys[i], nonce, i as u64);
+    let tag = committing_tag(&key_commitment(key, nonce), &state, &ciphertext);
+    ciphertext.extend_from_slice(&tag);
+    ciphertext
+}
+
+/// Fallible variant of `aegis_q_encrypt_committing`
+pub fn aegis_q_try_encrypt_committing(key: &[u8], nonce: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, AegisQError> {
+    check_plaintext_len(plaintext.len())?;
+    Ok(aegis_q_encrypt_committing(key, nonce, plaintext))
+}
+
+/// Decrypt ciphertext produced by `aegis_q_encrypt_committing`
+/// 
+/// # Returns
+/// Plaintext, or `AuthenticationFailed` for a wrong key, nonce or tampering
+pub fn aegis_q_decrypt_committing(key: &[u8], nonce: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AegisQError> {
+    if ciphertext.len() < TAG_SIZE {
+        return Err(AegisQError::InvalidLength("Ciphertext too short"));
+    }
+    let (data, tag) = ciphertext.split_at(ciphertext.len() - TAG_SIZE);
+    if data.len() > MAX_PLAINTEXT_SIZE {
+        return Err(AegisQError::LimitExceeded("Ciphertext exceeds maximum message size"));
     }
+    let state = keyed_state(key, nonce);
     
-    // Verify tag (constant-time comparison)
-    let computed_tag = generate_tag(&state, encrypted_data);
+    // Verify tag (constant-time comparison) before touching the data
+    let computed_tag = committing_tag(&key_commitment(key, nonce), &state, data);
     if !constant_time_eq(&computed_tag, tag) {
-        return Err("Authentication failed");
+        return Err(AegisQError::AuthenticationFailed);
     }
     
-    // Generate keystream
-    let keystream = kdf(&state, encrypted_data.len());
+    let mut plaintext = data.to_vec();
+    apply_keystream(&state, &mut plaintext);
+    Ok(plaintext)
+}
+
+/// Commitment to the key for committing tags
+fn key_commitment(key: &[u8], nonce: &[u8]) -> Vec<u8> {
+    utils::kdf::kdf_shake256(b"aegis-q-committing-tag-key", key, nonce, KEY_COMMITMENT_SIZE)
+}
+
+/// Key-committing authentication tag
+fn committing_tag(commitment: &[u8], state: &State, data: &[u8]) -> Vec<u8> {
+    use sha3::Sha3_256;
     
-    // XOR to decrypt
-    let mut plaintext = Vec::with_capacity(encrypted_data.len());
-    for i in 0..encrypted_data.len() {
-        plaintext.push(encrypted_data[i] ^ keystream[i]);
+    let mut hasher = Sha3_256::new();
+    Update::update(&mut hasher, b"aegis-q-committing-tag");
+    Update::update(&mut hasher, commitment);
+    state.absorb(&mut hasher);
+    Update::update(&mut hasher, data);
+    hasher.finalize().to_vec()
+}
+
+/// Initialize state and apply all rounds
+fn keyed_state(key: &[u8], nonce: &[u8]) -> State {
+    let mut state = aegis_q_init(key, nonce);
+    apply_rounds(&mut state, key, nonce);
+    state
+}
+
+/// Apply all rounds to an initialized state
+pub(crate) fn apply_rounds(state: &mut State, key: &[u8], nonce: &[u8]) {
+    apply_rounds_n(state, key, nonce, ROUNDS);
+}
+
+/// Apply `rounds` rounds to an initialized state
+pub(crate) fn apply_rounds_n(state: &mut State, key: &[u8], nonce: &[u8], rounds: usize) {
+    apply_rounds_with_layers(state, key, nonce, rounds, Layers::ALL);
+}
+
+/// `apply_rounds_n` applying only the enabled `layers` in each round
+pub(crate) fn apply_rounds_with_layers(state: &mut State, key: &[u8], nonce: &[u8], rounds: usize, layers: Layers) {
+    let mut round_keys = derive_round_keys(key, nonce, rounds);
+    for (i, round_key) in round_keys.iter().enumerate() {
+        round_with_layers(state, round_key, nonce, i as u64, layers);
+    }
+    for round_key in &mut round_keys {
+        round_key.wipe();
     }
-    
-    Ok(plaintext)
 }
 
+/// Keystream block size (the SHAKE-256 rate)
+const KEYSTREAM_BLOCK: usize = 136;
+
 /// Key Derivation Function (KDF)
-/// Uses SHAKE-256 to derive keystream from state
-fn kdf(state: &State, length: usize) -> Vec<u8> {
+/// XORs the SHAKE-256 keystream derived from `state` into `data`
+/// 
+/// The keystream is squeezed one block at a time into a stack buffer that
+/// is wiped afterwards, so no keystream-sized allocation is made.
+fn apply_keystream(state: &State, data: &mut [u8]) {
     let mut hasher = Shake256::default();
-    Update::update(&mut hasher, &state.to_bytes());
+    state.absorb(&mut hasher);
     
     let mut reader = hasher.finalize_xof();
-    let mut keystream = vec![0u8; length];
-    reader.read(&mut keystream);
-    
-    keystream
+    let mut block = [0u8; KEYSTREAM_BLOCK];
+    for chunk in data.chunks_mut(KEYSTREAM_BLOCK) {
+        let keystream = &mut block[..chunk.len()];
+        reader.read(keystream);
+        for (byte, k) in chunk.iter_mut().zip(keystream.iter()) {
+            *byte ^= k;
+        }
+    }
+    block.wipe();
 }
 
 /// Generate authentication tag
-fn generate_tag(state: &State, data: &[u8]) -> Vec<u8> {
-    use sha3::{Sha3_256, digest::Update};
+/// 
+/// 32-byte tags use SHA3-256; other sizes use length-separated SHAKE-256,
+/// so a tag of one size is never a prefix of another.
+fn generate_tag(state: &State, data: &[u8], tag_size: TagSize) -> Vec<u8> {
+    use sha3::Sha3_256;
     
-    let mut hasher = Sha3_256::new();
-    Update::update(&mut hasher, &state.to_bytes());
+    if tag_size == TagSize::Bytes32 {
+        let mut hasher = Sha3_256::new();
+        state.absorb(&mut hasher);
+        Update::update(&mut hasher, data);
+        return hasher.finalize().to_vec();
+    }
+    
+    let mut hasher = Shake256::default();
+    Update::update(&mut hasher, b"aegis-q-tag");
+    Update::update(&mut hasher, &[tag_size.bytes() as u8]);
+    state.absorb(&mut hasher);
     Update::update(&mut hasher, data);
-    hasher.finalize().to_vec()
+    
+    let mut tag = vec![0u8; tag_size.bytes()];
+    hasher.finalize_xof().read(&mut tag);
+    tag
 }
 
 /// Constant-time comparison of two byte slices
-fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
     if a.len() != b.len() {
         return false;
     }
@@ -163,6 +517,147 @@ mod tests {
         assert_eq!(decrypted, plaintext);
     }
     
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_decrypt_in_place() {
+        let key = b"test-key-123456789012345678901234567890";
+        let nonce = b"test-nonce-123456";
+        let plaintext = b"Hello, Aegis-Q!";
+        
+        let mut buffer = aegis_q_encrypt(key, nonce, plaintext);
+        let view = aegis_q_decrypt_in_place(key, nonce, &mut buffer).unwrap();
+        assert_eq!(view, plaintext);
+        
+        let mut tampered = aegis_q_encrypt(key, nonce, plaintext);
+        tampered[0] ^= 1;
+        let before = tampered.clone();
+        assert_eq!(aegis_q_decrypt_in_place(key, nonce, &mut tampered), Err(AegisQError::AuthenticationFailed));
+        assert_eq!(tampered, before);
+    }
+    
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_tag_sizes() {
+        let key = b"test-key-123456789012345678901234567890";
+        let nonce = b"test-nonce-123456";
+        let plaintext = b"Hello, Aegis-Q!";
+        
+        for tag_size in [TagSize::Bytes16, TagSize::Bytes32, TagSize::Bytes64] {
+            let ciphertext = aegis_q_encrypt_with_tag(key, nonce, plaintext, tag_size);
+            assert_eq!(ciphertext.len(), plaintext.len() + tag_size.bytes());
+            assert_eq!(aegis_q_decrypt_with_tag(key, nonce, &ciphertext, tag_size).unwrap(), plaintext);
+        }
+        
+        let ciphertext = aegis_q_encrypt_with_tag(key, nonce, plaintext, TagSize::Bytes64);
+        assert!(aegis_q_decrypt_with_tag(key, nonce, &ciphertext[..ciphertext.len() - 48], TagSize::Bytes16).is_err());
+    }
+    
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_detached_matches_combined() {
+        let key = b"test-key-123456789012345678901234567890";
+        let nonce = b"test-nonce-123456";
+        let plaintext = b"Hello, Aegis-Q!";
+        
+        let (ciphertext, tag) = aegis_q_encrypt_detached(key, nonce, plaintext);
+        let combined = aegis_q_encrypt(key, nonce, plaintext);
+        assert_eq!(ciphertext.len(), plaintext.len());
+        assert_eq!([ciphertext.as_slice(), tag.as_slice()].concat(), combined);
+        assert_eq!(aegis_q_decrypt_detached(key, nonce, &ciphertext, &tag).unwrap(), plaintext);
+        
+        let mut bad_tag = tag.clone();
+        bad_tag[0] ^= 1;
+        assert_eq!(aegis_q_decrypt_detached(key, nonce, &ciphertext, &bad_tag), Err(AegisQError::AuthenticationFailed));
+        assert_eq!(
+            aegis_q_decrypt_detached(key, nonce, &ciphertext, &tag[..16]),
+            Err(AegisQError::InvalidLength("Invalid tag length"))
+        );
+        
+        let (ciphertext, tag) = aegis_q_encrypt_detached_with_tag(key, nonce, plaintext, TagSize::Bytes16);
+        assert_eq!(tag.len(), 16);
+        assert_eq!(aegis_q_decrypt_detached_with_tag(key, nonce, &ciphertext, &tag, TagSize::Bytes16).unwrap(), plaintext);
+    }
+    
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_encrypt_in_place_matches_allocating() {
+        let key = b"test-key-123456789012345678901234567890";
+        let nonce = b"test-nonce-123456";
+        // Spans several keystream blocks, with a partial last one
+        let plaintext: Vec<u8> = (0..3 * KEYSTREAM_BLOCK as u32 + 5).map(|i| i as u8).collect();
+        
+        let mut buffer = plaintext.clone();
+        aegis_q_encrypt_in_place(key, nonce, &mut buffer);
+        assert_eq!(buffer, aegis_q_encrypt(key, nonce, &plaintext));
+        assert_eq!(aegis_q_decrypt_in_place(key, nonce, &mut buffer).unwrap(), plaintext.as_slice());
+        
+        let mut fixed = plaintext.clone();
+        let tag = aegis_q_encrypt_in_place_detached(key, nonce, &mut fixed);
+        assert_eq!(aegis_q_decrypt_detached(key, nonce, &fixed, &tag).unwrap(), plaintext);
+        
+        let mut short = plaintext.clone();
+        aegis_q_encrypt_in_place_with_tag(key, nonce, &mut short, TagSize::Bytes16);
+        assert_eq!(short, aegis_q_encrypt_with_tag(key, nonce, &plaintext, TagSize::Bytes16));
+    }
+    
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_encrypt_auto_fresh_nonces() {
+        let key = b"test-key-123456789012345678901234567890";
+        let plaintext = b"Hello, Aegis-Q!";
+        
+        let (nonce, ciphertext) = aegis_q_encrypt_auto(key, plaintext);
+        let (other_nonce, other_ciphertext) = aegis_q_encrypt_auto(key, plaintext);
+        assert_eq!(nonce.len(), AUTO_NONCE_SIZE);
+        assert_ne!(nonce, other_nonce);
+        assert_ne!(ciphertext, other_ciphertext);
+        assert_eq!(aegis_q_decrypt(key, &nonce, &ciphertext).unwrap(), plaintext);
+    }
+    
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_committing_mode() {
+        let key = b"test-key-123456789012345678901234567890";
+        let nonce = b"test-nonce-123456";
+        let plaintext = b"Hello, Aegis-Q!";
+        
+        let ciphertext = aegis_q_encrypt_committing(key, nonce, plaintext);
+        let plain = aegis_q_encrypt(key, nonce, plaintext);
+        assert_eq!(ciphertext.len(), plain.len());
+        // Same keystream, different tag
+        assert_eq!(ciphertext[..plaintext.len()], plain[..plaintext.len()]);
+        assert_ne!(ciphertext[plaintext.len()..], plain[plaintext.len()..]);
+        assert_eq!(aegis_q_decrypt_committing(key, nonce, &ciphertext).unwrap(), plaintext);
+        
+        // Modes do not cross-verify, and other keys are rejected
+        assert_eq!(aegis_q_decrypt(key, nonce, &ciphertext), Err(AegisQError::AuthenticationFailed));
+        assert_eq!(aegis_q_decrypt_committing(key, nonce, &plain), Err(AegisQError::AuthenticationFailed));
+        assert_eq!(aegis_q_decrypt_committing(b"other-key", nonce, &ciphertext), Err(AegisQError::AuthenticationFailed));
+        assert!(aegis_q_decrypt_committing(key, nonce, &ciphertext[..TAG_SIZE - 1]).is_err());
+    }
+    
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_segmented_mode() {
+        let key = b"test-key-123456789012345678901234567890";
+        let nonce = b"test-nonce-123456";
+        let plaintext: Vec<u8> = (0..3 * crate::keystream::SEGMENT_SIZE as u32 / 2).map(|i| i as u8).collect();
+        
+        let ciphertext = aegis_q_encrypt_segmented(key, nonce, &plaintext);
+        assert_eq!(ciphertext.len(), plaintext.len() + TAG_SIZE);
+        assert_eq!(aegis_q_decrypt_segmented(key, nonce, &ciphertext).unwrap(), plaintext);
+        
+        // Different keystream and tag; modes do not cross-verify
+        let plain = aegis_q_encrypt(key, nonce, &plaintext);
+        assert_ne!(ciphertext[..64], plain[..64]);
+        assert_eq!(aegis_q_decrypt(key, nonce, &ciphertext), Err(AegisQError::AuthenticationFailed));
+        assert_eq!(aegis_q_decrypt_segmented(key, nonce, &plain), Err(AegisQError::AuthenticationFailed));
+        
+        let mut tampered = ciphertext.clone();
+        tampered[crate::keystream::SEGMENT_SIZE + 1] ^= 1;
+        assert_eq!(aegis_q_decrypt_segmented(key, nonce, &tampered), Err(AegisQError::AuthenticationFailed));
+    }
+    
     #[test]
     #[ignore]
     fn test_authentication_failure() {
@@ -178,5 +673,35 @@ mod tests {
         let result = aegis_q_decrypt(key, nonce, &ciphertext);
         assert!(result.is_err());
     }
-}
+    
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_size_helpers_match_output() {
+        let key = b"test-key-123456789012345678901234567890";
+        let nonce = b"test-nonce-123456";
+        for len in [0usize, 1, 100] {
+            let ciphertext = aegis_q_encrypt(key, nonce, &vec![0x42; len]);
+            assert_eq!(ciphertext.len(), ciphertext_len(len));
+            assert_eq!(plaintext_len(ciphertext.len()), Some(len));
+            let short = aegis_q_encrypt_with_tag(key, nonce, &vec![0x42; len], TagSize::Bytes16);
+            assert_eq!(short.len(), TagSize::Bytes16.ciphertext_len(len));
+        }
+        assert_eq!(plaintext_len(TAG_SIZE - 1), None);
+        assert_eq!(TagSize::Bytes64.plaintext_len(64), Some(0));
+    }
 
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_try_encrypt_checks_size() {
+        let key = b"test-key-123456789012345678901234567890";
+        let nonce = b"test-nonce-123456";
+        assert_eq!(aegis_q_try_encrypt(key, nonce, b"data").unwrap(), aegis_q_encrypt(key, nonce, b"data"));
+        assert_eq!(aegis_q_try_encrypt_committing(key, nonce, b"data").unwrap(), aegis_q_encrypt_committing(key, nonce, b"data"));
+        assert_eq!(aegis_q_try_encrypt_segmented(key, nonce, b"data").unwrap(), aegis_q_encrypt_segmented(key, nonce, b"data"));
+        assert!(check_plaintext_len(MAX_PLAINTEXT_SIZE).is_ok());
+        assert_eq!(
+            check_plaintext_len(MAX_PLAINTEXT_SIZE + 1),
+            Err(AegisQError::LimitExceeded("Plaintext exceeds maximum message size"))
+        );
+    }
+}
@@ -0,0 +1,288 @@
+//! Multi-Recipient Envelopes
+//!
+//! Hybrid encryption to many recipients: a random content key encrypts the
+//! payload once and is wrapped separately for each recipient through a
+//! post-quantum KEM. Used for license distribution to several installs and
+//! for group message fan-out.
+//!
+//! ```text
+//! version (1) || KEM ID (2, BE) || slot count (2, BE) || slots || ciphertext
+//! slot = recipient ID (16) || KEM ciphertext (2-byte BE length) || wrapped content key (2-byte BE length)
+//! ```
+//!
+//! The recipient ID is a fingerprint of the recipient's public key, so
+//! `open` finds the caller's slot directly. Each slot wraps the content key
+//! under `KDF(shared secret, recipient ID || KEM ciphertext)`. The payload
+//! nonce is a hash of everything before the ciphertext, so slots cannot be
+//! added, removed or reordered without every recipient failing
+//! authentication.
+//!
+//! The byte layouts are `utils::wire::RecipientEnvelopeHeaderWire` and
+//! `utils::wire::EnvelopeSlotWire`.
+
+use sha3::{Digest, Sha3_256};
+use utils::kdf::kdf_shake256;
+use utils::memory::zeroize;
+use utils::rng::random_bytes;
+use utils::wire::{EnvelopeSlotWire, RecipientEnvelopeHeaderWire, Wire};
+
+use crate::encrypt::{aegis_q_decrypt, aegis_q_encrypt};
+use crate::error::AegisQError;
+use crate::suite::KemId;
+
+/// Envelope format version
+pub const RECIPIENT_ENVELOPE_VERSION: u8 = 1;
+
+/// Recipient ID (public key fingerprint) length
+pub const RECIPIENT_ID_SIZE: usize = 16;
+
+/// Content key length
+pub const CONTENT_KEY_SIZE: usize = 32;
+
+/// Nonce of the wrapped content key (the wrapping key is single-use)
+const SLOT_NONCE: &[u8] = b"aegis-q-envelope-slot";
+
+/// KEM wrapping the content key to each recipient
+pub trait EnvelopeKem {
+    /// Registry ID written in the envelope header
+    fn id(&self) -> KemId;
+
+    /// Encapsulate to `public_key`: (ciphertext, shared secret)
+    fn encapsulate(&self, public_key: &[u8]) -> Result<(Vec<u8>, Vec<u8>), AegisQError>;
+
+    /// Decapsulate with `secret_key`
+    fn decapsulate(&self, secret_key: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AegisQError>;
+}
+
+/// Recipient ID of a public key
+pub fn recipient_id(public_key: &[u8]) -> [u8; RECIPIENT_ID_SIZE] {
+    let mut hasher = Sha3_256::new();
+    hasher.update(b"aegis-q-envelope-recipient");
+    hasher.update(public_key);
+    let mut id = [0u8; RECIPIENT_ID_SIZE];
+    id.copy_from_slice(&hasher.finalize()[..RECIPIENT_ID_SIZE]);
+    id
+}
+
+/// Content key wrapped for one recipient
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecipientSlot {
+    pub recipient: [u8; RECIPIENT_ID_SIZE],
+    kem_ciphertext: Vec<u8>,
+    wrapped_key: Vec<u8>,
+}
+
+impl RecipientSlot {
+    fn to_wire(&self) -> EnvelopeSlotWire {
+        EnvelopeSlotWire {
+            recipient: self.recipient,
+            kem_ciphertext: self.kem_ciphertext.clone(),
+            wrapped_key: self.wrapped_key.clone(),
+        }
+    }
+}
+
+/// Key wrapping the content key in a slot
+fn slot_key(shared_secret: &[u8], recipient: &[u8; RECIPIENT_ID_SIZE], kem_ciphertext: &[u8]) -> Vec<u8> {
+    kdf_shake256(b"aegis-q-envelope-wrap", shared_secret, &[recipient.as_slice(), kem_ciphertext].concat(), 32)
+}
+
+/// Payload encrypted once, content key wrapped per recipient
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecipientEnvelope {
+    kem: KemId,
+    slots: Vec<RecipientSlot>,
+    ciphertext: Vec<u8>,
+}
+
+impl RecipientEnvelope {
+    /// Encrypt `plaintext` to every public key in `recipients`
+    pub fn seal(kem: &dyn EnvelopeKem, recipients: &[&[u8]], plaintext: &[u8]) -> Result<Self, AegisQError> {
+        if recipients.is_empty() {
+            return Err(AegisQError::InvalidInput("Envelope without recipients"));
+        }
+        if recipients.len() > u16::MAX as usize {
+            return Err(AegisQError::LimitExceeded("Too many envelope recipients"));
+        }
+
+        let mut content_key = random_bytes(CONTENT_KEY_SIZE);
+        let mut slots: Vec<RecipientSlot> = Vec::with_capacity(recipients.len());
+        for public_key in recipients {
+            let recipient = recipient_id(public_key);
+            if slots.iter().any(|slot| slot.recipient == recipient) {
+                return Err(AegisQError::InvalidInput("Duplicate envelope recipient"));
+            }
+            let (kem_ciphertext, mut shared_secret) = kem.encapsulate(public_key)?;
+            let mut key = slot_key(&shared_secret, &recipient, &kem_ciphertext);
+            let wrapped_key = aegis_q_encrypt(&key, SLOT_NONCE, &content_key);
+            zeroize(&mut key);
+            zeroize(&mut shared_secret);
+            slots.push(RecipientSlot {
+                recipient,
+                kem_ciphertext,
+                wrapped_key,
+            });
+        }
+
+        let mut envelope = Self {
+            kem: kem.id(),
+            slots,
+            ciphertext: Vec::new(),
+        };
+        envelope.ciphertext = aegis_q_encrypt(&content_key, &envelope.payload_nonce()?, plaintext);
+        zeroize(&mut content_key);
+        Ok(envelope)
+    }
+
+    /// Decrypt as the owner of `public_key` / `secret_key`
+    pub fn open(&self, kem: &dyn EnvelopeKem, public_key: &[u8], secret_key: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        if kem.id() != self.kem {
+            return Err(AegisQError::Unsupported("Envelope KEM mismatch"));
+        }
+        let slot = self
+            .slot(public_key)
+            .ok_or(AegisQError::NotFound("No envelope slot for this recipient"))?;
+        let mut shared_secret = kem.decapsulate(secret_key, &slot.kem_ciphertext)?;
+        let mut key = slot_key(&shared_secret, &slot.recipient, &slot.kem_ciphertext);
+        zeroize(&mut shared_secret);
+        let content_key = aegis_q_decrypt(&key, SLOT_NONCE, &slot.wrapped_key);
+        zeroize(&mut key);
+        let mut content_key = content_key?;
+        let plaintext = aegis_q_decrypt(&content_key, &self.payload_nonce()?, &self.ciphertext);
+        zeroize(&mut content_key);
+        plaintext
+    }
+
+    /// KEM the content key is wrapped with
+    pub fn kem(&self) -> KemId {
+        self.kem
+    }
+
+    /// Recipient IDs, in slot order
+    pub fn recipients(&self) -> impl Iterator<Item = &[u8; RECIPIENT_ID_SIZE]> {
+        self.slots.iter().map(|slot| &slot.recipient)
+    }
+
+    /// Slot of the owner of `public_key`
+    pub fn slot(&self, public_key: &[u8]) -> Option<&RecipientSlot> {
+        let recipient = recipient_id(public_key);
+        self.slots.iter().find(|slot| slot.recipient == recipient)
+    }
+
+    /// Header and slots: everything the payload nonce covers
+    fn encode_head(&self) -> Result<Vec<u8>, AegisQError> {
+        let header = RecipientEnvelopeHeaderWire {
+            version: RECIPIENT_ENVELOPE_VERSION,
+            kem: self.kem.id(),
+            slot_count: self.slots.len() as u16,
+        };
+        let mut bytes = header.to_wire().map_err(AegisQError::Serialization)?;
+        for slot in &self.slots {
+            bytes.extend_from_slice(&slot.to_wire().to_wire().map_err(AegisQError::Serialization)?);
+        }
+        Ok(bytes)
+    }
+
+    fn payload_nonce(&self) -> Result<Vec<u8>, AegisQError> {
+        let mut hasher = Sha3_256::new();
+        hasher.update(b"aegis-q-recipient-envelope");
+        hasher.update(self.encode_head()?);
+        Ok(hasher.finalize().to_vec())
+    }
+
+    /// Binary form (see the module docs)
+    pub fn encode(&self) -> Result<Vec<u8>, AegisQError> {
+        let mut bytes = self.encode_head()?;
+        bytes.extend_from_slice(&self.ciphertext);
+        Ok(bytes)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, AegisQError> {
+        let (header, mut rest) = RecipientEnvelopeHeaderWire::from_wire_prefix(bytes).map_err(AegisQError::Serialization)?;
+        if header.version != RECIPIENT_ENVELOPE_VERSION {
+            return Err(AegisQError::Unsupported("Unsupported envelope version"));
+        }
+        let kem = KemId::from_id(header.kem)?;
+        let mut slots = Vec::with_capacity(header.slot_count as usize);
+        for _ in 0..header.slot_count {
+            let (slot, tail) = EnvelopeSlotWire::from_wire_prefix(rest).map_err(AegisQError::Serialization)?;
+            slots.push(RecipientSlot {
+                recipient: slot.recipient,
+                kem_ciphertext: slot.kem_ciphertext,
+                wrapped_key: slot.wrapped_key,
+            });
+            rest = tail;
+        }
+        if slots.is_empty() {
+            return Err(AegisQError::InvalidInput("Envelope without recipients"));
+        }
+        Ok(Self {
+            kem,
+            slots,
+            ciphertext: rest.to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Insecure stand-in KEM (public key == secret key)
+    struct TestKem(KemId);
+
+    impl EnvelopeKem for TestKem {
+        fn id(&self) -> KemId {
+            self.0
+        }
+
+        fn encapsulate(&self, public_key: &[u8]) -> Result<(Vec<u8>, Vec<u8>), AegisQError> {
+            let ciphertext = random_bytes(32);
+            Ok((ciphertext.clone(), kdf_shake256(b"test-kem", public_key, &ciphertext, 32)))
+        }
+
+        fn decapsulate(&self, secret_key: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AegisQError> {
+            Ok(kdf_shake256(b"test-kem", secret_key, ciphertext, 32))
+        }
+    }
+
+    const KEM: TestKem = TestKem(KemId::MlKem768);
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_every_recipient_opens_its_slot() {
+        let keys: [&[u8]; 3] = [b"alice-key", b"bob-key", b"carol-key"];
+        let envelope = RecipientEnvelope::seal(&KEM, &keys, b"group message").unwrap();
+        let decoded = RecipientEnvelope::decode(&envelope.encode().unwrap()).unwrap();
+        assert_eq!(decoded, envelope);
+        assert_eq!(decoded.recipients().count(), 3);
+
+        for key in keys {
+            assert_eq!(decoded.open(&KEM, key, key).unwrap(), b"group message");
+        }
+        assert_eq!(
+            decoded.open(&KEM, b"mallory-key", b"mallory-key"),
+            Err(AegisQError::NotFound("No envelope slot for this recipient"))
+        );
+        assert_eq!(
+            decoded.open(&TestKem(KemId::MlKem1024), b"bob-key", b"bob-key"),
+            Err(AegisQError::Unsupported("Envelope KEM mismatch"))
+        );
+        assert!(decoded.open(&KEM, b"bob-key", b"wrong-secret").is_err());
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_slots_are_bound_to_payload() {
+        let keys: [&[u8]; 2] = [b"alice-key", b"bob-key"];
+        let mut envelope = RecipientEnvelope::seal(&KEM, &keys, b"license").unwrap();
+
+        // Dropping a recipient changes the header, so the remaining slot fails too
+        envelope.slots.pop();
+        assert_eq!(envelope.open(&KEM, b"alice-key", b"alice-key"), Err(AegisQError::AuthenticationFailed));
+
+        assert!(RecipientEnvelope::seal(&KEM, &[], b"x").is_err());
+        assert!(RecipientEnvelope::seal(&KEM, &[b"same", b"same"], b"x").is_err());
+        assert!(RecipientEnvelope::decode(&[9, 0x02, 0x02, 0, 0]).is_err());
+    }
+}
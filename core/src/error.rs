@@ -0,0 +1,74 @@
+//! Error type shared by the Aegis-Q crates
+//!
+//! Variants name the failure cause so callers can match on it; the broad
+//! ones carry a short static detail for logs. `Display` prints the same
+//! messages the string errors used to.
+
+use std::fmt;
+
+/// Aegis-Q failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AegisQError {
+    /// Input too short, too long or truncated
+    InvalidLength(&'static str),
+    /// Authentication tag or MAC did not verify
+    AuthenticationFailed,
+    /// Key commitment does not match the key
+    KeyCommitmentMismatch,
+    /// Padding is malformed
+    InvalidPadding,
+    /// Unknown version, mode, algorithm or flag
+    Unsupported(&'static str),
+    /// Decryption policy rejected the ciphertext's options
+    Policy(&'static str),
+    /// Algorithm or profile below the required security level
+    DowngradeRejected(&'static str),
+    /// Invalid argument or option combination
+    InvalidInput(&'static str),
+    /// Encoding or decoding of structured data failed
+    Serialization(&'static str),
+    /// Operation not valid in the current protocol state, or peer misbehaved
+    Protocol(&'static str),
+    /// Key, tunnel or other item not found
+    NotFound(&'static str),
+    /// Limit or capacity exceeded
+    LimitExceeded(&'static str),
+    /// OS or I/O operation failed
+    Io(&'static str),
+}
+
+impl fmt::Display for AegisQError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AegisQError::AuthenticationFailed => write!(f, "Authentication failed"),
+            AegisQError::KeyCommitmentMismatch => write!(f, "Key commitment mismatch"),
+            AegisQError::InvalidPadding => write!(f, "Invalid padding"),
+            AegisQError::InvalidLength(detail)
+            | AegisQError::Unsupported(detail)
+            | AegisQError::Policy(detail)
+            | AegisQError::DowngradeRejected(detail)
+            | AegisQError::InvalidInput(detail)
+            | AegisQError::Serialization(detail)
+            | AegisQError::Protocol(detail)
+            | AegisQError::NotFound(detail)
+            | AegisQError::LimitExceeded(detail)
+            | AegisQError::Io(detail) => write!(f, "{}", detail),
+        }
+    }
+}
+
+impl std::error::Error for AegisQError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_keeps_messages() {
+        assert_eq!(AegisQError::AuthenticationFailed.to_string(), "Authentication failed");
+        assert_eq!(AegisQError::InvalidLength("Ciphertext too short").to_string(), "Ciphertext too short");
+
+        let boxed: Box<dyn std::error::Error> = Box::new(AegisQError::Io("Connect failed"));
+        assert_eq!(boxed.to_string(), "Connect failed");
+    }
+}
@@ -0,0 +1,160 @@
+//! Aegis-Q Hash
+//!
+//! Unkeyed hashing through the round structure. The four state components
+//! never mix within a round, so input XORed into the state would not
+//! spread; instead each input block enters the way a nonce does, through
+//! the state derivation and the round keys. The message is cut into
+//! `HASH_BLOCK_SIZE` blocks and chained:
+//!
+//! ```text
+//! state_i = rounds(key = cv_{i-1}, nonce = "aegis-q-hash" || final flag || block_i)
+//! cv_i    = SHA3-256("aegis-q-hash-cv" || state_i)
+//! digest  = SHAKE-256("aegis-q-hash-out" || state_last)
+//! ```
+//!
+//! with `cv_0` all zeros. Only the last block is shorter than
+//! `HASH_BLOCK_SIZE`, and only it carries the final flag, so the padding is
+//! unambiguous; the empty message is one empty final block.
+//!
+//! Every block costs a full state derivation and all rounds: meant for
+//! fingerprints and commitments, not bulk data.
+
+use sha3::{digest::{ExtendableOutput, Update, XofReader}, Digest, Sha3_256, Shake256};
+
+use crate::encrypt::apply_rounds;
+use crate::state::State;
+
+/// Default digest size
+pub const HASH_SIZE: usize = 32;
+
+/// Bytes absorbed per state derivation
+pub const HASH_BLOCK_SIZE: usize = 4096;
+
+/// Incremental Aegis-Q hash
+#[derive(Clone)]
+pub struct AegisQHasher {
+    chaining_value: [u8; HASH_SIZE],
+    buffer: Vec<u8>,
+}
+
+impl AegisQHasher {
+    /// Hasher with no input yet
+    pub fn new() -> Self {
+        Self {
+            chaining_value: [0u8; HASH_SIZE],
+            buffer: Vec::with_capacity(HASH_BLOCK_SIZE),
+        }
+    }
+
+    /// Absorb more input
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            // A full block is only processed once more input follows it
+            if self.buffer.len() == HASH_BLOCK_SIZE {
+                let state = self.block_state(false);
+                self.chaining_value = chaining_value(&state);
+                self.buffer.clear();
+            }
+            let take = (HASH_BLOCK_SIZE - self.buffer.len()).min(data.len());
+            self.buffer.extend_from_slice(&data[..take]);
+            data = &data[take..];
+        }
+    }
+
+    /// `HASH_SIZE`-byte digest
+    pub fn finalize(self) -> [u8; HASH_SIZE] {
+        let mut digest = [0u8; HASH_SIZE];
+        self.finalize_into(&mut digest);
+        digest
+    }
+
+    /// Digest of any length (shorter outputs are prefixes of longer ones)
+    pub fn finalize_xof(self, len: usize) -> Vec<u8> {
+        let mut digest = vec![0u8; len];
+        self.finalize_into(&mut digest);
+        digest
+    }
+
+    fn finalize_into(self, out: &mut [u8]) {
+        let state = self.block_state(true);
+        let mut hasher = Shake256::default();
+        Update::update(&mut hasher, b"aegis-q-hash-out");
+        state.absorb(&mut hasher);
+        hasher.finalize_xof().read(out);
+    }
+
+    /// State after the rounds for the buffered block
+    fn block_state(&self, last: bool) -> State {
+        let mut nonce = Vec::with_capacity(13 + self.buffer.len());
+        nonce.extend_from_slice(b"aegis-q-hash");
+        nonce.push(last as u8);
+        nonce.extend_from_slice(&self.buffer);
+
+        let mut state = State::from_key(&self.chaining_value, &nonce);
+        apply_rounds(&mut state, &self.chaining_value, &nonce);
+        state
+    }
+}
+
+impl Default for AegisQHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn chaining_value(state: &State) -> [u8; HASH_SIZE] {
+    let mut hasher = Sha3_256::new();
+    Digest::update(&mut hasher, b"aegis-q-hash-cv");
+    state.absorb(&mut hasher);
+    hasher.finalize().into()
+}
+
+/// Aegis-Q hash of `data`
+pub fn aegis_q_hash(data: &[u8]) -> [u8; HASH_SIZE] {
+    let mut hasher = AegisQHasher::new();
+    hasher.update(data);
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_hash_incremental_across_blocks() {
+        let data: Vec<u8> = (0..2 * HASH_BLOCK_SIZE + 7).map(|i| i as u8).collect();
+        let digest = aegis_q_hash(&data);
+
+        let mut hasher = AegisQHasher::new();
+        for chunk in data.chunks(1000) {
+            hasher.update(chunk);
+        }
+        assert_eq!(hasher.finalize(), digest);
+
+        let mut hasher = AegisQHasher::new();
+        hasher.update(&data);
+        assert_eq!(hasher.finalize_xof(64)[..HASH_SIZE], digest);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_hash_separates_inputs() {
+        let block = vec![0u8; HASH_BLOCK_SIZE];
+        let mut longer = block.clone();
+        longer.push(0);
+
+        let digests = [
+            aegis_q_hash(b""),
+            aegis_q_hash(b"\x00"),
+            aegis_q_hash(&block),
+            aegis_q_hash(&longer),
+        ];
+        for (i, a) in digests.iter().enumerate() {
+            for b in &digests[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+        assert_eq!(aegis_q_hash(b"fingerprint"), aegis_q_hash(b"fingerprint"));
+    }
+}
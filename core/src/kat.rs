@@ -0,0 +1,266 @@
+//! Known-answer vectors
+//!
+//! Canonical `(key, nonce, aad, plaintext) -> ciphertext` vectors shipped
+//! with the crate, so an incompatible change to the algorithm (round
+//! function, state derivation, tag) fails tests instead of silently
+//! breaking stored data between releases. Round-trip tests cannot notice
+//! such a change.
+//!
+//! Associated data is bound into the nonce (`nonce || aad`), the layout the
+//! `aead` trait implementation uses. That concatenation is unambiguous only
+//! for a fixed-size nonce, so vectors with non-empty `aad` must have a
+//! 16-byte (`NONCE_SIZE`) nonce; with empty `aad` a vector is a plain
+//! `aegis_q_encrypt` vector and the nonce may have any length.
+//!
+//! Vectors depend on the parameter set: `canonical()` returns the ones for
+//! the set the crate is built with. The text format is the one used by
+//! the conformance fixtures: `name = hex` lines, records separated by a
+//! blank line, `#` starts a comment.
+
+use crate::encrypt::{aegis_q_decrypt, aegis_q_encrypt};
+use crate::error::AegisQError;
+
+/// Parameter set the crate is built with
+#[cfg(feature = "small_params")]
+pub const PARAMETER_SET: &str = "small_params";
+
+/// Parameter set the crate is built with
+#[cfg(not(feature = "small_params"))]
+pub const PARAMETER_SET: &str = "full";
+
+/// Nonce size required by vectors with associated data
+pub const NONCE_SIZE: usize = 16;
+
+/// Shipped vectors for `PARAMETER_SET`
+#[cfg(feature = "small_params")]
+const CANONICAL: &str = include_str!("../kat/small_params.kat");
+
+/// Shipped vectors for `PARAMETER_SET`
+#[cfg(not(feature = "small_params"))]
+const CANONICAL: &str = include_str!("../kat/full.kat");
+
+/// One known-answer vector
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KatVector {
+    pub key: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub aad: Vec<u8>,
+    pub plaintext: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+impl KatVector {
+    /// Check the vector against this build: encryption must reproduce the
+    /// ciphertext and decryption the plaintext
+    pub fn verify(&self) -> Result<(), AegisQError> {
+        let nonce = bound_nonce(&self.nonce, &self.aad)?;
+        if aegis_q_encrypt(&self.key, &nonce, &self.plaintext) != self.ciphertext {
+            return Err(AegisQError::Protocol("Ciphertext differs from known answer"));
+        }
+        if aegis_q_decrypt(&self.key, &nonce, &self.ciphertext)? != self.plaintext {
+            return Err(AegisQError::Protocol("Plaintext differs from known answer"));
+        }
+        Ok(())
+    }
+}
+
+/// Nonce with the associated data appended
+///
+/// Without a length prefix `(ab, c)` and `(a, bc)` would bind the same
+/// bytes, so associated data needs a `NONCE_SIZE` nonce.
+fn bound_nonce(nonce: &[u8], aad: &[u8]) -> Result<Vec<u8>, AegisQError> {
+    if !aad.is_empty() && nonce.len() != NONCE_SIZE {
+        return Err(AegisQError::InvalidLength("Associated data requires a 16-byte nonce"));
+    }
+    let mut bound = nonce.to_vec();
+    bound.extend_from_slice(aad);
+    Ok(bound)
+}
+
+/// Compute a new vector with this build
+///
+/// Fails if `aad` is non-empty and the nonce is not `NONCE_SIZE` bytes.
+pub fn generate(key: &[u8], nonce: &[u8], aad: &[u8], plaintext: &[u8]) -> Result<KatVector, AegisQError> {
+    let ciphertext = aegis_q_encrypt(key, &bound_nonce(nonce, aad)?, plaintext);
+    Ok(KatVector {
+        key: key.to_vec(),
+        nonce: nonce.to_vec(),
+        aad: aad.to_vec(),
+        plaintext: plaintext.to_vec(),
+        ciphertext,
+    })
+}
+
+/// `(key, nonce, aad, plaintext)`
+type KatInput = (&'static [u8], &'static [u8], &'static [u8], &'static [u8]);
+
+/// Inputs of the shipped vectors
+const DEFAULT_INPUTS: [KatInput; 5] = [
+    (b"00000000000000000000000000000000", b"0000000000000000", b"", b""),
+    (b"00000000000000000000000000000000", b"0000000000000000", b"", b"Hello, Aegis-Q!"),
+    (
+        b"0123456789abcdef0123456789abcdef",
+        b"fedcba9876543210",
+        b"",
+        b"The quick brown fox jumps over the lazy dog",
+    ),
+    (b"0123456789abcdef0123456789abcdef", b"fedcba9876543210", b"header v1", b"Hello, Aegis-Q!"),
+    (
+        b"kat-key-with-a-different-length",
+        b"nonce-000000001",
+        b"",
+        b"Aegis-Q known-answer vector spanning more than one keystream block: \
+          0123456789abcdefghijklmnopqrstuvwxyz ABCDEFGHIJKLMNOPQRSTUVWXYZ",
+    ),
+];
+
+/// Recompute the shipped vectors with this build (to re-record them)
+pub fn generate_default() -> Vec<KatVector> {
+    DEFAULT_INPUTS
+        .iter()
+        .map(|(key, nonce, aad, plaintext)| {
+            generate(key, nonce, aad, plaintext).expect("default inputs use 16-byte nonces with associated data")
+        })
+        .collect()
+}
+
+/// Vectors shipped for `PARAMETER_SET`
+pub fn canonical() -> Vec<KatVector> {
+    parse(CANONICAL).expect("shipped known-answer vectors are well-formed")
+}
+
+/// Parse vectors in the text format
+pub fn parse(text: &str) -> Result<Vec<KatVector>, AegisQError> {
+    let mut vectors = Vec::new();
+    let mut fields: Vec<(String, Vec<u8>)> = Vec::new();
+    for line in text.lines().map(str::trim).chain(std::iter::once("")) {
+        if line.starts_with('#') {
+            continue;
+        }
+        if line.is_empty() {
+            if !fields.is_empty() {
+                vectors.push(vector_from_fields(&fields)?);
+                fields.clear();
+            }
+            continue;
+        }
+        let (name, value) = line.split_once('=').ok_or(AegisQError::Serialization("Expected `name = hex`"))?;
+        fields.push((name.trim().to_string(), hex_decode(value.trim())?));
+    }
+    Ok(vectors)
+}
+
+fn vector_from_fields(fields: &[(String, Vec<u8>)]) -> Result<KatVector, AegisQError> {
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.clone())
+            .ok_or(AegisQError::Serialization("Known-answer vector is missing a field"))
+    };
+    Ok(KatVector {
+        key: field("key")?,
+        nonce: field("nonce")?,
+        aad: field("aad")?,
+        plaintext: field("plaintext")?,
+        ciphertext: field("ciphertext")?,
+    })
+}
+
+/// Vectors in the text format, with a header naming the parameter set
+pub fn format(vectors: &[KatVector]) -> String {
+    let mut out = format!(
+        "# Aegis-Q known-answer vectors\n# parameter set: {}\n# ciphertext = aegis_q_encrypt(key, nonce || aad, plaintext)\n",
+        PARAMETER_SET
+    );
+    for vector in vectors {
+        out.push('\n');
+        for (name, value) in [
+            ("key", &vector.key),
+            ("nonce", &vector.nonce),
+            ("aad", &vector.aad),
+            ("plaintext", &vector.plaintext),
+            ("ciphertext", &vector.ciphertext),
+        ] {
+            if value.is_empty() {
+                out.push_str(&format!("{} =\n", name));
+            } else {
+                out.push_str(&format!("{} = {}\n", name, hex_encode(value)));
+            }
+        }
+    }
+    out
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hex_decode(text: &str) -> Result<Vec<u8>, AegisQError> {
+    if !text.len().is_multiple_of(2) {
+        return Err(AegisQError::Serialization("Odd-length hex"));
+    }
+    text.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|s| u8::from_str_radix(s, 16).ok())
+                .ok_or(AegisQError::Serialization("Invalid hex"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_parse_roundtrip() {
+        let vector = KatVector {
+            key: b"k".to_vec(),
+            nonce: vec![0x00, 0xff],
+            aad: Vec::new(),
+            plaintext: Vec::new(),
+            ciphertext: vec![0xab; 40],
+        };
+        let text = format(&[vector.clone(), vector.clone()]);
+        assert_eq!(parse(&text).unwrap(), vec![vector.clone(), vector]);
+
+        assert!(parse("key = 0\n").is_err());
+        assert!(parse("key = 00\nnonce = 00\n").is_err());
+    }
+
+    #[test]
+    fn test_aad_requires_fixed_nonce() {
+        // (ab, c) and (a, bc) would otherwise bind the same nonce
+        assert_eq!(bound_nonce(b"0123456789abcdef", b"header"), Ok(b"0123456789abcdefheader".to_vec()));
+        assert!(bound_nonce(b"0123456789abcdefh", b"eader").is_err());
+        assert!(bound_nonce(b"short", b"header").is_err());
+        assert_eq!(bound_nonce(b"short", b""), Ok(b"short".to_vec()));
+
+        let vector = KatVector {
+            key: b"k".to_vec(),
+            nonce: b"0123456789abcdefh".to_vec(),
+            aad: b"eader".to_vec(),
+            plaintext: Vec::new(),
+            ciphertext: Vec::new(),
+        };
+        assert_eq!(vector.verify(), Err(AegisQError::InvalidLength("Associated data requires a 16-byte nonce")));
+    }
+    #[test]
+    fn test_shipped_vectors_present() {
+        // Runs without encrypting, so an empty file fails every build, not
+        // only the (slow, ignored) full-parameter verification
+        for (set, text) in [
+            ("full", include_str!("../kat/full.kat")),
+            ("small_params", include_str!("../kat/small_params.kat")),
+        ] {
+            let vectors = parse(text).unwrap();
+            assert_eq!(vectors.len(), DEFAULT_INPUTS.len(), "known-answer vectors for {}", set);
+            for (vector, (key, nonce, aad, plaintext)) in vectors.iter().zip(DEFAULT_INPUTS) {
+                assert_eq!((&vector.key[..], &vector.nonce[..], &vector.aad[..], &vector.plaintext[..]), (key, nonce, aad, plaintext));
+            }
+        }
+    }
+}
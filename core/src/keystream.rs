@@ -0,0 +1,103 @@
+//! Segmented Keystream
+//!
+//! The standard keystream is one SHAKE-256 squeeze over the whole message,
+//! which cannot be split across threads. `Mode::Segmented` instead derives
+//! a 64-byte seed from the state once and expands every `SEGMENT_SIZE`
+//! block of the message from its own SHAKE-256 instance,
+//! `SHAKE256(seed || segment index)`, so segments are produced and XORed
+//! independently. With the `parallel` feature segments run on the rayon
+//! thread pool; the output is the same either way.
+//!
+//! Segmented ciphertexts use their own tag construction and never open in
+//! standard mode (or the reverse).
+
+use sha3::{digest::{ExtendableOutput, Update, XofReader}, Shake256};
+use utils::memory::Wipe;
+
+use crate::options::TagSize;
+use crate::state::State;
+
+/// Bytes of keystream per segment
+pub const SEGMENT_SIZE: usize = 64 * 1024;
+
+/// Seed size
+const SEED_SIZE: usize = 64;
+
+/// Squeeze granularity (SHAKE-256 rate)
+const BLOCK: usize = 136;
+
+/// XOR the segmented keystream derived from `state` into `data`
+pub(crate) fn apply_segmented_keystream(state: &State, data: &mut [u8]) {
+    let mut hasher = Shake256::default();
+    hasher.update(b"aegis-q-segmented-keystream");
+    state.absorb(&mut hasher);
+    let mut seed = [0u8; SEED_SIZE];
+    hasher.finalize_xof().read(&mut seed);
+
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        data.par_chunks_mut(SEGMENT_SIZE)
+            .enumerate()
+            .for_each(|(index, segment)| apply_segment(&seed, index as u64, segment));
+    }
+    #[cfg(not(feature = "parallel"))]
+    for (index, segment) in data.chunks_mut(SEGMENT_SIZE).enumerate() {
+        apply_segment(&seed, index as u64, segment);
+    }
+
+    seed.wipe();
+}
+
+/// XOR the keystream of segment `index` into `segment`
+fn apply_segment(seed: &[u8; SEED_SIZE], index: u64, segment: &mut [u8]) {
+    let mut hasher = Shake256::default();
+    hasher.update(seed);
+    hasher.update(&index.to_be_bytes());
+    let mut reader = hasher.finalize_xof();
+
+    let mut block = [0u8; BLOCK];
+    for chunk in segment.chunks_mut(BLOCK) {
+        let keystream = &mut block[..chunk.len()];
+        reader.read(keystream);
+        for (byte, k) in chunk.iter_mut().zip(keystream.iter()) {
+            *byte ^= k;
+        }
+    }
+    block.wipe();
+}
+
+/// Tag of a segmented ciphertext (SHAKE-256, separate label)
+pub(crate) fn segmented_tag(state: &State, data: &[u8], tag_size: TagSize) -> Vec<u8> {
+    let mut hasher = Shake256::default();
+    hasher.update(b"aegis-q-segmented-tag");
+    hasher.update(&[tag_size.bytes() as u8]);
+    state.absorb(&mut hasher);
+    hasher.update(data);
+
+    let mut tag = vec![0u8; tag_size.bytes()];
+    hasher.finalize_xof().read(&mut tag);
+    tag
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_segments_are_independent() {
+        let state = State::from_key(b"segment-key-0123456789abcdef0123", b"segment-nonce-01");
+        let mut whole = vec![0u8; 2 * SEGMENT_SIZE + 100];
+        apply_segmented_keystream(&state, &mut whole);
+
+        // A prefix gets the same keystream; the second segment is its own stream
+        let mut prefix = vec![0u8; SEGMENT_SIZE + 10];
+        apply_segmented_keystream(&state, &mut prefix);
+        assert_eq!(prefix, whole[..SEGMENT_SIZE + 10]);
+        assert_ne!(whole[..64], whole[SEGMENT_SIZE..SEGMENT_SIZE + 64]);
+
+        apply_segmented_keystream(&state, &mut whole);
+        assert!(whole.iter().all(|&b| b == 0));
+    }
+}
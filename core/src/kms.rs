@@ -0,0 +1,446 @@
+//! Envelope Encryption with a Key Management Service
+//!
+//! Data keys are generated locally (`utils::rng`) and only wrapped and
+//! unwrapped by the KMS: bulk data never leaves the process and the master
+//! key never enters it. A `WrappedKey` (master key ID plus the KMS blob) is
+//! what gets stored next to the data.
+//!
+//! The wrap `context` is bound into the wrap (AWS encryption context, GCP
+//! additional authenticated data) and must be presented again to unwrap.
+//!
+//! Providers:
+//! - `LocalKms`: master key held in process (development, on-prem, tests)
+//! - `AwsKms` (feature `aws-kms`): AWS KMS `Encrypt` / `Decrypt`
+//! - `GcpKms` (feature `gcp-kms`): Cloud KMS `encrypt` / `decrypt`
+//!
+//! The cloud providers build the JSON API requests and hand them to a
+//! `KmsTransport`, which owns the HTTP client and credentials (SigV4
+//! signing for AWS, an OAuth bearer token for GCP). No SDK is pulled in.
+//!
+//! The `WrappedKey` byte layout is `utils::wire::WrappedKeyWire`.
+
+use utils::kdf::kdf_shake256;
+use utils::keys::{EncryptionKey, TypedKey};
+use utils::rng::random_bytes;
+use utils::wire::{Wire, WrappedKeyWire};
+
+use crate::encrypt::{aegis_q_decrypt, aegis_q_encrypt_auto, AUTO_NONCE_SIZE};
+use crate::error::AegisQError;
+
+/// Length of generated data keys
+pub const DATA_KEY_SIZE: usize = 32;
+
+/// Current `WrappedKey` format version
+pub const WRAPPED_KEY_VERSION: u8 = 1;
+
+/// Key wrapping service holding a master key
+pub trait Kms {
+    /// Identifier of the master key (key ARN, Cloud KMS resource name, ...)
+    fn key_id(&self) -> &str;
+
+    /// Wrap `data_key` under the master key, bound to `context`
+    fn wrap(&self, data_key: &[u8], context: &[u8]) -> Result<Vec<u8>, AegisQError>;
+
+    /// Unwrap a blob produced by `wrap` with the same `context`
+    fn unwrap(&self, wrapped: &[u8], context: &[u8]) -> Result<Vec<u8>, AegisQError>;
+}
+
+/// Data key wrapped by a KMS, as stored next to the data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrappedKey {
+    /// Master key that wrapped the data key
+    pub kms_key_id: String,
+    /// Provider-specific wrapped blob
+    pub blob: Vec<u8>,
+}
+
+impl WrappedKey {
+    /// version || key ID length (2, BE) || key ID || blob length (2, BE) || blob
+    pub fn encode(&self) -> Result<Vec<u8>, AegisQError> {
+        WrappedKeyWire {
+            version: WRAPPED_KEY_VERSION,
+            kms_key_id: self.kms_key_id.clone(),
+            blob: self.blob.clone(),
+        }
+        .to_wire()
+        .map_err(AegisQError::Serialization)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, AegisQError> {
+        let wire = WrappedKeyWire::from_wire(bytes).map_err(AegisQError::Serialization)?;
+        if wire.version != WRAPPED_KEY_VERSION {
+            return Err(AegisQError::Unsupported("Unsupported wrapped key version"));
+        }
+        Ok(Self {
+            kms_key_id: wire.kms_key_id,
+            blob: wire.blob,
+        })
+    }
+}
+
+/// Generate a data key locally and wrap it with `kms`
+///
+/// Use the returned key, store only the `WrappedKey`.
+pub fn generate_data_key(kms: &dyn Kms, context: &[u8]) -> Result<(EncryptionKey, WrappedKey), AegisQError> {
+    let data_key = EncryptionKey::from_bytes(&random_bytes(DATA_KEY_SIZE));
+    let blob = kms.wrap(data_key.as_bytes(), context)?;
+    Ok((data_key, WrappedKey {
+        kms_key_id: kms.key_id().to_string(),
+        blob,
+    }))
+}
+
+/// Unwrap a stored data key; fails if another master key wrapped it
+pub fn unwrap_data_key(kms: &dyn Kms, wrapped: &WrappedKey, context: &[u8]) -> Result<EncryptionKey, AegisQError> {
+    if wrapped.kms_key_id != kms.key_id() {
+        return Err(AegisQError::NotFound("Data key wrapped by another KMS key"));
+    }
+    let mut bytes = kms.unwrap(&wrapped.blob, context)?;
+    let data_key = EncryptionKey::from_bytes(&bytes);
+    utils::memory::zeroize(&mut bytes);
+    Ok(data_key)
+}
+
+/// In-process KMS with a local master key
+///
+/// Blob: nonce || Aegis-Q ciphertext of the data key under
+/// `KDF("aegis-q-kms-local", master key, context)`.
+pub struct LocalKms {
+    key_id: String,
+    master_key: EncryptionKey,
+}
+
+impl LocalKms {
+    pub fn new(key_id: &str, master_key: EncryptionKey) -> Self {
+        Self {
+            key_id: key_id.to_string(),
+            master_key,
+        }
+    }
+
+    fn wrapping_key(&self, context: &[u8]) -> EncryptionKey {
+        EncryptionKey::from_bytes(&kdf_shake256(b"aegis-q-kms-local", self.master_key.as_bytes(), context, 32))
+    }
+}
+
+impl Kms for LocalKms {
+    fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    fn wrap(&self, data_key: &[u8], context: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        let (mut blob, ciphertext) = aegis_q_encrypt_auto(self.wrapping_key(context).as_bytes(), data_key);
+        blob.extend_from_slice(&ciphertext);
+        Ok(blob)
+    }
+
+    fn unwrap(&self, wrapped: &[u8], context: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        if wrapped.len() < AUTO_NONCE_SIZE {
+            return Err(AegisQError::InvalidLength("Wrapped key too short"));
+        }
+        let (nonce, ciphertext) = wrapped.split_at(AUTO_NONCE_SIZE);
+        aegis_q_decrypt(self.wrapping_key(context).as_bytes(), nonce, ciphertext)
+    }
+}
+
+impl std::fmt::Debug for LocalKms {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LocalKms").field("key_id", &self.key_id).finish_non_exhaustive()
+    }
+}
+
+/// HTTP request to a cloud KMS API
+#[cfg(any(feature = "aws-kms", feature = "gcp-kms"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KmsRequest {
+    pub method: &'static str,
+    pub url: String,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Vec<u8>,
+}
+
+/// HTTP client of a cloud KMS provider
+///
+/// Implementations send the request authenticated (SigV4 for AWS, OAuth
+/// bearer token for GCP) and return the body of a successful response;
+/// API errors map to `AegisQError` (e.g. `Io`, `AuthenticationFailed`).
+#[cfg(any(feature = "aws-kms", feature = "gcp-kms"))]
+pub trait KmsTransport {
+    fn send(&self, request: &KmsRequest) -> Result<Vec<u8>, AegisQError>;
+}
+
+/// String field of a JSON response, base64-decoded
+#[cfg(any(feature = "aws-kms", feature = "gcp-kms"))]
+fn response_field(body: &[u8], field: &str) -> Result<Vec<u8>, AegisQError> {
+    let response: serde_json::Value =
+        serde_json::from_slice(body).map_err(|_| AegisQError::Serialization("Malformed KMS response"))?;
+    let value = response
+        .get(field)
+        .and_then(serde_json::Value::as_str)
+        .ok_or(AegisQError::Serialization("KMS response field missing"))?;
+    base64::decode(value)
+}
+
+/// AWS KMS (`TrentService.Encrypt` / `TrentService.Decrypt`)
+///
+/// The context is sent as the encryption context `{"aegis-q": hex(context)}`.
+#[cfg(feature = "aws-kms")]
+pub struct AwsKms<T: KmsTransport> {
+    region: String,
+    key_id: String,
+    transport: T,
+}
+
+#[cfg(feature = "aws-kms")]
+impl<T: KmsTransport> AwsKms<T> {
+    /// `key_id` is a key ID, ARN or alias
+    pub fn new(region: &str, key_id: &str, transport: T) -> Self {
+        Self {
+            region: region.to_string(),
+            key_id: key_id.to_string(),
+            transport,
+        }
+    }
+
+    fn call(&self, target: &str, body: serde_json::Value) -> Result<Vec<u8>, AegisQError> {
+        self.transport.send(&KmsRequest {
+            method: "POST",
+            url: format!("https://kms.{}.amazonaws.com/", self.region),
+            headers: vec![
+                ("Content-Type", "application/x-amz-json-1.1".to_string()),
+                ("X-Amz-Target", format!("TrentService.{}", target)),
+            ],
+            body: body.to_string().into_bytes(),
+        })
+    }
+
+    fn encryption_context(context: &[u8]) -> serde_json::Value {
+        let hex: String = context.iter().map(|b| format!("{:02x}", b)).collect();
+        serde_json::json!({ "aegis-q": hex })
+    }
+}
+
+#[cfg(feature = "aws-kms")]
+impl<T: KmsTransport> Kms for AwsKms<T> {
+    fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    fn wrap(&self, data_key: &[u8], context: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        let body = self.call("Encrypt", serde_json::json!({
+            "KeyId": self.key_id,
+            "Plaintext": base64::encode(data_key),
+            "EncryptionContext": Self::encryption_context(context),
+        }))?;
+        response_field(&body, "CiphertextBlob")
+    }
+
+    fn unwrap(&self, wrapped: &[u8], context: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        let body = self.call("Decrypt", serde_json::json!({
+            "KeyId": self.key_id,
+            "CiphertextBlob": base64::encode(wrapped),
+            "EncryptionContext": Self::encryption_context(context),
+        }))?;
+        response_field(&body, "Plaintext")
+    }
+}
+
+/// Google Cloud KMS (`cryptoKeys.encrypt` / `cryptoKeys.decrypt`)
+///
+/// The context is sent as additional authenticated data.
+#[cfg(feature = "gcp-kms")]
+pub struct GcpKms<T: KmsTransport> {
+    key_name: String,
+    transport: T,
+}
+
+#[cfg(feature = "gcp-kms")]
+impl<T: KmsTransport> GcpKms<T> {
+    /// `key_name` is `projects/*/locations/*/keyRings/*/cryptoKeys/*`
+    pub fn new(key_name: &str, transport: T) -> Self {
+        Self {
+            key_name: key_name.to_string(),
+            transport,
+        }
+    }
+
+    fn call(&self, method: &str, body: serde_json::Value) -> Result<Vec<u8>, AegisQError> {
+        self.transport.send(&KmsRequest {
+            method: "POST",
+            url: format!("https://cloudkms.googleapis.com/v1/{}:{}", self.key_name, method),
+            headers: vec![("Content-Type", "application/json".to_string())],
+            body: body.to_string().into_bytes(),
+        })
+    }
+}
+
+#[cfg(feature = "gcp-kms")]
+impl<T: KmsTransport> Kms for GcpKms<T> {
+    fn key_id(&self) -> &str {
+        &self.key_name
+    }
+
+    fn wrap(&self, data_key: &[u8], context: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        let body = self.call("encrypt", serde_json::json!({
+            "plaintext": base64::encode(data_key),
+            "additionalAuthenticatedData": base64::encode(context),
+        }))?;
+        response_field(&body, "ciphertext")
+    }
+
+    fn unwrap(&self, wrapped: &[u8], context: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        let body = self.call("decrypt", serde_json::json!({
+            "ciphertext": base64::encode(wrapped),
+            "additionalAuthenticatedData": base64::encode(context),
+        }))?;
+        response_field(&body, "plaintext")
+    }
+}
+
+/// Standard padded base64, as used by both KMS JSON APIs
+#[cfg(any(feature = "aws-kms", feature = "gcp-kms"))]
+mod base64 {
+    use crate::error::AegisQError;
+
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    pub fn encode(bytes: &[u8]) -> String {
+        let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+        for chunk in bytes.chunks(3) {
+            let n = (chunk[0] as u32) << 16 | (*chunk.get(1).unwrap_or(&0) as u32) << 8 | *chunk.get(2).unwrap_or(&0) as u32;
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+                } else {
+                    out.push('=');
+                }
+            }
+        }
+        out
+    }
+
+    pub fn decode(text: &str) -> Result<Vec<u8>, AegisQError> {
+        let text = text.as_bytes();
+        if !text.len().is_multiple_of(4) {
+            return Err(AegisQError::Serialization("Invalid base64 length"));
+        }
+        let mut out = Vec::with_capacity(text.len() / 4 * 3);
+        for (index, chunk) in text.chunks(4).enumerate() {
+            let last = index + 1 == text.len() / 4;
+            let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+            if padding > 2 || (padding > 0 && !last) {
+                return Err(AegisQError::Serialization("Invalid base64 padding"));
+            }
+            let mut n = 0u32;
+            for &c in &chunk[..4 - padding] {
+                let value = ALPHABET
+                    .iter()
+                    .position(|&a| a == c)
+                    .ok_or(AegisQError::Serialization("Invalid base64 character"))?;
+                n = n << 6 | value as u32;
+            }
+            n <<= 6 * padding as u32;
+            out.extend_from_slice(&n.to_be_bytes()[1..4 - padding]);
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local() -> LocalKms {
+        LocalKms::new("local-master", EncryptionKey::from_bytes(b"master-key-0123456789abcdef01234"))
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_local_kms_envelope() {
+        let kms = local();
+        let (data_key, wrapped) = generate_data_key(&kms, b"config").unwrap();
+        assert_eq!(data_key.len(), DATA_KEY_SIZE);
+        assert_eq!(wrapped.kms_key_id, "local-master");
+
+        let stored = WrappedKey::decode(&wrapped.encode().unwrap()).unwrap();
+        assert_eq!(unwrap_data_key(&kms, &stored, b"config").unwrap(), data_key);
+
+        // Context and master key are both bound
+        assert_eq!(unwrap_data_key(&kms, &stored, b"other").unwrap_err(), AegisQError::AuthenticationFailed);
+        let other = LocalKms::new("other-master", EncryptionKey::from_bytes(b"another-master-key-0123456789abc"));
+        assert!(matches!(unwrap_data_key(&other, &stored, b"config"), Err(AegisQError::NotFound(_))));
+    }
+
+    #[cfg(any(feature = "aws-kms", feature = "gcp-kms"))]
+    #[test]
+    fn test_base64() {
+        for (bytes, text) in [(&b""[..], ""), (b"f", "Zg=="), (b"fo", "Zm8="), (b"foo", "Zm9v"), (b"foobar", "Zm9vYmFy")] {
+            assert_eq!(base64::encode(bytes), text);
+            assert_eq!(base64::decode(text).unwrap(), bytes);
+        }
+        assert!(base64::decode("Zm9").is_err());
+        assert!(base64::decode("Zg==Zm9v").is_err());
+        assert!(base64::decode("Z!==").is_err());
+    }
+
+    /// Fake cloud KMS: "wraps" by reversing, checks the request shape
+    #[cfg(any(feature = "aws-kms", feature = "gcp-kms"))]
+    struct FakeCloud {
+        requests: std::cell::RefCell<Vec<KmsRequest>>,
+    }
+
+    #[cfg(any(feature = "aws-kms", feature = "gcp-kms"))]
+    impl KmsTransport for &FakeCloud {
+        fn send(&self, request: &KmsRequest) -> Result<Vec<u8>, AegisQError> {
+            self.requests.borrow_mut().push(request.clone());
+            let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+            let aws = request.url.contains("amazonaws");
+            let encrypt = request.url.ends_with(":encrypt") || request.headers.iter().any(|(_, value)| value == "TrentService.Encrypt");
+            let (input, output) = match (aws, encrypt) {
+                (true, true) => ("Plaintext", "CiphertextBlob"),
+                (true, false) => ("CiphertextBlob", "Plaintext"),
+                (false, true) => ("plaintext", "ciphertext"),
+                (false, false) => ("ciphertext", "plaintext"),
+            };
+            let mut bytes = base64::decode(body[input].as_str().unwrap())?;
+            bytes.reverse();
+            let mut response = serde_json::Map::new();
+            response.insert(output.to_string(), base64::encode(&bytes).into());
+            Ok(serde_json::Value::Object(response).to_string().into_bytes())
+        }
+    }
+
+    #[cfg(feature = "aws-kms")]
+    #[test]
+    fn test_aws_kms_requests() {
+        let cloud = FakeCloud { requests: Default::default() };
+        let kms = AwsKms::new("eu-west-1", "alias/aegis", &cloud);
+        let wrapped = kms.wrap(b"data-key", b"ctx").unwrap();
+        assert_eq!(wrapped, b"yek-atad");
+        assert_eq!(kms.unwrap(&wrapped, b"ctx").unwrap(), b"data-key");
+
+        let requests = cloud.requests.borrow();
+        assert_eq!(requests[0].url, "https://kms.eu-west-1.amazonaws.com/");
+        assert!(requests[0].headers.contains(&("X-Amz-Target", "TrentService.Encrypt".to_string())));
+        assert!(requests[1].headers.contains(&("X-Amz-Target", "TrentService.Decrypt".to_string())));
+        let body: serde_json::Value = serde_json::from_slice(&requests[1].body).unwrap();
+        assert_eq!(body["KeyId"], "alias/aegis");
+        assert_eq!(body["EncryptionContext"]["aegis-q"], "637478");
+    }
+
+    #[cfg(feature = "gcp-kms")]
+    #[test]
+    fn test_gcp_kms_requests() {
+        let cloud = FakeCloud { requests: Default::default() };
+        let name = "projects/p/locations/global/keyRings/r/cryptoKeys/k";
+        let kms = GcpKms::new(name, &cloud);
+        let wrapped = kms.wrap(b"data-key", b"ctx").unwrap();
+        assert_eq!(kms.unwrap(&wrapped, b"ctx").unwrap(), b"data-key");
+
+        let requests = cloud.requests.borrow();
+        assert_eq!(requests[0].url, format!("https://cloudkms.googleapis.com/v1/{}:encrypt", name));
+        assert_eq!(requests[1].url, format!("https://cloudkms.googleapis.com/v1/{}:decrypt", name));
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body["additionalAuthenticatedData"], "Y3R4");
+    }
+}
@@ -9,7 +9,58 @@
 pub mod state;
 pub mod round;
 pub mod encrypt;
+pub mod options;
+pub mod context;
+pub mod cipher;
+pub mod session;
+#[cfg(feature = "aead")]
+pub mod aead;
+pub mod error;
+pub mod stream;
+pub mod types;
+pub mod params;
+pub mod keystream;
+pub mod suite;
+pub mod log;
+pub mod mac;
+pub mod xof;
+pub mod hash;
+pub mod kat;
+pub mod versioned;
+pub mod kms;
+pub mod envelope;
+#[cfg(feature = "parallel")]
+pub mod batch;
+#[cfg(feature = "cryptanalysis")]
+pub mod cryptanalysis;
 
 pub use state::State;
-pub use encrypt::{aegis_q_encrypt, aegis_q_decrypt, aegis_q_init};
+pub use encrypt::{
+    aegis_q_encrypt, aegis_q_decrypt, aegis_q_decrypt_in_place, aegis_q_init,
+    aegis_q_encrypt_with_tag, aegis_q_decrypt_with_tag, aegis_q_try_encrypt, aegis_q_try_encrypt_with_tag, aegis_q_decrypt_in_place_with_tag,
+    aegis_q_encrypt_detached, aegis_q_decrypt_detached,
+    aegis_q_encrypt_detached_with_tag, aegis_q_decrypt_detached_with_tag,
+    aegis_q_encrypt_in_place, aegis_q_encrypt_in_place_with_tag, aegis_q_encrypt_in_place_detached,
+    aegis_q_encrypt_committing, aegis_q_try_encrypt_committing, aegis_q_decrypt_committing, aegis_q_encrypt_auto,
+    aegis_q_encrypt_segmented, aegis_q_try_encrypt_segmented, aegis_q_decrypt_segmented,
+    TAG_SIZE, MAX_PLAINTEXT_SIZE, ciphertext_len, plaintext_len,
+};
+pub use options::{EncryptOptions, DecryptOptions, Mode, Padding, TagSize, SecurityLevel};
+pub use context::AegisQContext;
+pub use cipher::AegisQCipher;
+pub use session::{SessionCipher, SessionLimits};
+pub use error::AegisQError;
+pub use stream::{AegisQStreamEncryptor, AegisQStreamDecryptor};
+pub use log::{AegisQLogEncryptor, AegisQLogDecryptor, LogCursor};
+pub use mac::{aegis_q_mac, aegis_q_mac_verify, AegisQMac};
+pub use xof::{aegis_q_xof, AegisQXof};
+pub use hash::{aegis_q_hash, AegisQHasher};
+pub use versioned::{aegis_q_encrypt_versioned, aegis_q_encrypt_versioned_with, aegis_q_decrypt_versioned};
+pub use types::{AegisKey, AegisNonce};
+pub use envelope::{EnvelopeKem, RecipientEnvelope};
+pub use kms::{Kms, LocalKms, WrappedKey, generate_data_key, unwrap_data_key};
+pub use params::{Preset, Params, ParameterSet, AegisQ128, AegisQ192, AegisQ256};
+pub use suite::{AlgorithmSuite, AeadId, KemId, SignatureId, HashId, SecurityPolicy};
+#[cfg(feature = "parallel")]
+pub use batch::{aegis_q_encrypt_batch, aegis_q_decrypt_batch};
 
@@ -0,0 +1,220 @@
+//! Aegis-Q Log Encryption
+//!
+//! Incremental AEAD for append-only logs. Every record is sealed on its own
+//! under a nonce chained to the previous record's tag:
+//!
+//! record nonce = "aegis-q-log" || index (8 bytes, BE) || previous tag || log ID
+//!
+//! The first record chains to an all-zero tag. Each tag therefore
+//! authenticates the whole prefix: a record that was edited, dropped,
+//! duplicated or moved fails authentication, and so does every record
+//! after it. Cutting records off the end leaves a valid shorter log, so
+//! the reader compares its final `LogCursor` with a head kept elsewhere
+//! (signed, published or stored with the key) via `verify_head`.
+//!
+//! Writers do not need earlier records to append: the cursor (index and
+//! last tag) is enough to resume.
+
+use utils::memory::Wipe;
+
+use crate::encrypt::{aegis_q_decrypt, aegis_q_encrypt, constant_time_eq, TAG_SIZE};
+use crate::error::AegisQError;
+
+/// Position in a log: records so far and the tag of the last one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LogCursor {
+    pub index: u64,
+    pub head: [u8; TAG_SIZE],
+}
+
+impl LogCursor {
+    /// Cursor of an empty log
+    pub const fn genesis() -> Self {
+        Self {
+            index: 0,
+            head: [0u8; TAG_SIZE],
+        }
+    }
+
+    /// Advance past a sealed record
+    fn advance(&mut self, sealed: &[u8]) -> Result<(), AegisQError> {
+        self.index = self.index.checked_add(1).ok_or(AegisQError::LimitExceeded("Log record counter exhausted"))?;
+        self.head.copy_from_slice(&sealed[sealed.len() - TAG_SIZE..]);
+        Ok(())
+    }
+}
+
+impl Default for LogCursor {
+    fn default() -> Self {
+        Self::genesis()
+    }
+}
+
+fn record_nonce(log_id: &[u8], cursor: &LogCursor) -> Vec<u8> {
+    let mut nonce = Vec::with_capacity(11 + 8 + TAG_SIZE + log_id.len());
+    nonce.extend_from_slice(b"aegis-q-log");
+    nonce.extend_from_slice(&cursor.index.to_be_bytes());
+    nonce.extend_from_slice(&cursor.head);
+    nonce.extend_from_slice(log_id);
+    nonce
+}
+
+/// Appends records to a log
+pub struct AegisQLogEncryptor {
+    key: Vec<u8>,
+    log_id: Vec<u8>,
+    cursor: LogCursor,
+}
+
+impl AegisQLogEncryptor {
+    /// Encryptor for a new log; `log_id` must be unique under `key`
+    pub fn new(key: &[u8], log_id: &[u8]) -> Self {
+        Self::resume(key, log_id, LogCursor::genesis())
+    }
+
+    /// Continue a log from its cursor
+    pub fn resume(key: &[u8], log_id: &[u8], cursor: LogCursor) -> Self {
+        Self {
+            key: key.to_vec(),
+            log_id: log_id.to_vec(),
+            cursor,
+        }
+    }
+
+    /// Seal the next record: ciphertext || tag
+    pub fn append(&mut self, record: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        let sealed = aegis_q_encrypt(&self.key, &record_nonce(&self.log_id, &self.cursor), record);
+        self.cursor.advance(&sealed)?;
+        Ok(sealed)
+    }
+
+    /// Current position (store it to resume or to check the log later)
+    pub fn cursor(&self) -> LogCursor {
+        self.cursor
+    }
+}
+
+impl Drop for AegisQLogEncryptor {
+    fn drop(&mut self) {
+        self.key.wipe();
+    }
+}
+
+/// Reads a log record by record, oldest first
+pub struct AegisQLogDecryptor {
+    key: Vec<u8>,
+    log_id: Vec<u8>,
+    cursor: LogCursor,
+}
+
+impl AegisQLogDecryptor {
+    /// Decryptor from the start of a log
+    pub fn new(key: &[u8], log_id: &[u8]) -> Self {
+        Self::resume(key, log_id, LogCursor::genesis())
+    }
+
+    /// Decryptor from a trusted cursor (skips records already read)
+    pub fn resume(key: &[u8], log_id: &[u8], cursor: LogCursor) -> Self {
+        Self {
+            key: key.to_vec(),
+            log_id: log_id.to_vec(),
+            cursor,
+        }
+    }
+
+    /// Authenticate and decrypt the next record
+    ///
+    /// On failure the cursor does not move.
+    pub fn open(&mut self, sealed: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        let record = aegis_q_decrypt(&self.key, &record_nonce(&self.log_id, &self.cursor), sealed)?;
+        self.cursor.advance(sealed)?;
+        Ok(record)
+    }
+
+    /// Current position
+    pub fn cursor(&self) -> LogCursor {
+        self.cursor
+    }
+
+    /// Check that everything up to `expected` has been read
+    ///
+    /// Fails with `InvalidLength` if the log ends early, and with
+    /// `AuthenticationFailed` if it diverges from `expected`.
+    pub fn verify_head(&self, expected: &LogCursor) -> Result<(), AegisQError> {
+        if self.cursor.index < expected.index {
+            return Err(AegisQError::InvalidLength("Log truncated"));
+        }
+        if self.cursor.index != expected.index || !constant_time_eq(&self.cursor.head, &expected.head) {
+            return Err(AegisQError::AuthenticationFailed);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for AegisQLogDecryptor {
+    fn drop(&mut self) {
+        self.key.wipe();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = b"log-key-0123456789abcdef01234567";
+    const LOG_ID: &[u8] = b"audit-2026";
+
+    fn sealed_log(records: &[&[u8]]) -> (Vec<Vec<u8>>, LogCursor) {
+        let mut encryptor = AegisQLogEncryptor::new(KEY, LOG_ID);
+        let sealed = records.iter().map(|r| encryptor.append(r).unwrap()).collect();
+        (sealed, encryptor.cursor())
+    }
+
+    fn read(sealed: &[Vec<u8>], expected: &LogCursor) -> Result<Vec<Vec<u8>>, AegisQError> {
+        let mut decryptor = AegisQLogDecryptor::new(KEY, LOG_ID);
+        let records = sealed.iter().map(|s| decryptor.open(s)).collect::<Result<Vec<_>, _>>()?;
+        decryptor.verify_head(expected)?;
+        Ok(records)
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_log_round_trip_and_resume() {
+        let (mut sealed, cursor) = sealed_log(&[b"first", b"second"]);
+        assert_eq!(cursor.index, 2);
+        assert_eq!(read(&sealed, &cursor).unwrap(), [b"first".to_vec(), b"second".to_vec()]);
+
+        // Appending from the cursor alone continues the same chain
+        let mut encryptor = AegisQLogEncryptor::resume(KEY, LOG_ID, cursor);
+        sealed.push(encryptor.append(b"third").unwrap());
+        let (expected, full) = sealed_log(&[b"first", b"second", b"third"]);
+        assert_eq!(sealed, expected);
+        assert_eq!(encryptor.cursor(), full);
+
+        // A reader can also pick up from a trusted cursor
+        let mut decryptor = AegisQLogDecryptor::resume(KEY, LOG_ID, cursor);
+        assert_eq!(decryptor.open(&sealed[2]).unwrap(), b"third");
+        decryptor.verify_head(&full).unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_log_detects_tampering() {
+        let (sealed, cursor) = sealed_log(&[b"a", b"b", b"c"]);
+
+        let mut swapped = sealed.clone();
+        swapped.swap(0, 1);
+        assert_eq!(read(&swapped, &cursor), Err(AegisQError::AuthenticationFailed));
+
+        let mut dropped = sealed.clone();
+        dropped.remove(1);
+        assert_eq!(read(&dropped, &cursor), Err(AegisQError::AuthenticationFailed));
+
+        assert_eq!(read(&sealed[..2], &cursor), Err(AegisQError::InvalidLength("Log truncated")));
+
+        // Same records under another log ID do not open
+        let mut other = AegisQLogDecryptor::new(KEY, b"other-log");
+        assert_eq!(other.open(&sealed[0]), Err(AegisQError::AuthenticationFailed));
+        assert_eq!(other.cursor(), LogCursor::genesis());
+    }
+}
@@ -0,0 +1,91 @@
+//! Aegis-Q MAC
+//!
+//! Authentication without encryption. The key goes through the full
+//! Aegis-Q round function under a fixed, domain-separating nonce and the
+//! tag is `SHA3-256("aegis-q-mac" || state || data)`. Tags are
+//! deterministic, so the same key and data always give the same tag; they
+//! never verify as AEAD tags (or the reverse).
+//!
+//! Deriving the state is the expensive part: `AegisQMac` keeps it for
+//! authenticating many messages under one key.
+
+use sha3::{Digest, Sha3_256};
+
+use crate::encrypt::{apply_rounds, constant_time_eq};
+use crate::error::AegisQError;
+use crate::state::State;
+
+/// MAC tag size
+pub const MAC_SIZE: usize = 32;
+
+/// Nonce the state is derived under
+const MAC_NONCE: &[u8] = b"aegis-q-mac";
+
+/// MAC with a precomputed keyed state
+pub struct AegisQMac {
+    state: State,
+}
+
+impl AegisQMac {
+    /// Derive the keyed state for `key`
+    pub fn new(key: &[u8]) -> Self {
+        let mut state = State::from_key(key, MAC_NONCE);
+        apply_rounds(&mut state, key, MAC_NONCE);
+        Self { state }
+    }
+
+    /// Tag of `data`
+    pub fn mac(&self, data: &[u8]) -> [u8; MAC_SIZE] {
+        let mut hasher = Sha3_256::new();
+        Digest::update(&mut hasher, b"aegis-q-mac");
+        self.state.absorb(&mut hasher);
+        Digest::update(&mut hasher, data);
+        hasher.finalize().into()
+    }
+
+    /// Check `tag` against `data` in constant time
+    pub fn verify(&self, data: &[u8], tag: &[u8]) -> Result<(), AegisQError> {
+        if !constant_time_eq(&self.mac(data), tag) {
+            return Err(AegisQError::AuthenticationFailed);
+        }
+        Ok(())
+    }
+}
+
+/// MAC of `data` under `key`
+pub fn aegis_q_mac(key: &[u8], data: &[u8]) -> [u8; MAC_SIZE] {
+    AegisQMac::new(key).mac(data)
+}
+
+/// Verify a tag produced by `aegis_q_mac`
+pub fn aegis_q_mac_verify(key: &[u8], data: &[u8], tag: &[u8]) -> Result<(), AegisQError> {
+    AegisQMac::new(key).verify(data, tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encrypt::aegis_q_encrypt;
+
+    const KEY: &[u8] = b"mac-key-0123456789abcdef01234567";
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_mac_verify() {
+        let tag = aegis_q_mac(KEY, b"license fields");
+        assert_eq!(tag, AegisQMac::new(KEY).mac(b"license fields"));
+        aegis_q_mac_verify(KEY, b"license fields", &tag).unwrap();
+
+        assert_eq!(aegis_q_mac_verify(KEY, b"license field", &tag), Err(AegisQError::AuthenticationFailed));
+        assert_eq!(aegis_q_mac_verify(b"other-key", b"license fields", &tag), Err(AegisQError::AuthenticationFailed));
+        assert_eq!(aegis_q_mac_verify(KEY, b"license fields", &tag[..16]), Err(AegisQError::AuthenticationFailed));
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_mac_differs_from_aead_tag() {
+        // Same key and "nonce": the MAC is not the tag of an empty encryption
+        let sealed = aegis_q_encrypt(KEY, MAC_NONCE, b"");
+        assert_ne!(aegis_q_mac(KEY, b"")[..], sealed[..]);
+    }
+}
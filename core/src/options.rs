@@ -0,0 +1,504 @@
+//! Aegis-Q Encryption Options
+//!
+//! Builders selecting mode, padding and key commitment, with validation of
+//! the combination and a stable header encoding of the selected options.
+//!
+//! Header layout (8 bytes), shared by `AegisQContext` and the versioned
+//! format:
+//! - magic "AQCT" (4 bytes)
+//! - version (1 byte)
+//! - mode (1 byte)
+//! - flags (1 byte): bit 0 = key commitment, bit 1 = padded,
+//!   bits 2-3 = tag size (0 = 32 bytes, 1 = 16 bytes, 2 = 64 bytes),
+//!   bits 4-5 = parameter preset (0 = Aegis-Q-256, 1 = 128, 2 = 192)
+//! - log2(padding block) (1 byte, 0 when unpadded)
+//!
+//! The byte layout is `utils::wire::CiphertextHeaderWire`.
+
+use utils::wire::{CiphertextHeaderWire, Wire};
+
+use crate::error::AegisQError;
+use crate::params::Preset;
+use crate::suite::AeadId;
+
+/// Leading bytes of every Aegis-Q ciphertext header
+pub const HEADER_MAGIC: [u8; 4] = *b"AQCT";
+
+/// Current header version (version 1 was the separate versioned header)
+pub const HEADER_VERSION: u8 = 2;
+
+/// Encoded header size
+pub const HEADER_SIZE: usize = 8;
+
+/// Smallest allowed padding block
+pub const MIN_PADDING_BLOCK: usize = 16;
+
+/// Largest allowed padding block
+pub const MAX_PADDING_BLOCK: usize = 65536;
+
+const FLAG_COMMITMENT: u8 = 0x01;
+const FLAG_PADDED: u8 = 0x02;
+const TAG_SIZE_SHIFT: u8 = 2;
+const TAG_SIZE_MASK: u8 = 0x0C;
+const PRESET_SHIFT: u8 = 4;
+const PRESET_MASK: u8 = 0x30;
+
+/// Encryption mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Ciphertext followed by tag
+    Standard = 0x00,
+    /// Ciphertext followed by tag, keystream in independent segments for
+    /// parallel generation (see `keystream`)
+    Segmented = 0x01,
+}
+
+impl Mode {
+    fn from_u8(value: u8) -> Result<Self, AegisQError> {
+        match value {
+            0x00 => Ok(Mode::Standard),
+            0x01 => Ok(Mode::Segmented),
+            _ => Err(AegisQError::Unsupported("Unknown mode")),
+        }
+    }
+}
+
+/// Authentication tag size
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum TagSize {
+    /// 128-bit tag
+    Bytes16,
+    /// 256-bit tag (default)
+    #[default]
+    Bytes32,
+    /// 512-bit tag
+    Bytes64,
+}
+
+impl TagSize {
+    /// Tag length in bytes
+    pub const fn bytes(self) -> usize {
+        match self {
+            TagSize::Bytes16 => 16,
+            TagSize::Bytes32 => 32,
+            TagSize::Bytes64 => 64,
+        }
+    }
+
+    /// Ciphertext length for a plaintext of `plaintext_len` bytes
+    pub const fn ciphertext_len(self, plaintext_len: usize) -> usize {
+        plaintext_len + self.bytes()
+    }
+
+    /// Plaintext length of a ciphertext; `None` if shorter than the tag
+    pub const fn plaintext_len(self, ciphertext_len: usize) -> Option<usize> {
+        ciphertext_len.checked_sub(self.bytes())
+    }
+
+    fn code(self) -> u8 {
+        match self {
+            TagSize::Bytes32 => 0,
+            TagSize::Bytes16 => 1,
+            TagSize::Bytes64 => 2,
+        }
+    }
+
+    fn from_code(code: u8) -> Result<Self, AegisQError> {
+        match code {
+            0 => Ok(TagSize::Bytes32),
+            1 => Ok(TagSize::Bytes16),
+            2 => Ok(TagSize::Bytes64),
+            _ => Err(AegisQError::Unsupported("Unknown tag size")),
+        }
+    }
+}
+
+/// Security level (classical bits) a profile targets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SecurityLevel {
+    L128,
+    L192,
+    L256,
+}
+
+impl SecurityLevel {
+    /// Shortest tag acceptable at this level
+    pub const fn min_tag_size(self) -> TagSize {
+        match self {
+            SecurityLevel::L128 => TagSize::Bytes16,
+            SecurityLevel::L192 | SecurityLevel::L256 => TagSize::Bytes32,
+        }
+    }
+}
+
+/// Plaintext padding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Padding {
+    /// No padding, ciphertext length reveals plaintext length
+    None,
+    /// ISO/IEC 7816-4 padding up to a multiple of the block size
+    Block(usize),
+}
+
+/// Parsed ciphertext header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub mode: Mode,
+    pub padding: Padding,
+    pub commitment: bool,
+    pub tag_size: TagSize,
+    pub preset: Preset,
+}
+
+impl Header {
+    /// AEAD profile in the algorithm registry
+    pub fn aead(&self) -> AeadId {
+        self.preset.into()
+    }
+
+    /// Encode header
+    pub fn encode(&self) -> [u8; HEADER_SIZE] {
+        let mut flags = 0u8;
+        if self.commitment {
+            flags |= FLAG_COMMITMENT;
+        }
+        flags |= self.tag_size.code() << TAG_SIZE_SHIFT;
+        flags |= self.preset.code() << PRESET_SHIFT;
+        let block_log2 = match self.padding {
+            Padding::None => 0,
+            Padding::Block(block) => {
+                flags |= FLAG_PADDED;
+                block.trailing_zeros() as u8
+            }
+        };
+
+        let wire = CiphertextHeaderWire {
+            magic: HEADER_MAGIC,
+            version: HEADER_VERSION,
+            mode: self.mode as u8,
+            flags,
+            block_log2,
+        };
+        let mut bytes = [0u8; HEADER_SIZE];
+        bytes.copy_from_slice(&wire.to_wire().expect("fixed-size header"));
+        bytes
+    }
+
+    /// Decode and validate header
+    pub fn decode(bytes: &[u8]) -> Result<Self, AegisQError> {
+        let (wire, _) = CiphertextHeaderWire::from_wire_prefix(bytes).map_err(|_| AegisQError::InvalidLength("Ciphertext too short"))?;
+        if wire.magic != HEADER_MAGIC {
+            return Err(AegisQError::InvalidInput("Not an Aegis-Q ciphertext"));
+        }
+        if wire.version != HEADER_VERSION {
+            return Err(AegisQError::Unsupported("Unsupported header version"));
+        }
+
+        let mode = Mode::from_u8(wire.mode)?;
+        let flags = wire.flags;
+        if flags & !(FLAG_COMMITMENT | FLAG_PADDED | TAG_SIZE_MASK | PRESET_MASK) != 0 {
+            return Err(AegisQError::Unsupported("Unknown header flags"));
+        }
+
+        let padding = if flags & FLAG_PADDED != 0 {
+            if wire.block_log2 >= usize::BITS as u8 {
+                return Err(AegisQError::InvalidInput("Invalid padding block"));
+            }
+            Padding::Block(1usize << wire.block_log2)
+        } else if wire.block_log2 != 0 {
+            return Err(AegisQError::InvalidInput("Invalid padding block"));
+        } else {
+            Padding::None
+        };
+
+        let header = Self {
+            mode,
+            padding,
+            commitment: flags & FLAG_COMMITMENT != 0,
+            tag_size: TagSize::from_code((flags & TAG_SIZE_MASK) >> TAG_SIZE_SHIFT)?,
+            preset: Preset::from_code((flags & PRESET_MASK) >> PRESET_SHIFT)?,
+        };
+        validate_padding(header.padding)?;
+        Ok(header)
+    }
+}
+
+fn validate_padding(padding: Padding) -> Result<(), AegisQError> {
+    match padding {
+        Padding::None => Ok(()),
+        Padding::Block(block) => {
+            if !block.is_power_of_two() || !(MIN_PADDING_BLOCK..=MAX_PADDING_BLOCK).contains(&block) {
+                Err(AegisQError::InvalidInput("Invalid padding block"))
+            } else {
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Options for `AegisQContext::encrypt`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncryptOptions {
+    mode: Mode,
+    padding: Padding,
+    commitment: bool,
+    tag_size: TagSize,
+    security_level: SecurityLevel,
+    preset: Preset,
+}
+
+impl Default for EncryptOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EncryptOptions {
+    /// Standard mode, no padding, no commitment, 32-byte tag, Aegis-Q-256
+    pub fn new() -> Self {
+        Self {
+            mode: Mode::Standard,
+            padding: Padding::None,
+            commitment: false,
+            tag_size: TagSize::Bytes32,
+            security_level: SecurityLevel::L256,
+            preset: Preset::AegisQ256,
+        }
+    }
+
+    /// Select mode
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Select plaintext padding
+    pub fn padding(mut self, padding: Padding) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Enable key commitment
+    pub fn commitment(mut self, commitment: bool) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    /// Select authentication tag size
+    pub fn tag_size(mut self, tag_size: TagSize) -> Self {
+        self.tag_size = tag_size;
+        self
+    }
+
+    /// Select target security level (bounds the tag size from below)
+    pub fn security_level(mut self, level: SecurityLevel) -> Self {
+        self.security_level = level;
+        self
+    }
+
+    /// Select parameter preset; also sets the security level to the preset's
+    pub fn preset(mut self, preset: Preset) -> Self {
+        self.preset = preset;
+        self.security_level = preset.security_level();
+        self
+    }
+
+    /// Check that the combination of options is supported
+    pub fn validate(&self) -> Result<(), AegisQError> {
+        if self.tag_size < self.security_level.min_tag_size() {
+            return Err(AegisQError::InvalidInput("Tag too short for security level"));
+        }
+        if self.preset.security_level() < self.security_level {
+            return Err(AegisQError::InvalidInput("Preset below security level"));
+        }
+        validate_padding(self.padding)
+    }
+
+    /// Header describing these options
+    pub fn header(&self) -> Header {
+        Header {
+            mode: self.mode,
+            padding: self.padding,
+            commitment: self.commitment,
+            tag_size: self.tag_size,
+            preset: self.preset,
+        }
+    }
+}
+
+/// Options for `AegisQContext::decrypt`
+///
+/// The header carries the sender's choices; these options state what the
+/// receiver is willing to accept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DecryptOptions {
+    mode: Option<Mode>,
+    require_commitment: bool,
+    require_padding: bool,
+    min_security_level: Option<SecurityLevel>,
+}
+
+impl DecryptOptions {
+    /// Accept any valid header
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only accept the given mode
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Reject ciphertexts without key commitment
+    pub fn require_commitment(mut self, require: bool) -> Self {
+        self.require_commitment = require;
+        self
+    }
+
+    /// Reject unpadded ciphertexts
+    pub fn require_padding(mut self, require: bool) -> Self {
+        self.require_padding = require;
+        self
+    }
+
+    /// Reject tags shorter than the minimum for `level` and presets below it
+    pub fn min_security_level(mut self, level: SecurityLevel) -> Self {
+        self.min_security_level = Some(level);
+        self
+    }
+
+    /// Check a decoded header against these options
+    pub fn check(&self, header: &Header) -> Result<(), AegisQError> {
+        if let Some(mode) = self.mode {
+            if header.mode != mode {
+                return Err(AegisQError::Policy("Mode not accepted"));
+            }
+        }
+        if self.require_commitment && !header.commitment {
+            return Err(AegisQError::Policy("Key commitment required"));
+        }
+        if self.require_padding && header.padding == Padding::None {
+            return Err(AegisQError::Policy("Padding required"));
+        }
+        if let Some(level) = self.min_security_level {
+            if header.tag_size < level.min_tag_size() {
+                return Err(AegisQError::DowngradeRejected("Tag too short for security level"));
+            }
+            if header.preset.security_level() < level {
+                return Err(AegisQError::DowngradeRejected("Preset below security level"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Apply ISO/IEC 7816-4 padding
+pub(crate) fn pad(plaintext: &[u8], padding: Padding) -> Vec<u8> {
+    let mut padded = plaintext.to_vec();
+    if let Padding::Block(block) = padding {
+        padded.push(0x80);
+        let target = padded.len().div_ceil(block) * block;
+        padded.resize(target, 0);
+    }
+    padded
+}
+
+/// Remove ISO/IEC 7816-4 padding
+pub(crate) fn unpad(mut padded: Vec<u8>, padding: Padding) -> Result<Vec<u8>, AegisQError> {
+    if let Padding::Block(block) = padding {
+        if padded.is_empty() || !padded.len().is_multiple_of(block) {
+            return Err(AegisQError::InvalidPadding);
+        }
+        let marker = padded.iter().rposition(|&b| b != 0).ok_or(AegisQError::InvalidPadding)?;
+        if padded[marker] != 0x80 {
+            return Err(AegisQError::InvalidPadding);
+        }
+        padded.truncate(marker);
+    }
+    Ok(padded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header_bytes(version: u8, mode: u8, flags: u8, block_log2: u8) -> [u8; HEADER_SIZE] {
+        let [a, b, c, d] = HEADER_MAGIC;
+        [a, b, c, d, version, mode, flags, block_log2]
+    }
+
+    #[test]
+    fn test_header_roundtrip() {
+        let options = EncryptOptions::new().padding(Padding::Block(64)).commitment(true);
+        options.validate().unwrap();
+
+        let encoded = options.header().encode();
+        assert_eq!(encoded, header_bytes(HEADER_VERSION, 0x00, 0x03, 6));
+        assert_eq!(Header::decode(&encoded).unwrap(), options.header());
+
+        assert!(Header::decode(&header_bytes(HEADER_VERSION, 0x00, 0x40, 0)).is_err());
+        assert!(Header::decode(&header_bytes(HEADER_VERSION, 0x00, 0x02, 2)).is_err());
+        assert!(Header::decode(&header_bytes(1, 0x00, 0x00, 0)).is_err());
+        assert_eq!(
+            Header::decode(&[0u8; HEADER_SIZE]),
+            Err(AegisQError::InvalidInput("Not an Aegis-Q ciphertext"))
+        );
+
+        let segmented = EncryptOptions::new().mode(Mode::Segmented).header();
+        assert_eq!(Header::decode(&segmented.encode()).unwrap().mode, Mode::Segmented);
+        assert_eq!(Header::decode(&header_bytes(HEADER_VERSION, 0x02, 0x00, 0)), Err(AegisQError::Unsupported("Unknown mode")));
+    }
+
+    #[test]
+    fn test_tag_size_header_and_levels() {
+        let options = EncryptOptions::new().tag_size(TagSize::Bytes16).security_level(SecurityLevel::L128);
+        options.validate().unwrap();
+        let encoded = options.header().encode();
+        assert_eq!(encoded, header_bytes(HEADER_VERSION, 0x00, 0x04, 0));
+        assert_eq!(Header::decode(&encoded).unwrap().tag_size, TagSize::Bytes16);
+        assert!(Header::decode(&header_bytes(HEADER_VERSION, 0x00, 0x0C, 0)).is_err());
+
+        assert!(EncryptOptions::new().tag_size(TagSize::Bytes16).validate().is_err());
+        assert!(EncryptOptions::new().tag_size(TagSize::Bytes64).validate().is_ok());
+
+        let header = options.header();
+        assert!(DecryptOptions::new().check(&header).is_ok());
+        assert!(DecryptOptions::new().min_security_level(SecurityLevel::L192).check(&header).is_err());
+    }
+
+    #[test]
+    fn test_preset_header_and_levels() {
+        let options = EncryptOptions::new().preset(Preset::AegisQ128).tag_size(TagSize::Bytes16);
+        options.validate().unwrap();
+        let encoded = options.header().encode();
+        assert_eq!(encoded, header_bytes(HEADER_VERSION, 0x00, 0x14, 0));
+        assert_eq!(Header::decode(&encoded).unwrap().preset, Preset::AegisQ128);
+        assert_eq!(Header::decode(&encoded).unwrap().aead(), AeadId::AegisQ128);
+        assert_eq!(Header::decode(&header_bytes(HEADER_VERSION, 0x00, 0x20, 0)).unwrap().preset, Preset::AegisQ192);
+        assert!(Header::decode(&header_bytes(HEADER_VERSION, 0x00, 0x30, 0)).is_err());
+
+        let below = EncryptOptions::new().preset(Preset::AegisQ192).security_level(SecurityLevel::L256);
+        assert_eq!(below.validate(), Err(AegisQError::InvalidInput("Preset below security level")));
+        let policy = DecryptOptions::new().min_security_level(SecurityLevel::L192);
+        assert_eq!(policy.check(&options.header()), Err(AegisQError::DowngradeRejected("Tag too short for security level")));
+        let header = EncryptOptions::new().preset(Preset::AegisQ128).header();
+        assert_eq!(policy.check(&header), Err(AegisQError::DowngradeRejected("Preset below security level")));
+    }
+
+    #[test]
+    fn test_invalid_padding_rejected() {
+        assert!(EncryptOptions::new().padding(Padding::Block(48)).validate().is_err());
+        assert!(EncryptOptions::new().padding(Padding::Block(8)).validate().is_err());
+    }
+
+    #[test]
+    fn test_pad_unpad() {
+        for len in [0, 1, 15, 16, 17] {
+            let data = vec![0u8; len];
+            let padded = pad(&data, Padding::Block(16));
+            assert!(padded.len().is_multiple_of(16) && padded.len() > len);
+            assert_eq!(unpad(padded, Padding::Block(16)).unwrap(), data);
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! Aegis-Q Parameter Presets
+//!
+//! Named parameter sets trading speed for margin: lattice dimension, code
+//! dimension and round count. `AegisQ256` is the original configuration
+//! and what the plain `aegis_q_*` functions use; `AegisQ128` and
+//! `AegisQ192` are smaller and faster.
+//!
+//! Presets are selected at the type level (`AegisQ128::encrypt(...)`) or,
+//! for self-describing ciphertexts, through `EncryptOptions::preset`, which
+//! records the preset in the header. The `small_params` test feature
+//! shrinks every preset.
+
+use crate::encrypt::{apply_rounds_n, open_with_state, seal_with_state, TAG_SIZE};
+use crate::error::AegisQError;
+use crate::options::{SecurityLevel, TagSize};
+use crate::state::State;
+
+/// Dimensions and round count of a preset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Params {
+    /// LatticeMix polynomial degree
+    pub lattice_n: usize,
+    /// CodeMix code dimension
+    pub code_n: usize,
+    /// Number of rounds
+    pub rounds: usize,
+}
+
+/// Named parameter set
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Preset {
+    /// Aegis-Q-128
+    AegisQ128,
+    /// Aegis-Q-192
+    AegisQ192,
+    /// Aegis-Q-256 (default)
+    #[default]
+    AegisQ256,
+}
+
+impl Preset {
+    /// Dimensions and round count
+    #[cfg(not(feature = "small_params"))]
+    pub const fn params(self) -> Params {
+        match self {
+            Preset::AegisQ128 => Params { lattice_n: 1024, code_n: 1024, rounds: 6 },
+            Preset::AegisQ192 => Params { lattice_n: 2048, code_n: 2048, rounds: 8 },
+            Preset::AegisQ256 => Params { lattice_n: 4096, code_n: 4096, rounds: 10 },
+        }
+    }
+
+    /// Dimensions and round count (reduced for tests)
+    #[cfg(feature = "small_params")]
+    pub const fn params(self) -> Params {
+        match self {
+            Preset::AegisQ128 => Params { lattice_n: 64, code_n: 64, rounds: 3 },
+            Preset::AegisQ192 => Params { lattice_n: 128, code_n: 128, rounds: 3 },
+            Preset::AegisQ256 => Params { lattice_n: 256, code_n: 256, rounds: 3 },
+        }
+    }
+
+    /// Security level the preset targets
+    pub const fn security_level(self) -> SecurityLevel {
+        match self {
+            Preset::AegisQ128 => SecurityLevel::L128,
+            Preset::AegisQ192 => SecurityLevel::L192,
+            Preset::AegisQ256 => SecurityLevel::L256,
+        }
+    }
+
+    /// Header code (0 is the original configuration)
+    pub(crate) fn code(self) -> u8 {
+        match self {
+            Preset::AegisQ256 => 0,
+            Preset::AegisQ128 => 1,
+            Preset::AegisQ192 => 2,
+        }
+    }
+
+    pub(crate) fn from_code(code: u8) -> Result<Self, AegisQError> {
+        match code {
+            0 => Ok(Preset::AegisQ256),
+            1 => Ok(Preset::AegisQ128),
+            2 => Ok(Preset::AegisQ192),
+            _ => Err(AegisQError::Unsupported("Unknown parameter preset")),
+        }
+    }
+
+    /// Initialize the state for `key` and `nonce` and apply all rounds
+    pub(crate) fn keyed_state(self, key: &[u8], nonce: &[u8]) -> State {
+        let params = self.params();
+        let mut state = State::from_key_with(key, nonce, params);
+        apply_rounds_n(&mut state, key, nonce, params.rounds);
+        state
+    }
+}
+
+/// Parameter set selected at the type level
+///
+/// Ciphertexts have the `aegis_q_encrypt` layout (ciphertext || 32-byte
+/// tag) and only open under the same preset.
+pub trait ParameterSet {
+    /// The preset
+    const PRESET: Preset;
+
+    /// Encrypt under this preset
+    fn encrypt(key: &[u8], nonce: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        let mut ciphertext = plaintext.to_vec();
+        let tag = seal_with_state(&Self::PRESET.keyed_state(key, nonce), &mut ciphertext, TagSize::Bytes32);
+        ciphertext.extend_from_slice(&tag);
+        ciphertext
+    }
+
+    /// Decrypt a ciphertext produced by `encrypt` of this preset
+    fn decrypt(key: &[u8], nonce: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        if ciphertext.len() < TAG_SIZE {
+            return Err(AegisQError::InvalidLength("Ciphertext too short"));
+        }
+        let (data, tag) = ciphertext.split_at(ciphertext.len() - TAG_SIZE);
+        let mut plaintext = data.to_vec();
+        open_with_state(&Self::PRESET.keyed_state(key, nonce), &mut plaintext, tag, TagSize::Bytes32)?;
+        Ok(plaintext)
+    }
+}
+
+/// Aegis-Q-128 parameter set
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AegisQ128;
+
+/// Aegis-Q-192 parameter set
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AegisQ192;
+
+/// Aegis-Q-256 parameter set (same output as `aegis_q_encrypt`)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AegisQ256;
+
+impl ParameterSet for AegisQ128 {
+    const PRESET: Preset = Preset::AegisQ128;
+}
+
+impl ParameterSet for AegisQ192 {
+    const PRESET: Preset = Preset::AegisQ192;
+}
+
+impl ParameterSet for AegisQ256 {
+    const PRESET: Preset = Preset::AegisQ256;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encrypt::aegis_q_encrypt;
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_presets_round_trip_and_differ() {
+        let key = b"preset-key-0123456789abcdef01234";
+        let nonce = b"preset-nonce-012";
+
+        let c128 = AegisQ128::encrypt(key, nonce, b"preset");
+        let c192 = AegisQ192::encrypt(key, nonce, b"preset");
+        let c256 = AegisQ256::encrypt(key, nonce, b"preset");
+        assert_eq!(c256, aegis_q_encrypt(key, nonce, b"preset"));
+        assert_ne!(c128, c192);
+        assert_ne!(c128, c256);
+
+        assert_eq!(AegisQ128::decrypt(key, nonce, &c128).unwrap(), b"preset");
+        assert_eq!(AegisQ192::decrypt(key, nonce, &c192).unwrap(), b"preset");
+        assert_eq!(AegisQ128::decrypt(key, nonce, &c256), Err(AegisQError::AuthenticationFailed));
+    }
+
+    #[test]
+    fn test_preset_codes() {
+        for preset in [Preset::AegisQ128, Preset::AegisQ192, Preset::AegisQ256] {
+            assert_eq!(Preset::from_code(preset.code()).unwrap(), preset);
+            assert!(preset.params().rounds > 0);
+        }
+        assert_eq!(Preset::AegisQ256.code(), 0);
+        assert!(Preset::from_code(3).is_err());
+        assert!(Preset::AegisQ128.params().lattice_n < Preset::AegisQ256.params().lattice_n);
+
+        // The default preset is the configuration of the plain functions
+        let params = Preset::default().params();
+        assert_eq!(
+            (params.lattice_n, params.code_n, params.rounds),
+            (pq_primitives::lattice::N, pq_primitives::eccodes::CODE_N, crate::round::ROUNDS)
+        );
+    }
+}
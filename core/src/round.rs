@@ -8,10 +8,11 @@
 //! S_next = concat(S_L', S_C', S_Z', S_M')
 
 use crate::state::State;
-use pq_primitives::lattice::{lattice_mix, derive_lattice_params};
+use pq_primitives::lattice::{lattice_mix, derive_lattice_params_n, LatticeState};
 use pq_primitives::eccodes::{code_mix, GeneratorMatrix, Permutation};
 use pq_primitives::zk::zk_mix;
 use sha3::{Shake256, digest::{Update, ExtendableOutput, XofReader}};
+use utils::memory::Wipe;
 
 /// Number of rounds
 #[cfg(feature = "small_params")]
@@ -22,7 +23,7 @@ pub const ROUNDS: usize = 10;
 /// Apply MaskMix transformation
 /// mask = SHAKE256(round_key || nonce || counter)
 /// state_M' = state_M XOR mask
-fn mask_mix(state: &mut Vec<u8>, round_key: &[u8], nonce: &[u8], counter: u64) {
+fn mask_mix(state: &mut [u8], round_key: &[u8], nonce: &[u8], counter: u64) {
     let mut hasher = Shake256::default();
     hasher.update(round_key);
     hasher.update(nonce);
@@ -36,6 +37,23 @@ fn mask_mix(state: &mut Vec<u8>, round_key: &[u8], nonce: &[u8], counter: u64) {
     for i in 0..state.len() {
         state[i] ^= mask[i];
     }
+    mask.wipe();
+}
+
+/// Layers applied by a round
+///
+/// Always `ALL` outside the `cryptanalysis` feature; a disabled layer
+/// leaves its state component unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Layers {
+    pub(crate) lattice: bool,
+    pub(crate) code: bool,
+    pub(crate) zk: bool,
+    pub(crate) mask: bool,
+}
+
+impl Layers {
+    pub(crate) const ALL: Layers = Layers { lattice: true, code: true, zk: true, mask: true };
 }
 
 /// Apply one round of Aegis-Q transformation
@@ -46,36 +64,94 @@ fn mask_mix(state: &mut Vec<u8>, round_key: &[u8], nonce: &[u8], counter: u64) {
 /// * `nonce` - Nonce
 /// * `counter` - Round counter
 pub fn round(state: &mut State, round_key: &[u8], nonce: &[u8], counter: u64) {
-    // Derive lattice parameters
-    let (a, b) = derive_lattice_params(round_key, nonce);
-    
+    round_with_layers(state, round_key, nonce, counter, Layers::ALL);
+}
+
+/// `round` applying only the enabled `layers`
+pub(crate) fn round_with_layers(state: &mut State, round_key: &[u8], nonce: &[u8], counter: u64, layers: Layers) {
     // Step 1: LatticeMix
     // S_L' = LatticeMix(S_L)
-    let lattice_new = lattice_mix(&state.lattice, &a, &b);
+    if layers.lattice {
+        // Derive lattice parameters (dimensions follow the state's preset)
+        let (mut a, mut b) = derive_lattice_params_n(round_key, nonce, state.lattice.len());
+        let lattice_new = lattice_mix(&state.lattice, &a, &b);
+        a.wipe();
+        b.wipe();
+        std::mem::replace(&mut state.lattice, lattice_new).wipe();
+    }
     
     // Step 2: CodeMix
     // S_C' = CodeMix(S_C)
-    let generator = GeneratorMatrix::from_key(round_key, nonce);
-    let permutation = Permutation::from_key(round_key, nonce);
-    let code_new = code_mix(&state.code, &generator, &permutation);
+    if layers.code {
+        let generator = GeneratorMatrix::from_key_n(round_key, nonce, state.code.len());
+        let permutation = Permutation::from_key_n(round_key, nonce, state.code.len());
+        let code_new = code_mix(&state.code, &generator, &permutation);
+        std::mem::replace(&mut state.code, code_new).wipe();
+    }
     
     // Step 3: ZKMix
     // S_Z' = ZKMix(S_Z, nonce)
-    let zk_new = zk_mix(&state.zk, nonce);
+    if layers.zk {
+        let zk_new = zk_mix(&state.zk, nonce);
+        std::mem::replace(&mut state.zk, zk_new).wipe();
+    }
     
     // Step 4: MaskMix
     // S_M' = MaskMix(S_M, nonce)
-    let mut mask_new = state.mask.clone();
-    mask_mix(&mut mask_new, round_key, nonce, counter);
-    
-    // Update state
-    state.lattice = lattice_new;
-    state.code = code_new;
-    state.zk = zk_new;
-    state.mask = mask_new;
+    if layers.mask {
+        mask_mix(&mut state.mask, round_key, nonce, counter);
+    }
+}
+
+/// Key-only layers of one round, derived once per key (`AegisQCipher`)
+///
+/// Holds what `round_with_layers` derives from the round key on every
+/// call: the LatticeMix parameters and the CodeMix matrix and permutation.
+/// ZKMix and MaskMix still take the nonce of each message.
+pub(crate) struct RoundSchedule {
+    round_key: Vec<u8>,
+    lattice_a: LatticeState,
+    lattice_b: LatticeState,
+    generator: GeneratorMatrix,
+    permutation: Permutation,
+}
+
+impl RoundSchedule {
+    /// Derive the layers of `round_key` for `lattice_n`/`code_n` dimensions
+    pub(crate) fn new(round_key: Vec<u8>, lattice_n: usize, code_n: usize) -> Self {
+        let (lattice_a, lattice_b) = derive_lattice_params_n(&round_key, &[], lattice_n);
+        let generator = GeneratorMatrix::from_key_n(&round_key, &[], code_n);
+        let permutation = Permutation::from_key_n(&round_key, &[], code_n);
+        Self { round_key, lattice_a, lattice_b, generator, permutation }
+    }
+
+    /// Apply the round to `state` for `nonce`
+    pub(crate) fn apply(&self, state: &mut State, nonce: &[u8], counter: u64) {
+        let lattice_new = lattice_mix(&state.lattice, &self.lattice_a, &self.lattice_b);
+        std::mem::replace(&mut state.lattice, lattice_new).wipe();
+
+        let code_new = code_mix(&state.code, &self.generator, &self.permutation);
+        std::mem::replace(&mut state.code, code_new).wipe();
+
+        let zk_new = zk_mix(&state.zk, nonce);
+        std::mem::replace(&mut state.zk, zk_new).wipe();
+
+        mask_mix(&mut state.mask, &self.round_key, nonce, counter);
+    }
+}
+
+impl Drop for RoundSchedule {
+    /// Wipes the round key and lattice parameters
+    fn drop(&mut self) {
+        self.round_key.wipe();
+        self.lattice_a.wipe();
+        self.lattice_b.wipe();
+    }
 }
 
 /// Generate round keys from master key
+/// 
+/// The keys are secret; wipe them after use.
 pub fn derive_round_keys(key: &[u8], nonce: &[u8], num_rounds: usize) -> Vec<Vec<u8>> {
     use sha3::Sha3_512;
     use hkdf::Hkdf;
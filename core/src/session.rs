@@ -0,0 +1,196 @@
+//! Session Cipher
+//!
+//! One direction of a session: a key, a 64-bit message counter and usage
+//! limits. Per-message nonces are built from the counter, so callers never
+//! construct nonces themselves:
+//!
+//! ```text
+//! nonce = nonce prefix || "aegis-q-session" || counter (8 bytes, BE)
+//! ```
+//!
+//! The counter only moves forward and the cipher hard-fails with
+//! `LimitExceeded` once the message or byte limit is reached; the session
+//! must then be rekeyed. Ciphertexts are `AegisQCipher` ciphertexts, so the
+//! receiver can also be an `AegisQCipher` with the same nonce.
+//!
+//! It is meant for channels with one key per direction and in-order (or
+//! increasing) messages, such as the licensing agent channel. Schemes with
+//! a fresh key per message (the messenger ratchet, VPN frame keys), with
+//! caller-supplied keys and wire sequence numbers (`framing`, QUIC
+//! streams) or with a nonce layout fixed by a spec (Noise) keep their own.
+
+use crate::cipher::AegisQCipher;
+use crate::error::AegisQError;
+
+/// Default message limit per key
+pub const DEFAULT_MAX_MESSAGES: u64 = 1 << 32;
+
+/// Default byte limit per key
+pub const DEFAULT_MAX_BYTES: u64 = 1 << 40;
+
+/// Build the nonce of message `sequence`
+pub fn session_nonce(prefix: &[u8], sequence: u64) -> Vec<u8> {
+    let mut nonce = Vec::with_capacity(prefix.len() + 15 + 8);
+    nonce.extend_from_slice(prefix);
+    nonce.extend_from_slice(b"aegis-q-session");
+    nonce.extend_from_slice(&sequence.to_be_bytes());
+    nonce
+}
+
+/// Usage limits of one key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionLimits {
+    /// Messages sealed or opened
+    pub max_messages: u64,
+    /// Plaintext bytes sealed or opened
+    pub max_bytes: u64,
+}
+
+impl Default for SessionLimits {
+    fn default() -> Self {
+        Self {
+            max_messages: DEFAULT_MAX_MESSAGES,
+            max_bytes: DEFAULT_MAX_BYTES,
+        }
+    }
+}
+
+/// Keyed cipher with automatic nonce sequencing (one direction)
+pub struct SessionCipher {
+    cipher: AegisQCipher,
+    nonce_prefix: Vec<u8>,
+    /// Sequence of the next message
+    next_sequence: u64,
+    messages: u64,
+    bytes: u64,
+    limits: SessionLimits,
+}
+
+impl SessionCipher {
+    /// Cipher for `key`; `nonce_prefix` separates sessions sharing a key
+    /// (e.g. a session ID or direction label)
+    pub fn new(key: &[u8], nonce_prefix: &[u8]) -> Self {
+        Self {
+            cipher: AegisQCipher::new(key),
+            nonce_prefix: nonce_prefix.to_vec(),
+            next_sequence: 0,
+            messages: 0,
+            bytes: 0,
+            limits: SessionLimits::default(),
+        }
+    }
+
+    /// Override the usage limits
+    pub fn with_limits(mut self, limits: SessionLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Sequence number the next message will use
+    pub fn next_sequence(&self) -> u64 {
+        self.next_sequence
+    }
+
+    /// Messages and plaintext bytes processed so far
+    pub fn usage(&self) -> (u64, u64) {
+        (self.messages, self.bytes)
+    }
+
+    pub fn limits(&self) -> SessionLimits {
+        self.limits
+    }
+
+    /// Encrypt the next message; returns its sequence number and ciphertext
+    pub fn seal(&mut self, plaintext: &[u8]) -> Result<(u64, Vec<u8>), AegisQError> {
+        let sequence = self.take_sequence(plaintext.len())?;
+        Ok((sequence, self.cipher.encrypt(&session_nonce(&self.nonce_prefix, sequence), plaintext)))
+    }
+
+    /// Decrypt the next message in order
+    pub fn open(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        self.check_limits(0)?;
+        let plaintext = self.cipher.decrypt(&session_nonce(&self.nonce_prefix, self.next_sequence), ciphertext)?;
+        self.take_sequence(plaintext.len())?;
+        Ok(plaintext)
+    }
+
+    /// Decrypt message `sequence` (transports that reorder); the counter
+    /// moves past it, so older sequences are rejected afterwards
+    pub fn open_at(&mut self, sequence: u64, ciphertext: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        if sequence < self.next_sequence {
+            return Err(AegisQError::Protocol("Message sequence already used"));
+        }
+        self.check_limits(0)?;
+        let plaintext = self.cipher.decrypt(&session_nonce(&self.nonce_prefix, sequence), ciphertext)?;
+        self.next_sequence = sequence;
+        self.take_sequence(plaintext.len())?;
+        Ok(plaintext)
+    }
+
+    fn check_limits(&self, len: usize) -> Result<(), AegisQError> {
+        if self.messages >= self.limits.max_messages || self.next_sequence == u64::MAX {
+            return Err(AegisQError::LimitExceeded("Session message limit reached"));
+        }
+        if self.bytes.saturating_add(len as u64) > self.limits.max_bytes {
+            return Err(AegisQError::LimitExceeded("Session byte limit reached"));
+        }
+        Ok(())
+    }
+
+    /// Account for one message of `len` bytes and advance the counter
+    fn take_sequence(&mut self, len: usize) -> Result<u64, AegisQError> {
+        self.check_limits(len)?;
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.messages += 1;
+        self.bytes += len as u64;
+        Ok(sequence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cipher::AegisQCipher;
+
+    const KEY: &[u8] = b"session-key-0123456789abcdef0123";
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_session_cipher_sequences_nonces() {
+        let mut sender = SessionCipher::new(KEY, b"c2s");
+        let mut receiver = SessionCipher::new(KEY, b"c2s");
+
+        let (first, ciphertext) = sender.seal(b"first").unwrap();
+        let (second, next) = sender.seal(b"second").unwrap();
+        assert_eq!((first, second), (0, 1));
+        assert_eq!(AegisQCipher::new(KEY).decrypt(&session_nonce(b"c2s", 1), &next).unwrap(), b"second");
+
+        assert_eq!(receiver.open(&ciphertext).unwrap(), b"first");
+        assert_eq!(receiver.open(&next).unwrap(), b"second");
+        assert_eq!(receiver.usage(), (2, 11));
+
+        // Out of order: skipping ahead works, going back does not
+        let (_, third) = sender.seal(b"third").unwrap();
+        let (_, fourth) = sender.seal(b"fourth").unwrap();
+        assert_eq!(receiver.open_at(3, &fourth).unwrap(), b"fourth");
+        assert!(receiver.open_at(2, &third).is_err());
+        assert_eq!(receiver.next_sequence(), 4);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_session_cipher_hard_fails_at_limits() {
+        let limits = SessionLimits { max_messages: 2, max_bytes: 1 << 20 };
+        let mut sender = SessionCipher::new(KEY, b"").with_limits(limits);
+        sender.seal(b"a").unwrap();
+        sender.seal(b"b").unwrap();
+        assert_eq!(sender.seal(b"c"), Err(AegisQError::LimitExceeded("Session message limit reached")));
+        assert_eq!(sender.next_sequence(), 2);
+
+        let mut bytes = SessionCipher::new(KEY, b"").with_limits(SessionLimits { max_messages: 10, max_bytes: 8 });
+        bytes.seal(b"12345").unwrap();
+        assert_eq!(bytes.seal(b"6789"), Err(AegisQError::LimitExceeded("Session byte limit reached")));
+        bytes.seal(b"678").unwrap();
+    }
+}
@@ -5,11 +5,48 @@
 //! - code: CodeMix state (4096 u32 values)
 //! - zk: ZKMix state (64 bytes)
 //! - mask: MaskMix state (variable size, typically 64 bytes)
+//!
+//! `encode`/`decode` are the persistent form: a fixed-layout header
+//! (`utils::wire::StateHeaderWire`: version, lattice and code dimensions,
+//! ZK and mask lengths) followed by the components, with every length
+//! checked on decode. With the `serde` feature `State` serializes as those
+//! bytes.
+//!
+//! `ratchet` advances a state one way: every layer is replaced by output
+//! of a KDF seeded with a hash of the whole state, so a state captured
+//! later does not reveal earlier ones. Transport rekeys
+//! (`VpnSession::rekey_send`) and the messenger chain step build on it.
+//!
+//! `export_keying_material` mirrors TLS exporters (RFC 8446 §7.5): secrets
+//! bound to the state under an application label and context, without
+//! exposing the state itself.
 
 use pq_primitives::lattice::{LatticeState, N as LATTICE_N};
 use pq_primitives::eccodes::{CodeState, CODE_N};
 use pq_primitives::zk::ZKState;
-use utils::kdf::kdf_shake256_fill;
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+use sha3::Shake256;
+use utils::kdf::{kdf_shake256, kdf_shake256_fill};
+use utils::memory::Wipe;
+use utils::wire::{StateHeaderWire, Wire};
+
+use crate::error::AegisQError;
+use crate::params::{Params, Preset};
+
+/// KDF domain labels for the four state components
+pub(crate) const DOMAIN_LATTICE: &[u8] = b"aegis-q-state-lattice";
+pub(crate) const DOMAIN_CODE: &[u8] = b"aegis-q-state-code";
+pub(crate) const DOMAIN_ZK: &[u8] = b"aegis-q-state-zk";
+pub(crate) const DOMAIN_MASK: &[u8] = b"aegis-q-state-mask";
+
+/// Current `State::encode` format version
+pub const STATE_FORMAT_VERSION: u8 = 1;
+
+/// Encoded `StateHeaderWire` size
+pub const STATE_HEADER_SIZE: usize = 9;
+
+/// Largest mask accepted by `State::decode`
+pub const MAX_MASK_SIZE: usize = 1024;
 
 /// Aegis-Q State structure
 #[derive(Clone)]
@@ -41,30 +78,49 @@ impl State {
     
     /// Initialize state from key and nonce
     pub fn from_key(key: &[u8], nonce: &[u8]) -> Self {
+        Self::from_kdf(|domain, out| kdf_shake256_fill(domain, key, nonce, out))
+    }
+    
+    /// Initialize state with the dimensions of a parameter preset
+    pub(crate) fn from_key_with(key: &[u8], nonce: &[u8], params: Params) -> Self {
+        Self::from_kdf_with(params, |domain, out| kdf_shake256_fill(domain, key, nonce, out))
+    }
+    
+    /// Initialize state from a KDF filling `out` for each domain label
+    /// 
+    /// Lets `AegisQCipher` reuse its key-absorbed KDF states.
+    pub(crate) fn from_kdf(fill: impl FnMut(&[u8], &mut [u8])) -> Self {
+        Self::from_kdf_with(Preset::default().params(), fill)
+    }
+    
+    /// `from_kdf` with the dimensions of a parameter preset
+    pub(crate) fn from_kdf_with(params: Params, mut fill: impl FnMut(&[u8], &mut [u8])) -> Self {
         // Derive lattice state
-        let mut lattice_bytes = vec![0u8; LATTICE_N * 4];
-        kdf_shake256_fill(b"aegis-q-state-lattice", key, nonce, &mut lattice_bytes);
+        let mut lattice_bytes = vec![0u8; params.lattice_n * 4];
+        fill(DOMAIN_LATTICE, &mut lattice_bytes);
         let lattice: LatticeState = lattice_bytes
             .chunks_exact(4)
             .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
             .collect();
+        lattice_bytes.wipe();
         
         // Derive code state
-        let mut code_bytes = vec![0u8; CODE_N * 4];
-        kdf_shake256_fill(b"aegis-q-state-code", key, nonce, &mut code_bytes);
+        let mut code_bytes = vec![0u8; params.code_n * 4];
+        fill(DOMAIN_CODE, &mut code_bytes);
         let code: CodeState = code_bytes
             .chunks_exact(4)
             .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
             .collect();
+        code_bytes.wipe();
         
         // Derive ZK state
         let mut zk_bytes = vec![0u8; pq_primitives::zk::ZK_STATE_SIZE];
-        kdf_shake256_fill(b"aegis-q-state-zk", key, nonce, &mut zk_bytes);
+        fill(DOMAIN_ZK, &mut zk_bytes);
         let zk = zk_bytes;
         
         // Derive mask state
         let mut mask_bytes = vec![0u8; 64];
-        kdf_shake256_fill(b"aegis-q-state-mask", key, nonce, &mut mask_bytes);
+        fill(DOMAIN_MASK, &mut mask_bytes);
         let mask = mask_bytes;
         
         Self {
@@ -75,7 +131,78 @@ impl State {
         }
     }
     
+    /// Feed `to_bytes()` into `hasher` without building the byte vector
+    /// 
+    /// Words are serialized through a small stack buffer that is wiped
+    /// afterwards, so no copy of the state is left on the heap.
+    pub(crate) fn absorb(&self, hasher: &mut impl Update) {
+        let mut buffer = [0u8; 1024];
+        for words in [&self.lattice, &self.code] {
+            for chunk in words.chunks(buffer.len() / 4) {
+                for (bytes, word) in buffer.chunks_exact_mut(4).zip(chunk) {
+                    bytes.copy_from_slice(&word.to_le_bytes());
+                }
+                hasher.update(&buffer[..chunk.len() * 4]);
+            }
+        }
+        buffer.wipe();
+        hasher.update(&self.zk);
+        hasher.update(&self.mask);
+    }
+    
+    /// Irreversibly advance the state (forward secrecy within a session)
+    ///
+    /// The seed is `SHAKE256("aegis-q-state-ratchet" || state)`; all four
+    /// layers are overwritten in place with KDF output under their domain
+    /// labels, keeping their dimensions. Both sides of a session that
+    /// ratchet after the same message stay in step.
+    pub fn ratchet(&mut self) {
+        let mut hasher = Shake256::default();
+        hasher.update(b"aegis-q-state-ratchet");
+        self.absorb(&mut hasher);
+        let mut seed = [0u8; 64];
+        hasher.finalize_xof().read(&mut seed);
+
+        let mut buffer = vec![0u8; 4 * self.lattice.len().max(self.code.len())];
+        for (domain, words) in [(DOMAIN_LATTICE, &mut self.lattice), (DOMAIN_CODE, &mut self.code)] {
+            let bytes = &mut buffer[..words.len() * 4];
+            kdf_shake256_fill(domain, &seed, b"ratchet", bytes);
+            for (word, chunk) in words.iter_mut().zip(bytes.chunks_exact(4)) {
+                *word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+            }
+        }
+        kdf_shake256_fill(DOMAIN_ZK, &seed, b"ratchet", &mut self.zk);
+        kdf_shake256_fill(DOMAIN_MASK, &seed, b"ratchet", &mut self.mask);
+        buffer.wipe();
+        seed.wipe();
+    }
+
+    /// Derive `len` bytes of keying material bound to this state
+    ///
+    /// `SHAKE256("aegis-q-kdf" || "aegis-q-exporter" || secret || label || context)`
+    /// with length-prefixed label and context, where `secret` is a hash of
+    /// the whole state. Different labels or contexts give independent
+    /// outputs; the output does not reveal the state.
+    pub fn export_keying_material(&self, label: &[u8], context: &[u8], len: usize) -> Vec<u8> {
+        let mut hasher = Shake256::default();
+        hasher.update(b"aegis-q-state-exporter");
+        self.absorb(&mut hasher);
+        let mut secret = [0u8; 64];
+        hasher.finalize_xof().read(&mut secret);
+
+        let mut info = Vec::with_capacity(8 + label.len() + context.len());
+        for part in [label, context] {
+            info.extend_from_slice(&(part.len() as u32).to_be_bytes());
+            info.extend_from_slice(part);
+        }
+        let output = kdf_shake256(b"aegis-q-exporter", &secret, &info, len);
+        secret.wipe();
+        output
+    }
+
     /// Concatenate state components into byte vector
+    /// 
+    /// The result holds the full secret state; wipe it after use.
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut result = Vec::new();
         
@@ -99,14 +226,14 @@ impl State {
     }
     
     /// Reconstruct state from bytes
-    pub fn from_bytes(bytes: &[u8]) -> Result<Self, &'static str> {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, AegisQError> {
         const LATTICE_BYTES: usize = LATTICE_N * 4;
         const CODE_BYTES: usize = CODE_N * 4;
         const ZK_BYTES: usize = pq_primitives::zk::ZK_STATE_SIZE;
         const MIN_SIZE: usize = LATTICE_BYTES + CODE_BYTES + ZK_BYTES;
         
         if bytes.len() < MIN_SIZE {
-            return Err("Invalid state size");
+            return Err(AegisQError::InvalidLength("Invalid state size"));
         }
         
         // Parse lattice
@@ -134,6 +261,121 @@ impl State {
             mask,
         })
     }
+
+    /// Compact persistent encoding: header || lattice || code || zk || mask
+    ///
+    /// Words are little-endian. The result holds the full secret state;
+    /// wipe it after use.
+    pub fn encode(&self) -> Vec<u8> {
+        let header = StateHeaderWire {
+            version: STATE_FORMAT_VERSION,
+            lattice_n: self.lattice.len() as u16,
+            code_n: self.code.len() as u16,
+            zk_len: self.zk.len() as u16,
+            mask_len: self.mask.len() as u16,
+        };
+        let mut encoded = header.to_wire().expect("fixed-size header");
+        encoded.reserve(4 * (self.lattice.len() + self.code.len()) + self.zk.len() + self.mask.len());
+        for &word in self.lattice.iter().chain(&self.code) {
+            encoded.extend_from_slice(&word.to_le_bytes());
+        }
+        encoded.extend_from_slice(&self.zk);
+        encoded.extend_from_slice(&self.mask);
+        encoded
+    }
+
+    /// Decode and validate a state produced by `encode`
+    ///
+    /// Dimensions must be those of a parameter preset in this build and the
+    /// input must be exactly as long as the header says.
+    pub fn decode(bytes: &[u8]) -> Result<Self, AegisQError> {
+        let (header, body) = StateHeaderWire::from_wire_prefix(bytes)
+            .map_err(|_| AegisQError::InvalidLength("Invalid state size"))?;
+        if header.version != STATE_FORMAT_VERSION {
+            return Err(AegisQError::Unsupported("Unsupported state format version"));
+        }
+        let (lattice_n, code_n) = (header.lattice_n as usize, header.code_n as usize);
+        let known = [Preset::AegisQ128, Preset::AegisQ192, Preset::AegisQ256]
+            .iter()
+            .any(|preset| preset.params().lattice_n == lattice_n && preset.params().code_n == code_n);
+        if !known {
+            return Err(AegisQError::Unsupported("Unknown state dimensions"));
+        }
+        if header.zk_len as usize != pq_primitives::zk::ZK_STATE_SIZE || header.mask_len as usize > MAX_MASK_SIZE {
+            return Err(AegisQError::InvalidInput("Invalid state component length"));
+        }
+
+        let words_len = 4 * (lattice_n + code_n);
+        if body.len() != words_len + header.zk_len as usize + header.mask_len as usize {
+            return Err(AegisQError::InvalidLength("Invalid state size"));
+        }
+        let (words, rest) = body.split_at(words_len);
+        let (zk, mask) = rest.split_at(header.zk_len as usize);
+        let mut words = words
+            .chunks_exact(4)
+            .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]));
+        Ok(Self {
+            lattice: words.by_ref().take(lattice_n).collect(),
+            code: words.collect(),
+            zk: zk.to_vec(),
+            mask: mask.to_vec(),
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for State {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut encoded = self.encode();
+        let result = serializer.serialize_bytes(&encoded);
+        encoded.wipe();
+        result
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for State {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StateVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for StateVisitor {
+            type Value = State;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("an encoded Aegis-Q state")
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, bytes: &[u8]) -> Result<State, E> {
+                State::decode(bytes).map_err(E::custom)
+            }
+
+            fn visit_byte_buf<E: serde::de::Error>(self, mut bytes: Vec<u8>) -> Result<State, E> {
+                let state = State::decode(&bytes);
+                bytes.wipe();
+                state.map_err(E::custom)
+            }
+
+            // Self-describing text formats (JSON) carry bytes as a sequence
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<State, A::Error> {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(1 << 20));
+                while let Some(byte) = seq.next_element::<u8>()? {
+                    bytes.push(byte);
+                }
+                self.visit_byte_buf(bytes)
+            }
+        }
+
+        deserializer.deserialize_bytes(StateVisitor)
+    }
+}
+
+impl Drop for State {
+    fn drop(&mut self) {
+        self.lattice.wipe();
+        self.code.wipe();
+        self.zk.wipe();
+        self.mask.wipe();
+    }
 }
 
 #[cfg(test)]
@@ -154,5 +396,83 @@ mod tests {
         assert_eq!(state1.zk, state2.zk);
         assert_eq!(state1.mask, state2.mask);
     }
+    
+    #[test]
+    fn test_state_encode_validates() {
+        let state = State::from_key(b"test-key-12345678", b"test-nonce");
+        let encoded = state.encode();
+        assert_eq!(encoded.len(), STATE_HEADER_SIZE + state.to_bytes().len());
+        let decoded = State::decode(&encoded).unwrap();
+        assert_eq!(decoded.to_bytes(), state.to_bytes());
+
+        // Smaller presets keep their dimensions
+        let small = Preset::AegisQ128.keyed_state(b"test-key-12345678", b"test-nonce");
+        assert_eq!(State::decode(&small.encode()).unwrap().lattice.len(), Preset::AegisQ128.params().lattice_n);
+
+        assert!(matches!(State::decode(&encoded[..encoded.len() - 1]), Err(AegisQError::InvalidLength(_))));
+        let mut future = encoded.clone();
+        future[0] = STATE_FORMAT_VERSION + 1;
+        assert!(matches!(State::decode(&future), Err(AegisQError::Unsupported(_))));
+        let mut odd = encoded.clone();
+        odd[1..3].copy_from_slice(&7u16.to_be_bytes());
+        assert!(matches!(State::decode(&odd), Err(AegisQError::Unsupported("Unknown state dimensions"))));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_state_serde() {
+        let state = State::from_key(b"test-key-12345678", b"test-nonce");
+        let json = serde_json::to_vec(&state).unwrap();
+        let restored: State = serde_json::from_slice(&json).unwrap();
+        assert_eq!(restored.to_bytes(), state.to_bytes());
+        assert!(serde_json::from_str::<State>("[1, 2, 3]").is_err());
+    }
+
+    #[test]
+    fn test_ratchet_is_one_way_and_deterministic() {
+        let initial = Preset::AegisQ128.keyed_state(b"test-key-12345678", b"test-nonce");
+        let mut a = initial.clone();
+        let mut b = initial.clone();
+        a.ratchet();
+        b.ratchet();
+        assert_eq!(a.to_bytes(), b.to_bytes());
+        assert_eq!(a.lattice.len(), initial.lattice.len());
+        assert_eq!(a.mask.len(), initial.mask.len());
+        assert_ne!(a.lattice, initial.lattice);
+        assert_ne!(a.code, initial.code);
+        assert_ne!(a.zk, initial.zk);
+        assert_ne!(a.mask, initial.mask);
+
+        let once = a.to_bytes();
+        a.ratchet();
+        assert_ne!(a.to_bytes(), once);
+    }
+
+    #[test]
+    fn test_export_keying_material_is_label_and_context_bound() {
+        let state = Preset::AegisQ128.keyed_state(b"test-key-12345678", b"test-nonce");
+        let token_key = state.export_keying_material(b"app token", b"", 32);
+        assert_eq!(token_key.len(), 32);
+        assert_eq!(state.export_keying_material(b"app token", b"", 32), token_key);
+        assert_eq!(state.export_keying_material(b"app token", b"", 64)[..32], token_key[..]);
+        assert_ne!(state.export_keying_material(b"app token", b"user-1", 32), token_key);
+        assert_ne!(state.export_keying_material(b"other", b"", 32), token_key);
+        // Length prefixes keep label and context apart
+        assert_ne!(state.export_keying_material(b"ab", b"c", 32), state.export_keying_material(b"a", b"bc", 32));
+
+        let mut ratcheted = state.clone();
+        ratcheted.ratchet();
+        assert_ne!(ratcheted.export_keying_material(b"app token", b"", 32), token_key);
+    }
+
+    #[test]
+    fn test_absorb_matches_to_bytes() {
+        use sha3::{Digest, Sha3_256};
+        
+        let state = State::from_key(b"test-key-12345678", b"test-nonce");
+        let mut streamed = Sha3_256::new();
+        state.absorb(&mut streamed);
+        assert_eq!(streamed.finalize(), Sha3_256::digest(state.to_bytes()));
+    }
 }
 
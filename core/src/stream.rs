@@ -0,0 +1,238 @@
+//! Aegis-Q Streaming AEAD
+//!
+//! Chunked encryption for payloads too large to hold in memory (STREAM
+//! construction). The plaintext is cut into fixed-size chunks and each
+//! chunk is sealed on its own, with a nonce derived from the stream nonce,
+//! the chunk counter and a last-chunk flag:
+//!
+//! chunk nonce = nonce || "aegis-q-stream" || counter (8 bytes, BE) || last (1 byte)
+//!
+//! Reordered or duplicated chunks fail authentication through the counter;
+//! a stream cut at a chunk boundary fails because its final chunk was not
+//! sealed with the last flag. Every ciphertext chunk but the last is exactly
+//! `chunk_size + TAG_SIZE` bytes, so the decryptor needs no framing.
+
+use utils::memory::Wipe;
+
+use crate::encrypt::{aegis_q_decrypt, aegis_q_encrypt, TAG_SIZE};
+use crate::error::AegisQError;
+
+/// Default plaintext chunk size
+pub const DEFAULT_STREAM_CHUNK: usize = 64 * 1024;
+
+fn chunk_nonce(nonce: &[u8], counter: u64, last: bool) -> Vec<u8> {
+    let mut chunk_nonce = Vec::with_capacity(nonce.len() + 23);
+    chunk_nonce.extend_from_slice(nonce);
+    chunk_nonce.extend_from_slice(b"aegis-q-stream");
+    chunk_nonce.extend_from_slice(&counter.to_be_bytes());
+    chunk_nonce.push(last as u8);
+    chunk_nonce
+}
+
+fn next_counter(counter: u64) -> Result<u64, AegisQError> {
+    counter.checked_add(1).ok_or(AegisQError::LimitExceeded("Stream chunk counter exhausted"))
+}
+
+fn check_chunk_size(chunk_size: usize) -> Result<(), AegisQError> {
+    if chunk_size == 0 {
+        return Err(AegisQError::InvalidInput("Stream chunk size must be positive"));
+    }
+    Ok(())
+}
+
+/// Incremental encryptor
+pub struct AegisQStreamEncryptor {
+    key: Vec<u8>,
+    nonce: Vec<u8>,
+    chunk_size: usize,
+    counter: u64,
+    buffer: Vec<u8>,
+}
+
+impl AegisQStreamEncryptor {
+    /// Encryptor with the default chunk size
+    pub fn new(key: &[u8], nonce: &[u8]) -> Self {
+        Self {
+            key: key.to_vec(),
+            nonce: nonce.to_vec(),
+            chunk_size: DEFAULT_STREAM_CHUNK,
+            counter: 0,
+            buffer: Vec::with_capacity(DEFAULT_STREAM_CHUNK),
+        }
+    }
+
+    /// Encryptor with a custom chunk size (the decryptor must use the same)
+    pub fn with_chunk_size(key: &[u8], nonce: &[u8], chunk_size: usize) -> Result<Self, AegisQError> {
+        check_chunk_size(chunk_size)?;
+        let mut encryptor = Self::new(key, nonce);
+        encryptor.chunk_size = chunk_size;
+        encryptor.buffer = Vec::with_capacity(chunk_size);
+        Ok(encryptor)
+    }
+
+    /// Feed plaintext; returns ciphertext for every chunk completed so far
+    ///
+    /// A full chunk is held back until more data arrives, since it may turn
+    /// out to be the last one.
+    pub fn push(&mut self, mut data: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        let mut out = Vec::new();
+        while !data.is_empty() {
+            if self.buffer.len() == self.chunk_size {
+                out.extend_from_slice(&self.seal(false)?);
+            }
+            let take = (self.chunk_size - self.buffer.len()).min(data.len());
+            self.buffer.extend_from_slice(&data[..take]);
+            data = &data[take..];
+        }
+        Ok(out)
+    }
+
+    /// Seal the final chunk (possibly empty) with the end-of-stream marker
+    pub fn finalize(mut self) -> Result<Vec<u8>, AegisQError> {
+        self.seal(true)
+    }
+
+    fn seal(&mut self, last: bool) -> Result<Vec<u8>, AegisQError> {
+        let ciphertext = aegis_q_encrypt(&self.key, &chunk_nonce(&self.nonce, self.counter, last), &self.buffer);
+        self.counter = next_counter(self.counter)?;
+        self.buffer.wipe();
+        self.buffer.clear();
+        Ok(ciphertext)
+    }
+}
+
+impl Drop for AegisQStreamEncryptor {
+    fn drop(&mut self) {
+        self.key.wipe();
+        self.buffer.wipe();
+    }
+}
+
+/// Incremental decryptor
+///
+/// Plaintext is only released once its chunk has been authenticated;
+/// `finalize` fails if the stream ended without a valid last chunk.
+pub struct AegisQStreamDecryptor {
+    key: Vec<u8>,
+    nonce: Vec<u8>,
+    chunk_size: usize,
+    counter: u64,
+    buffer: Vec<u8>,
+}
+
+impl AegisQStreamDecryptor {
+    /// Decryptor with the default chunk size
+    pub fn new(key: &[u8], nonce: &[u8]) -> Self {
+        Self {
+            key: key.to_vec(),
+            nonce: nonce.to_vec(),
+            chunk_size: DEFAULT_STREAM_CHUNK,
+            counter: 0,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Decryptor with a custom chunk size
+    pub fn with_chunk_size(key: &[u8], nonce: &[u8], chunk_size: usize) -> Result<Self, AegisQError> {
+        check_chunk_size(chunk_size)?;
+        let mut decryptor = Self::new(key, nonce);
+        decryptor.chunk_size = chunk_size;
+        Ok(decryptor)
+    }
+
+    /// Feed ciphertext; returns plaintext of every chunk authenticated so far
+    pub fn push(&mut self, data: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        self.buffer.extend_from_slice(data);
+        let sealed_chunk = self.chunk_size + TAG_SIZE;
+
+        let mut out = Vec::new();
+        let mut consumed = 0;
+        // Keep at least one full chunk back: it may be the last one
+        while self.buffer.len() - consumed > sealed_chunk {
+            let chunk = &self.buffer[consumed..consumed + sealed_chunk];
+            out.extend_from_slice(&aegis_q_decrypt(&self.key, &chunk_nonce(&self.nonce, self.counter, false), chunk)?);
+            self.counter = next_counter(self.counter)?;
+            consumed += sealed_chunk;
+        }
+        self.buffer.drain(..consumed);
+        Ok(out)
+    }
+
+    /// Authenticate the last chunk; fails on a truncated stream
+    pub fn finalize(mut self) -> Result<Vec<u8>, AegisQError> {
+        let chunk = std::mem::take(&mut self.buffer);
+        if chunk.len() < TAG_SIZE {
+            return Err(AegisQError::InvalidLength("Stream truncated"));
+        }
+        aegis_q_decrypt(&self.key, &chunk_nonce(&self.nonce, self.counter, true), &chunk)
+    }
+}
+
+impl Drop for AegisQStreamDecryptor {
+    fn drop(&mut self) {
+        self.key.wipe();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = b"stream-key-0123456789abcdef01234";
+    const NONCE: &[u8] = b"stream-nonce";
+
+    fn encrypt(data: &[u8], chunk_size: usize, pieces: usize) -> Vec<u8> {
+        let mut encryptor = AegisQStreamEncryptor::with_chunk_size(KEY, NONCE, chunk_size).unwrap();
+        let mut ciphertext = Vec::new();
+        for piece in data.chunks(data.len().div_ceil(pieces).max(1)) {
+            ciphertext.extend(encryptor.push(piece).unwrap());
+        }
+        ciphertext.extend(encryptor.finalize().unwrap());
+        ciphertext
+    }
+
+    fn decrypt(ciphertext: &[u8], chunk_size: usize, pieces: usize) -> Result<Vec<u8>, AegisQError> {
+        let mut decryptor = AegisQStreamDecryptor::with_chunk_size(KEY, NONCE, chunk_size).unwrap();
+        let mut plaintext = Vec::new();
+        for piece in ciphertext.chunks(ciphertext.len().div_ceil(pieces).max(1)) {
+            plaintext.extend(decryptor.push(piece)?);
+        }
+        plaintext.extend(decryptor.finalize()?);
+        Ok(plaintext)
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_stream_round_trip() {
+        let data: Vec<u8> = (0..100u8).collect();
+        for (pushes, chunk_size) in [(1, 32), (7, 32), (3, 25), (1, 100)] {
+            let ciphertext = encrypt(&data, chunk_size, pushes);
+            assert_eq!(ciphertext.len(), data.len() + data.len().div_ceil(chunk_size) * TAG_SIZE);
+            assert_eq!(decrypt(&ciphertext, chunk_size, 5).unwrap(), data);
+        }
+
+        // Empty stream is a single sealed last chunk
+        let empty = encrypt(&[], 32, 1);
+        assert_eq!(empty.len(), TAG_SIZE);
+        assert_eq!(decrypt(&empty, 32, 1).unwrap(), b"");
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_stream_detects_truncation_and_reordering() {
+        let data = [7u8; 96];
+        let ciphertext = encrypt(&data, 32, 1);
+        let sealed = 32 + TAG_SIZE;
+
+        // Cut at a chunk boundary: the new final chunk lacks the last flag
+        assert_eq!(decrypt(&ciphertext[..2 * sealed], 32, 1), Err(AegisQError::AuthenticationFailed));
+        assert_eq!(decrypt(&[], 32, 1), Err(AegisQError::InvalidLength("Stream truncated")));
+
+        let mut swapped = ciphertext.clone();
+        let (first, second) = swapped.split_at_mut(sealed);
+        first.swap_with_slice(&mut second[..sealed]);
+        assert_eq!(decrypt(&swapped, 32, 1), Err(AegisQError::AuthenticationFailed));
+
+        assert!(AegisQStreamEncryptor::with_chunk_size(KEY, NONCE, 0).is_err());
+    }
+}
@@ -0,0 +1,438 @@
+//! Algorithm Registry
+//!
+//! Stable 16-bit identifiers for every algorithm the workspace can name on
+//! the wire: AEAD profiles, KEMs, signatures and hashes. The high byte is
+//! the algorithm family (0x01 AEAD, 0x02 KEM, 0x03 signature, 0x04 hash);
+//! identifiers are never reused.
+//!
+//! An `AlgorithmSuite` picks one of each and is validated so that no
+//! component is weaker than the AEAD profile. Suites are what ciphertext
+//! headers, handshakes, license signatures and envelopes refer to when an
+//! algorithm changes; `negotiate` picks the first suite of a peer's offer
+//! that we support. Offers may list suites we do not know: those are
+//! skipped, so new algorithms can be offered before every peer has them.
+//!
+//! A `SecurityPolicy` sets the lowest level a deployment accepts. Its
+//! checks fail with `DowngradeRejected`, and its `negotiate` tells a
+//! downgrade (the only common suites are too weak) apart from plain
+//! incompatibility. An attacker who strips the strong suites from an offer
+//! is caught by binding the offer into the handshake: both sides mix
+//! `offer_transcript` of the exact offer bytes and the selected suite into
+//! the transcript, so an edited offer makes the handshake fail.
+//!
+//! The byte layouts are `utils::wire::AlgorithmSuiteWire` and
+//! `utils::wire::SuiteOfferWire`.
+
+use utils::wire::{AlgorithmSuiteWire, SuiteOfferWire, Wire};
+
+use crate::error::AegisQError;
+use crate::options::SecurityLevel;
+use crate::params::Preset;
+
+/// Encoded size of one suite
+pub const SUITE_SIZE: usize = 8;
+
+/// Most suites accepted in one offer
+pub const MAX_OFFERED_SUITES: usize = 16;
+
+macro_rules! registry {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident ($unknown:literal) {
+            $($(#[$vmeta:meta])* $variant:ident = $id:literal, $label:literal, $level:ident;)+
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub enum $name {
+            $($(#[$vmeta])* $variant,)+
+        }
+
+        impl $name {
+            /// All registered algorithms
+            pub const ALL: &'static [$name] = &[$($name::$variant,)+];
+
+            /// Registry identifier
+            pub const fn id(self) -> u16 {
+                match self {
+                    $($name::$variant => $id,)+
+                }
+            }
+
+            /// Algorithm for a registry identifier
+            pub fn from_id(id: u16) -> Result<Self, AegisQError> {
+                match id {
+                    $($id => Ok($name::$variant),)+
+                    _ => Err(AegisQError::Unsupported($unknown)),
+                }
+            }
+
+            /// Canonical name
+            pub const fn name(self) -> &'static str {
+                match self {
+                    $($name::$variant => $label,)+
+                }
+            }
+
+            /// Security level (classical bits)
+            pub const fn security_level(self) -> SecurityLevel {
+                match self {
+                    $($name::$variant => SecurityLevel::$level,)+
+                }
+            }
+        }
+    };
+}
+
+registry! {
+    /// AEAD profile
+    pub enum AeadId ("Unknown AEAD algorithm") {
+        AegisQ128 = 0x0101, "Aegis-Q-128", L128;
+        AegisQ192 = 0x0102, "Aegis-Q-192", L192;
+        AegisQ256 = 0x0103, "Aegis-Q-256", L256;
+    }
+}
+
+registry! {
+    /// Key encapsulation mechanism
+    pub enum KemId ("Unknown KEM algorithm") {
+        MlKem512 = 0x0201, "ML-KEM-512", L128;
+        MlKem768 = 0x0202, "ML-KEM-768", L192;
+        MlKem1024 = 0x0203, "ML-KEM-1024", L256;
+    }
+}
+
+registry! {
+    /// Signature (or symmetric signature) algorithm
+    pub enum SignatureId ("Unknown signature algorithm") {
+        /// Keyed SHA3-512 over the signed fields (`License::sign`)
+        KeyedSha3_512 = 0x0301, "Keyed-SHA3-512", L256;
+        MlDsa44 = 0x0302, "ML-DSA-44", L128;
+        MlDsa65 = 0x0303, "ML-DSA-65", L192;
+        MlDsa87 = 0x0304, "ML-DSA-87", L256;
+        /// Aegis-Q MAC (`aegis_q_mac`)
+        AegisQMac = 0x0305, "Aegis-Q-MAC", L256;
+    }
+}
+
+registry! {
+    /// Hash function
+    pub enum HashId ("Unknown hash algorithm") {
+        Sha3_256 = 0x0401, "SHA3-256", L128;
+        Sha3_512 = 0x0402, "SHA3-512", L256;
+        Shake256 = 0x0403, "SHAKE256", L256;
+        /// Aegis-Q hash (`aegis_q_hash`)
+        AegisQHash = 0x0404, "Aegis-Q-Hash", L256;
+    }
+}
+
+impl AeadId {
+    /// Parameter preset of the profile
+    pub const fn preset(self) -> Preset {
+        match self {
+            AeadId::AegisQ128 => Preset::AegisQ128,
+            AeadId::AegisQ192 => Preset::AegisQ192,
+            AeadId::AegisQ256 => Preset::AegisQ256,
+        }
+    }
+}
+
+impl From<Preset> for AeadId {
+    fn from(preset: Preset) -> Self {
+        match preset {
+            Preset::AegisQ128 => AeadId::AegisQ128,
+            Preset::AegisQ192 => AeadId::AegisQ192,
+            Preset::AegisQ256 => AeadId::AegisQ256,
+        }
+    }
+}
+
+/// One algorithm of each kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AlgorithmSuite {
+    aead: AeadId,
+    kem: KemId,
+    signature: SignatureId,
+    hash: HashId,
+}
+
+impl AlgorithmSuite {
+    /// Current default: Aegis-Q-256, ML-KEM-1024, keyed SHA3-512, SHAKE256
+    pub const DEFAULT: AlgorithmSuite = AlgorithmSuite {
+        aead: AeadId::AegisQ256,
+        kem: KemId::MlKem1024,
+        signature: SignatureId::KeyedSha3_512,
+        hash: HashId::Shake256,
+    };
+
+    /// Validated suite
+    ///
+    /// Rejects combinations where the KEM, signature or hash is weaker
+    /// than the AEAD profile.
+    pub fn new(aead: AeadId, kem: KemId, signature: SignatureId, hash: HashId) -> Result<Self, AegisQError> {
+        let level = aead.security_level();
+        if kem.security_level() < level || signature.security_level() < level || hash.security_level() < level {
+            return Err(AegisQError::InvalidInput("Algorithm suite weaker than its AEAD profile"));
+        }
+        Ok(Self { aead, kem, signature, hash })
+    }
+
+    pub fn aead(&self) -> AeadId {
+        self.aead
+    }
+
+    pub fn kem(&self) -> KemId {
+        self.kem
+    }
+
+    pub fn signature(&self) -> SignatureId {
+        self.signature
+    }
+
+    pub fn hash(&self) -> HashId {
+        self.hash
+    }
+
+    /// Security level of the suite (that of its AEAD profile)
+    pub fn security_level(&self) -> SecurityLevel {
+        self.aead.security_level()
+    }
+
+    /// Canonical name, e.g. `Aegis-Q-256_ML-KEM-1024_Keyed-SHA3-512_SHAKE256`
+    pub fn name(&self) -> String {
+        format!("{}_{}_{}_{}", self.aead.name(), self.kem.name(), self.signature.name(), self.hash.name())
+    }
+
+    /// Encode to `SUITE_SIZE` bytes
+    pub fn encode(&self) -> [u8; SUITE_SIZE] {
+        let mut bytes = [0u8; SUITE_SIZE];
+        bytes.copy_from_slice(&self.wire_struct().to_wire().expect("fixed-size suite"));
+        bytes
+    }
+
+    /// Decode and validate a suite
+    pub fn decode(bytes: &[u8]) -> Result<Self, AegisQError> {
+        Self::from_wire_struct(&AlgorithmSuiteWire::from_wire(bytes).map_err(AegisQError::Serialization)?)
+    }
+
+    /// Encode an offer, most preferred first
+    pub fn encode_offer(suites: &[AlgorithmSuite]) -> Result<Vec<u8>, AegisQError> {
+        if suites.len() > MAX_OFFERED_SUITES {
+            return Err(AegisQError::LimitExceeded("Too many offered suites"));
+        }
+        SuiteOfferWire {
+            suites: suites.iter().map(Self::wire_struct).collect(),
+        }
+        .to_wire()
+        .map_err(AegisQError::Serialization)
+    }
+
+    /// Decode an offer, dropping suites that are unknown or invalid here
+    pub fn decode_offer(bytes: &[u8]) -> Result<Vec<AlgorithmSuite>, AegisQError> {
+        let offer = SuiteOfferWire::from_wire(bytes).map_err(AegisQError::Serialization)?;
+        if offer.suites.len() > MAX_OFFERED_SUITES {
+            return Err(AegisQError::LimitExceeded("Too many offered suites"));
+        }
+        Ok(offer.suites.iter().filter_map(|wire| Self::from_wire_struct(wire).ok()).collect())
+    }
+
+    fn wire_struct(&self) -> AlgorithmSuiteWire {
+        AlgorithmSuiteWire {
+            aead: self.aead.id(),
+            kem: self.kem.id(),
+            signature: self.signature.id(),
+            hash: self.hash.id(),
+        }
+    }
+
+    fn from_wire_struct(wire: &AlgorithmSuiteWire) -> Result<Self, AegisQError> {
+        Self::new(
+            AeadId::from_id(wire.aead)?,
+            KemId::from_id(wire.kem)?,
+            SignatureId::from_id(wire.signature)?,
+            HashId::from_id(wire.hash)?,
+        )
+    }
+}
+
+impl Default for AlgorithmSuite {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Pick the first suite of `offered` (peer preference order) we support
+pub fn negotiate(offered: &[AlgorithmSuite], supported: &[AlgorithmSuite]) -> Result<AlgorithmSuite, AegisQError> {
+    offered
+        .iter()
+        .find(|suite| supported.contains(suite))
+        .copied()
+        .ok_or(AegisQError::Unsupported("No common algorithm suite"))
+}
+
+/// Transcript value binding an encoded offer to the suite selected from it
+///
+/// Both sides mix it into the handshake (e.g. the Noise prologue). `offer`
+/// must be the bytes as sent, including suites the responder skipped.
+pub fn offer_transcript(offer: &[u8], selected: &AlgorithmSuite) -> Vec<u8> {
+    let mut transcript = Vec::with_capacity(19 + 4 + offer.len() + SUITE_SIZE);
+    transcript.extend_from_slice(b"aegis-q-suite-offer");
+    transcript.extend_from_slice(&(offer.len() as u32).to_be_bytes());
+    transcript.extend_from_slice(offer);
+    transcript.extend_from_slice(&selected.encode());
+    transcript
+}
+
+/// Lowest security level a deployment accepts
+///
+/// The default accepts every registered algorithm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SecurityPolicy {
+    min_level: SecurityLevel,
+}
+
+impl SecurityPolicy {
+    /// Policy requiring at least `min_level`
+    pub const fn new(min_level: SecurityLevel) -> Self {
+        Self { min_level }
+    }
+
+    pub fn min_level(&self) -> SecurityLevel {
+        self.min_level
+    }
+
+    /// Reject a level below the minimum
+    pub fn check_level(&self, level: SecurityLevel) -> Result<(), AegisQError> {
+        if level < self.min_level {
+            return Err(AegisQError::DowngradeRejected("Security level below policy"));
+        }
+        Ok(())
+    }
+
+    /// Reject an AEAD profile below the minimum
+    pub fn check_aead(&self, aead: AeadId) -> Result<(), AegisQError> {
+        self.check_level(aead.security_level())
+    }
+
+    /// Reject a signature algorithm below the minimum
+    pub fn check_signature(&self, signature: SignatureId) -> Result<(), AegisQError> {
+        self.check_level(signature.security_level())
+    }
+
+    /// Reject a suite below the minimum
+    pub fn check_suite(&self, suite: &AlgorithmSuite) -> Result<(), AegisQError> {
+        self.check_level(suite.security_level())
+    }
+
+    /// `negotiate` restricted to suites the policy accepts
+    ///
+    /// Fails with `DowngradeRejected` if suites are in common but all of
+    /// them are below the minimum.
+    pub fn negotiate(&self, offered: &[AlgorithmSuite], supported: &[AlgorithmSuite]) -> Result<AlgorithmSuite, AegisQError> {
+        let acceptable: Vec<AlgorithmSuite> =
+            supported.iter().copied().filter(|suite| self.check_suite(suite).is_ok()).collect();
+        match negotiate(offered, &acceptable) {
+            Err(_) if negotiate(offered, supported).is_ok() => {
+                Err(AegisQError::DowngradeRejected("Only suites below policy in common"))
+            }
+            result => result,
+        }
+    }
+
+    /// Initiator side: check the suite the responder selected
+    ///
+    /// It must be one we offered and acceptable under this policy.
+    pub fn confirm(&self, offered: &[AlgorithmSuite], selected: &AlgorithmSuite) -> Result<(), AegisQError> {
+        if !offered.contains(selected) {
+            return Err(AegisQError::Protocol("Selected suite was not offered"));
+        }
+        self.check_suite(selected)
+    }
+}
+
+impl Default for SecurityPolicy {
+    fn default() -> Self {
+        Self::new(SecurityLevel::L128)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suite_128() -> AlgorithmSuite {
+        AlgorithmSuite::new(AeadId::AegisQ128, KemId::MlKem768, SignatureId::MlDsa44, HashId::Sha3_256).unwrap()
+    }
+
+    #[test]
+    fn test_registry_ids_unique_and_round_trip() {
+        let mut ids: Vec<u16> = AeadId::ALL.iter().map(|a| a.id()).collect();
+        ids.extend(KemId::ALL.iter().map(|a| a.id()));
+        ids.extend(SignatureId::ALL.iter().map(|a| a.id()));
+        ids.extend(HashId::ALL.iter().map(|a| a.id()));
+        let count = ids.len();
+        ids.sort_unstable();
+        ids.dedup();
+        assert_eq!(ids.len(), count);
+
+        for aead in AeadId::ALL {
+            assert_eq!(AeadId::from_id(aead.id()).unwrap(), *aead);
+            assert_eq!(AeadId::from(aead.preset()), *aead);
+        }
+        assert_eq!(KemId::from_id(0x0204), Err(AegisQError::Unsupported("Unknown KEM algorithm")));
+    }
+
+    #[test]
+    fn test_suite_validation_and_encoding() {
+        let suite = AlgorithmSuite::DEFAULT;
+        assert_eq!(suite.name(), "Aegis-Q-256_ML-KEM-1024_Keyed-SHA3-512_SHAKE256");
+        assert_eq!(suite.encode(), [0x01, 0x03, 0x02, 0x03, 0x03, 0x01, 0x04, 0x03]);
+        assert_eq!(AlgorithmSuite::decode(&suite.encode()).unwrap(), suite);
+
+        // A 256-bit AEAD with a 128-bit KEM is not a valid suite
+        assert!(AlgorithmSuite::new(AeadId::AegisQ256, KemId::MlKem512, SignatureId::MlDsa87, HashId::Sha3_512).is_err());
+        assert!(AlgorithmSuite::decode(&[0x01, 0x03, 0x02, 0x01, 0x03, 0x01, 0x04, 0x03]).is_err());
+        assert_eq!(suite_128().security_level(), SecurityLevel::L128);
+    }
+
+    #[test]
+    fn test_negotiation_skips_unknown_suites() {
+        let mut offer = AlgorithmSuite::encode_offer(&[suite_128(), AlgorithmSuite::DEFAULT]).unwrap();
+        // A suite from the future, offered first
+        offer.splice(0..0, [0x01, 0x99, 0x02, 0x03, 0x03, 0x01, 0x04, 0x03]);
+
+        let offered = AlgorithmSuite::decode_offer(&offer).unwrap();
+        assert_eq!(offered, [suite_128(), AlgorithmSuite::DEFAULT]);
+        assert_eq!(negotiate(&offered, &[AlgorithmSuite::DEFAULT, suite_128()]).unwrap(), suite_128());
+        assert_eq!(negotiate(&offered, &[AlgorithmSuite::DEFAULT]).unwrap(), AlgorithmSuite::DEFAULT);
+        assert!(negotiate(&offered, &[]).is_err());
+
+        assert!(AlgorithmSuite::decode_offer(&offer[..offer.len() - 1]).is_err());
+        assert!(AlgorithmSuite::encode_offer(&[AlgorithmSuite::DEFAULT; MAX_OFFERED_SUITES + 1]).is_err());
+    }
+
+    #[test]
+    fn test_policy_rejects_downgrade() {
+        let policy = SecurityPolicy::new(SecurityLevel::L192);
+        assert_eq!(policy.check_aead(AeadId::AegisQ128), Err(AegisQError::DowngradeRejected("Security level below policy")));
+        policy.check_suite(&AlgorithmSuite::DEFAULT).unwrap();
+        SecurityPolicy::default().check_suite(&suite_128()).unwrap();
+
+        // An offer stripped down to the weak suite is a downgrade, not a mismatch
+        let supported = [AlgorithmSuite::DEFAULT, suite_128()];
+        assert_eq!(
+            policy.negotiate(&[suite_128()], &supported),
+            Err(AegisQError::DowngradeRejected("Only suites below policy in common"))
+        );
+        assert_eq!(policy.negotiate(&[suite_128(), AlgorithmSuite::DEFAULT], &supported).unwrap(), AlgorithmSuite::DEFAULT);
+        assert_eq!(policy.negotiate(&[suite_128()], &[AlgorithmSuite::DEFAULT]), Err(AegisQError::Unsupported("No common algorithm suite")));
+
+        assert!(policy.confirm(&[AlgorithmSuite::DEFAULT], &suite_128()).is_err());
+        policy.confirm(&[AlgorithmSuite::DEFAULT], &AlgorithmSuite::DEFAULT).unwrap();
+
+        // The transcript covers the exact offer bytes
+        let full = AlgorithmSuite::encode_offer(&supported).unwrap();
+        let stripped = AlgorithmSuite::encode_offer(&supported[..1]).unwrap();
+        assert_ne!(offer_transcript(&full, &AlgorithmSuite::DEFAULT), offer_transcript(&stripped, &AlgorithmSuite::DEFAULT));
+    }
+}
@@ -0,0 +1,175 @@
+//! Length-checked key and nonce types
+//!
+//! The encryption functions take raw byte slices and accept any length,
+//! including empty keys. `AegisKey` and `AegisNonce` check the documented
+//! sizes once, at construction, and deref to `[u8]` so they pass straight
+//! into the slice APIs.
+
+use std::fmt;
+use std::ops::Deref;
+
+use utils::memory::Wipe;
+
+use crate::encrypt::AUTO_NONCE_SIZE;
+use crate::error::AegisQError;
+
+/// Shortest accepted key
+pub const MIN_KEY_SIZE: usize = 32;
+/// Longest accepted key
+pub const MAX_KEY_SIZE: usize = 64;
+/// Shortest accepted nonce
+pub const MIN_NONCE_SIZE: usize = 16;
+/// Longest accepted nonce
+pub const MAX_NONCE_SIZE: usize = 32;
+
+/// Aegis-Q key of 32–64 bytes, wiped on drop
+#[derive(Clone, PartialEq, Eq)]
+pub struct AegisKey(Vec<u8>);
+
+impl AegisKey {
+    /// Copy a key, checking its length
+    pub fn new(key: &[u8]) -> Result<Self, AegisQError> {
+        Self::try_from(key.to_vec())
+    }
+
+    /// Fresh random 32-byte key
+    pub fn random() -> Self {
+        Self(utils::rng::random_bytes(MIN_KEY_SIZE))
+    }
+
+    /// Key bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl TryFrom<Vec<u8>> for AegisKey {
+    type Error = AegisQError;
+
+    fn try_from(mut key: Vec<u8>) -> Result<Self, AegisQError> {
+        if !(MIN_KEY_SIZE..=MAX_KEY_SIZE).contains(&key.len()) {
+            key.wipe();
+            return Err(AegisQError::InvalidLength("Key must be 32 to 64 bytes"));
+        }
+        Ok(Self(key))
+    }
+}
+
+impl TryFrom<&[u8]> for AegisKey {
+    type Error = AegisQError;
+
+    fn try_from(key: &[u8]) -> Result<Self, AegisQError> {
+        Self::new(key)
+    }
+}
+
+impl Deref for AegisKey {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for AegisKey {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for AegisKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AegisKey([{} bytes])", self.0.len())
+    }
+}
+
+impl Drop for AegisKey {
+    fn drop(&mut self) {
+        self.0.wipe();
+    }
+}
+
+/// Aegis-Q nonce of 16–32 bytes
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AegisNonce(Vec<u8>);
+
+impl AegisNonce {
+    /// Copy a nonce, checking its length
+    pub fn new(nonce: &[u8]) -> Result<Self, AegisQError> {
+        Self::try_from(nonce.to_vec())
+    }
+
+    /// Fresh random nonce of `AUTO_NONCE_SIZE` bytes
+    pub fn random() -> Self {
+        Self(utils::rng::random_bytes(AUTO_NONCE_SIZE))
+    }
+
+    /// Nonce bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl TryFrom<Vec<u8>> for AegisNonce {
+    type Error = AegisQError;
+
+    fn try_from(nonce: Vec<u8>) -> Result<Self, AegisQError> {
+        if !(MIN_NONCE_SIZE..=MAX_NONCE_SIZE).contains(&nonce.len()) {
+            return Err(AegisQError::InvalidLength("Nonce must be 16 to 32 bytes"));
+        }
+        Ok(Self(nonce))
+    }
+}
+
+impl TryFrom<&[u8]> for AegisNonce {
+    type Error = AegisQError;
+
+    fn try_from(nonce: &[u8]) -> Result<Self, AegisQError> {
+        Self::new(nonce)
+    }
+}
+
+impl Deref for AegisNonce {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for AegisNonce {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_length_validation() {
+        assert_eq!(AegisKey::new(b""), Err(AegisQError::InvalidLength("Key must be 32 to 64 bytes")));
+        assert!(AegisKey::new(&[7; 31]).is_err());
+        assert!(AegisKey::new(&[7; 65]).is_err());
+        assert_eq!(AegisKey::new(&[7; 64]).unwrap().len(), 64);
+        assert_eq!(AegisKey::random().len(), MIN_KEY_SIZE);
+
+        assert!(AegisNonce::try_from(&[1u8; 15][..]).is_err());
+        assert!(AegisNonce::try_from(vec![1; 33]).is_err());
+        assert_eq!(AegisNonce::try_from(vec![1; 32]).unwrap().as_bytes(), &[1; 32]);
+        assert_ne!(AegisNonce::random(), AegisNonce::random());
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_types_pass_into_slice_api() {
+        use crate::encrypt::{aegis_q_decrypt, aegis_q_encrypt};
+
+        let key = AegisKey::new(b"typed-key-0123456789abcdef012345").unwrap();
+        let nonce = AegisNonce::random();
+        let ciphertext = aegis_q_encrypt(&key, &nonce, b"typed");
+        assert_eq!(aegis_q_decrypt(&key, &nonce, &ciphertext).unwrap(), b"typed");
+        assert_eq!(format!("{:?}", key), "AegisKey([32 bytes])");
+    }
+}
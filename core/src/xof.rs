@@ -0,0 +1,88 @@
+//! Aegis-Q XOF
+//!
+//! The keystream generator as a public extendable-output function: the key
+//! and nonce go through the full round function and the output is squeezed
+//! from SHAKE-256 over the resulting state. A separate label keeps the
+//! output apart from the encryption keystream, so XOF bytes may be
+//! published (IDs, padding) without exposing ciphertexts under the same key
+//! and nonce.
+//!
+//! The nonce doubles as the purpose label: different purposes under one
+//! key (`b"padding"`, `b"session-id"`, `b"subkey/storage"`) give
+//! independent outputs. `aegis_q_xof(key, nonce, n)` is a prefix of any
+//! longer output for the same inputs.
+
+use sha3::{digest::{ExtendableOutput, Update, XofReader}, Shake256, Shake256Reader};
+
+use crate::encrypt::apply_rounds;
+use crate::state::State;
+
+/// Incremental XOF output
+pub struct AegisQXof {
+    reader: Shake256Reader,
+}
+
+impl AegisQXof {
+    /// Reader for `key` and `nonce`
+    pub fn new(key: &[u8], nonce: &[u8]) -> Self {
+        let mut state = State::from_key(key, nonce);
+        apply_rounds(&mut state, key, nonce);
+
+        let mut hasher = Shake256::default();
+        hasher.update(b"aegis-q-xof");
+        state.absorb(&mut hasher);
+        Self {
+            reader: hasher.finalize_xof(),
+        }
+    }
+
+    /// Fill `out` with the next output bytes
+    pub fn read(&mut self, out: &mut [u8]) {
+        self.reader.read(out);
+    }
+
+    /// Next `len` output bytes
+    pub fn squeeze(&mut self, len: usize) -> Vec<u8> {
+        let mut out = vec![0u8; len];
+        self.read(&mut out);
+        out
+    }
+}
+
+/// First `len` bytes of the XOF output for `key` and `nonce`
+pub fn aegis_q_xof(key: &[u8], nonce: &[u8], len: usize) -> Vec<u8> {
+    AegisQXof::new(key, nonce).squeeze(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encrypt::{aegis_q_encrypt, TAG_SIZE};
+
+    const KEY: &[u8] = b"xof-key-0123456789abcdef01234567";
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_xof_incremental_matches_one_shot() {
+        let full = aegis_q_xof(KEY, b"padding", 300);
+        let mut reader = AegisQXof::new(KEY, b"padding");
+        let mut pieces = reader.squeeze(1);
+        pieces.extend(reader.squeeze(136));
+        pieces.extend(reader.squeeze(163));
+        assert_eq!(pieces, full);
+        assert_eq!(aegis_q_xof(KEY, b"padding", 40), full[..40]);
+
+        assert_ne!(aegis_q_xof(KEY, b"session-id", 32), full[..32]);
+        assert_ne!(aegis_q_xof(b"other-key", b"padding", 32), full[..32]);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "small_params"), ignore)]
+    fn test_xof_is_not_the_keystream() {
+        // Encrypting zeros reveals the keystream; the XOF must not repeat it
+        let zeros = [0u8; 64];
+        let sealed = aegis_q_encrypt(KEY, b"nonce", &zeros);
+        assert_eq!(sealed.len(), 64 + TAG_SIZE);
+        assert_ne!(aegis_q_xof(KEY, b"nonce", 64), sealed[..64]);
+    }
+}
@@ -1,6 +1,6 @@
 //! Known Answer Tests (KAT) for Aegis-Q
 
-use aegis_q_core::{aegis_q_encrypt, aegis_q_decrypt};
+use aegis_q_core::{aegis_q_encrypt, aegis_q_decrypt, kat};
 
 #[test]
 fn kat_test_1() {
@@ -38,3 +38,24 @@ fn kat_test_3() {
     assert_eq!(plaintext, decrypted.as_slice());
 }
 
+#[test]
+#[cfg_attr(not(feature = "small_params"), ignore)]
+fn kat_canonical_vectors() {
+    let vectors = kat::canonical();
+    assert!(!vectors.is_empty(), "no known-answer vectors for {}", kat::PARAMETER_SET);
+    for (i, vector) in vectors.iter().enumerate() {
+        assert_eq!(vector.verify(), Ok(()), "vector {} ({})", i, kat::PARAMETER_SET);
+    }
+}
+
+/// Re-record the shipped vectors after an intentional algorithm change:
+/// `cargo test -p aegis-q-core --features small_params --test kat -- --ignored record_kat_vectors`
+#[test]
+#[ignore]
+fn record_kat_vectors() {
+    let vectors = kat::generate_default();
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("kat")
+        .join(format!("{}.kat", kat::PARAMETER_SET));
+    std::fs::write(path, kat::format(&vectors)).unwrap();
+}
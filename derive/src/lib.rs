@@ -0,0 +1,140 @@
+//! `#[derive(Protected)]`: field-level encryption for application models
+//!
+//! For a struct with named fields, the derive generates a companion
+//! `<Name>Sealed` struct in which every field marked `#[protected]` is
+//! replaced by a `messenger::storage::StorageEntry`, and implements
+//! `messenger::storage::Protected` to convert between the two. Each
+//! protected field is encrypted under its own storage key, derived from the
+//! master key for `<purpose>/<field>`; the purpose defaults to the struct
+//! name and is set with `#[protected(purpose = "...")]` on the struct.
+//!
+//! Other fields are copied as they are, together with their `doc` and
+//! `serde` attributes. The sealed struct derives `Debug`, `Clone` and the
+//! serde traits, so it can be persisted directly.
+//!
+//! Use it through `messenger::storage::Protected` (feature `derive`).
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Error, Fields, LitStr, Meta};
+
+#[proc_macro_derive(Protected, attributes(protected))]
+pub fn derive_protected(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(&input.generics, "Protected does not support generic structs"));
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(named) => &named.named,
+            _ => return Err(Error::new_spanned(&input.ident, "Protected requires named fields")),
+        },
+        _ => return Err(Error::new_spanned(&input.ident, "Protected can only be derived for structs")),
+    };
+
+    let name = &input.ident;
+    let vis = &input.vis;
+    let sealed = format_ident!("{}Sealed", name);
+    let purpose = struct_purpose(input)?;
+    let runtime = quote!(::messenger::storage);
+    let private = quote!(::messenger::storage::__private);
+
+    let mut sealed_fields = Vec::new();
+    let mut seal = Vec::new();
+    let mut open = Vec::new();
+    let mut protected_count = 0;
+
+    for field in fields {
+        let ident = field.ident.as_ref().expect("named field");
+        let field_vis = &field.vis;
+        let ty = &field.ty;
+        if is_protected(field)? {
+            protected_count += 1;
+            let field_purpose = format!("{}/{}", purpose, ident);
+            sealed_fields.push(quote! {
+                #field_vis #ident: #runtime::StorageEntry
+            });
+            seal.push(quote! {
+                #ident: #runtime::seal_field(&self.#ident, master_key, #field_purpose)?
+            });
+            open.push(quote! {
+                #ident: #runtime::open_field(&sealed.#ident, master_key, #field_purpose)?
+            });
+        } else {
+            let kept = field.attrs.iter().filter(|attr| attr.path().is_ident("doc") || attr.path().is_ident("serde"));
+            sealed_fields.push(quote! {
+                #(#kept)*
+                #field_vis #ident: #ty
+            });
+            seal.push(quote! { #ident: ::core::clone::Clone::clone(&self.#ident) });
+            open.push(quote! { #ident: ::core::clone::Clone::clone(&sealed.#ident) });
+        }
+    }
+    if protected_count == 0 {
+        return Err(Error::new_spanned(name, "Protected needs at least one #[protected] field"));
+    }
+
+    let doc = format!("`{}` with its protected fields encrypted", name);
+    let serde_crate = "::messenger::storage::__private::serde";
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(Debug, Clone, #private::serde::Serialize, #private::serde::Deserialize)]
+        #[serde(crate = #serde_crate)]
+        #vis struct #sealed {
+            #(#sealed_fields,)*
+        }
+
+        impl #runtime::Protected for #name {
+            type Sealed = #sealed;
+
+            fn seal(&self, master_key: &#private::RootKey) -> ::core::result::Result<#sealed, #private::AegisQError> {
+                ::core::result::Result::Ok(#sealed {
+                    #(#seal,)*
+                })
+            }
+
+            fn open(sealed: &#sealed, master_key: &#private::RootKey) -> ::core::result::Result<Self, #private::AegisQError> {
+                ::core::result::Result::Ok(Self {
+                    #(#open,)*
+                })
+            }
+        }
+    })
+}
+
+/// `#[protected(purpose = "...")]` on the struct, or the struct name
+fn struct_purpose(input: &DeriveInput) -> syn::Result<String> {
+    let mut purpose = input.ident.to_string();
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("protected")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("purpose") {
+                purpose = meta.value()?.parse::<LitStr>()?.value();
+                Ok(())
+            } else {
+                Err(meta.error("expected `purpose = \"...\"`"))
+            }
+        })?;
+    }
+    if purpose.is_empty() || purpose.contains('/') {
+        return Err(Error::new_spanned(&input.ident, "purpose must be non-empty and contain no '/'"));
+    }
+    Ok(purpose)
+}
+
+/// Whether the field carries a bare `#[protected]`
+fn is_protected(field: &syn::Field) -> syn::Result<bool> {
+    let attrs: Vec<&Attribute> = field.attrs.iter().filter(|attr| attr.path().is_ident("protected")).collect();
+    match attrs.as_slice() {
+        [] => Ok(false),
+        [attr] => match &attr.meta {
+            Meta::Path(_) => Ok(true),
+            _ => Err(Error::new_spanned(attr, "field attribute takes no arguments: #[protected]")),
+        },
+        [_, duplicate, ..] => Err(Error::new_spanned(duplicate, "duplicate #[protected]")),
+    }
+}
@@ -0,0 +1,11 @@
+//! Messenger relay: `chat-relay [addr]` (default 127.0.0.1:7500)
+
+use examples::chat::Relay;
+
+fn main() -> examples::Result<()> {
+    let addr = std::env::args().nth(1).unwrap_or_else(|| "127.0.0.1:7500".to_string());
+    let relay = Relay::bind(&addr)?;
+    println!("relay listening on {}", relay.local_addr()?);
+    relay.run();
+    Ok(())
+}
@@ -0,0 +1,41 @@
+//! Alice and Bob chat through a relay: `chat [relay-addr]`
+//!
+//! Without an address a relay is started in-process.
+
+use examples::chat::{ChatClient, Relay};
+
+fn main() -> examples::Result<()> {
+    let relay_addr = match std::env::args().nth(1) {
+        Some(addr) => addr,
+        None => {
+            let relay = Relay::bind("127.0.0.1:0")?;
+            let addr = relay.local_addr()?.to_string();
+            relay.spawn();
+            addr
+        }
+    };
+
+    let mut alice = ChatClient::connect(&relay_addr, "alice", b"alice seed: correct horse battery staple")?;
+    let mut bob = ChatClient::connect(&relay_addr, "bob", b"bob seed: tr0ub4dor&3 is not a passphrase")?;
+
+    // Fingerprints are exchanged out of band, e.g. by scanning QR codes
+    let (alice_qr, bob_qr) = (alice.fingerprint_qr()?, bob.fingerprint_qr()?);
+    println!("alice fingerprint: {}", alice_qr);
+    println!("bob fingerprint:   {}", bob_qr);
+
+    alice.publish_bundle()?;
+    bob.publish_bundle()?;
+    alice.open_session("bob", &bob_qr)?;
+    bob.open_session("alice", &alice_qr)?;
+
+    alice.send("Hi Bob!")?;
+    alice.send("Are we post-quantum yet?")?;
+    for text in bob.receive()? {
+        println!("bob   <- {}", text);
+    }
+    bob.send("Hi Alice, we are.")?;
+    for text in alice.receive()? {
+        println!("alice <- {}", text);
+    }
+    Ok(())
+}
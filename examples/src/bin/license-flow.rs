@@ -0,0 +1,35 @@
+//! License issuance and activation end to end: `license-flow [storage-dir]`
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use examples::license::{activate, admit, Vendor};
+use utils::keys::{EnvelopeKey, SigningKey};
+
+fn main() -> examples::Result<()> {
+    let storage = std::env::args()
+        .nth(1)
+        .map(Into::into)
+        .unwrap_or_else(|| std::env::temp_dir().join("aegis-q-license-flow"));
+    std::fs::create_dir_all(&storage)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+    let vendor_key = b"vendor signing key (keep in an HSM or the signing agent)";
+    let delivery_key = b"delivery key shared with the customer";
+    let mut vendor = Vendor::new(SigningKey::from_bytes(vendor_key), EnvelopeKey::from_bytes(delivery_key));
+
+    let envelope = vendor.issue("ACME-0001", &["pro", "vpn.multi-hop"], 365 * 86400, now)?;
+    println!("issued envelope, {} bytes", envelope.len());
+
+    let sdk = activate(&envelope, &EnvelopeKey::from_bytes(delivery_key), SigningKey::from_bytes(vendor_key), &storage)?;
+    println!("activated {} (stored in {})", sdk.license().license_id, storage.display());
+    for feature in ["pro", "vpn.multi-hop", "enterprise"] {
+        println!("feature {:<14} {}", feature, if sdk.feature(feature) { "enabled" } else { "disabled" });
+    }
+
+    let entitlements = admit(&vendor.entitlement_policy(), &sdk, now)?;
+    println!(
+        "server admits {}: {:?} B/s, {} tunnels",
+        entitlements.license_id, entitlements.bytes_per_second, entitlements.max_tunnels
+    );
+    Ok(())
+}
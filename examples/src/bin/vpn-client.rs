@@ -0,0 +1,20 @@
+//! Mini VPN client: `vpn-client [addr] [packet...]`
+
+use examples::vpn::VpnClient;
+
+fn main() -> examples::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let addr = args.next().unwrap_or_else(|| "127.0.0.1:7400".to_string());
+    let mut packets: Vec<String> = args.collect();
+    if packets.is_empty() {
+        packets = vec!["ping".to_string(), "hello through the tunnel".to_string()];
+    }
+
+    let mut client = VpnClient::connect(&addr)?;
+    println!("tunnel up, session {}", client.session_id());
+    for packet in packets {
+        let reply = client.exchange(packet.as_bytes())?;
+        println!("sent {:?}, got {:?}", packet, String::from_utf8_lossy(&reply));
+    }
+    Ok(())
+}
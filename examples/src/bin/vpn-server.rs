@@ -0,0 +1,15 @@
+//! Mini VPN server: `vpn-server [addr]` (default 127.0.0.1:7400)
+
+use examples::vpn::VpnServer;
+
+fn main() -> examples::Result<()> {
+    let addr = std::env::args().nth(1).unwrap_or_else(|| "127.0.0.1:7400".to_string());
+    let server = VpnServer::bind(&addr)?;
+    println!("listening on {}", server.local_addr()?);
+    loop {
+        match server.serve_one() {
+            Ok(packets) => println!("client done, {} packets tunnelled", packets),
+            Err(e) => eprintln!("client failed: {}", e),
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! Two-party messenger chat through a mailbox relay
+//!
+//! The relay only stores and forwards opaque blobs per mailbox; it never
+//! sees plaintext. Each party:
+//! 1. derives its identity from an HD seed (`messenger::hd`) and shows the
+//!    identity fingerprint as a QR payload for out-of-band verification,
+//! 2. posts a prekey bundle (`messenger::qr`) to its `bundle/<name>` mailbox,
+//! 3. fetches the peer's bundle, checks the fingerprint and derives one key
+//!    per direction from both key contributions,
+//! 4. posts sealed messages to the peer's `inbox/<name>` mailbox.
+//!
+//! Key agreement is simplified as in `transport::vpn::Handshake::perform`:
+//! contributions travel in the clear, so the relay could derive the keys.
+//! A deployment replaces `agree` with a PQ KEM.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use aegis_q_core::SessionCipher;
+use messenger::hd::HdKeychain;
+use messenger::qr::{PrekeyBundle, QrPayload, FINGERPRINT_SIZE};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256, Sha3_512};
+use utils::kdf::kdf_shake256;
+use utils::rng::random_bytes;
+
+use crate::io::{expect_message, read_message, write_message};
+use crate::Result;
+
+/// Request to the relay
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RelayRequest {
+    /// Append `body` to `mailbox`
+    Post { mailbox: String, body: Vec<u8> },
+    /// Take everything queued in `mailbox`
+    Fetch { mailbox: String },
+}
+
+/// Relay answer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RelayResponse {
+    Posted,
+    Messages(Vec<Vec<u8>>),
+}
+
+type Mailboxes = Arc<Mutex<HashMap<String, VecDeque<Vec<u8>>>>>;
+
+/// Store-and-forward relay
+pub struct Relay {
+    listener: TcpListener,
+    mailboxes: Mailboxes,
+}
+
+impl Relay {
+    /// Listen on `addr` (port 0 picks a free port)
+    pub fn bind(addr: impl ToSocketAddrs) -> Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            mailboxes: Mailboxes::default(),
+        })
+    }
+
+    /// Address the relay listens on
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Serve clients forever, one thread per connection
+    pub fn run(self) {
+        for stream in self.listener.incoming().flatten() {
+            let mailboxes = self.mailboxes.clone();
+            thread::spawn(move || {
+                if let Err(e) = serve(stream, &mailboxes) {
+                    eprintln!("relay: {}", e);
+                }
+            });
+        }
+    }
+
+    /// Run the relay on a background thread
+    pub fn spawn(self) -> thread::JoinHandle<()> {
+        thread::spawn(move || self.run())
+    }
+}
+
+fn serve(mut stream: TcpStream, mailboxes: &Mailboxes) -> Result<()> {
+    while let Some(request) = read_message(&mut stream)? {
+        let response = match serde_json::from_slice(&request)? {
+            RelayRequest::Post { mailbox, body } => {
+                mailboxes.lock().unwrap().entry(mailbox).or_default().push_back(body);
+                RelayResponse::Posted
+            }
+            RelayRequest::Fetch { mailbox } => {
+                let messages = mailboxes.lock().unwrap().remove(&mailbox).unwrap_or_default();
+                RelayResponse::Messages(messages.into())
+            }
+        };
+        write_message(&mut stream, &serde_json::to_vec(&response)?)?;
+    }
+    Ok(())
+}
+
+/// Message as posted to an inbox
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ChatMessage {
+    from: String,
+    counter: u64,
+    ciphertext: Vec<u8>,
+}
+
+/// Ciphers for one conversation, one per direction
+struct Session {
+    peer: String,
+    send: SessionCipher,
+    recv: SessionCipher,
+}
+
+/// One chat participant
+pub struct ChatClient {
+    name: String,
+    relay: TcpStream,
+    fingerprint: [u8; FINGERPRINT_SIZE],
+    contribution: Vec<u8>,
+    session: Option<Session>,
+}
+
+impl ChatClient {
+    /// Connect to the relay as `name`, with the identity derived from `seed`
+    pub fn connect(relay: impl ToSocketAddrs, name: &str, seed: &[u8]) -> Result<Self> {
+        let keychain = HdKeychain::from_seed(seed)?;
+        let mut hasher = Sha3_256::new();
+        hasher.update(b"aegis-q-example-identity");
+        hasher.update(keychain.identity(0).key());
+
+        Ok(Self {
+            name: name.to_string(),
+            relay: TcpStream::connect(relay)?,
+            fingerprint: hasher.finalize().into(),
+            contribution: random_bytes(32),
+            session: None,
+        })
+    }
+
+    /// Identity fingerprint as a scannable QR payload
+    pub fn fingerprint_qr(&self) -> Result<String> {
+        Ok(QrPayload::IdentityFingerprint(self.fingerprint).to_armored()?)
+    }
+
+    /// Publish the prekey bundle for peers to fetch
+    pub fn publish_bundle(&mut self) -> Result<()> {
+        let bundle = QrPayload::PrekeyBundle(PrekeyBundle {
+            identity_key: self.fingerprint.to_vec(),
+            signed_prekey: self.contribution.clone(),
+            prekey_signature: Vec::new(),
+            one_time_prekey: None,
+        });
+        let mailbox = format!("bundle/{}", self.name);
+        self.request(RelayRequest::Post { mailbox, body: bundle.encode()? })?;
+        Ok(())
+    }
+
+    /// Start a conversation with `peer`, whose fingerprint QR was scanned
+    pub fn open_session(&mut self, peer: &str, peer_fingerprint_qr: &str) -> Result<()> {
+        let QrPayload::IdentityFingerprint(expected) = QrPayload::from_armored(peer_fingerprint_qr)? else {
+            return Err("not a fingerprint QR payload".into());
+        };
+        let bundle = self.fetch(&format!("bundle/{}", peer))?.pop().ok_or("peer has not published a bundle")?;
+        let QrPayload::PrekeyBundle(bundle) = &QrPayload::decode(&bundle)? else {
+            return Err("not a prekey bundle".into());
+        };
+        if bundle.identity_key != expected {
+            return Err("peer identity does not match the scanned fingerprint".into());
+        }
+
+        let shared = agree((&self.name, &self.contribution), (peer, &bundle.signed_prekey));
+        let direction_key = |from: &str, to: &str| kdf_shake256(b"aegis-q-example-chat-key", &shared, format!("{}->{}", from, to).as_bytes(), 64);
+        self.session = Some(Session {
+            peer: peer.to_string(),
+            send: SessionCipher::new(&direction_key(&self.name, peer), b""),
+            recv: SessionCipher::new(&direction_key(peer, &self.name), b""),
+        });
+        Ok(())
+    }
+
+    /// Send a text message to the peer
+    pub fn send(&mut self, text: &str) -> Result<()> {
+        let session = self.session.as_mut().ok_or("no open session")?;
+        let (counter, ciphertext) = session.send.seal(text.as_bytes())?;
+        let message = ChatMessage { from: self.name.clone(), counter, ciphertext };
+        let mailbox = format!("inbox/{}", session.peer);
+        self.request(RelayRequest::Post { mailbox, body: serde_json::to_vec(&message)? })?;
+        Ok(())
+    }
+
+    /// Fetch and decrypt messages queued for us
+    pub fn receive(&mut self) -> Result<Vec<String>> {
+        let queued = self.fetch(&format!("inbox/{}", self.name))?;
+        let session = self.session.as_mut().ok_or("no open session")?;
+        let mut texts = Vec::with_capacity(queued.len());
+        for body in queued {
+            let message: ChatMessage = serde_json::from_slice(&body)?;
+            // The relay keeps order, so anything else is a replay or a drop
+            if message.from != session.peer || message.counter != session.recv.next_sequence() {
+                return Err("unexpected message in inbox".into());
+            }
+            let plaintext = session.recv.open(&message.ciphertext)?;
+            texts.push(String::from_utf8(plaintext)?);
+        }
+        Ok(texts)
+    }
+
+    fn fetch(&mut self, mailbox: &str) -> Result<Vec<Vec<u8>>> {
+        match self.request(RelayRequest::Fetch { mailbox: mailbox.to_string() })? {
+            RelayResponse::Messages(messages) => Ok(messages),
+            RelayResponse::Posted => Err("unexpected relay response".into()),
+        }
+    }
+
+    fn request(&mut self, request: RelayRequest) -> Result<RelayResponse> {
+        write_message(&mut self.relay, &serde_json::to_vec(&request)?)?;
+        Ok(serde_json::from_slice(&expect_message(&mut self.relay)?)?)
+    }
+}
+
+/// Shared secret from both contributions, independent of who computes it
+fn agree(ours: (&str, &[u8]), theirs: (&str, &[u8])) -> Vec<u8> {
+    let (first, second) = if ours.0 < theirs.0 { (ours, theirs) } else { (theirs, ours) };
+    let mut hasher = Sha3_512::new();
+    hasher.update(b"aegis-q-example-chat");
+    for (name, contribution) in [first, second] {
+        hasher.update((name.len() as u32).to_le_bytes());
+        hasher.update(name.as_bytes());
+        hasher.update(contribution);
+    }
+    hasher.finalize().to_vec()
+}
@@ -0,0 +1,40 @@
+//! Length-prefixed messages over a byte stream
+//!
+//! Every message is a 4-byte big-endian length followed by the body.
+
+use std::io::{self, Read, Write};
+
+/// Largest accepted message
+pub const MAX_MESSAGE: usize = 1 << 20;
+
+/// Write one message
+pub fn write_message(writer: &mut impl Write, body: &[u8]) -> io::Result<()> {
+    if body.len() > MAX_MESSAGE {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "message too large"));
+    }
+    writer.write_all(&(body.len() as u32).to_be_bytes())?;
+    writer.write_all(body)?;
+    writer.flush()
+}
+
+/// Read one message; `None` when the peer closed the stream
+pub fn read_message(reader: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_MESSAGE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "message too large"));
+    }
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+/// Read one message, treating a closed stream as an error
+pub fn expect_message(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    read_message(reader)?.ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "peer closed the stream"))
+}
@@ -0,0 +1,20 @@
+//! End-to-end examples
+//!
+//! Small but complete applications built on the public APIs, one per
+//! subsystem. Each is a library module (so `tests/` can drive it) plus
+//! binaries under `src/bin`:
+//! - `vpn` — tunnel server and client over TCP (`vpn-server`, `vpn-client`)
+//! - `chat` — two-party messenger chat through a mailbox relay
+//!   (`chat-relay`, `chat`)
+//! - `license` — license issuance, delivery and activation (`license-flow`)
+
+pub mod chat;
+pub mod io;
+pub mod license;
+pub mod vpn;
+
+/// Error type of the examples
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// Result type of the examples
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,64 @@
+//! License issuance, delivery and activation
+//!
+//! 1. The vendor issues a license with `LicenseBuilder` and signs it.
+//! 2. It is delivered inside an encrypted `LicenseEnvelope` (wire bytes).
+//! 3. The customer opens the envelope, stores the license file and
+//!    activates it through the SDK facade, which gates features.
+//! 4. A transport server admits the same license and resolves the
+//!    customer's entitlements.
+
+use std::path::Path;
+
+use licensing::sdk::{LicenseSdk, SdkConfig};
+use licensing::{License, LicenseEnvelope};
+use transport::entitlement::{EntitlementPolicy, Entitlements};
+use utils::keys::{EnvelopeKey, SigningKey};
+
+use crate::Result;
+
+/// Vendor side: keys and issuance
+pub struct Vendor {
+    signing_key: SigningKey,
+    envelope_key: EnvelopeKey,
+}
+
+impl Vendor {
+    /// Vendor with the given signing and delivery keys
+    pub fn new(signing_key: SigningKey, envelope_key: EnvelopeKey) -> Self {
+        Self { signing_key, envelope_key }
+    }
+
+    /// Issue a signed license valid for `validity_secs` and seal it for delivery
+    pub fn issue(&mut self, license_id: &str, features: &[&str], validity_secs: u64, now: u64) -> Result<Vec<u8>> {
+        let license = License::builder(license_id)
+            .features(features.iter().copied())
+            .expiry(now + validity_secs)
+            .build_signed(&mut self.signing_key, now)?;
+        Ok(LicenseEnvelope::create(&license, &self.envelope_key)?.to_bytes()?)
+    }
+
+    /// Server policy admitting this vendor's licenses
+    pub fn entitlement_policy(&self) -> EntitlementPolicy {
+        EntitlementPolicy::new(&self.signing_key)
+            .bandwidth_tier("pro", 100_000_000)
+            .default_bandwidth(Some(1_000_000))
+            .tunnel_limit("pro", 5)
+    }
+}
+
+/// Customer side: open the delivered envelope and activate the license
+///
+/// The license file and the SDK state record are kept in `storage`.
+pub fn activate(envelope: &[u8], envelope_key: &EnvelopeKey, vendor_key: SigningKey, storage: &Path) -> Result<LicenseSdk> {
+    let license = LicenseEnvelope::from_bytes(envelope)?.extract(envelope_key)?;
+    let license_path = storage.join("license.json");
+    std::fs::write(&license_path, serde_json::to_vec_pretty(&license)?)?;
+
+    let config = SdkConfig::new(vendor_key).state_path(storage.join("license.state"));
+    Ok(LicenseSdk::init(license_path.as_path(), config)?)
+}
+
+/// Server side: admit the activated license
+pub fn admit(policy: &EntitlementPolicy, sdk: &LicenseSdk, now: u64) -> Result<Entitlements> {
+    Ok(policy.admit(sdk.license(), now)?)
+}
@@ -0,0 +1,98 @@
+//! Mini VPN: tunnel server and client over TCP
+//!
+//! 1. Client and server exchange 32-byte key contributions and run
+//!    `Handshake::perform` (simplified, as in the library: production would
+//!    use a PQ KEM here).
+//! 2. Each side derives a `VpnSession` from the shared secret for its role,
+//!    so the two directions use different keys.
+//! 3. The client proves it holds the session by presenting the session ID.
+//! 4. Packets travel as encrypted frames; this server echoes them back, as
+//!    a stand-in for forwarding them to the network.
+
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+
+use transport::tls::{session_from_exporter, Role};
+use transport::vpn::{Handshake, VpnSession};
+use utils::rng::random_bytes;
+
+use crate::io::{expect_message, read_message, write_message};
+use crate::Result;
+
+/// Size of each side's handshake contribution
+const CONTRIBUTION_LEN: usize = 32;
+
+/// Tunnel endpoint accepting clients
+pub struct VpnServer {
+    listener: TcpListener,
+}
+
+impl VpnServer {
+    /// Listen on `addr` (port 0 picks a free port)
+    pub fn bind(addr: impl ToSocketAddrs) -> Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+        })
+    }
+
+    /// Address the server listens on
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Accept one client and serve it until it disconnects
+    ///
+    /// Returns the number of packets tunnelled.
+    pub fn serve_one(&self) -> Result<usize> {
+        let (mut stream, _) = self.listener.accept()?;
+        let client_key = expect_message(&mut stream)?;
+        let server_key = random_bytes(CONTRIBUTION_LEN);
+        write_message(&mut stream, &server_key)?;
+
+        let handshake = Handshake::perform(&client_key, &server_key);
+        let mut session = session_from_exporter(&handshake.shared_secret, Role::Server)?;
+        if !session.verify_session_id(&expect_message(&mut stream)?) {
+            return Err("client presented a wrong session ID".into());
+        }
+
+        let mut packets = 0;
+        while let Some(frame) = read_message(&mut stream)? {
+            let packet = session.decrypt_data(&frame)?;
+            write_message(&mut stream, &session.encrypt_data(&packet))?;
+            packets += 1;
+        }
+        Ok(packets)
+    }
+}
+
+/// Tunnel client
+pub struct VpnClient {
+    stream: TcpStream,
+    session: VpnSession,
+}
+
+impl VpnClient {
+    /// Connect and establish the tunnel
+    pub fn connect(addr: impl ToSocketAddrs) -> Result<Self> {
+        let mut stream = TcpStream::connect(addr)?;
+        let client_key = random_bytes(CONTRIBUTION_LEN);
+        write_message(&mut stream, &client_key)?;
+        let server_key = expect_message(&mut stream)?;
+
+        let handshake = Handshake::perform(&client_key, &server_key);
+        let session = session_from_exporter(&handshake.shared_secret, Role::Client)?;
+        write_message(&mut stream, session.session_id().as_bytes())?;
+        Ok(Self { stream, session })
+    }
+
+    /// Session identifier, for logs
+    pub fn session_id(&self) -> String {
+        self.session.session_id().to_hex()
+    }
+
+    /// Send a packet through the tunnel and return the server's reply
+    pub fn exchange(&mut self, packet: &[u8]) -> Result<Vec<u8>> {
+        write_message(&mut self.stream, &self.session.encrypt_data(packet))?;
+        let reply = expect_message(&mut self.stream)?;
+        Ok(self.session.decrypt_data(&reply)?)
+    }
+}
@@ -0,0 +1,88 @@
+//! The example applications, run end to end over loopback
+//!
+//! Full-parameter Aegis-Q is slow, so these run with
+//! `cargo test -p examples --features small_params`.
+
+use std::thread;
+
+use examples::chat::{ChatClient, Relay};
+use examples::license::{activate, admit, Vendor};
+use examples::vpn::{VpnClient, VpnServer};
+use licensing::sdk::SdkError;
+use utils::keys::{EnvelopeKey, SigningKey};
+
+#[test]
+#[cfg_attr(not(feature = "small_params"), ignore)]
+fn vpn_client_server() {
+    let server = VpnServer::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let served = thread::spawn(move || server.serve_one().unwrap());
+
+    let mut client = VpnClient::connect(addr).unwrap();
+    for packet in [&b"ping"[..], b"", &[0xabu8; 1500][..]] {
+        assert_eq!(client.exchange(packet).unwrap(), packet);
+    }
+    drop(client);
+    assert_eq!(served.join().unwrap(), 3);
+}
+
+#[test]
+#[cfg_attr(not(feature = "small_params"), ignore)]
+fn messenger_chat_over_relay() {
+    let relay = Relay::bind("127.0.0.1:0").unwrap();
+    let addr = relay.local_addr().unwrap();
+    relay.spawn();
+
+    let mut alice = ChatClient::connect(addr, "alice", b"alice-seed-0123456789").unwrap();
+    let mut bob = ChatClient::connect(addr, "bob", b"bob-seed-0123456789").unwrap();
+    let mut mallory = ChatClient::connect(addr, "mallory", b"mallory-seed-0123456789").unwrap();
+    let (alice_qr, bob_qr) = (alice.fingerprint_qr().unwrap(), bob.fingerprint_qr().unwrap());
+
+    alice.publish_bundle().unwrap();
+    bob.publish_bundle().unwrap();
+    alice.open_session("bob", &bob_qr).unwrap();
+    bob.open_session("alice", &alice_qr).unwrap();
+
+    alice.send("one").unwrap();
+    alice.send("two").unwrap();
+    assert_eq!(bob.receive().unwrap(), ["one", "two"]);
+    assert!(bob.receive().unwrap().is_empty());
+    bob.send("three").unwrap();
+    assert_eq!(alice.receive().unwrap(), ["three"]);
+
+    // A bundle whose identity does not match the scanned fingerprint is refused
+    mallory.publish_bundle().unwrap();
+    assert!(alice.open_session("mallory", &bob_qr).is_err());
+}
+
+#[test]
+#[cfg_attr(not(feature = "small_params"), ignore)]
+fn license_issuance_and_activation() {
+    let storage = std::env::temp_dir().join(format!("aegis-q-examples-license-{}", std::process::id()));
+    std::fs::create_dir_all(&storage).unwrap();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let mut vendor = Vendor::new(SigningKey::from_bytes(b"vendor-key"), EnvelopeKey::from_bytes(b"delivery-key"));
+    let envelope = vendor.issue("ACME-0001", &["Pro", "vpn.multi-hop"], 86400, now).unwrap();
+
+    let sdk = activate(&envelope, &EnvelopeKey::from_bytes(b"delivery-key"), SigningKey::from_bytes(b"vendor-key"), &storage).unwrap();
+    assert!(sdk.feature("pro"));
+    assert!(sdk.feature("vpn.multi-hop"));
+    assert!(!sdk.feature("enterprise"));
+    assert!(storage.join("license.json").exists());
+    assert!(storage.join("license.state").exists());
+
+    let entitlements = admit(&vendor.entitlement_policy(), &sdk, now).unwrap();
+    assert_eq!(entitlements.bytes_per_second, Some(100_000_000));
+    assert_eq!(entitlements.max_tunnels, 5);
+
+    // Wrong delivery key or vendor key
+    assert!(activate(&envelope, &EnvelopeKey::from_bytes(b"other-key"), SigningKey::from_bytes(b"vendor-key"), &storage).is_err());
+    let err = activate(&envelope, &EnvelopeKey::from_bytes(b"delivery-key"), SigningKey::from_bytes(b"other-key"), &storage)
+        .unwrap_err();
+    assert_eq!(err.downcast_ref::<SdkError>(), Some(&SdkError::InvalidSignature));
+    std::fs::remove_dir_all(&storage).unwrap();
+}
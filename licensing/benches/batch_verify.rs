@@ -0,0 +1,53 @@
+use criterion::{criterion_group, criterion_main, Criterion, BenchmarkId};
+use licensing::License;
+use licensing::signatures::{verify_sequential, BatchVerifier};
+use utils::keys::SigningKey;
+
+fn signed_licenses(count: usize, key: &SigningKey) -> Vec<License> {
+    (0..count)
+        .map(|i| {
+            let mut license = License::new(format!("license-{}", i), vec!["vpn".to_string(), "relay".to_string()], u64::MAX);
+            license.sign(key);
+            license
+        })
+        .collect()
+}
+
+fn bench_batch_verify(c: &mut Criterion) {
+    let key = &SigningKey::from_bytes(b"bench-vendor-key-1234567890");
+    
+    let mut group = c.benchmark_group("license_verify");
+    
+    for count in [100, 1000, 10000].iter() {
+        let licenses = signed_licenses(*count, key);
+        
+        group.bench_with_input(
+            BenchmarkId::new("individual", count),
+            &licenses,
+            |b, licenses| {
+                b.iter(|| licenses.iter().filter(|l| l.verify(key)).count());
+            },
+        );
+        
+        group.bench_with_input(
+            BenchmarkId::new("sequential", count),
+            &licenses,
+            |b, licenses| {
+                b.iter(|| verify_sequential(licenses, key));
+            },
+        );
+        
+        group.bench_with_input(
+            BenchmarkId::new("batch", count),
+            &licenses,
+            |b, licenses| {
+                b.iter(|| BatchVerifier::new().verify(licenses, key));
+            },
+        );
+    }
+    
+    group.finish();
+}
+
+criterion_group!(benches, bench_batch_verify);
+criterion_main!(benches);
@@ -0,0 +1,580 @@
+//! License Signing Agent
+//!
+//! Local daemon holding vendor signing keys (ssh-agent style).
+//! Issuance tooling sends unsigned licenses over a local socket; the agent
+//! applies policy checks, signs, and records every decision in the audit log.
+//! Each connection opens with an exchange of fresh randoms; requests and
+//! responses are then length-prefixed Aegis-Q frames under keys bound to them.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+#[cfg(unix)]
+use std::sync::{Mutex, PoisonError};
+
+use aegis_q_core::{AegisQError, SessionCipher};
+use serde::{Serialize, Deserialize};
+use utils::kdf::kdf_shake256;
+use utils::memory::zeroize;
+use utils::rng::random_bytes;
+use utils::keys::SigningKey;
+
+use crate::audit::AuditLog;
+use crate::builder::Signer;
+use crate::License;
+
+/// Maximum accepted frame size
+pub const MAX_FRAME_SIZE: usize = 1 << 20;
+
+/// Length of the per-connection randoms exchanged at connect
+pub const CONNECTION_RANDOM_SIZE: usize = 32;
+
+/// Nonce prefixes of the two channel directions
+const AGENT_PREFIX: &[u8] = b"agent";
+const CLIENT_PREFIX: &[u8] = b"client";
+
+const NOT_SIGNED: AegisQError = AegisQError::InvalidInput("License not signed");
+const UNKNOWN_KEY: AegisQError = AegisQError::NotFound("Unknown key");
+const RATE_LIMITED: AegisQError = AegisQError::LimitExceeded("Rate limit exceeded");
+const ALREADY_EXPIRED: AegisQError = AegisQError::Policy("License already expired");
+const VALIDITY_TOO_LONG: AegisQError = AegisQError::Policy("Validity exceeds policy");
+const FEATURE_NOT_ALLOWED: AegisQError = AegisQError::Policy("Feature not allowed by policy");
+const SELF_APPROVAL: AegisQError = AegisQError::Policy("Approver key signed the license");
+
+/// Reasons the agent denies a request with
+const DENIALS: [AegisQError; 7] =
+    [NOT_SIGNED, UNKNOWN_KEY, RATE_LIMITED, ALREADY_EXPIRED, VALIDITY_TOO_LONG, FEATURE_NOT_ALLOWED, SELF_APPROVAL];
+
+/// Agent request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AgentRequest {
+    /// List loaded key identifiers
+    ListKeys,
+    /// Sign a license with the named key
+    Sign { key_id: String, license: License },
+    /// Co-sign a signed license with the named issuer key (dual control);
+    /// denied if that key is the one the license is signed with
+    Approve { key_id: String, license: License },
+}
+
+/// Agent response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AgentResponse {
+    Keys(Vec<String>),
+    Signed(License),
+    Denied(String),
+}
+
+/// Signing policy enforced by the agent
+#[derive(Debug, Clone)]
+pub struct AgentPolicy {
+    /// Maximum signatures per rate-limit window
+    pub max_signatures_per_window: u32,
+    /// Rate-limit window length (seconds)
+    pub window_secs: u64,
+    /// Maximum license validity from now (seconds)
+    pub max_validity_secs: u64,
+    /// Features the agent may sign (None = any)
+    pub allowed_features: Option<Vec<String>>,
+}
+
+impl Default for AgentPolicy {
+    fn default() -> Self {
+        Self {
+            max_signatures_per_window: 100,
+            window_secs: 3600,
+            max_validity_secs: 366 * 24 * 3600,
+            allowed_features: None,
+        }
+    }
+}
+
+/// Encrypted agent channel (one per connection)
+///
+/// Both ends share `channel_key` (e.g. read from a 0600 socket key file).
+/// The connection key is derived from it and from randoms both ends pick at
+/// connect, so no two connections share a keystream and frames recorded on
+/// one connection do not open on another. Within a connection each
+/// direction is a `SessionCipher` with its own nonce prefix, so frames
+/// cannot be replayed, reordered or reflected.
+pub struct AgentChannel {
+    send: SessionCipher,
+    recv: SessionCipher,
+}
+
+impl AgentChannel {
+    /// Exchange connection randoms over `stream` and create the endpoint
+    ///
+    /// The client sends its random first and the agent answers with its own.
+    pub fn connect<S: Read + Write>(stream: &mut S, channel_key: &[u8], is_agent: bool) -> Result<Self, AegisQError> {
+        let mut own = random_bytes(CONNECTION_RANDOM_SIZE);
+        let mut peer = vec![0u8; CONNECTION_RANDOM_SIZE];
+        if is_agent {
+            stream.read_exact(&mut peer).map_err(|_| AegisQError::Io("Read failed"))?;
+            stream.write_all(&own).map_err(|_| AegisQError::Io("Write failed"))?;
+        } else {
+            stream.write_all(&own).map_err(|_| AegisQError::Io("Write failed"))?;
+            stream.read_exact(&mut peer).map_err(|_| AegisQError::Io("Read failed"))?;
+        }
+        stream.flush().map_err(|_| AegisQError::Io("Write failed"))?;
+
+        let channel = if is_agent {
+            Self::new(channel_key, is_agent, &peer, &own)
+        } else {
+            Self::new(channel_key, is_agent, &own, &peer)
+        };
+        zeroize(&mut own);
+        zeroize(&mut peer);
+        Ok(channel)
+    }
+
+    /// Create channel endpoint from already exchanged connection randoms
+    pub fn new(channel_key: &[u8], is_agent: bool, client_random: &[u8], agent_random: &[u8]) -> Self {
+        let mut info = Vec::with_capacity(8 + client_random.len() + agent_random.len());
+        for random in [client_random, agent_random] {
+            info.extend_from_slice(&(random.len() as u32).to_le_bytes());
+            info.extend_from_slice(random);
+        }
+        let mut key = kdf_shake256(b"aegis-q-licensing-agent-channel", channel_key, &info, 64);
+        let (send, recv) = if is_agent { (AGENT_PREFIX, CLIENT_PREFIX) } else { (CLIENT_PREFIX, AGENT_PREFIX) };
+        let channel = Self {
+            send: SessionCipher::new(&key, send),
+            recv: SessionCipher::new(&key, recv),
+        };
+        zeroize(&mut key);
+        channel
+    }
+
+    /// Encrypt message into a length-prefixed frame
+    pub fn seal(&mut self, message: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        let (_, ciphertext) = self.send.seal(message)?;
+        let mut frame = (ciphertext.len() as u32).to_le_bytes().to_vec();
+        frame.extend_from_slice(&ciphertext);
+        Ok(frame)
+    }
+
+    /// Decrypt frame body (without length prefix)
+    pub fn open(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        self.recv.open(ciphertext)
+    }
+
+    /// Send message over a stream
+    pub fn send<W: Write>(&mut self, stream: &mut W, message: &[u8]) -> Result<(), AegisQError> {
+        let frame = self.seal(message)?;
+        stream.write_all(&frame).map_err(|_| AegisQError::Io("Write failed"))?;
+        stream.flush().map_err(|_| AegisQError::Io("Write failed"))
+    }
+
+    /// Receive message from a stream; `Ok(None)` on clean EOF
+    pub fn recv<R: Read>(&mut self, stream: &mut R) -> Result<Option<Vec<u8>>, AegisQError> {
+        let mut len_bytes = [0u8; 4];
+        match stream.read_exact(&mut len_bytes) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(_) => return Err(AegisQError::Io("Read failed")),
+        }
+
+        let len = u32::from_le_bytes(len_bytes) as usize;
+        if len > MAX_FRAME_SIZE {
+            return Err(AegisQError::InvalidLength("Frame too large"));
+        }
+
+        let mut ciphertext = vec![0u8; len];
+        stream.read_exact(&mut ciphertext).map_err(|_| AegisQError::Io("Read failed"))?;
+        self.open(&ciphertext).map(Some)
+    }
+}
+
+/// Signing agent holding vendor keys
+pub struct SigningAgent {
+    keys: HashMap<String, SigningKey>,
+    policy: AgentPolicy,
+    audit: AuditLog,
+    window_start: u64,
+    window_count: u32,
+}
+
+impl SigningAgent {
+    /// Create agent with policy
+    pub fn new(policy: AgentPolicy) -> Self {
+        Self {
+            keys: HashMap::new(),
+            policy,
+            audit: AuditLog::new(),
+            window_start: 0,
+            window_count: 0,
+        }
+    }
+
+    /// Load a signing key (wiped when the agent drops it)
+    pub fn add_key(&mut self, key_id: &str, signing_key: SigningKey) {
+        self.keys.insert(key_id.to_string(), signing_key);
+    }
+
+    /// Audit log of all decisions
+    pub fn audit_log(&self) -> &AuditLog {
+        &self.audit
+    }
+
+    /// Handle one request at time `now` (unix seconds)
+    pub fn handle(&mut self, request: AgentRequest, now: u64) -> AgentResponse {
+        match request {
+            AgentRequest::ListKeys => {
+                let mut ids: Vec<String> = self.keys.keys().cloned().collect();
+                ids.sort();
+                AgentResponse::Keys(ids)
+            }
+            AgentRequest::Sign { key_id, mut license } => {
+                if let Err(reason) = self.check_policy(&key_id, &license, now) {
+                    self.audit.record(now, &key_id, "deny", &format!("{}: {}", license.license_id, reason));
+                    return AgentResponse::Denied(reason.to_string());
+                }
+
+                license.sign(&self.keys[&key_id]);
+                self.window_count += 1;
+                self.audit.record(now, &key_id, "sign", &license.license_id);
+                AgentResponse::Signed(license)
+            }
+            AgentRequest::Approve { key_id, mut license } => {
+                let checked = if license.signature.is_empty() {
+                    Err(NOT_SIGNED)
+                } else {
+                    self.check_policy(&key_id, &license, now)
+                };
+                // Four eyes: the signer cannot approve its own license
+                let checked = checked.and_then(|()| match license.verify(&self.keys[&key_id]) {
+                    true => Err(SELF_APPROVAL),
+                    false => Ok(()),
+                });
+                if let Err(reason) = checked {
+                    self.audit.record(now, &key_id, "deny", &format!("{}: {}", license.license_id, reason));
+                    return AgentResponse::Denied(reason.to_string());
+                }
+
+                license.approve(&key_id, &self.keys[&key_id]);
+                self.window_count += 1;
+                self.audit.record(now, &key_id, "approve", &license.license_id);
+                AgentResponse::Signed(license)
+            }
+        }
+    }
+
+    fn check_policy(&mut self, key_id: &str, license: &License, now: u64) -> Result<(), AegisQError> {
+        if !self.keys.contains_key(key_id) {
+            return Err(UNKNOWN_KEY);
+        }
+
+        if now.saturating_sub(self.window_start) >= self.policy.window_secs {
+            self.window_start = now;
+            self.window_count = 0;
+        }
+        if self.window_count >= self.policy.max_signatures_per_window {
+            return Err(RATE_LIMITED);
+        }
+
+        if license.expiry <= now {
+            return Err(ALREADY_EXPIRED);
+        }
+        if license.expiry - now > self.policy.max_validity_secs {
+            return Err(VALIDITY_TOO_LONG);
+        }
+
+        if let Some(allowed) = &self.policy.allowed_features {
+            if license.features.iter().any(|f| !allowed.contains(f)) {
+                return Err(FEATURE_NOT_ALLOWED);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Serve requests on one connection until EOF
+    pub fn serve_connection<S: Read + Write>(&mut self, stream: &mut S, channel_key: &[u8]) -> Result<(), AegisQError> {
+        serve_requests(stream, channel_key, |request| self.handle(request, unix_now()))
+    }
+
+    /// Accept connections on a Unix socket forever
+    ///
+    /// Each connection is served on its own thread, so a client that stalls
+    /// mid-request does not block the others; requests are still handled
+    /// one at a time.
+    #[cfg(unix)]
+    pub fn serve_unix(&mut self, path: &std::path::Path, channel_key: &[u8]) -> Result<(), AegisQError> {
+        let listener = std::os::unix::net::UnixListener::bind(path).map_err(|_| AegisQError::Io("Bind failed"))?;
+        let agent = Mutex::new(self);
+        std::thread::scope(|scope| {
+            for stream in listener.incoming() {
+                let mut stream = stream.map_err(|_| AegisQError::Io("Accept failed"))?;
+                let agent = &agent;
+                scope.spawn(move || {
+                    // A misbehaving client must not take the agent down
+                    let _ = serve_requests(&mut stream, channel_key, |request| {
+                        agent.lock().unwrap_or_else(PoisonError::into_inner).handle(request, unix_now())
+                    });
+                });
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Answer requests on one connection with `handle` until EOF
+fn serve_requests<S: Read + Write>(
+    stream: &mut S,
+    channel_key: &[u8],
+    mut handle: impl FnMut(AgentRequest) -> AgentResponse,
+) -> Result<(), AegisQError> {
+    let mut channel = AgentChannel::connect(stream, channel_key, true)?;
+
+    while let Some(message) = channel.recv(stream)? {
+        let request: AgentRequest = serde_json::from_slice(&message)
+            .map_err(|_| AegisQError::Serialization("Deserialization failed"))?;
+        let response = handle(request);
+        let bytes = serde_json::to_vec(&response).map_err(|_| AegisQError::Serialization("Serialization failed"))?;
+        channel.send(stream, &bytes)?;
+    }
+
+    Ok(())
+}
+
+/// Client side of the agent protocol
+pub struct AgentClient<S: Read + Write> {
+    stream: S,
+    channel: AgentChannel,
+}
+
+impl<S: Read + Write> AgentClient<S> {
+    /// Wrap an established stream and exchange connection randoms
+    pub fn new(mut stream: S, channel_key: &[u8]) -> Result<Self, AegisQError> {
+        let channel = AgentChannel::connect(&mut stream, channel_key, false)?;
+        Ok(Self { stream, channel })
+    }
+
+    fn call(&mut self, request: &AgentRequest) -> Result<AgentResponse, AegisQError> {
+        let bytes = serde_json::to_vec(request).map_err(|_| AegisQError::Serialization("Serialization failed"))?;
+        self.channel.send(&mut self.stream, &bytes)?;
+
+        let message = self.channel.recv(&mut self.stream)?.ok_or(AegisQError::Protocol("Agent closed connection"))?;
+        serde_json::from_slice(&message).map_err(|_| AegisQError::Serialization("Deserialization failed"))
+    }
+
+    /// List key identifiers held by the agent
+    pub fn list_keys(&mut self) -> Result<Vec<String>, AegisQError> {
+        match self.call(&AgentRequest::ListKeys)? {
+            AgentResponse::Keys(ids) => Ok(ids),
+            _ => Err(AegisQError::Protocol("Unexpected response")),
+        }
+    }
+
+    /// Ask the agent to sign a license
+    pub fn sign(&mut self, key_id: &str, license: License) -> Result<License, AegisQError> {
+        let request = AgentRequest::Sign { key_id: key_id.to_string(), license };
+        match self.call(&request)? {
+            AgentResponse::Signed(license) => Ok(license),
+            AgentResponse::Denied(reason) => Err(denial(&reason)),
+            _ => Err(AegisQError::Protocol("Unexpected response")),
+        }
+    }
+
+    /// Ask the agent to co-sign a signed license with an issuer key
+    pub fn approve(&mut self, key_id: &str, license: License) -> Result<License, AegisQError> {
+        let request = AgentRequest::Approve { key_id: key_id.to_string(), license };
+        match self.call(&request)? {
+            AgentResponse::Signed(license) => Ok(license),
+            AgentResponse::Denied(reason) => Err(denial(&reason)),
+            _ => Err(AegisQError::Protocol("Unexpected response")),
+        }
+    }
+
+    /// Signer for `LicenseBuilder::build_signed` backed by one agent key
+    pub fn signer<'a>(&'a mut self, key_id: &str) -> AgentSigner<'a, S> {
+        AgentSigner { client: self, key_id: key_id.to_string() }
+    }
+}
+
+/// Agent key usable as a `builder::Signer`
+pub struct AgentSigner<'a, S: Read + Write> {
+    client: &'a mut AgentClient<S>,
+    key_id: String,
+}
+
+impl<S: Read + Write> Signer for AgentSigner<'_, S> {
+    fn sign_license(&mut self, license: License) -> Result<License, AegisQError> {
+        self.client.sign(&self.key_id, license)
+    }
+}
+
+#[cfg(unix)]
+impl AgentClient<std::os::unix::net::UnixStream> {
+    /// Connect to an agent listening on a Unix socket
+    pub fn connect(path: &std::path::Path, channel_key: &[u8]) -> Result<Self, AegisQError> {
+        let stream = std::os::unix::net::UnixStream::connect(path).map_err(|_| AegisQError::Io("Connect failed"))?;
+        Self::new(stream, channel_key)
+    }
+}
+
+/// Typed error for a `Denied` reason; unknown reasons (e.g. from a newer
+/// agent) become a generic policy denial
+fn denial(reason: &str) -> AegisQError {
+    DENIALS
+        .into_iter()
+        .find(|denial| denial.to_string() == reason)
+        .unwrap_or(AegisQError::Policy("Request denied by agent"))
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn license(expiry: u64) -> License {
+        License::new("lic-1".to_string(), vec!["pro".to_string()], expiry)
+    }
+
+    #[test]
+    fn test_agent_policy() {
+        let policy = AgentPolicy {
+            max_signatures_per_window: 1,
+            allowed_features: Some(vec!["pro".to_string()]),
+            ..AgentPolicy::default()
+        };
+        let mut agent = SigningAgent::new(policy);
+        agent.add_key("vendor", SigningKey::from_bytes(b"signing-key"));
+
+        match agent.handle(AgentRequest::Sign { key_id: "vendor".to_string(), license: license(2000) }, 1000) {
+            AgentResponse::Signed(l) => assert!(l.verify(&SigningKey::from_bytes(b"signing-key"))),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // Rate limit, unknown key, expired
+        for (key_id, expiry) in [("vendor", 2000), ("other", 2000)] {
+            let request = AgentRequest::Sign { key_id: key_id.to_string(), license: license(expiry) };
+            assert!(matches!(agent.handle(request, 1001), AgentResponse::Denied(_)));
+        }
+        let request = AgentRequest::Sign { key_id: "vendor".to_string(), license: license(10) };
+        assert!(matches!(agent.handle(request, 5000), AgentResponse::Denied(_)));
+
+        assert_eq!(agent.audit_log().entries().len(), 4);
+        assert!(agent.audit_log().verify_chain());
+    }
+
+    #[test]
+    fn test_agent_approval() {
+        let mut agent = SigningAgent::new(AgentPolicy::default());
+        agent.add_key("vendor", SigningKey::from_bytes(b"signing-key"));
+        agent.add_key("alice", SigningKey::from_bytes(b"alice-key"));
+
+        let unsigned = AgentRequest::Approve { key_id: "alice".to_string(), license: license(2000) };
+        assert!(matches!(agent.handle(unsigned, 1000), AgentResponse::Denied(_)));
+
+        let AgentResponse::Signed(signed) = agent.handle(AgentRequest::Sign { key_id: "vendor".to_string(), license: license(2000) }, 1000) else {
+            panic!("sign denied");
+        };
+        // The signing key cannot approve, under any key ID
+        agent.add_key("vendor-copy", SigningKey::from_bytes(b"signing-key"));
+        for key_id in ["vendor", "vendor-copy"] {
+            let own = AgentRequest::Approve { key_id: key_id.to_string(), license: signed.clone() };
+            let AgentResponse::Denied(reason) = agent.handle(own, 1000) else {
+                panic!("self-approval accepted");
+            };
+            assert_eq!(denial(&reason), SELF_APPROVAL);
+        }
+
+        let AgentResponse::Signed(approved) = agent.handle(AgentRequest::Approve { key_id: "alice".to_string(), license: signed }, 1000) else {
+            panic!("approve denied");
+        };
+        let policy = crate::approval::ApprovalPolicy::new(0, 0).required_approvals(1).issuer("alice", SigningKey::from_bytes(b"alice-key"));
+        assert_eq!(approved.verify_chain(&SigningKey::from_bytes(b"signing-key"), &policy, 1000), Ok(()));
+        assert_eq!(agent.audit_log().entries().last().unwrap().action, "approve");
+    }
+
+    #[test]
+    fn test_agent_channel_is_bound_to_connection() {
+        let channel_key = b"agent-channel-key";
+        let connect = || {
+            let client_random = random_bytes(CONNECTION_RANDOM_SIZE);
+            let agent_random = random_bytes(CONNECTION_RANDOM_SIZE);
+            (
+                AgentChannel::new(channel_key, false, &client_random, &agent_random),
+                AgentChannel::new(channel_key, true, &client_random, &agent_random),
+            )
+        };
+        let (mut client_1, mut agent_1) = connect();
+        let (mut client_2, mut agent_2) = connect();
+
+        let frame_1 = client_1.seal(b"sign request").unwrap();
+        let frame_2 = client_2.seal(b"sign request").unwrap();
+        assert_ne!(frame_1, frame_2);
+
+        // A frame recorded on connection 1 does not open on connection 2
+        assert!(agent_2.open(&frame_1[4..]).is_err());
+        assert_eq!(agent_1.open(&frame_1[4..]).unwrap(), b"sign request");
+        assert_eq!(agent_2.open(&frame_2[4..]).unwrap(), b"sign request");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_agent_over_unix_socket() {
+        let (mut agent_end, client_end) = std::os::unix::net::UnixStream::pair().unwrap();
+        let channel_key = b"agent-channel-key";
+
+        let server = std::thread::spawn(move || {
+            let mut agent = SigningAgent::new(AgentPolicy::default());
+            agent.add_key("vendor", SigningKey::from_bytes(b"signing-key"));
+            agent.serve_connection(&mut agent_end, channel_key).unwrap();
+        });
+
+        let mut client = AgentClient::new(client_end, channel_key).unwrap();
+        assert_eq!(client.list_keys().unwrap(), vec!["vendor".to_string()]);
+
+        let signed = client.sign("vendor", license(unix_now() + 3600)).unwrap();
+        assert!(signed.verify(&SigningKey::from_bytes(b"signing-key")));
+        assert_eq!(client.sign("other", license(unix_now() + 3600)).unwrap_err(), UNKNOWN_KEY);
+        assert_eq!(client.approve("vendor", license(unix_now() + 3600)).unwrap_err(), NOT_SIGNED);
+
+        let now = unix_now();
+        let built = License::builder("lic-2")
+            .feature("Pro")
+            .expiry(now + 3600)
+            .build_signed(&mut client.signer("vendor"), now)
+            .unwrap();
+        assert_eq!(built.features, vec!["pro".to_string()]);
+        assert!(built.verify(&SigningKey::from_bytes(b"signing-key")));
+
+        drop(client);
+        server.join().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_agent_serves_connections_concurrently() {
+        let path = std::env::temp_dir().join(format!("aegisq-agent-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let channel_key = b"agent-channel-key";
+
+        let server_path = path.clone();
+        std::thread::spawn(move || {
+            let mut agent = SigningAgent::new(AgentPolicy::default());
+            agent.add_key("vendor", SigningKey::from_bytes(b"signing-key"));
+            let _ = agent.serve_unix(&server_path, channel_key);
+        });
+        let stalled = loop {
+            if let Ok(stream) = std::os::unix::net::UnixStream::connect(&path) {
+                break stream;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        };
+
+        // The stalled connection never sends its random; others are served
+        let mut client = AgentClient::connect(&path, channel_key).unwrap();
+        assert_eq!(client.list_keys().unwrap(), vec!["vendor".to_string()]);
+
+        drop(stalled);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,245 @@
+//! Dual control (four-eyes) for high-value licenses
+//!
+//! Licenses above the policy thresholds (seat count or remaining validity)
+//! need co-signatures from two distinct issuer keys on top of the vendor
+//! signature, so an issuer holding only their own key can not mint a large
+//! license alone. Approvals form
+//! a chain: each one signs the license contents together with the previous
+//! link (the vendor signature for the first approval), so approvals cannot
+//! be reordered, dropped from the middle or moved to another license.
+//! `License::verify_chain` checks the vendor signature, every link, and the
+//! number of distinct approvers the policy demands.
+//!
+//! Validity is measured from the verification time, so a license issued
+//! without approvals for longer than the threshold is rejected until its
+//! remaining validity falls below it.
+//!
+//! # Trust boundary
+//!
+//! Approvals are `aegis_q_mac` tags, a symmetric MAC, so checking one takes
+//! the issuer's signing key. An `ApprovalPolicy` holds every issuer key
+//! and the vendor key, and whoever holds the policy can produce a complete
+//! approval chain alone. Dual control therefore only separates the
+//! issuers from each other: the verifier (the licensing service or its
+//! operators) is trusted, and no policy may be deployed where a single
+//! insider could read it, including to clients. Per-issuer asymmetric
+//! signatures would remove that; the license format does not have them.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use aegis_q_core::{aegis_q_mac, aegis_q_mac_verify};
+use serde::{Deserialize, Serialize};
+use utils::keys::{SigningKey, TypedKey};
+
+use crate::License;
+
+/// Distinct issuer approvals required above the thresholds
+pub const DEFAULT_REQUIRED_APPROVALS: usize = 2;
+
+/// Issuer co-signature on a license
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Approval {
+    /// Issuer key identifier
+    pub issuer: String,
+    pub signature: Vec<u8>,
+}
+
+/// Typed approval failure
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApprovalError {
+    /// Vendor signature does not verify
+    InvalidSignature,
+    /// Approval by an issuer the policy does not know
+    UnknownIssuer(String),
+    /// Approval at this position in the chain does not verify
+    InvalidApproval { index: usize },
+    /// Issuer (or an issuer sharing its key) approved twice
+    DuplicateIssuer(String),
+    /// Too few distinct approvals for a high-value license
+    ApprovalsRequired { required: usize, present: usize },
+}
+
+impl fmt::Display for ApprovalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApprovalError::InvalidSignature => write!(f, "license signature invalid"),
+            ApprovalError::UnknownIssuer(issuer) => write!(f, "unknown issuer: {}", issuer),
+            ApprovalError::InvalidApproval { index } => write!(f, "approval {} invalid", index),
+            ApprovalError::DuplicateIssuer(issuer) => write!(f, "duplicate approval by {}", issuer),
+            ApprovalError::ApprovalsRequired { required, present } => {
+                write!(f, "{} distinct approvals required, {} present", required, present)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ApprovalError {}
+
+/// Thresholds above which a license needs dual control, and the issuer keys
+///
+/// Holds every issuer's signing key: anyone with the policy can approve as
+/// any issuer (see the module's trust boundary).
+#[derive(Debug, Clone)]
+pub struct ApprovalPolicy {
+    max_seats: u32,
+    max_validity_secs: u64,
+    required_approvals: usize,
+    issuers: HashMap<String, SigningKey>,
+}
+
+impl ApprovalPolicy {
+    /// Licenses with more than `max_seats` seats or more than
+    /// `max_validity_secs` of remaining validity need approval
+    pub fn new(max_seats: u32, max_validity_secs: u64) -> Self {
+        Self {
+            max_seats,
+            max_validity_secs,
+            required_approvals: DEFAULT_REQUIRED_APPROVALS,
+            issuers: HashMap::new(),
+        }
+    }
+
+    /// Register an issuer key
+    pub fn issuer(mut self, issuer: &str, key: SigningKey) -> Self {
+        self.issuers.insert(issuer.to_string(), key);
+        self
+    }
+
+    /// Distinct approvals required above the thresholds
+    pub fn required_approvals(mut self, count: usize) -> Self {
+        self.required_approvals = count;
+        self
+    }
+
+    /// Whether `license` is high-value at time `now`
+    pub fn requires_approval(&self, license: &License, now: u64) -> bool {
+        license.seats.is_some_and(|seats| seats > self.max_seats)
+            || license.expiry.saturating_sub(now) > self.max_validity_secs
+    }
+}
+
+/// Domain of issuer approvals
+const APPROVE_DOMAIN: &[u8] = b"aegis-q-license-approve";
+
+impl License {
+    /// Append an issuer approval to the chain
+    ///
+    /// Sign the license first: the chain starts at the vendor signature.
+    pub fn approve(&mut self, issuer: &str, key: &SigningKey) {
+        let previous = self.approvals.last().map_or(&self.signature, |a| &a.signature);
+        let signature = aegis_q_mac(key.as_bytes(), &self.signed_bytes(APPROVE_DOMAIN, issuer, previous)).to_vec();
+        self.approvals.push(Approval {
+            issuer: issuer.to_string(),
+            signature,
+        });
+    }
+
+    /// Verify the vendor signature and the approval chain against `policy`
+    pub fn verify_chain(&self, signing_key: &SigningKey, policy: &ApprovalPolicy, now: u64) -> Result<(), ApprovalError> {
+        if !self.verify(signing_key) {
+            return Err(ApprovalError::InvalidSignature);
+        }
+
+        let mut previous = &self.signature;
+        let mut approvers: Vec<&SigningKey> = Vec::new();
+        for (index, approval) in self.approvals.iter().enumerate() {
+            let key = policy
+                .issuers
+                .get(&approval.issuer)
+                .ok_or_else(|| ApprovalError::UnknownIssuer(approval.issuer.clone()))?;
+            let signed = self.signed_bytes(APPROVE_DOMAIN, &approval.issuer, previous);
+            if aegis_q_mac_verify(key.as_bytes(), &signed, &approval.signature).is_err() {
+                return Err(ApprovalError::InvalidApproval { index });
+            }
+            if approvers.contains(&key) {
+                return Err(ApprovalError::DuplicateIssuer(approval.issuer.clone()));
+            }
+            approvers.push(key);
+            previous = &approval.signature;
+        }
+
+        if policy.requires_approval(self, now) && approvers.len() < policy.required_approvals {
+            return Err(ApprovalError::ApprovalsRequired {
+                required: policy.required_approvals,
+                present: approvers.len(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> (SigningKey, ApprovalPolicy) {
+        let policy = ApprovalPolicy::new(100, 365 * 86400)
+            .issuer("alice", SigningKey::from_bytes(b"alice-key"))
+            .issuer("bob", SigningKey::from_bytes(b"bob-key"))
+            .issuer("bob-alias", SigningKey::from_bytes(b"bob-key"));
+        (SigningKey::from_bytes(b"vendor-key"), policy)
+    }
+
+    fn license(seats: u32, vendor: &SigningKey) -> License {
+        let mut license = License::new("lic-1".to_string(), vec!["pro".to_string()], 86400);
+        license.seats = Some(seats);
+        license.sign(vendor);
+        license
+    }
+
+    #[test]
+    fn test_high_value_needs_two_distinct_approvals() {
+        let (vendor, policy) = setup();
+        assert_eq!(license(10, &vendor).verify_chain(&vendor, &policy, 0), Ok(()));
+
+        let mut big = license(500, &vendor);
+        assert_eq!(
+            big.verify_chain(&vendor, &policy, 0),
+            Err(ApprovalError::ApprovalsRequired { required: 2, present: 0 })
+        );
+        big.approve("alice", &SigningKey::from_bytes(b"alice-key"));
+        assert_eq!(
+            big.verify_chain(&vendor, &policy, 0),
+            Err(ApprovalError::ApprovalsRequired { required: 2, present: 1 })
+        );
+
+        let mut same_key = big.clone();
+        same_key.approve("alice", &SigningKey::from_bytes(b"alice-key"));
+        assert_eq!(same_key.verify_chain(&vendor, &policy, 0), Err(ApprovalError::DuplicateIssuer("alice".into())));
+
+        big.approve("bob", &SigningKey::from_bytes(b"bob-key"));
+        assert_eq!(big.verify_chain(&vendor, &policy, 0), Ok(()));
+        let mut aliased = big.clone();
+        aliased.approve("bob-alias", &SigningKey::from_bytes(b"bob-key"));
+        assert_eq!(aliased.verify_chain(&vendor, &policy, 0), Err(ApprovalError::DuplicateIssuer("bob-alias".into())));
+    }
+
+    #[test]
+    fn test_chain_binds_order_and_contents() {
+        let (vendor, policy) = setup();
+        let mut license = license(500, &vendor);
+        license.approve("alice", &SigningKey::from_bytes(b"alice-key"));
+        license.approve("bob", &SigningKey::from_bytes(b"bob-key"));
+
+        let mut reordered = license.clone();
+        reordered.approvals.swap(0, 1);
+        assert_eq!(reordered.verify_chain(&vendor, &policy, 0), Err(ApprovalError::InvalidApproval { index: 0 }));
+
+        // Approvals cannot be lifted onto a re-signed license with more seats
+        let mut inflated = license.clone();
+        inflated.seats = Some(5000);
+        inflated.sign(&vendor);
+        assert_eq!(inflated.verify_chain(&vendor, &policy, 0), Err(ApprovalError::InvalidApproval { index: 0 }));
+
+        let mut forged = license.clone();
+        forged.approvals[1].issuer = "mallory".into();
+        assert_eq!(forged.verify_chain(&vendor, &policy, 0), Err(ApprovalError::UnknownIssuer("mallory".into())));
+
+        // Long validity alone also triggers dual control
+        let mut long = License::new("lic-2".to_string(), vec!["pro".to_string()], 2 * 365 * 86400);
+        long.sign(&vendor);
+        assert!(policy.requires_approval(&long, 0));
+        assert!(!policy.requires_approval(&long, 365 * 86400 + 1));
+    }
+}
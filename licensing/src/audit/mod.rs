@@ -0,0 +1,228 @@
+//! Audit Log
+//!
+//! Append-only, hash-chained record of key and license operations.
+//! Each entry commits to its predecessor, so truncation or edits in the
+//! middle of the log are detected by `verify_chain`. Snapshots can also be
+//! compared through a Merkle tree over the entry hashes (`merkle_tree`),
+//! which yields compact inclusion and consistency proofs for auditors.
+//!
+//! `EncryptedAuditLog` keeps the log confidential at rest with the
+//! incremental log AEAD (`aegis_q_core::log`): entries are appended one
+//! at a time and a reader holding a trusted `LogCursor` detects edits,
+//! reordering and truncation.
+
+use aegis_q_core::encrypt::TAG_SIZE;
+use aegis_q_core::{AegisQError, AegisQLogDecryptor, AegisQLogEncryptor, LogCursor};
+use serde::{Serialize, Deserialize};
+use sha3::{Digest, Sha3_256};
+use utils::keys::{EncryptionKey, TypedKey};
+use utils::merkle::{self, MerkleTree};
+
+/// Single audit entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: u64,
+    pub actor: String,
+    pub action: String,
+    pub detail: String,
+    /// Hash of the previous entry (zeros for the first one)
+    pub prev_hash: Vec<u8>,
+}
+
+impl AuditEntry {
+    /// Hash of this entry, including the chain link
+    pub fn hash(&self) -> Vec<u8> {
+        let mut hasher = Sha3_256::new();
+        hasher.update(b"aegis-q-audit-entry");
+        hasher.update(self.timestamp.to_le_bytes());
+        for field in [&self.actor, &self.action, &self.detail] {
+            hasher.update((field.len() as u32).to_le_bytes());
+            hasher.update(field.as_bytes());
+        }
+        hasher.update(&self.prev_hash);
+        hasher.finalize().to_vec()
+    }
+}
+
+/// Hash-chained audit log
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditLog {
+    entries: Vec<AuditEntry>,
+}
+
+impl AuditLog {
+    /// Create empty log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append an entry
+    pub fn record(&mut self, timestamp: u64, actor: &str, action: &str, detail: &str) {
+        let prev_hash = self
+            .entries
+            .last()
+            .map(|e| e.hash())
+            .unwrap_or_else(|| vec![0u8; 32]);
+
+        self.entries.push(AuditEntry {
+            timestamp,
+            actor: actor.to_string(),
+            action: action.to_string(),
+            detail: detail.to_string(),
+            prev_hash,
+        });
+    }
+
+    /// All entries, oldest first
+    pub fn entries(&self) -> &[AuditEntry] {
+        &self.entries
+    }
+
+    /// Hash of the latest entry (commits to the whole log)
+    pub fn head(&self) -> Option<Vec<u8>> {
+        self.entries.last().map(|e| e.hash())
+    }
+
+    /// Merkle tree over entry hashes, oldest first
+    pub fn merkle_tree(&self) -> MerkleTree {
+        MerkleTree::from_leaf_hashes(self.entries.iter().map(|e| merkle::leaf_hash(&e.hash())).collect())
+    }
+
+    /// Verify the hash chain
+    pub fn verify_chain(&self) -> bool {
+        let mut expected = vec![0u8; 32];
+        for entry in &self.entries {
+            if entry.prev_hash != expected {
+                return false;
+            }
+            expected = entry.hash();
+        }
+        true
+    }
+}
+
+/// Audit log encrypted entry by entry
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncryptedAuditLog {
+    log_id: Vec<u8>,
+    records: Vec<Vec<u8>>,
+}
+
+impl EncryptedAuditLog {
+    /// Empty log; `log_id` must be unique under the key
+    pub fn new(log_id: &[u8]) -> Self {
+        Self {
+            log_id: log_id.to_vec(),
+            records: Vec::new(),
+        }
+    }
+
+    /// Encrypt every entry of `log`
+    pub fn seal(log: &AuditLog, key: &EncryptionKey, log_id: &[u8]) -> Result<Self, AegisQError> {
+        let mut sealed = Self::new(log_id);
+        for entry in log.entries() {
+            sealed.append(key, entry)?;
+        }
+        Ok(sealed)
+    }
+
+    /// Encrypt and append one entry
+    pub fn append(&mut self, key: &EncryptionKey, entry: &AuditEntry) -> Result<(), AegisQError> {
+        let encoded = serde_json::to_vec(entry).map_err(|_| AegisQError::Serialization("Failed to encode audit entry"))?;
+        let mut encryptor = AegisQLogEncryptor::resume(key.as_bytes(), &self.log_id, self.cursor());
+        self.records.push(encryptor.append(&encoded)?);
+        Ok(())
+    }
+
+    /// Number of entries
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Whether the log is empty
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Position after the last entry; keep it (e.g. signed) to check the log later
+    pub fn cursor(&self) -> LogCursor {
+        let mut cursor = LogCursor::genesis();
+        if let Some(last) = self.records.last() {
+            cursor.index = self.records.len() as u64;
+            cursor.head.copy_from_slice(&last[last.len() - TAG_SIZE..]);
+        }
+        cursor
+    }
+
+    /// Decrypt the log and check it against a trusted cursor
+    pub fn open(&self, key: &EncryptionKey, trusted: &LogCursor) -> Result<AuditLog, AegisQError> {
+        let mut decryptor = AegisQLogDecryptor::new(key.as_bytes(), &self.log_id);
+        let mut entries = Vec::with_capacity(self.records.len());
+        for record in &self.records {
+            let encoded = decryptor.open(record)?;
+            entries.push(serde_json::from_slice(&encoded).map_err(|_| AegisQError::Serialization("Failed to decode audit entry"))?);
+        }
+        decryptor.verify_head(trusted)?;
+
+        let log = AuditLog { entries };
+        if !log.verify_chain() {
+            return Err(AegisQError::Protocol("Audit hash chain broken"));
+        }
+        Ok(log)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_chain() {
+        let mut log = AuditLog::new();
+        log.record(1, "agent", "sign", "license-1");
+        log.record(2, "agent", "deny", "license-2");
+        assert!(log.verify_chain());
+
+        let mut tampered = log.clone();
+        tampered.entries[0].detail = "license-x".to_string();
+        assert!(!tampered.verify_chain());
+    }
+
+    #[test]
+    fn test_audit_merkle_snapshots() {
+        let mut log = AuditLog::new();
+        log.record(1, "agent", "sign", "license-1");
+        log.record(2, "agent", "sign", "license-2");
+        let snapshot = log.merkle_tree();
+
+        log.record(3, "agent", "deny", "license-3");
+        let tree = log.merkle_tree();
+        let proof = tree.consistency_proof(snapshot.len(), tree.len()).unwrap();
+        assert!(merkle::verify_consistency(snapshot.len(), &snapshot.root(), tree.len(), &tree.root(), &proof));
+
+        let entry = merkle::leaf_hash(&log.entries()[2].hash());
+        assert!(tree.inclusion_proof(2, tree.len()).unwrap().verify(&entry, &tree.root()));
+    }
+
+    #[test]
+    fn test_encrypted_audit_log() {
+        let key = EncryptionKey::from_bytes(b"audit-log-key-0123456789abcdef01");
+        let mut log = AuditLog::new();
+        log.record(1, "agent", "sign", "license-1");
+        let mut sealed = EncryptedAuditLog::seal(&log, &key, b"agent-audit").unwrap();
+
+        log.record(2, "agent", "deny", "license-2");
+        sealed.append(&key, log.entries().last().unwrap()).unwrap();
+        let trusted = sealed.cursor();
+        assert_eq!(sealed.open(&key, &trusted).unwrap().entries(), log.entries());
+
+        // Dropping the newest entry is caught by the trusted cursor
+        let mut truncated = sealed.clone();
+        truncated.records.pop();
+        assert_eq!(truncated.open(&key, &trusted).unwrap_err(), AegisQError::InvalidLength("Log truncated"));
+
+        let mut reordered = sealed.clone();
+        reordered.records.swap(0, 1);
+        assert_eq!(reordered.open(&key, &trusted).unwrap_err(), AegisQError::AuthenticationFailed);
+    }
+}
@@ -0,0 +1,82 @@
+//! Vendor root key ceremony CLI
+//!
+//! ```text
+//! aegis-q-ceremony split <threshold> <custodian>...
+//! aegis-q-ceremony combine <fingerprint> <custodian>=<share>...
+//! ```
+//!
+//! `split` prints one printable share per custodian and the root key
+//! fingerprint; `combine` verifies that the shares reconstruct the root key
+//! with that fingerprint. The audit
+//! transcript is written to stderr as JSON.
+
+use std::process::ExitCode;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use aegis_q_core::AegisQError;
+use licensing::audit::AuditLog;
+use licensing::ceremony::{
+    fingerprint, generate_root_key, reconstruct_root_key, split_root_key, CustodianShare,
+};
+
+fn usage() -> ExitCode {
+    eprintln!("usage: aegis-q-ceremony split <threshold> <custodian>...");
+    eprintln!("       aegis-q-ceremony combine <fingerprint> <custodian>=<share>...");
+    ExitCode::FAILURE
+}
+
+fn run(args: &[String], audit: &mut AuditLog, now: u64) -> Result<(), AegisQError> {
+    match args.first().map(String::as_str) {
+        Some("split") if args.len() >= 3 => {
+            let threshold: u8 = args[1].parse().map_err(|_| AegisQError::InvalidInput("Invalid threshold"))?;
+            let custodians: Vec<&str> = args[2..].iter().map(String::as_str).collect();
+
+            let root = generate_root_key();
+            let shares = split_root_key(&root, &custodians, threshold, audit, now)?;
+
+            println!("root key fingerprint: {}", fingerprint(root.as_slice()));
+            for share in &shares {
+                println!("{}: {}", share.custodian, share.to_printable());
+            }
+            Ok(())
+        }
+        Some("combine") if args.len() >= 3 => {
+            let shares = args[2..]
+                .iter()
+                .map(|arg| {
+                    let (custodian, text) = arg.split_once('=').ok_or(AegisQError::Serialization("Expected <custodian>=<share>"))?;
+                    CustodianShare::from_printable(custodian, text)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let root = reconstruct_root_key(&shares, &args[1], audit, now)?;
+            println!("root key fingerprint: {}", fingerprint(root.as_slice()));
+            Ok(())
+        }
+        _ => Err(AegisQError::InvalidInput("usage")),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let mut audit = AuditLog::new();
+    let result = run(&args, &mut audit, now);
+
+    if let Ok(json) = serde_json::to_string(&audit) {
+        eprintln!("{}", json);
+    }
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(AegisQError::InvalidInput("usage")) => usage(),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,320 @@
+//! Break-glass access to protected configuration
+//!
+//! A `ProtectedConfig` can carry a second copy of its key, wrapped under an
+//! offline recovery key and bound to a config ID. The recovery key alone is
+//! not accepted: `BreakGlass::unlock` also takes a `BreakGlassToken` that
+//! the break-glass authority signs for one config, one operator and a short
+//! time window. The token is checked, refused if it was already used, and
+//! the access is written to the audit log before anything is decrypted.
+//! Denied attempts are logged as well.
+//!
+//! The normal path (`ProtectedConfig::retrieve` with the config key) is
+//! unchanged, and removing the recovery wrap turns break-glass off again.
+//! Used tokens are remembered through the audit log, so a `BreakGlass`
+//! restored with `resume` keeps refusing them. The log records the token ID
+//! as `token=<id>`, so IDs with whitespace or `=` are refused when issued
+//! and when presented; they could not be read back unambiguously.
+
+use std::collections::HashSet;
+
+use aegis_q_core::{AegisQError, SignatureId, aegis_q_decrypt, aegis_q_encrypt_auto, aegis_q_mac, aegis_q_mac_verify};
+use serde::{Serialize, Deserialize};
+use utils::kdf::kdf_shake256;
+use utils::keys::{EncryptionKey, SigningKey, TypedKey};
+use utils::memory::zeroize;
+
+use crate::audit::AuditLog;
+use crate::ProtectedConfig;
+
+/// Longest validity window of a token
+pub const MAX_TOKEN_TTL_SECS: u64 = 4 * 3600;
+
+/// Audit action of a successful unlock
+pub const UNLOCK_ACTION: &str = "break-glass-unlock";
+
+/// Audit action of a refused unlock
+pub const DENIED_ACTION: &str = "break-glass-denied";
+
+/// Config key wrapped under the recovery key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecoveryWrap {
+    pub config_id: String,
+    nonce: Vec<u8>,
+    wrapped_key: Vec<u8>,
+}
+
+/// Recovery key specialised to one config
+fn wrapping_key(recovery_key: &EncryptionKey, config_id: &str) -> EncryptionKey {
+    EncryptionKey::from_bytes(&kdf_shake256(b"aegis-q-break-glass-wrap", recovery_key.as_bytes(), config_id.as_bytes(), 32))
+}
+
+impl ProtectedConfig {
+    /// Allow break-glass access with `recovery_key`
+    ///
+    /// `config_key` must open the config. Replaces any earlier recovery wrap.
+    pub fn enable_recovery(&mut self, config_key: &EncryptionKey, recovery_key: &EncryptionKey, config_id: &str) -> Result<(), AegisQError> {
+        self.retrieve(config_key)?;
+        let (nonce, wrapped_key) = aegis_q_encrypt_auto(wrapping_key(recovery_key, config_id).as_bytes(), config_key.as_bytes());
+        self.recovery = Some(RecoveryWrap {
+            config_id: config_id.to_string(),
+            nonce,
+            wrapped_key,
+        });
+        Ok(())
+    }
+
+    /// Remove the recovery wrap
+    pub fn disable_recovery(&mut self) {
+        self.recovery = None;
+    }
+
+    /// Recovery wrap, if break-glass access is enabled
+    pub fn recovery(&self) -> Option<&RecoveryWrap> {
+        self.recovery.as_ref()
+    }
+}
+
+/// Time-limited authorization for one break-glass unlock
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BreakGlassToken {
+    pub token_id: String,
+    pub config_id: String,
+    pub operator: String,
+    pub reason: String,
+    pub not_before: u64,
+    pub not_after: u64,
+    pub signature: Vec<u8>,
+}
+
+impl BreakGlassToken {
+    /// Registry ID of the token signature
+    pub const SIGNATURE_ALGORITHM: SignatureId = SignatureId::AegisQMac;
+
+    /// Issue a token valid from `not_before` for `ttl_secs`
+    pub fn issue(
+        authority_key: &SigningKey,
+        token_id: &str,
+        config_id: &str,
+        operator: &str,
+        reason: &str,
+        not_before: u64,
+        ttl_secs: u64,
+    ) -> Result<Self, AegisQError> {
+        if ttl_secs == 0 || ttl_secs > MAX_TOKEN_TTL_SECS {
+            return Err(AegisQError::InvalidInput("Break-glass token lifetime out of range"));
+        }
+        if !valid_token_id(token_id) {
+            return Err(AegisQError::InvalidInput("Invalid break-glass token ID"));
+        }
+        let mut token = Self {
+            token_id: token_id.to_string(),
+            config_id: config_id.to_string(),
+            operator: operator.to_string(),
+            reason: reason.to_string(),
+            not_before,
+            not_after: not_before.saturating_add(ttl_secs),
+            signature: Vec::new(),
+        };
+        token.signature = aegis_q_mac(authority_key.as_bytes(), &token.signed_bytes()).to_vec();
+        Ok(token)
+    }
+
+    /// Check the signature and that `now` is inside the window
+    pub fn verify(&self, authority_key: &SigningKey, now: u64) -> Result<(), AegisQError> {
+        aegis_q_mac_verify(authority_key.as_bytes(), &self.signed_bytes(), &self.signature)?;
+        if !valid_token_id(&self.token_id) {
+            return Err(AegisQError::InvalidInput("Invalid break-glass token ID"));
+        }
+        if self.not_after.saturating_sub(self.not_before) > MAX_TOKEN_TTL_SECS {
+            return Err(AegisQError::Policy("Break-glass token lifetime exceeds maximum"));
+        }
+        if now < self.not_before || now >= self.not_after {
+            return Err(AegisQError::Policy("Break-glass token outside its validity window"));
+        }
+        Ok(())
+    }
+
+    /// Fields covered by the signature
+    fn signed_bytes(&self) -> Vec<u8> {
+        let mut bytes = b"aegis-q-break-glass-token".to_vec();
+        for field in [&self.token_id, &self.config_id, &self.operator, &self.reason] {
+            bytes.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(field.as_bytes());
+        }
+        bytes.extend_from_slice(&self.not_before.to_le_bytes());
+        bytes.extend_from_slice(&self.not_after.to_le_bytes());
+        bytes
+    }
+
+    fn audit_detail(&self) -> String {
+        format!("token={} config={} reason={}", self.token_id, self.config_id, self.reason)
+    }
+}
+
+/// Non-empty, without whitespace or `=`, so `audited_token` reads it back whole
+fn valid_token_id(token_id: &str) -> bool {
+    !token_id.is_empty() && !token_id.contains(|c: char| c.is_whitespace() || c == '=')
+}
+
+/// Token ID of an audit entry detail written by `audit_detail`
+fn audited_token(detail: &str) -> Option<&str> {
+    detail.strip_prefix("token=")?.split(' ').next()
+}
+
+/// Verifies break-glass tokens and keeps the access log
+pub struct BreakGlass {
+    authority_key: SigningKey,
+    audit: AuditLog,
+    used: HashSet<String>,
+}
+
+impl BreakGlass {
+    /// Verifier for tokens signed with `authority_key`
+    pub fn new(authority_key: SigningKey) -> Self {
+        Self::resume(authority_key, AuditLog::new())
+    }
+
+    /// Continue from an existing audit log; tokens it records as used stay used
+    pub fn resume(authority_key: SigningKey, audit: AuditLog) -> Self {
+        let used = audit
+            .entries()
+            .iter()
+            .filter(|entry| entry.action == UNLOCK_ACTION)
+            .filter_map(|entry| audited_token(&entry.detail).map(str::to_string))
+            .collect();
+        Self { authority_key, audit, used }
+    }
+
+    /// Open `config` with the recovery key under `token` at time `now`
+    pub fn unlock(&mut self, config: &ProtectedConfig, recovery_key: &EncryptionKey, token: &BreakGlassToken, now: u64) -> Result<Vec<u8>, AegisQError> {
+        if let Err(err) = self.authorize(config, token, now) {
+            self.audit.record(now, &token.operator, DENIED_ACTION, &format!("{} error={}", token.audit_detail(), err));
+            return Err(err);
+        }
+        self.used.insert(token.token_id.clone());
+        self.audit.record(now, &token.operator, UNLOCK_ACTION, &token.audit_detail());
+
+        let wrap = config.recovery.as_ref().ok_or(AegisQError::NotFound("Config has no recovery key"))?;
+        let mut unwrapped = aegis_q_decrypt(wrapping_key(recovery_key, &wrap.config_id).as_bytes(), &wrap.nonce, &wrap.wrapped_key)?;
+        let config_key = EncryptionKey::from_bytes(&unwrapped);
+        zeroize(&mut unwrapped);
+        config.retrieve(&config_key)
+    }
+
+    /// Access log, including refused attempts
+    pub fn audit(&self) -> &AuditLog {
+        &self.audit
+    }
+
+    fn authorize(&self, config: &ProtectedConfig, token: &BreakGlassToken, now: u64) -> Result<(), AegisQError> {
+        let wrap = config.recovery.as_ref().ok_or(AegisQError::NotFound("Config has no recovery key"))?;
+        token.verify(&self.authority_key, now)?;
+        if token.config_id != wrap.config_id {
+            return Err(AegisQError::Policy("Break-glass token issued for another config"));
+        }
+        if self.used.contains(&token.token_id) {
+            return Err(AegisQError::Policy("Break-glass token already used"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> (ProtectedConfig, EncryptionKey, SigningKey) {
+        let config_key = EncryptionKey::from_bytes(b"config-key-0123456789abcdef01234");
+        let recovery_key = EncryptionKey::from_bytes(b"recovery-key-0123456789abcdef012");
+        let mut config = ProtectedConfig::new(b"db_password=hunter2", &config_key);
+        config.enable_recovery(&config_key, &recovery_key, "prod-db").unwrap();
+        (config, recovery_key, SigningKey::from_bytes(b"break-glass-authority"))
+    }
+
+    #[test]
+    fn test_break_glass_unlock_is_single_use_and_audited() {
+        let (config, recovery_key, authority) = setup();
+        let token = BreakGlassToken::issue(&authority, "bg-1", "prod-db", "oncall", "INC-42", 1000, 3600).unwrap();
+
+        let mut break_glass = BreakGlass::new(authority.clone());
+        assert_eq!(break_glass.unlock(&config, &recovery_key, &token, 1500).unwrap(), b"db_password=hunter2");
+        assert_eq!(
+            break_glass.unlock(&config, &recovery_key, &token, 1600),
+            Err(AegisQError::Policy("Break-glass token already used"))
+        );
+
+        let entries = break_glass.audit().entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].action.as_str(), entries[0].actor.as_str()), (UNLOCK_ACTION, "oncall"));
+        assert_eq!(entries[1].action, DENIED_ACTION);
+        assert!(break_glass.audit().verify_chain());
+
+        // Used tokens survive a restart through the audit log
+        let mut restored = BreakGlass::resume(authority, break_glass.audit().clone());
+        assert!(restored.unlock(&config, &recovery_key, &token, 1700).is_err());
+    }
+
+    #[test]
+    fn test_break_glass_rejects_bad_tokens() {
+        let (mut config, recovery_key, authority) = setup();
+        let mut break_glass = BreakGlass::new(authority.clone());
+        let token = BreakGlassToken::issue(&authority, "bg-2", "prod-db", "oncall", "INC-43", 1000, 600).unwrap();
+
+        assert!(matches!(break_glass.unlock(&config, &recovery_key, &token, 999), Err(AegisQError::Policy(_))));
+        assert!(matches!(break_glass.unlock(&config, &recovery_key, &token, 1600), Err(AegisQError::Policy(_))));
+
+        let mut extended = token.clone();
+        extended.not_after += 3600;
+        assert_eq!(break_glass.unlock(&config, &recovery_key, &extended, 1700), Err(AegisQError::AuthenticationFailed));
+
+        let forged = BreakGlassToken::issue(&SigningKey::from_bytes(b"insider"), "bg-3", "prod-db", "eve", "", 1000, 600).unwrap();
+        assert_eq!(break_glass.unlock(&config, &recovery_key, &forged, 1100), Err(AegisQError::AuthenticationFailed));
+
+        let other = BreakGlassToken::issue(&authority, "bg-4", "staging-db", "oncall", "", 1000, 600).unwrap();
+        assert!(break_glass.unlock(&config, &recovery_key, &other, 1100).is_err());
+        assert!(BreakGlassToken::issue(&authority, "bg-5", "prod-db", "oncall", "", 0, MAX_TOKEN_TTL_SECS + 1).is_err());
+
+        // A valid token is spent and logged even if the recovery key is wrong
+        let wrong_key = EncryptionKey::from_bytes(b"not-the-recovery-key-0123456789a");
+        assert_eq!(break_glass.unlock(&config, &wrong_key, &token, 1100), Err(AegisQError::AuthenticationFailed));
+        assert_eq!(break_glass.audit().entries().iter().filter(|e| e.action == DENIED_ACTION).count(), 5);
+        assert_eq!(break_glass.audit().entries().last().unwrap().action, UNLOCK_ACTION);
+
+        config.disable_recovery();
+        assert_eq!(
+            break_glass.unlock(&config, &recovery_key, &token, 1100),
+            Err(AegisQError::NotFound("Config has no recovery key"))
+        );
+    }
+
+    #[test]
+    fn test_break_glass_token_ids_survive_resume() {
+        let (config, recovery_key, authority) = setup();
+        for token_id in ["bg 1", "bg=1", "bg\t1", ""] {
+            assert_eq!(
+                BreakGlassToken::issue(&authority, token_id, "prod-db", "oncall", "", 1000, 600),
+                Err(AegisQError::InvalidInput("Invalid break-glass token ID"))
+            );
+        }
+
+        // A token signed with a spaced ID some other way is refused before
+        // it is spent, so a restart can not make it usable twice
+        let mut spaced = BreakGlassToken::issue(&authority, "bg-1", "prod-db", "oncall", "INC-44", 1000, 600).unwrap();
+        spaced.token_id = "bg 1".to_string();
+        spaced.signature = aegis_q_mac(authority.as_bytes(), &spaced.signed_bytes()).to_vec();
+        let mut break_glass = BreakGlass::new(authority.clone());
+        assert!(matches!(break_glass.unlock(&config, &recovery_key, &spaced, 1100), Err(AegisQError::InvalidInput(_))));
+        let mut restored = BreakGlass::resume(authority.clone(), break_glass.audit().clone());
+        assert!(restored.unlock(&config, &recovery_key, &spaced, 1200).is_err());
+        assert!(restored.audit().entries().iter().all(|entry| entry.action == DENIED_ACTION));
+
+        // A well-formed ID used before the restart stays used after it
+        let token = BreakGlassToken::issue(&authority, "bg-1.a_b", "prod-db", "oncall", "INC 44 follow-up", 1000, 600).unwrap();
+        restored.unlock(&config, &recovery_key, &token, 1300).unwrap();
+        let mut restarted = BreakGlass::resume(authority, restored.audit().clone());
+        assert_eq!(
+            restarted.unlock(&config, &recovery_key, &token, 1400),
+            Err(AegisQError::Policy("Break-glass token already used"))
+        );
+    }
+}
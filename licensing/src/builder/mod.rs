@@ -0,0 +1,246 @@
+//! License issuance
+//!
+//! `LicenseBuilder` is the checked way to issue a license: invariants are
+//! enforced at build time (non-empty id, expiry in the future, at least one
+//! feature, no duplicates) and feature names are normalized to lowercase
+//! before they are signed, so `"Pro "` and `"pro"` cannot end up as two
+//! entitlements. `build_signed` hands the result to a `Signer` — a local
+//! `SigningKey` or the signing agent — in one step. Activation constraints
+//! (see `constraints`) are signed along with the rest.
+
+use std::fmt;
+
+use aegis_q_core::AegisQError;
+use utils::keys::SigningKey;
+
+use crate::constraints::Constraint;
+use crate::License;
+
+/// Typed issuance failure
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LicenseError {
+    /// License id is empty or whitespace
+    EmptyId,
+    /// No expiry was set
+    MissingExpiry,
+    /// Seat count of zero
+    ZeroSeats,
+    /// Expiry is not after the issuance time
+    ExpiryInPast { expiry: u64, now: u64 },
+    /// License grants no features
+    NoFeatures,
+    /// Feature name is empty or contains characters outside `[a-z0-9._-]`
+    InvalidFeature(String),
+    /// Feature listed twice (after normalization)
+    DuplicateFeature(String),
+    /// Activation constraint allows nothing
+    InvalidConstraint(String),
+    /// Signer refused or failed
+    Signing(AegisQError),
+}
+
+impl fmt::Display for LicenseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LicenseError::EmptyId => write!(f, "license id is empty"),
+            LicenseError::MissingExpiry => write!(f, "license expiry not set"),
+            LicenseError::ZeroSeats => write!(f, "license seat count is zero"),
+            LicenseError::ExpiryInPast { expiry, now } => {
+                write!(f, "license expiry {} is not after {}", expiry, now)
+            }
+            LicenseError::NoFeatures => write!(f, "license grants no features"),
+            LicenseError::InvalidFeature(feature) => write!(f, "invalid feature name: {:?}", feature),
+            LicenseError::DuplicateFeature(feature) => write!(f, "duplicate feature: {}", feature),
+            LicenseError::InvalidConstraint(detail) => write!(f, "invalid constraint: {}", detail),
+            LicenseError::Signing(reason) => write!(f, "signing failed: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for LicenseError {}
+
+/// Anything that can sign a license
+pub trait Signer {
+    /// Return `license` with its signature set
+    fn sign_license(&mut self, license: License) -> Result<License, AegisQError>;
+}
+
+impl Signer for SigningKey {
+    fn sign_license(&mut self, mut license: License) -> Result<License, AegisQError> {
+        license.sign(self);
+        Ok(license)
+    }
+}
+
+/// Normalize a feature name: trimmed, lowercase, `[a-z0-9._-]` only
+pub fn normalize_feature(feature: &str) -> Result<String, LicenseError> {
+    let normalized = feature.trim().to_ascii_lowercase();
+    let valid = !normalized.is_empty()
+        && normalized
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || matches!(b, b'.' | b'_' | b'-'));
+    if valid {
+        Ok(normalized)
+    } else {
+        Err(LicenseError::InvalidFeature(feature.to_string()))
+    }
+}
+
+/// Validating license builder
+#[derive(Debug, Clone)]
+pub struct LicenseBuilder {
+    license_id: String,
+    features: Vec<String>,
+    expiry: Option<u64>,
+    seats: Option<u32>,
+    constraints: Vec<Constraint>,
+}
+
+impl LicenseBuilder {
+    /// Start a license with the given id
+    pub fn new(license_id: impl Into<String>) -> Self {
+        Self {
+            license_id: license_id.into(),
+            features: Vec::new(),
+            expiry: None,
+            seats: None,
+            constraints: Vec::new(),
+        }
+    }
+
+    /// Grant a feature
+    pub fn feature(mut self, feature: impl Into<String>) -> Self {
+        self.features.push(feature.into());
+        self
+    }
+
+    /// Grant several features
+    pub fn features<I, F>(mut self, features: I) -> Self
+    where
+        I: IntoIterator<Item = F>,
+        F: Into<String>,
+    {
+        self.features.extend(features.into_iter().map(Into::into));
+        self
+    }
+
+    /// Expiry (Unix seconds)
+    pub fn expiry(mut self, expiry: u64) -> Self {
+        self.expiry = Some(expiry);
+        self
+    }
+
+    /// Limit the license to `seats` seats
+    pub fn seats(mut self, seats: u32) -> Self {
+        self.seats = Some(seats);
+        self
+    }
+
+    /// Restrict activation (all constraints must hold)
+    pub fn constraint(mut self, constraint: Constraint) -> Self {
+        self.constraints.push(constraint);
+        self
+    }
+
+    /// Check invariants and produce an unsigned license
+    ///
+    /// Features keep their insertion order, since the signature covers it.
+    pub fn build(self, now: u64) -> Result<License, LicenseError> {
+        let license_id = self.license_id.trim();
+        if license_id.is_empty() {
+            return Err(LicenseError::EmptyId);
+        }
+        let expiry = self.expiry.ok_or(LicenseError::MissingExpiry)?;
+        if expiry <= now {
+            return Err(LicenseError::ExpiryInPast { expiry, now });
+        }
+        if self.seats == Some(0) {
+            return Err(LicenseError::ZeroSeats);
+        }
+        if self.features.is_empty() {
+            return Err(LicenseError::NoFeatures);
+        }
+
+        let mut features = Vec::with_capacity(self.features.len());
+        for feature in &self.features {
+            let normalized = normalize_feature(feature)?;
+            if features.contains(&normalized) {
+                return Err(LicenseError::DuplicateFeature(normalized));
+            }
+            features.push(normalized);
+        }
+
+        for constraint in &self.constraints {
+            constraint
+                .validate()
+                .map_err(|_| LicenseError::InvalidConstraint(constraint.kind().to_string()))?;
+        }
+
+        let mut license = License::new(license_id.to_string(), features, expiry);
+        license.seats = self.seats;
+        license.constraints = self.constraints;
+        Ok(license)
+    }
+
+    /// Build and sign in one step
+    pub fn build_signed<S: Signer + ?Sized>(self, signer: &mut S, now: u64) -> Result<License, LicenseError> {
+        let license = self.build(now)?;
+        signer.sign_license(license).map_err(LicenseError::Signing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_normalizes_and_signs() {
+        let mut key = SigningKey::from_bytes(b"vendor-key");
+        let license = LicenseBuilder::new(" lic-1 ")
+            .feature(" Pro ")
+            .features(["vpn.multi-hop", "audit_log"])
+            .expiry(2000)
+            .build_signed(&mut key, 1000)
+            .unwrap();
+
+        assert_eq!(license.license_id, "lic-1");
+        assert_eq!(license.features, vec!["pro", "vpn.multi-hop", "audit_log"]);
+        assert_eq!(license.expiry, 2000);
+        assert!(license.verify(&key));
+    }
+
+    #[test]
+    fn test_build_rejects_invalid() {
+        let base = || LicenseBuilder::new("lic").feature("pro").expiry(2000);
+
+        assert_eq!(LicenseBuilder::new("  ").feature("pro").expiry(2000).build(0).unwrap_err(), LicenseError::EmptyId);
+        assert_eq!(LicenseBuilder::new("lic").feature("pro").build(0).unwrap_err(), LicenseError::MissingExpiry);
+        assert_eq!(LicenseBuilder::new("lic").expiry(2000).build(0).unwrap_err(), LicenseError::NoFeatures);
+        assert_eq!(base().build(2000).unwrap_err(), LicenseError::ExpiryInPast { expiry: 2000, now: 2000 });
+        assert_eq!(base().feature("PRO").build(0).unwrap_err(), LicenseError::DuplicateFeature("pro".into()));
+        assert_eq!(base().feature("two words").build(0).unwrap_err(), LicenseError::InvalidFeature("two words".into()));
+        assert_eq!(base().feature(" ").build(0).unwrap_err(), LicenseError::InvalidFeature(" ".into()));
+        assert_eq!(base().seats(0).build(0).unwrap_err(), LicenseError::ZeroSeats);
+        assert_eq!(base().seats(25).build(0).unwrap().seats, Some(25));
+        assert_eq!(
+            base().constraint(Constraint::Platforms(Vec::new())).build(0).unwrap_err(),
+            LicenseError::InvalidConstraint("platform".into())
+        );
+        let restricted = base().constraint(Constraint::countries(["de"]).unwrap()).build(0).unwrap();
+        assert_eq!(restricted.constraints, vec![Constraint::Countries(vec!["DE".into()])]);
+    }
+
+    #[test]
+    fn test_signer_error_is_surfaced() {
+        struct Refusing;
+        impl Signer for Refusing {
+            fn sign_license(&mut self, _license: License) -> Result<License, AegisQError> {
+                Err(AegisQError::Policy("key revoked"))
+            }
+        }
+
+        let err = LicenseBuilder::new("lic").feature("pro").expiry(2000).build_signed(&mut Refusing, 0).unwrap_err();
+        assert_eq!(err, LicenseError::Signing(AegisQError::Policy("key revoked")));
+        assert_eq!(err.to_string(), "signing failed: key revoked");
+    }
+}
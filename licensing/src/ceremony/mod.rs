@@ -0,0 +1,242 @@
+//! Key Ceremony
+//!
+//! Vendor root key generation and custodian share handling.
+//! The root key only ever lives in a SecureArena; it is split with Shamir
+//! sharing right after generation and reconstructed for signing ceremonies.
+//! Every step is recorded in the audit log with the key fingerprint.
+
+use aegis_q_core::AegisQError;
+use sha3::{Digest, Sha3_256};
+use utils::memory::{SecureArena, zeroize};
+use utils::rng::random_bytes;
+use utils::shamir::{self, Share};
+
+use crate::audit::AuditLog;
+
+/// Root key length
+pub const ROOT_KEY_SIZE: usize = 64;
+
+/// Printable share prefix
+const SHARE_PREFIX: &str = "AEGISQ-SHARE";
+
+/// Share assigned to a named custodian
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustodianShare {
+    pub custodian: String,
+    pub share: Share,
+}
+
+impl CustodianShare {
+    fn checksum(share: &Share) -> [u8; 4] {
+        let mut hasher = Sha3_256::new();
+        hasher.update(b"aegis-q-ceremony-share");
+        hasher.update([share.index, share.threshold]);
+        hasher.update(&share.value);
+        let hash = hasher.finalize();
+        [hash[0], hash[1], hash[2], hash[3]]
+    }
+
+    /// Printable form: `AEGISQ-SHARE:<index>:<threshold>:<HEX>:<CHECK>`
+    ///
+    /// Uses only QR alphanumeric-mode characters, so it can be printed
+    /// or rendered as a compact QR code.
+    pub fn to_printable(&self) -> String {
+        format!(
+            "{}:{}:{}:{}:{}",
+            SHARE_PREFIX,
+            self.share.index,
+            self.share.threshold,
+            hex_upper(&self.share.value),
+            hex_upper(&Self::checksum(&self.share)),
+        )
+    }
+
+    /// Parse printable form (custodian name is supplied by the operator)
+    pub fn from_printable(custodian: &str, text: &str) -> Result<Self, AegisQError> {
+        let parts: Vec<&str> = text.trim().split(':').collect();
+        if parts.len() != 5 || parts[0] != SHARE_PREFIX {
+            return Err(AegisQError::Serialization("Malformed share"));
+        }
+
+        let index: u8 = parts[1].parse().map_err(|_| AegisQError::Serialization("Malformed share"))?;
+        let threshold: u8 = parts[2].parse().map_err(|_| AegisQError::Serialization("Malformed share"))?;
+        let value = hex_decode(parts[3])?;
+        let share = Share { index, threshold, value };
+
+        if hex_decode(parts[4])? != Self::checksum(&share) {
+            return Err(AegisQError::AuthenticationFailed);
+        }
+
+        Ok(Self {
+            custodian: custodian.to_string(),
+            share,
+        })
+    }
+}
+
+/// Short public fingerprint of a root key (safe to log)
+pub fn fingerprint(key: &[u8]) -> String {
+    let mut hasher = Sha3_256::new();
+    hasher.update(b"aegis-q-ceremony-fingerprint");
+    hasher.update(key);
+    hex_upper(&hasher.finalize()[..8])
+}
+
+/// Generate a fresh vendor root key inside a SecureArena
+pub fn generate_root_key() -> SecureArena {
+    let mut arena = SecureArena::new(ROOT_KEY_SIZE);
+    let mut random = random_bytes(ROOT_KEY_SIZE);
+    arena.as_mut_slice().copy_from_slice(&random);
+    zeroize(&mut random);
+    arena
+}
+
+/// Split root key into one share per custodian
+pub fn split_root_key(
+    root_key: &SecureArena,
+    custodians: &[&str],
+    threshold: u8,
+    audit: &mut AuditLog,
+    now: u64,
+) -> Result<Vec<CustodianShare>, AegisQError> {
+    if custodians.len() > 255 {
+        return Err(AegisQError::InvalidInput("Too many custodians"));
+    }
+
+    let shares = shamir::split(root_key.as_slice(), threshold, custodians.len() as u8).map_err(AegisQError::InvalidInput)?;
+    audit.record(
+        now,
+        "ceremony",
+        "split",
+        &format!(
+            "key={} threshold={} custodians={}",
+            fingerprint(root_key.as_slice()),
+            threshold,
+            custodians.join(",")
+        ),
+    );
+
+    Ok(custodians
+        .iter()
+        .zip(shares)
+        .map(|(custodian, share)| CustodianShare {
+            custodian: custodian.to_string(),
+            share,
+        })
+        .collect())
+}
+
+/// Reconstruct root key from custodian shares
+///
+/// `expected_fingerprint` is the fingerprint recorded at split time. Share
+/// checksums are unkeyed, so a custodian could alter their share; a
+/// result with any other fingerprint is rejected.
+pub fn reconstruct_root_key(
+    shares: &[CustodianShare],
+    expected_fingerprint: &str,
+    audit: &mut AuditLog,
+    now: u64,
+) -> Result<SecureArena, AegisQError> {
+    let custodians: Vec<&str> = shares.iter().map(|s| s.custodian.as_str()).collect();
+    let raw: Vec<Share> = shares.iter().map(|s| s.share.clone()).collect();
+
+    let mut secret = match shamir::combine(&raw) {
+        Ok(secret) => secret,
+        Err(e) => {
+            audit.record(now, "ceremony", "reconstruct-failed", &format!("custodians={} error={}", custodians.join(","), e));
+            return Err(AegisQError::InvalidInput(e));
+        }
+    };
+
+    if !fingerprint(&secret).eq_ignore_ascii_case(expected_fingerprint.trim()) {
+        zeroize(&mut secret);
+        audit.record(now, "ceremony", "reconstruct-failed", &format!("custodians={} error=fingerprint mismatch", custodians.join(",")));
+        return Err(AegisQError::AuthenticationFailed);
+    }
+
+    let mut arena = SecureArena::new(secret.len());
+    arena.as_mut_slice().copy_from_slice(&secret);
+    zeroize(&mut secret);
+
+    audit.record(
+        now,
+        "ceremony",
+        "reconstruct",
+        &format!("key={} custodians={}", fingerprint(arena.as_slice()), custodians.join(",")),
+    );
+
+    Ok(arena)
+}
+
+fn hex_upper(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+fn hex_decode(text: &str) -> Result<Vec<u8>, AegisQError> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return Err(AegisQError::Serialization("Malformed share"));
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).map_err(|_| AegisQError::Serialization("Malformed share")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ceremony_split_reconstruct() {
+        let mut audit = AuditLog::new();
+        let root = generate_root_key();
+        let shares = split_root_key(&root, &["alice", "bob", "carol"], 2, &mut audit, 100).unwrap();
+
+        let printed: Vec<String> = shares.iter().map(|s| s.to_printable()).collect();
+        let parsed = vec![
+            CustodianShare::from_printable("carol", &printed[2]).unwrap(),
+            CustodianShare::from_printable("alice", &printed[0]).unwrap(),
+        ];
+
+        let restored = reconstruct_root_key(&parsed, &fingerprint(root.as_slice()), &mut audit, 200).unwrap();
+        assert_eq!(restored.as_slice(), root.as_slice());
+
+        assert_eq!(audit.entries().len(), 2);
+        assert!(audit.entries()[1].detail.contains(&fingerprint(root.as_slice())));
+        assert!(audit.verify_chain());
+    }
+
+    #[test]
+    fn test_reconstruct_checks_fingerprint() {
+        let mut audit = AuditLog::new();
+        let root = generate_root_key();
+        let expected = fingerprint(root.as_slice());
+        let mut shares = split_root_key(&root, &["alice", "bob", "carol"], 2, &mut audit, 100).unwrap();
+
+        // A custodian replacing their share with a re-checksummed one of their choosing
+        shares[0].share.value[0] ^= 1;
+        let forged = CustodianShare::from_printable("alice", &shares[0].to_printable()).unwrap();
+        let result = reconstruct_root_key(&[forged, shares[1].clone()], &expected, &mut audit, 200);
+        assert!(matches!(result, Err(AegisQError::AuthenticationFailed)));
+
+        // A lowered threshold is refused outright
+        let mut lowered = shares[1].clone();
+        lowered.share.threshold = 1;
+        let result = reconstruct_root_key(&[lowered], &expected, &mut audit, 200);
+        assert!(matches!(result, Err(AegisQError::InvalidInput("Invalid threshold"))));
+
+        assert_eq!(audit.entries().last().unwrap().action, "reconstruct-failed");
+    }
+
+    #[test]
+    fn test_printable_share_checksum() {
+        let mut audit = AuditLog::new();
+        let shares = split_root_key(&generate_root_key(), &["a", "b"], 2, &mut audit, 0).unwrap();
+
+        let mut printed = shares[0].to_printable();
+        let flip = if printed.ends_with('0') { '1' } else { '0' };
+        printed.pop();
+        printed.push(flip);
+        assert!(CustodianShare::from_printable("a", &printed).is_err());
+    }
+}
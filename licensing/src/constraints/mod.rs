@@ -0,0 +1,401 @@
+//! Activation constraints (geofencing)
+//!
+//! A license may carry constraints on where it can be activated: allowed
+//! country codes, client IP ranges and OS platforms. Constraints are part of
+//! the signed contents, and all of them must hold. They are checked by the
+//! activation or enforcement layer against an `ActivationContext` through a
+//! `ConstraintEvaluator`, so a server can plug in its GeoIP lookup while a
+//! client checks its own platform.
+//!
+//! Evaluation fails closed: a constraint on an attribute the context does
+//! not know (no country resolved, no client IP) is a violation.
+
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use utils::wire::LicenseConstraintWire;
+
+use crate::License;
+
+/// Single activation constraint
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type", content = "allow")]
+pub enum Constraint {
+    /// ISO 3166-1 alpha-2 country codes (uppercase)
+    Countries(Vec<String>),
+    /// Client IP ranges
+    IpRanges(Vec<IpRange>),
+    /// OS platforms as reported by `std::env::consts::OS` (lowercase)
+    Platforms(Vec<String>),
+}
+
+impl Constraint {
+    /// Countries constraint with codes normalized to uppercase
+    pub fn countries<I, S>(codes: I) -> Result<Self, ConstraintError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let codes = codes
+            .into_iter()
+            .map(|code| {
+                let code = code.as_ref().trim().to_ascii_uppercase();
+                if code.len() == 2 && code.bytes().all(|b| b.is_ascii_uppercase()) {
+                    Ok(code)
+                } else {
+                    Err(ConstraintError::Invalid(format!("country code {:?}", code)))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::Countries(codes).checked()
+    }
+
+    /// IP ranges constraint from CIDR strings
+    pub fn ip_ranges<I, S>(ranges: I) -> Result<Self, ConstraintError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let ranges = ranges
+            .into_iter()
+            .map(|range| range.as_ref().parse())
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::IpRanges(ranges).checked()
+    }
+
+    /// Platforms constraint with names normalized to lowercase
+    pub fn platforms<I, S>(platforms: I) -> Result<Self, ConstraintError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let platforms = platforms
+            .into_iter()
+            .map(|platform| platform.as_ref().trim().to_ascii_lowercase())
+            .collect::<Vec<_>>();
+        if platforms.iter().any(String::is_empty) {
+            return Err(ConstraintError::Invalid("empty platform".to_string()));
+        }
+        Self::Platforms(platforms).checked()
+    }
+
+    fn checked(self) -> Result<Self, ConstraintError> {
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Reject constraints that allow nothing
+    pub fn validate(&self) -> Result<(), ConstraintError> {
+        let empty = match self {
+            Constraint::Countries(codes) => codes.is_empty(),
+            Constraint::IpRanges(ranges) => ranges.is_empty(),
+            Constraint::Platforms(platforms) => platforms.is_empty(),
+        };
+        if empty {
+            return Err(ConstraintError::Invalid(format!("{} constraint allows nothing", self.kind())));
+        }
+        Ok(())
+    }
+
+    /// Attribute name, for error messages
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Constraint::Countries(_) => "country",
+            Constraint::IpRanges(_) => "ip",
+            Constraint::Platforms(_) => "platform",
+        }
+    }
+
+    /// Canonical form covered by the license signature
+    pub(crate) fn wire(&self) -> LicenseConstraintWire {
+        let (kind, values) = match self {
+            Constraint::Countries(codes) => (1, codes.clone()),
+            Constraint::IpRanges(ranges) => (2, ranges.iter().map(IpRange::to_string).collect()),
+            Constraint::Platforms(platforms) => (3, platforms.clone()),
+        };
+        LicenseConstraintWire { kind, values }
+    }
+}
+
+/// Typed constraint failure
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConstraintError {
+    /// Malformed constraint (bad country code, CIDR, empty allow list)
+    Invalid(String),
+    /// Context lacks the attribute the constraint needs
+    Unknown(&'static str),
+    /// Attribute value is not allowed by the license
+    Violated { kind: &'static str, value: String },
+}
+
+impl fmt::Display for ConstraintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConstraintError::Invalid(detail) => write!(f, "invalid constraint: {}", detail),
+            ConstraintError::Unknown(kind) => write!(f, "{} unknown, license is restricted", kind),
+            ConstraintError::Violated { kind, value } => write!(f, "{} {} not allowed by license", kind, value),
+        }
+    }
+}
+
+impl std::error::Error for ConstraintError {}
+
+/// IP network in CIDR notation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct IpRange {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl IpRange {
+    /// Range with the host bits cleared
+    pub fn new(address: IpAddr, prefix: u8) -> Result<Self, ConstraintError> {
+        let network = match address {
+            IpAddr::V4(a) if prefix <= 32 => IpAddr::V4((u32::from(a) & mask32(prefix)).into()),
+            IpAddr::V6(a) if prefix <= 128 => IpAddr::V6((u128::from(a) & mask128(prefix)).into()),
+            _ => return Err(ConstraintError::Invalid(format!("prefix /{} too long", prefix))),
+        };
+        Ok(Self { network, prefix })
+    }
+
+    /// Whether `address` lies in this range
+    pub fn contains(&self, address: IpAddr) -> bool {
+        match (self.network, address) {
+            (IpAddr::V4(net), IpAddr::V4(a)) => u32::from(a) & mask32(self.prefix) == u32::from(net),
+            (IpAddr::V6(net), IpAddr::V6(a)) => u128::from(a) & mask128(self.prefix) == u128::from(net),
+            _ => false,
+        }
+    }
+}
+
+fn mask32(prefix: u8) -> u32 {
+    u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0)
+}
+
+fn mask128(prefix: u8) -> u128 {
+    u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0)
+}
+
+impl FromStr for IpRange {
+    type Err = ConstraintError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ConstraintError::Invalid(format!("IP range {:?}", s));
+        let (address, prefix) = match s.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (s, None),
+        };
+        let address: IpAddr = address.parse().map_err(|_| invalid())?;
+        let prefix = match prefix {
+            Some(p) => p.parse().map_err(|_| invalid())?,
+            None if address.is_ipv4() => 32,
+            None => 128,
+        };
+        Self::new(address, prefix)
+    }
+}
+
+impl TryFrom<String> for IpRange {
+    type Error = ConstraintError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<IpRange> for String {
+    fn from(range: IpRange) -> Self {
+        range.to_string()
+    }
+}
+
+impl fmt::Display for IpRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+/// What is known about the activating client
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ActivationContext {
+    /// Country code, if resolved
+    pub country: Option<String>,
+    /// Client address as seen by the server
+    pub ip: Option<IpAddr>,
+    /// OS platform
+    pub platform: Option<String>,
+}
+
+impl ActivationContext {
+    /// Empty context
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Context for client-side checks: the local platform only
+    pub fn local() -> Self {
+        Self::new().platform(std::env::consts::OS)
+    }
+
+    /// Set the country code
+    pub fn country(mut self, country: &str) -> Self {
+        self.country = Some(country.trim().to_ascii_uppercase());
+        self
+    }
+
+    /// Set the client address
+    pub fn ip(mut self, ip: IpAddr) -> Self {
+        self.ip = Some(ip);
+        self
+    }
+
+    /// Set the platform
+    pub fn platform(mut self, platform: &str) -> Self {
+        self.platform = Some(platform.trim().to_ascii_lowercase());
+        self
+    }
+}
+
+/// Decides whether a constraint holds in a context
+///
+/// Implement this to resolve attributes the context lacks, e.g. a country
+/// from the client IP via a GeoIP database, and defer to `DefaultEvaluator`.
+pub trait ConstraintEvaluator: fmt::Debug + Send + Sync {
+    /// Check one constraint
+    fn evaluate(&self, constraint: &Constraint, context: &ActivationContext) -> Result<(), ConstraintError>;
+}
+
+/// Evaluates constraints against the context as given
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultEvaluator;
+
+impl ConstraintEvaluator for DefaultEvaluator {
+    fn evaluate(&self, constraint: &Constraint, context: &ActivationContext) -> Result<(), ConstraintError> {
+        let kind = constraint.kind();
+        let (allowed, value) = match constraint {
+            Constraint::Countries(codes) => {
+                let country = context.country.as_ref().ok_or(ConstraintError::Unknown(kind))?;
+                (codes.contains(country), country.clone())
+            }
+            Constraint::IpRanges(ranges) => {
+                let ip = context.ip.ok_or(ConstraintError::Unknown(kind))?;
+                (ranges.iter().any(|range| range.contains(ip)), ip.to_string())
+            }
+            Constraint::Platforms(platforms) => {
+                let platform = context.platform.as_ref().ok_or(ConstraintError::Unknown(kind))?;
+                (platforms.contains(platform), platform.clone())
+            }
+        };
+        if allowed {
+            Ok(())
+        } else {
+            Err(ConstraintError::Violated { kind, value })
+        }
+    }
+}
+
+impl License {
+    /// Check every activation constraint (call after verifying the signature)
+    pub fn check_constraints(
+        &self,
+        evaluator: &dyn ConstraintEvaluator,
+        context: &ActivationContext,
+    ) -> Result<(), ConstraintError> {
+        self.constraints
+            .iter()
+            .try_for_each(|constraint| evaluator.evaluate(constraint, context))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::keys::SigningKey;
+
+    fn restricted() -> License {
+        let mut license = License::new("lic-1".to_string(), vec!["pro".to_string()], 2000);
+        license.constraints = vec![
+            Constraint::countries(["de", "FR"]).unwrap(),
+            Constraint::ip_ranges(["10.0.0.0/8", "2001:db8::/32"]).unwrap(),
+        ];
+        license
+    }
+
+    #[test]
+    fn test_constraints_evaluated() {
+        let license = restricted();
+        let context = ActivationContext::new().country("de").ip("10.1.2.3".parse().unwrap());
+        assert_eq!(license.check_constraints(&DefaultEvaluator, &context), Ok(()));
+
+        let abroad = context.clone().country("US");
+        assert_eq!(
+            license.check_constraints(&DefaultEvaluator, &abroad),
+            Err(ConstraintError::Violated { kind: "country", value: "US".into() })
+        );
+        let outside = context.clone().ip("2001:db9::1".parse().unwrap());
+        assert!(matches!(
+            license.check_constraints(&DefaultEvaluator, &outside),
+            Err(ConstraintError::Violated { kind: "ip", .. })
+        ));
+        // Fails closed when the attribute is unknown
+        assert_eq!(
+            license.check_constraints(&DefaultEvaluator, &ActivationContext::new().country("FR")),
+            Err(ConstraintError::Unknown("ip"))
+        );
+
+        let mut client = License::new("lic-2".to_string(), vec!["pro".to_string()], 2000);
+        client.constraints = vec![Constraint::platforms([std::env::consts::OS]).unwrap()];
+        assert_eq!(client.check_constraints(&DefaultEvaluator, &ActivationContext::local()), Ok(()));
+    }
+
+    #[test]
+    fn test_pluggable_evaluator() {
+        // Resolves the country from the client IP, as a GeoIP lookup would
+        #[derive(Debug)]
+        struct GeoIp;
+        impl ConstraintEvaluator for GeoIp {
+            fn evaluate(&self, constraint: &Constraint, context: &ActivationContext) -> Result<(), ConstraintError> {
+                let mut context = context.clone();
+                if context.country.is_none() && context.ip == Some("10.1.2.3".parse().unwrap()) {
+                    context = context.country("FR");
+                }
+                DefaultEvaluator.evaluate(constraint, &context)
+            }
+        }
+
+        let context = ActivationContext::new().ip("10.1.2.3".parse().unwrap());
+        assert_eq!(restricted().check_constraints(&GeoIp, &context), Ok(()));
+        assert_eq!(
+            restricted().check_constraints(&DefaultEvaluator, &context),
+            Err(ConstraintError::Unknown("country"))
+        );
+    }
+
+    #[test]
+    fn test_constraints_signed_and_serialized() {
+        let key = SigningKey::from_bytes(b"vendor-key");
+        let mut license = restricted();
+        license.sign(&key);
+
+        let json = serde_json::to_string(&license).unwrap();
+        assert!(json.contains(r#""type":"countries","allow":["DE","FR"]"#));
+        assert!(json.contains(r#""10.0.0.0/8""#));
+        let parsed: License = serde_json::from_str(&json).unwrap();
+        assert!(parsed.verify(&key));
+
+        let mut widened = license.clone();
+        widened.constraints[0] = Constraint::countries(["DE", "FR", "US"]).unwrap();
+        assert!(!widened.verify(&key));
+        let mut lifted = license.clone();
+        lifted.constraints.clear();
+        assert!(!lifted.verify(&key));
+
+        assert!(Constraint::countries(["DEU"]).is_err());
+        assert!(Constraint::countries(Vec::<String>::new()).is_err());
+        assert!(Constraint::ip_ranges(["10.0.0.0/33"]).is_err());
+        assert_eq!("10.1.2.3/8".parse::<IpRange>().unwrap().to_string(), "10.0.0.0/8");
+    }
+}
@@ -0,0 +1,180 @@
+//! Embedded license/config blobs
+//!
+//! A build script seals a license or config with `generate`, which writes
+//! a Rust file defining the static `AEGISQ_EMBEDDED_BLOB`; the crate pulls
+//! it in with `licensing::embed_blob!()` and opens it at runtime with
+//! `open`. The symbol is `#[no_mangle]` and `#[used]`, so it survives
+//! linking and is easy to find in the binary.
+//!
+//! Sealing is deterministic: the nonce is derived from the key and the
+//! data, and the generated file contains nothing else (no timestamps or
+//! paths), so the same inputs give a byte-identical binary and
+//! reproducible builds keep working. The only thing this reveals is
+//! whether two embedded blobs are equal.
+//!
+//! Tools that only have the binary (e.g. after stripping) use `find`,
+//! which scans an image for the blob's magic and opens the first match
+//! that authenticates.
+//!
+//! Blob: `"AEGISQ-EMBED" || version || nonce || len (LE u32) || Aegis-Q(data)`,
+//! see `utils::wire::EmbeddedBlobWire`.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use aegis_q_core::{AegisQError, aegis_q_decrypt, aegis_q_encrypt};
+use utils::kdf::kdf_shake256;
+use utils::keys::{EncryptionKey, TypedKey};
+use utils::wire::{EmbeddedBlobWire, Wire};
+
+/// Leading bytes of every embedded blob
+pub const MAGIC: [u8; 12] = *b"AEGISQ-EMBED";
+
+/// Blob format version
+pub const EMBED_VERSION: u8 = 1;
+
+/// Symbol of the embedded blob
+pub const SYMBOL: &str = "AEGISQ_EMBEDDED_BLOB";
+
+/// File written by `generate` into `OUT_DIR`
+pub const SOURCE_FILE: &str = "aegisq_embedded.rs";
+
+const NONCE_SIZE: usize = 16;
+
+/// Seal `data` under `key`; the same inputs always give the same blob
+pub fn seal(data: &[u8], key: &EncryptionKey) -> Result<Vec<u8>, AegisQError> {
+    let mut nonce = [0u8; NONCE_SIZE];
+    nonce.copy_from_slice(&kdf_shake256(b"aegis-q-embed-nonce", key.as_bytes(), data, NONCE_SIZE));
+    EmbeddedBlobWire {
+        magic: MAGIC,
+        version: EMBED_VERSION,
+        nonce,
+        sealed: aegis_q_encrypt(key.as_bytes(), &nonce, data),
+    }
+    .to_wire()
+    .map_err(AegisQError::Serialization)
+}
+
+/// Open a blob produced by `seal`, e.g. `&AEGISQ_EMBEDDED_BLOB`
+pub fn open(blob: &[u8], key: &EncryptionKey) -> Result<Vec<u8>, AegisQError> {
+    let wire = EmbeddedBlobWire::from_wire(blob).map_err(AegisQError::Serialization)?;
+    open_wire(&wire, key)
+}
+
+/// Locate and open the embedded blob in a binary image
+///
+/// Every occurrence of `MAGIC` is tried, as the constant itself may also
+/// appear in the image.
+pub fn find(image: &[u8], key: &EncryptionKey) -> Result<Vec<u8>, AegisQError> {
+    let mut start = 0;
+    while let Some(offset) = image[start..].windows(MAGIC.len()).position(|window| window == MAGIC) {
+        let at = start + offset;
+        if let Ok((wire, _)) = EmbeddedBlobWire::from_wire_prefix(&image[at..]) {
+            if let Ok(data) = open_wire(&wire, key) {
+                return Ok(data);
+            }
+        }
+        start = at + 1;
+    }
+    Err(AegisQError::NotFound("No embedded blob"))
+}
+
+/// `find` in the running executable
+pub fn find_in_current_exe(key: &EncryptionKey) -> Result<Vec<u8>, AegisQError> {
+    let image = std::env::current_exe()
+        .and_then(fs::read)
+        .map_err(|_| AegisQError::Io("Cannot read current executable"))?;
+    find(&image, key)
+}
+
+/// Rust source defining `AEGISQ_EMBEDDED_BLOB` for `blob`
+pub fn source(blob: &[u8]) -> String {
+    let mut out = String::new();
+    out.push_str("// Generated by licensing::embed::generate; do not edit\n");
+    out.push_str("#[used]\n#[no_mangle]\n");
+    let _ = write!(out, "pub static {}: [u8; {}] = [", SYMBOL, blob.len());
+    for (i, byte) in blob.iter().enumerate() {
+        if i % 16 == 0 {
+            out.push_str("\n   ");
+        }
+        let _ = write!(out, " 0x{:02x},", byte);
+    }
+    out.push_str("\n];\n");
+    out
+}
+
+/// Build-script helper: seal `data` and write `SOURCE_FILE` into `out_dir`
+///
+/// The file is only rewritten when its contents change. Emit
+/// `cargo:rerun-if-changed` for the inputs in the build script.
+pub fn generate(out_dir: impl AsRef<Path>, data: &[u8], key: &EncryptionKey) -> io::Result<PathBuf> {
+    let blob = seal(data, key).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+    let path = out_dir.as_ref().join(SOURCE_FILE);
+    let contents = source(&blob);
+    if fs::read_to_string(&path).ok().as_deref() != Some(contents.as_str()) {
+        fs::write(&path, contents)?;
+    }
+    Ok(path)
+}
+
+/// Define `AEGISQ_EMBEDDED_BLOB` from the file written by `embed::generate`
+#[macro_export]
+macro_rules! embed_blob {
+    () => {
+        include!(concat!(env!("OUT_DIR"), "/aegisq_embedded.rs"));
+    };
+}
+
+fn open_wire(wire: &EmbeddedBlobWire, key: &EncryptionKey) -> Result<Vec<u8>, AegisQError> {
+    if wire.magic != MAGIC {
+        return Err(AegisQError::Serialization("Not an embedded blob"));
+    }
+    if wire.version != EMBED_VERSION {
+        return Err(AegisQError::Unsupported("Unknown embedded blob version"));
+    }
+    aegis_q_decrypt(key.as_bytes(), &wire.nonce, &wire.sealed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(byte: u8) -> EncryptionKey {
+        EncryptionKey::from_bytes(&[byte; 32])
+    }
+
+    #[test]
+    fn test_seal_is_deterministic() {
+        let blob = seal(b"{\"license_id\":\"L-1\"}", &key(1)).unwrap();
+        assert_eq!(blob, seal(b"{\"license_id\":\"L-1\"}", &key(1)).unwrap());
+        assert_ne!(blob, seal(b"{\"license_id\":\"L-2\"}", &key(1)).unwrap());
+        assert_eq!(open(&blob, &key(1)).unwrap(), b"{\"license_id\":\"L-1\"}");
+        assert!(open(&blob, &key(2)).is_err());
+
+        let dir = std::env::temp_dir().join(format!("aegis-q-embed-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = generate(&dir, b"config", &key(1)).unwrap();
+        let first = fs::read_to_string(&path).unwrap();
+        generate(&dir, b"config", &key(1)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), first);
+        assert!(first.contains("#[no_mangle]\npub static AEGISQ_EMBEDDED_BLOB: [u8; "));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_find_in_image() {
+        let blob = seal(b"embedded config", &key(3)).unwrap();
+        // A stray copy of the magic before the blob, as in a real binary
+        let mut image = b"\x7fELF padding".to_vec();
+        image.extend_from_slice(&MAGIC);
+        image.extend_from_slice(&[1, 0xff, 0xff]);
+        image.extend_from_slice(&blob);
+        image.extend_from_slice(b"trailing sections");
+
+        assert_eq!(find(&image, &key(3)).unwrap(), b"embedded config");
+        assert!(matches!(find(&image, &key(4)), Err(AegisQError::NotFound(_))));
+        assert!(matches!(find(b"no blob here", &key(3)), Err(AegisQError::NotFound(_))));
+    }
+}
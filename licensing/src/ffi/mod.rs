@@ -0,0 +1,150 @@
+//! C interface to the vendor SDK (feature `ffi`)
+//!
+//! Mirrors `sdk::aegis_license_init` / `aegis_license_check` /
+//! `aegis_license_feature` with default `SdkConfig` settings. Functions
+//! return `AEGIS_LICENSE_OK` (0) or a negative `AEGIS_LICENSE_ERR_*` code;
+//! `aegis_license_feature` returns 1 or 0.
+//!
+//! # Security
+//!
+//! `vendor_key` is the license signing key itself: license signatures are
+//! a symmetric MAC, so there is no separate verify-only key. A program
+//! passing it here ships the key that forges licenses, and extracting it
+//! from the binary defeats the check. See `sdk` before embedding it.
+//!
+//! ```c
+//! if (aegis_license_init_path("license.json", VENDOR_KEY, sizeof VENDOR_KEY) != 0) exit(1);
+//! if (aegis_license_feature("pro")) enable_pro();
+//! ```
+
+use std::ffi::{c_char, CStr};
+use std::path::PathBuf;
+
+use utils::keys::SigningKey;
+
+use crate::sdk::{self, LicenseSource, SdkConfig, SdkError};
+
+pub const AEGIS_LICENSE_OK: i32 = 0;
+pub const AEGIS_LICENSE_ERR_INVALID_ARGUMENT: i32 = -1;
+pub const AEGIS_LICENSE_ERR_NOT_INITIALIZED: i32 = -2;
+pub const AEGIS_LICENSE_ERR_STORAGE: i32 = -3;
+pub const AEGIS_LICENSE_ERR_MALFORMED: i32 = -4;
+pub const AEGIS_LICENSE_ERR_INVALID_SIGNATURE: i32 = -5;
+pub const AEGIS_LICENSE_ERR_EXPIRED: i32 = -6;
+pub const AEGIS_LICENSE_ERR_CONSTRAINT: i32 = -7;
+pub const AEGIS_LICENSE_ERR_CLOCK_ROLLBACK: i32 = -8;
+
+fn code(result: Result<(), SdkError>) -> i32 {
+    match result {
+        Ok(()) => AEGIS_LICENSE_OK,
+        Err(SdkError::NotInitialized) => AEGIS_LICENSE_ERR_NOT_INITIALIZED,
+        Err(SdkError::Storage(_)) => AEGIS_LICENSE_ERR_STORAGE,
+        Err(SdkError::Malformed) => AEGIS_LICENSE_ERR_MALFORMED,
+        Err(SdkError::InvalidSignature) => AEGIS_LICENSE_ERR_INVALID_SIGNATURE,
+        Err(SdkError::Expired) => AEGIS_LICENSE_ERR_EXPIRED,
+        Err(SdkError::Constraint(_)) => AEGIS_LICENSE_ERR_CONSTRAINT,
+        Err(SdkError::ClockRollback { .. }) => AEGIS_LICENSE_ERR_CLOCK_ROLLBACK,
+    }
+}
+
+/// Borrow a caller buffer; `None` for a null pointer
+///
+/// # Safety
+/// `ptr` must be null or valid for reads of `len` bytes.
+unsafe fn bytes<'a>(ptr: *const u8, len: usize) -> Option<&'a [u8]> {
+    // SAFETY: guaranteed by the caller
+    (!ptr.is_null()).then(|| unsafe { std::slice::from_raw_parts(ptr, len) })
+}
+
+/// Borrow a NUL-terminated UTF-8 string; `None` for null or invalid UTF-8
+///
+/// # Safety
+/// `ptr` must be null or point to a NUL-terminated string.
+unsafe fn string<'a>(ptr: *const c_char) -> Option<&'a str> {
+    // SAFETY: guaranteed by the caller
+    (!ptr.is_null()).then(|| unsafe { CStr::from_ptr(ptr) }).and_then(|s| s.to_str().ok())
+}
+
+unsafe fn init(source: Option<LicenseSource>, vendor_key: *const u8, vendor_key_len: usize) -> i32 {
+    // SAFETY: forwarded from the exported function's contract
+    let (Some(source), Some(key)) = (source, unsafe { bytes(vendor_key, vendor_key_len) }) else {
+        return AEGIS_LICENSE_ERR_INVALID_ARGUMENT;
+    };
+    code(sdk::aegis_license_init(source, SdkConfig::new(SigningKey::from_bytes(key))))
+}
+
+/// Load a license from memory
+///
+/// `vendor_key` is the license signing key (see the module's security
+/// notes).
+///
+/// # Safety
+/// `license` must be valid for reads of `license_len` bytes and
+/// `vendor_key` for `vendor_key_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn aegis_license_init(
+    license: *const u8,
+    license_len: usize,
+    vendor_key: *const u8,
+    vendor_key_len: usize,
+) -> i32 {
+    // SAFETY: guaranteed by the caller
+    unsafe {
+        let source = bytes(license, license_len).map(LicenseSource::from);
+        init(source, vendor_key, vendor_key_len)
+    }
+}
+
+/// Load a license file
+///
+/// `vendor_key` is the license signing key (see the module's security
+/// notes).
+///
+/// # Safety
+/// `path` must be a NUL-terminated string and `vendor_key` valid for reads
+/// of `vendor_key_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn aegis_license_init_path(path: *const c_char, vendor_key: *const u8, vendor_key_len: usize) -> i32 {
+    // SAFETY: guaranteed by the caller
+    unsafe {
+        let source = string(path).map(|path| LicenseSource::Path(PathBuf::from(path)));
+        init(source, vendor_key, vendor_key_len)
+    }
+}
+
+/// Check the loaded license
+#[no_mangle]
+pub extern "C" fn aegis_license_check() -> i32 {
+    code(sdk::aegis_license_check())
+}
+
+/// 1 if `feature` is usable under the loaded license, else 0
+///
+/// # Safety
+/// `feature` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn aegis_license_feature(feature: *const c_char) -> i32 {
+    // SAFETY: guaranteed by the caller
+    unsafe { string(feature) }.is_some_and(sdk::aegis_license_feature) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ffi_arguments() {
+        let key = b"vendor-key";
+        unsafe {
+            assert_eq!(aegis_license_init(std::ptr::null(), 0, key.as_ptr(), key.len()), AEGIS_LICENSE_ERR_INVALID_ARGUMENT);
+            let junk = b"not json";
+            assert_eq!(aegis_license_init(junk.as_ptr(), junk.len(), key.as_ptr(), key.len()), AEGIS_LICENSE_ERR_MALFORMED);
+            assert_eq!(
+                aegis_license_init_path(c"/nonexistent/license.json".as_ptr(), key.as_ptr(), key.len()),
+                AEGIS_LICENSE_ERR_STORAGE
+            );
+            assert_eq!(aegis_license_feature(std::ptr::null()), 0);
+        }
+        assert_eq!(code(Err(SdkError::Expired)), AEGIS_LICENSE_ERR_EXPIRED);
+    }
+}
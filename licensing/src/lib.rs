@@ -3,9 +3,26 @@
 //! Key obfuscation, protected configuration, Aegis-Q envelope for license transmission
 //! Binary protection (embeddable module)
 
-use aegis_q_core::{aegis_q_encrypt, aegis_q_decrypt};
-use sha3::{Digest, Sha3_512};
+pub mod audit;
+pub mod agent;
+pub mod approval;
+pub mod breakglass;
+pub mod builder;
+pub mod ceremony;
+pub mod constraints;
+pub mod embed;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod sdk;
+pub mod signatures;
+pub mod transparency;
+
+use aegis_q_core::kms::{generate_data_key, unwrap_data_key, Kms, WrappedKey};
+use aegis_q_core::{AeadId, AegisQError, AegisQMac, SecurityPolicy, SignatureId, aegis_q_encrypt, aegis_q_encrypt_auto, aegis_q_decrypt};
 use serde::{Serialize, Deserialize};
+use utils::keys::{EncryptionKey, EnvelopeKey, SigningKey, TypedKey};
+use utils::rng::random_bytes;
+use utils::wire::{LicenseEnvelopeWire, LicenseFeatureWire, LicenseSignedWire, Wire};
 
 /// License key structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,59 +30,104 @@ pub struct License {
     pub license_id: String,
     pub features: Vec<String>,
     pub expiry: u64,
+    /// Seat count, for seat-limited licenses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seats: Option<u32>,
+    /// Activation constraints: countries, IP ranges, platforms (see `constraints`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub constraints: Vec<constraints::Constraint>,
     pub signature: Vec<u8>,
+    /// Issuer co-signatures for dual control (see `approval`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub approvals: Vec<approval::Approval>,
 }
 
 impl License {
+    /// Registry ID of the license signature (`sign`/`verify`)
+    pub const SIGNATURE_ALGORITHM: SignatureId = SignatureId::AegisQMac;
+
     /// Create new license
     pub fn new(license_id: String, features: Vec<String>, expiry: u64) -> Self {
         Self {
             license_id,
             features,
             expiry,
+            seats: None,
+            constraints: Vec::new(),
             signature: Vec::new(),
+            approvals: Vec::new(),
         }
     }
+
+    /// Start a validated license (see `builder::LicenseBuilder`)
+    pub fn builder(license_id: impl Into<String>) -> builder::LicenseBuilder {
+        builder::LicenseBuilder::new(license_id)
+    }
     
     /// Sign license
-    pub fn sign(&mut self, signing_key: &[u8]) {
-        let mut hasher = Sha3_512::new();
-        // Domain separation for license signing
-        hasher.update(b"aegis-q-license-sign");
-        hasher.update(signing_key);
-        hasher.update(&self.license_id.as_bytes());
-        for feature in &self.features {
-            hasher.update(feature.as_bytes());
-        }
-        hasher.update(&self.expiry.to_le_bytes());
-        self.signature = hasher.finalize().to_vec();
+    pub fn sign(&mut self, signing_key: &SigningKey) {
+        self.signature = self.signature_with(&signing_mac(signing_key));
     }
     
     /// Verify license signature
-    pub fn verify(&self, signing_key: &[u8]) -> bool {
-        let mut hasher = Sha3_512::new();
-        // Domain separation for license signing
-        hasher.update(b"aegis-q-license-sign");
-        hasher.update(signing_key);
-        hasher.update(&self.license_id.as_bytes());
-        for feature in &self.features {
-            hasher.update(feature.as_bytes());
+    pub fn verify(&self, signing_key: &SigningKey) -> bool {
+        self.verify_with(&signing_mac(signing_key))
+    }
+
+    /// Verify under a deployment policy
+    ///
+    /// Fails with `DowngradeRejected` if the signature algorithm is below
+    /// the policy level, and `AuthenticationFailed` if the signature is bad.
+    pub fn verify_with_policy(&self, signing_key: &SigningKey, policy: &SecurityPolicy) -> Result<(), AegisQError> {
+        policy.check_signature(Self::SIGNATURE_ALGORITHM)?;
+        if !self.verify(signing_key) {
+            return Err(AegisQError::AuthenticationFailed);
         }
-        hasher.update(&self.expiry.to_le_bytes());
-        let computed = hasher.finalize();
-        
-        // Constant-time comparison
-        let mut result = 0u8;
-        for (a, b) in computed.iter().zip(self.signature.iter()) {
-            result |= a ^ b;
+        Ok(())
+    }
+    
+    /// Signature under a keyed MAC (see `signing_mac`)
+    pub(crate) fn signature_with(&self, mac: &AegisQMac) -> Vec<u8> {
+        mac.mac(&self.signed_bytes(SIGN_DOMAIN, "", &[])).to_vec()
+    }
+
+    /// Fields covered by a signature, each length-prefixed (`LicenseSignedWire`)
+    pub(crate) fn signed_bytes(&self, domain: &[u8], issuer: &str, previous: &[u8]) -> Vec<u8> {
+        LicenseSignedWire {
+            domain: domain.to_vec(),
+            issuer: issuer.to_string(),
+            previous: previous.to_vec(),
+            license_id: self.license_id.clone(),
+            expiry: self.expiry,
+            seats: self.seats,
+            constraints: self.constraints.iter().map(constraints::Constraint::wire).collect(),
+            features: self.features.iter().map(|name| LicenseFeatureWire { name: name.clone() }).collect(),
         }
-        result == 0
+        .to_wire()
+        .expect("license fields fit 32-bit length prefixes")
     }
+    
+    /// Verify against a keyed MAC
+    pub(crate) fn verify_with(&self, mac: &AegisQMac) -> bool {
+        mac.verify(&self.signed_bytes(SIGN_DOMAIN, "", &[]), &self.signature).is_ok()
+    }
+}
+
+/// Domain of the vendor signature
+const SIGN_DOMAIN: &[u8] = b"aegis-q-license-sign";
+
+/// MAC keyed with the signing key
+///
+/// Sharing it across licenses lets batch verification derive the keyed
+/// state once.
+pub(crate) fn signing_mac(signing_key: &SigningKey) -> AegisQMac {
+    AegisQMac::new(signing_key.as_bytes())
 }
 
 /// Obfuscated key storage
 pub struct ObfuscatedKey {
     encrypted_key: Vec<u8>,
+    nonce: Vec<u8>,
     obfuscation_seed: Vec<u8>,
 }
 
@@ -73,19 +135,18 @@ impl ObfuscatedKey {
     /// Create obfuscated key
     pub fn new(key: &[u8], obfuscation_seed: &[u8]) -> Self {
         // Encrypt key using Aegis-Q
-        let nonce = vec![0u8; 16];
-        let encrypted_key = aegis_q_encrypt(obfuscation_seed, &nonce, key);
+        let (nonce, encrypted_key) = aegis_q_encrypt_auto(obfuscation_seed, key);
         
         Self {
             encrypted_key,
+            nonce,
             obfuscation_seed: obfuscation_seed.to_vec(),
         }
     }
     
     /// Retrieve deobfuscated key
-    pub fn deobfuscate(&self) -> Result<Vec<u8>, &'static str> {
-        let nonce = vec![0u8; 16];
-        aegis_q_decrypt(&self.obfuscation_seed, &nonce, &self.encrypted_key)
+    pub fn deobfuscate(&self) -> Result<Vec<u8>, AegisQError> {
+        aegis_q_decrypt(&self.obfuscation_seed, &self.nonce, &self.encrypted_key)
     }
 }
 
@@ -94,62 +155,216 @@ impl ObfuscatedKey {
 pub struct ProtectedConfig {
     encrypted_config: Vec<u8>,
     config_nonce: Vec<u8>,
+    /// Config key under the recovery key (see `breakglass`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    recovery: Option<breakglass::RecoveryWrap>,
+    /// Config key wrapped by a KMS (see `with_kms`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kms: Option<KmsWrap>,
+}
+
+/// Config key wrapped by a KMS master key, bound to a config ID
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KmsWrap {
+    pub config_id: String,
+    pub kms_key_id: String,
+    wrapped_key: Vec<u8>,
+}
+
+/// KMS wrap context of a config
+fn kms_context(config_id: &str) -> Vec<u8> {
+    [b"aegis-q-protected-config:".as_slice(), config_id.as_bytes()].concat()
 }
 
 impl ProtectedConfig {
     /// Create protected configuration
-    pub fn new(config_data: &[u8], config_key: &[u8]) -> Self {
-        let config_nonce = vec![0u8; 16];
-        let encrypted_config = aegis_q_encrypt(config_key, &config_nonce, config_data);
+    pub fn new(config_data: &[u8], config_key: &EncryptionKey) -> Self {
+        let (config_nonce, encrypted_config) = aegis_q_encrypt_auto(config_key.as_bytes(), config_data);
         
         Self {
             encrypted_config,
             config_nonce,
+            recovery: None,
+            kms: None,
         }
     }
     
+    /// Protect under a fresh config key generated locally and wrapped by `kms`
+    ///
+    /// Only the wrapped key is stored; opening needs the KMS
+    /// (`retrieve_with_kms`). The config ID is bound into the wrap.
+    pub fn with_kms(config_data: &[u8], kms: &dyn Kms, config_id: &str) -> Result<Self, AegisQError> {
+        let (config_key, wrapped) = generate_data_key(kms, &kms_context(config_id))?;
+        let mut config = Self::new(config_data, &config_key);
+        config.kms = Some(KmsWrap {
+            config_id: config_id.to_string(),
+            kms_key_id: wrapped.kms_key_id,
+            wrapped_key: wrapped.blob,
+        });
+        Ok(config)
+    }
+
     /// Retrieve configuration
-    pub fn retrieve(&self, config_key: &[u8]) -> Result<Vec<u8>, &'static str> {
-        aegis_q_decrypt(config_key, &self.config_nonce, &self.encrypted_config)
+    pub fn retrieve(&self, config_key: &EncryptionKey) -> Result<Vec<u8>, AegisQError> {
+        aegis_q_decrypt(config_key.as_bytes(), &self.config_nonce, &self.encrypted_config)
+    }
+
+    /// Unwrap the config key through `kms` (e.g. for `enable_recovery`)
+    pub fn config_key_from_kms(&self, kms: &dyn Kms) -> Result<EncryptionKey, AegisQError> {
+        let wrap = self.kms.as_ref().ok_or(AegisQError::NotFound("Config is not KMS-wrapped"))?;
+        let wrapped = WrappedKey {
+            kms_key_id: wrap.kms_key_id.clone(),
+            blob: wrap.wrapped_key.clone(),
+        };
+        unwrap_data_key(kms, &wrapped, &kms_context(&wrap.config_id))
+    }
+
+    /// Retrieve configuration with the key unwrapped by `kms`
+    pub fn retrieve_with_kms(&self, kms: &dyn Kms) -> Result<Vec<u8>, AegisQError> {
+        self.retrieve(&self.config_key_from_kms(kms)?)
+    }
+
+    /// KMS wrap, if the config key is KMS-wrapped
+    pub fn kms(&self) -> Option<&KmsWrap> {
+        self.kms.as_ref()
+    }
+}
+
+/// Current envelope format version
+pub const ENVELOPE_VERSION: u8 = 1;
+
+/// Envelope nonce length
+pub const ENVELOPE_NONCE_LEN: usize = 16;
+
+/// Encryption algorithm of a license envelope
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EnvelopeAlgorithm {
+    /// Aegis-Q AEAD (`aegis_q_encrypt`)
+    AegisQ,
+}
+
+impl EnvelopeAlgorithm {
+    /// Wire identifier
+    pub fn id(self) -> u8 {
+        match self {
+            EnvelopeAlgorithm::AegisQ => 1,
+        }
+    }
+
+    /// AEAD profile in the algorithm registry
+    pub fn aead(self) -> AeadId {
+        match self {
+            EnvelopeAlgorithm::AegisQ => AeadId::AegisQ256,
+        }
+    }
+
+    /// Algorithm for a wire identifier
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(EnvelopeAlgorithm::AegisQ),
+            _ => None,
+        }
     }
 }
 
 /// Aegis-Q envelope for license transmission
+///
+/// Binary form (`to_bytes`): version || algorithm || nonce length || nonce
+/// || ciphertext, see `utils::wire::LicenseEnvelopeWire`. The serde form
+/// carries the same fields, for JSON transports. Both are rejected on
+/// extraction if the version or algorithm is unknown.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct LicenseEnvelope {
-    encrypted_license: Vec<u8>,
+    version: u8,
+    algorithm: EnvelopeAlgorithm,
     envelope_nonce: Vec<u8>,
+    encrypted_license: Vec<u8>,
 }
 
 impl LicenseEnvelope {
-    /// Create license envelope
-    pub fn create(license: &License, envelope_key: &[u8]) -> Result<Self, &'static str> {
+    /// Create license envelope (fresh random nonce)
+    pub fn create(license: &License, envelope_key: &EnvelopeKey) -> Result<Self, AegisQError> {
         let license_bytes = serde_json::to_vec(license)
-            .map_err(|_| "Serialization failed")?;
+            .map_err(|_| AegisQError::Serialization("Serialization failed"))?;
         
-        let envelope_nonce = vec![0u8; 16];
-        let encrypted_license = aegis_q_encrypt(envelope_key, &envelope_nonce, &license_bytes);
+        let envelope_nonce = random_bytes(ENVELOPE_NONCE_LEN);
+        let encrypted_license = aegis_q_encrypt(envelope_key.as_bytes(), &envelope_nonce, &license_bytes);
         
         Ok(Self {
-            encrypted_license,
+            version: ENVELOPE_VERSION,
+            algorithm: EnvelopeAlgorithm::AegisQ,
             envelope_nonce,
+            encrypted_license,
         })
     }
     
     /// Extract license from envelope
-    pub fn extract(&self, envelope_key: &[u8]) -> Result<License, &'static str> {
-        let license_bytes = aegis_q_decrypt(envelope_key, &self.envelope_nonce, &self.encrypted_license)?;
+    pub fn extract(&self, envelope_key: &EnvelopeKey) -> Result<License, AegisQError> {
+        if self.version != ENVELOPE_VERSION {
+            return Err(AegisQError::Unsupported("Unsupported envelope version"));
+        }
+        let license_bytes = match self.algorithm {
+            EnvelopeAlgorithm::AegisQ => {
+                aegis_q_decrypt(envelope_key.as_bytes(), &self.envelope_nonce, &self.encrypted_license)?
+            }
+        };
         serde_json::from_slice(&license_bytes)
-            .map_err(|_| "Deserialization failed")
+            .map_err(|_| AegisQError::Serialization("Deserialization failed"))
+    }
+
+    /// Extract, first rejecting envelopes whose algorithm is below `policy`
+    pub fn extract_with_policy(&self, envelope_key: &EnvelopeKey, policy: &SecurityPolicy) -> Result<License, AegisQError> {
+        policy.check_aead(self.algorithm.aead())?;
+        self.extract(envelope_key)
+    }
+
+    /// Format version
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Encryption algorithm
+    pub fn algorithm(&self) -> EnvelopeAlgorithm {
+        self.algorithm
+    }
+
+    /// Encode to the binary wire format
+    pub fn to_bytes(&self) -> Result<Vec<u8>, AegisQError> {
+        LicenseEnvelopeWire {
+            version: self.version,
+            algorithm: self.algorithm.id(),
+            nonce: self.envelope_nonce.clone(),
+            ciphertext: self.encrypted_license.clone(),
+        }
+        .to_wire()
+        .map_err(AegisQError::Serialization)
+    }
+
+    /// Decode from the binary wire format
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, AegisQError> {
+        let wire = LicenseEnvelopeWire::from_wire(bytes).map_err(AegisQError::Serialization)?;
+        if wire.version != ENVELOPE_VERSION {
+            return Err(AegisQError::Unsupported("Unsupported envelope version"));
+        }
+        let algorithm = EnvelopeAlgorithm::from_id(wire.algorithm).ok_or(AegisQError::Unsupported("Unsupported envelope algorithm"))?;
+        Ok(Self {
+            version: wire.version,
+            algorithm,
+            envelope_nonce: wire.nonce,
+            encrypted_license: wire.ciphertext,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use aegis_q_core::LocalKms;
     
     #[test]
     fn test_license_sign_verify() {
-        let signing_key = b"signing-key";
+        let signing_key = &SigningKey::from_bytes(b"signing-key");
         let mut license = License::new(
             "test-license".to_string(),
             vec!["feature1".to_string(), "feature2".to_string()],
@@ -158,8 +373,28 @@ mod tests {
         
         license.sign(signing_key);
         assert!(license.verify(signing_key));
+        
+        license.signature.clear();
+        assert!(!license.verify(signing_key));
     }
-    
+
+    #[test]
+    fn test_signature_separates_fields() {
+        let signing_key = &SigningKey::from_bytes(b"signing-key");
+        let features = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        let mut license = License::new("id".to_string(), features(&["ab", "c"]), 1234567890);
+        license.sign(signing_key);
+
+        let mut regrouped = license.clone();
+        regrouped.features = features(&["a", "bc"]);
+        assert!(!regrouped.verify(signing_key));
+
+        let mut shifted = license.clone();
+        shifted.license_id = "ida".to_string();
+        shifted.features = features(&["b", "c"]);
+        assert!(!shifted.verify(signing_key));
+    }
+
     #[test]
     fn test_obfuscated_key() {
         let key = b"secret-key-123456789012345678901234567890";
@@ -171,20 +406,98 @@ mod tests {
         assert_eq!(key, deobf.as_slice());
     }
     
+    #[test]
+    fn test_protected_config_with_kms() {
+        let kms = LocalKms::new("config-master", EncryptionKey::from_bytes(b"kms-master-key-0123456789abcdef0"));
+        let config = ProtectedConfig::with_kms(b"db_password=secret", &kms, "prod-db").unwrap();
+        assert_eq!(config.kms().unwrap().kms_key_id, "config-master");
+
+        let stored: ProtectedConfig = serde_json::from_str(&serde_json::to_string(&config).unwrap()).unwrap();
+        assert_eq!(stored.retrieve_with_kms(&kms).unwrap(), b"db_password=secret");
+        let config_key = stored.config_key_from_kms(&kms).unwrap();
+        assert_eq!(stored.retrieve(&config_key).unwrap(), b"db_password=secret");
+
+        let other = LocalKms::new("config-master", EncryptionKey::from_bytes(b"another-master-key-0123456789abc"));
+        assert!(stored.retrieve_with_kms(&other).is_err());
+        assert!(ProtectedConfig::new(b"plain", &config_key).retrieve_with_kms(&kms).is_err());
+    }
+
     #[test]
     fn test_license_envelope() {
-        let envelope_key = b"envelope-key-123456789012345678901234567890";
+        let envelope_key = &EnvelopeKey::from_bytes(b"envelope-key-123456789012345678901234567890");
         let mut license = License::new(
             "test-license".to_string(),
             vec!["feature1".to_string()],
             1234567890,
         );
-        license.sign(b"signing-key");
+        license.sign(&SigningKey::from_bytes(b"signing-key"));
         
         let envelope = LicenseEnvelope::create(&license, envelope_key).unwrap();
         let extracted = envelope.extract(envelope_key).unwrap();
         
         assert_eq!(license.license_id, extracted.license_id);
+        assert_eq!(envelope.algorithm().aead(), AeadId::AegisQ256);
+        assert_eq!(License::SIGNATURE_ALGORITHM.name(), "Aegis-Q-MAC");
     }
-}
 
+    #[test]
+    fn test_policy_checked_extract_and_verify() {
+        use aegis_q_core::SecurityLevel;
+
+        let envelope_key = &EnvelopeKey::from_bytes(b"envelope-key-123456789012345678901234567890");
+        let signing_key = &SigningKey::from_bytes(b"signing-key");
+        let mut license = License::new("test-license".to_string(), vec!["pro".to_string()], 1234567890);
+        license.sign(signing_key);
+
+        let strict = SecurityPolicy::new(SecurityLevel::L256);
+        let envelope = LicenseEnvelope::create(&license, envelope_key).unwrap();
+        let extracted = envelope.extract_with_policy(envelope_key, &strict).unwrap();
+        extracted.verify_with_policy(signing_key, &strict).unwrap();
+
+        license.expiry += 1;
+        assert_eq!(license.verify_with_policy(signing_key, &strict), Err(AegisQError::AuthenticationFailed));
+        assert!(strict.check_aead(AeadId::AegisQ128).is_err());
+    }
+    
+    #[test]
+    fn test_envelope_wire_and_json_round_trip() {
+        let envelope_key = &EnvelopeKey::from_bytes(b"envelope-key-123456789012345678901234567890");
+        let license = License::new("test-license".to_string(), vec!["pro".to_string()], 1234567890);
+        let envelope = LicenseEnvelope::create(&license, envelope_key).unwrap();
+        
+        let bytes = envelope.to_bytes().unwrap();
+        assert_eq!(&bytes[..3], &[ENVELOPE_VERSION, 1, ENVELOPE_NONCE_LEN as u8]);
+        let decoded = LicenseEnvelope::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, envelope);
+        assert_eq!(decoded.extract(envelope_key).unwrap().features, license.features);
+        
+        let json = serde_json::to_string(&envelope).unwrap();
+        assert!(json.contains("\"algorithm\":\"aegis-q\""));
+        let from_json: LicenseEnvelope = serde_json::from_str(&json).unwrap();
+        assert_eq!(from_json, envelope);
+        
+        // Fresh nonce per envelope
+        assert_ne!(LicenseEnvelope::create(&license, envelope_key).unwrap().envelope_nonce, envelope.envelope_nonce);
+    }
+    
+    #[test]
+    fn test_envelope_rejects_unknown_versions() {
+        let envelope_key = &EnvelopeKey::from_bytes(b"envelope-key-123456789012345678901234567890");
+        let license = License::new("test-license".to_string(), vec!["pro".to_string()], 1234567890);
+        let bytes = LicenseEnvelope::create(&license, envelope_key).unwrap().to_bytes().unwrap();
+        
+        let mut future = bytes.clone();
+        future[0] = ENVELOPE_VERSION + 1;
+        assert_eq!(LicenseEnvelope::from_bytes(&future), Err(AegisQError::Unsupported("Unsupported envelope version")));
+        let mut unknown_algorithm = bytes.clone();
+        unknown_algorithm[1] = 0xff;
+        assert_eq!(LicenseEnvelope::from_bytes(&unknown_algorithm), Err(AegisQError::Unsupported("Unsupported envelope algorithm")));
+        assert!(LicenseEnvelope::from_bytes(&bytes[..2]).is_err());
+        
+        // Same checks apply to the serde form
+        let mut envelope = LicenseEnvelope::from_bytes(&bytes).unwrap();
+        envelope.version = ENVELOPE_VERSION + 1;
+        let envelope: LicenseEnvelope = serde_json::from_str(&serde_json::to_string(&envelope).unwrap()).unwrap();
+        assert_eq!(envelope.extract(envelope_key).unwrap_err(), AegisQError::Unsupported("Unsupported envelope version"));
+    }
+}
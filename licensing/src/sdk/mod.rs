@@ -0,0 +1,416 @@
+//! Vendor SDK facade
+//!
+//! Three calls for applications embedding license checks:
+//! - `aegis_license_init(source, config)` loads the license (file path or
+//!   bytes) and verifies it against the vendor key
+//! - `aegis_license_check()` re-checks signature, expiry and activation
+//!   constraints, with the result cached for `SdkConfig::cache_ttl`
+//! - `aegis_license_feature("x")` tells whether a feature is usable now
+//!
+//! Internally the SDK binds a small state record to the device fingerprint
+//! (when `SdkConfig::state_path` is set) holding the time of the last
+//! successful check, so moving the clock back to revive an expired license
+//! is detected. The same calls are exported over C in `ffi`.
+//!
+//! # Security
+//!
+//! License signatures are `aegis_q_mac` tags, a symmetric MAC: the key
+//! that verifies a license is the key that signs one. Every binary built
+//! with this SDK therefore carries the vendor signing key, and anyone who
+//! extracts it from the binary can mint licenses for every product signed
+//! with it. Use the SDK only where that is acceptable (e.g. a per-product
+//! key and licenses as a deterrent, not a security boundary); a
+//! verify-only client needs an asymmetric license signature, which the
+//! format does not have yet. Never embed a key shared with other products
+//! or with the issuing service.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+use sha3::{Digest, Sha3_256};
+use utils::keys::{SigningKey, TypedKey};
+
+use crate::builder::normalize_feature;
+use crate::constraints::{ActivationContext, ConstraintError, ConstraintEvaluator, DefaultEvaluator};
+use crate::License;
+
+/// Default lifetime of a successful check
+pub const DEFAULT_CACHE_TTL_SECS: u64 = 3600;
+
+/// Clock skew tolerated before a rollback is reported
+const ROLLBACK_TOLERANCE_SECS: u64 = 300;
+
+/// Where the license comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LicenseSource {
+    /// License file (JSON)
+    Path(PathBuf),
+    /// License contents (JSON)
+    Bytes(Vec<u8>),
+}
+
+impl From<&Path> for LicenseSource {
+    fn from(path: &Path) -> Self {
+        LicenseSource::Path(path.to_path_buf())
+    }
+}
+
+impl From<PathBuf> for LicenseSource {
+    fn from(path: PathBuf) -> Self {
+        LicenseSource::Path(path)
+    }
+}
+
+impl From<&[u8]> for LicenseSource {
+    fn from(bytes: &[u8]) -> Self {
+        LicenseSource::Bytes(bytes.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for LicenseSource {
+    fn from(bytes: Vec<u8>) -> Self {
+        LicenseSource::Bytes(bytes)
+    }
+}
+
+/// Typed SDK failure
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SdkError {
+    /// `aegis_license_init` has not succeeded yet
+    NotInitialized,
+    /// License file or state record could not be read or written
+    Storage(String),
+    /// License contents are not a valid license
+    Malformed,
+    /// Vendor signature does not verify
+    InvalidSignature,
+    /// License expiry is in the past
+    Expired,
+    /// Activation constraint not met
+    Constraint(ConstraintError),
+    /// Clock is earlier than a previous successful check
+    ClockRollback { last_check: u64, now: u64 },
+}
+
+impl fmt::Display for SdkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SdkError::NotInitialized => write!(f, "license SDK not initialized"),
+            SdkError::Storage(detail) => write!(f, "license storage: {}", detail),
+            SdkError::Malformed => write!(f, "license malformed"),
+            SdkError::InvalidSignature => write!(f, "license signature invalid"),
+            SdkError::Expired => write!(f, "license expired"),
+            SdkError::Constraint(err) => write!(f, "{}", err),
+            SdkError::ClockRollback { last_check, now } => {
+                write!(f, "clock rolled back: {} is before last check at {}", now, last_check)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SdkError {}
+
+/// Embedder settings
+#[derive(Debug, Clone)]
+pub struct SdkConfig {
+    signing_key: SigningKey,
+    cache_ttl_secs: u64,
+    state_path: Option<PathBuf>,
+    context: ActivationContext,
+    evaluator: Arc<dyn ConstraintEvaluator>,
+}
+
+impl SdkConfig {
+    /// Verify licenses with the vendor `signing_key`
+    ///
+    /// The same key signs licenses (see the module's security notes): it
+    /// ends up in the embedding binary and can be extracted to forge them.
+    ///
+    /// Defaults: one-hour cache, no state record, the local platform as
+    /// activation context, `DefaultEvaluator`.
+    pub fn new(signing_key: SigningKey) -> Self {
+        Self {
+            signing_key,
+            cache_ttl_secs: DEFAULT_CACHE_TTL_SECS,
+            state_path: None,
+            context: ActivationContext::local(),
+            evaluator: Arc::new(DefaultEvaluator),
+        }
+    }
+
+    /// How long a successful check is reused (0 = check every time)
+    pub fn cache_ttl(mut self, secs: u64) -> Self {
+        self.cache_ttl_secs = secs;
+        self
+    }
+
+    /// File for the device-bound state record (clock rollback detection)
+    pub fn state_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.state_path = Some(path.into());
+        self
+    }
+
+    /// Activation context for constraint checks
+    pub fn context(mut self, context: ActivationContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Constraint evaluator
+    pub fn evaluator(mut self, evaluator: impl ConstraintEvaluator + 'static) -> Self {
+        self.evaluator = Arc::new(evaluator);
+        self
+    }
+}
+
+/// Stable identifier of this device
+///
+/// SHA3-256 over the OS, architecture, machine id and host name, whichever
+/// are available.
+pub fn device_fingerprint() -> Vec<u8> {
+    let read = |path: &str| std::fs::read_to_string(path).map(|s| s.trim().to_string()).unwrap_or_default();
+    let machine_id = [read("/etc/machine-id"), read("/var/lib/dbus/machine-id")]
+        .into_iter()
+        .find(|id| !id.is_empty())
+        .unwrap_or_default();
+    let host = std::env::var("COMPUTERNAME").unwrap_or_else(|_| read("/etc/hostname"));
+
+    let mut hasher = Sha3_256::new();
+    hasher.update(b"aegis-q-device");
+    for part in [std::env::consts::OS, std::env::consts::ARCH, &machine_id, &host] {
+        hasher.update((part.len() as u32).to_le_bytes());
+        hasher.update(part.as_bytes());
+    }
+    hasher.finalize().to_vec()
+}
+
+/// Loaded license with its checking policy
+#[derive(Debug)]
+pub struct LicenseSdk {
+    license: License,
+    config: SdkConfig,
+    fingerprint: Vec<u8>,
+    /// Time of the last successful check
+    checked_at: Mutex<Option<u64>>,
+}
+
+impl LicenseSdk {
+    /// Load and check a license
+    pub fn init(source: impl Into<LicenseSource>, config: SdkConfig) -> Result<Self, SdkError> {
+        Self::init_at(source, config, unix_now())
+    }
+
+    /// `init` at time `now`
+    pub fn init_at(source: impl Into<LicenseSource>, config: SdkConfig, now: u64) -> Result<Self, SdkError> {
+        let bytes = match source.into() {
+            LicenseSource::Path(path) => std::fs::read(&path).map_err(|e| SdkError::Storage(e.to_string()))?,
+            LicenseSource::Bytes(bytes) => bytes,
+        };
+        let license: License = serde_json::from_slice(&bytes).map_err(|_| SdkError::Malformed)?;
+        let sdk = Self {
+            license,
+            config,
+            fingerprint: device_fingerprint(),
+            checked_at: Mutex::new(None),
+        };
+        sdk.check_at(now)?;
+        Ok(sdk)
+    }
+
+    /// The loaded license
+    pub fn license(&self) -> &License {
+        &self.license
+    }
+
+    /// Check the license now
+    pub fn check(&self) -> Result<(), SdkError> {
+        self.check_at(unix_now())
+    }
+
+    /// Check the license at time `now`, reusing a recent successful check
+    pub fn check_at(&self, now: u64) -> Result<(), SdkError> {
+        let mut checked_at = self.checked_at.lock().unwrap();
+        if let Some(at) = *checked_at {
+            if now >= at && now - at < self.config.cache_ttl_secs && now <= self.license.expiry {
+                return Ok(());
+            }
+        }
+        *checked_at = None;
+
+        if !self.license.verify(&self.config.signing_key) {
+            return Err(SdkError::InvalidSignature);
+        }
+        if now > self.license.expiry {
+            return Err(SdkError::Expired);
+        }
+        self.license
+            .check_constraints(self.config.evaluator.as_ref(), &self.config.context)
+            .map_err(SdkError::Constraint)?;
+        self.record_check(now)?;
+
+        *checked_at = Some(now);
+        Ok(())
+    }
+
+    /// Whether `feature` is licensed and the license currently checks out
+    pub fn feature(&self, feature: &str) -> bool {
+        let Ok(feature) = normalize_feature(feature) else {
+            return false;
+        };
+        self.license.features.contains(&feature) && self.check().is_ok()
+    }
+
+    /// Compare `now` with the state record, then store it
+    fn record_check(&self, now: u64) -> Result<(), SdkError> {
+        let Some(path) = &self.config.state_path else {
+            return Ok(());
+        };
+
+        let mut last_check = 0;
+        match std::fs::read(path) {
+            Ok(record) => {
+                let (time, mac) = record.split_at(record.len().min(8));
+                let time = u64::from_le_bytes(time.try_into().map_err(|_| SdkError::Storage("state record truncated".into()))?);
+                // Records from another device or license do not verify
+                if mac == self.state_mac(time).as_slice() {
+                    last_check = time;
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(SdkError::Storage(e.to_string())),
+        }
+        if now.saturating_add(ROLLBACK_TOLERANCE_SECS) < last_check {
+            return Err(SdkError::ClockRollback { last_check, now });
+        }
+
+        let time = now.max(last_check);
+        let mut record = time.to_le_bytes().to_vec();
+        record.extend_from_slice(&self.state_mac(time));
+        std::fs::write(path, record).map_err(|e| SdkError::Storage(e.to_string()))
+    }
+
+    fn state_mac(&self, time: u64) -> Vec<u8> {
+        let mut hasher = Sha3_256::new();
+        hasher.update(b"aegis-q-sdk-state");
+        hasher.update(self.config.signing_key.as_bytes());
+        hasher.update(&self.fingerprint);
+        hasher.update(&self.license.signature);
+        hasher.update(time.to_le_bytes());
+        hasher.finalize().to_vec()
+    }
+}
+
+static SDK: RwLock<Option<LicenseSdk>> = RwLock::new(None);
+
+/// Load the license for this process (replaces any earlier one)
+pub fn aegis_license_init(source: impl Into<LicenseSource>, config: SdkConfig) -> Result<(), SdkError> {
+    let sdk = LicenseSdk::init(source, config)?;
+    *SDK.write().unwrap() = Some(sdk);
+    Ok(())
+}
+
+/// Check the process license
+pub fn aegis_license_check() -> Result<(), SdkError> {
+    SDK.read().unwrap().as_ref().ok_or(SdkError::NotInitialized)?.check()
+}
+
+/// Whether `feature` is usable under the process license
+pub fn aegis_license_feature(feature: &str) -> bool {
+    SDK.read().unwrap().as_ref().is_some_and(|sdk| sdk.feature(feature))
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constraints::Constraint;
+
+    const VENDOR: &[u8] = b"vendor-key";
+
+    fn license_bytes(expiry: u64, constraints: Vec<Constraint>) -> Vec<u8> {
+        let mut license = License::new("lic-1".to_string(), vec!["pro".to_string()], expiry);
+        license.constraints = constraints;
+        license.sign(&SigningKey::from_bytes(VENDOR));
+        serde_json::to_vec(&license).unwrap()
+    }
+
+    fn config() -> SdkConfig {
+        SdkConfig::new(SigningKey::from_bytes(VENDOR))
+    }
+
+    #[test]
+    fn test_init_check_feature() {
+        let sdk = LicenseSdk::init_at(license_bytes(2000, Vec::new()), config(), 1000).unwrap();
+        assert!(sdk.check_at(1500).is_ok());
+        assert_eq!(sdk.check_at(2001), Err(SdkError::Expired));
+
+        let dir = std::env::temp_dir().join(format!("aegis-q-sdk-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("license.json");
+        std::fs::write(&path, license_bytes(u64::MAX, Vec::new())).unwrap();
+        let sdk = LicenseSdk::init(path.as_path(), config()).unwrap();
+        assert!(sdk.feature("pro"));
+        assert!(sdk.feature(" PRO "));
+        assert!(!sdk.feature("enterprise"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let wrong_key = SdkConfig::new(SigningKey::from_bytes(b"other-key"));
+        assert_eq!(
+            LicenseSdk::init_at(license_bytes(2000, Vec::new()), wrong_key, 1000).unwrap_err(),
+            SdkError::InvalidSignature
+        );
+        assert_eq!(LicenseSdk::init_at(&b"not json"[..], config(), 0).unwrap_err(), SdkError::Malformed);
+        assert!(matches!(
+            LicenseSdk::init(PathBuf::from("/nonexistent/license.json"), config()),
+            Err(SdkError::Storage(_))
+        ));
+
+        let geo = license_bytes(2000, vec![Constraint::countries(["DE"]).unwrap()]);
+        assert_eq!(
+            LicenseSdk::init_at(geo.clone(), config(), 1000).unwrap_err(),
+            SdkError::Constraint(ConstraintError::Unknown("country"))
+        );
+        let german = config().context(ActivationContext::local().country("de"));
+        assert!(LicenseSdk::init_at(geo, german, 1000).is_ok());
+    }
+
+    #[test]
+    fn test_cache_and_clock_rollback() {
+        let dir = std::env::temp_dir().join(format!("aegis-q-sdk-state-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let state = dir.join("state");
+        let with_state = || config().cache_ttl(100).state_path(&state);
+
+        let sdk = LicenseSdk::init_at(license_bytes(100_000, Vec::new()), with_state(), 50_000).unwrap();
+        assert!(sdk.check_at(50_050).is_ok());
+
+        // A fresh instance sees the recorded check time
+        let sdk = LicenseSdk::init_at(license_bytes(100_000, Vec::new()), with_state(), 50_000).unwrap();
+        assert_eq!(sdk.check_at(10_000), Err(SdkError::ClockRollback { last_check: 50_000, now: 10_000 }));
+
+        // A record written for another license is ignored
+        let mut other = License::new("lic-2".to_string(), vec!["pro".to_string()], 100_000);
+        other.sign(&SigningKey::from_bytes(VENDOR));
+        let other = serde_json::to_vec(&other).unwrap();
+        assert!(LicenseSdk::init_at(other, with_state(), 10_000).is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_global_facade() {
+        assert_eq!(aegis_license_check(), Err(SdkError::NotInitialized));
+        assert!(!aegis_license_feature("pro"));
+
+        aegis_license_init(license_bytes(u64::MAX, Vec::new()), config()).unwrap();
+        assert_eq!(aegis_license_check(), Ok(()));
+        assert!(aegis_license_feature("pro"));
+        assert!(!aegis_license_feature("enterprise"));
+    }
+}
@@ -0,0 +1,163 @@
+//! License Signatures
+//!
+//! Batch verification for servers checking many licenses at once.
+//! The keyed MAC state is derived once and shared by every license,
+//! and large batches are split across worker threads. Small batches (or a
+//! single thread) fall back to sequential verification.
+//!
+//! License signatures are Aegis-Q MACs (`aegis_q_mac`), which admit no
+//! aggregation: each signature is still checked individually, and a batch
+//! reports exactly which licenses failed.
+
+use std::thread;
+
+use utils::keys::SigningKey;
+
+use crate::{signing_mac, License};
+
+/// Batches smaller than this are verified sequentially
+pub const DEFAULT_MIN_PARALLEL_BATCH: usize = 256;
+
+/// Outcome of a batch verification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchResult {
+    /// Number of licenses checked
+    pub total: usize,
+    /// Indices (into the input) of licenses that failed verification
+    pub invalid: Vec<usize>,
+}
+
+impl BatchResult {
+    /// Whether every license verified
+    pub fn all_valid(&self) -> bool {
+        self.invalid.is_empty()
+    }
+}
+
+/// Batch license verifier
+#[derive(Debug, Clone)]
+pub struct BatchVerifier {
+    threads: usize,
+    min_parallel_batch: usize,
+}
+
+impl Default for BatchVerifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BatchVerifier {
+    /// Verifier using all available cores
+    pub fn new() -> Self {
+        Self {
+            threads: thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            min_parallel_batch: DEFAULT_MIN_PARALLEL_BATCH,
+        }
+    }
+
+    /// Limit worker threads (1 = always sequential)
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Smallest batch worth splitting across threads
+    pub fn min_parallel_batch(mut self, size: usize) -> Self {
+        self.min_parallel_batch = size;
+        self
+    }
+
+    /// Verify all licenses against one signing key
+    pub fn verify(&self, licenses: &[License], signing_key: &SigningKey) -> BatchResult {
+        if self.threads == 1 || licenses.len() < self.min_parallel_batch {
+            return verify_sequential(licenses, signing_key);
+        }
+
+        let mac = signing_mac(signing_key);
+        let chunk_size = licenses.len().div_ceil(self.threads);
+        let invalid = thread::scope(|scope| {
+            let workers: Vec<_> = licenses
+                .chunks(chunk_size)
+                .enumerate()
+                .map(|(chunk_index, chunk)| {
+                    let mac = &mac;
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, license)| !license.verify_with(mac))
+                            .map(|(i, _)| chunk_index * chunk_size + i)
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("verification worker panicked"))
+                .collect()
+        });
+
+        BatchResult {
+            total: licenses.len(),
+            invalid,
+        }
+    }
+}
+
+/// Verify licenses one after another (reference path)
+pub fn verify_sequential(licenses: &[License], signing_key: &SigningKey) -> BatchResult {
+    let mac = signing_mac(signing_key);
+    BatchResult {
+        total: licenses.len(),
+        invalid: licenses
+            .iter()
+            .enumerate()
+            .filter(|(_, license)| !license.verify_with(&mac))
+            .map(|(i, _)| i)
+            .collect(),
+    }
+}
+
+/// Verify with the default batch verifier
+pub fn verify_batch(licenses: &[License], signing_key: &SigningKey) -> BatchResult {
+    BatchVerifier::new().verify(licenses, signing_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn licenses(count: usize, key: &SigningKey) -> Vec<License> {
+        (0..count)
+            .map(|i| {
+                let mut license = License::new(format!("lic-{}", i), vec!["pro".to_string()], 1000);
+                license.sign(key);
+                license
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_batch_matches_sequential() {
+        let key = SigningKey::from_bytes(b"vendor-key");
+        let mut batch = licenses(100, &key);
+        batch[3].expiry += 1;
+        batch[97].signature[0] ^= 1;
+
+        let parallel = BatchVerifier::new().threads(4).min_parallel_batch(1).verify(&batch, &key);
+        assert_eq!(parallel, verify_sequential(&batch, &key));
+        assert_eq!(parallel.invalid, vec![3, 97]);
+        assert_eq!(parallel.total, 100);
+    }
+
+    #[test]
+    fn test_batch_all_valid_and_wrong_key() {
+        let key = SigningKey::from_bytes(b"vendor-key");
+        let batch = licenses(10, &key);
+        assert!(verify_batch(&batch, &key).all_valid());
+        assert_eq!(verify_batch(&batch, &SigningKey::from_bytes(b"other-key")).invalid.len(), 10);
+        assert!(verify_batch(&[], &key).all_valid());
+    }
+}
@@ -0,0 +1,227 @@
+//! License Transparency Log
+//!
+//! Append-only Merkle-tree log of every license the vendor issues.
+//! Clients ask for inclusion proofs that their license is in the log;
+//! auditors compare signed tree heads over time and check consistency
+//! proofs, so a license issued outside the log (e.g. a secret clone)
+//! or a rewritten history is detectable.
+//!
+//! The tree itself is `utils::merkle` (RFC 6962/9162 hashing, SHA3-256).
+
+use aegis_q_core::AegisQError;
+use serde::{Serialize, Deserialize};
+use sha3::{Digest, Sha3_256};
+use utils::keys::{SigningKey, TypedKey};
+use utils::merkle::{self, MerkleTree};
+
+use crate::License;
+
+pub use utils::merkle::{Hash, InclusionProof, HASH_SIZE};
+
+/// Logged license record
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub license_id: String,
+    pub issued_at: u64,
+    /// Hash over all license fields, including the signature
+    pub license_hash: Vec<u8>,
+}
+
+impl LogEntry {
+    /// Entry for a license
+    pub fn new(license: &License, issued_at: u64) -> Self {
+        Self {
+            license_id: license.license_id.clone(),
+            issued_at,
+            license_hash: license_hash(license),
+        }
+    }
+
+    fn leaf_data(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&(self.license_id.len() as u32).to_le_bytes());
+        data.extend_from_slice(self.license_id.as_bytes());
+        data.extend_from_slice(&self.issued_at.to_le_bytes());
+        data.extend_from_slice(&self.license_hash);
+        data
+    }
+
+    /// Merkle leaf hash of this entry
+    pub fn leaf_hash(&self) -> Hash {
+        merkle::leaf_hash(&self.leaf_data())
+    }
+}
+
+/// Signed commitment to the log at a given size
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedTreeHead {
+    pub tree_size: u64,
+    pub root_hash: Hash,
+    pub timestamp: u64,
+    pub signature: Vec<u8>,
+}
+
+impl SignedTreeHead {
+    fn digest(tree_size: u64, root_hash: &Hash, timestamp: u64, log_key: &SigningKey) -> Vec<u8> {
+        let mut hasher = Sha3_256::new();
+        hasher.update(b"aegis-q-transparency-sth");
+        hasher.update(log_key.as_bytes());
+        hasher.update(tree_size.to_le_bytes());
+        hasher.update(root_hash);
+        hasher.update(timestamp.to_le_bytes());
+        hasher.finalize().to_vec()
+    }
+
+    /// Verify the tree head signature
+    pub fn verify(&self, log_key: &SigningKey) -> bool {
+        let expected = Self::digest(self.tree_size, &self.root_hash, self.timestamp, log_key);
+        if expected.len() != self.signature.len() {
+            return false;
+        }
+        let mut diff = 0u8;
+        for (a, b) in expected.iter().zip(&self.signature) {
+            diff |= a ^ b;
+        }
+        diff == 0
+    }
+}
+
+/// Vendor-maintained transparency log
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TransparencyLog {
+    entries: Vec<LogEntry>,
+    tree: MerkleTree,
+}
+
+impl TransparencyLog {
+    /// Empty log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append an issued license, returning its leaf index
+    pub fn append(&mut self, license: &License, issued_at: u64) -> u64 {
+        let entry = LogEntry::new(license, issued_at);
+        self.entries.push(entry.clone());
+        self.tree.push_leaf_hash(entry.leaf_hash())
+    }
+
+    /// Number of entries
+    pub fn size(&self) -> u64 {
+        self.entries.len() as u64
+    }
+
+    /// Logged entries, oldest first
+    pub fn entries(&self) -> &[LogEntry] {
+        &self.entries
+    }
+
+    /// Root hash of the first `tree_size` entries
+    pub fn root_at(&self, tree_size: u64) -> Result<Hash, AegisQError> {
+        self.tree.root_at(tree_size).map_err(AegisQError::InvalidInput)
+    }
+
+    /// Current root hash
+    pub fn root(&self) -> Hash {
+        self.tree.root()
+    }
+
+    /// Sign the current tree head with the log key
+    pub fn signed_tree_head(&self, log_key: &SigningKey, timestamp: u64) -> SignedTreeHead {
+        let root_hash = self.root();
+        SignedTreeHead {
+            tree_size: self.size(),
+            root_hash,
+            timestamp,
+            signature: SignedTreeHead::digest(self.size(), &root_hash, timestamp, log_key),
+        }
+    }
+
+    /// Inclusion proof for entry `leaf_index` in the tree of size `tree_size`
+    pub fn inclusion_proof(&self, leaf_index: u64, tree_size: u64) -> Result<InclusionProof, AegisQError> {
+        self.tree.inclusion_proof(leaf_index, tree_size).map_err(AegisQError::InvalidInput)
+    }
+
+    /// Index of a license in the log, if present
+    pub fn find(&self, license: &License) -> Option<u64> {
+        let hash = license_hash(license);
+        self.entries
+            .iter()
+            .position(|e| e.license_id == license.license_id && e.license_hash == hash)
+            .map(|i| i as u64)
+    }
+
+    /// Consistency proof between an older tree size and a newer one
+    pub fn consistency_proof(&self, old_size: u64, new_size: u64) -> Result<Vec<Hash>, AegisQError> {
+        self.tree.consistency_proof(old_size, new_size).map_err(AegisQError::InvalidInput)
+    }
+}
+
+/// Verify that `entry` is included under `root`
+pub fn verify_inclusion(entry: &LogEntry, proof: &InclusionProof, root: &Hash) -> bool {
+    proof.verify(&entry.leaf_hash(), root)
+}
+
+/// Verify that the tree `(new_size, new_root)` extends `(old_size, old_root)`
+pub fn verify_consistency(old_size: u64, old_root: &Hash, new_size: u64, new_root: &Hash, proof: &[Hash]) -> bool {
+    merkle::verify_consistency(old_size, old_root, new_size, new_root, proof)
+}
+
+/// Hash of the signed fields (`License::signed_bytes`) followed by the signature
+fn license_hash(license: &License) -> Vec<u8> {
+    let mut hasher = Sha3_256::new();
+    hasher.update(license.signed_bytes(b"aegis-q-transparency-license", "", &[]));
+    hasher.update(&license.signature);
+    hasher.finalize().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issue(log: &mut TransparencyLog, count: usize) -> Vec<License> {
+        (0..count)
+            .map(|i| {
+                let mut license = License::new(format!("lic-{}", log.size()), vec!["pro".to_string()], 1000 + i as u64);
+                license.sign(&SigningKey::from_bytes(b"vendor-key"));
+                log.append(&license, i as u64);
+                license
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_log_proofs() {
+        let mut log = TransparencyLog::new();
+        issue(&mut log, 5);
+        let old_root = log.root();
+        issue(&mut log, 4);
+
+        let proof = log.inclusion_proof(2, 7).unwrap();
+        assert!(verify_inclusion(&log.entries()[2], &proof, &log.root_at(7).unwrap()));
+        assert!(!verify_inclusion(&log.entries()[3], &proof, &log.root_at(7).unwrap()));
+
+        let consistency = log.consistency_proof(5, 9).unwrap();
+        assert!(verify_consistency(5, &old_root, 9, &log.root(), &consistency));
+        assert!(!verify_consistency(5, &log.root_at(4).unwrap(), 9, &log.root(), &consistency));
+    }
+
+    #[test]
+    fn test_signed_tree_head_and_lookup() {
+        let mut log = TransparencyLog::new();
+        let licenses = issue(&mut log, 5);
+
+        let log_key = SigningKey::from_bytes(b"log-key");
+        let sth = log.signed_tree_head(&log_key, 42);
+        assert!(sth.verify(&log_key));
+        assert!(!sth.verify(&SigningKey::from_bytes(b"other-key")));
+
+        let index = log.find(&licenses[3]).unwrap();
+        let proof = log.inclusion_proof(index, sth.tree_size).unwrap();
+        assert!(verify_inclusion(&log.entries()[index as usize], &proof, &sth.root_hash));
+
+        let mut clone = licenses[3].clone();
+        clone.features.push("enterprise".to_string());
+        assert_eq!(log.find(&clone), None);
+    }
+}
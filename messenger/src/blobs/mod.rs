@@ -0,0 +1,284 @@
+//! Content-Addressed Blob Store
+//!
+//! Encrypted attachment storage with store-once semantics. A blob is
+//! addressed by a keyed MAC of its plaintext (`BlobId`), so the same
+//! attachment forwarded to ten chats is encrypted and kept once; the key
+//! comes from the master key, so IDs reveal nothing to anyone without it.
+//!
+//! - `put` stores new content with the segmented media format
+//!   (`StorageEntry::store_segmented`) or adds a reference to a blob that is
+//!   already there; `retain`/`release` move the reference count.
+//! - Blobs whose count drops to zero stay until `collect_garbage`, so a
+//!   message deleted and restored in one pass does not re-encrypt.
+//! - An optional byte quota covers the encrypted size of all blobs. A `put`
+//!   over quota first collects garbage and fails with `LimitExceeded` only
+//!   if that is not enough; references to existing content never count.
+//!
+//! `get` checks the decrypted content against its ID, so entries cannot be
+//! swapped between IDs. The index is kept encrypted with `storage`.
+
+use std::collections::BTreeMap;
+
+use aegis_q_core::{AegisQError, AegisQMac};
+use serde::{Deserialize, Serialize};
+use utils::keys::{MacKey, RootKey, TypedKey};
+
+use crate::storage::StorageEntry;
+
+/// Storage purpose of blob contents
+const BLOB_PURPOSE: &str = "blob";
+
+/// Storage purpose of the encrypted index
+const STORAGE_PURPOSE: &str = "blob-store";
+
+/// Blob ID size
+pub const BLOB_ID_SIZE: usize = aegis_q_core::mac::MAC_SIZE;
+
+/// Keyed MAC of a blob's plaintext
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct BlobId([u8; BLOB_ID_SIZE]);
+
+impl BlobId {
+    /// ID from its raw bytes (as referenced from messages)
+    pub fn from_bytes(bytes: [u8; BLOB_ID_SIZE]) -> Self {
+        Self(bytes)
+    }
+
+    /// Raw ID bytes
+    pub fn as_bytes(&self) -> &[u8; BLOB_ID_SIZE] {
+        &self.0
+    }
+
+    /// Lowercase hex form
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+/// One stored blob
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BlobRecord {
+    entry: StorageEntry,
+    /// Plaintext size
+    size: u64,
+    refs: u32,
+}
+
+/// Persisted form of the store
+#[derive(Serialize, Deserialize)]
+struct BlobIndex {
+    blobs: Vec<(BlobId, BlobRecord)>,
+    quota: Option<u64>,
+}
+
+/// Content-addressed encrypted blob store
+pub struct BlobStore {
+    master_key: RootKey,
+    id_mac: AegisQMac,
+    blobs: BTreeMap<BlobId, BlobRecord>,
+    quota: Option<u64>,
+}
+
+impl BlobStore {
+    /// Empty store under `master_key`, without a quota
+    pub fn new(master_key: &RootKey) -> Self {
+        let id_key: MacKey = master_key.derive("blob-id");
+        Self {
+            master_key: master_key.clone(),
+            id_mac: AegisQMac::new(id_key.as_bytes()),
+            blobs: BTreeMap::new(),
+            quota: None,
+        }
+    }
+
+    /// Limit the encrypted size of all blobs to `max_bytes`
+    pub fn with_quota(mut self, max_bytes: u64) -> Self {
+        self.quota = Some(max_bytes);
+        self
+    }
+
+    /// ID `data` is stored under
+    pub fn blob_id(&self, data: &[u8]) -> BlobId {
+        BlobId(self.id_mac.mac(data))
+    }
+
+    /// Store `data` once and add a reference to it
+    pub fn put(&mut self, data: &[u8]) -> Result<BlobId, AegisQError> {
+        let id = self.blob_id(data);
+        if let Some(record) = self.blobs.get_mut(&id) {
+            record.refs = record.refs.checked_add(1).ok_or(AegisQError::LimitExceeded("Too many blob references"))?;
+            return Ok(id);
+        }
+
+        let entry = StorageEntry::store_segmented(data, &self.master_key, BLOB_PURPOSE)?;
+        let needed = entry.encrypted_data.len() as u64;
+        if let Some(quota) = self.quota {
+            if self.used_bytes() + needed > quota {
+                self.collect_garbage();
+            }
+            if self.used_bytes() + needed > quota {
+                return Err(AegisQError::LimitExceeded("Blob store quota exceeded"));
+            }
+        }
+        self.blobs.insert(id, BlobRecord { entry, size: data.len() as u64, refs: 1 });
+        Ok(id)
+    }
+
+    /// Decrypt a blob
+    pub fn get(&self, id: &BlobId) -> Result<Vec<u8>, AegisQError> {
+        let record = self.blobs.get(id).ok_or(AegisQError::NotFound("Unknown blob"))?;
+        let data = record.entry.retrieve(&self.master_key)?;
+        self.id_mac.verify(&data, id.as_bytes())?;
+        Ok(data)
+    }
+
+    /// Add a reference to a stored blob
+    pub fn retain(&mut self, id: &BlobId) -> Result<(), AegisQError> {
+        let record = self.blobs.get_mut(id).ok_or(AegisQError::NotFound("Unknown blob"))?;
+        record.refs = record.refs.checked_add(1).ok_or(AegisQError::LimitExceeded("Too many blob references"))?;
+        Ok(())
+    }
+
+    /// Drop a reference; returns the references left
+    pub fn release(&mut self, id: &BlobId) -> Result<u32, AegisQError> {
+        let record = self.blobs.get_mut(id).ok_or(AegisQError::NotFound("Unknown blob"))?;
+        if record.refs == 0 {
+            return Err(AegisQError::InvalidInput("Blob has no references"));
+        }
+        record.refs -= 1;
+        Ok(record.refs)
+    }
+
+    /// References to a blob, if stored
+    pub fn refs(&self, id: &BlobId) -> Option<u32> {
+        self.blobs.get(id).map(|record| record.refs)
+    }
+
+    /// Plaintext size of a blob, if stored
+    pub fn size(&self, id: &BlobId) -> Option<u64> {
+        self.blobs.get(id).map(|record| record.size)
+    }
+
+    /// Remove unreferenced blobs; returns their IDs
+    pub fn collect_garbage(&mut self) -> Vec<BlobId> {
+        let unreferenced: Vec<BlobId> = self.blobs.iter().filter(|(_, record)| record.refs == 0).map(|(id, _)| *id).collect();
+        for id in &unreferenced {
+            self.blobs.remove(id);
+        }
+        unreferenced
+    }
+
+    /// Encrypted bytes held, including blobs awaiting collection
+    pub fn used_bytes(&self) -> u64 {
+        self.blobs.values().map(|record| record.entry.encrypted_data.len() as u64).sum()
+    }
+
+    /// Configured quota
+    pub fn quota(&self) -> Option<u64> {
+        self.quota
+    }
+
+    /// Number of stored blobs
+    pub fn len(&self) -> usize {
+        self.blobs.len()
+    }
+
+    /// Whether no blobs are stored
+    pub fn is_empty(&self) -> bool {
+        self.blobs.is_empty()
+    }
+
+    /// Encrypt the store for local storage
+    pub fn to_entry(&self) -> Result<StorageEntry, AegisQError> {
+        let index = BlobIndex {
+            blobs: self.blobs.iter().map(|(id, record)| (*id, record.clone())).collect(),
+            quota: self.quota,
+        };
+        let bytes = serde_json::to_vec(&index).map_err(|_| AegisQError::Serialization("Failed to encode blob store"))?;
+        StorageEntry::store(&bytes, &self.master_key, STORAGE_PURPOSE)
+    }
+
+    /// Decrypt a store saved with `to_entry`
+    pub fn from_entry(entry: &StorageEntry, master_key: &RootKey) -> Result<Self, AegisQError> {
+        if entry.purpose != STORAGE_PURPOSE {
+            return Err(AegisQError::InvalidInput("Not a blob store entry"));
+        }
+        let bytes = entry.retrieve(master_key)?;
+        let index: BlobIndex = serde_json::from_slice(&bytes).map_err(|_| AegisQError::Serialization("Malformed blob store"))?;
+        let mut store = Self::new(master_key);
+        store.blobs = index.blobs.into_iter().collect();
+        store.quota = index.quota;
+        Ok(store)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn master_key() -> RootKey {
+        RootKey::from_bytes(b"master-key-123456789012345678901234567890")
+    }
+
+    #[test]
+    fn test_dedup_refcount_and_gc() {
+        let mut store = BlobStore::new(&master_key());
+        let photo = vec![7u8; 5000];
+
+        let id = store.put(&photo).unwrap();
+        assert_eq!(store.put(&photo).unwrap(), id);
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.refs(&id), Some(2));
+        assert_eq!(store.size(&id), Some(5000));
+        assert!(store.get(&id).unwrap() == photo);
+
+        assert_eq!(store.release(&id).unwrap(), 1);
+        assert!(store.collect_garbage().is_empty());
+        assert_eq!(store.release(&id).unwrap(), 0);
+        assert_eq!(store.release(&id), Err(AegisQError::InvalidInput("Blob has no references")));
+
+        // Unreferenced content is kept until collected and can be revived
+        store.retain(&id).unwrap();
+        store.release(&id).unwrap();
+        assert_eq!(store.collect_garbage(), vec![id]);
+        assert!(store.is_empty());
+        assert_eq!(store.get(&id), Err(AegisQError::NotFound("Unknown blob")));
+
+        // IDs depend on the master key
+        let other = BlobStore::new(&RootKey::from_bytes(b"other-master-key-1234567890123456789012"));
+        assert_ne!(other.blob_id(&photo), id);
+    }
+
+    #[test]
+    fn test_quota_collects_garbage_first() {
+        let mut store = BlobStore::new(&master_key());
+        let first = store.put(&[1u8; 1000]).unwrap();
+        let quota = store.used_bytes() + 500;
+        let mut store = store.with_quota(quota);
+
+        assert_eq!(store.put(&[2u8; 1000]), Err(AegisQError::LimitExceeded("Blob store quota exceeded")));
+        // Another reference to stored content is always accepted
+        store.put(&[1u8; 1000]).unwrap();
+
+        store.release(&first).unwrap();
+        store.release(&first).unwrap();
+        let second = store.put(&[2u8; 1000]).unwrap();
+        assert_eq!(store.refs(&first), None);
+        assert!(store.used_bytes() <= quota);
+
+        let restored = BlobStore::from_entry(&store.to_entry().unwrap(), &master_key()).unwrap();
+        assert_eq!(restored.quota(), Some(quota));
+        assert_eq!(restored.get(&second).unwrap(), vec![2u8; 1000]);
+    }
+
+    #[test]
+    fn test_swapped_entries_are_rejected() {
+        let mut store = BlobStore::new(&master_key());
+        let a = store.put(b"attachment a").unwrap();
+        let b = store.put(b"attachment b").unwrap();
+
+        let entry_b = store.blobs[&b].entry.clone();
+        store.blobs.get_mut(&a).unwrap().entry = entry_b;
+        assert_eq!(store.get(&a), Err(AegisQError::AuthenticationFailed));
+    }
+}
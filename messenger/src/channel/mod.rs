@@ -0,0 +1,374 @@
+//! Encrypted Channels
+//!
+//! Publish/subscribe over the relay for broadcast announcements, presence
+//! and other one-to-many traffic. Everything published to a channel is
+//! encrypted under the channel key of the current epoch; the relay stores
+//! opaque blobs under an opaque topic ID and hands them back in order.
+//!
+//! The channel owner rotates the key on every membership change and hands
+//! the resulting `ChannelKeyShare` to each current member over their 1:1
+//! sessions, so removed members cannot read new posts and new members
+//! cannot read old ones. Subscribers keep a few previous epoch keys to
+//! read a backlog fetched late.
+//!
+//! The channel key is shared: any member can post, and posts are not
+//! attributed to a sender. A removed member still holds the key of the
+//! epoch it was removed from, so readers should treat posts whose
+//! `ChannelMessage::epoch` is older than `Channel::epoch` as stale.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use aegis_q_core::{AegisQError, aegis_q_decrypt, aegis_q_encrypt};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use utils::keys::RootKey;
+use utils::memory::zeroize;
+use utils::rng::random_bytes;
+
+use crate::storage::StorageEntry;
+
+/// Storage purpose of an encrypted channel
+const STORAGE_PURPOSE: &str = "channel";
+
+/// Channel key size
+pub const CHANNEL_KEY_SIZE: usize = 32;
+
+/// Nonce size of a published blob
+pub const CHANNEL_NONCE_SIZE: usize = 16;
+
+/// Previous epoch keys kept for reading a backlog
+pub const MAX_RETAINED_EPOCHS: usize = 8;
+
+/// Relay side of channels: stores and returns opaque blobs per topic
+pub trait ChannelRelay {
+    /// Append a blob to the topic
+    fn publish(&mut self, topic: &[u8; 32], blob: &[u8]) -> Result<(), AegisQError>;
+
+    /// Blobs with a sequence number above `after`, oldest first
+    fn fetch(&mut self, topic: &[u8; 32], after: u64) -> Result<Vec<(u64, Vec<u8>)>, AegisQError>;
+}
+
+/// Relay address of a channel
+pub fn topic_id(channel_id: &str) -> [u8; 32] {
+    let mut hasher = Sha3_256::new();
+    hasher.update(b"aegis-q-messenger-channel-topic");
+    hasher.update(channel_id.as_bytes());
+    hasher.finalize().into()
+}
+
+/// Channel key of one epoch, sent to members over their 1:1 sessions
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelKeyShare {
+    pub channel_id: String,
+    pub epoch: u32,
+    pub key: Vec<u8>,
+}
+
+impl Drop for ChannelKeyShare {
+    fn drop(&mut self) {
+        zeroize(&mut self.key);
+    }
+}
+
+impl std::fmt::Debug for ChannelKeyShare {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChannelKeyShare")
+            .field("channel_id", &self.channel_id)
+            .field("epoch", &self.epoch)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Decrypted post
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelMessage {
+    /// Relay sequence number
+    pub seq: u64,
+    /// Key epoch the post was encrypted under
+    pub epoch: u32,
+    pub plaintext: Vec<u8>,
+}
+
+/// Channel state of the owner or a subscriber
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Channel {
+    id: String,
+    /// Epoch keys; the current epoch is the last
+    keys: BTreeMap<u32, Vec<u8>>,
+    /// Members to hand key shares to (owner only)
+    members: BTreeSet<String>,
+    /// Highest relay sequence number fetched
+    cursor: u64,
+}
+
+impl Channel {
+    /// Create a channel as its owner
+    ///
+    /// Returns the first key share, to be sent to every member.
+    pub fn create(id: &str, members: impl IntoIterator<Item = String>) -> (Self, ChannelKeyShare) {
+        let mut channel = Self {
+            id: id.to_string(),
+            keys: BTreeMap::new(),
+            members: members.into_iter().collect(),
+            cursor: 0,
+        };
+        let share = channel.rotate();
+        (channel, share)
+    }
+
+    /// Subscribe with a key share received from the owner
+    pub fn join(share: &ChannelKeyShare) -> Self {
+        Self {
+            id: share.channel_id.clone(),
+            keys: BTreeMap::from([(share.epoch, share.key.clone())]),
+            members: BTreeSet::new(),
+            cursor: 0,
+        }
+    }
+
+    /// Channel ID
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Current key epoch
+    pub fn epoch(&self) -> u32 {
+        self.keys.keys().next_back().copied().unwrap_or(0)
+    }
+
+    /// Members known to the owner
+    pub fn members(&self) -> impl Iterator<Item = &str> {
+        self.members.iter().map(String::as_str)
+    }
+
+    /// Add a member and rotate the key; send the share to all members
+    pub fn add_member(&mut self, member: &str) -> ChannelKeyShare {
+        self.members.insert(member.to_string());
+        self.rotate()
+    }
+
+    /// Remove a member and rotate the key; send the share to the rest
+    pub fn remove_member(&mut self, member: &str) -> Result<ChannelKeyShare, AegisQError> {
+        if !self.members.remove(member) {
+            return Err(AegisQError::NotFound("Unknown channel member"));
+        }
+        Ok(self.rotate())
+    }
+
+    /// Install a newer key share from the owner
+    pub fn accept_key(&mut self, share: &ChannelKeyShare) -> Result<(), AegisQError> {
+        if share.channel_id != self.id {
+            return Err(AegisQError::InvalidInput("Key share for another channel"));
+        }
+        if share.epoch <= self.epoch() {
+            return Err(AegisQError::Protocol("Stale channel key"));
+        }
+        self.install(share.epoch, share.key.clone());
+        Ok(())
+    }
+
+    /// Encrypt a post under the current epoch key
+    ///
+    /// Blob format: epoch (4, big-endian) || nonce (16) || ciphertext.
+    pub fn seal(&self, plaintext: &[u8]) -> Vec<u8> {
+        let epoch = self.epoch();
+        let nonce = random_bytes(CHANNEL_NONCE_SIZE);
+        let ciphertext = aegis_q_encrypt(&self.keys[&epoch], &self.bound_nonce(epoch, &nonce), plaintext);
+
+        let mut blob = Vec::with_capacity(4 + CHANNEL_NONCE_SIZE + ciphertext.len());
+        blob.extend_from_slice(&epoch.to_be_bytes());
+        blob.extend_from_slice(&nonce);
+        blob.extend_from_slice(&ciphertext);
+        blob
+    }
+
+    /// Decrypt a blob produced by `seal`; returns (epoch, plaintext)
+    pub fn open(&self, blob: &[u8]) -> Result<(u32, Vec<u8>), AegisQError> {
+        if blob.len() < 4 + CHANNEL_NONCE_SIZE {
+            return Err(AegisQError::InvalidLength("Channel blob too short"));
+        }
+        let (epoch, rest) = blob.split_at(4);
+        let epoch = u32::from_be_bytes(epoch.try_into().expect("4 bytes"));
+        let (nonce, ciphertext) = rest.split_at(CHANNEL_NONCE_SIZE);
+        let key = self.keys.get(&epoch).ok_or(AegisQError::NotFound("Unknown channel epoch"))?;
+        let plaintext = aegis_q_decrypt(key, &self.bound_nonce(epoch, nonce), ciphertext)?;
+        Ok((epoch, plaintext))
+    }
+
+    /// Encrypt and publish a post
+    pub fn publish(&self, relay: &mut dyn ChannelRelay, plaintext: &[u8]) -> Result<(), AegisQError> {
+        relay.publish(&topic_id(&self.id), &self.seal(plaintext))
+    }
+
+    /// Fetch and decrypt posts newer than the last fetch
+    ///
+    /// Blobs that do not decrypt (unknown epoch, forged or corrupted) are
+    /// skipped. Persist the channel afterwards to keep the cursor.
+    pub fn fetch(&mut self, relay: &mut dyn ChannelRelay) -> Result<Vec<ChannelMessage>, AegisQError> {
+        let blobs = relay.fetch(&topic_id(&self.id), self.cursor)?;
+        let mut messages = Vec::with_capacity(blobs.len());
+        for (seq, blob) in blobs {
+            self.cursor = self.cursor.max(seq);
+            if let Ok((epoch, plaintext)) = self.open(&blob) {
+                messages.push(ChannelMessage { seq, epoch, plaintext });
+            }
+        }
+        Ok(messages)
+    }
+
+    /// Encrypt the channel state for local storage
+    pub fn to_entry(&self, master_key: &RootKey) -> Result<StorageEntry, AegisQError> {
+        let mut bytes = serde_json::to_vec(self).map_err(|_| AegisQError::Serialization("Failed to encode channel"))?;
+        let entry = StorageEntry::store(&bytes, master_key, STORAGE_PURPOSE);
+        zeroize(&mut bytes);
+        entry
+    }
+
+    /// Decrypt a channel saved with `to_entry`
+    pub fn from_entry(entry: &StorageEntry, master_key: &RootKey) -> Result<Self, AegisQError> {
+        if entry.purpose != STORAGE_PURPOSE {
+            return Err(AegisQError::InvalidInput("Not a channel entry"));
+        }
+        let mut bytes = entry.retrieve(master_key)?;
+        let channel = serde_json::from_slice(&bytes).map_err(|_| AegisQError::Serialization("Malformed channel"));
+        zeroize(&mut bytes);
+        channel
+    }
+
+    fn rotate(&mut self) -> ChannelKeyShare {
+        let epoch = if self.keys.is_empty() { 0 } else { self.epoch() + 1 };
+        let key = random_bytes(CHANNEL_KEY_SIZE);
+        self.install(epoch, key.clone());
+        ChannelKeyShare {
+            channel_id: self.id.clone(),
+            epoch,
+            key,
+        }
+    }
+
+    fn install(&mut self, epoch: u32, key: Vec<u8>) {
+        self.keys.insert(epoch, key);
+        while self.keys.len() > MAX_RETAINED_EPOCHS + 1 {
+            if let Some((_, mut old)) = self.keys.pop_first() {
+                zeroize(&mut old);
+            }
+        }
+    }
+
+    /// Nonce with the topic and epoch bound in (Aegis-Q has no separate AD)
+    fn bound_nonce(&self, epoch: u32, nonce: &[u8]) -> Vec<u8> {
+        let mut bound = nonce.to_vec();
+        bound.extend_from_slice(&topic_id(&self.id));
+        bound.extend_from_slice(&epoch.to_be_bytes());
+        bound
+    }
+}
+
+impl Drop for Channel {
+    fn drop(&mut self) {
+        for key in self.keys.values_mut() {
+            zeroize(key);
+        }
+    }
+}
+
+impl std::fmt::Debug for Channel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Channel")
+            .field("id", &self.id)
+            .field("epoch", &self.epoch())
+            .field("members", &self.members.len())
+            .field("cursor", &self.cursor)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// In-memory relay: topic -> blobs, sequence numbers from 1
+    #[derive(Default)]
+    struct TestRelay {
+        topics: BTreeMap<[u8; 32], Vec<Vec<u8>>>,
+    }
+
+    impl ChannelRelay for TestRelay {
+        fn publish(&mut self, topic: &[u8; 32], blob: &[u8]) -> Result<(), AegisQError> {
+            self.topics.entry(*topic).or_default().push(blob.to_vec());
+            Ok(())
+        }
+
+        fn fetch(&mut self, topic: &[u8; 32], after: u64) -> Result<Vec<(u64, Vec<u8>)>, AegisQError> {
+            let blobs = self.topics.get(topic).map(Vec::as_slice).unwrap_or_default();
+            Ok(blobs.iter().enumerate().map(|(i, blob)| (i as u64 + 1, blob.clone())).skip(after as usize).collect())
+        }
+    }
+
+    #[test]
+    fn test_publish_fetch() {
+        let mut relay = TestRelay::default();
+        let (owner, share) = Channel::create("announcements", ["alice".to_string(), "bob".to_string()]);
+        let mut alice = Channel::join(&share);
+
+        owner.publish(&mut relay, b"maintenance at 22:00").unwrap();
+        alice.publish(&mut relay, b"noted").unwrap();
+
+        // The relay only sees opaque blobs
+        let stored = &relay.topics[&topic_id("announcements")];
+        assert!(stored.iter().all(|blob| !blob.windows(5).any(|w| w == b"noted")));
+
+        let messages = alice.fetch(&mut relay).unwrap();
+        let texts: Vec<_> = messages.iter().map(|m| m.plaintext.as_slice()).collect();
+        assert_eq!(texts, [&b"maintenance at 22:00"[..], b"noted"]);
+        assert_eq!((messages[1].seq, messages[1].epoch), (2, 0));
+        assert!(alice.fetch(&mut relay).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rotation_on_membership_change() {
+        let mut relay = TestRelay::default();
+        let (mut owner, share) = Channel::create("presence", ["alice".to_string(), "bob".to_string()]);
+        let mut alice = Channel::join(&share);
+        let mut bob = Channel::join(&share);
+        owner.publish(&mut relay, b"before").unwrap();
+
+        let share = owner.remove_member("bob").unwrap();
+        assert_eq!(share.epoch, 1);
+        assert_eq!(owner.remove_member("bob"), Err(AegisQError::NotFound("Unknown channel member")));
+        alice.accept_key(&share).unwrap();
+        assert_eq!(alice.accept_key(&share), Err(AegisQError::Protocol("Stale channel key")));
+        owner.publish(&mut relay, b"after").unwrap();
+
+        // Alice reads the backlog across epochs; Bob only what he could before
+        let alice_epochs: Vec<_> = alice.fetch(&mut relay).unwrap().iter().map(|m| m.epoch).collect();
+        assert_eq!(alice_epochs, [0, 1]);
+        let bob_texts: Vec<_> = bob.fetch(&mut relay).unwrap().into_iter().map(|m| m.plaintext).collect();
+        assert_eq!(bob_texts, [b"before".to_vec()]);
+
+        // A newcomer cannot read posts from before joining
+        let mut carol = Channel::join(&owner.add_member("carol"));
+        assert!(owner.members().any(|m| m == "carol"));
+        owner.publish(&mut relay, b"welcome").unwrap();
+        let carol_texts: Vec<_> = carol.fetch(&mut relay).unwrap().into_iter().map(|m| m.plaintext).collect();
+        assert_eq!(carol_texts, [b"welcome".to_vec()]);
+    }
+
+    #[test]
+    fn test_tampering_and_persistence() {
+        let (owner, share) = Channel::create("news", ["alice".to_string()]);
+        let mut blob = owner.seal(b"headline");
+
+        // Another channel's key does not open it, nor does a tampered blob
+        let (other, _) = Channel::create("other", []);
+        assert!(other.open(&blob).is_err());
+        blob[10] ^= 1;
+        assert_eq!(Channel::join(&share).open(&blob), Err(AegisQError::AuthenticationFailed));
+
+        let master_key = RootKey::from_bytes(b"channel-master-key");
+        let entry = owner.to_entry(&master_key).unwrap();
+        let restored = Channel::from_entry(&entry, &master_key).unwrap();
+        assert!(restored == owner);
+        assert_eq!(restored.open(&owner.seal(b"again")).unwrap(), (0, b"again".to_vec()));
+    }
+}
@@ -0,0 +1,244 @@
+//! Async Conversations (feature `async`)
+//!
+//! `Conversation` joins a message session to a transport of opaque blobs
+//! and exposes the pair as a `futures` `Stream` of incoming messages and a
+//! `Sink` of outgoing ones, so applications can use standard async
+//! combinators (`StreamExt`, `SinkExt`, `select`, ...) instead of polling
+//! by hand.
+//!
+//! Only the `futures-core`/`futures-sink` traits are used; the adapter
+//! works on any executor. The transport (relay connection, WebSocket,
+//! channel, ...) and the session (per-direction keys, a ratchet) are
+//! supplied by the application.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use aegis_q_core::AegisQError;
+use futures_core::Stream;
+use futures_sink::Sink;
+
+/// Encryption of one conversation's messages
+pub trait MessageSession {
+    /// Encrypt an outgoing message
+    fn seal(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, AegisQError>;
+
+    /// Decrypt an incoming message
+    fn open(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, AegisQError>;
+}
+
+/// Decrypted incoming message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncomingMessage {
+    /// Position in the conversation, counting from 0
+    pub seq: u64,
+    pub body: Vec<u8>,
+}
+
+/// Message to send
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutgoingMessage {
+    pub body: Vec<u8>,
+}
+
+impl OutgoingMessage {
+    /// Message with `body`
+    pub fn new(body: impl Into<Vec<u8>>) -> Self {
+        Self { body: body.into() }
+    }
+}
+
+/// Session over a blob transport, as `Stream` + `Sink`
+///
+/// The transport yields `Result<Vec<u8>, AegisQError>` and accepts
+/// `Vec<u8>`. A blob that fails to decrypt is yielded as an error and the
+/// stream continues; transport errors are passed through.
+pub struct Conversation<T, S> {
+    transport: T,
+    session: S,
+    received: u64,
+}
+
+impl<T, S> Conversation<T, S> {
+    /// Join `session` to `transport`
+    pub fn new(transport: T, session: S) -> Self {
+        Self {
+            transport,
+            session,
+            received: 0,
+        }
+    }
+
+    /// Split back into transport and session
+    pub fn into_parts(self) -> (T, S) {
+        (self.transport, self.session)
+    }
+}
+
+impl<T, S> Stream for Conversation<T, S>
+where
+    T: Stream<Item = Result<Vec<u8>, AegisQError>> + Unpin,
+    S: MessageSession + Unpin,
+{
+    type Item = Result<IncomingMessage, AegisQError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let blob = match Pin::new(&mut this.transport).poll_next(cx) {
+            Poll::Ready(Some(Ok(blob))) => blob,
+            Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => return Poll::Pending,
+        };
+        let message = this.session.open(&blob).map(|body| {
+            let seq = this.received;
+            this.received += 1;
+            IncomingMessage { seq, body }
+        });
+        Poll::Ready(Some(message))
+    }
+}
+
+impl<T, S> Sink<OutgoingMessage> for Conversation<T, S>
+where
+    T: Sink<Vec<u8>, Error = AegisQError> + Unpin,
+    S: MessageSession + Unpin,
+{
+    type Error = AegisQError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), AegisQError>> {
+        Pin::new(&mut self.get_mut().transport).poll_ready(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, message: OutgoingMessage) -> Result<(), AegisQError> {
+        let this = self.get_mut();
+        let blob = this.session.seal(&message.body)?;
+        Pin::new(&mut this.transport).start_send(blob)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), AegisQError>> {
+        Pin::new(&mut self.get_mut().transport).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), AegisQError>> {
+        Pin::new(&mut self.get_mut().transport).poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+
+    use aegis_q_core::SessionCipher;
+    use futures::executor::block_on;
+    use futures::{SinkExt, StreamExt};
+
+    type Queue = Rc<RefCell<VecDeque<Vec<u8>>>>;
+
+    /// In-memory transport: sends to one queue, receives from another
+    /// (the stream ends when the inbox is empty)
+    struct Pipe {
+        inbox: Queue,
+        outbox: Queue,
+    }
+
+    fn pipe_pair() -> (Pipe, Pipe) {
+        let (a, b) = (Queue::default(), Queue::default());
+        (Pipe { inbox: a.clone(), outbox: b.clone() }, Pipe { inbox: b, outbox: a })
+    }
+
+    impl Stream for Pipe {
+        type Item = Result<Vec<u8>, AegisQError>;
+
+        fn poll_next(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            Poll::Ready(self.inbox.borrow_mut().pop_front().map(Ok))
+        }
+    }
+
+    impl Sink<Vec<u8>> for Pipe {
+        type Error = AegisQError;
+
+        fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), AegisQError>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn start_send(self: Pin<&mut Self>, blob: Vec<u8>) -> Result<(), AegisQError> {
+            self.outbox.borrow_mut().push_back(blob);
+            Ok(())
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), AegisQError>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), AegisQError>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// One `SessionCipher` per direction
+    struct TestSession {
+        send: SessionCipher,
+        recv: SessionCipher,
+    }
+
+    fn session_pair() -> (TestSession, TestSession) {
+        let (ab, ba) = (b"conversation-key-alice-to-bob-01", b"conversation-key-bob-to-alice-01");
+        (
+            TestSession { send: SessionCipher::new(ab, b""), recv: SessionCipher::new(ba, b"") },
+            TestSession { send: SessionCipher::new(ba, b""), recv: SessionCipher::new(ab, b"") },
+        )
+    }
+
+    impl MessageSession for TestSession {
+        fn seal(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, AegisQError> {
+            Ok(self.send.seal(plaintext)?.1)
+        }
+
+        fn open(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, AegisQError> {
+            self.recv.open(ciphertext)
+        }
+    }
+
+    #[test]
+    fn test_stream_sink_round_trip() {
+        let (alice_pipe, bob_pipe) = pipe_pair();
+        let (alice_session, bob_session) = session_pair();
+        let mut alice = Conversation::new(alice_pipe, alice_session);
+        let mut bob = Conversation::new(bob_pipe, bob_session);
+
+        block_on(async {
+            let mut outgoing = futures::stream::iter([OutgoingMessage::new("hi"), OutgoingMessage::new("there")].map(Ok));
+            alice.send_all(&mut outgoing).await.unwrap();
+
+            let bodies: Vec<_> = (&mut bob).map(|m| m.unwrap().body).collect().await;
+            assert_eq!(bodies, [b"hi".to_vec(), b"there".to_vec()]);
+
+            bob.send(OutgoingMessage::new("reply")).await.unwrap();
+            let reply = alice.next().await.unwrap().unwrap();
+            assert_eq!((reply.seq, reply.body), (0, b"reply".to_vec()));
+        });
+    }
+
+    #[test]
+    fn test_stream_reports_bad_blobs_and_continues() {
+        let (alice_pipe, bob_pipe) = pipe_pair();
+        let (alice_session, bob_session) = session_pair();
+        let mut alice = Conversation::new(alice_pipe, alice_session);
+        let inbox = bob_pipe.inbox.clone();
+        let mut bob = Conversation::new(bob_pipe, bob_session);
+
+        block_on(async {
+            inbox.borrow_mut().push_back(b"garbage-from-the-relay-0123456789".to_vec());
+            alice.send(OutgoingMessage::new("real")).await.unwrap();
+
+            assert_eq!(bob.next().await, Some(Err(AegisQError::AuthenticationFailed)));
+            let message = bob.next().await.unwrap().unwrap();
+            assert_eq!((message.seq, message.body), (0, b"real".to_vec()));
+            assert_eq!(bob.next().await, None);
+        });
+    }
+}
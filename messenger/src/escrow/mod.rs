@@ -0,0 +1,237 @@
+//! Key Escrow
+//!
+//! Optional, policy-controlled recovery for enterprise deployments.
+//! When the organization policy enables escrow, each message key is also
+//! wrapped to the organization recovery public key (via a post-quantum KEM)
+//! and the envelope carries a visible `EscrowMarker`. The marker is bound
+//! into the message nonce, so it cannot be stripped or altered without the
+//! recipient failing authentication — clients can always tell, and show,
+//! when escrow is active.
+
+use aegis_q_core::{AegisQError, aegis_q_decrypt, aegis_q_encrypt};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use utils::kdf::kdf_shake256;
+use utils::keys::{EncryptionKey, EnvelopeKey, TypedKey};
+
+/// KEM used to wrap message keys to the recovery key
+pub trait RecoveryKem {
+    /// Encapsulate to `public_key`: (ciphertext, shared secret)
+    fn encapsulate(&self, public_key: &[u8]) -> Result<(Vec<u8>, Vec<u8>), AegisQError>;
+
+    /// Decapsulate with `secret_key`
+    fn decapsulate(&self, secret_key: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AegisQError>;
+}
+
+/// Organization recovery public key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryKey {
+    pub org_id: String,
+    pub public_key: Vec<u8>,
+}
+
+impl RecoveryKey {
+    /// Create recovery key
+    pub fn new(org_id: &str, public_key: &[u8]) -> Self {
+        Self {
+            org_id: org_id.to_string(),
+            public_key: public_key.to_vec(),
+        }
+    }
+
+    /// Fingerprint shown to users alongside the escrow notice
+    pub fn fingerprint(&self) -> Vec<u8> {
+        let mut hasher = Sha3_256::new();
+        hasher.update(b"aegis-q-messenger-escrow-fingerprint");
+        for field in [self.org_id.as_bytes(), &self.public_key] {
+            hasher.update((field.len() as u32).to_le_bytes());
+            hasher.update(field);
+        }
+        hasher.finalize()[..16].to_vec()
+    }
+}
+
+/// Organization escrow policy
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EscrowPolicy {
+    recovery_key: Option<RecoveryKey>,
+}
+
+impl EscrowPolicy {
+    /// Escrow disabled
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Escrow every message key to `recovery_key`
+    pub fn enabled(recovery_key: RecoveryKey) -> Self {
+        Self {
+            recovery_key: Some(recovery_key),
+        }
+    }
+
+    /// Whether escrow is active
+    pub fn is_enabled(&self) -> bool {
+        self.recovery_key.is_some()
+    }
+}
+
+/// Visible escrow marker carried in the envelope
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EscrowMarker {
+    pub org_id: String,
+    pub recovery_fingerprint: Vec<u8>,
+    pub kem_ciphertext: Vec<u8>,
+    pub wrapped_key: Vec<u8>,
+}
+
+impl EscrowMarker {
+    fn digest(&self) -> Vec<u8> {
+        let mut hasher = Sha3_256::new();
+        hasher.update(b"aegis-q-messenger-escrow-marker");
+        for field in [self.org_id.as_bytes(), &self.recovery_fingerprint, &self.kem_ciphertext, &self.wrapped_key] {
+            hasher.update((field.len() as u32).to_le_bytes());
+            hasher.update(field);
+        }
+        hasher.finalize().to_vec()
+    }
+
+    fn wrap_key(shared_secret: &[u8], kem_ciphertext: &[u8]) -> EnvelopeKey {
+        EnvelopeKey::from_bytes(&kdf_shake256(b"aegis-q-messenger-escrow-wrap", shared_secret, kem_ciphertext, 64))
+    }
+}
+
+/// Message envelope with optional escrow marker
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageEnvelope {
+    pub escrow: Option<EscrowMarker>,
+    pub ciphertext: Vec<u8>,
+}
+
+impl MessageEnvelope {
+    /// Encrypt `plaintext` under `message_key`, escrowing the key if the policy says so
+    pub fn seal(
+        kem: &dyn RecoveryKem,
+        policy: &EscrowPolicy,
+        message_key: &EncryptionKey,
+        nonce: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Self, AegisQError> {
+        let escrow = match &policy.recovery_key {
+            None => None,
+            Some(recovery_key) => {
+                let (kem_ciphertext, shared_secret) = kem.encapsulate(&recovery_key.public_key)?;
+                let wrap_key = EscrowMarker::wrap_key(&shared_secret, &kem_ciphertext);
+                Some(EscrowMarker {
+                    org_id: recovery_key.org_id.clone(),
+                    recovery_fingerprint: recovery_key.fingerprint(),
+                    wrapped_key: aegis_q_encrypt(wrap_key.as_bytes(), nonce, message_key.as_bytes()),
+                    kem_ciphertext,
+                })
+            }
+        };
+
+        let ciphertext = aegis_q_encrypt(message_key.as_bytes(), &Self::bound_nonce(nonce, escrow.as_ref()), plaintext);
+        Ok(Self { escrow, ciphertext })
+    }
+
+    /// Whether this message was escrowed
+    pub fn is_escrowed(&self) -> bool {
+        self.escrow.is_some()
+    }
+
+    /// Organization holding the recovery key, for display
+    pub fn escrow_org(&self) -> Option<&str> {
+        self.escrow.as_ref().map(|marker| marker.org_id.as_str())
+    }
+
+    /// Decrypt as the recipient
+    pub fn open(&self, message_key: &EncryptionKey, nonce: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        aegis_q_decrypt(message_key.as_bytes(), &Self::bound_nonce(nonce, self.escrow.as_ref()), &self.ciphertext)
+    }
+
+    /// Recover the plaintext with the organization recovery secret key
+    pub fn recover(&self, kem: &dyn RecoveryKem, recovery_secret: &[u8], nonce: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        let marker = self.escrow.as_ref().ok_or(AegisQError::NotFound("Message not escrowed"))?;
+        let shared_secret = kem.decapsulate(recovery_secret, &marker.kem_ciphertext)?;
+        let wrap_key = EscrowMarker::wrap_key(&shared_secret, &marker.kem_ciphertext);
+        let mut unwrapped = aegis_q_decrypt(wrap_key.as_bytes(), nonce, &marker.wrapped_key)?;
+        let message_key = EncryptionKey::from_bytes(&unwrapped);
+        utils::memory::zeroize(&mut unwrapped);
+        self.open(&message_key, nonce)
+    }
+
+    /// Nonce with the escrow marker bound in (Aegis-Q has no separate AD)
+    fn bound_nonce(nonce: &[u8], escrow: Option<&EscrowMarker>) -> Vec<u8> {
+        let mut bound = nonce.to_vec();
+        match escrow {
+            None => bound.push(0x00),
+            Some(marker) => {
+                bound.push(0x01);
+                bound.extend_from_slice(&marker.digest());
+            }
+        }
+        bound
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::rng::random_bytes;
+
+    /// Insecure stand-in KEM (public key == secret key)
+    struct TestKem;
+
+    impl RecoveryKem for TestKem {
+        fn encapsulate(&self, public_key: &[u8]) -> Result<(Vec<u8>, Vec<u8>), AegisQError> {
+            let ciphertext = random_bytes(32);
+            Ok((ciphertext.clone(), kdf_shake256(b"test-kem", public_key, &ciphertext, 32)))
+        }
+
+        fn decapsulate(&self, secret_key: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AegisQError> {
+            Ok(kdf_shake256(b"test-kem", secret_key, ciphertext, 32))
+        }
+    }
+
+    #[test]
+    fn test_escrow_seal_open_recover() {
+        let recovery = RecoveryKey::new("acme", b"org-recovery-key");
+        let message_key = EncryptionKey::from_bytes(b"message-key");
+        let envelope = MessageEnvelope::seal(&TestKem, &EscrowPolicy::enabled(recovery), &message_key, b"nonce", b"hello").unwrap();
+
+        assert!(envelope.is_escrowed());
+        assert_eq!(envelope.escrow_org(), Some("acme"));
+        assert_eq!(envelope.open(&message_key, b"nonce").unwrap(), b"hello");
+        assert_eq!(envelope.recover(&TestKem, b"org-recovery-key", b"nonce").unwrap(), b"hello");
+        assert!(envelope.recover(&TestKem, b"wrong-key", b"nonce").is_err());
+    }
+
+    #[test]
+    fn test_fingerprint_separates_fields() {
+        let key = RecoveryKey::new("acme", b"org-recovery-key");
+        assert_eq!(key.fingerprint(), RecoveryKey::new("acme", b"org-recovery-key").fingerprint());
+        assert_ne!(key.fingerprint(), RecoveryKey::new("acmeo", b"rg-recovery-key").fingerprint());
+    }
+
+    #[test]
+    fn test_escrow_marker_cannot_be_stripped() {
+        let recovery = RecoveryKey::new("acme", b"org-recovery-key");
+        let key = EncryptionKey::from_bytes(b"key");
+        let mut envelope = MessageEnvelope::seal(&TestKem, &EscrowPolicy::enabled(recovery), &key, b"nonce", b"hi").unwrap();
+        let sealed = envelope.clone();
+        envelope.escrow = None;
+        assert!(envelope.open(&key, b"nonce").is_err());
+
+        // Nor can bytes move between marker fields
+        let mut shifted = sealed;
+        let marker = shifted.escrow.as_mut().unwrap();
+        let byte = marker.kem_ciphertext.pop().unwrap();
+        marker.wrapped_key.insert(0, byte);
+        assert!(shifted.open(&key, b"nonce").is_err());
+
+        let plain = MessageEnvelope::seal(&TestKem, &EscrowPolicy::disabled(), &key, b"nonce", b"hi").unwrap();
+        assert!(!plain.is_escrowed());
+        assert!(plain.recover(&TestKem, b"org-recovery-key", b"nonce").is_err());
+    }
+}
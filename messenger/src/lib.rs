@@ -1,3 +1,18 @@
+// Lets `#[derive(Protected)]` expansions name `::messenger` inside this crate
+extern crate self as messenger;
+
 pub mod ratchet;
 pub mod storage;
-
+pub mod blobs;
+pub mod escrow;
+pub mod hd;
+pub mod qr;
+pub mod nat;
+pub mod trust;
+pub mod prekeys;
+pub mod channel;
+pub mod presence;
+#[cfg(feature = "async")]
+pub mod conversation;
+pub mod outbox;
+pub mod postage;
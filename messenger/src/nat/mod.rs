@@ -0,0 +1,399 @@
+//! NAT Traversal
+//!
+//! Hole punching for peer-to-peer calls. Calls start on the relayed path;
+//! each peer gathers candidate addresses (host, server-reflexive as seen
+//! by the relay) and sends them to the other through the relay in an
+//! authenticated `Introduction`. Both sides then probe every candidate
+//! pair at once (simultaneous open), so each NAT sees outbound traffic
+//! before the peer's probe arrives. The first pair that completes a
+//! request/response exchange upgrades the call to a direct path;
+//! keepalives hold the NAT binding open, and a dead direct path falls
+//! back to the relay.
+//!
+//! Introductions and probes carry a tag keyed from the call's session key,
+//! so the relay cannot inject candidates and strangers cannot hijack the
+//! path. Sans-IO: the caller owns the sockets and clock.
+
+use std::net::SocketAddr;
+
+use aegis_q_core::AegisQError;
+use serde::{Serialize, Deserialize};
+use sha3::{Digest, Sha3_256};
+use utils::kdf::kdf_shake256_fill;
+use utils::wire::{NatProbeWire, Wire};
+
+/// Probe retransmission interval
+pub const PROBE_INTERVAL_MS: u64 = 200;
+
+/// Give up on hole punching after this long
+pub const PUNCH_TIMEOUT_MS: u64 = 5_000;
+
+/// Keepalive interval on the direct path (below common NAT binding timeouts)
+pub const KEEPALIVE_INTERVAL_MS: u64 = 15_000;
+
+/// Direct path is declared dead after this much silence
+pub const PATH_TIMEOUT_MS: u64 = 45_000;
+
+/// Probe packet size
+pub const PROBE_SIZE: usize = 4 + 1 + 8 + PROBE_TAG_SIZE;
+
+const PROBE_MAGIC: &[u8; 4] = b"AQNP";
+const PROBE_TAG_SIZE: usize = 16;
+
+/// Where a candidate address came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CandidateKind {
+    /// Local interface address
+    Host,
+    /// Public address observed by the relay
+    ServerReflexive,
+}
+
+impl CandidateKind {
+    fn preference(self) -> u32 {
+        match self {
+            CandidateKind::Host => 126,
+            CandidateKind::ServerReflexive => 100,
+        }
+    }
+}
+
+/// Candidate address offered to the peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Candidate {
+    pub kind: CandidateKind,
+    pub address: SocketAddr,
+    pub priority: u32,
+}
+
+impl Candidate {
+    /// Candidate with ICE-style priority (`local_preference` breaks ties between interfaces)
+    pub fn new(kind: CandidateKind, address: SocketAddr, local_preference: u16) -> Self {
+        Self {
+            kind,
+            address,
+            priority: (kind.preference() << 24) | ((local_preference as u32) << 8) | 0xff,
+        }
+    }
+}
+
+/// Candidate list sent to the peer through the relay
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Introduction {
+    pub candidates: Vec<Candidate>,
+    pub tag: Vec<u8>,
+}
+
+impl Introduction {
+    /// Authenticated introduction for our candidates
+    pub fn new(keys: &TraversalKeys, candidates: Vec<Candidate>) -> Self {
+        let tag = keys.introduction_tag(&candidates);
+        Self { candidates, tag }
+    }
+
+    /// Encode for the relay
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("introduction serializes")
+    }
+
+    /// Decode and authenticate a peer introduction
+    pub fn decode(bytes: &[u8], keys: &TraversalKeys) -> Result<Self, AegisQError> {
+        let intro: Self = serde_json::from_slice(bytes).map_err(|_| AegisQError::Serialization("Malformed introduction"))?;
+        if !constant_time_eq(&intro.tag, &keys.introduction_tag(&intro.candidates)) {
+            return Err(AegisQError::AuthenticationFailed);
+        }
+        Ok(intro)
+    }
+}
+
+/// Keys derived from the call's session key
+#[derive(Clone)]
+pub struct TraversalKeys {
+    key: [u8; 32],
+}
+
+impl TraversalKeys {
+    /// Derive traversal keys from an established session key
+    pub fn new(session_key: &[u8]) -> Self {
+        let mut key = [0u8; 32];
+        kdf_shake256_fill(b"aegis-q-messenger-nat-traversal", session_key, &[], &mut key);
+        Self { key }
+    }
+
+    fn mac(&self, label: &[u8], data: &[u8]) -> [u8; 32] {
+        let mut hasher = Sha3_256::new();
+        hasher.update(self.key);
+        hasher.update(label);
+        hasher.update(data);
+        hasher.finalize().into()
+    }
+
+    fn introduction_tag(&self, candidates: &[Candidate]) -> Vec<u8> {
+        let encoded = serde_json::to_vec(candidates).expect("candidates serialize");
+        self.mac(b"introduction", &encoded).to_vec()
+    }
+
+    fn probe(&self, kind: ProbeKind, transaction: u64) -> Vec<u8> {
+        let mut packet = NatProbeWire {
+            magic: *PROBE_MAGIC,
+            kind: kind as u8,
+            transaction,
+        }
+        .to_wire()
+        .expect("fixed-size probe");
+        let tag = self.mac(b"probe", &packet);
+        packet.extend_from_slice(&tag[..PROBE_TAG_SIZE]);
+        packet
+    }
+
+    fn parse_probe(&self, packet: &[u8]) -> Option<(ProbeKind, u64)> {
+        if packet.len() != PROBE_SIZE {
+            return None;
+        }
+        let (body, tag) = packet.split_at(PROBE_SIZE - PROBE_TAG_SIZE);
+        let probe = NatProbeWire::from_wire(body).ok().filter(|p| &p.magic == PROBE_MAGIC)?;
+        if !constant_time_eq(tag, &self.mac(b"probe", body)[..PROBE_TAG_SIZE]) {
+            return None;
+        }
+        let kind = match probe.kind {
+            1 => ProbeKind::Request,
+            2 => ProbeKind::Response,
+            3 => ProbeKind::Keepalive,
+            _ => return None,
+        };
+        Some((kind, probe.transaction))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProbeKind {
+    Request = 1,
+    Response = 2,
+    Keepalive = 3,
+}
+
+/// Path currently carrying the call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallPath {
+    /// Through the relay
+    Relayed,
+    /// Direct peer-to-peer
+    Direct(SocketAddr),
+}
+
+/// Datagram the caller must send
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transmit {
+    pub destination: SocketAddr,
+    pub payload: Vec<u8>,
+}
+
+/// Hole-punching and path-upgrade state for one call
+pub struct PathUpgrade {
+    keys: TraversalKeys,
+    path: CallPath,
+    remote_candidates: Vec<Candidate>,
+    punch_started: Option<u64>,
+    last_probe: Option<u64>,
+    last_keepalive: u64,
+    last_heard: u64,
+    transaction: u64,
+}
+
+impl PathUpgrade {
+    /// Start on the relayed path
+    pub fn new(keys: TraversalKeys) -> Self {
+        Self {
+            keys,
+            path: CallPath::Relayed,
+            remote_candidates: Vec::new(),
+            punch_started: None,
+            last_probe: None,
+            last_keepalive: 0,
+            last_heard: 0,
+            transaction: 0,
+        }
+    }
+
+    /// Active path
+    pub fn path(&self) -> CallPath {
+        self.path
+    }
+
+    /// Whether probing is in progress
+    pub fn is_punching(&self) -> bool {
+        self.punch_started.is_some()
+    }
+
+    /// Peer introduction arrived via the relay: begin simultaneous open
+    pub fn on_introduction(&mut self, intro: Introduction, now_ms: u64) {
+        let mut candidates = intro.candidates;
+        candidates.sort_by_key(|c| std::cmp::Reverse(c.priority));
+        candidates.dedup_by_key(|c| c.address);
+        self.remote_candidates = candidates;
+        if self.path == CallPath::Relayed && !self.remote_candidates.is_empty() {
+            self.punch_started = Some(now_ms);
+            self.last_probe = None;
+        }
+    }
+
+    /// Datagrams to send now (probes or keepalives)
+    pub fn poll(&mut self, now_ms: u64) -> Vec<Transmit> {
+        match self.path {
+            CallPath::Direct(address) => {
+                if now_ms.saturating_sub(self.last_heard) > PATH_TIMEOUT_MS {
+                    // Direct path died: fall back to the relay
+                    self.path = CallPath::Relayed;
+                    return Vec::new();
+                }
+                if now_ms.saturating_sub(self.last_keepalive) < KEEPALIVE_INTERVAL_MS {
+                    return Vec::new();
+                }
+                self.last_keepalive = now_ms;
+                let transaction = self.next_transaction();
+                vec![Transmit {
+                    destination: address,
+                    payload: self.keys.probe(ProbeKind::Keepalive, transaction),
+                }]
+            }
+            CallPath::Relayed => {
+                let Some(started) = self.punch_started else {
+                    return Vec::new();
+                };
+                if now_ms.saturating_sub(started) > PUNCH_TIMEOUT_MS {
+                    self.punch_started = None;
+                    return Vec::new();
+                }
+                if self.last_probe.is_some_and(|last| now_ms.saturating_sub(last) < PROBE_INTERVAL_MS) {
+                    return Vec::new();
+                }
+                self.last_probe = Some(now_ms);
+                let transaction = self.next_transaction();
+                self.remote_candidates
+                    .iter()
+                    .map(|candidate| Transmit {
+                        destination: candidate.address,
+                        payload: self.keys.probe(ProbeKind::Request, transaction),
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Handle a datagram from `source`; returns a reply to send, if any
+    ///
+    /// Packets that are not authentic probes are ignored.
+    pub fn on_datagram(&mut self, source: SocketAddr, packet: &[u8], now_ms: u64) -> Option<Transmit> {
+        let (kind, transaction) = self.keys.parse_probe(packet)?;
+        match kind {
+            ProbeKind::Request => Some(Transmit {
+                destination: source,
+                payload: self.keys.probe(ProbeKind::Response, transaction),
+            }),
+            ProbeKind::Response => {
+                if self.path == CallPath::Relayed && self.punch_started.is_some() {
+                    self.path = CallPath::Direct(source);
+                    self.punch_started = None;
+                    self.last_keepalive = now_ms;
+                }
+                if self.path == CallPath::Direct(source) {
+                    self.last_heard = now_ms;
+                }
+                None
+            }
+            ProbeKind::Keepalive => {
+                if self.path == CallPath::Direct(source) {
+                    self.last_heard = now_ms;
+                }
+                Some(Transmit {
+                    destination: source,
+                    payload: self.keys.probe(ProbeKind::Response, transaction),
+                })
+            }
+        }
+    }
+
+    fn next_transaction(&mut self) -> u64 {
+        self.transaction += 1;
+        self.transaction
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_introduction_authentication() {
+        let keys = TraversalKeys::new(b"call-session-key");
+        let intro = Introduction::new(&keys, vec![Candidate::new(CandidateKind::ServerReflexive, addr("203.0.113.5:4000"), 1)]);
+        let encoded = intro.encode();
+        assert_eq!(Introduction::decode(&encoded, &keys).unwrap(), intro);
+
+        // Relay swaps in its own address
+        let mut forged = intro.clone();
+        forged.candidates[0].address = addr("198.51.100.1:4000");
+        assert!(Introduction::decode(&forged.encode(), &keys).is_err());
+        assert!(Introduction::decode(&encoded, &TraversalKeys::new(b"other")).is_err());
+    }
+
+    #[test]
+    fn test_simultaneous_open_upgrade_and_fallback() {
+        let alice_addr = addr("203.0.113.5:4000");
+        let bob_addr = addr("198.51.100.7:5000");
+        let mut alice = PathUpgrade::new(TraversalKeys::new(b"call-session-key"));
+        let mut bob = PathUpgrade::new(TraversalKeys::new(b"call-session-key"));
+
+        let keys = TraversalKeys::new(b"call-session-key");
+        alice.on_introduction(Introduction::new(&keys, vec![
+            Candidate::new(CandidateKind::Host, addr("10.0.0.2:5000"), 1),
+            Candidate::new(CandidateKind::ServerReflexive, bob_addr, 1),
+        ]), 0);
+        bob.on_introduction(Introduction::new(&keys, vec![Candidate::new(CandidateKind::ServerReflexive, alice_addr, 1)]), 0);
+
+        // Both probe at once; only the reflexive pair is reachable
+        let alice_probes = alice.poll(0);
+        assert_eq!(alice_probes.len(), 2);
+        let bob_probes = bob.poll(0);
+        let probe_to_bob = alice_probes.iter().find(|t| t.destination == bob_addr).unwrap();
+        let reply = bob.on_datagram(alice_addr, &probe_to_bob.payload, 10).unwrap();
+        alice.on_datagram(bob_addr, &reply.payload, 20);
+        assert_eq!(alice.path(), CallPath::Direct(bob_addr));
+
+        let reply = alice.on_datagram(bob_addr, &bob_probes[0].payload, 20).unwrap();
+        bob.on_datagram(alice_addr, &reply.payload, 30);
+        assert_eq!(bob.path(), CallPath::Direct(alice_addr));
+
+        // Spoofed packet is ignored
+        assert!(alice.on_datagram(addr("192.0.2.66:1"), b"AQNP junk", 40).is_none());
+
+        // Keepalive after the interval; silence falls back to the relay
+        assert_eq!(alice.poll(20 + KEEPALIVE_INTERVAL_MS).len(), 1);
+        alice.poll(20 + PATH_TIMEOUT_MS + 1);
+        assert_eq!(alice.path(), CallPath::Relayed);
+    }
+
+    #[test]
+    fn test_punch_timeout() {
+        let keys = TraversalKeys::new(b"call-session-key");
+        let mut upgrade = PathUpgrade::new(keys.clone());
+        upgrade.on_introduction(Introduction::new(&keys, vec![Candidate::new(CandidateKind::Host, addr("10.0.0.9:1"), 1)]), 0);
+        assert_eq!(upgrade.poll(0).len(), 1);
+        assert!(upgrade.poll(PROBE_INTERVAL_MS / 2).is_empty());
+        assert_eq!(upgrade.poll(PROBE_INTERVAL_MS).len(), 1);
+        assert!(upgrade.poll(PUNCH_TIMEOUT_MS + 1).is_empty());
+        assert!(!upgrade.is_punching());
+        assert_eq!(upgrade.path(), CallPath::Relayed);
+    }
+}
@@ -0,0 +1,362 @@
+//! Outbound Message Queue
+//!
+//! Holds messages until the relay accepts them. Poll-driven like
+//! `nat::PathUpgrade`: call `Outbox::poll` periodically (and when the
+//! relay becomes reachable); it sends what is due and reports delivery
+//! state changes for the app to show.
+//!
+//! Messages of one conversation leave strictly in the order they were
+//! queued: a message is only sent once every earlier message of its
+//! conversation was accepted, and a failed send backs the whole
+//! conversation off exponentially. Conversations are independent.
+//!
+//! Delivery states only move forward: queued -> sent -> delivered -> read.
+//! Delivered and read come from the peer's receipts (`on_delivered`,
+//! `on_read`). A read message is dropped from the queue. The queue is
+//! persisted encrypted through `storage`; save it after every change.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use aegis_q_core::AegisQError;
+use serde::{Deserialize, Serialize};
+use utils::keys::RootKey;
+use utils::memory::zeroize;
+
+use crate::storage::StorageEntry;
+
+/// Storage purpose of the encrypted queue
+const STORAGE_PURPOSE: &str = "outbox";
+
+/// Default delay before the first retry (1 s)
+pub const DEFAULT_INITIAL_BACKOFF_MS: u64 = 1_000;
+
+/// Default retry delay cap (5 min)
+pub const DEFAULT_MAX_BACKOFF_MS: u64 = 5 * 60 * 1_000;
+
+/// Relay side of the queue
+pub trait OutboxRelay {
+    /// Hand one message to the relay
+    fn send(&mut self, conversation: &str, id: u64, payload: &[u8]) -> Result<(), AegisQError>;
+}
+
+/// Delivery state of a queued message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum DeliveryState {
+    /// Waiting to be sent
+    Queued,
+    /// Accepted by the relay
+    Sent,
+    /// Receipt from the peer's device
+    Delivered,
+    /// Read receipt from the peer
+    Read,
+}
+
+/// State change to show in the app
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeliveryEvent {
+    pub id: u64,
+    pub conversation: String,
+    pub state: DeliveryState,
+}
+
+/// Retry backoff
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryConfig {
+    /// Delay after the first failure
+    pub initial_backoff_ms: u64,
+    /// Upper bound of the doubling delay
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            initial_backoff_ms: DEFAULT_INITIAL_BACKOFF_MS,
+            max_backoff_ms: DEFAULT_MAX_BACKOFF_MS,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct OutboundMessage {
+    conversation: String,
+    /// Cleared once delivered
+    payload: Vec<u8>,
+    state: DeliveryState,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct Backoff {
+    failures: u32,
+    next_attempt_ms: u64,
+}
+
+/// Persistent outbound queue
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Outbox {
+    #[serde(skip)]
+    config: RetryConfig,
+    next_id: u64,
+    messages: BTreeMap<u64, OutboundMessage>,
+    /// Conversations backing off after a failed send
+    backoff: BTreeMap<String, Backoff>,
+}
+
+impl Outbox {
+    /// Empty queue
+    pub fn new(config: RetryConfig) -> Self {
+        Self {
+            config,
+            next_id: 0,
+            messages: BTreeMap::new(),
+            backoff: BTreeMap::new(),
+        }
+    }
+
+    /// Queue an (already encrypted) message; returns its ID
+    pub fn enqueue(&mut self, conversation: &str, payload: Vec<u8>) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.messages.insert(
+            id,
+            OutboundMessage {
+                conversation: conversation.to_string(),
+                payload,
+                state: DeliveryState::Queued,
+            },
+        );
+        id
+    }
+
+    /// Current state; `None` once read (or never queued)
+    pub fn state(&self, id: u64) -> Option<DeliveryState> {
+        self.messages.get(&id).map(|message| message.state)
+    }
+
+    /// Messages of `conversation` not yet accepted by the relay
+    pub fn pending(&self, conversation: &str) -> usize {
+        self.messages
+            .values()
+            .filter(|message| message.conversation == conversation && message.state == DeliveryState::Queued)
+            .count()
+    }
+
+    /// Send everything that is due
+    ///
+    /// Each conversation sends its queued messages in order until one
+    /// fails; that conversation then waits out its backoff.
+    pub fn poll(&mut self, relay: &mut dyn OutboxRelay, now_ms: u64) -> Vec<DeliveryEvent> {
+        let mut events = Vec::new();
+        let mut blocked = BTreeSet::new();
+        for (&id, message) in self.messages.iter_mut() {
+            if message.state != DeliveryState::Queued || blocked.contains(&message.conversation) {
+                continue;
+            }
+            let backoff = self.backoff.get(&message.conversation).copied().unwrap_or_default();
+            if now_ms < backoff.next_attempt_ms {
+                blocked.insert(message.conversation.clone());
+                continue;
+            }
+
+            match relay.send(&message.conversation, id, &message.payload) {
+                Ok(()) => {
+                    self.backoff.remove(&message.conversation);
+                    message.state = DeliveryState::Sent;
+                    events.push(DeliveryEvent {
+                        id,
+                        conversation: message.conversation.clone(),
+                        state: DeliveryState::Sent,
+                    });
+                }
+                Err(_) => {
+                    let failures = backoff.failures.saturating_add(1);
+                    let delay = self
+                        .config
+                        .initial_backoff_ms
+                        .saturating_mul(1u64 << (failures - 1).min(32))
+                        .min(self.config.max_backoff_ms);
+                    self.backoff.insert(
+                        message.conversation.clone(),
+                        Backoff {
+                            failures,
+                            next_attempt_ms: now_ms.saturating_add(delay),
+                        },
+                    );
+                    blocked.insert(message.conversation.clone());
+                }
+            }
+        }
+        events
+    }
+
+    /// Delivery receipt from the peer
+    pub fn on_delivered(&mut self, id: u64) -> Result<Option<DeliveryEvent>, AegisQError> {
+        self.advance(id, DeliveryState::Delivered)
+    }
+
+    /// Read receipt from the peer; the message leaves the queue
+    pub fn on_read(&mut self, id: u64) -> Result<Option<DeliveryEvent>, AegisQError> {
+        let event = self.advance(id, DeliveryState::Read)?;
+        if let Some(mut message) = self.messages.remove(&id) {
+            zeroize(&mut message.payload);
+        }
+        Ok(event)
+    }
+
+    /// Encrypt the queue for local storage
+    pub fn to_entry(&self, master_key: &RootKey) -> Result<StorageEntry, AegisQError> {
+        let mut bytes = serde_json::to_vec(self).map_err(|_| AegisQError::Serialization("Failed to encode outbox"))?;
+        let entry = StorageEntry::store(&bytes, master_key, STORAGE_PURPOSE);
+        zeroize(&mut bytes);
+        entry
+    }
+
+    /// Decrypt a queue saved with `to_entry`
+    pub fn from_entry(entry: &StorageEntry, master_key: &RootKey, config: RetryConfig) -> Result<Self, AegisQError> {
+        if entry.purpose != STORAGE_PURPOSE {
+            return Err(AegisQError::InvalidInput("Not an outbox entry"));
+        }
+        let mut bytes = entry.retrieve(master_key)?;
+        let outbox: Result<Self, _> = serde_json::from_slice(&bytes).map_err(|_| AegisQError::Serialization("Malformed outbox"));
+        zeroize(&mut bytes);
+        let mut outbox = outbox?;
+        outbox.config = config;
+        Ok(outbox)
+    }
+
+    /// Move a sent message forward; receipts for an older state are ignored
+    fn advance(&mut self, id: u64, state: DeliveryState) -> Result<Option<DeliveryEvent>, AegisQError> {
+        let message = self.messages.get_mut(&id).ok_or(AegisQError::NotFound("Unknown message"))?;
+        if message.state == DeliveryState::Queued {
+            return Err(AegisQError::Protocol("Receipt for an unsent message"));
+        }
+        if state <= message.state {
+            return Ok(None);
+        }
+        message.state = state;
+        zeroize(&mut message.payload);
+        message.payload.clear();
+        Ok(Some(DeliveryEvent {
+            id,
+            conversation: message.conversation.clone(),
+            state,
+        }))
+    }
+}
+
+impl Drop for Outbox {
+    fn drop(&mut self) {
+        for message in self.messages.values_mut() {
+            zeroize(&mut message.payload);
+        }
+    }
+}
+
+impl std::fmt::Debug for Outbox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Outbox")
+            .field("messages", &self.messages.len())
+            .field("backoff", &self.backoff)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Relay that records sends and can refuse some conversations
+    #[derive(Default)]
+    struct TestRelay {
+        sent: Vec<(String, Vec<u8>)>,
+        down: BTreeSet<String>,
+    }
+
+    impl OutboxRelay for TestRelay {
+        fn send(&mut self, conversation: &str, _id: u64, payload: &[u8]) -> Result<(), AegisQError> {
+            if self.down.contains(conversation) {
+                return Err(AegisQError::Io("Relay unreachable"));
+            }
+            self.sent.push((conversation.to_string(), payload.to_vec()));
+            Ok(())
+        }
+    }
+
+    fn config() -> RetryConfig {
+        RetryConfig {
+            initial_backoff_ms: 100,
+            max_backoff_ms: 300,
+        }
+    }
+
+    #[test]
+    fn test_ordering_and_backoff() {
+        let mut relay = TestRelay { down: BTreeSet::from(["bob".to_string()]), ..Default::default() };
+        let mut outbox = Outbox::new(config());
+        let first = outbox.enqueue("bob", b"1".to_vec());
+        outbox.enqueue("carol", b"a".to_vec());
+        outbox.enqueue("bob", b"2".to_vec());
+
+        // Bob's relay path fails: neither of his messages leaves, Carol's does
+        let events = outbox.poll(&mut relay, 0);
+        assert_eq!(events.iter().map(|e| e.conversation.as_str()).collect::<Vec<_>>(), ["carol"]);
+        assert_eq!(outbox.pending("bob"), 2);
+
+        // Backoff doubles up to the cap: retries at 100, 300, 600
+        for (now, next) in [(100, 300), (300, 600), (600, 900)] {
+            assert!(outbox.poll(&mut relay, now - 1).is_empty());
+            outbox.poll(&mut relay, now);
+            assert_eq!(outbox.backoff["bob"].next_attempt_ms, next);
+        }
+
+        relay.down.clear();
+        assert!(outbox.poll(&mut relay, 899).is_empty());
+        let events = outbox.poll(&mut relay, 900);
+        assert_eq!(events.len(), 2);
+        assert_eq!(outbox.state(first), Some(DeliveryState::Sent));
+        let bob: Vec<_> = relay.sent.iter().filter(|(c, _)| c == "bob").map(|(_, p)| p.as_slice()).collect();
+        assert_eq!(bob, [b"1", b"2"]);
+        assert!(outbox.backoff.is_empty());
+    }
+
+    #[test]
+    fn test_delivery_state_transitions() {
+        let mut relay = TestRelay::default();
+        let mut outbox = Outbox::new(config());
+        let id = outbox.enqueue("bob", b"hello".to_vec());
+
+        assert_eq!(outbox.on_delivered(id), Err(AegisQError::Protocol("Receipt for an unsent message")));
+        outbox.poll(&mut relay, 0);
+
+        let delivered = outbox.on_delivered(id).unwrap().unwrap();
+        assert_eq!(delivered.state, DeliveryState::Delivered);
+        assert_eq!(outbox.on_read(id).unwrap().unwrap().state, DeliveryState::Read);
+        assert_eq!(outbox.state(id), None);
+        assert_eq!(outbox.on_delivered(id), Err(AegisQError::NotFound("Unknown message")));
+
+        // A late delivery receipt after the read receipt is ignored
+        let id = outbox.enqueue("bob", b"again".to_vec());
+        outbox.poll(&mut relay, 0);
+        outbox.advance(id, DeliveryState::Read).unwrap();
+        assert_eq!(outbox.on_delivered(id), Ok(None));
+    }
+
+    #[test]
+    fn test_persisted_queue_resumes() {
+        let mut relay = TestRelay { down: BTreeSet::from(["bob".to_string()]), ..Default::default() };
+        let mut outbox = Outbox::new(config());
+        outbox.enqueue("bob", b"offline".to_vec());
+        outbox.poll(&mut relay, 0);
+
+        let master_key = RootKey::from_bytes(b"outbox-master-key");
+        let entry = outbox.to_entry(&master_key).unwrap();
+        let mut restored = Outbox::from_entry(&entry, &master_key, config()).unwrap();
+        assert!(restored == outbox);
+
+        relay.down.clear();
+        let events = restored.poll(&mut relay, 100);
+        assert_eq!(events[0].state, DeliveryState::Sent);
+        assert_eq!(restored.enqueue("bob", Vec::new()), 1);
+    }
+}
@@ -0,0 +1,558 @@
+//! Relay Rate Limiting and Postage
+//!
+//! Two ways for a relay to throttle abuse without reading messages:
+//!
+//! - `RateLimiter`: a token bucket per sender, for senders the relay can
+//!   identify (an account, a connection).
+//! - Postage: single-use tokens blind-signed by an issuer ahead of time.
+//!   The issuer limits how many tokens each account gets but cannot link
+//!   a token to the blinded request it signed, so the relay accepts an
+//!   anonymous post (sealed sender) without learning who sent it or to
+//!   whom that sender talks.
+//!
+//! The blind signature scheme is pluggable (`BlindSignature`, e.g. RSA
+//! blind signatures per RFC 9474); this module handles token format,
+//! unblinding, issuance quotas and double-spend tracking.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use aegis_q_core::AegisQError;
+use serde::{Deserialize, Serialize};
+use utils::memory::zeroize;
+use utils::rng::random_bytes;
+
+/// Random part of a postage token
+pub const TOKEN_NONCE_SIZE: usize = 32;
+
+/// Blind signature scheme
+pub trait BlindSignature {
+    /// Blind `message` for `public_key`: (blinded message, unblinding secret)
+    fn blind(&self, public_key: &[u8], message: &[u8]) -> Result<(Vec<u8>, Vec<u8>), AegisQError>;
+
+    /// Sign a blinded message (issuer side)
+    fn sign_blinded(&self, secret_key: &[u8], blinded: &[u8]) -> Result<Vec<u8>, AegisQError>;
+
+    /// Turn a blind signature into a signature on the original message
+    fn unblind(&self, public_key: &[u8], unblinder: &[u8], blind_signature: &[u8]) -> Result<Vec<u8>, AegisQError>;
+
+    /// Verify a signature on `message`
+    fn verify(&self, public_key: &[u8], message: &[u8], signature: &[u8]) -> bool;
+}
+
+/// Token bucket parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimit {
+    /// Bucket size: requests allowed back to back
+    pub burst: u32,
+    /// Sustained rate
+    pub per_minute: u32,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            burst: 20,
+            per_minute: 60,
+        }
+    }
+}
+
+/// One request in bucket units: `per_minute` units accrue per millisecond
+const UNITS_PER_REQUEST: u64 = 60_000;
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    units: u64,
+    updated_ms: u64,
+}
+
+/// Token bucket per sender
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    limit: RateLimit,
+    buckets: HashMap<String, Bucket>,
+}
+
+impl RateLimiter {
+    /// Limiter with `limit` for every sender
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Take one request from `sender`'s bucket
+    pub fn check(&mut self, sender: &str, now_ms: u64) -> Result<(), AegisQError> {
+        self.take(sender, 1, now_ms)
+    }
+
+    /// Take `count` requests at once; nothing is taken when they do not fit
+    pub fn take(&mut self, sender: &str, count: u32, now_ms: u64) -> Result<(), AegisQError> {
+        let capacity = self.capacity();
+        let bucket = self.buckets.entry(sender.to_string()).or_insert(Bucket {
+            units: capacity,
+            updated_ms: now_ms,
+        });
+        let elapsed = now_ms.saturating_sub(bucket.updated_ms);
+        bucket.units = bucket
+            .units
+            .saturating_add(elapsed.saturating_mul(self.limit.per_minute as u64))
+            .min(capacity);
+        bucket.updated_ms = bucket.updated_ms.max(now_ms);
+
+        let cost = count as u64 * UNITS_PER_REQUEST;
+        if bucket.units < cost {
+            return Err(AegisQError::LimitExceeded("Rate limit exceeded"));
+        }
+        bucket.units -= cost;
+        Ok(())
+    }
+
+    /// Forget senders whose bucket has refilled; returns how many were dropped
+    pub fn prune(&mut self, now_ms: u64) -> usize {
+        let capacity = self.capacity();
+        let per_minute = self.limit.per_minute as u64;
+        let before = self.buckets.len();
+        self.buckets.retain(|_, bucket| {
+            let refill = now_ms.saturating_sub(bucket.updated_ms).saturating_mul(per_minute);
+            bucket.units.saturating_add(refill) < capacity
+        });
+        before - self.buckets.len()
+    }
+
+    fn capacity(&self) -> u64 {
+        self.limit.burst as u64 * UNITS_PER_REQUEST
+    }
+}
+
+/// Single-use postage token
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PostageToken {
+    /// Issuer key epoch
+    pub epoch: u32,
+    pub nonce: [u8; TOKEN_NONCE_SIZE],
+    pub signature: Vec<u8>,
+}
+
+impl PostageToken {
+    /// Message signed by the issuer
+    pub fn message(epoch: u32, nonce: &[u8; TOKEN_NONCE_SIZE]) -> Vec<u8> {
+        let mut message = b"aegis-q-messenger-postage".to_vec();
+        message.extend_from_slice(&epoch.to_be_bytes());
+        message.extend_from_slice(nonce);
+        message
+    }
+}
+
+impl Drop for PostageToken {
+    fn drop(&mut self) {
+        zeroize(&mut self.nonce);
+    }
+}
+
+impl std::fmt::Debug for PostageToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PostageToken")
+            .field("epoch", &self.epoch)
+            .field("nonce", &"<redacted>")
+            .finish()
+    }
+}
+
+struct PendingToken {
+    nonce: [u8; TOKEN_NONCE_SIZE],
+    unblinder: Vec<u8>,
+}
+
+/// Client side: requests, unblinds and holds postage
+pub struct PostageWallet {
+    epoch: u32,
+    pending: Vec<PendingToken>,
+    tokens: Vec<PostageToken>,
+}
+
+impl PostageWallet {
+    /// Empty wallet
+    pub fn new() -> Self {
+        Self {
+            epoch: 0,
+            pending: Vec::new(),
+            tokens: Vec::new(),
+        }
+    }
+
+    /// Tokens ready to spend
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    /// Whether no tokens are left
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Prepare `count` blinded requests for the issuer key of `epoch`
+    ///
+    /// Replaces any request still waiting for signatures.
+    pub fn request(
+        &mut self,
+        scheme: &dyn BlindSignature,
+        epoch: u32,
+        public_key: &[u8],
+        count: usize,
+    ) -> Result<Vec<Vec<u8>>, AegisQError> {
+        self.clear_pending();
+        self.epoch = epoch;
+        let mut blinded = Vec::with_capacity(count);
+        for _ in 0..count {
+            let mut nonce = [0u8; TOKEN_NONCE_SIZE];
+            nonce.copy_from_slice(&random_bytes(TOKEN_NONCE_SIZE));
+            let (request, unblinder) = scheme.blind(public_key, &PostageToken::message(epoch, &nonce))?;
+            self.pending.push(PendingToken { nonce, unblinder });
+            blinded.push(request);
+        }
+        Ok(blinded)
+    }
+
+    /// Unblind the issuer's answer to `request`; returns the tokens added
+    ///
+    /// Signatures come in request order. Nothing is added if any of them
+    /// does not verify.
+    pub fn receive(
+        &mut self,
+        scheme: &dyn BlindSignature,
+        public_key: &[u8],
+        blind_signatures: &[Vec<u8>],
+    ) -> Result<usize, AegisQError> {
+        if blind_signatures.len() != self.pending.len() {
+            return Err(AegisQError::Protocol("Signature count mismatch"));
+        }
+        let mut tokens = Vec::with_capacity(self.pending.len());
+        for (pending, blind_signature) in self.pending.iter().zip(blind_signatures) {
+            let signature = scheme.unblind(public_key, &pending.unblinder, blind_signature)?;
+            if !scheme.verify(public_key, &PostageToken::message(self.epoch, &pending.nonce), &signature) {
+                return Err(AegisQError::AuthenticationFailed);
+            }
+            tokens.push(PostageToken {
+                epoch: self.epoch,
+                nonce: pending.nonce,
+                signature,
+            });
+        }
+        self.clear_pending();
+        let added = tokens.len();
+        self.tokens.extend(tokens);
+        Ok(added)
+    }
+
+    /// Take a token to attach to an outgoing post
+    pub fn take(&mut self) -> Option<PostageToken> {
+        self.tokens.pop()
+    }
+
+    fn clear_pending(&mut self) {
+        for pending in &mut self.pending {
+            zeroize(&mut pending.nonce);
+            zeroize(&mut pending.unblinder);
+        }
+        self.pending.clear();
+    }
+}
+
+impl Default for PostageWallet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for PostageWallet {
+    fn drop(&mut self) {
+        self.clear_pending();
+    }
+}
+
+impl std::fmt::Debug for PostageWallet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PostageWallet")
+            .field("pending", &self.pending.len())
+            .field("tokens", &self.tokens.len())
+            .finish()
+    }
+}
+
+/// Issuer side: signs blinded requests within a per-account quota
+pub struct PostageIssuer {
+    epoch: u32,
+    secret_key: Vec<u8>,
+    quota: RateLimiter,
+}
+
+impl PostageIssuer {
+    /// Issuer for the key of `epoch`, handing out tokens at `quota` per account
+    pub fn new(epoch: u32, secret_key: &[u8], quota: RateLimit) -> Self {
+        Self {
+            epoch,
+            secret_key: secret_key.to_vec(),
+            quota: RateLimiter::new(quota),
+        }
+    }
+
+    /// Current key epoch
+    pub fn epoch(&self) -> u32 {
+        self.epoch
+    }
+
+    /// Sign `blinded` requests for an authenticated `account`
+    ///
+    /// The whole request is refused if it exceeds the account's quota.
+    pub fn issue(
+        &mut self,
+        scheme: &dyn BlindSignature,
+        account: &str,
+        blinded: &[Vec<u8>],
+        now_ms: u64,
+    ) -> Result<Vec<Vec<u8>>, AegisQError> {
+        let count = u32::try_from(blinded.len()).map_err(|_| AegisQError::LimitExceeded("Rate limit exceeded"))?;
+        self.quota.take(account, count, now_ms)?;
+        blinded.iter().map(|request| scheme.sign_blinded(&self.secret_key, request)).collect()
+    }
+}
+
+impl Drop for PostageIssuer {
+    fn drop(&mut self) {
+        zeroize(&mut self.secret_key);
+    }
+}
+
+impl std::fmt::Debug for PostageIssuer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PostageIssuer")
+            .field("epoch", &self.epoch)
+            .field("secret_key", &"<redacted>")
+            .finish()
+    }
+}
+
+/// Relay side: checks tokens and remembers spent ones
+///
+/// Spent nonces are kept per issuer key epoch; retiring an epoch drops
+/// them along with the key, so memory stays bounded by key rotation.
+#[derive(Debug, Default)]
+pub struct PostageVerifier {
+    keys: BTreeMap<u32, Vec<u8>>,
+    spent: BTreeMap<u32, BTreeSet<[u8; TOKEN_NONCE_SIZE]>>,
+}
+
+impl PostageVerifier {
+    /// Verifier with no issuer keys
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept tokens of the issuer key of `epoch`
+    pub fn add_key(&mut self, epoch: u32, public_key: &[u8]) {
+        self.keys.insert(epoch, public_key.to_vec());
+    }
+
+    /// Stop accepting tokens of `epoch` and forget its spent tokens
+    pub fn retire_key(&mut self, epoch: u32) {
+        self.keys.remove(&epoch);
+        self.spent.remove(&epoch);
+    }
+
+    /// Check a token and mark it spent
+    pub fn redeem(&mut self, scheme: &dyn BlindSignature, token: &PostageToken) -> Result<(), AegisQError> {
+        let public_key = self.keys.get(&token.epoch).ok_or(AegisQError::NotFound("Unknown postage epoch"))?;
+        if !scheme.verify(public_key, &PostageToken::message(token.epoch, &token.nonce), &token.signature) {
+            return Err(AegisQError::AuthenticationFailed);
+        }
+        if !self.spent.entry(token.epoch).or_default().insert(token.nonce) {
+            return Err(AegisQError::Policy("Postage already spent"));
+        }
+        Ok(())
+    }
+}
+
+/// How a post was admitted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    /// Counted against the sender's bucket
+    Metered,
+    /// Paid with a postage token
+    Postage,
+}
+
+/// Relay admission: postage when attached, otherwise the sender's bucket
+#[derive(Debug)]
+pub struct RelayGate {
+    pub limiter: RateLimiter,
+    pub postage: PostageVerifier,
+}
+
+impl RelayGate {
+    /// Gate with `limit` per identified sender and no postage keys yet
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limiter: RateLimiter::new(limit),
+            postage: PostageVerifier::new(),
+        }
+    }
+
+    /// Decide whether to accept a post
+    ///
+    /// Anonymous posts (`sender` is `None`) must carry postage. An invalid
+    /// or spent token is rejected rather than falling back to the bucket.
+    pub fn admit(
+        &mut self,
+        scheme: &dyn BlindSignature,
+        sender: Option<&str>,
+        postage: Option<&PostageToken>,
+        now_ms: u64,
+    ) -> Result<Admission, AegisQError> {
+        match (postage, sender) {
+            (Some(token), _) => self.postage.redeem(scheme, token).map(|_| Admission::Postage),
+            (None, Some(sender)) => self.limiter.check(sender, now_ms).map(|_| Admission::Metered),
+            (None, None) => Err(AegisQError::Policy("Postage required")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha3::{Digest, Sha3_256};
+
+    /// Textbook RSA blind signatures over a 64-bit modulus (tests only)
+    struct ToyRsa;
+
+    const P: u128 = 4_294_967_291;
+    const Q: u128 = 4_294_967_279;
+    const N: u128 = P * Q;
+    const E: u128 = 65_537;
+
+    fn pow_mod(mut base: u128, mut exp: u128) -> u128 {
+        let mut result = 1;
+        base %= N;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = result * base % N;
+            }
+            base = base * base % N;
+            exp >>= 1;
+        }
+        result
+    }
+
+    fn inverse(value: u128, modulus: u128) -> u128 {
+        let (mut r0, mut r1) = (modulus as i128, value as i128);
+        let (mut t0, mut t1) = (0i128, 1i128);
+        while r1 != 0 {
+            let q = r0 / r1;
+            (r0, r1) = (r1, r0 - q * r1);
+            (t0, t1) = (t1, t0 - q * t1);
+        }
+        assert_eq!(r0, 1, "not invertible");
+        t0.rem_euclid(modulus as i128) as u128
+    }
+
+    fn hash(message: &[u8]) -> u128 {
+        let digest = Sha3_256::digest(message);
+        u64::from_be_bytes(digest[..8].try_into().unwrap()) as u128 % N
+    }
+
+    fn decode(bytes: &[u8]) -> Result<u128, AegisQError> {
+        let bytes: [u8; 8] = bytes.try_into().map_err(|_| AegisQError::InvalidLength("Bad toy RSA value"))?;
+        Ok(u64::from_be_bytes(bytes) as u128)
+    }
+
+    fn encode(value: u128) -> Vec<u8> {
+        (value as u64).to_be_bytes().to_vec()
+    }
+
+    impl BlindSignature for ToyRsa {
+        fn blind(&self, _: &[u8], message: &[u8]) -> Result<(Vec<u8>, Vec<u8>), AegisQError> {
+            let r = loop {
+                let r = decode(&random_bytes(8))? % N;
+                if r > 1 && !r.is_multiple_of(P) && !r.is_multiple_of(Q) {
+                    break r;
+                }
+            };
+            Ok((encode(hash(message) * pow_mod(r, E) % N), encode(r)))
+        }
+
+        fn sign_blinded(&self, _: &[u8], blinded: &[u8]) -> Result<Vec<u8>, AegisQError> {
+            Ok(encode(pow_mod(decode(blinded)?, inverse(E, (P - 1) * (Q - 1)))))
+        }
+
+        fn unblind(&self, _: &[u8], unblinder: &[u8], blind_signature: &[u8]) -> Result<Vec<u8>, AegisQError> {
+            Ok(encode(decode(blind_signature)? * inverse(decode(unblinder)?, N) % N))
+        }
+
+        fn verify(&self, _: &[u8], message: &[u8], signature: &[u8]) -> bool {
+            decode(signature).is_ok_and(|s| pow_mod(s, E) == hash(message))
+        }
+    }
+
+    #[test]
+    fn test_token_bucket_refills() {
+        let mut limiter = RateLimiter::new(RateLimit { burst: 2, per_minute: 60 });
+        limiter.check("alice", 0).unwrap();
+        limiter.check("alice", 0).unwrap();
+        assert_eq!(limiter.check("alice", 500), Err(AegisQError::LimitExceeded("Rate limit exceeded")));
+        limiter.check("bob", 500).unwrap();
+
+        // One request per second at 60/min
+        limiter.check("alice", 1_000).unwrap();
+        assert!(limiter.check("alice", 1_000).is_err());
+        assert!(limiter.take("alice", 3, 60_000).is_err());
+        limiter.take("alice", 2, 60_000).unwrap();
+
+        assert_eq!(limiter.prune(60_000), 1);
+        assert_eq!(limiter.prune(200_000), 1);
+    }
+
+    #[test]
+    fn test_postage_issue_and_redeem() {
+        let mut issuer = PostageIssuer::new(1, b"issuer-secret", RateLimit { burst: 3, per_minute: 1 });
+        let mut wallet = PostageWallet::new();
+
+        let blinded = wallet.request(&ToyRsa, issuer.epoch(), b"issuer-public", 3).unwrap();
+        let signatures = issuer.issue(&ToyRsa, "alice", &blinded, 0).unwrap();
+        assert_eq!(wallet.receive(&ToyRsa, b"issuer-public", &signatures).unwrap(), 3);
+        assert_eq!(wallet.len(), 3);
+
+        // Quota used up
+        let blinded = wallet.request(&ToyRsa, 1, b"issuer-public", 1).unwrap();
+        assert!(issuer.issue(&ToyRsa, "alice", &blinded, 0).is_err());
+
+        let mut gate = RelayGate::new(RateLimit::default());
+        gate.postage.add_key(1, b"issuer-public");
+        let token = wallet.take().unwrap();
+        assert_eq!(gate.admit(&ToyRsa, None, Some(&token), 0).unwrap(), Admission::Postage);
+        assert_eq!(gate.admit(&ToyRsa, None, Some(&token), 0), Err(AegisQError::Policy("Postage already spent")));
+        assert_eq!(gate.admit(&ToyRsa, None, None, 0), Err(AegisQError::Policy("Postage required")));
+        assert_eq!(gate.admit(&ToyRsa, Some("alice"), None, 0).unwrap(), Admission::Metered);
+
+        // Forged and retired tokens
+        let mut forged = wallet.take().unwrap();
+        forged.nonce[0] ^= 1;
+        assert_eq!(gate.admit(&ToyRsa, None, Some(&forged), 0), Err(AegisQError::AuthenticationFailed));
+        gate.postage.retire_key(1);
+        let token = wallet.take().unwrap();
+        assert!(matches!(gate.admit(&ToyRsa, None, Some(&token), 0), Err(AegisQError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_wallet_rejects_bad_signatures() {
+        let mut issuer = PostageIssuer::new(1, b"issuer-secret", RateLimit::default());
+        let mut wallet = PostageWallet::new();
+
+        let blinded = wallet.request(&ToyRsa, 1, b"issuer-public", 2).unwrap();
+        let mut signatures = issuer.issue(&ToyRsa, "alice", &blinded, 0).unwrap();
+        assert!(matches!(wallet.receive(&ToyRsa, b"issuer-public", &signatures[..1]), Err(AegisQError::Protocol(_))));
+        signatures[1][7] ^= 1;
+        assert_eq!(wallet.receive(&ToyRsa, b"issuer-public", &signatures), Err(AegisQError::AuthenticationFailed));
+        assert!(wallet.is_empty());
+    }
+}
@@ -0,0 +1,422 @@
+//! Prekey Replenishment
+//!
+//! Keeps the relay stocked with one-time prekeys and the signed prekey
+//! fresh. Poll-driven like `nat::PathUpgrade`: call
+//! `PrekeyReplenisher::poll` periodically (and after going online); it
+//! asks the relay how many one-time prekeys remain, and when they run low
+//! or the signed prekey is due for rotation, generates a new batch,
+//! uploads the public halves and keeps the private halves in a
+//! `PrekeyStore`.
+//!
+//! The store is persisted encrypted through `storage` and must be saved
+//! after every upload, or incoming sessions built on the new prekeys
+//! cannot be answered. One-time private keys are deleted when consumed;
+//! a rotated-out signed prekey is kept for a grace period for sessions
+//! started just before the rotation.
+//!
+//! The key algorithm is pluggable (`PrekeyCrypto`), as with
+//! `escrow::RecoveryKem`.
+
+use std::collections::BTreeMap;
+
+use aegis_q_core::AegisQError;
+use serde::{Deserialize, Serialize};
+use utils::keys::RootKey;
+use utils::memory::zeroize;
+
+use crate::storage::StorageEntry;
+
+/// Storage purpose of the encrypted store
+const STORAGE_PURPOSE: &str = "prekeys";
+
+/// Default low-water mark of one-time prekeys on the relay
+pub const DEFAULT_LOW_WATER: usize = 20;
+
+/// Default number of one-time prekeys per upload
+pub const DEFAULT_BATCH_SIZE: usize = 100;
+
+/// Default signed prekey lifetime (7 days)
+pub const DEFAULT_SIGNED_PREKEY_LIFETIME: u64 = 7 * 24 * 3600;
+
+/// Default retention of a rotated-out signed prekey (2 days)
+pub const DEFAULT_RETIRED_GRACE: u64 = 2 * 24 * 3600;
+
+/// Key generation and identity signatures for prekeys
+pub trait PrekeyCrypto {
+    /// Fresh key pair: (public key, secret key)
+    fn keypair(&self) -> Result<(Vec<u8>, Vec<u8>), AegisQError>;
+
+    /// Sign `message` with the identity key
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, AegisQError>;
+}
+
+/// Relay side of prekey distribution
+pub trait PrekeyRelay {
+    /// One-time prekeys the relay still holds for us
+    fn remaining_one_time(&mut self) -> Result<usize, AegisQError>;
+
+    /// Publish new public prekeys
+    fn upload(&mut self, upload: &PrekeyUpload) -> Result<(), AegisQError>;
+}
+
+/// Message signed for a signed prekey: label || id || public key
+pub fn signed_prekey_message(id: u32, public_key: &[u8]) -> Vec<u8> {
+    let mut message = b"aegis-q-messenger-signed-prekey".to_vec();
+    message.extend_from_slice(&id.to_be_bytes());
+    message.extend_from_slice(public_key);
+    message
+}
+
+/// Public half of a one-time prekey
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicPrekey {
+    pub id: u32,
+    pub public_key: Vec<u8>,
+}
+
+/// Public half of a signed prekey
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedPrekey {
+    pub id: u32,
+    pub public_key: Vec<u8>,
+    /// Identity signature over `signed_prekey_message(id, public_key)`
+    pub signature: Vec<u8>,
+    pub created_at: u64,
+}
+
+/// One upload to the relay
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PrekeyUpload {
+    /// New signed prekey, when rotating
+    pub signed_prekey: Option<SignedPrekey>,
+    pub one_time_prekeys: Vec<PublicPrekey>,
+}
+
+/// Replenishment thresholds and schedule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplenishConfig {
+    /// Upload a batch when the relay holds fewer one-time prekeys
+    pub low_water: usize,
+    /// One-time prekeys per batch
+    pub batch_size: usize,
+    /// Rotate the signed prekey after this many seconds
+    pub signed_prekey_lifetime: u64,
+    /// Keep a rotated-out signed prekey for this many seconds
+    pub retired_grace: u64,
+}
+
+impl Default for ReplenishConfig {
+    fn default() -> Self {
+        Self {
+            low_water: DEFAULT_LOW_WATER,
+            batch_size: DEFAULT_BATCH_SIZE,
+            signed_prekey_lifetime: DEFAULT_SIGNED_PREKEY_LIFETIME,
+            retired_grace: DEFAULT_RETIRED_GRACE,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct StoredSignedPrekey {
+    id: u32,
+    secret_key: Vec<u8>,
+    created_at: u64,
+    /// Set once rotated out
+    retired_at: Option<u64>,
+}
+
+/// Private halves of our prekeys
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrekeyStore {
+    next_id: u32,
+    one_time: BTreeMap<u32, Vec<u8>>,
+    /// Current signed prekey last, rotated-out ones before it
+    signed: Vec<StoredSignedPrekey>,
+}
+
+impl PrekeyStore {
+    /// Empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take the secret of a one-time prekey, deleting it from the store
+    pub fn take_one_time(&mut self, id: u32) -> Option<Vec<u8>> {
+        self.one_time.remove(&id)
+    }
+
+    /// Secret of a signed prekey that is current or within its grace period
+    pub fn signed_prekey_secret(&self, id: u32) -> Option<&[u8]> {
+        self.signed.iter().find(|prekey| prekey.id == id).map(|prekey| prekey.secret_key.as_slice())
+    }
+
+    /// One-time prekeys held locally
+    pub fn one_time_count(&self) -> usize {
+        self.one_time.len()
+    }
+
+    /// ID of the current signed prekey
+    pub fn current_signed_prekey(&self) -> Option<u32> {
+        self.current().map(|prekey| prekey.id)
+    }
+
+    /// Encrypt the store for local storage
+    pub fn to_entry(&self, master_key: &RootKey) -> Result<StorageEntry, AegisQError> {
+        let mut bytes = serde_json::to_vec(self).map_err(|_| AegisQError::Serialization("Failed to encode prekey store"))?;
+        let entry = StorageEntry::store(&bytes, master_key, STORAGE_PURPOSE);
+        zeroize(&mut bytes);
+        entry
+    }
+
+    /// Decrypt a store saved with `to_entry`
+    pub fn from_entry(entry: &StorageEntry, master_key: &RootKey) -> Result<Self, AegisQError> {
+        if entry.purpose != STORAGE_PURPOSE {
+            return Err(AegisQError::InvalidInput("Not a prekey store entry"));
+        }
+        let mut bytes = entry.retrieve(master_key)?;
+        let store = serde_json::from_slice(&bytes).map_err(|_| AegisQError::Serialization("Malformed prekey store"));
+        zeroize(&mut bytes);
+        store
+    }
+
+    fn current(&self) -> Option<&StoredSignedPrekey> {
+        self.signed.last().filter(|prekey| prekey.retired_at.is_none())
+    }
+
+    fn allocate_id(&mut self) -> Result<u32, AegisQError> {
+        let id = self.next_id;
+        self.next_id = id.checked_add(1).ok_or(AegisQError::LimitExceeded("Prekey IDs exhausted"))?;
+        Ok(id)
+    }
+}
+
+impl Drop for PrekeyStore {
+    fn drop(&mut self) {
+        for secret in self.one_time.values_mut() {
+            zeroize(secret);
+        }
+        for prekey in &mut self.signed {
+            zeroize(&mut prekey.secret_key);
+        }
+    }
+}
+
+impl std::fmt::Debug for PrekeyStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PrekeyStore")
+            .field("one_time", &self.one_time.len())
+            .field("signed", &self.signed.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Poll-driven prekey maintenance
+#[derive(Debug)]
+pub struct PrekeyReplenisher {
+    config: ReplenishConfig,
+    store: PrekeyStore,
+}
+
+impl PrekeyReplenisher {
+    /// Resume from a (possibly empty) persisted store
+    pub fn new(config: ReplenishConfig, store: PrekeyStore) -> Self {
+        Self { config, store }
+    }
+
+    /// Private halves, for answering sessions and persisting
+    pub fn store(&self) -> &PrekeyStore {
+        &self.store
+    }
+
+    /// Mutable store (to take consumed one-time prekeys)
+    pub fn store_mut(&mut self) -> &mut PrekeyStore {
+        &mut self.store
+    }
+
+    /// Check the relay and upload what is missing
+    ///
+    /// Returns the upload made, if any; persist the store whenever it is
+    /// `Some`. If the upload fails the new keys are discarded and the next
+    /// poll tries again.
+    pub fn poll(&mut self, relay: &mut dyn PrekeyRelay, crypto: &dyn PrekeyCrypto, now: u64) -> Result<Option<PrekeyUpload>, AegisQError> {
+        // Rotated-out signed prekeys past their grace period are dropped
+        let grace = self.config.retired_grace;
+        self.store.signed.retain_mut(|prekey| match prekey.retired_at {
+            Some(retired_at) if now.saturating_sub(retired_at) >= grace => {
+                zeroize(&mut prekey.secret_key);
+                false
+            }
+            _ => true,
+        });
+
+        let rotate = self
+            .store
+            .current()
+            .is_none_or(|prekey| now.saturating_sub(prekey.created_at) >= self.config.signed_prekey_lifetime);
+        let refill = relay.remaining_one_time()? < self.config.low_water;
+        if !rotate && !refill {
+            return Ok(None);
+        }
+
+        let mut staged = self.store.clone();
+        let mut upload = PrekeyUpload::default();
+        if rotate {
+            let (public_key, secret_key) = crypto.keypair()?;
+            let id = staged.allocate_id()?;
+            let signature = crypto.sign(&signed_prekey_message(id, &public_key))?;
+            if let Some(previous) = staged.signed.last_mut() {
+                previous.retired_at.get_or_insert(now);
+            }
+            staged.signed.push(StoredSignedPrekey {
+                id,
+                secret_key,
+                created_at: now,
+                retired_at: None,
+            });
+            upload.signed_prekey = Some(SignedPrekey {
+                id,
+                public_key,
+                signature,
+                created_at: now,
+            });
+        }
+        if refill {
+            for _ in 0..self.config.batch_size {
+                let (public_key, secret_key) = crypto.keypair()?;
+                let id = staged.allocate_id()?;
+                staged.one_time.insert(id, secret_key);
+                upload.one_time_prekeys.push(PublicPrekey { id, public_key });
+            }
+        }
+
+        relay.upload(&upload)?;
+        self.store = staged;
+        Ok(Some(upload))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    use sha3::{Digest, Sha3_256};
+
+    /// Counter-based key pairs; the "public key" is a hash of the secret
+    #[derive(Default)]
+    struct TestCrypto {
+        counter: Cell<u32>,
+    }
+
+    impl PrekeyCrypto for TestCrypto {
+        fn keypair(&self) -> Result<(Vec<u8>, Vec<u8>), AegisQError> {
+            self.counter.set(self.counter.get() + 1);
+            let secret = self.counter.get().to_be_bytes().to_vec();
+            Ok((Sha3_256::digest(&secret).to_vec(), secret))
+        }
+
+        fn sign(&self, message: &[u8]) -> Result<Vec<u8>, AegisQError> {
+            Ok(Sha3_256::digest(message).to_vec())
+        }
+    }
+
+    #[derive(Default)]
+    struct TestRelay {
+        one_time: usize,
+        uploads: Vec<PrekeyUpload>,
+        offline: bool,
+    }
+
+    impl PrekeyRelay for TestRelay {
+        fn remaining_one_time(&mut self) -> Result<usize, AegisQError> {
+            Ok(self.one_time)
+        }
+
+        fn upload(&mut self, upload: &PrekeyUpload) -> Result<(), AegisQError> {
+            if self.offline {
+                return Err(AegisQError::Io("Relay unreachable"));
+            }
+            self.one_time += upload.one_time_prekeys.len();
+            self.uploads.push(upload.clone());
+            Ok(())
+        }
+    }
+
+    fn config() -> ReplenishConfig {
+        ReplenishConfig {
+            low_water: 3,
+            batch_size: 5,
+            signed_prekey_lifetime: 100,
+            retired_grace: 50,
+        }
+    }
+
+    #[test]
+    fn test_replenish_below_low_water() {
+        let (crypto, mut relay) = (TestCrypto::default(), TestRelay::default());
+        let mut replenisher = PrekeyReplenisher::new(config(), PrekeyStore::new());
+
+        let first = replenisher.poll(&mut relay, &crypto, 0).unwrap().unwrap();
+        let signed = first.signed_prekey.unwrap();
+        assert_eq!(signed.signature, crypto.sign(&signed_prekey_message(signed.id, &signed.public_key)).unwrap());
+        assert_eq!(first.one_time_prekeys.len(), 5);
+        assert_eq!(replenisher.store().one_time_count(), 5);
+
+        // Stocked: nothing to do
+        assert_eq!(replenisher.poll(&mut relay, &crypto, 10).unwrap(), None);
+
+        // Peers consume prekeys; we answer with the private halves
+        relay.one_time = 2;
+        let id = first.one_time_prekeys[0].id;
+        assert!(replenisher.store_mut().take_one_time(id).is_some());
+        assert!(replenisher.store_mut().take_one_time(id).is_none());
+
+        let refill = replenisher.poll(&mut relay, &crypto, 20).unwrap().unwrap();
+        assert!(refill.signed_prekey.is_none());
+        assert_eq!(refill.one_time_prekeys.len(), 5);
+        assert!(refill.one_time_prekeys.iter().all(|p| p.id > first.one_time_prekeys[4].id));
+    }
+
+    #[test]
+    fn test_signed_prekey_rotation_and_grace() {
+        let (crypto, mut relay) = (TestCrypto::default(), TestRelay { one_time: 10, ..Default::default() });
+        let mut replenisher = PrekeyReplenisher::new(config(), PrekeyStore::new());
+
+        let old = replenisher.poll(&mut relay, &crypto, 0).unwrap().unwrap().signed_prekey.unwrap();
+        assert_eq!(replenisher.poll(&mut relay, &crypto, 99).unwrap(), None);
+
+        let new = replenisher.poll(&mut relay, &crypto, 100).unwrap().unwrap().signed_prekey.unwrap();
+        assert_eq!(replenisher.store().current_signed_prekey(), Some(new.id));
+        // The old one still answers in-flight sessions during the grace period
+        assert!(replenisher.store().signed_prekey_secret(old.id).is_some());
+
+        replenisher.poll(&mut relay, &crypto, 150).unwrap();
+        assert!(replenisher.store().signed_prekey_secret(old.id).is_none());
+        assert!(replenisher.store().signed_prekey_secret(new.id).is_some());
+    }
+
+    #[test]
+    fn test_failed_upload_and_persistence() {
+        let crypto = TestCrypto::default();
+        let mut relay = TestRelay { offline: true, ..Default::default() };
+        let mut replenisher = PrekeyReplenisher::new(config(), PrekeyStore::new());
+
+        assert_eq!(replenisher.poll(&mut relay, &crypto, 0), Err(AegisQError::Io("Relay unreachable")));
+        assert_eq!(replenisher.store().one_time_count(), 0);
+        assert_eq!(replenisher.store().current_signed_prekey(), None);
+
+        relay.offline = false;
+        replenisher.poll(&mut relay, &crypto, 1).unwrap().unwrap();
+
+        let master_key = RootKey::from_bytes(b"prekey-store-master-key");
+        let entry = replenisher.store().to_entry(&master_key).unwrap();
+        let restored = PrekeyStore::from_entry(&entry, &master_key).unwrap();
+        assert!(restored == *replenisher.store());
+
+        // A restarted client continues the ID sequence
+        let mut resumed = PrekeyReplenisher::new(config(), restored);
+        relay.one_time = 0;
+        let upload = resumed.poll(&mut relay, &crypto, 2).unwrap().unwrap();
+        assert_eq!(upload.one_time_prekeys[0].id, 6);
+    }
+}
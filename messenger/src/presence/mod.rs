@@ -0,0 +1,313 @@
+//! Presence
+//!
+//! Online/away status and last-seen time, published through an encrypted
+//! `channel::Channel` whose members are exactly the contacts allowed to see
+//! it. Visibility is enforced client-side by channel membership: whenever
+//! the policy or contact list changes the audience, the channel key
+//! rotates and only the new audience receives it, so a contact dropped
+//! from the audience stops reading updates.
+//!
+//! Last-seen time is rounded down to the configured granularity before it
+//! leaves the device, or omitted entirely.
+
+use std::collections::BTreeSet;
+
+use aegis_q_core::AegisQError;
+use serde::{Deserialize, Serialize};
+
+use crate::channel::{Channel, ChannelKeyShare, ChannelRelay};
+
+/// Presence status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PresenceStatus {
+    Online,
+    Away,
+    Offline,
+}
+
+/// Who may see presence
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Visibility {
+    Nobody,
+    /// All contacts
+    #[default]
+    Contacts,
+    /// Only the listed contacts
+    Selected(BTreeSet<String>),
+}
+
+/// Precision of the shared last-seen time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LastSeenGranularity {
+    /// Not shared
+    Hidden,
+    Minute,
+    #[default]
+    Hour,
+    Day,
+}
+
+impl LastSeenGranularity {
+    /// Round `time` down to this granularity; `None` when hidden
+    pub fn coarsen(self, time: u64) -> Option<u64> {
+        let step = match self {
+            LastSeenGranularity::Hidden => return None,
+            LastSeenGranularity::Minute => 60,
+            LastSeenGranularity::Hour => 3600,
+            LastSeenGranularity::Day => 24 * 3600,
+        };
+        Some(time - time % step)
+    }
+}
+
+/// Presence privacy settings
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PresencePolicy {
+    pub visibility: Visibility,
+    pub last_seen: LastSeenGranularity,
+}
+
+/// Update published to the audience
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PresenceUpdate {
+    pub status: PresenceStatus,
+    /// Coarsened last-seen time, if shared
+    pub last_seen: Option<u64>,
+}
+
+/// New presence key to deliver to `recipients` over their 1:1 sessions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PresenceKeyUpdate {
+    pub recipients: Vec<String>,
+    pub share: ChannelKeyShare,
+}
+
+/// Channel ID of a user's presence
+pub fn presence_channel_id(user_id: &str) -> String {
+    format!("presence:{}", user_id)
+}
+
+/// Publishing side: our own presence
+#[derive(Debug)]
+pub struct PresencePublisher {
+    policy: PresencePolicy,
+    contacts: BTreeSet<String>,
+    channel: Channel,
+}
+
+impl PresencePublisher {
+    /// Start publishing our presence
+    ///
+    /// Returns the initial key update for the audience, if it is not empty.
+    pub fn new(user_id: &str, policy: PresencePolicy, contacts: impl IntoIterator<Item = String>) -> (Self, Option<PresenceKeyUpdate>) {
+        let (channel, _) = Channel::create(&presence_channel_id(user_id), []);
+        let mut publisher = Self {
+            policy,
+            contacts: contacts.into_iter().collect(),
+            channel,
+        };
+        let update = publisher.sync_audience();
+        (publisher, update)
+    }
+
+    /// Current policy
+    pub fn policy(&self) -> &PresencePolicy {
+        &self.policy
+    }
+
+    /// Contacts currently allowed to see our presence
+    pub fn audience(&self) -> impl Iterator<Item = &str> {
+        self.channel.members()
+    }
+
+    /// Change the policy; returns a key update if the audience changed
+    pub fn set_policy(&mut self, policy: PresencePolicy) -> Option<PresenceKeyUpdate> {
+        self.policy = policy;
+        self.sync_audience()
+    }
+
+    /// Add a contact; returns a key update if the audience changed
+    pub fn add_contact(&mut self, contact: &str) -> Option<PresenceKeyUpdate> {
+        self.contacts.insert(contact.to_string());
+        self.sync_audience()
+    }
+
+    /// Remove a contact; returns a key update if the audience changed
+    pub fn remove_contact(&mut self, contact: &str) -> Option<PresenceKeyUpdate> {
+        self.contacts.remove(contact);
+        self.sync_audience()
+    }
+
+    /// Publish our status as of `now`
+    ///
+    /// Returns `false` without publishing when nobody may see it.
+    pub fn publish(&self, relay: &mut dyn ChannelRelay, status: PresenceStatus, now: u64) -> Result<bool, AegisQError> {
+        if self.channel.members().next().is_none() {
+            return Ok(false);
+        }
+        let update = PresenceUpdate {
+            status,
+            last_seen: self.policy.last_seen.coarsen(now),
+        };
+        let encoded = serde_json::to_vec(&update).map_err(|_| AegisQError::Serialization("Failed to encode presence"))?;
+        self.channel.publish(relay, &encoded)?;
+        Ok(true)
+    }
+
+    /// Contacts the policy allows
+    fn allowed(&self) -> BTreeSet<String> {
+        match &self.policy.visibility {
+            Visibility::Nobody => BTreeSet::new(),
+            Visibility::Contacts => self.contacts.clone(),
+            Visibility::Selected(selected) => self.contacts.intersection(selected).cloned().collect(),
+        }
+    }
+
+    /// Bring channel membership in line with the policy
+    fn sync_audience(&mut self) -> Option<PresenceKeyUpdate> {
+        let allowed = self.allowed();
+        let current: BTreeSet<String> = self.channel.members().map(str::to_string).collect();
+        if allowed == current {
+            return None;
+        }
+
+        let mut share = None;
+        for removed in current.difference(&allowed) {
+            share = Some(self.channel.remove_member(removed).expect("current member"));
+        }
+        for added in allowed.difference(&current) {
+            share = Some(self.channel.add_member(added));
+        }
+        let share = share.expect("audience changed");
+        if allowed.is_empty() {
+            return None;
+        }
+        Some(PresenceKeyUpdate {
+            recipients: allowed.into_iter().collect(),
+            share,
+        })
+    }
+}
+
+/// Receiving side: one contact's presence
+#[derive(Debug)]
+pub struct PresenceSubscription {
+    channel: Channel,
+    latest: Option<PresenceUpdate>,
+}
+
+impl PresenceSubscription {
+    /// Subscribe with the contact's presence key
+    pub fn new(share: &ChannelKeyShare) -> Self {
+        Self {
+            channel: Channel::join(share),
+            latest: None,
+        }
+    }
+
+    /// Install a rotated presence key
+    pub fn accept_key(&mut self, share: &ChannelKeyShare) -> Result<(), AegisQError> {
+        self.channel.accept_key(share)
+    }
+
+    /// Last known presence
+    pub fn latest(&self) -> Option<&PresenceUpdate> {
+        self.latest.as_ref()
+    }
+
+    /// Fetch new updates and return the latest presence
+    ///
+    /// Updates under an older key epoch are ignored: that key may be held
+    /// by someone no longer in the audience.
+    pub fn fetch(&mut self, relay: &mut dyn ChannelRelay) -> Result<Option<&PresenceUpdate>, AegisQError> {
+        let epoch = self.channel.epoch();
+        for message in self.channel.fetch(relay)? {
+            if message.epoch < epoch {
+                continue;
+            }
+            if let Ok(update) = serde_json::from_slice(&message.plaintext) {
+                self.latest = Some(update);
+            }
+        }
+        Ok(self.latest.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[derive(Default)]
+    struct TestRelay {
+        topics: BTreeMap<[u8; 32], Vec<Vec<u8>>>,
+    }
+
+    impl ChannelRelay for TestRelay {
+        fn publish(&mut self, topic: &[u8; 32], blob: &[u8]) -> Result<(), AegisQError> {
+            self.topics.entry(*topic).or_default().push(blob.to_vec());
+            Ok(())
+        }
+
+        fn fetch(&mut self, topic: &[u8; 32], after: u64) -> Result<Vec<(u64, Vec<u8>)>, AegisQError> {
+            let blobs = self.topics.get(topic).map(Vec::as_slice).unwrap_or_default();
+            Ok(blobs.iter().enumerate().map(|(i, blob)| (i as u64 + 1, blob.clone())).skip(after as usize).collect())
+        }
+    }
+
+    fn contacts() -> Vec<String> {
+        vec!["bob".to_string(), "carol".to_string()]
+    }
+
+    #[test]
+    fn test_presence_with_coarse_last_seen() {
+        let mut relay = TestRelay::default();
+        let (alice, update) = PresencePublisher::new("alice", PresencePolicy::default(), contacts());
+        let update = update.unwrap();
+        assert_eq!(update.recipients, contacts());
+
+        let mut bob = PresenceSubscription::new(&update.share);
+        assert!(alice.publish(&mut relay, PresenceStatus::Away, 7_265).unwrap());
+        let seen = bob.fetch(&mut relay).unwrap().unwrap();
+        assert_eq!((seen.status, seen.last_seen), (PresenceStatus::Away, Some(7_200)));
+
+        assert_eq!(LastSeenGranularity::Minute.coarsen(7_265), Some(7_260));
+        assert_eq!(LastSeenGranularity::Day.coarsen(90_000), Some(86_400));
+        assert_eq!(LastSeenGranularity::Hidden.coarsen(7_265), None);
+    }
+
+    #[test]
+    fn test_selected_audience_rotates_key() {
+        let mut relay = TestRelay::default();
+        let (mut alice, update) = PresencePublisher::new("alice", PresencePolicy::default(), contacts());
+        let share = update.unwrap().share;
+        let mut bob = PresenceSubscription::new(&share);
+        let mut carol = PresenceSubscription::new(&share);
+
+        // Narrow to Bob only: Carol is left out of the new key
+        let selected = Visibility::Selected(BTreeSet::from(["bob".to_string(), "mallory".to_string()]));
+        let update = alice.set_policy(PresencePolicy { visibility: selected, ..Default::default() }).unwrap();
+        assert_eq!(update.recipients, ["bob"]);
+        bob.accept_key(&update.share).unwrap();
+
+        alice.publish(&mut relay, PresenceStatus::Online, 100).unwrap();
+        assert_eq!(bob.fetch(&mut relay).unwrap().unwrap().status, PresenceStatus::Online);
+        assert_eq!(carol.fetch(&mut relay).unwrap(), None);
+
+        // Unchanged audience: no rotation
+        assert_eq!(alice.add_contact("dave"), None);
+    }
+
+    #[test]
+    fn test_share_with_nobody() {
+        let mut relay = TestRelay::default();
+        let (mut alice, update) = PresencePublisher::new("alice", PresencePolicy::default(), contacts());
+        let mut bob = PresenceSubscription::new(&update.unwrap().share);
+
+        let nobody = PresencePolicy { visibility: Visibility::Nobody, last_seen: LastSeenGranularity::Hidden };
+        assert_eq!(alice.set_policy(nobody), None);
+        assert_eq!(alice.audience().count(), 0);
+        assert!(!alice.publish(&mut relay, PresenceStatus::Online, 100).unwrap());
+        assert_eq!(bob.fetch(&mut relay).unwrap(), None);
+    }
+}
@@ -0,0 +1,288 @@
+//! QR Payloads
+//!
+//! Compact binary payloads for scanning flows: identity fingerprint
+//! verification, prekey bundle exchange and device linking.
+//!
+//! Binary layout:
+//! - version (1 byte)
+//! - kind (1 byte)
+//! - body length (2 bytes, big-endian)
+//! - body (kind-specific, length-prefixed fields)
+//! - checksum (4 bytes, SHA3-256 over everything before it)
+//!
+//! The armored form is `AEGISQ:` followed by Base45 (RFC 9285), which uses
+//! only QR alphanumeric-mode characters and so encodes densely.
+//! Parsers are strict: unknown versions or kinds, wrong field sizes,
+//! trailing bytes and checksum mismatches are all rejected.
+
+use aegis_q_core::AegisQError;
+use sha3::{Digest, Sha3_256};
+use utils::memory::zeroize;
+use utils::wire::{PrekeyBundleWire, QrPayloadWire, Wire};
+
+/// Current payload version
+pub const QR_VERSION: u8 = 1;
+
+/// Armor prefix
+pub const ARMOR_PREFIX: &str = "AEGISQ:";
+
+/// Identity fingerprint size
+pub const FINGERPRINT_SIZE: usize = 32;
+
+/// Device-linking secret size
+pub const LINK_SECRET_SIZE: usize = 32;
+
+/// Maximum device name length (bytes)
+pub const MAX_DEVICE_NAME: usize = 64;
+
+const CHECKSUM_SIZE: usize = 4;
+const KIND_IDENTITY: u8 = 0x01;
+const KIND_PREKEY_BUNDLE: u8 = 0x02;
+const KIND_DEVICE_LINK: u8 = 0x03;
+
+const BASE45_ALPHABET: &[u8; 45] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// Prekey bundle as exchanged by scanning
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrekeyBundle {
+    pub identity_key: Vec<u8>,
+    pub signed_prekey: Vec<u8>,
+    pub prekey_signature: Vec<u8>,
+    pub one_time_prekey: Option<Vec<u8>>,
+}
+
+/// Scannable payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QrPayload {
+    /// Fingerprint for out-of-band identity verification
+    IdentityFingerprint([u8; FINGERPRINT_SIZE]),
+    /// Prekey bundle for starting a session without a server
+    PrekeyBundle(PrekeyBundle),
+    /// Secret for linking a new device to an account
+    DeviceLink {
+        secret: [u8; LINK_SECRET_SIZE],
+        device_name: String,
+    },
+}
+
+impl QrPayload {
+    /// Encode to binary
+    pub fn encode(&self) -> Result<Vec<u8>, AegisQError> {
+        let (kind, mut body) = match self {
+            QrPayload::IdentityFingerprint(fingerprint) => (KIND_IDENTITY, fingerprint.to_vec()),
+            QrPayload::PrekeyBundle(bundle) => {
+                let body = PrekeyBundleWire {
+                    identity_key: bundle.identity_key.clone(),
+                    signed_prekey: bundle.signed_prekey.clone(),
+                    prekey_signature: bundle.prekey_signature.clone(),
+                    one_time_prekey: bundle.one_time_prekey.clone(),
+                }
+                .to_wire()
+                .map_err(AegisQError::Serialization)?;
+                (KIND_PREKEY_BUNDLE, body)
+            }
+            QrPayload::DeviceLink { secret, device_name } => {
+                if device_name.len() > MAX_DEVICE_NAME {
+                    return Err(AegisQError::InvalidLength("Device name too long"));
+                }
+                let mut body = secret.to_vec();
+                body.extend_from_slice(device_name.as_bytes());
+                (KIND_DEVICE_LINK, body)
+            }
+        };
+
+        let mut wire = QrPayloadWire {
+            version: QR_VERSION,
+            kind,
+            body: std::mem::take(&mut body),
+        };
+        let encoded = wire.to_wire();
+        zeroize(&mut wire.body);
+        let mut out = encoded.map_err(|_| AegisQError::InvalidLength("Payload too large"))?;
+        let check = checksum(&out);
+        out.extend_from_slice(&check);
+        Ok(out)
+    }
+
+    /// Decode and strictly validate binary payload
+    pub fn decode(bytes: &[u8]) -> Result<Self, AegisQError> {
+        if bytes.len() < 4 + CHECKSUM_SIZE {
+            return Err(AegisQError::InvalidLength("Payload too short"));
+        }
+        let (content, check) = bytes.split_at(bytes.len() - CHECKSUM_SIZE);
+        if checksum(content) != check {
+            return Err(AegisQError::AuthenticationFailed);
+        }
+        if content[0] != QR_VERSION {
+            return Err(AegisQError::Unsupported("Unsupported payload version"));
+        }
+        let wire = QrPayloadWire::from_wire(content).map_err(|_| AegisQError::InvalidLength("Payload length mismatch"))?;
+        let body = &wire.body[..];
+
+        match wire.kind {
+            KIND_IDENTITY => {
+                let fingerprint = body.try_into().map_err(|_| AegisQError::InvalidLength("Invalid fingerprint size"))?;
+                Ok(QrPayload::IdentityFingerprint(fingerprint))
+            }
+            KIND_PREKEY_BUNDLE => {
+                let bundle = PrekeyBundleWire::from_wire(body).map_err(AegisQError::Serialization)?;
+                Ok(QrPayload::PrekeyBundle(PrekeyBundle {
+                    identity_key: bundle.identity_key,
+                    signed_prekey: bundle.signed_prekey,
+                    prekey_signature: bundle.prekey_signature,
+                    one_time_prekey: bundle.one_time_prekey,
+                }))
+            }
+            KIND_DEVICE_LINK => {
+                if body.len() < LINK_SECRET_SIZE || body.len() > LINK_SECRET_SIZE + MAX_DEVICE_NAME {
+                    return Err(AegisQError::InvalidLength("Invalid device link size"));
+                }
+                let (secret, name) = body.split_at(LINK_SECRET_SIZE);
+                let device_name = std::str::from_utf8(name).map_err(|_| AegisQError::InvalidInput("Invalid device name"))?;
+                Ok(QrPayload::DeviceLink {
+                    secret: secret.try_into().map_err(|_| AegisQError::InvalidLength("Invalid device link size"))?,
+                    device_name: device_name.to_string(),
+                })
+            }
+            _ => Err(AegisQError::Unsupported("Unknown payload kind")),
+        }
+    }
+
+    /// Armored text for QR alphanumeric mode
+    pub fn to_armored(&self) -> Result<String, AegisQError> {
+        let mut bytes = self.encode()?;
+        let armored = format!("{}{}", ARMOR_PREFIX, base45_encode(&bytes));
+        zeroize(&mut bytes);
+        Ok(armored)
+    }
+
+    /// Parse armored text
+    pub fn from_armored(text: &str) -> Result<Self, AegisQError> {
+        let body = text.strip_prefix(ARMOR_PREFIX).ok_or(AegisQError::Serialization("Missing armor prefix"))?;
+        let mut bytes = base45_decode(body)?;
+        let payload = Self::decode(&bytes);
+        zeroize(&mut bytes);
+        payload
+    }
+}
+
+impl Drop for QrPayload {
+    fn drop(&mut self) {
+        if let QrPayload::DeviceLink { secret, .. } = self {
+            zeroize(secret);
+        }
+    }
+}
+
+fn checksum(bytes: &[u8]) -> [u8; CHECKSUM_SIZE] {
+    let mut hasher = Sha3_256::new();
+    hasher.update(b"aegis-q-messenger-qr");
+    hasher.update(bytes);
+    let hash = hasher.finalize();
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// Base45 encode (RFC 9285)
+pub fn base45_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(2) * 3);
+    for chunk in bytes.chunks(2) {
+        let (mut value, digits) = match chunk {
+            [a, b] => ((*a as u32) * 256 + *b as u32, 3),
+            [a] => (*a as u32, 2),
+            _ => unreachable!(),
+        };
+        for _ in 0..digits {
+            out.push(BASE45_ALPHABET[(value % 45) as usize] as char);
+            value /= 45;
+        }
+    }
+    out
+}
+
+/// Base45 decode (RFC 9285), rejecting non-canonical input
+pub fn base45_decode(text: &str) -> Result<Vec<u8>, AegisQError> {
+    let digits: Vec<u32> = text
+        .bytes()
+        .map(|c| BASE45_ALPHABET.iter().position(|&a| a == c).map(|p| p as u32).ok_or(AegisQError::Serialization("Invalid Base45 character")))
+        .collect::<Result<_, _>>()?;
+
+    let mut out = Vec::with_capacity(digits.len() / 3 * 2 + 1);
+    for chunk in digits.chunks(3) {
+        match chunk {
+            [c, d, e] => {
+                let value = c + d * 45 + e * 45 * 45;
+                if value > 0xFFFF {
+                    return Err(AegisQError::Serialization("Invalid Base45 value"));
+                }
+                out.extend_from_slice(&(value as u16).to_be_bytes());
+            }
+            [c, d] => {
+                let value = c + d * 45;
+                if value > 0xFF {
+                    return Err(AegisQError::Serialization("Invalid Base45 value"));
+                }
+                out.push(value as u8);
+            }
+            _ => return Err(AegisQError::InvalidLength("Invalid Base45 length")),
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base45_rfc_vectors() {
+        assert_eq!(base45_encode(b"AB"), "BB8");
+        assert_eq!(base45_encode(b"Hello!!"), "%69 VD92EX0");
+        assert_eq!(base45_encode(b"base-45"), "UJCLQE7W581");
+        assert_eq!(base45_decode("QED8WEX0").unwrap(), b"ietf!");
+        assert!(base45_decode("GGW").is_err());
+        assert!(base45_decode("A").is_err());
+    }
+
+    #[test]
+    fn test_qr_payload_roundtrip() {
+        let payloads = vec![
+            QrPayload::IdentityFingerprint([7u8; FINGERPRINT_SIZE]),
+            QrPayload::PrekeyBundle(PrekeyBundle {
+                identity_key: vec![1; 48],
+                signed_prekey: vec![2; 48],
+                prekey_signature: vec![3; 64],
+                one_time_prekey: Some(vec![4; 48]),
+            }),
+            QrPayload::DeviceLink {
+                secret: [9u8; LINK_SECRET_SIZE],
+                device_name: "laptop".to_string(),
+            },
+        ];
+
+        for payload in payloads {
+            let armored = payload.to_armored().unwrap();
+            assert!(armored.bytes().skip(ARMOR_PREFIX.len()).all(|c| BASE45_ALPHABET.contains(&c)));
+            assert_eq!(QrPayload::from_armored(&armored).unwrap(), payload);
+        }
+    }
+
+    #[test]
+    fn test_qr_payload_strict_validation() {
+        let encoded = QrPayload::IdentityFingerprint([7u8; FINGERPRINT_SIZE]).encode().unwrap();
+
+        let mut corrupted = encoded.clone();
+        corrupted[10] ^= 1;
+        assert_eq!(QrPayload::decode(&corrupted), Err(AegisQError::AuthenticationFailed));
+
+        // Wrong version with a valid checksum
+        let mut content = encoded[..encoded.len() - CHECKSUM_SIZE].to_vec();
+        content[0] = 2;
+        let check = checksum(&content);
+        content.extend_from_slice(&check);
+        assert_eq!(QrPayload::decode(&content), Err(AegisQError::Unsupported("Unsupported payload version")));
+
+        assert!(QrPayload::from_armored("AEGISQ:").is_err());
+        assert!(QrPayload::from_armored(&base45_encode(&encoded)).is_err());
+        assert!(QrPayload::DeviceLink { secret: [0; LINK_SECRET_SIZE], device_name: "x".repeat(65) }.encode().is_err());
+    }
+}
@@ -2,40 +2,43 @@
 //! 
 //! Post-quantum double ratchet for E2EE messaging
 //! Uses Aegis-Q for encryption, no trusted centers
+//!
+//! Each chain is an Aegis-Q `State`: message keys are exported from it and
+//! the chain step is `State::ratchet`, so a chain captured later does not
+//! reveal the keys of earlier messages.
 
-use aegis_q_core::{aegis_q_encrypt, aegis_q_decrypt};
-use sha3::{Digest, Sha3_512};
-use utils::kdf::kdf_shake256_fill;
+use aegis_q_core::{AegisQError, aegis_q_encrypt, aegis_q_decrypt, aegis_q_init, State};
+use utils::keys::{RootKey, TypedKey};
+use utils::memory::zeroize;
 
 /// Ratchet state
+#[allow(dead_code)]
 pub struct RatchetState {
     dh_private: Vec<u8>,
     dh_public: Vec<u8>,
-    root_key: Vec<u8>,
-    chain_key_send: Vec<u8>,
-    chain_key_recv: Vec<u8>,
+    root_key: RootKey,
+    chain_send: State,
+    chain_recv: State,
     message_number_send: u32,
     message_number_recv: u32,
 }
 
 impl RatchetState {
     /// Initialize ratchet state
-    pub fn new(root_key: Vec<u8>) -> Self {
+    pub fn new(root_key: RootKey) -> Self {
         // Generate DH key pair (simplified - in production use PQ KEM)
         let dh_private = vec![0u8; 32]; // Placeholder
         let dh_public = vec![0u8; 32]; // Placeholder
         
-        let mut chain_key_send = vec![0u8; 64];
-        let mut chain_key_recv = vec![0u8; 64];
-        kdf_shake256_fill(b"aegis-q-messenger-ratchet-chain-send", &root_key, &[], &mut chain_key_send);
-        kdf_shake256_fill(b"aegis-q-messenger-ratchet-chain-recv", &root_key, &[], &mut chain_key_recv);
+        let chain_send = aegis_q_init(root_key.as_bytes(), b"aegis-q-messenger-ratchet-chain-send");
+        let chain_recv = aegis_q_init(root_key.as_bytes(), b"aegis-q-messenger-ratchet-chain-recv");
         
         Self {
             dh_private,
             dh_public,
             root_key,
-            chain_key_send,
-            chain_key_recv,
+            chain_send,
+            chain_recv,
             message_number_send: 0,
             message_number_recv: 0,
         }
@@ -44,12 +47,10 @@ impl RatchetState {
     /// Encrypt message
     pub fn encrypt(&mut self, plaintext: &[u8]) -> Vec<u8> {
         // Derive message key
-        let mut message_key = vec![0u8; 64];
-        kdf_shake256_fill(
+        let mut message_key = self.chain_send.export_keying_material(
             b"aegis-q-messenger-ratchet-message-send",
-            &self.chain_key_send,
             &self.message_number_send.to_le_bytes(),
-            &mut message_key,
+            64,
         );
         
         // Create nonce from message number
@@ -57,6 +58,7 @@ impl RatchetState {
         
         // Encrypt
         let ciphertext = aegis_q_encrypt(&message_key, &nonce, plaintext);
+        zeroize(&mut message_key);
         
         // Advance chain
         self.advance_send_chain();
@@ -65,21 +67,21 @@ impl RatchetState {
     }
     
     /// Decrypt message
-    pub fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, &'static str> {
+    pub fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, AegisQError> {
         // Derive message key
-        let mut message_key = vec![0u8; 64];
-        kdf_shake256_fill(
+        let mut message_key = self.chain_recv.export_keying_material(
             b"aegis-q-messenger-ratchet-message-recv",
-            &self.chain_key_recv,
             &self.message_number_recv.to_le_bytes(),
-            &mut message_key,
+            64,
         );
         
         // Create nonce from message number
         let nonce = self.message_number_recv.to_le_bytes().to_vec();
         
         // Decrypt
-        let plaintext = aegis_q_decrypt(&message_key, &nonce, ciphertext)?;
+        let plaintext = aegis_q_decrypt(&message_key, &nonce, ciphertext);
+        zeroize(&mut message_key);
+        let plaintext = plaintext?;
         
         // Advance chain
         self.advance_recv_chain();
@@ -89,40 +91,51 @@ impl RatchetState {
     
     /// Advance send chain
     fn advance_send_chain(&mut self) {
-        let mut hasher = Sha3_512::new();
-        hasher.update(&self.chain_key_send);
-        hasher.update(b"chain-advance");
-        self.chain_key_send = hasher.finalize().to_vec();
+        self.chain_send.ratchet();
         self.message_number_send += 1;
     }
     
     /// Advance receive chain
     fn advance_recv_chain(&mut self) {
-        let mut hasher = Sha3_512::new();
-        hasher.update(&self.chain_key_recv);
-        hasher.update(b"chain-advance");
-        self.chain_key_recv = hasher.finalize().to_vec();
+        self.chain_recv.ratchet();
         self.message_number_recv += 1;
     }
 }
 
+impl Drop for RatchetState {
+    /// Wipes the DH key; the chain states wipe themselves
+    fn drop(&mut self) {
+        zeroize(&mut self.dh_private);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     
     #[test]
     fn test_ratchet_encrypt_decrypt() {
-        let root_key = b"root-key-123456789012345678901234567890".to_vec();
+        let root_key = RootKey::from_bytes(b"root-key-123456789012345678901234567890");
         let mut ratchet = RatchetState::new(root_key);
         
         let plaintext = b"Hello, Ratchet!";
         let ciphertext = ratchet.encrypt(plaintext);
         
         // Create new ratchet with same root key for decryption
-        let mut ratchet2 = RatchetState::new(b"root-key-123456789012345678901234567890".to_vec());
+        let mut ratchet2 = RatchetState::new(RootKey::from_bytes(b"root-key-123456789012345678901234567890"));
         let decrypted = ratchet2.decrypt(&ciphertext).unwrap();
         
         assert_eq!(plaintext, decrypted.as_slice());
     }
-}
 
+    #[test]
+    fn test_chain_step_is_state_ratchet() {
+        let mut ratchet = RatchetState::new(RootKey::from_bytes(b"root-key-123456789012345678901234567890"));
+        ratchet.encrypt(b"first");
+
+        let mut expected = aegis_q_init(b"root-key-123456789012345678901234567890", b"aegis-q-messenger-ratchet-chain-send");
+        expected.ratchet();
+        assert_eq!(ratchet.chain_send.to_bytes(), expected.to_bytes());
+        assert_eq!(ratchet.message_number_send, 1);
+    }
+}
@@ -2,18 +2,98 @@
 //! 
 //! Encrypted storage for messenger data
 //! Media, reactions, profile encryption
+//!
+//! Application models can encrypt individual fields through `Protected`;
+//! with the `derive` feature `#[derive(Protected)]` writes the glue.
+//!
+//! Entries can pass through transforms before encryption (`Transform`,
+//! configured per purpose in `StoragePipeline`). The applied transforms are
+//! recorded in the entry and reversed by `retrieve`.
 
-use aegis_q_core::{aegis_q_encrypt, aegis_q_decrypt};
+use aegis_q_core::{AegisQError, aegis_q_try_encrypt, aegis_q_decrypt, aegis_q_encrypt_segmented, aegis_q_decrypt_segmented, MAX_PLAINTEXT_SIZE};
 use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
 use sha3::{Digest, Sha3_256};
+use utils::kdf::kdf_shake256;
+use utils::keys::{EncryptionKey, RootKey, TypedKey};
+use utils::memory::zeroize;
 use utils::rng::random_bytes;
 
+#[cfg(feature = "derive")]
+pub use aegis_q_derive::Protected;
+
 /// Storage key derivation
-pub fn derive_storage_key(master_key: &[u8], purpose: &str) -> Vec<u8> {
+pub fn derive_storage_key(master_key: &RootKey, purpose: &str) -> EncryptionKey {
     let mut hasher = Sha3_256::new();
-    hasher.update(master_key);
+    hasher.update(master_key.as_bytes());
     hasher.update(purpose.as_bytes());
-    hasher.finalize().to_vec()
+    EncryptionKey::from_bytes(&hasher.finalize())
+}
+
+/// Upper bound on a decompressed entry
+pub const MAX_INFLATED_SIZE: usize = 64 * 1024 * 1024;
+
+/// Transform applied to the plaintext before encryption
+///
+/// Compression leaks the compressibility of the plaintext through the
+/// ciphertext length: do not compress data that mixes secrets with
+/// attacker-chosen content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transform {
+    /// Raw DEFLATE
+    Deflate,
+}
+
+impl Transform {
+    fn label(self) -> &'static str {
+        match self {
+            Transform::Deflate => "deflate",
+        }
+    }
+
+    fn apply(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Transform::Deflate => miniz_oxide::deflate::compress_to_vec(data, 6),
+        }
+    }
+
+    fn reverse(self, data: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        match self {
+            Transform::Deflate => miniz_oxide::inflate::decompress_to_vec_with_limit(data, MAX_INFLATED_SIZE)
+                .map_err(|err| match err.status {
+                    miniz_oxide::inflate::TINFLStatus::HasMoreOutput => AegisQError::LimitExceeded("Decompressed entry too large"),
+                    _ => AegisQError::Serialization("Corrupt compressed entry"),
+                }),
+        }
+    }
+}
+
+/// Storage key for an entry with `transforms` applied
+///
+/// The transforms are part of the key derivation, so an entry whose header
+/// was edited fails authentication instead of returning transformed bytes.
+/// Transformed entries use their own KDF domain over the length-prefixed
+/// purpose and transform list, so no purpose string can collide with them.
+/// Without transforms this is `derive_storage_key`, so older entries open.
+fn entry_key(master_key: &RootKey, purpose: &str, transforms: &[Transform]) -> EncryptionKey {
+    if transforms.is_empty() {
+        return derive_storage_key(master_key, purpose);
+    }
+    let mut info = Vec::new();
+    info.extend_from_slice(&(purpose.len() as u32).to_le_bytes());
+    info.extend_from_slice(purpose.as_bytes());
+    info.extend_from_slice(&(transforms.len() as u32).to_le_bytes());
+    for transform in transforms {
+        let label = transform.label();
+        info.extend_from_slice(&(label.len() as u32).to_le_bytes());
+        info.extend_from_slice(label.as_bytes());
+    }
+    let mut key = kdf_shake256(b"aegis-q-messenger-storage-transforms", master_key.as_bytes(), &info, 32);
+    let storage_key = EncryptionKey::from_bytes(&key);
+    zeroize(&mut key);
+    storage_key
 }
 
 /// Encrypted storage entry
@@ -22,27 +102,134 @@ pub struct StorageEntry {
     pub encrypted_data: Vec<u8>,
     pub nonce: Vec<u8>,
     pub purpose: String,
+    /// Encrypted with the segmented keystream (large payloads)
+    #[serde(default)]
+    pub segmented: bool,
+    /// Transforms applied before encryption, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transforms: Vec<Transform>,
 }
 
 impl StorageEntry {
     /// Store data
-    pub fn store(data: &[u8], master_key: &[u8], purpose: &str) -> Self {
+    ///
+    /// Fails with `LimitExceeded` above `MAX_PLAINTEXT_SIZE`.
+    pub fn store(data: &[u8], master_key: &RootKey, purpose: &str) -> Result<Self, AegisQError> {
         let storage_key = derive_storage_key(master_key, purpose);
         let nonce = random_bytes(16);
         
-        let encrypted_data = aegis_q_encrypt(&storage_key, &nonce, data);
+        let encrypted_data = aegis_q_try_encrypt(storage_key.as_bytes(), &nonce, data)?;
         
-        Self {
+        Ok(Self {
             encrypted_data,
             nonce,
             purpose: purpose.to_string(),
+            segmented: false,
+            transforms: Vec::new(),
+        })
+    }
+
+    /// Store data after applying `transforms`
+    ///
+    /// A transform that does not shrink the data is skipped and not
+    /// recorded.
+    pub fn store_with(data: &[u8], master_key: &RootKey, purpose: &str, transforms: &[Transform]) -> Result<Self, AegisQError> {
+        let mut applied = Vec::new();
+        let mut current = data.to_vec();
+        for &transform in transforms {
+            let mut next = transform.apply(&current);
+            if next.len() < current.len() {
+                applied.push(transform);
+                std::mem::swap(&mut current, &mut next);
+            }
+            zeroize(&mut next);
         }
+
+        let storage_key = entry_key(master_key, purpose, &applied);
+        let nonce = random_bytes(16);
+        let encrypted_data = aegis_q_try_encrypt(storage_key.as_bytes(), &nonce, &current);
+        zeroize(&mut current);
+
+        Ok(Self {
+            encrypted_data: encrypted_data?,
+            nonce,
+            purpose: purpose.to_string(),
+            segmented: false,
+            transforms: applied,
+        })
     }
     
-    /// Retrieve data
-    pub fn retrieve(&self, master_key: &[u8]) -> Result<Vec<u8>, &'static str> {
-        let storage_key = derive_storage_key(master_key, &self.purpose);
-        aegis_q_decrypt(&storage_key, &self.nonce, &self.encrypted_data)
+    /// Store a large payload with the segmented keystream
+    /// 
+    /// Keystream segments are generated in parallel with the `parallel`
+    /// feature.
+    pub fn store_segmented(data: &[u8], master_key: &RootKey, purpose: &str) -> Result<Self, AegisQError> {
+        if data.len() > MAX_PLAINTEXT_SIZE {
+            return Err(AegisQError::LimitExceeded("Plaintext exceeds maximum message size"));
+        }
+        let storage_key = derive_storage_key(master_key, purpose);
+        let nonce = random_bytes(16);
+        
+        let encrypted_data = aegis_q_encrypt_segmented(storage_key.as_bytes(), &nonce, data);
+        
+        Ok(Self {
+            encrypted_data,
+            nonce,
+            purpose: purpose.to_string(),
+            segmented: true,
+            transforms: Vec::new(),
+        })
+    }
+    
+    /// Retrieve data, reversing the recorded transforms
+    pub fn retrieve(&self, master_key: &RootKey) -> Result<Vec<u8>, AegisQError> {
+        let storage_key = entry_key(master_key, &self.purpose, &self.transforms);
+        let mut data = if self.segmented {
+            aegis_q_decrypt_segmented(storage_key.as_bytes(), &self.nonce, &self.encrypted_data)?
+        } else {
+            aegis_q_decrypt(storage_key.as_bytes(), &self.nonce, &self.encrypted_data)?
+        };
+        for transform in self.transforms.iter().rev() {
+            let reversed = transform.reverse(&data);
+            zeroize(&mut data);
+            data = reversed?;
+        }
+        Ok(data)
+    }
+}
+
+/// Per-purpose transform configuration
+///
+/// ```ignore
+/// let pipeline = StoragePipeline::new().with("history", &[Transform::Deflate]);
+/// let entry = pipeline.store(&json, &master_key, "history")?;
+/// let json = entry.retrieve(&master_key)?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StoragePipeline {
+    purposes: HashMap<String, Vec<Transform>>,
+}
+
+impl StoragePipeline {
+    /// Pipeline without transforms for any purpose
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply `transforms` to entries stored for `purpose`
+    pub fn with(mut self, purpose: &str, transforms: &[Transform]) -> Self {
+        self.purposes.insert(purpose.to_string(), transforms.to_vec());
+        self
+    }
+
+    /// Transforms configured for `purpose`
+    pub fn transforms(&self, purpose: &str) -> &[Transform] {
+        self.purposes.get(purpose).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Store data with the transforms configured for `purpose`
+    pub fn store(&self, data: &[u8], master_key: &RootKey, purpose: &str) -> Result<StorageEntry, AegisQError> {
+        StorageEntry::store_with(data, master_key, purpose, self.transforms(purpose))
     }
 }
 
@@ -50,13 +237,13 @@ impl StorageEntry {
 pub struct MediaStorage;
 
 impl MediaStorage {
-    /// Encrypt media file
-    pub fn encrypt_media(media_data: &[u8], master_key: &[u8]) -> StorageEntry {
-        StorageEntry::store(media_data, master_key, "media")
+    /// Encrypt media file (segmented keystream)
+    pub fn encrypt_media(media_data: &[u8], master_key: &RootKey) -> Result<StorageEntry, AegisQError> {
+        StorageEntry::store_segmented(media_data, master_key, "media")
     }
     
     /// Decrypt media file
-    pub fn decrypt_media(entry: &StorageEntry, master_key: &[u8]) -> Result<Vec<u8>, &'static str> {
+    pub fn decrypt_media(entry: &StorageEntry, master_key: &RootKey) -> Result<Vec<u8>, AegisQError> {
         entry.retrieve(master_key)
     }
 }
@@ -66,29 +253,151 @@ pub struct ProfileStorage;
 
 impl ProfileStorage {
     /// Encrypt profile data
-    pub fn encrypt_profile(profile_data: &[u8], master_key: &[u8]) -> StorageEntry {
+    pub fn encrypt_profile(profile_data: &[u8], master_key: &RootKey) -> Result<StorageEntry, AegisQError> {
         StorageEntry::store(profile_data, master_key, "profile")
     }
     
     /// Decrypt profile data
-    pub fn decrypt_profile(entry: &StorageEntry, master_key: &[u8]) -> Result<Vec<u8>, &'static str> {
+    pub fn decrypt_profile(entry: &StorageEntry, master_key: &RootKey) -> Result<Vec<u8>, AegisQError> {
         entry.retrieve(master_key)
     }
 }
 
+/// Model stored with some fields encrypted
+///
+/// `Sealed` is the storable form. `#[derive(Protected)]` (feature `derive`)
+/// generates it and this impl from `#[protected]` field annotations.
+pub trait Protected: Sized {
+    /// Form with the protected fields encrypted
+    type Sealed;
+
+    /// Encrypt the protected fields
+    fn seal(&self, master_key: &RootKey) -> Result<Self::Sealed, AegisQError>;
+
+    /// Decrypt the protected fields
+    fn open(sealed: &Self::Sealed, master_key: &RootKey) -> Result<Self, AegisQError>;
+}
+
+/// Encrypt one field value under the storage key for `purpose`
+pub fn seal_field<T: Serialize + ?Sized>(value: &T, master_key: &RootKey, purpose: &str) -> Result<StorageEntry, AegisQError> {
+    let mut encoded = serde_json::to_vec(value).map_err(|_| AegisQError::Serialization("Failed to encode protected field"))?;
+    let entry = StorageEntry::store(&encoded, master_key, purpose);
+    zeroize(&mut encoded);
+    entry
+}
+
+/// Decrypt a field sealed by `seal_field` for the same `purpose`
+pub fn open_field<T: DeserializeOwned>(entry: &StorageEntry, master_key: &RootKey, purpose: &str) -> Result<T, AegisQError> {
+    if entry.purpose != purpose {
+        return Err(AegisQError::InvalidInput("Protected field has another purpose"));
+    }
+    let mut decoded = entry.retrieve(master_key)?;
+    let value = serde_json::from_slice(&decoded).map_err(|_| AegisQError::Serialization("Failed to decode protected field"));
+    zeroize(&mut decoded);
+    value
+}
+
+/// Paths used by `#[derive(Protected)]` expansions
+#[doc(hidden)]
+pub mod __private {
+    pub use aegis_q_core::AegisQError;
+    pub use serde;
+    pub use utils::keys::RootKey;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     
     #[test]
     fn test_storage_encrypt_decrypt() {
-        let master_key = b"master-key-123456789012345678901234567890";
+        let master_key = RootKey::from_bytes(b"master-key-123456789012345678901234567890");
         let data = b"test-data";
         
-        let entry = StorageEntry::store(data, master_key, "test");
-        let retrieved = entry.retrieve(master_key).unwrap();
+        let entry = StorageEntry::store(data, &master_key, "test").unwrap();
+        let retrieved = entry.retrieve(&master_key).unwrap();
         
         assert_eq!(data, retrieved.as_slice());
     }
-}
+    
+    #[test]
+    fn test_media_segmented_and_legacy_entries() {
+        let master_key = RootKey::from_bytes(b"master-key-123456789012345678901234567890");
+        let entry = MediaStorage::encrypt_media(b"media-bytes", &master_key).unwrap();
+        assert!(entry.segmented);
+        assert_eq!(MediaStorage::decrypt_media(&entry, &master_key).unwrap(), b"media-bytes");
+        
+        // Entries stored before segmentation have no flag and still open
+        let legacy = StorageEntry::store(b"old-media", &master_key, "media").unwrap();
+        let mut json: serde_json::Value = serde_json::to_value(&legacy).unwrap();
+        json.as_object_mut().unwrap().remove("segmented");
+        let legacy: StorageEntry = serde_json::from_value(json).unwrap();
+        assert_eq!(MediaStorage::decrypt_media(&legacy, &master_key).unwrap(), b"old-media");
+    }
+
+    #[test]
+    fn test_pipeline_compresses_and_binds_header() {
+        let master_key = RootKey::from_bytes(b"master-key-123456789012345678901234567890");
+        let pipeline = StoragePipeline::new().with("history", &[Transform::Deflate]);
+        let json = serde_json::to_vec(&vec!["message"; 500]).unwrap();
+
+        let entry = pipeline.store(&json, &master_key, "history").unwrap();
+        assert_eq!(entry.transforms, vec![Transform::Deflate]);
+        assert!(entry.encrypted_data.len() < json.len() / 4);
+        assert_eq!(entry.retrieve(&master_key).unwrap(), json);
+
+        // Other purposes and incompressible data are stored as before
+        assert!(pipeline.store(&json, &master_key, "profile").unwrap().transforms.is_empty());
+        let tiny = pipeline.store(b"x", &master_key, "history").unwrap();
+        assert!(tiny.transforms.is_empty());
+        assert_eq!(tiny.retrieve(&master_key).unwrap(), b"x");
 
+        // Stripping the transform from the header does not yield the deflated bytes
+        let mut stripped = entry.clone();
+        stripped.transforms.clear();
+        assert_eq!(stripped.retrieve(&master_key), Err(AegisQError::AuthenticationFailed));
+
+        // Nor does folding the transform into the purpose
+        let mut renamed = entry.clone();
+        renamed.purpose = "history#deflate".to_string();
+        renamed.transforms.clear();
+        assert_eq!(renamed.retrieve(&master_key), Err(AegisQError::AuthenticationFailed));
+    }
+
+    #[test]
+    #[cfg(feature = "derive")]
+    fn test_derive_protected() {
+        #[derive(Debug, Clone, PartialEq, Protected)]
+        #[protected(purpose = "settings")]
+        struct Settings {
+            user_id: String,
+            #[protected]
+            phone: String,
+            #[protected]
+            recovery_codes: Vec<u32>,
+        }
+
+        let master_key = RootKey::from_bytes(b"master-key-123456789012345678901234567890");
+        let settings = Settings {
+            user_id: "alice".to_string(),
+            phone: "+1 555 0100".to_string(),
+            recovery_codes: vec![1234, 5678],
+        };
+
+        let sealed: SettingsSealed = settings.seal(&master_key).unwrap();
+        assert_eq!(sealed.user_id, "alice");
+        assert_eq!(sealed.phone.purpose, "settings/phone");
+        let json = serde_json::to_string(&sealed).unwrap();
+        assert!(!json.contains("555"));
+
+        let restored: SettingsSealed = serde_json::from_str(&json).unwrap();
+        assert_eq!(Settings::open(&restored, &master_key).unwrap(), settings);
+
+        // Each field has its own key: swapping ciphertexts between fields fails
+        let mut swapped = sealed.clone();
+        swapped.phone = sealed.recovery_codes.clone();
+        assert!(Settings::open(&swapped, &master_key).is_err());
+        let other_key = RootKey::from_bytes(b"other-master-key-1234567890123456789012");
+        assert_eq!(Settings::open(&sealed, &other_key), Err(AegisQError::AuthenticationFailed));
+    }
+}
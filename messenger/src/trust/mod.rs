@@ -0,0 +1,439 @@
+//! Peer Trust Store
+//!
+//! Records each peer's identity key with when it was first and last seen,
+//! a pin policy and the history of key changes. It backs fingerprint
+//! verification (`qr::QrPayload::IdentityFingerprint`) and key-change
+//! warnings:
+//! - `Tofu` peers (the default) may change keys; each change is recorded
+//!   and reported so the client can show a "safety number changed" notice.
+//! - `Pinned` peers must keep their key; a different key is rejected until
+//!   the user unpins (or re-verifies) the peer.
+//! - A peer rotating on purpose publishes a `ContinuityStatement` signed by
+//!   both the old and the new key. A verified chain of statements moves the
+//!   pin and verification to the new key without a key-change warning.
+//!
+//! The store is serializable and is kept encrypted with `storage`.
+
+use std::collections::BTreeMap;
+
+use aegis_q_core::AegisQError;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use utils::keys::RootKey;
+
+use crate::qr::FINGERPRINT_SIZE;
+use crate::storage::StorageEntry;
+
+/// Storage purpose of the encrypted store
+const STORAGE_PURPOSE: &str = "trust-store";
+
+/// Fingerprint of an identity key, as shown in QR payloads
+pub fn identity_fingerprint(identity_key: &[u8]) -> [u8; FINGERPRINT_SIZE] {
+    let mut hasher = Sha3_256::new();
+    hasher.update(b"aegis-q-messenger-identity-fingerprint");
+    hasher.update(identity_key);
+    hasher.finalize().into()
+}
+
+/// How key changes of a peer are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PinPolicy {
+    /// Trust on first use: changes are accepted and recorded
+    #[default]
+    Tofu,
+    /// Changes are rejected
+    Pinned,
+}
+
+/// One recorded key change
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyRotation {
+    pub previous_key: Vec<u8>,
+    pub new_key: Vec<u8>,
+    pub at: u64,
+    /// Vouched for by a continuity statement
+    #[serde(default)]
+    pub attested: bool,
+}
+
+/// Checks identity-key signatures (the signature scheme is pluggable)
+pub trait IdentityVerifier {
+    /// Whether `signature` over `message` is valid for `identity_key`
+    fn verify(&self, identity_key: &[u8], message: &[u8], signature: &[u8]) -> bool;
+}
+
+/// Statement that `new_key` succeeds `old_key`, signed by both
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContinuityStatement {
+    pub old_key: Vec<u8>,
+    pub new_key: Vec<u8>,
+    pub issued_at: u64,
+    pub old_signature: Vec<u8>,
+    pub new_signature: Vec<u8>,
+}
+
+impl ContinuityStatement {
+    /// Unsigned statement; sign `message()` with both keys
+    pub fn new(old_key: &[u8], new_key: &[u8], issued_at: u64) -> Self {
+        Self {
+            old_key: old_key.to_vec(),
+            new_key: new_key.to_vec(),
+            issued_at,
+            old_signature: Vec::new(),
+            new_signature: Vec::new(),
+        }
+    }
+
+    /// Attach the signatures of the old and the new key
+    pub fn with_signatures(mut self, old_signature: Vec<u8>, new_signature: Vec<u8>) -> Self {
+        self.old_signature = old_signature;
+        self.new_signature = new_signature;
+        self
+    }
+
+    /// Signed message: label || len || old key || len || new key || issued_at
+    pub fn message(&self) -> Vec<u8> {
+        let mut message = b"aegis-q-messenger-identity-continuity".to_vec();
+        for key in [&self.old_key, &self.new_key] {
+            message.extend_from_slice(&(key.len() as u32).to_be_bytes());
+            message.extend_from_slice(key);
+        }
+        message.extend_from_slice(&self.issued_at.to_be_bytes());
+        message
+    }
+
+    /// Check both signatures
+    pub fn verify(&self, verifier: &dyn IdentityVerifier) -> Result<(), AegisQError> {
+        let message = self.message();
+        if self.old_key == self.new_key
+            || !verifier.verify(&self.old_key, &message, &self.old_signature)
+            || !verifier.verify(&self.new_key, &message, &self.new_signature)
+        {
+            return Err(AegisQError::AuthenticationFailed);
+        }
+        Ok(())
+    }
+}
+
+/// What the store knows about a peer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerRecord {
+    pub identity_key: Vec<u8>,
+    pub first_seen: u64,
+    pub last_seen: u64,
+    pub pin: PinPolicy,
+    /// When the current key's fingerprint was verified out of band
+    pub verified_at: Option<u64>,
+    /// Key changes, oldest first
+    pub history: Vec<KeyRotation>,
+}
+
+impl PeerRecord {
+    /// Fingerprint of the current identity key
+    pub fn fingerprint(&self) -> [u8; FINGERPRINT_SIZE] {
+        identity_fingerprint(&self.identity_key)
+    }
+}
+
+/// Result of presenting a peer's identity key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Observation {
+    /// First time this peer is seen
+    New,
+    /// Same key as before
+    Unchanged,
+    /// The key changed (TOFU peer); warn the user
+    Rotated(KeyRotation),
+    /// The key changed with a verified continuity chain; no warning needed
+    Continued(KeyRotation),
+}
+
+/// Identity keys of known peers
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustStore {
+    peers: BTreeMap<String, PeerRecord>,
+}
+
+impl TrustStore {
+    /// Empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the identity key `peer` presented at `now`
+    ///
+    /// A pinned peer presenting a different key is rejected and the store
+    /// is left unchanged.
+    pub fn observe(&mut self, peer: &str, identity_key: &[u8], now: u64) -> Result<Observation, AegisQError> {
+        let Some(record) = self.peers.get_mut(peer) else {
+            self.peers.insert(
+                peer.to_string(),
+                PeerRecord {
+                    identity_key: identity_key.to_vec(),
+                    first_seen: now,
+                    last_seen: now,
+                    pin: PinPolicy::Tofu,
+                    verified_at: None,
+                    history: Vec::new(),
+                },
+            );
+            return Ok(Observation::New);
+        };
+
+        if record.identity_key == identity_key {
+            record.last_seen = record.last_seen.max(now);
+            return Ok(Observation::Unchanged);
+        }
+        if record.pin == PinPolicy::Pinned {
+            return Err(AegisQError::Policy("Pinned identity key changed"));
+        }
+
+        let rotation = KeyRotation {
+            previous_key: std::mem::replace(&mut record.identity_key, identity_key.to_vec()),
+            new_key: identity_key.to_vec(),
+            at: now,
+            attested: false,
+        };
+        record.history.push(rotation.clone());
+        record.last_seen = record.last_seen.max(now);
+        record.verified_at = None;
+        Ok(Observation::Rotated(rotation))
+    }
+
+    /// Follow a chain of continuity statements from the peer's current key
+    ///
+    /// Each statement must start from the key the previous one ended on,
+    /// the first from the stored key, and none may predate the peer's last
+    /// recorded change. The whole chain is checked before anything is
+    /// applied; on success the pin policy and verification carry over to
+    /// the final key. Apply it before `observe`-ing the new key, which then
+    /// reports `Unchanged`.
+    pub fn apply_continuity(
+        &mut self,
+        peer: &str,
+        chain: &[ContinuityStatement],
+        verifier: &dyn IdentityVerifier,
+        now: u64,
+    ) -> Result<Observation, AegisQError> {
+        let record = self.peers.get_mut(peer).ok_or(AegisQError::NotFound("Unknown peer"))?;
+        let last = chain.last().ok_or(AegisQError::InvalidInput("Empty continuity chain"))?;
+
+        let mut key = &record.identity_key;
+        let mut not_before = record.history.last().map_or(0, |rotation| rotation.at);
+        for statement in chain {
+            if statement.old_key != *key {
+                return Err(AegisQError::Protocol("Continuity chain does not start from the known key"));
+            }
+            if statement.issued_at < not_before {
+                return Err(AegisQError::Protocol("Stale continuity statement"));
+            }
+            statement.verify(verifier)?;
+            key = &statement.new_key;
+            not_before = statement.issued_at;
+        }
+
+        for statement in chain {
+            record.history.push(KeyRotation {
+                previous_key: statement.old_key.clone(),
+                new_key: statement.new_key.clone(),
+                at: statement.issued_at,
+                attested: true,
+            });
+        }
+        record.identity_key = last.new_key.clone();
+        record.last_seen = record.last_seen.max(now);
+        let rotation = record.history.last().cloned().expect("chain is not empty");
+        Ok(Observation::Continued(rotation))
+    }
+
+    /// Check a scanned fingerprint against the peer's current key
+    ///
+    /// On a match the peer is marked verified and pinned.
+    pub fn verify(&mut self, peer: &str, fingerprint: &[u8; FINGERPRINT_SIZE], now: u64) -> Result<(), AegisQError> {
+        let record = self.peers.get_mut(peer).ok_or(AegisQError::NotFound("Unknown peer"))?;
+        if !constant_time_eq(&record.fingerprint(), fingerprint) {
+            return Err(AegisQError::AuthenticationFailed);
+        }
+        record.verified_at = Some(now);
+        record.pin = PinPolicy::Pinned;
+        Ok(())
+    }
+
+    /// Pin the peer's current key
+    pub fn pin(&mut self, peer: &str) -> Result<(), AegisQError> {
+        self.set_pin(peer, PinPolicy::Pinned)
+    }
+
+    /// Go back to trust on first use for the peer
+    pub fn unpin(&mut self, peer: &str) -> Result<(), AegisQError> {
+        self.set_pin(peer, PinPolicy::Tofu)
+    }
+
+    fn set_pin(&mut self, peer: &str, pin: PinPolicy) -> Result<(), AegisQError> {
+        let record = self.peers.get_mut(peer).ok_or(AegisQError::NotFound("Unknown peer"))?;
+        record.pin = pin;
+        Ok(())
+    }
+
+    /// Record for `peer`
+    pub fn get(&self, peer: &str) -> Option<&PeerRecord> {
+        self.peers.get(peer)
+    }
+
+    /// Key changes of `peer` at or after `since`
+    pub fn changes_since(&self, peer: &str, since: u64) -> Vec<&KeyRotation> {
+        self.peers
+            .get(peer)
+            .map(|record| record.history.iter().filter(|rotation| rotation.at >= since).collect())
+            .unwrap_or_default()
+    }
+
+    /// Forget a peer entirely
+    pub fn remove(&mut self, peer: &str) -> Option<PeerRecord> {
+        self.peers.remove(peer)
+    }
+
+    /// Known peers, in name order
+    pub fn peers(&self) -> impl Iterator<Item = (&str, &PeerRecord)> {
+        self.peers.iter().map(|(peer, record)| (peer.as_str(), record))
+    }
+
+    /// Encrypt the store for local storage
+    pub fn to_entry(&self, master_key: &RootKey) -> Result<StorageEntry, AegisQError> {
+        let bytes = serde_json::to_vec(self).map_err(|_| AegisQError::Serialization("Failed to encode trust store"))?;
+        StorageEntry::store(&bytes, master_key, STORAGE_PURPOSE)
+    }
+
+    /// Decrypt a store saved with `to_entry`
+    pub fn from_entry(entry: &StorageEntry, master_key: &RootKey) -> Result<Self, AegisQError> {
+        if entry.purpose != STORAGE_PURPOSE {
+            return Err(AegisQError::InvalidInput("Not a trust store entry"));
+        }
+        let bytes = entry.retrieve(master_key)?;
+        serde_json::from_slice(&bytes).map_err(|_| AegisQError::Serialization("Malformed trust store"))
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tofu_records_rotations() {
+        let mut store = TrustStore::new();
+        assert_eq!(store.observe("bob", b"bob-key-1", 100).unwrap(), Observation::New);
+        assert_eq!(store.observe("bob", b"bob-key-1", 150).unwrap(), Observation::Unchanged);
+
+        let Observation::Rotated(rotation) = store.observe("bob", b"bob-key-2", 200).unwrap() else {
+            panic!("expected a rotation");
+        };
+        assert_eq!(rotation.previous_key, b"bob-key-1");
+        store.observe("bob", b"bob-key-3", 300).unwrap();
+
+        let record = store.get("bob").unwrap();
+        assert_eq!((record.first_seen, record.last_seen), (100, 300));
+        assert_eq!(record.identity_key, b"bob-key-3");
+        assert_eq!(store.changes_since("bob", 0).len(), 2);
+        assert_eq!(store.changes_since("bob", 201)[0].new_key, b"bob-key-3");
+        assert!(store.changes_since("carol", 0).is_empty());
+    }
+
+    #[test]
+    fn test_pinning_and_verification() {
+        let mut store = TrustStore::new();
+        store.observe("alice", b"alice-key", 10).unwrap();
+
+        assert_eq!(store.verify("alice", &identity_fingerprint(b"other"), 20), Err(AegisQError::AuthenticationFailed));
+        store.verify("alice", &identity_fingerprint(b"alice-key"), 20).unwrap();
+        assert_eq!(store.get("alice").unwrap().pin, PinPolicy::Pinned);
+
+        // Pinned: a new key is refused and nothing changes
+        assert_eq!(store.observe("alice", b"alice-key-2", 30), Err(AegisQError::Policy("Pinned identity key changed")));
+        assert_eq!(store.get("alice").unwrap().identity_key, b"alice-key");
+
+        // After unpinning the change goes through and drops verification
+        store.unpin("alice").unwrap();
+        assert!(matches!(store.observe("alice", b"alice-key-2", 40).unwrap(), Observation::Rotated(_)));
+        assert_eq!(store.get("alice").unwrap().verified_at, None);
+        assert_eq!(store.pin("mallory"), Err(AegisQError::NotFound("Unknown peer")));
+    }
+
+    /// Test signature: SHA3-256(key || message)
+    struct HashVerifier;
+
+    fn test_sign(key: &[u8], message: &[u8]) -> Vec<u8> {
+        Sha3_256::new().chain_update(key).chain_update(message).finalize().to_vec()
+    }
+
+    impl IdentityVerifier for HashVerifier {
+        fn verify(&self, identity_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+            test_sign(identity_key, message) == signature
+        }
+    }
+
+    fn statement(old: &[u8], new: &[u8], at: u64) -> ContinuityStatement {
+        let unsigned = ContinuityStatement::new(old, new, at);
+        let message = unsigned.message();
+        unsigned.with_signatures(test_sign(old, &message), test_sign(new, &message))
+    }
+
+    #[test]
+    fn test_continuity_chain_keeps_pin() {
+        let mut store = TrustStore::new();
+        store.observe("bob", b"bob-key-1", 10).unwrap();
+        store.verify("bob", &identity_fingerprint(b"bob-key-1"), 10).unwrap();
+
+        let chain = [statement(b"bob-key-1", b"bob-key-2", 20), statement(b"bob-key-2", b"bob-key-3", 30)];
+        let Observation::Continued(rotation) = store.apply_continuity("bob", &chain, &HashVerifier, 40).unwrap() else {
+            panic!("expected continuity");
+        };
+        assert!(rotation.attested);
+        assert_eq!(rotation.new_key, b"bob-key-3");
+
+        // Still pinned and verified; the new key raises no warning
+        let record = store.get("bob").unwrap();
+        assert_eq!((record.pin, record.verified_at), (PinPolicy::Pinned, Some(10)));
+        assert_eq!(record.history.len(), 2);
+        assert_eq!(store.observe("bob", b"bob-key-3", 50).unwrap(), Observation::Unchanged);
+    }
+
+    #[test]
+    fn test_continuity_rejects_bad_chains() {
+        let mut store = TrustStore::new();
+        store.observe("bob", b"bob-key-1", 10).unwrap();
+        store.pin("bob").unwrap();
+
+        // Only the new key signed
+        let mut forged = statement(b"bob-key-1", b"evil-key", 20);
+        forged.old_signature = test_sign(b"evil-key", &forged.message());
+        assert_eq!(store.apply_continuity("bob", &[forged], &HashVerifier, 20), Err(AegisQError::AuthenticationFailed));
+
+        // Broken link, and a chain not starting from the stored key
+        let broken = [statement(b"bob-key-1", b"bob-key-2", 20), statement(b"other", b"bob-key-3", 30)];
+        assert!(store.apply_continuity("bob", &broken, &HashVerifier, 30).is_err());
+        assert!(store.apply_continuity("bob", &[statement(b"other", b"bob-key-2", 20)], &HashVerifier, 20).is_err());
+        assert_eq!(store.get("bob").unwrap().identity_key, b"bob-key-1");
+
+        // Statements older than the last change are refused
+        store.apply_continuity("bob", &[statement(b"bob-key-1", b"bob-key-2", 20)], &HashVerifier, 20).unwrap();
+        let stale = statement(b"bob-key-2", b"bob-key-3", 15);
+        assert_eq!(store.apply_continuity("bob", &[stale], &HashVerifier, 30), Err(AegisQError::Protocol("Stale continuity statement")));
+    }
+
+    #[test]
+    fn test_encrypted_persistence() {
+        let master_key = RootKey::from_bytes(b"trust-store-master-key");
+        let mut store = TrustStore::new();
+        store.observe("bob", b"bob-key-1", 1).unwrap();
+        store.observe("bob", b"bob-key-2", 2).unwrap();
+        store.pin("bob").unwrap();
+
+        let entry = store.to_entry(&master_key).unwrap();
+        assert_eq!(TrustStore::from_entry(&entry, &master_key).unwrap(), store);
+        assert!(TrustStore::from_entry(&entry, &RootKey::from_bytes(b"other")).is_err());
+    }
+}
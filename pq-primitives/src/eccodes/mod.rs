@@ -1,7 +1,7 @@
 //! CodeMix - Linear code operations
 //! 
 //! Implements: state_C' = P * G * state_C
-//! Where G is a generator matrix (4096×4096) and P is a secret permutation
+//! Where G is a generator matrix (n×n, 4096×4096 by default) and P is a secret permutation
 //! Strictly linear operations, O(n²) complexity
 
 use sha3::{Digest, Sha3_512};
@@ -23,12 +23,17 @@ pub struct GeneratorMatrix {
 impl GeneratorMatrix {
     /// Generate generator matrix from key using HKDF
     pub fn from_key(key: &[u8], nonce: &[u8]) -> Self {
+        Self::from_key_n(key, nonce, CODE_N)
+    }
+    
+    /// Generate an `n`×`n` generator matrix
+    pub fn from_key_n(key: &[u8], _nonce: &[u8], n: usize) -> Self {
         // Derive matrix entries deterministically
         let mut entries = Vec::new();
         
         // Generate sparse matrix (density ~0.1 for efficiency)
-        for row in 0..CODE_N {
-            for col in 0..CODE_N {
+        for row in 0..n {
+            for col in 0..n {
                 // Sparse: only include ~10% of entries
                 let seed = kdf_shake256(
                     b"aegis-q-codemix-matrix",
@@ -51,15 +56,15 @@ impl GeneratorMatrix {
         
         Self {
             entries,
-            n: CODE_N,
+            n,
         }
     }
     
     /// Matrix-vector multiplication: G * state
     pub fn multiply(&self, state: &[u32]) -> Vec<u32> {
-        assert_eq!(state.len(), CODE_N);
+        assert_eq!(state.len(), self.n);
         
-        let mut result = vec![0u32; CODE_N];
+        let mut result = vec![0u32; self.n];
         
         // Sparse matrix multiplication
         for (row, col, value) in &self.entries {
@@ -83,11 +88,16 @@ pub struct Permutation {
 impl Permutation {
     /// Generate permutation from key using HKDF
     pub fn from_key(key: &[u8], nonce: &[u8]) -> Self {
+        Self::from_key_n(key, nonce, CODE_N)
+    }
+    
+    /// Generate a permutation of `n` positions
+    pub fn from_key_n(key: &[u8], _nonce: &[u8], n: usize) -> Self {
         // Generate permutation using Fisher-Yates shuffle with deterministic RNG
-        let mut perm: Vec<usize> = (0..CODE_N).collect();
+        let mut perm: Vec<usize> = (0..n).collect();
         
         // Deterministic shuffle based on key
-        for i in (1..CODE_N).rev() {
+        for i in (1..n).rev() {
             let seed = kdf_shake256(
                 b"aegis-q-codemix-perm",
                 key,
@@ -104,7 +114,7 @@ impl Permutation {
         }
         
         // Compute inverse permutation
-        let mut inv_perm = vec![0; CODE_N];
+        let mut inv_perm = vec![0; n];
         for (i, &p) in perm.iter().enumerate() {
             inv_perm[p] = i;
         }
@@ -114,10 +124,11 @@ impl Permutation {
     
     /// Apply permutation: P * state
     pub fn apply(&self, state: &[u32]) -> Vec<u32> {
-        assert_eq!(state.len(), CODE_N);
+        let n = self.perm.len();
+        assert_eq!(state.len(), n);
         
-        let mut result = vec![0u32; CODE_N];
-        for i in 0..CODE_N {
+        let mut result = vec![0u32; n];
+        for i in 0..n {
             result[i] = state[self.perm[i]];
         }
         result
@@ -125,10 +136,11 @@ impl Permutation {
     
     /// Apply inverse permutation: P^(-1) * state
     pub fn apply_inverse(&self, state: &[u32]) -> Vec<u32> {
-        assert_eq!(state.len(), CODE_N);
+        let n = self.perm.len();
+        assert_eq!(state.len(), n);
         
-        let mut result = vec![0u32; CODE_N];
-        for i in 0..CODE_N {
+        let mut result = vec![0u32; n];
+        for i in 0..n {
             result[self.inv_perm[i]] = state[i];
         }
         result
@@ -145,7 +157,7 @@ pub fn code_mix(
     generator: &GeneratorMatrix,
     permutation: &Permutation,
 ) -> CodeState {
-    assert_eq!(state.len(), CODE_N);
+    assert_eq!(state.len(), permutation.perm.len());
     
     // Step 1: G * state
     let g_state = generator.multiply(state);
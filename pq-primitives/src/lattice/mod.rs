@@ -1,7 +1,7 @@
 //! LatticeMix - RLWE-based lattice operations
 //! 
 //! Implements: state_L' = (a * state_L + b) mod q
-//! Parameters: n = 4096, q = 2^32 - 5
+//! Parameters: n = 4096 by default (other dimensions via `_n` variants), q = 2^32 - 5
 //! Uses NTT (Number Theoretic Transform) for efficient polynomial multiplication
 
 use utils::kdf::kdf_shake256_fill;
@@ -18,12 +18,17 @@ pub type LatticeState = Vec<u32>;
 
 /// Generate lattice parameters from master key using SHAKE-256 (XOF)
 pub fn derive_lattice_params(key: &[u8], nonce: &[u8]) -> (LatticeState, LatticeState) {
+    derive_lattice_params_n(key, nonce, N)
+}
+
+/// Generate lattice parameters for dimension `n`
+pub fn derive_lattice_params_n(key: &[u8], nonce: &[u8], n: usize) -> (LatticeState, LatticeState) {
     // Derive 'a' parameter
-    let mut a_bytes = vec![0u8; N * 4];
+    let mut a_bytes = vec![0u8; n * 4];
     kdf_shake256_fill(b"aegis-q-lattice-a", key, nonce, &mut a_bytes);
 
     // Derive 'b' parameter
-    let mut b_bytes = vec![0u8; N * 4];
+    let mut b_bytes = vec![0u8; n * 4];
     kdf_shake256_fill(b"aegis-q-lattice-b", key, nonce, &mut b_bytes);
 
     // Convert bytes to u32 coefficients (mod q)
@@ -49,20 +54,21 @@ pub fn derive_lattice_params(key: &[u8], nonce: &[u8]) -> (LatticeState, Lattice
 /// Apply LatticeMix transformation
 /// state_L' = (a * state_L + b) mod q
 /// 
+/// The dimension is the state's length; `a` and `b` must match it.
 /// Uses NTT for polynomial multiplication in constant time
 pub fn lattice_mix(state: &LatticeState, a: &LatticeState, b: &LatticeState) -> LatticeState {
-    // Ensure state has correct length
-    assert_eq!(state.len(), N);
-    assert_eq!(a.len(), N);
-    assert_eq!(b.len(), N);
+    // Ensure parameters match the state's dimension
+    let n = state.len();
+    assert_eq!(a.len(), n);
+    assert_eq!(b.len(), n);
     
     // Compute a * state using NTT
     let a_ntt = ntt_forward(a);
     let state_ntt = ntt_forward(state);
     
     // Pointwise multiplication in NTT domain
-    let mut product_ntt = Vec::with_capacity(N);
-    for i in 0..N {
+    let mut product_ntt = Vec::with_capacity(n);
+    for i in 0..n {
         let prod = (a_ntt[i] as u64 * state_ntt[i] as u64) % Q;
         product_ntt.push(prod as u32);
     }
@@ -71,7 +77,7 @@ pub fn lattice_mix(state: &LatticeState, a: &LatticeState, b: &LatticeState) ->
     let mut result = ntt_inverse(&product_ntt);
     
     // Add b and reduce mod q
-    for i in 0..N {
+    for i in 0..n {
         result[i] = ((result[i] as u64 + b[i] as u64) % Q) as u32;
     }
     
@@ -80,6 +86,7 @@ pub fn lattice_mix(state: &LatticeState, a: &LatticeState, b: &LatticeState) ->
 
 /// Number Theoretic Transform (forward)
 /// Constant-time implementation
+#[allow(clippy::needless_range_loop)]
 fn ntt_forward(poly: &LatticeState) -> LatticeState {
     // Simplified NTT - full implementation would use optimized butterfly operations
     // This is a placeholder that maintains constant-time properties
@@ -90,10 +97,11 @@ fn ntt_forward(poly: &LatticeState) -> LatticeState {
     // This is a simplified version - full NTT would be more complex
     
     // Constant-time polynomial evaluation
-    for i in 0..N {
+    let n = poly.len();
+    for i in 0..n {
         let mut sum = 0u64;
-        for j in 0..N {
-            let omega_pow = mod_pow(5, (i * j) % N, Q); // Primitive root approximation
+        for j in 0..n {
+            let omega_pow = mod_pow(5, (i * j) % n, Q); // Primitive root approximation
             sum = (sum + (poly[j] as u64 * omega_pow) % Q) % Q;
         }
         result[i] = sum as u32;
@@ -104,15 +112,17 @@ fn ntt_forward(poly: &LatticeState) -> LatticeState {
 
 /// Number Theoretic Transform (inverse)
 /// Constant-time implementation
+#[allow(clippy::needless_range_loop)]
 fn ntt_inverse(poly: &LatticeState) -> LatticeState {
-    // Inverse NTT with modular inverse of N
-    let n_inv = mod_inverse(N as u64, Q);
-    let mut result = vec![0u32; N];
+    // Inverse NTT with modular inverse of n
+    let n = poly.len();
+    let n_inv = mod_inverse(n as u64, Q);
+    let mut result = vec![0u32; n];
     
-    for i in 0..N {
+    for i in 0..n {
         let mut sum = 0u64;
-        for j in 0..N {
-            let omega_pow = mod_pow(5, (Q as usize - 1 - (i * j) % N) % N, Q);
+        for j in 0..n {
+            let omega_pow = mod_pow(5, (Q as usize - 1 - (i * j) % n) % n, Q);
             sum = (sum + (poly[j] as u64 * omega_pow) % Q) % Q;
         }
         result[i] = ((sum * n_inv) % Q) as u32;
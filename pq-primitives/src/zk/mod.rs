@@ -42,7 +42,7 @@ pub fn zk_simulate(nonce: &[u8], rng: &mut impl FnMut() -> u8) -> ZKState {
     // H(r || 0)
     let mut hasher = Sha3_512::new();
     Update::update(&mut hasher, nonce);
-    Update::update(&mut hasher, &vec![0u8; ZK_STATE_SIZE]);
+    Update::update(&mut hasher, &[0u8; ZK_STATE_SIZE]);
     let hash = hasher.finalize();
     
     // XOR with random
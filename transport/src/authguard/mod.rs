@@ -0,0 +1,296 @@
+//! Authentication Guard
+//!
+//! Per-source tracking of handshake and authentication failures to blunt
+//! online PSK guessing and handshake flooding. Each failure doubles the
+//! delay before the source may try again; too many failures within the
+//! window put the source on a temporary ban list, with ban length doubling
+//! for repeat offenders. IPv6 sources are tracked per /64, since a single
+//! host usually controls the whole prefix.
+//!
+//! `tenant::SessionManager::accept` calls `check` before doing handshake
+//! work, and `record_failure` / `record_success` afterwards. Ban and unban
+//! events are queued for logging or metrics and collected with
+//! `drain_events`. Offence history is dropped once a source has been quiet
+//! for `offence_ttl_ms`, so sources rotated through by an attacker do not
+//! pile up.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+
+use crate::config::LimitsSection;
+
+/// Thresholds and timings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GuardConfig {
+    /// Failures within `window_ms` that trigger a ban
+    pub max_failures: u32,
+    /// Failure counting window
+    pub window_ms: u64,
+    /// Delay after the first failure (doubles per failure)
+    pub base_backoff_ms: u64,
+    /// Longest delay between attempts
+    pub max_backoff_ms: u64,
+    /// First ban length (doubles per repeat offence)
+    pub base_ban_ms: u64,
+    /// Longest ban
+    pub max_ban_ms: u64,
+    /// How long a source's offence history is kept after its last failure
+    pub offence_ttl_ms: u64,
+}
+
+impl Default for GuardConfig {
+    fn default() -> Self {
+        Self {
+            max_failures: 5,
+            window_ms: 5 * 60_000,
+            base_backoff_ms: 250,
+            max_backoff_ms: 30_000,
+            base_ban_ms: 60_000,
+            max_ban_ms: 24 * 3_600_000,
+            offence_ttl_ms: 7 * 24 * 3_600_000,
+        }
+    }
+}
+
+impl From<&LimitsSection> for GuardConfig {
+    fn from(section: &LimitsSection) -> Self {
+        Self {
+            max_failures: section.max_auth_failures,
+            ..Self::default()
+        }
+    }
+}
+
+/// Why a source is refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuardError {
+    /// Source is banned until the given time
+    Banned { until_ms: u64 },
+    /// Source must wait before trying again
+    Backoff { retry_at_ms: u64 },
+}
+
+impl fmt::Display for GuardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GuardError::Banned { until_ms } => write!(f, "source banned until {} ms", until_ms),
+            GuardError::Backoff { retry_at_ms } => write!(f, "retry not before {} ms", retry_at_ms),
+        }
+    }
+}
+
+impl std::error::Error for GuardError {}
+
+/// Ban list change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuardEvent {
+    Banned { source: IpAddr, until_ms: u64, offences: u32 },
+    Unbanned { source: IpAddr },
+}
+
+#[derive(Debug, Clone, Default)]
+struct SourceState {
+    failures: u32,
+    window_start: u64,
+    last_failure: u64,
+    retry_at: u64,
+    banned_until: Option<u64>,
+    offences: u32,
+}
+
+/// Failure tracker and temporary ban list
+#[derive(Debug, Clone, Default)]
+pub struct AuthGuard {
+    config: GuardConfig,
+    sources: HashMap<IpAddr, SourceState>,
+    events: Vec<GuardEvent>,
+}
+
+impl AuthGuard {
+    /// Guard with the given thresholds
+    pub fn new(config: GuardConfig) -> Self {
+        Self {
+            config,
+            sources: HashMap::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Whether `source` may attempt a handshake now
+    pub fn check(&mut self, source: IpAddr, now_ms: u64) -> Result<(), GuardError> {
+        let key = source_key(source);
+        let Some(state) = self.sources.get_mut(&key) else {
+            return Ok(());
+        };
+        if let Some(until_ms) = state.banned_until {
+            if now_ms < until_ms {
+                return Err(GuardError::Banned { until_ms });
+            }
+            state.banned_until = None;
+            state.failures = 0;
+            self.events.push(GuardEvent::Unbanned { source: key });
+        }
+        if now_ms < state.retry_at {
+            return Err(GuardError::Backoff { retry_at_ms: state.retry_at });
+        }
+        Ok(())
+    }
+
+    /// Record a failed handshake or authentication
+    pub fn record_failure(&mut self, source: IpAddr, now_ms: u64) {
+        let key = source_key(source);
+        let config = self.config;
+        let state = self.sources.entry(key).or_default();
+
+        if now_ms.saturating_sub(state.window_start) > config.window_ms {
+            state.window_start = now_ms;
+            state.failures = 0;
+        }
+        state.failures += 1;
+        state.last_failure = now_ms;
+        let backoff = config.base_backoff_ms.saturating_mul(1 << (state.failures - 1).min(32)).min(config.max_backoff_ms);
+        state.retry_at = now_ms + backoff;
+
+        if state.failures >= config.max_failures && state.banned_until.is_none() {
+            state.offences += 1;
+            let ban = config.base_ban_ms.saturating_mul(1 << (state.offences - 1).min(32)).min(config.max_ban_ms);
+            let until_ms = now_ms + ban;
+            state.banned_until = Some(until_ms);
+            self.events.push(GuardEvent::Banned {
+                source: key,
+                until_ms,
+                offences: state.offences,
+            });
+        }
+    }
+
+    /// Record a successful authentication (clears failures, keeps offence history)
+    pub fn record_success(&mut self, source: IpAddr) {
+        if let Some(state) = self.sources.get_mut(&source_key(source)) {
+            state.failures = 0;
+            state.retry_at = 0;
+        }
+    }
+
+    /// Lift expired bans, forget idle sources without offences and drop
+    /// offence history older than `offence_ttl_ms`
+    pub fn expire(&mut self, now_ms: u64) {
+        let window = self.config.window_ms;
+        let offence_ttl = self.config.offence_ttl_ms;
+        for (source, state) in self.sources.iter_mut() {
+            if state.banned_until.is_some_and(|until| now_ms >= until) {
+                state.banned_until = None;
+                state.failures = 0;
+                self.events.push(GuardEvent::Unbanned { source: *source });
+            }
+        }
+        self.sources.retain(|_, s| {
+            s.banned_until.is_some()
+                || (s.offences > 0 && now_ms.saturating_sub(s.last_failure) <= offence_ttl)
+                || now_ms.saturating_sub(s.window_start) <= window
+        });
+    }
+
+    /// Number of tracked sources
+    pub fn tracked(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// Currently banned sources
+    pub fn banned(&self, now_ms: u64) -> Vec<(IpAddr, u64)> {
+        self.sources
+            .iter()
+            .filter_map(|(source, s)| s.banned_until.filter(|until| *until > now_ms).map(|until| (*source, until)))
+            .collect()
+    }
+
+    /// Take queued ban/unban events
+    pub fn drain_events(&mut self) -> Vec<GuardEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+/// Tracking key: the address itself for IPv4, the /64 prefix for IPv6
+fn source_key(source: IpAddr) -> IpAddr {
+    match source {
+        IpAddr::V4(_) => source,
+        IpAddr::V6(v6) => IpAddr::V6((u128::from(v6) & !((1u128 << 64) - 1)).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_backoff_and_ban() {
+        let mut guard = AuthGuard::default();
+        let attacker = ip("198.51.100.9");
+
+        guard.record_failure(attacker, 0);
+        assert_eq!(guard.check(attacker, 100), Err(GuardError::Backoff { retry_at_ms: 250 }));
+        assert!(guard.check(attacker, 250).is_ok());
+        guard.record_failure(attacker, 250);
+        assert_eq!(guard.check(attacker, 500), Err(GuardError::Backoff { retry_at_ms: 750 }));
+
+        for t in 1..4 {
+            guard.record_failure(attacker, 10_000 * t);
+        }
+        assert!(matches!(guard.check(attacker, 30_001), Err(GuardError::Banned { until_ms: 90_000 })));
+        assert_eq!(guard.drain_events(), vec![GuardEvent::Banned { source: attacker, until_ms: 90_000, offences: 1 }]);
+        assert!(guard.check(ip("198.51.100.10"), 30_001).is_ok());
+
+        guard.expire(90_000);
+        assert_eq!(guard.drain_events(), vec![GuardEvent::Unbanned { source: attacker }]);
+        assert!(guard.check(attacker, 200_000).is_ok());
+
+        // Repeat offender gets a longer ban
+        for t in 0..5 {
+            guard.record_failure(attacker, 200_000 + t * 1_000);
+        }
+        assert_eq!(guard.banned(204_000), vec![(attacker, 204_000 + 120_000)]);
+    }
+
+    #[test]
+    fn test_success_resets_and_ipv6_prefix() {
+        let mut guard = AuthGuard::new(GuardConfig { max_failures: 2, ..GuardConfig::default() });
+        let client = ip("192.0.2.1");
+        guard.record_failure(client, 0);
+        guard.record_success(client);
+        guard.record_failure(client, 1_000);
+        assert!(guard.banned(1_000).is_empty());
+
+        // Rotating addresses within one /64 does not evade the ban
+        guard.record_failure(ip("2001:db8:1:2::1"), 0);
+        guard.record_failure(ip("2001:db8:1:2::ffff"), 1_000);
+        assert!(matches!(guard.check(ip("2001:db8:1:2::abcd"), 2_000), Err(GuardError::Banned { .. })));
+        assert!(guard.check(ip("2001:db8:1:3::1"), 2_000).is_ok());
+    }
+
+    #[test]
+    fn test_offence_history_expires() {
+        let config = GuardConfig { max_failures: 1, offence_ttl_ms: 3_600_000, ..GuardConfig::default() };
+        let mut guard = AuthGuard::new(config);
+        assert_eq!(GuardConfig::from(&LimitsSection::default()).max_failures, 5);
+
+        // An attacker rotating through sources leaves one record each
+        for i in 0..100u32 {
+            guard.record_failure(IpAddr::V4((0xc633_6400 + i).into()), 0);
+        }
+        guard.expire(1_000_000);
+        assert_eq!(guard.tracked(), 100);
+        assert!(guard.banned(1_000_000).is_empty());
+
+        // Quiet for longer than the TTL: records are gone, a repeat is a first offence
+        guard.expire(3_600_001);
+        assert_eq!(guard.tracked(), 0);
+        let source = ip("198.51.100.0");
+        guard.record_failure(source, 4_000_000);
+        assert_eq!(guard.banned(4_000_000), vec![(source, 4_000_000 + config.base_ban_ms)]);
+    }
+}
@@ -0,0 +1,49 @@
+//! decrypt-capture: offline decryption of Aegis-Q debug captures
+//!
+//! Usage: `decrypt-capture <capture-file> <operator-key-file> <secrets-file>`
+//!
+//! Prints one line per captured frame:
+//! `<timestamp_ms> <in|out> <session_id> <sequence> <payload hex | error>`
+
+use std::process::ExitCode;
+
+use transport::capture::{decrypt_records, hex_encode, read_capture, Direction, SessionSecrets};
+
+fn run(args: &[String]) -> Result<(), String> {
+    let [capture_path, key_path, secrets_path] = args else {
+        return Err("usage: decrypt-capture <capture-file> <operator-key-file> <secrets-file>".to_string());
+    };
+    let read = |path: &String| std::fs::read(path).map_err(|e| format!("{}: {}", path, e));
+
+    let capture = read(capture_path)?;
+    let operator_key = read(key_path)?;
+    let secrets_text = String::from_utf8(read(secrets_path)?).map_err(|_| format!("{}: not UTF-8", secrets_path))?;
+
+    let records = read_capture(&capture, &operator_key).map_err(|e| format!("{}: {}", capture_path, e))?;
+    let secrets = SessionSecrets::parse_all(&secrets_text).map_err(|e| format!("{}: {}", secrets_path, e))?;
+
+    for frame in decrypt_records(records, &secrets) {
+        let direction = match frame.record.direction {
+            Direction::Inbound => "in",
+            Direction::Outbound => "out",
+        };
+        let sequence = frame.sequence.map_or_else(|| "-".to_string(), |s| s.to_string());
+        let payload = match &frame.payload {
+            Ok(plaintext) => hex_encode(plaintext),
+            Err(reason) => format!("<{}>", reason),
+        };
+        println!("{} {} {} {} {}", frame.record.timestamp_ms, direction, frame.record.session_id, sequence, payload);
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("decrypt-capture: {}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,56 @@
+//! dissect: field-by-field breakdown of a raw Aegis-Q frame
+//!
+//! Usage: `dissect <frame-hex | -> [<secrets-file> <in|out>]`
+//!
+//! With `-` the frame hex is read from stdin (whitespace ignored). Given a
+//! secrets export and the frame's direction relative to the exporting
+//! endpoint, `Data` payloads are decrypted and classified as well.
+
+use std::io::Read;
+use std::process::ExitCode;
+
+use transport::capture::{hex_decode, Direction, SessionSecrets};
+use transport::dissect::{dissect, dissect_with_secrets};
+
+fn run(args: &[String]) -> Result<(), String> {
+    let (frame_arg, secrets) = match args {
+        [frame] => (frame, None),
+        [frame, secrets_path, direction] => (frame, Some((secrets_path, direction))),
+        _ => return Err("usage: dissect <frame-hex | -> [<secrets-file> <in|out>]".to_string()),
+    };
+
+    let mut frame_hex = frame_arg.clone();
+    if frame_arg == "-" {
+        frame_hex.clear();
+        std::io::stdin().read_to_string(&mut frame_hex).map_err(|e| format!("stdin: {}", e))?;
+    }
+    let frame_hex: String = frame_hex.split_whitespace().collect();
+    let frame = hex_decode(&frame_hex).map_err(|e| format!("frame: {}", e))?;
+
+    let dissection = match secrets {
+        None => dissect(&frame),
+        Some((secrets_path, direction)) => {
+            let direction = match direction.as_str() {
+                "in" => Direction::Inbound,
+                "out" => Direction::Outbound,
+                other => return Err(format!("direction must be `in` or `out`, not `{}`", other)),
+            };
+            let text = std::fs::read_to_string(secrets_path).map_err(|e| format!("{}: {}", secrets_path, e))?;
+            let secrets = SessionSecrets::parse_all(&text).map_err(|e| format!("{}: {}", secrets_path, e))?;
+            dissect_with_secrets(&frame, &secrets, direction)
+        }
+    };
+    print!("{}", dissection);
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("dissect: {}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,328 @@
+//! Encrypted Traffic Capture
+//!
+//! Debug capture of encrypted frames, similar to a pcap taken alongside an
+//! SSLKEYLOGFILE. A `CaptureWriter` attached to a session records frames
+//! as they cross the wire; the capture itself is encrypted under an
+//! operator key, so leaking the file reveals nothing without that key.
+//! Frames stay encrypted inside it: reading plaintext additionally needs
+//! the session's exported traffic secrets (`VpnSession::export_secrets`).
+//!
+//! Capture file layout:
+//! `"AQCP" || version (1) || file nonce (16) || records`, where each record
+//! is `len (4 BE) || aegis(operator_key, file_nonce || index, body)` and
+//! body is `session_id (32) || direction (1) || timestamp_ms (8 BE) || frame`.
+//!
+//! Secrets export, one session per line (`#` starts a comment):
+//! `AEGISQ_TRAFFIC_SECRETS <session_id> <nonce> <send_key> <recv_key>`,
+//! all hex, keys from the exporting endpoint's point of view.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use aegis_q_core::{AegisQError, aegis_q_decrypt, aegis_q_try_encrypt};
+use utils::memory::zeroize;
+use utils::rng::random_bytes;
+use utils::wire::{CaptureRecordWire, Wire};
+
+use crate::framing::FrameHeader;
+use crate::session::SessionId;
+use crate::vpn::VpnSession;
+
+/// Capture file magic
+pub const CAPTURE_MAGIC: &[u8; 4] = b"AQCP";
+
+/// Capture format version
+pub const CAPTURE_VERSION: u8 = 1;
+
+/// Secrets export line label
+pub const SECRETS_LABEL: &str = "AEGISQ_TRAFFIC_SECRETS";
+
+const FILE_NONCE_SIZE: usize = 16;
+
+/// Frame direction relative to the capturing endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Outbound = 0,
+    Inbound = 1,
+}
+
+/// Traffic secrets of one session
+#[derive(Clone, PartialEq, Eq)]
+pub struct SessionSecrets {
+    pub session_id: SessionId,
+    pub nonce: Vec<u8>,
+    /// Key of frames the exporting endpoint sends
+    pub send_key: Vec<u8>,
+    /// Key of frames the exporting endpoint receives
+    pub recv_key: Vec<u8>,
+}
+
+impl Drop for SessionSecrets {
+    fn drop(&mut self) {
+        zeroize(&mut self.send_key);
+        zeroize(&mut self.recv_key);
+    }
+}
+
+impl std::fmt::Debug for SessionSecrets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionSecrets").field("session_id", &self.session_id).finish_non_exhaustive()
+    }
+}
+
+impl SessionSecrets {
+    /// Export line (without trailing newline)
+    pub fn to_line(&self) -> String {
+        format!(
+            "{} {} {} {} {}",
+            SECRETS_LABEL,
+            self.session_id.to_hex(),
+            hex_encode(&self.nonce),
+            hex_encode(&self.send_key),
+            hex_encode(&self.recv_key)
+        )
+    }
+
+    /// Parse an export file, skipping blank lines and comments
+    pub fn parse_all(text: &str) -> Result<Vec<Self>, AegisQError> {
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(Self::parse_line)
+            .collect()
+    }
+
+    fn parse_line(line: &str) -> Result<Self, AegisQError> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [label, session_id, nonce, send_key, recv_key] = fields[..] else {
+            return Err(AegisQError::Serialization("Malformed secrets line"));
+        };
+        if label != SECRETS_LABEL {
+            return Err(AegisQError::Serialization("Unknown secrets label"));
+        }
+        let secrets = Self {
+            session_id: VpnSession::session_id_for_keys(&hex_decode(send_key)?, &hex_decode(recv_key)?, &hex_decode(nonce)?),
+            nonce: hex_decode(nonce)?,
+            send_key: hex_decode(send_key)?,
+            recv_key: hex_decode(recv_key)?,
+        };
+        if secrets.session_id.to_hex() != session_id.to_ascii_lowercase() {
+            return Err(AegisQError::Protocol("Session ID does not match secrets"));
+        }
+        Ok(secrets)
+    }
+}
+
+/// One captured frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureRecord {
+    pub session_id: SessionId,
+    pub direction: Direction,
+    pub timestamp_ms: u64,
+    /// Encrypted frame as sent on the wire
+    pub frame: Vec<u8>,
+}
+
+/// Writes an operator-encrypted capture
+pub struct CaptureWriter<W: Write> {
+    inner: W,
+    key: Vec<u8>,
+    file_nonce: Vec<u8>,
+    index: u64,
+}
+
+impl<W: Write> CaptureWriter<W> {
+    /// Start a capture (writes the file header)
+    pub fn new(mut inner: W, operator_key: &[u8]) -> io::Result<Self> {
+        let file_nonce = random_bytes(FILE_NONCE_SIZE);
+        inner.write_all(CAPTURE_MAGIC)?;
+        inner.write_all(&[CAPTURE_VERSION])?;
+        inner.write_all(&file_nonce)?;
+        Ok(Self {
+            inner,
+            key: operator_key.to_vec(),
+            file_nonce,
+            index: 0,
+        })
+    }
+
+    /// Record a frame
+    pub fn record(&mut self, session_id: SessionId, direction: Direction, timestamp_ms: u64, frame: &[u8]) -> io::Result<()> {
+        let body = CaptureRecordWire {
+            session_id: *session_id.as_bytes(),
+            direction: direction as u8,
+            timestamp_ms,
+            frame: frame.to_vec(),
+        }
+        .to_wire()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        let ciphertext = aegis_q_try_encrypt(&self.key, &record_nonce(&self.file_nonce, self.index), &body)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        self.index += 1;
+        self.inner.write_all(&(ciphertext.len() as u32).to_be_bytes())?;
+        self.inner.write_all(&ciphertext)
+    }
+
+    /// Flush and return the underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+fn record_nonce(file_nonce: &[u8], index: u64) -> Vec<u8> {
+    let mut nonce = file_nonce.to_vec();
+    nonce.extend_from_slice(&index.to_be_bytes());
+    nonce
+}
+
+/// Decrypt a capture file with the operator key
+pub fn read_capture(data: &[u8], operator_key: &[u8]) -> Result<Vec<CaptureRecord>, AegisQError> {
+    let header_len = CAPTURE_MAGIC.len() + 1 + FILE_NONCE_SIZE;
+    if data.len() < header_len || &data[..4] != CAPTURE_MAGIC {
+        return Err(AegisQError::Serialization("Not a capture file"));
+    }
+    if data[4] != CAPTURE_VERSION {
+        return Err(AegisQError::Unsupported("Unsupported capture version"));
+    }
+    let file_nonce = &data[5..header_len];
+
+    let mut records = Vec::new();
+    let mut rest = &data[header_len..];
+    while !rest.is_empty() {
+        if rest.len() < 4 {
+            return Err(AegisQError::InvalidLength("Truncated capture"));
+        }
+        let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        let ciphertext = rest.get(4..4 + len).ok_or(AegisQError::InvalidLength("Truncated capture"))?;
+        rest = &rest[4 + len..];
+
+        let body = aegis_q_decrypt(operator_key, &record_nonce(file_nonce, records.len() as u64), ciphertext)
+            .map_err(|_| AegisQError::AuthenticationFailed)?;
+        let body = CaptureRecordWire::from_wire(&body).map_err(|_| AegisQError::Serialization("Malformed capture record"))?;
+        let direction = match body.direction {
+            0 => Direction::Outbound,
+            1 => Direction::Inbound,
+            _ => return Err(AegisQError::Serialization("Malformed capture record")),
+        };
+        records.push(CaptureRecord {
+            session_id: SessionId::from_bytes(body.session_id),
+            direction,
+            timestamp_ms: body.timestamp_ms,
+            frame: body.frame,
+        });
+    }
+    Ok(records)
+}
+
+/// Captured frame after offline decryption
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecryptedFrame {
+    pub record: CaptureRecord,
+    pub sequence: Option<u64>,
+    /// Plaintext, or why it could not be recovered
+    pub payload: Result<Vec<u8>, AegisQError>,
+}
+
+/// Reconstruct plaintext frames using exported session secrets
+///
+/// Secrets must come from the endpoint that made the capture, so that
+/// directions line up. Frames of sessions without secrets are reported
+/// as undecryptable rather than dropped.
+pub fn decrypt_records(records: Vec<CaptureRecord>, secrets: &[SessionSecrets]) -> Vec<DecryptedFrame> {
+    let by_session: HashMap<SessionId, &SessionSecrets> = secrets.iter().map(|s| (s.session_id, s)).collect();
+    let mut decoders: HashMap<(SessionId, Direction), VpnSession> = HashMap::new();
+
+    records
+        .into_iter()
+        .map(|record| {
+            let sequence = FrameHeader::decode(&record.frame).ok().map(|h| h.sequence);
+            let payload = match (by_session.get(&record.session_id), sequence) {
+                (None, _) => Err(AegisQError::NotFound("No secrets for session")),
+                (_, None) => Err(AegisQError::Serialization("Malformed frame")),
+                (Some(secrets), Some(sequence)) => {
+                    let decoder = decoders.entry((record.session_id, record.direction)).or_insert_with(|| {
+                        let (other, key) = match record.direction {
+                            Direction::Outbound => (&secrets.recv_key, &secrets.send_key),
+                            Direction::Inbound => (&secrets.send_key, &secrets.recv_key),
+                        };
+                        VpnSession::from_keys(other, key, &secrets.nonce)
+                    });
+                    decoder.resync_recv(sequence);
+                    decoder.decrypt_data(&record.frame)
+                }
+            };
+            DecryptedFrame { record, sequence, payload }
+        })
+        .collect()
+}
+
+/// Lowercase hex
+pub fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Parse hex (either case)
+pub fn hex_decode(text: &str) -> Result<Vec<u8>, AegisQError> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return Err(AegisQError::Serialization("Invalid hex"));
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).map_err(|_| AegisQError::Serialization("Invalid hex")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session_pair() -> (VpnSession, VpnSession) {
+        let nonce = b"capture-nonce-01";
+        (VpnSession::from_keys(b"key-c2s", b"key-s2c", nonce), VpnSession::from_keys(b"key-s2c", b"key-c2s", nonce))
+    }
+
+    #[test]
+    fn test_capture_roundtrip() {
+        let (mut client, mut server) = session_pair();
+        let id = server.session_id();
+        let mut writer = CaptureWriter::new(Vec::new(), b"operator-key").unwrap();
+
+        let up = client.encrypt_data(b"GET /");
+        server.decrypt_data(&up).unwrap();
+        writer.record(id, Direction::Inbound, 10, &up).unwrap();
+        // Frame missing from the capture: decryption resyncs on the next one
+        server.encrypt_data(b"lost in capture");
+        let down = server.encrypt_data(b"200 OK");
+        writer.record(id, Direction::Outbound, 12, &down).unwrap();
+        let capture = writer.finish().unwrap();
+
+        assert!(read_capture(&capture, b"wrong-key").is_err());
+        let records = read_capture(&capture, b"operator-key").unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].timestamp_ms, 12);
+
+        // Secrets go through the text export format
+        let export = format!("# server\n{}\n", server.export_secrets().to_line());
+        let secrets = SessionSecrets::parse_all(&export).unwrap();
+        let frames = decrypt_records(records.clone(), &secrets);
+        assert_eq!(frames[0].payload.as_deref(), Ok(&b"GET /"[..]));
+        assert_eq!(frames[1].sequence, Some(1));
+        assert_eq!(frames[1].payload.as_deref(), Ok(&b"200 OK"[..]));
+
+        let frames = decrypt_records(records, &[]);
+        assert_eq!(frames[0].payload, Err(AegisQError::NotFound("No secrets for session")));
+    }
+
+    #[test]
+    fn test_secrets_line() {
+        let (client, _) = session_pair();
+        let secrets = client.export_secrets();
+        let line = secrets.to_line();
+        assert!(line.starts_with("AEGISQ_TRAFFIC_SECRETS "));
+        assert_eq!(SessionSecrets::parse_all(&line).unwrap(), vec![secrets]);
+        assert!(SessionSecrets::parse_all(&line.replace("6b6579", "6b6578")).is_err());
+        assert!(SessionSecrets::parse_all("AEGISQ_TRAFFIC_SECRETS 00").is_err());
+    }
+}
@@ -0,0 +1,383 @@
+//! Connection ID Routing
+//!
+//! Routable connection IDs for server fleets behind L4 load balancers.
+//! Each server embeds its server ID in the connection IDs it issues; the
+//! load balancer recovers it with the routing key and forwards the packet
+//! to that backend, so a connection stays on one server across client
+//! address changes without per-connection state in the balancer.
+//!
+//! ```text
+//! first octet: config ID (3 bits) || CID length - 1 (5 bits)
+//! then:        server ID || nonce      (encrypted when the config has a key)
+//! ```
+//!
+//! - Encrypted configs run a four-pass Feistel network over
+//!   `server ID || nonce` with a SHAKE-256 round function keyed by the
+//!   routing key. Observers cannot link connection IDs of one server; the
+//!   nonce must be long enough that it does not repeat.
+//! - Plaintext configs carry the server ID in clear at a fixed offset.
+//!   They are meant for eBPF/XDP balancers, which can match it against a
+//!   map (`CidRouter::xdp_entries`) without running the cipher.
+//!
+//! Config ID 7 is reserved: connection IDs starting with it, or with a
+//! config the balancer does not know (e.g. the client's first random
+//! connection ID), are routed by a hash of the whole connection ID over the
+//! backends, which is stable for as long as the ID is used.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use aegis_q_core::AegisQError;
+use utils::kdf::KeyedKdf;
+use utils::rng::random_bytes;
+
+/// Longest connection ID (as in QUIC)
+pub const MAX_CID_LEN: usize = 20;
+
+/// Config ID that never names a routing config
+pub const UNROUTABLE_CONFIG_ID: u8 = 7;
+
+/// Shortest nonce accepted for encrypted configs
+pub const MIN_ENCRYPTED_NONCE_LEN: usize = 4;
+
+/// Connection ID layout shared by the servers and the load balancer
+#[derive(Clone)]
+pub struct CidConfig {
+    config_id: u8,
+    server_id_len: usize,
+    nonce_len: usize,
+    cipher: Option<KeyedKdf>,
+}
+
+impl CidConfig {
+    /// Encrypted layout under `routing_key`
+    pub fn encrypted(config_id: u8, server_id_len: usize, nonce_len: usize, routing_key: &[u8]) -> Result<Self, AegisQError> {
+        if nonce_len < MIN_ENCRYPTED_NONCE_LEN {
+            return Err(AegisQError::InvalidInput("Connection ID nonce too short"));
+        }
+        let mut config = Self::plaintext(config_id, server_id_len, nonce_len)?;
+        config.cipher = Some(KeyedKdf::new(b"aegis-q-cid", routing_key));
+        Ok(config)
+    }
+
+    /// Layout with the server ID in clear (for eBPF/XDP balancers)
+    pub fn plaintext(config_id: u8, server_id_len: usize, nonce_len: usize) -> Result<Self, AegisQError> {
+        if config_id >= UNROUTABLE_CONFIG_ID {
+            return Err(AegisQError::InvalidInput("Connection ID config ID must be below 7"));
+        }
+        if server_id_len == 0 || nonce_len == 0 {
+            return Err(AegisQError::InvalidInput("Server ID and nonce must not be empty"));
+        }
+        if 1 + server_id_len + nonce_len > MAX_CID_LEN {
+            return Err(AegisQError::InvalidLength("Connection ID too long"));
+        }
+        Ok(Self {
+            config_id,
+            server_id_len,
+            nonce_len,
+            cipher: None,
+        })
+    }
+
+    pub fn config_id(&self) -> u8 {
+        self.config_id
+    }
+
+    /// Length of connection IDs under this config
+    pub fn cid_len(&self) -> usize {
+        1 + self.server_id_len + self.nonce_len
+    }
+
+    pub fn server_id_len(&self) -> usize {
+        self.server_id_len
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// Connection ID for `server_id` with an explicit nonce
+    pub fn encode(&self, server_id: &[u8], nonce: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        if server_id.len() != self.server_id_len || nonce.len() != self.nonce_len {
+            return Err(AegisQError::InvalidLength("Server ID or nonce length does not match config"));
+        }
+        let mut cid = Vec::with_capacity(self.cid_len());
+        cid.push(self.first_octet());
+        cid.extend_from_slice(server_id);
+        cid.extend_from_slice(nonce);
+        if let Some(cipher) = &self.cipher {
+            self.feistel(cipher, &mut cid[1..], false);
+        }
+        Ok(cid)
+    }
+
+    /// Server ID embedded in `cid`
+    pub fn server_id(&self, cid: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        if cid.len() != self.cid_len() {
+            return Err(AegisQError::InvalidLength("Connection ID length does not match config"));
+        }
+        if cid[0] != self.first_octet() {
+            return Err(AegisQError::InvalidInput("Connection ID belongs to another config"));
+        }
+        let mut body = cid[1..].to_vec();
+        if let Some(cipher) = &self.cipher {
+            self.feistel(cipher, &mut body, true);
+        }
+        body.truncate(self.server_id_len);
+        Ok(body)
+    }
+
+    fn first_octet(&self) -> u8 {
+        (self.config_id << 5) | (self.cid_len() - 1) as u8
+    }
+
+    /// Four-pass Feistel network over `server ID || nonce`
+    fn feistel(&self, cipher: &KeyedKdf, body: &mut [u8], decrypt: bool) {
+        let (left, right) = body.split_at_mut(body.len() / 2);
+        let passes: [u8; 4] = if decrypt { [4, 3, 2, 1] } else { [1, 2, 3, 4] };
+        for pass in passes {
+            // Odd passes mask the right half, even passes the left one
+            let (source, target) = if pass % 2 == 1 { (&*left, &mut *right) } else { (&*right, &mut *left) };
+            let mut info = Vec::with_capacity(2 + source.len());
+            info.extend_from_slice(&[self.config_id, pass]);
+            info.extend_from_slice(source);
+            let mut mask = [0u8; MAX_CID_LEN];
+            cipher.fill(&info, &mut mask[..target.len()]);
+            for (byte, m) in target.iter_mut().zip(mask.iter()) {
+                *byte ^= m;
+            }
+        }
+    }
+}
+
+impl fmt::Debug for CidConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CidConfig")
+            .field("config_id", &self.config_id)
+            .field("server_id_len", &self.server_id_len)
+            .field("nonce_len", &self.nonce_len)
+            .field("encrypted", &self.is_encrypted())
+            .finish()
+    }
+}
+
+/// Issues routable connection IDs on one server
+#[derive(Debug, Clone)]
+pub struct CidGenerator {
+    config: CidConfig,
+    server_id: Vec<u8>,
+}
+
+impl CidGenerator {
+    pub fn new(config: CidConfig, server_id: &[u8]) -> Result<Self, AegisQError> {
+        if server_id.len() != config.server_id_len {
+            return Err(AegisQError::InvalidLength("Server ID length does not match config"));
+        }
+        Ok(Self {
+            config,
+            server_id: server_id.to_vec(),
+        })
+    }
+
+    /// New connection ID with a random nonce
+    pub fn generate(&self) -> Vec<u8> {
+        let nonce = random_bytes(self.config.nonce_len);
+        self.config.encode(&self.server_id, &nonce).expect("lengths checked in new")
+    }
+
+    pub fn server_id(&self) -> &[u8] {
+        &self.server_id
+    }
+}
+
+/// Where a packet goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    /// Backend named by the server ID in the connection ID
+    Server(usize),
+    /// No known server ID: backend picked by hashing the connection ID
+    Hashed(usize),
+}
+
+impl Route {
+    pub fn backend(&self) -> usize {
+        match self {
+            Route::Server(backend) | Route::Hashed(backend) => *backend,
+        }
+    }
+}
+
+/// Server ID map entry for an eBPF/XDP balancer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XdpEntry {
+    /// Expected first octet of the connection ID
+    pub first_octet: u8,
+    /// Server ID, at offset 1 of the connection ID
+    pub server_id: Vec<u8>,
+    pub backend: u32,
+}
+
+/// Load balancer side: connection ID to backend
+#[derive(Debug, Clone)]
+pub struct CidRouter {
+    configs: [Option<CidConfig>; UNROUTABLE_CONFIG_ID as usize],
+    servers: HashMap<Vec<u8>, usize>,
+    backends: usize,
+}
+
+impl CidRouter {
+    /// Router over `backends` backends (used for hashed routes)
+    pub fn new(backends: usize) -> Self {
+        Self {
+            configs: Default::default(),
+            servers: HashMap::new(),
+            backends: backends.max(1),
+        }
+    }
+
+    /// Add or replace the config with the same ID (rotation: add the new
+    /// config ID, remove the old one once its connections are gone)
+    pub fn add_config(&mut self, config: CidConfig) {
+        let id = config.config_id as usize;
+        self.configs[id] = Some(config);
+    }
+
+    pub fn remove_config(&mut self, config_id: u8) -> Option<CidConfig> {
+        self.configs.get_mut(config_id as usize).and_then(Option::take)
+    }
+
+    /// Route connection IDs carrying `server_id` to `backend`
+    pub fn set_server(&mut self, server_id: &[u8], backend: usize) -> Result<(), AegisQError> {
+        if backend >= self.backends {
+            return Err(AegisQError::InvalidInput("Backend index out of range"));
+        }
+        self.servers.insert(server_id.to_vec(), backend);
+        Ok(())
+    }
+
+    pub fn remove_server(&mut self, server_id: &[u8]) -> Option<usize> {
+        self.servers.remove(server_id)
+    }
+
+    /// Server ID in `cid`, if a known config produced it
+    pub fn server_id(&self, cid: &[u8]) -> Option<Vec<u8>> {
+        let config = self.configs.get((*cid.first()? >> 5) as usize)?.as_ref()?;
+        config.server_id(cid).ok()
+    }
+
+    /// Backend for a packet with destination connection ID `cid`
+    pub fn route(&self, cid: &[u8]) -> Route {
+        if let Some(&backend) = self.server_id(cid).and_then(|server_id| self.servers.get(&server_id)) {
+            return Route::Server(backend);
+        }
+        Route::Hashed(hash_route(cid, self.backends))
+    }
+
+    /// Map entries for plaintext configs, for an eBPF/XDP program that
+    /// compares the first octet and the server ID at offset 1
+    pub fn xdp_entries(&self) -> Vec<XdpEntry> {
+        let mut entries = Vec::new();
+        for config in self.configs.iter().flatten().filter(|config| !config.is_encrypted()) {
+            for (server_id, &backend) in &self.servers {
+                if server_id.len() == config.server_id_len {
+                    entries.push(XdpEntry {
+                        first_octet: config.first_octet(),
+                        server_id: server_id.clone(),
+                        backend: backend as u32,
+                    });
+                }
+            }
+        }
+        entries.sort_by(|a, b| (a.first_octet, &a.server_id).cmp(&(b.first_octet, &b.server_id)));
+        entries
+    }
+}
+
+/// Hash-based backend (rendezvous hashing, so adding a backend moves few
+/// connection IDs)
+pub fn hash_route(cid: &[u8], backends: usize) -> usize {
+    (0..backends.max(1))
+        .max_by_key(|&backend| {
+            let mut info = (backend as u32).to_be_bytes().to_vec();
+            info.extend_from_slice(cid);
+            let score = utils::kdf::kdf_shake256(b"aegis-q-cid-hash", &[], &info, 8);
+            u64::from_be_bytes(score.try_into().expect("8-byte score"))
+        })
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypted_cid_routes_to_server() {
+        let config = CidConfig::encrypted(1, 2, 8, b"routing-key-0123456789abcdef").unwrap();
+        let generator = CidGenerator::new(config.clone(), &[0x00, 0x2a]).unwrap();
+        let mut router = CidRouter::new(4);
+        router.add_config(config);
+        router.set_server(&[0x00, 0x2a], 3).unwrap();
+
+        let first = generator.generate();
+        let second = generator.generate();
+        assert_eq!(first.len(), 11);
+        assert_eq!(first[0], (1 << 5) | 10);
+        // The server ID is not visible in the connection ID
+        assert_ne!(&first[1..3], &[0x00, 0x2a]);
+        assert_ne!(first, second);
+        assert_eq!(router.server_id(&first).unwrap(), vec![0x00, 0x2a]);
+        assert_eq!(router.route(&first), Route::Server(3));
+        assert_eq!(router.route(&second), Route::Server(3));
+
+        // Another key recovers some other server ID
+        let other = CidConfig::encrypted(1, 2, 8, b"other-routing-key").unwrap();
+        assert_ne!(other.server_id(&first).unwrap(), vec![0x00, 0x2a]);
+    }
+
+    #[test]
+    fn test_unknown_cids_hash_consistently() {
+        let mut router = CidRouter::new(5);
+        router.add_config(CidConfig::plaintext(0, 1, 4).unwrap());
+        let client_cid = [0xe7, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77];
+        let route = router.route(&client_cid);
+        assert!(matches!(route, Route::Hashed(backend) if backend < 5));
+        assert_eq!(router.route(&client_cid), route);
+        assert!(matches!(router.route(&[]), Route::Hashed(_)));
+
+        // Adding a backend moves only the IDs that now hash to it
+        let grown = CidRouter::new(6);
+        let moved = (0u8..=200)
+            .filter(|&i| {
+                let cid = [i, i ^ 0x5a, 0x01, 0x02];
+                let before = router.route(&cid).backend();
+                let after = grown.route(&cid).backend();
+                before != after && after != 5
+            })
+            .count();
+        assert_eq!(moved, 0);
+    }
+
+    #[test]
+    fn test_plaintext_xdp_entries() {
+        let config = CidConfig::plaintext(2, 2, 6).unwrap();
+        assert!(CidConfig::plaintext(7, 2, 6).is_err());
+        assert!(CidConfig::encrypted(1, 2, 2, b"key").is_err());
+        assert!(CidConfig::plaintext(0, 10, 10).is_err());
+
+        let cid = CidGenerator::new(config.clone(), b"s1").unwrap().generate();
+        assert_eq!(&cid[1..3], b"s1");
+
+        let mut router = CidRouter::new(2);
+        router.add_config(config);
+        router.set_server(b"s1", 1).unwrap();
+        router.set_server(b"s0", 0).unwrap();
+        assert!(router.set_server(b"s2", 2).is_err());
+        assert_eq!(router.route(&cid), Route::Server(1));
+
+        let entries = router.xdp_entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1], XdpEntry { first_octet: (2 << 5) | 8, server_id: b"s1".to_vec(), backend: 1 });
+        assert!(router.remove_config(2).is_some());
+        assert!(router.xdp_entries().is_empty());
+    }
+}
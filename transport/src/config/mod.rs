@@ -0,0 +1,576 @@
+//! Server Configuration
+//!
+//! Typed TOML schema for the transport server and CLI: listeners, key
+//! material, parameter profile, limits, obfuscation, licensing policy,
+//! session ticket keys and metrics export.
+//! Unknown fields are rejected, and validation errors name the offending
+//! field (`limits.max_sessions: must be greater than zero`).
+//!
+//! Any scalar setting can be overridden from the environment as
+//! `AEGISQ_<SECTION>_<KEY>`, e.g. `AEGISQ_LIMITS_MAX_SESSIONS=500`.
+//!
+//! `ConfigWatcher` reloads the file when it changes. Key material,
+//! listeners and the service user are only read at startup: a reload
+//! applies everything else and reports which changed fields need a restart.
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+use std::fmt;
+
+use aegis_q_core::{SecurityLevel, SecurityPolicy};
+use serde::{Serialize, Deserialize};
+
+use crate::tickets::TicketPolicy;
+
+/// Prefix of environment overrides
+pub const ENV_PREFIX: &str = "AEGISQ_";
+
+/// Largest obfuscation padding
+pub const MAX_OBFUSCATION_PADDING: usize = 1024;
+
+/// Configuration error
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// File could not be read
+    Io(String),
+    /// Not valid TOML or does not match the schema
+    Parse(String),
+    /// Field value rejected by validation
+    Invalid { field: String, message: String },
+}
+
+impl ConfigError {
+    fn invalid(field: impl Into<String>, message: impl Into<String>) -> Self {
+        ConfigError::Invalid {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(reason) => write!(f, "cannot read config: {}", reason),
+            ConfigError::Parse(reason) => write!(f, "invalid config: {}", reason),
+            ConfigError::Invalid { field, message } => write!(f, "{}: {}", field, message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Listener protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListenerProtocol {
+    Vpn,
+    Quic,
+    Tls,
+}
+
+/// Listening socket
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ListenerConfig {
+    pub bind: SocketAddr,
+    pub protocol: ListenerProtocol,
+}
+
+/// Cipher parameter profile
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamsProfile {
+    L128,
+    L192,
+    #[default]
+    L256,
+}
+
+impl ParamsProfile {
+    /// Core security level
+    pub fn security_level(self) -> SecurityLevel {
+        match self {
+            ParamsProfile::L128 => SecurityLevel::L128,
+            ParamsProfile::L192 => SecurityLevel::L192,
+            ParamsProfile::L256 => SecurityLevel::L256,
+        }
+    }
+
+    /// Policy rejecting suites and profiles below this one
+    pub fn security_policy(self) -> SecurityPolicy {
+        SecurityPolicy::new(self.security_level())
+    }
+}
+
+/// General server settings
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct ServerSection {
+    pub params_profile: ParamsProfile,
+    pub log_level: LogLevel,
+    /// Unprivileged user to switch to after binding listeners
+    pub user: Option<String>,
+    /// Group to switch to (defaults to the user's primary group)
+    pub group: Option<String>,
+}
+
+/// Log verbosity
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+/// Key material locations (startup only)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeysSection {
+    pub identity_key: PathBuf,
+    #[serde(default)]
+    pub psk_database: Option<PathBuf>,
+}
+
+/// Resource limits
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct LimitsSection {
+    pub max_sessions: usize,
+    pub max_streams_per_session: usize,
+    pub handshake_timeout_ms: u64,
+    pub idle_timeout_ms: u64,
+    pub max_auth_failures: u32,
+}
+
+impl Default for LimitsSection {
+    fn default() -> Self {
+        Self {
+            max_sessions: 10_000,
+            max_streams_per_session: 256,
+            handshake_timeout_ms: 5_000,
+            idle_timeout_ms: 300_000,
+            max_auth_failures: 5,
+        }
+    }
+}
+
+/// Traffic shape obfuscation
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct ObfuscationSection {
+    pub enabled: bool,
+    /// Random padding added per packet, up to this many bytes
+    pub max_padding: usize,
+}
+
+/// License enforcement
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct LicensingSection {
+    pub enabled: bool,
+    /// Hex-encoded license verifying key
+    pub verifying_key: String,
+    pub stream_feature: String,
+    pub default_max_tunnels: usize,
+}
+
+impl Default for LicensingSection {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            verifying_key: String::new(),
+            stream_feature: "streams".to_string(),
+            default_max_tunnels: 1,
+        }
+    }
+}
+
+/// Session ticket keys (see `tickets`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct TicketsSection {
+    pub enabled: bool,
+    /// Ticket keys kept for opening tickets, the newest issues them
+    pub keys: usize,
+    /// Age at which the active key is replaced
+    pub rotation_interval_ms: u64,
+    pub ticket_lifetime_ms: u64,
+}
+
+impl Default for TicketsSection {
+    fn default() -> Self {
+        let policy = TicketPolicy::default();
+        Self {
+            enabled: false,
+            keys: policy.max_keys,
+            rotation_interval_ms: policy.rotation_interval_ms,
+            ticket_lifetime_ms: policy.ticket_lifetime_ms,
+        }
+    }
+}
+
+/// Metrics export
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct MetricsSection {
+    pub enabled: bool,
+    /// Address of the HTTP `/metrics` endpoint
+    pub bind: Option<SocketAddr>,
+    /// File for node_exporter's textfile collector
+    pub textfile: Option<PathBuf>,
+}
+
+/// Complete server configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    #[serde(default)]
+    pub server: ServerSection,
+    pub listeners: Vec<ListenerConfig>,
+    pub keys: KeysSection,
+    #[serde(default)]
+    pub limits: LimitsSection,
+    #[serde(default)]
+    pub obfuscation: ObfuscationSection,
+    #[serde(default)]
+    pub licensing: LicensingSection,
+    #[serde(default)]
+    pub tickets: TicketsSection,
+    #[serde(default)]
+    pub metrics: MetricsSection,
+}
+
+impl ServerConfig {
+    /// Parse and validate TOML text (no environment overrides)
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        Self::from_toml_with_env(text, std::iter::empty())
+    }
+
+    /// Parse TOML text, apply `AEGISQ_*` overrides from `env`, and validate
+    pub fn from_toml_with_env<I>(text: &str, env: I) -> Result<Self, ConfigError>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut value: toml::Table = toml::from_str(text).map_err(|e| ConfigError::Parse(e.to_string()))?;
+        apply_env_overrides(&mut value, env)?;
+        let config: Self = toml::Value::Table(value)
+            .try_into()
+            .map_err(|e: toml::de::Error| ConfigError::Parse(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Load a file with overrides from the process environment
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let text = std::fs::read_to_string(path).map_err(|e| ConfigError::Io(e.to_string()))?;
+        Self::from_toml_with_env(&text, std::env::vars())
+    }
+
+    /// Serialize back to TOML
+    pub fn to_toml(&self) -> Result<String, ConfigError> {
+        toml::to_string_pretty(self).map_err(|e| ConfigError::Parse(e.to_string()))
+    }
+
+    /// Check field values
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.listeners.is_empty() {
+            return Err(ConfigError::invalid("listeners", "at least one listener is required"));
+        }
+        for (i, listener) in self.listeners.iter().enumerate() {
+            if self.listeners[..i].iter().any(|l| l.bind == listener.bind) {
+                return Err(ConfigError::invalid(format!("listeners[{}].bind", i), "duplicate bind address"));
+            }
+        }
+        if self.server.group.is_some() && self.server.user.is_none() {
+            return Err(ConfigError::invalid("server.group", "requires server.user"));
+        }
+        if self.keys.identity_key.as_os_str().is_empty() {
+            return Err(ConfigError::invalid("keys.identity_key", "must not be empty"));
+        }
+
+        let limits = &self.limits;
+        for (field, value) in [
+            ("limits.max_sessions", limits.max_sessions as u64),
+            ("limits.max_streams_per_session", limits.max_streams_per_session as u64),
+            ("limits.handshake_timeout_ms", limits.handshake_timeout_ms),
+            ("limits.idle_timeout_ms", limits.idle_timeout_ms),
+            ("limits.max_auth_failures", limits.max_auth_failures as u64),
+        ] {
+            if value == 0 {
+                return Err(ConfigError::invalid(field, "must be greater than zero"));
+            }
+        }
+
+        if self.obfuscation.enabled && self.obfuscation.max_padding > MAX_OBFUSCATION_PADDING {
+            return Err(ConfigError::invalid(
+                "obfuscation.max_padding",
+                format!("must be at most {}", MAX_OBFUSCATION_PADDING),
+            ));
+        }
+
+        if self.tickets.enabled {
+            let tickets = &self.tickets;
+            for (field, value) in [
+                ("tickets.keys", tickets.keys as u64),
+                ("tickets.rotation_interval_ms", tickets.rotation_interval_ms),
+                ("tickets.ticket_lifetime_ms", tickets.ticket_lifetime_ms),
+            ] {
+                if value == 0 {
+                    return Err(ConfigError::invalid(field, "must be greater than zero"));
+                }
+            }
+            if TicketPolicy::from(tickets).validate().is_err() {
+                return Err(ConfigError::invalid(
+                    "tickets.ticket_lifetime_ms",
+                    "must be at most (keys - 1) * rotation_interval_ms",
+                ));
+            }
+        }
+
+        if self.metrics.enabled && self.metrics.bind.is_none() && self.metrics.textfile.is_none() {
+            return Err(ConfigError::invalid("metrics", "enabled but neither bind nor textfile is set"));
+        }
+
+        let licensing = &self.licensing;
+        if licensing.enabled {
+            let key = &licensing.verifying_key;
+            if key.is_empty() || !key.len().is_multiple_of(2) || !key.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(ConfigError::invalid("licensing.verifying_key", "must be a non-empty hex string"));
+            }
+            if licensing.default_max_tunnels == 0 {
+                return Err(ConfigError::invalid("licensing.default_max_tunnels", "must be greater than zero"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Fields that differ from `other` and cannot change without a restart
+    pub fn restart_required(&self, other: &ServerConfig) -> Vec<&'static str> {
+        let mut fields = Vec::new();
+        if self.keys != other.keys {
+            fields.push("keys");
+        }
+        if self.listeners != other.listeners {
+            fields.push("listeners");
+        }
+        if (&self.server.user, &self.server.group) != (&other.server.user, &other.server.group) {
+            fields.push("server.user");
+        }
+        fields
+    }
+}
+
+/// Apply `AEGISQ_<SECTION>_<KEY>` variables to the parsed table
+fn apply_env_overrides<I>(table: &mut toml::Table, env: I) -> Result<(), ConfigError>
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    const SECTIONS: [&str; 7] = ["server", "keys", "limits", "obfuscation", "licensing", "tickets", "metrics"];
+
+    for (name, raw) in env {
+        let Some(rest) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let rest = rest.to_ascii_lowercase();
+        let Some((section, key)) = SECTIONS
+            .iter()
+            .find_map(|s| rest.strip_prefix(s).and_then(|k| k.strip_prefix('_')).map(|k| (*s, k)))
+        else {
+            return Err(ConfigError::invalid(name, "unknown configuration section"));
+        };
+
+        // Typed where possible (numbers, booleans), string otherwise
+        let value = toml::from_str::<toml::Table>(&format!("v = {}", raw))
+            .ok()
+            .and_then(|mut t| t.remove("v"))
+            .filter(|v| !v.is_table() && !v.is_array())
+            .unwrap_or_else(|| toml::Value::String(raw.clone()));
+
+        let entry = table
+            .entry(section.to_string())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        let toml::Value::Table(section_table) = entry else {
+            return Err(ConfigError::invalid(section, "must be a table"));
+        };
+        section_table.insert(key.to_string(), value);
+    }
+    Ok(())
+}
+
+/// Outcome of a reload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReloadOutcome {
+    /// Whether the file had changed and new settings were applied
+    pub applied: bool,
+    /// Changed fields that were ignored until restart
+    pub restart_required: Vec<&'static str>,
+}
+
+/// Hot-reloading configuration handle
+#[derive(Debug, Clone)]
+pub struct ConfigWatcher {
+    path: PathBuf,
+    current: Arc<RwLock<Arc<ServerConfig>>>,
+    modified: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    /// Load `path` and start watching it
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref().to_path_buf();
+        let config = ServerConfig::load(&path)?;
+        Ok(Self {
+            modified: modified_time(&path),
+            path,
+            current: Arc::new(RwLock::new(Arc::new(config))),
+        })
+    }
+
+    /// Active configuration
+    pub fn current(&self) -> Arc<ServerConfig> {
+        self.current.read().expect("config lock poisoned").clone()
+    }
+
+    /// Reload if the file changed since the last load
+    pub fn poll(&mut self) -> Result<ReloadOutcome, ConfigError> {
+        let modified = modified_time(&self.path);
+        if modified.is_some() && modified == self.modified {
+            return Ok(ReloadOutcome {
+                applied: false,
+                restart_required: Vec::new(),
+            });
+        }
+        self.reload()
+    }
+
+    /// Re-read the file; an invalid file leaves the active configuration untouched
+    pub fn reload(&mut self) -> Result<ReloadOutcome, ConfigError> {
+        let mut next = ServerConfig::load(&self.path)?;
+        self.modified = modified_time(&self.path);
+
+        let current = self.current();
+        let restart_required = next.restart_required(&current);
+        next.keys = current.keys.clone();
+        next.listeners = current.listeners.clone();
+        next.server.user = current.server.user.clone();
+        next.server.group = current.server.group.clone();
+
+        *self.current.write().expect("config lock poisoned") = Arc::new(next);
+        Ok(ReloadOutcome {
+            applied: true,
+            restart_required,
+        })
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = r#"
+[server]
+params_profile = "l192"
+
+[[listeners]]
+bind = "0.0.0.0:443"
+protocol = "quic"
+
+[[listeners]]
+bind = "[::]:443"
+protocol = "vpn"
+
+[keys]
+identity_key = "/etc/aegisq/identity.key"
+
+[limits]
+max_sessions = 2000
+
+[licensing]
+enabled = true
+verifying_key = "a1b2c3"
+"#;
+
+    #[test]
+    fn test_parse_defaults_and_roundtrip() {
+        let config = ServerConfig::from_toml(EXAMPLE).unwrap();
+        assert_eq!(config.server.params_profile.security_level(), SecurityLevel::L192);
+        assert_eq!(config.server.params_profile.security_policy().min_level(), SecurityLevel::L192);
+        assert_eq!(config.listeners[1].protocol, ListenerProtocol::Vpn);
+        assert_eq!(config.limits.max_sessions, 2000);
+        assert_eq!(config.limits.max_streams_per_session, 256);
+        assert!(!config.obfuscation.enabled);
+        assert_eq!(ServerConfig::from_toml(&config.to_toml().unwrap()).unwrap(), config);
+    }
+
+    #[test]
+    fn test_errors_name_fields() {
+        let err = ServerConfig::from_toml(&EXAMPLE.replace("max_sessions = 2000", "max_sessions = 0")).unwrap_err();
+        assert_eq!(err.to_string(), "limits.max_sessions: must be greater than zero");
+
+        let err = ServerConfig::from_toml(&EXAMPLE.replace("a1b2c3", "not-hex")).unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid { ref field, .. } if field == "licensing.verifying_key"));
+
+        let err = ServerConfig::from_toml(&EXAMPLE.replace("[::]:443", "0.0.0.0:443")).unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid { ref field, .. } if field == "listeners[1].bind"));
+
+        let tickets = format!("{}\n[tickets]\nenabled = true\nkeys = 2\nrotation_interval_ms = 1000\nticket_lifetime_ms = 5000\n", EXAMPLE);
+        let err = ServerConfig::from_toml(&tickets).unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid { ref field, .. } if field == "tickets.ticket_lifetime_ms"));
+        let config = ServerConfig::from_toml(&tickets.replace("keys = 2", "keys = 6")).unwrap();
+        assert_eq!(TicketPolicy::from(&config.tickets).max_keys, 6);
+
+        let err = ServerConfig::from_toml(&EXAMPLE.replace("max_sessions", "max_sesions")).unwrap_err();
+        assert!(matches!(err, ConfigError::Parse(ref msg) if msg.contains("max_sesions")));
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let env = vec![
+            ("AEGISQ_LIMITS_MAX_SESSIONS".to_string(), "50".to_string()),
+            ("AEGISQ_OBFUSCATION_ENABLED".to_string(), "true".to_string()),
+            ("AEGISQ_SERVER_LOG_LEVEL".to_string(), "debug".to_string()),
+            ("PATH".to_string(), "/usr/bin".to_string()),
+        ];
+        let config = ServerConfig::from_toml_with_env(EXAMPLE, env).unwrap();
+        assert_eq!(config.limits.max_sessions, 50);
+        assert!(config.obfuscation.enabled);
+        assert_eq!(config.server.log_level, LogLevel::Debug);
+
+        let bad = vec![("AEGISQ_NOPE_X".to_string(), "1".to_string())];
+        assert!(ServerConfig::from_toml_with_env(EXAMPLE, bad).is_err());
+    }
+
+    #[test]
+    fn test_hot_reload_keeps_keys() {
+        let path = std::env::temp_dir().join(format!("aegisq-config-{}.toml", std::process::id()));
+        std::fs::write(&path, EXAMPLE).unwrap();
+        let mut watcher = ConfigWatcher::open(&path).unwrap();
+
+        let edited = EXAMPLE
+            .replace("max_sessions = 2000", "max_sessions = 3000")
+            .replace("identity.key", "rotated.key");
+        std::fs::write(&path, edited).unwrap();
+        let outcome = watcher.reload().unwrap();
+        assert!(outcome.applied);
+        assert_eq!(outcome.restart_required, vec!["keys"]);
+        assert_eq!(watcher.current().limits.max_sessions, 3000);
+        assert_eq!(watcher.current().keys.identity_key, PathBuf::from("/etc/aegisq/identity.key"));
+
+        // Broken edit leaves the running config in place
+        std::fs::write(&path, "listeners = 7").unwrap();
+        assert!(watcher.reload().is_err());
+        assert_eq!(watcher.current().limits.max_sessions, 3000);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
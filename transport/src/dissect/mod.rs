@@ -0,0 +1,366 @@
+//! Frame Dissector
+//!
+//! Breaks a raw frame into its fields for interop debugging, printed side
+//! by side with the bytes they came from:
+//! `offset  hex  field  value`.
+//!
+//! Header fields are always decoded. Handshake payloads are split into
+//! extension TLVs and `Ack` payloads into their counters. `Data` payloads
+//! are encrypted; given exported session secrets (`capture::SessionSecrets`)
+//! the dissector decrypts them and classifies the plaintext (IPv4/IPv6 with
+//! the transport protocol, or opaque bytes). Malformed input never fails:
+//! the breakdown stops at the first bad field with a note saying why.
+
+use std::fmt;
+
+use utils::wire::{FrameHeaderWire, HandshakeExtensionWire, Wire};
+
+use crate::auth::AUTH_EXTENSION_TYPE;
+#[cfg(feature = "capture")]
+use crate::capture::{hex_encode, Direction, SessionSecrets};
+use crate::ecn::AckFrame;
+use crate::framing::{Frame, FrameHeader, FrameType, FRAME_HEADER_SIZE};
+use crate::session::SessionId;
+#[cfg(feature = "capture")]
+use crate::vpn::VpnSession;
+
+/// Bytes of a field shown before eliding
+const HEX_COLUMN_BYTES: usize = 8;
+
+/// One decoded field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    /// Offset in the frame (in the plaintext for decrypted fields)
+    pub offset: usize,
+    pub bytes: Vec<u8>,
+    pub name: String,
+    pub value: String,
+}
+
+/// Structured breakdown of one frame
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dissection {
+    pub fields: Vec<Field>,
+    /// Session whose secrets decrypted the payload
+    pub session_id: Option<SessionId>,
+    /// Decrypted payload fields
+    pub plaintext: Vec<Field>,
+    /// Problems found while dissecting
+    pub notes: Vec<String>,
+}
+
+impl Dissection {
+    fn field(&mut self, offset: usize, bytes: &[u8], name: impl Into<String>, value: impl Into<String>) {
+        self.fields.push(Field {
+            offset,
+            bytes: bytes.to_vec(),
+            name: name.into(),
+            value: value.into(),
+        });
+    }
+}
+
+impl fmt::Display for Dissection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for field in &self.fields {
+            write_field(f, field)?;
+        }
+        if let Some(session_id) = &self.session_id {
+            writeln!(f, "-- decrypted with session {}", session_id.to_hex())?;
+            for field in &self.plaintext {
+                write_field(f, field)?;
+            }
+        }
+        for note in &self.notes {
+            writeln!(f, "!! {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_field(f: &mut fmt::Formatter<'_>, field: &Field) -> fmt::Result {
+    let shown = &field.bytes[..field.bytes.len().min(HEX_COLUMN_BYTES)];
+    let mut hex: Vec<String> = shown.iter().map(|b| format!("{:02x}", b)).collect();
+    if field.bytes.len() > HEX_COLUMN_BYTES {
+        hex.push("..".to_string());
+    }
+    writeln!(f, "{:04x}  {:<26}  {:<16} {}", field.offset, hex.join(" "), field.name, field.value)
+}
+
+/// Name of a frame type byte (`FrameType::from` maps unknown values to `Data`)
+fn frame_type_name(value: u8) -> Option<&'static str> {
+    Some(match value {
+        0x01 => "Handshake",
+        0x02 => "Data",
+        0x03 => "Close",
+        0x04 => "Heartbeat",
+        0x05 => "Probe",
+        0x06 => "Ack",
+        _ => return None,
+    })
+}
+
+/// Name of a handshake extension type
+fn extension_name(extension_type: u16) -> &'static str {
+    match extension_type {
+        AUTH_EXTENSION_TYPE => "auth",
+        // entitlement::LICENSE_EXTENSION_TYPE, named even without the feature
+        0x4c51 => "license",
+        _ => "unknown",
+    }
+}
+
+/// Dissect a frame without decrypting it
+pub fn dissect(frame: &[u8]) -> Dissection {
+    let mut dissection = Dissection::default();
+    dissect_into(&mut dissection, frame);
+    dissection
+}
+
+/// Dissect a frame and decrypt its payload with exported session secrets
+/// (feature `capture`)
+///
+/// The frame carries no session ID, so each session in `secrets` is tried
+/// in turn; `direction` is relative to the endpoint that exported them.
+#[cfg(feature = "capture")]
+pub fn dissect_with_secrets(frame: &[u8], secrets: &[SessionSecrets], direction: Direction) -> Dissection {
+    let mut dissection = Dissection::default();
+    let Some(header) = dissect_into(&mut dissection, frame) else {
+        return dissection;
+    };
+    if header.frame_type != FrameType::Data {
+        return dissection;
+    }
+
+    for session in secrets {
+        let (other, key) = match direction {
+            Direction::Outbound => (&session.recv_key, &session.send_key),
+            Direction::Inbound => (&session.send_key, &session.recv_key),
+        };
+        let mut decoder = VpnSession::from_keys(other, key, &session.nonce);
+        decoder.resync_recv(header.sequence);
+        if let Ok(plaintext) = decoder.decrypt_data(frame) {
+            dissection.session_id = Some(session.session_id);
+            dissection.plaintext = classify(&plaintext);
+            return dissection;
+        }
+    }
+    dissection.notes.push("no session secrets authenticate this frame".to_string());
+    dissection
+}
+
+/// Decode the header and any cleartext payload; `None` if the header is bad
+fn dissect_into(dissection: &mut Dissection, frame: &[u8]) -> Option<FrameHeader> {
+    let Ok((wire, _)) = FrameHeaderWire::from_wire_prefix(frame) else {
+        dissection.notes.push(format!("frame too short: {} bytes, header is {}", frame.len(), FRAME_HEADER_SIZE));
+        return None;
+    };
+    let type_name = frame_type_name(wire.frame_type).map_or_else(|| "unknown".to_string(), str::to_string);
+    dissection.field(0, &frame[0..1], "frame_type", format!("0x{:02x} {}", wire.frame_type, type_name));
+    dissection.field(1, &frame[1..9], "sequence", wire.sequence.to_string());
+    dissection.field(9, &frame[9..13], "payload_len", wire.payload_len.to_string());
+    let reserved = if wire.reserved == [0u8; 3] { "zero" } else { "NONZERO" };
+    dissection.field(13, &frame[13..16], "reserved", reserved);
+    if frame_type_name(wire.frame_type).is_none() {
+        dissection.notes.push(format!("unknown frame type 0x{:02x}; receivers treat it as Data", wire.frame_type));
+    }
+
+    let header = match FrameHeader::decode(frame) {
+        Ok(header) => header,
+        Err(_) => {
+            let available = frame.len() - FRAME_HEADER_SIZE;
+            dissection.field(FRAME_HEADER_SIZE, &frame[FRAME_HEADER_SIZE..], "payload", format!("{} bytes", available));
+            dissection.notes.push(format!("truncated: payload_len {} but {} bytes follow", wire.payload_len, available));
+            return None;
+        }
+    };
+    let range = header.payload_range();
+    let payload = &frame[range.clone()];
+    match header.frame_type {
+        _ if frame_type_name(wire.frame_type).is_none() => {
+            dissection.field(range.start, payload, "payload", format!("{} bytes", payload.len()));
+        }
+        FrameType::Handshake => dissect_extensions(dissection, range.start, payload),
+        FrameType::Ack => dissect_ack(dissection, range.start, payload, header.sequence),
+        FrameType::Data => {
+            dissection.field(range.start, payload, "payload", format!("{} bytes, encrypted", payload.len()));
+        }
+        FrameType::Probe => {
+            dissection.field(range.start, payload, "padding", format!("{} bytes", payload.len()));
+        }
+        FrameType::Close | FrameType::Heartbeat => {
+            dissection.field(range.start, payload, "payload", format!("{} bytes", payload.len()));
+        }
+    }
+    if frame.len() > range.end {
+        dissection.field(range.end, &frame[range.end..], "trailing", format!("{} bytes after the frame", frame.len() - range.end));
+    }
+    Some(header)
+}
+
+fn dissect_extensions(dissection: &mut Dissection, mut offset: usize, mut rest: &[u8]) {
+    let mut index = 0;
+    while !rest.is_empty() {
+        let Ok((extension, remaining)) = HandshakeExtensionWire::from_wire_prefix(rest) else {
+            dissection.field(offset, rest, "undecoded", format!("{} bytes", rest.len()));
+            dissection.notes.push(format!("extension {} is malformed", index));
+            return;
+        };
+        let name = format!("ext[{}]", index);
+        dissection.field(offset, &rest[..2], format!("{}.type", name), format!("0x{:04x} {}", extension.extension_type, extension_name(extension.extension_type)));
+        dissection.field(offset + 2, &rest[2..4], format!("{}.length", name), extension.body.len().to_string());
+        dissection.field(offset + 4, &extension.body, format!("{}.body", name), format!("{} bytes, encrypted", extension.body.len()));
+        offset += rest.len() - remaining.len();
+        rest = remaining;
+        index += 1;
+    }
+}
+
+fn dissect_ack(dissection: &mut Dissection, offset: usize, payload: &[u8], sequence: u64) {
+    match AckFrame::from_frame(&Frame::new(FrameType::Ack, payload.to_vec(), sequence)) {
+        Ok(ack) => {
+            dissection.field(offset, &payload[..8], "largest_acked", ack.largest_acked.to_string());
+            let value = ack.ecn.map_or_else(
+                || "none".to_string(),
+                |ecn| format!("ect0={} ect1={} ce={}", ecn.ect0, ecn.ect1, ecn.ce),
+            );
+            dissection.field(offset + 8, &payload[8..], "ecn", value);
+        }
+        Err(_) => {
+            dissection.field(offset, payload, "payload", format!("{} bytes", payload.len()));
+            dissection.notes.push("malformed ACK payload".to_string());
+        }
+    }
+}
+
+/// Fields of a decrypted payload, by what it looks like
+#[cfg(feature = "capture")]
+fn classify(plaintext: &[u8]) -> Vec<Field> {
+    let field = |offset: usize, bytes: &[u8], name: &str, value: String| Field {
+        offset,
+        bytes: bytes.to_vec(),
+        name: name.to_string(),
+        value,
+    };
+    let version = plaintext.first().map(|b| b >> 4);
+    match version {
+        Some(4) if plaintext.len() >= 20 => {
+            let header_len = usize::from(plaintext[0] & 0x0f) * 4;
+            vec![
+                field(0, &plaintext[..1], "ip.version", format!("IPv4, header {} bytes", header_len)),
+                field(2, &plaintext[2..4], "ip.total_len", u16::from_be_bytes([plaintext[2], plaintext[3]]).to_string()),
+                field(9, &plaintext[9..10], "ip.protocol", protocol_name(plaintext[9])),
+                field(12, &plaintext[12..16], "ip.src", format!("{}", std::net::Ipv4Addr::from([plaintext[12], plaintext[13], plaintext[14], plaintext[15]]))),
+                field(16, &plaintext[16..20], "ip.dst", format!("{}", std::net::Ipv4Addr::from([plaintext[16], plaintext[17], plaintext[18], plaintext[19]]))),
+            ]
+        }
+        Some(6) if plaintext.len() >= 40 => {
+            let address = |bytes: &[u8]| {
+                let octets: [u8; 16] = bytes.try_into().expect("16-byte slice");
+                format!("{}", std::net::Ipv6Addr::from(octets))
+            };
+            vec![
+                field(0, &plaintext[..1], "ip.version", "IPv6".to_string()),
+                field(4, &plaintext[4..6], "ip.payload_len", u16::from_be_bytes([plaintext[4], plaintext[5]]).to_string()),
+                field(6, &plaintext[6..7], "ip.next_header", protocol_name(plaintext[6])),
+                field(8, &plaintext[8..24], "ip.src", address(&plaintext[8..24])),
+                field(24, &plaintext[24..40], "ip.dst", address(&plaintext[24..40])),
+            ]
+        }
+        _ => {
+            let preview = String::from_utf8(plaintext.to_vec())
+                .ok()
+                .filter(|text| !text.chars().any(char::is_control))
+                .map_or_else(|| hex_encode(&plaintext[..plaintext.len().min(32)]), |text| format!("{:?}", text));
+            vec![field(0, plaintext, "opaque", format!("{} bytes: {}", plaintext.len(), preview))]
+        }
+    }
+}
+
+#[cfg(feature = "capture")]
+fn protocol_name(protocol: u8) -> String {
+    let name = match protocol {
+        1 => "ICMP",
+        6 => "TCP",
+        17 => "UDP",
+        58 => "ICMPv6",
+        _ => "other",
+    };
+    format!("{} {}", protocol, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecn::EcnCounts;
+    use crate::framing::Frame;
+
+    fn value<'a>(fields: &'a [Field], name: &str) -> &'a str {
+        &fields.iter().find(|f| f.name == name).unwrap().value
+    }
+
+    #[test]
+    fn test_dissect_header_and_cleartext_payloads() {
+        let ack = AckFrame { largest_acked: 41, ecn: Some(EcnCounts { ect0: 3, ect1: 0, ce: 1 }) };
+        let dissection = dissect(&ack.to_frame(9).encode());
+        assert_eq!(value(&dissection.fields, "frame_type"), "0x06 Ack");
+        assert_eq!(value(&dissection.fields, "sequence"), "9");
+        assert_eq!(value(&dissection.fields, "largest_acked"), "41");
+        assert_eq!(value(&dissection.fields, "ecn"), "ect0=3 ect1=0 ce=1");
+        assert!(dissection.notes.is_empty());
+
+        let mut payload = HandshakeExtensionWire { extension_type: AUTH_EXTENSION_TYPE, body: vec![7; 5] }.to_wire().unwrap();
+        payload.extend(HandshakeExtensionWire { extension_type: 0x1234, body: vec![] }.to_wire().unwrap());
+        let dissection = dissect(&Frame::new(FrameType::Handshake, payload, 0).encode());
+        assert_eq!(value(&dissection.fields, "ext[0].type"), "0x4151 auth");
+        assert_eq!(value(&dissection.fields, "ext[0].length"), "5");
+        assert_eq!(value(&dissection.fields, "ext[1].type"), "0x1234 unknown");
+        let second = dissection.fields.iter().find(|f| f.name == "ext[1].type").unwrap();
+        assert_eq!(second.offset, FRAME_HEADER_SIZE + 9);
+    }
+
+    #[test]
+    fn test_dissect_malformed_frames() {
+        assert_eq!(dissect(&[0x02; 5]).fields, vec![]);
+        assert_eq!(dissect(&[0x02; 5]).notes.len(), 1);
+
+        let mut frame = Frame::new(FrameType::Data, vec![1, 2, 3], 0).encode();
+        frame.pop();
+        assert!(dissect(&frame).notes[0].starts_with("truncated"));
+
+        frame[0] = 0x7f;
+        frame.extend([3, 0xee]);
+        let dissection = dissect(&frame);
+        assert_eq!(value(&dissection.fields, "frame_type"), "0x7f unknown");
+        assert_eq!(value(&dissection.fields, "trailing"), "1 bytes after the frame");
+        assert!(dissection.notes[0].contains("unknown frame type"));
+    }
+
+    #[test]
+    #[cfg(feature = "capture")]
+    fn test_dissect_with_secrets() {
+        let nonce = b"dissect-nonce-01";
+        let mut client = VpnSession::from_keys(b"key-c2s", b"key-s2c", nonce);
+        let server = VpnSession::from_keys(b"key-s2c", b"key-c2s", nonce);
+        let unrelated = VpnSession::from_keys(b"key-a", b"key-b", nonce).export_secrets();
+        let secrets = [unrelated, server.export_secrets()];
+
+        let mut packet = vec![0u8; 28];
+        packet[0] = 0x45;
+        packet[9] = 17;
+        packet[12..16].copy_from_slice(&[10, 0, 0, 1]);
+        packet[16..20].copy_from_slice(&[10, 0, 0, 2]);
+        client.encrypt_data(b"skipped");
+        let frame = client.encrypt_data(&packet);
+
+        let dissection = dissect_with_secrets(&frame, &secrets, Direction::Inbound);
+        assert_eq!(dissection.session_id, Some(server.session_id()));
+        assert_eq!(value(&dissection.plaintext, "ip.protocol"), "17 UDP");
+        assert_eq!(value(&dissection.plaintext, "ip.dst"), "10.0.0.2");
+        assert!(dissection.to_string().contains("-- decrypted with session"));
+
+        let dissection = dissect_with_secrets(&frame, &secrets, Direction::Outbound);
+        assert_eq!(dissection.session_id, None);
+        assert_eq!(dissection.notes, ["no session secrets authenticate this frame"]);
+    }
+}
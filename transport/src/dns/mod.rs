@@ -0,0 +1,412 @@
+//! DNS over Aegis-Q
+//!
+//! Resolver tunnel mode for the VPN client. `DnsProxy` takes the DNS
+//! queries the client intercepts (anything to port 53), answers from a
+//! local cache when it can, and otherwise forwards them through the tunnel
+//! to a resolver on the server side, so no plaintext DNS leaves the
+//! device. Forwarded queries get a fresh proxy-assigned ID; responses are
+//! matched back to the asking client, cached for their minimum TTL and
+//! returned with the client's original ID.
+//!
+//! Tunnel payloads are `DNS_TUNNEL_MARKER || dns message`, carried as
+//! ordinary VPN data frames; the server side unwraps them with
+//! `decode_tunnel` and wraps its resolver's answer with `encode_tunnel`.
+//! `DnsProxy` does no I/O and can be used standalone.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use aegis_q_core::AegisQError;
+use utils::rng::random_bytes;
+
+/// First byte of a tunnel payload carrying a DNS message
+pub const DNS_TUNNEL_MARKER: u8 = 0xd5;
+
+/// DNS port
+pub const DNS_PORT: u16 = 53;
+
+/// Cache lifetime for answers without records (e.g. NXDOMAIN)
+pub const NEGATIVE_TTL_SECS: u32 = 30;
+
+/// Cached entries never live longer than this
+pub const MAX_TTL_SECS: u32 = 86_400;
+
+/// Forwarded queries without an answer are dropped after this
+pub const QUERY_TIMEOUT_SECS: u64 = 5;
+
+/// Default cache capacity
+pub const DEFAULT_CACHE_ENTRIES: usize = 1024;
+
+const HEADER_SIZE: usize = 12;
+const TYPE_OPT: u16 = 41;
+
+/// Question section of a DNS message (name lowercased)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DnsQuestion {
+    pub name: String,
+    pub qtype: u16,
+    pub qclass: u16,
+}
+
+/// Parse the message ID and first question
+pub fn parse_question(message: &[u8]) -> Result<(u16, DnsQuestion), AegisQError> {
+    if message.len() < HEADER_SIZE {
+        return Err(AegisQError::InvalidLength("DNS message too short"));
+    }
+    if u16::from_be_bytes([message[4], message[5]]) == 0 {
+        return Err(AegisQError::Serialization("DNS message has no question"));
+    }
+    let (name, pos) = read_name(message, HEADER_SIZE)?;
+    let fixed = message.get(pos..pos + 4).ok_or(AegisQError::InvalidLength("Truncated DNS question"))?;
+    Ok((
+        u16::from_be_bytes([message[0], message[1]]),
+        DnsQuestion {
+            name: name.to_ascii_lowercase(),
+            qtype: u16::from_be_bytes([fixed[0], fixed[1]]),
+            qclass: u16::from_be_bytes([fixed[2], fixed[3]]),
+        },
+    ))
+}
+
+/// Wrap a DNS message for the tunnel
+pub fn encode_tunnel(message: &[u8]) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(message.len() + 1);
+    payload.push(DNS_TUNNEL_MARKER);
+    payload.extend_from_slice(message);
+    payload
+}
+
+/// DNS message inside a tunnel payload, if it carries one
+pub fn decode_tunnel(payload: &[u8]) -> Option<&[u8]> {
+    match payload.split_first() {
+        Some((&DNS_TUNNEL_MARKER, message)) => Some(message),
+        _ => None,
+    }
+}
+
+/// Read a (possibly compressed) domain name; returns it and the offset after it
+fn read_name(message: &[u8], mut pos: usize) -> Result<(String, usize), AegisQError> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut end = None;
+    let mut jumps = 0;
+    loop {
+        let len = *message.get(pos).ok_or(AegisQError::InvalidLength("Truncated DNS name"))? as usize;
+        match len {
+            0 => {
+                return Ok((labels.join("."), end.unwrap_or(pos + 1)));
+            }
+            l if l & 0xc0 == 0xc0 => {
+                let low = *message.get(pos + 1).ok_or(AegisQError::InvalidLength("Truncated DNS name"))? as usize;
+                end.get_or_insert(pos + 2);
+                jumps += 1;
+                if jumps > 16 {
+                    return Err(AegisQError::Serialization("DNS name compression loop"));
+                }
+                pos = ((l & 0x3f) << 8) | low;
+            }
+            l if l <= 63 => {
+                let label = message.get(pos + 1..pos + 1 + l).ok_or(AegisQError::InvalidLength("Truncated DNS name"))?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                pos += 1 + l;
+            }
+            _ => return Err(AegisQError::Serialization("Invalid DNS label")),
+        }
+    }
+}
+
+/// Offsets of every record TTL in a response, and the minimum TTL
+fn record_ttls(message: &[u8]) -> Result<(Vec<usize>, Option<u32>), AegisQError> {
+    let count = |i: usize| u16::from_be_bytes([message[i], message[i + 1]]) as usize;
+    let questions = count(4);
+    let records = count(6) + count(8) + count(10);
+
+    let mut pos = HEADER_SIZE;
+    for _ in 0..questions {
+        pos = read_name(message, pos)?.1 + 4;
+    }
+
+    let mut offsets = Vec::new();
+    let mut min_ttl: Option<u32> = None;
+    for _ in 0..records {
+        pos = read_name(message, pos)?.1;
+        let fixed = message.get(pos..pos + 10).ok_or(AegisQError::InvalidLength("Truncated DNS record"))?;
+        let rtype = u16::from_be_bytes([fixed[0], fixed[1]]);
+        let ttl = u32::from_be_bytes([fixed[4], fixed[5], fixed[6], fixed[7]]);
+        let rdlen = u16::from_be_bytes([fixed[8], fixed[9]]) as usize;
+        if rtype != TYPE_OPT {
+            offsets.push(pos + 4);
+            min_ttl = Some(min_ttl.map_or(ttl, |m| m.min(ttl)));
+        }
+        pos += 10 + rdlen;
+        if pos > message.len() {
+            return Err(AegisQError::InvalidLength("Truncated DNS record"));
+        }
+    }
+    Ok((offsets, min_ttl))
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    response: Vec<u8>,
+    ttl_offsets: Vec<usize>,
+    stored_at: u64,
+    expires_at: u64,
+}
+
+/// Response cache keyed by question
+#[derive(Debug, Clone)]
+pub struct DnsCache {
+    entries: HashMap<DnsQuestion, CacheEntry>,
+    capacity: usize,
+}
+
+impl DnsCache {
+    /// Cache holding at most `capacity` answers
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            capacity,
+        }
+    }
+
+    /// Number of cached answers (including expired ones not yet evicted)
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Cache a response for its minimum record TTL
+    pub fn insert(&mut self, response: &[u8], now: u64) -> Result<(), AegisQError> {
+        let (_, question) = parse_question(response)?;
+        let (ttl_offsets, min_ttl) = record_ttls(response)?;
+        let ttl = min_ttl.unwrap_or(NEGATIVE_TTL_SECS).min(MAX_TTL_SECS);
+        if ttl == 0 || self.capacity == 0 {
+            return Ok(());
+        }
+
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&question) {
+            self.entries.retain(|_, e| e.expires_at > now);
+            if self.entries.len() >= self.capacity {
+                let soonest = self.entries.iter().min_by_key(|(_, e)| e.expires_at).map(|(q, _)| q.clone());
+                if let Some(question) = soonest {
+                    self.entries.remove(&question);
+                }
+            }
+        }
+
+        self.entries.insert(
+            question,
+            CacheEntry {
+                response: response.to_vec(),
+                ttl_offsets,
+                stored_at: now,
+                expires_at: now + ttl as u64,
+            },
+        );
+        Ok(())
+    }
+
+    /// Cached response for `question` with `id` and TTLs adjusted for age
+    pub fn lookup(&mut self, question: &DnsQuestion, id: u16, now: u64) -> Option<Vec<u8>> {
+        let entry = self.entries.get(question)?;
+        if entry.expires_at <= now {
+            self.entries.remove(question);
+            return None;
+        }
+        let age = (now - entry.stored_at) as u32;
+        let mut response = entry.response.clone();
+        response[..2].copy_from_slice(&id.to_be_bytes());
+        for &offset in &entry.ttl_offsets {
+            let ttl = u32::from_be_bytes(response[offset..offset + 4].try_into().unwrap());
+            response[offset..offset + 4].copy_from_slice(&ttl.saturating_sub(age).to_be_bytes());
+        }
+        Some(response)
+    }
+}
+
+/// What the caller should do with an intercepted query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DnsAction {
+    /// Answer the client directly (cache hit)
+    Respond { client: SocketAddr, response: Vec<u8> },
+    /// Send this payload through the tunnel
+    Forward(Vec<u8>),
+}
+
+#[derive(Debug, Clone)]
+struct PendingQuery {
+    client: SocketAddr,
+    client_id: u16,
+    question: DnsQuestion,
+    sent_at: u64,
+}
+
+/// Client-side DNS proxy with caching
+#[derive(Debug, Clone)]
+pub struct DnsProxy {
+    cache: DnsCache,
+    pending: HashMap<u16, PendingQuery>,
+}
+
+impl Default for DnsProxy {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHE_ENTRIES)
+    }
+}
+
+impl DnsProxy {
+    /// Proxy with a cache of `cache_entries` answers
+    pub fn new(cache_entries: usize) -> Self {
+        Self {
+            cache: DnsCache::new(cache_entries),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Whether traffic to `destination` should be intercepted
+    pub fn intercepts(destination: SocketAddr) -> bool {
+        destination.port() == DNS_PORT
+    }
+
+    /// Response cache
+    pub fn cache(&self) -> &DnsCache {
+        &self.cache
+    }
+
+    /// Handle a query intercepted from `client`
+    pub fn handle_query(&mut self, client: SocketAddr, query: &[u8], now: u64) -> Result<DnsAction, AegisQError> {
+        let (client_id, question) = parse_question(query)?;
+        if let Some(response) = self.cache.lookup(&question, client_id, now) {
+            return Ok(DnsAction::Respond { client, response });
+        }
+
+        self.expire_pending(now);
+        if self.pending.len() >= u16::MAX as usize {
+            return Err(AegisQError::LimitExceeded("Too many outstanding DNS queries"));
+        }
+        let tunnel_id = loop {
+            let id = random_bytes(2);
+            let id = u16::from_be_bytes([id[0], id[1]]);
+            if !self.pending.contains_key(&id) {
+                break id;
+            }
+        };
+
+        let mut forwarded = query.to_vec();
+        forwarded[..2].copy_from_slice(&tunnel_id.to_be_bytes());
+        self.pending.insert(
+            tunnel_id,
+            PendingQuery {
+                client,
+                client_id,
+                question,
+                sent_at: now,
+            },
+        );
+        Ok(DnsAction::Forward(encode_tunnel(&forwarded)))
+    }
+
+    /// Handle a tunnel payload from the server; returns the client and its response
+    pub fn handle_tunnel_response(&mut self, payload: &[u8], now: u64) -> Result<(SocketAddr, Vec<u8>), AegisQError> {
+        let response = decode_tunnel(payload).ok_or(AegisQError::Serialization("Not a DNS tunnel payload"))?;
+        let (tunnel_id, question) = parse_question(response)?;
+        let pending = self.pending.get(&tunnel_id).ok_or(AegisQError::Protocol("Unexpected DNS response"))?;
+        if pending.question != question {
+            return Err(AegisQError::Protocol("DNS response does not match query"));
+        }
+        let pending = self.pending.remove(&tunnel_id).expect("pending query present");
+
+        self.cache.insert(response, now)?;
+        let mut response = response.to_vec();
+        response[..2].copy_from_slice(&pending.client_id.to_be_bytes());
+        Ok((pending.client, response))
+    }
+
+    /// Drop forwarded queries that timed out
+    pub fn expire_pending(&mut self, now: u64) {
+        self.pending.retain(|_, p| now.saturating_sub(p.sent_at) < QUERY_TIMEOUT_SECS);
+    }
+
+    /// Number of queries awaiting an answer
+    pub fn pending_queries(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(id: u16, name: &str) -> Vec<u8> {
+        let mut msg = Vec::new();
+        msg.extend_from_slice(&id.to_be_bytes());
+        msg.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+        for label in name.split('.') {
+            msg.push(label.len() as u8);
+            msg.extend_from_slice(label.as_bytes());
+        }
+        msg.extend_from_slice(&[0, 0, 1, 0, 1]);
+        msg
+    }
+
+    /// Answer with one A record (name compressed to the question)
+    fn answer(query: &[u8], ttl: u32) -> Vec<u8> {
+        let mut msg = query.to_vec();
+        msg[2] = 0x81;
+        msg[3] = 0x80;
+        msg[7] = 1;
+        msg.extend_from_slice(&[0xc0, 0x0c, 0, 1, 0, 1]);
+        msg.extend_from_slice(&ttl.to_be_bytes());
+        msg.extend_from_slice(&[0, 4, 192, 0, 2, 7]);
+        msg
+    }
+
+    #[test]
+    fn test_forward_then_cache() {
+        let client: SocketAddr = "10.8.0.2:5353".parse().unwrap();
+        let mut proxy = DnsProxy::default();
+
+        let DnsAction::Forward(payload) = proxy.handle_query(client, &query(0x1234, "Example.COM"), 100).unwrap() else {
+            panic!("expected forward");
+        };
+        // Server side: unwrap, resolve, wrap the answer
+        let forwarded = decode_tunnel(&payload).unwrap();
+        assert_eq!(parse_question(forwarded).unwrap().1.name, "example.com");
+        let reply = encode_tunnel(&answer(forwarded, 300));
+
+        let (to, response) = proxy.handle_tunnel_response(&reply, 100).unwrap();
+        assert_eq!(to, client);
+        assert_eq!(&response[..2], &0x1234u16.to_be_bytes());
+        assert_eq!(proxy.pending_queries(), 0);
+
+        // Cache hit with new ID and aged TTL
+        let DnsAction::Respond { response, .. } = proxy.handle_query(client, &query(0x0042, "example.com"), 160).unwrap() else {
+            panic!("expected cache hit");
+        };
+        assert_eq!(&response[..2], &0x0042u16.to_be_bytes());
+        let ttl_at = response.len() - 10;
+        assert_eq!(u32::from_be_bytes(response[ttl_at..ttl_at + 4].try_into().unwrap()), 240);
+
+        // Expired
+        assert!(matches!(proxy.handle_query(client, &query(1, "example.com"), 400).unwrap(), DnsAction::Forward(_)));
+    }
+
+    #[test]
+    fn test_rejects_unsolicited_and_malformed() {
+        let mut proxy = DnsProxy::default();
+        let stray = encode_tunnel(&answer(&query(7, "example.com"), 60));
+        assert!(proxy.handle_tunnel_response(&stray, 0).is_err());
+        assert!(proxy.handle_tunnel_response(b"\x00not dns", 0).is_err());
+        assert!(proxy.handle_query("10.8.0.2:1".parse().unwrap(), &[0u8; 5], 0).is_err());
+
+        let mut looped = query(1, "a");
+        looped.truncate(HEADER_SIZE);
+        looped.extend_from_slice(&[0xc0, 0x0c]);
+        assert!(parse_question(&looped).is_err());
+        assert!(DnsProxy::intercepts("1.1.1.1:53".parse().unwrap()));
+    }
+}
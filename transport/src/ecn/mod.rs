@@ -0,0 +1,221 @@
+//! Explicit Congestion Notification
+//!
+//! Optional ECN marking for the UDP path (RFC 3168, validation as in
+//! RFC 9000 §13.4). The sender marks datagrams ECT(0); the receiver counts
+//! the codepoints it sees and echoes the counts in `Ack` frames. A rising
+//! CE count is a congestion signal the sender can act on before any loss.
+//! If the echoed counts are inconsistent (a path or peer bleaching or
+//! mangling ECN bits), marking is disabled for the rest of the session.
+//!
+//! The caller sets and reads the IP TOS/traffic-class byte; this module
+//! only handles the two ECN bits.
+
+use aegis_q_core::AegisQError;
+use utils::wire::{AckWire, EcnCountsWire, Wire};
+
+use crate::framing::{Frame, FrameType};
+
+/// ECN codepoint (low two bits of the TOS byte)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EcnCodepoint {
+    NotEct = 0b00,
+    Ect1 = 0b01,
+    Ect0 = 0b10,
+    Ce = 0b11,
+}
+
+impl EcnCodepoint {
+    /// Codepoint from a TOS / traffic-class byte
+    pub fn from_tos(tos: u8) -> Self {
+        match tos & 0b11 {
+            0b01 => EcnCodepoint::Ect1,
+            0b10 => EcnCodepoint::Ect0,
+            0b11 => EcnCodepoint::Ce,
+            _ => EcnCodepoint::NotEct,
+        }
+    }
+
+    /// TOS byte with the ECN bits replaced
+    pub fn apply_to_tos(self, tos: u8) -> u8 {
+        (tos & !0b11) | self as u8
+    }
+}
+
+/// Per-codepoint receive counts echoed in acknowledgements
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EcnCounts {
+    pub ect0: u64,
+    pub ect1: u64,
+    pub ce: u64,
+}
+
+impl EcnCounts {
+    /// Count a received datagram
+    pub fn record(&mut self, codepoint: EcnCodepoint) {
+        match codepoint {
+            EcnCodepoint::Ect0 => self.ect0 += 1,
+            EcnCodepoint::Ect1 => self.ect1 += 1,
+            EcnCodepoint::Ce => self.ce += 1,
+            EcnCodepoint::NotEct => {}
+        }
+    }
+
+    fn total(&self) -> u64 {
+        self.ect0 + self.ect1 + self.ce
+    }
+}
+
+/// Acknowledgement payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AckFrame {
+    /// Highest sequence number received
+    pub largest_acked: u64,
+    /// Receive counts, if the peer supports ECN
+    pub ecn: Option<EcnCounts>,
+}
+
+impl AckFrame {
+    /// Encode as an `Ack` frame
+    pub fn to_frame(&self, sequence: u64) -> Frame {
+        let wire = AckWire {
+            largest_acked: self.largest_acked,
+            ecn: self.ecn.map(|counts| EcnCountsWire {
+                ect0: counts.ect0,
+                ect1: counts.ect1,
+                ce: counts.ce,
+            }),
+        };
+        Frame::new(FrameType::Ack, wire.to_wire().expect("fixed-size ACK"), sequence)
+    }
+
+    /// Decode from an `Ack` frame
+    pub fn from_frame(frame: &Frame) -> Result<Self, AegisQError> {
+        if frame.frame_type != FrameType::Ack {
+            return Err(AegisQError::Serialization("Not an ACK frame"));
+        }
+        let wire = AckWire::from_wire(&frame.payload).map_err(|_| AegisQError::Serialization("Malformed ACK frame"))?;
+        Ok(Self {
+            largest_acked: wire.largest_acked,
+            ecn: wire.ecn.map(|counts| EcnCounts {
+                ect0: counts.ect0,
+                ect1: counts.ect1,
+                ce: counts.ce,
+            }),
+        })
+    }
+}
+
+/// Sender-side ECN state
+#[derive(Debug, Clone)]
+pub struct EcnController {
+    enabled: bool,
+    /// ECT(0)-marked datagrams sent
+    sent_marked: u64,
+    last_counts: EcnCounts,
+}
+
+impl Default for EcnController {
+    fn default() -> Self {
+        Self::new(true)
+    }
+}
+
+impl EcnController {
+    /// Controller with marking on or off
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            sent_marked: 0,
+            last_counts: EcnCounts::default(),
+        }
+    }
+
+    /// Whether datagrams are currently marked
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Codepoint for the next outgoing datagram
+    pub fn on_send(&mut self) -> EcnCodepoint {
+        if self.enabled {
+            self.sent_marked += 1;
+            EcnCodepoint::Ect0
+        } else {
+            EcnCodepoint::NotEct
+        }
+    }
+
+    /// Process echoed counts; returns true on a new congestion signal
+    ///
+    /// Counts that decrease, report more marks than were sent, or vanish
+    /// from acknowledgements disable ECN.
+    pub fn on_ack(&mut self, ack: &AckFrame) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        let Some(counts) = ack.ecn else {
+            if self.sent_marked > 0 {
+                self.enabled = false;
+            }
+            return false;
+        };
+
+        let last = self.last_counts;
+        if counts.ect0 < last.ect0
+            || counts.ect1 < last.ect1
+            || counts.ce < last.ce
+            || counts.total() > self.sent_marked
+            || counts.ect1 > 0
+        {
+            self.enabled = false;
+            return false;
+        }
+
+        self.last_counts = counts;
+        counts.ce > last.ce
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tos_and_ack_roundtrip() {
+        assert_eq!(EcnCodepoint::from_tos(0xb8 | 0b11), EcnCodepoint::Ce);
+        assert_eq!(EcnCodepoint::Ect0.apply_to_tos(0xb9), 0xba);
+
+        let ack = AckFrame {
+            largest_acked: 42,
+            ecn: Some(EcnCounts { ect0: 10, ect1: 0, ce: 2 }),
+        };
+        assert_eq!(AckFrame::from_frame(&ack.to_frame(7)).unwrap(), ack);
+        let plain = AckFrame { largest_acked: 1, ecn: None };
+        assert_eq!(AckFrame::from_frame(&plain.to_frame(8)).unwrap(), plain);
+        assert!(AckFrame::from_frame(&Frame::new(FrameType::Ack, vec![0; 10], 0)).is_err());
+    }
+
+    #[test]
+    fn test_congestion_signal_and_validation() {
+        let mut sender = EcnController::default();
+        let mut receiver = EcnCounts::default();
+
+        for _ in 0..4 {
+            receiver.record(sender.on_send());
+        }
+        let ack = |counts| AckFrame { largest_acked: 0, ecn: Some(counts) };
+        assert!(!sender.on_ack(&ack(receiver)));
+
+        // Router marks congestion
+        sender.on_send();
+        receiver.record(EcnCodepoint::Ce);
+        assert!(sender.on_ack(&ack(receiver)));
+        assert!(sender.is_enabled());
+
+        // Counts going backwards: ECN is unreliable on this path
+        receiver.ect0 -= 1;
+        assert!(!sender.on_ack(&ack(receiver)));
+        assert!(!sender.is_enabled());
+        assert_eq!(sender.on_send(), EcnCodepoint::NotEct);
+    }
+}
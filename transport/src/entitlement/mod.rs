@@ -0,0 +1,532 @@
+//! License entitlements for transport services
+//!
+//! A server attaches an `EntitlementPolicy` and admits each client from the
+//! license it presents. The resulting `Entitlements` gate stream creation,
+//! cap bandwidth by tier and bound concurrent tunnels per license.
+//! Licensed features are plain strings on the license:
+//! - `EntitlementPolicy::stream_feature` enables multiplexed streams
+//! - bandwidth tiers map a feature to a bytes-per-second limit (highest wins)
+//! - tunnel limits map a feature to a concurrent tunnel count (highest wins)
+//!
+//! Clients present their license inside the handshake as an extension
+//! (`encode_license_extension`), encrypted under a key derived from the
+//! handshake secret and bound to the session ID, so admission needs no
+//! extra round trip. `tenant::SessionManager::accept` admits it from the
+//! `ClientHello` for tenants with a policy.
+//!
+//! Licenses restricted by country, IP range or platform (see
+//! `licensing::constraints`) are admitted only through the `_with_context`
+//! variants with a context describing the client; the policy's
+//! `ConstraintEvaluator` (e.g. a GeoIP lookup) decides. Without a context a
+//! restricted license is rejected.
+
+use std::collections::HashMap;
+use std::fmt;
+#[cfg(loom)]
+use loom::sync::{Arc, Mutex};
+#[cfg(not(loom))]
+use std::sync::{Arc, Mutex};
+
+use aegis_q_core::{AegisQError, aegis_q_decrypt, aegis_q_encrypt};
+use licensing::License;
+use licensing::constraints::{ActivationContext, ConstraintEvaluator, DefaultEvaluator};
+use utils::keys::SigningKey;
+use utils::kdf::kdf_shake256;
+use utils::wire::{HandshakeExtensionWire, Wire};
+
+use crate::hello::ClientHello;
+use crate::quic::QuicSession;
+use crate::vpn::Handshake;
+
+/// Handshake extension type carrying an encrypted license
+pub const LICENSE_EXTENSION_TYPE: u16 = 0x4c51;
+
+/// Typed policy failure
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyError {
+    /// Signature does not verify against the policy key
+    InvalidLicense,
+    /// License extension missing, malformed or not decryptable
+    MalformedExtension,
+    /// License expiry is in the past
+    Expired,
+    /// Required feature missing from the license
+    FeatureNotLicensed(String),
+    /// Concurrent tunnel limit for the license reached
+    TunnelLimitReached { limit: usize },
+    /// Bandwidth budget for the current window exhausted
+    BandwidthExceeded { bytes_per_second: u64 },
+    /// Activation constraint (country, IP range, platform) not met
+    ConstraintViolated(String),
+}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyError::InvalidLicense => write!(f, "license signature invalid"),
+            PolicyError::MalformedExtension => write!(f, "license extension malformed"),
+            PolicyError::Expired => write!(f, "license expired"),
+            PolicyError::FeatureNotLicensed(feature) => write!(f, "feature not licensed: {}", feature),
+            PolicyError::TunnelLimitReached { limit } => write!(f, "concurrent tunnel limit reached ({})", limit),
+            PolicyError::BandwidthExceeded { bytes_per_second } => {
+                write!(f, "bandwidth limit exceeded ({} B/s)", bytes_per_second)
+            }
+            PolicyError::ConstraintViolated(reason) => write!(f, "license constraint violated: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for PolicyError {}
+
+/// Server-side entitlement policy
+#[derive(Debug, Clone)]
+pub struct EntitlementPolicy {
+    verifying_key: SigningKey,
+    stream_feature: String,
+    bandwidth_tiers: Vec<(String, u64)>,
+    default_bandwidth: Option<u64>,
+    tunnel_limits: Vec<(String, usize)>,
+    default_max_tunnels: usize,
+    /// Immutable once set, so a plain `std` Arc even under loom
+    constraint_evaluator: std::sync::Arc<dyn ConstraintEvaluator>,
+}
+
+impl EntitlementPolicy {
+    /// Policy verifying licenses with `verifying_key`
+    ///
+    /// Defaults: streams require feature `"streams"`, unlimited bandwidth,
+    /// one concurrent tunnel.
+    pub fn new(verifying_key: &SigningKey) -> Self {
+        Self {
+            verifying_key: verifying_key.clone(),
+            stream_feature: "streams".to_string(),
+            bandwidth_tiers: Vec::new(),
+            default_bandwidth: None,
+            tunnel_limits: Vec::new(),
+            default_max_tunnels: 1,
+            constraint_evaluator: std::sync::Arc::new(DefaultEvaluator),
+        }
+    }
+
+    /// Feature required to open multiplexed streams
+    pub fn stream_feature(mut self, feature: &str) -> Self {
+        self.stream_feature = feature.to_string();
+        self
+    }
+
+    /// Bandwidth tier granted by `feature`
+    pub fn bandwidth_tier(mut self, feature: &str, bytes_per_second: u64) -> Self {
+        self.bandwidth_tiers.push((feature.to_string(), bytes_per_second));
+        self
+    }
+
+    /// Bandwidth for licenses without any tier feature (`None` = unlimited)
+    pub fn default_bandwidth(mut self, bytes_per_second: Option<u64>) -> Self {
+        self.default_bandwidth = bytes_per_second;
+        self
+    }
+
+    /// Concurrent tunnel limit granted by `feature`
+    pub fn tunnel_limit(mut self, feature: &str, max_tunnels: usize) -> Self {
+        self.tunnel_limits.push((feature.to_string(), max_tunnels));
+        self
+    }
+
+    /// Tunnel limit for licenses without any tunnel feature
+    pub fn default_max_tunnels(mut self, max_tunnels: usize) -> Self {
+        self.default_max_tunnels = max_tunnels;
+        self
+    }
+
+    /// Evaluator for license activation constraints (default: `DefaultEvaluator`)
+    pub fn constraint_evaluator(mut self, evaluator: impl ConstraintEvaluator + 'static) -> Self {
+        self.constraint_evaluator = std::sync::Arc::new(evaluator);
+        self
+    }
+
+    /// Verify a presented license and resolve its entitlements
+    pub fn admit(&self, license: &License, now: u64) -> Result<Entitlements, PolicyError> {
+        self.admit_with_context(license, &ActivationContext::new(), now)
+    }
+
+    /// `admit`, also checking activation constraints against the client `context`
+    pub fn admit_with_context(&self, license: &License, context: &ActivationContext, now: u64) -> Result<Entitlements, PolicyError> {
+        if !license.verify(&self.verifying_key) {
+            return Err(PolicyError::InvalidLicense);
+        }
+        if now > license.expiry {
+            return Err(PolicyError::Expired);
+        }
+        license
+            .check_constraints(self.constraint_evaluator.as_ref(), context)
+            .map_err(|e| PolicyError::ConstraintViolated(e.to_string()))?;
+
+        let has = |feature: &str| license.features.iter().any(|f| f == feature);
+
+        let bandwidth = self
+            .bandwidth_tiers
+            .iter()
+            .filter(|(feature, _)| has(feature))
+            .map(|&(_, bps)| bps)
+            .max()
+            .or(self.default_bandwidth);
+
+        let max_tunnels = self
+            .tunnel_limits
+            .iter()
+            .filter(|(feature, _)| has(feature))
+            .map(|&(_, limit)| limit)
+            .max()
+            .unwrap_or(self.default_max_tunnels);
+
+        Ok(Entitlements {
+            license_id: license.license_id.clone(),
+            features: license.features.clone(),
+            stream_feature: self.stream_feature.clone(),
+            bytes_per_second: bandwidth,
+            max_tunnels,
+        })
+    }
+
+    /// Decrypt the client's license extension and admit it
+    ///
+    /// Called by the server before completing the handshake.
+    pub fn admit_extension(&self, extension: &[u8], handshake: &Handshake, now: u64) -> Result<Entitlements, PolicyError> {
+        self.admit_extension_with_context(extension, handshake, &ActivationContext::new(), now)
+    }
+
+    /// `admit_extension` with the client context (address, resolved country)
+    pub fn admit_extension_with_context(
+        &self,
+        extension: &[u8],
+        handshake: &Handshake,
+        context: &ActivationContext,
+        now: u64,
+    ) -> Result<Entitlements, PolicyError> {
+        let extension = HandshakeExtensionWire::from_wire(extension).map_err(|_| PolicyError::MalformedExtension)?;
+        if extension.extension_type != LICENSE_EXTENSION_TYPE {
+            return Err(PolicyError::MalformedExtension);
+        }
+        self.admit_body(&extension.body, handshake, context, now)
+    }
+
+    /// Admit the license extension of a client's `ClientHello`
+    ///
+    /// A hello without the extension is rejected as `MalformedExtension`.
+    pub fn admit_hello(&self, hello: &ClientHello, handshake: &Handshake, now: u64) -> Result<Entitlements, PolicyError> {
+        let body = hello.extension(LICENSE_EXTENSION_TYPE).ok_or(PolicyError::MalformedExtension)?;
+        self.admit_body(body, handshake, &ActivationContext::new(), now)
+    }
+
+    fn admit_body(&self, body: &[u8], handshake: &Handshake, context: &ActivationContext, now: u64) -> Result<Entitlements, PolicyError> {
+        let (key, nonce) = extension_keys(handshake);
+        let bytes = aegis_q_decrypt(&key, &nonce, body).map_err(|_| PolicyError::MalformedExtension)?;
+        let license: License = serde_json::from_slice(&bytes).map_err(|_| PolicyError::MalformedExtension)?;
+        self.admit_with_context(&license, context, now)
+    }
+}
+
+/// Encode a license as a handshake extension: type (2) || length (2) || ciphertext
+///
+/// Only the holder of the handshake secret (the server) can read it.
+pub fn encode_license_extension(license: &License, handshake: &Handshake) -> Result<Vec<u8>, AegisQError> {
+    let bytes = serde_json::to_vec(license).map_err(|_| AegisQError::Serialization("Serialization failed"))?;
+    let (key, nonce) = extension_keys(handshake);
+    HandshakeExtensionWire {
+        extension_type: LICENSE_EXTENSION_TYPE,
+        body: aegis_q_encrypt(&key, &nonce, &bytes),
+    }
+    .to_wire()
+    .map_err(|_| AegisQError::InvalidLength("License too large"))
+}
+
+fn extension_keys(handshake: &Handshake) -> (Vec<u8>, Vec<u8>) {
+    let session_id = handshake.session_id();
+    let key = kdf_shake256(b"aegis-q-transport-license-ext", &handshake.shared_secret, session_id.as_bytes(), 64);
+    (key, session_id.as_bytes().to_vec())
+}
+
+/// Entitlements resolved for one admitted license
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entitlements {
+    pub license_id: String,
+    pub features: Vec<String>,
+    stream_feature: String,
+    /// Bandwidth cap (`None` = unlimited)
+    pub bytes_per_second: Option<u64>,
+    /// Concurrent tunnel limit
+    pub max_tunnels: usize,
+}
+
+impl Entitlements {
+    /// Whether the license carries `feature`
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+
+    /// Require a licensed feature
+    pub fn require(&self, feature: &str) -> Result<(), PolicyError> {
+        if self.has_feature(feature) {
+            Ok(())
+        } else {
+            Err(PolicyError::FeatureNotLicensed(feature.to_string()))
+        }
+    }
+
+    /// Open a stream on `session` if streams are licensed
+    pub fn create_stream(&self, session: &mut QuicSession) -> Result<u32, PolicyError> {
+        self.require(&self.stream_feature)?;
+        Ok(session.create_stream())
+    }
+
+    /// Bandwidth limiter for one tunnel
+    pub fn bandwidth_limiter(&self) -> BandwidthLimiter {
+        BandwidthLimiter::new(self.bytes_per_second)
+    }
+}
+
+/// Per-second bandwidth budget
+#[derive(Debug, Clone)]
+pub struct BandwidthLimiter {
+    bytes_per_second: Option<u64>,
+    window_start: u64,
+    used: u64,
+}
+
+impl BandwidthLimiter {
+    /// Limiter allowing `bytes_per_second` (`None` = unlimited)
+    pub fn new(bytes_per_second: Option<u64>) -> Self {
+        Self {
+            bytes_per_second,
+            window_start: 0,
+            used: 0,
+        }
+    }
+
+    /// Account `bytes` sent at `now` (seconds); rejects once the budget is spent
+    pub fn consume(&mut self, bytes: u64, now: u64) -> Result<(), PolicyError> {
+        let Some(limit) = self.bytes_per_second else {
+            return Ok(());
+        };
+
+        if now != self.window_start {
+            self.window_start = now;
+            self.used = 0;
+        }
+        if self.used.saturating_add(bytes) > limit {
+            return Err(PolicyError::BandwidthExceeded { bytes_per_second: limit });
+        }
+        self.used += bytes;
+        Ok(())
+    }
+}
+
+/// Concurrent tunnel accounting shared across server workers
+#[derive(Debug, Clone, Default)]
+pub struct TunnelRegistry {
+    active: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+impl TunnelRegistry {
+    /// Empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reserve a tunnel slot for the license; released when the permit drops
+    pub fn open(&self, entitlements: &Entitlements) -> Result<TunnelPermit, PolicyError> {
+        let mut active = self.active.lock().unwrap();
+        let count = active.entry(entitlements.license_id.clone()).or_insert(0);
+        if *count >= entitlements.max_tunnels {
+            return Err(PolicyError::TunnelLimitReached { limit: entitlements.max_tunnels });
+        }
+        *count += 1;
+
+        Ok(TunnelPermit {
+            registry: self.active.clone(),
+            license_id: entitlements.license_id.clone(),
+        })
+    }
+
+    /// Active tunnels for a license
+    pub fn active(&self, license_id: &str) -> usize {
+        self.active.lock().unwrap().get(license_id).copied().unwrap_or(0)
+    }
+}
+
+/// Reserved tunnel slot
+#[derive(Debug)]
+pub struct TunnelPermit {
+    registry: Arc<Mutex<HashMap<String, usize>>>,
+    license_id: String,
+}
+
+impl Drop for TunnelPermit {
+    fn drop(&mut self) {
+        let mut active = self.registry.lock().unwrap();
+        if let Some(count) = active.get_mut(&self.license_id) {
+            *count -= 1;
+            if *count == 0 {
+                active.remove(&self.license_id);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = b"vendor-license-key";
+
+    fn license(features: &[&str], expiry: u64) -> License {
+        let mut license = License::new(
+            "lic-1".to_string(),
+            features.iter().map(|f| f.to_string()).collect(),
+            expiry,
+        );
+        license.sign(&SigningKey::from_bytes(KEY));
+        license
+    }
+
+    fn policy() -> EntitlementPolicy {
+        EntitlementPolicy::new(&SigningKey::from_bytes(KEY))
+            .bandwidth_tier("tier-basic", 1_000)
+            .bandwidth_tier("tier-pro", 10_000)
+            .tunnel_limit("tunnels-3", 3)
+    }
+
+    #[test]
+    fn test_admit_resolves_tiers() {
+        let entitlements = policy().admit(&license(&["tier-basic", "tier-pro", "tunnels-3"], 100), 50).unwrap();
+        assert_eq!(entitlements.bytes_per_second, Some(10_000));
+        assert_eq!(entitlements.max_tunnels, 3);
+
+        let basic = policy().admit(&license(&[], 100), 50).unwrap();
+        assert_eq!(basic.bytes_per_second, None);
+        assert_eq!(basic.max_tunnels, 1);
+    }
+
+    #[test]
+    fn test_admit_rejects() {
+        assert_eq!(policy().admit(&license(&[], 100), 101), Err(PolicyError::Expired));
+
+        let mut forged = license(&[], 100);
+        forged.features.push("tier-pro".to_string());
+        assert_eq!(policy().admit(&forged, 50), Err(PolicyError::InvalidLicense));
+    }
+
+    #[test]
+    fn test_stream_and_tunnel_gating() {
+        let mut session = QuicSession::new(b"session-key".to_vec(), b"nonce".to_vec());
+
+        let without = policy().admit(&license(&[], 100), 0).unwrap();
+        assert_eq!(
+            without.create_stream(&mut session),
+            Err(PolicyError::FeatureNotLicensed("streams".to_string()))
+        );
+        let with = policy().admit(&license(&["streams"], 100), 0).unwrap();
+        assert!(with.create_stream(&mut session).is_ok());
+
+        let registry = TunnelRegistry::new();
+        let permit = registry.open(&with).unwrap();
+        assert_eq!(registry.open(&with).unwrap_err(), PolicyError::TunnelLimitReached { limit: 1 });
+        drop(permit);
+        assert!(registry.open(&with).is_ok());
+    }
+
+    #[test]
+    fn test_license_extension() {
+        let client = Handshake::perform(b"client", b"server");
+        let extension = encode_license_extension(&license(&["streams"], 100), &client).unwrap();
+
+        let server = Handshake::perform(b"client", b"server");
+        let entitlements = policy().admit_extension(&extension, &server, 10).unwrap();
+        assert!(entitlements.has_feature("streams"));
+
+        let other = Handshake::perform(b"client", b"other-server");
+        assert_eq!(policy().admit_extension(&extension, &other, 10), Err(PolicyError::MalformedExtension));
+        assert_eq!(
+            policy().admit_extension(&extension[..extension.len() - 1], &server, 10),
+            Err(PolicyError::MalformedExtension)
+        );
+    }
+
+    #[test]
+    fn test_admit_checks_constraints() {
+        use licensing::constraints::{Constraint, ConstraintError};
+
+        #[derive(Debug)]
+        struct GeoIp;
+        impl ConstraintEvaluator for GeoIp {
+            fn evaluate(&self, constraint: &Constraint, context: &ActivationContext) -> Result<(), ConstraintError> {
+                let country = match context.ip {
+                    Some(ip) if ip.is_loopback() => "DE",
+                    _ => "US",
+                };
+                DefaultEvaluator.evaluate(constraint, &context.clone().country(country))
+            }
+        }
+
+        let mut restricted = License::new("lic-geo".to_string(), Vec::new(), 100);
+        restricted.constraints = vec![Constraint::countries(["DE"]).unwrap()];
+        restricted.sign(&SigningKey::from_bytes(KEY));
+        let geo = policy().constraint_evaluator(GeoIp);
+
+        let local = ActivationContext::new().ip("127.0.0.1".parse().unwrap());
+        assert!(geo.admit_with_context(&restricted, &local, 0).is_ok());
+        let remote = ActivationContext::new().ip("192.0.2.1".parse().unwrap());
+        assert_eq!(
+            geo.admit_with_context(&restricted, &remote, 0),
+            Err(PolicyError::ConstraintViolated("country US not allowed by license".into()))
+        );
+        // No context: the default evaluator cannot tell the country
+        assert!(matches!(policy().admit(&restricted, 0), Err(PolicyError::ConstraintViolated(_))));
+    }
+
+    #[test]
+    fn test_bandwidth_limiter() {
+        let mut limiter = BandwidthLimiter::new(Some(1_000));
+        limiter.consume(600, 10).unwrap();
+        assert!(limiter.consume(600, 10).is_err());
+        limiter.consume(600, 11).unwrap();
+        assert!(BandwidthLimiter::new(None).consume(u64::MAX, 0).is_ok());
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use super::*;
+    use loom::thread;
+
+    #[test]
+    fn loom_tunnel_limit() {
+        loom::model(|| {
+            let entitlements = Entitlements {
+                license_id: "lic-1".to_string(),
+                features: Vec::new(),
+                stream_feature: String::new(),
+                bytes_per_second: None,
+                max_tunnels: 1,
+            };
+            let registry = TunnelRegistry::new();
+            let workers: Vec<_> = (0..2)
+                .map(|_| {
+                    let registry = registry.clone();
+                    let entitlements = entitlements.clone();
+                    thread::spawn(move || {
+                        // Open and evict: the slot is never held twice
+                        if let Ok(permit) = registry.open(&entitlements) {
+                            assert_eq!(registry.active("lic-1"), 1);
+                            drop(permit);
+                        }
+                    })
+                })
+                .collect();
+            for worker in workers {
+                worker.join().unwrap();
+            }
+            assert_eq!(registry.active("lic-1"), 0);
+        });
+    }
+}
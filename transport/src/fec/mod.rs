@@ -0,0 +1,456 @@
+//! Forward Error Correction
+//!
+//! Erasure coding across groups of frames for lossy datagram paths where
+//! retransmission is too slow (VPN over UDP, real-time media). Frames are
+//! sent unchanged as data shards and delivered as soon as they arrive; after
+//! every `data_shards` frames the encoder emits `parity_shards` Reed-Solomon
+//! parity shards (systematic Cauchy code over GF(2^8)). Any `data_shards`
+//! of the group's shards recover all of its frames, so up to
+//! `parity_shards / (data_shards + parity_shards)` loss per group is
+//! repaired without a round trip. One parity shard is plain XOR parity.
+//!
+//! Shards are framed with `utils::wire::FecShardWire`. Parity covers each
+//! frame as `len (2 BE) || frame`, zero-padded to the group's longest frame.
+
+use std::collections::BTreeMap;
+
+use aegis_q_core::AegisQError;
+use utils::wire::{FecShardWire, Wire};
+
+/// Largest frame that can be protected
+pub const MAX_FEC_FRAME: usize = u16::MAX as usize;
+
+/// Groups the decoder keeps before evicting the oldest
+pub const DEFAULT_GROUP_WINDOW: usize = 64;
+
+const KIND_DATA: u8 = 0;
+const KIND_PARITY: u8 = 1;
+
+/// Shards per group
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FecConfig {
+    pub data_shards: u8,
+    pub parity_shards: u8,
+}
+
+impl FecConfig {
+    /// Check shard counts
+    pub fn validate(&self) -> Result<(), AegisQError> {
+        if self.data_shards == 0 || self.parity_shards == 0 {
+            return Err(AegisQError::InvalidInput("FEC needs at least one data and one parity shard"));
+        }
+        if self.data_shards as usize + self.parity_shards as usize > 255 {
+            return Err(AegisQError::InvalidInput("FEC group too large"));
+        }
+        Ok(())
+    }
+
+    /// Fraction of each group's packets that may be lost
+    pub fn recoverable_loss(&self) -> f64 {
+        self.parity_shards as f64 / (self.data_shards as f64 + self.parity_shards as f64)
+    }
+}
+
+impl Default for FecConfig {
+    /// 10 data + 2 parity: 20% overhead, repairs ~16% loss
+    fn default() -> Self {
+        Self {
+            data_shards: 10,
+            parity_shards: 2,
+        }
+    }
+}
+
+/// Sending side: frames in, shards out
+#[derive(Debug)]
+pub struct FecEncoder {
+    config: FecConfig,
+    group: u32,
+    pending: Vec<Vec<u8>>,
+}
+
+impl FecEncoder {
+    /// Encoder with the given group shape
+    pub fn new(config: FecConfig) -> Result<Self, AegisQError> {
+        config.validate()?;
+        Ok(Self {
+            config,
+            group: 0,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Protect a frame; returns the packets to send now
+    ///
+    /// The frame's data shard comes first, followed by the group's parity
+    /// shards when this frame completes the group.
+    pub fn push(&mut self, frame: &[u8]) -> Result<Vec<Vec<u8>>, AegisQError> {
+        if frame.len() > MAX_FEC_FRAME {
+            return Err(AegisQError::InvalidLength("Frame too large for FEC"));
+        }
+        let mut out = vec![shard_packet(self.group, KIND_DATA, self.pending.len() as u8, 0, frame.to_vec())];
+        self.pending.push(frame.to_vec());
+        if self.pending.len() == self.config.data_shards as usize {
+            out.extend(self.flush());
+        }
+        Ok(out)
+    }
+
+    /// Close a partial group (e.g. when the link goes idle) and return its parity
+    pub fn flush(&mut self) -> Vec<Vec<u8>> {
+        if self.pending.is_empty() {
+            return Vec::new();
+        }
+        let data_count = self.pending.len() as u8;
+        let shards = padded_shards(self.pending.iter().map(Vec::as_slice));
+        let packets = (0..self.config.parity_shards)
+            .map(|j| {
+                let row = cauchy_row(data_count, j);
+                shard_packet(self.group, KIND_PARITY, j, data_count, combine(&row, &shards))
+            })
+            .collect();
+        self.pending.clear();
+        self.group = self.group.wrapping_add(1);
+        packets
+    }
+}
+
+fn shard_packet(group: u32, kind: u8, index: u8, data_count: u8, shard: Vec<u8>) -> Vec<u8> {
+    FecShardWire {
+        group,
+        kind,
+        index,
+        data_count,
+        shard,
+    }
+    .to_wire()
+    .expect("shard has no length limit")
+}
+
+/// `len (2 BE) || frame`, zero-padded to a common length
+fn padded_shards<'a>(frames: impl Iterator<Item = &'a [u8]>) -> Vec<Vec<u8>> {
+    let mut shards: Vec<Vec<u8>> = frames
+        .map(|frame| {
+            let mut shard = (frame.len() as u16).to_be_bytes().to_vec();
+            shard.extend_from_slice(frame);
+            shard
+        })
+        .collect();
+    let len = shards.iter().map(Vec::len).max().unwrap_or(0);
+    for shard in &mut shards {
+        shard.resize(len, 0);
+    }
+    shards
+}
+
+/// Linear combination of equal-length shards
+fn combine(coefficients: &[u8], shards: &[Vec<u8>]) -> Vec<u8> {
+    let mut out = vec![0u8; shards.first().map_or(0, Vec::len)];
+    for (&c, shard) in coefficients.iter().zip(shards) {
+        for (o, &b) in out.iter_mut().zip(shard) {
+            *o ^= gf_mul(c, b);
+        }
+    }
+    out
+}
+
+#[derive(Debug, Default)]
+struct Group {
+    data: BTreeMap<u8, Vec<u8>>,
+    parity: BTreeMap<u8, Vec<u8>>,
+    data_count: Option<u8>,
+    complete: bool,
+}
+
+/// Receiving side: shards in, frames out
+#[derive(Debug)]
+pub struct FecDecoder {
+    groups: BTreeMap<u32, Group>,
+    window: usize,
+    recovered: u64,
+}
+
+impl Default for FecDecoder {
+    fn default() -> Self {
+        Self::new(DEFAULT_GROUP_WINDOW)
+    }
+}
+
+impl FecDecoder {
+    /// Decoder keeping state for up to `window` groups
+    pub fn new(window: usize) -> Self {
+        Self {
+            groups: BTreeMap::new(),
+            window: window.max(1),
+            recovered: 0,
+        }
+    }
+
+    /// Frames recovered from parity so far
+    pub fn recovered(&self) -> u64 {
+        self.recovered
+    }
+
+    /// Process a received shard; returns frames ready for delivery
+    ///
+    /// Data shards are delivered immediately; frames rebuilt from parity
+    /// follow once enough shards of their group have arrived. Each frame is
+    /// delivered at most once.
+    pub fn receive(&mut self, packet: &[u8]) -> Result<Vec<Vec<u8>>, AegisQError> {
+        let shard = FecShardWire::from_wire(packet).map_err(AegisQError::Serialization)?;
+        let mut delivered = Vec::new();
+
+        if !self.groups.contains_key(&shard.group) {
+            if self.groups.len() >= self.window {
+                let oldest = *self.groups.keys().next().expect("window is non-empty");
+                if shard.group < oldest {
+                    // Too late to matter
+                    return Ok(delivered);
+                }
+                self.groups.remove(&oldest);
+            }
+            self.groups.insert(shard.group, Group::default());
+        }
+        let group = self.groups.get_mut(&shard.group).expect("group inserted");
+
+        match shard.kind {
+            KIND_DATA => {
+                if group.data.contains_key(&shard.index) || group.complete {
+                    return Ok(delivered);
+                }
+                if shard.shard.len() > MAX_FEC_FRAME {
+                    return Err(AegisQError::InvalidLength("FEC data shard too large"));
+                }
+                delivered.push(shard.shard.clone());
+                group.data.insert(shard.index, shard.shard);
+            }
+            KIND_PARITY => {
+                if shard.data_count == 0 || shard.data_count as usize + shard.index as usize >= 256 {
+                    return Err(AegisQError::Serialization("Invalid FEC parity shard"));
+                }
+                if group.data_count.is_some_and(|count| count != shard.data_count) {
+                    return Err(AegisQError::Serialization("Inconsistent FEC group size"));
+                }
+                group.data_count = Some(shard.data_count);
+                group.parity.entry(shard.index).or_insert(shard.shard);
+            }
+            _ => return Err(AegisQError::Unsupported("Unknown FEC shard kind")),
+        }
+
+        if let Some(recovered) = group.try_recover()? {
+            self.recovered += recovered.len() as u64;
+            delivered.extend(recovered);
+        }
+        Ok(delivered)
+    }
+}
+
+impl Group {
+    /// Rebuild missing data frames once enough shards are present
+    fn try_recover(&mut self) -> Result<Option<Vec<Vec<u8>>>, AegisQError> {
+        let Some(k) = self.data_count else {
+            return Ok(None);
+        };
+        if self.complete || self.data.keys().any(|&i| i >= k) {
+            return Ok(None);
+        }
+        let missing: Vec<u8> = (0..k).filter(|i| !self.data.contains_key(i)).collect();
+        if missing.is_empty() {
+            self.complete = true;
+            return Ok(None);
+        }
+        if self.data.len() + self.parity.len() < k as usize {
+            return Ok(None);
+        }
+
+        // Rows of the k×k system: identity rows for received data, Cauchy rows for parity
+        let shard_len = self.parity.values().next().map_or(0, Vec::len);
+        let known = padded_shards(self.data.values().map(Vec::as_slice));
+        if known.first().is_some_and(|s| s.len() > shard_len) {
+            return Err(AegisQError::InvalidLength("FEC shard length mismatch"));
+        }
+        let mut rows: Vec<Vec<u8>> = Vec::with_capacity(k as usize);
+        let mut values: Vec<Vec<u8>> = Vec::with_capacity(k as usize);
+        for (&i, shard) in self.data.keys().zip(known) {
+            let mut row = vec![0u8; k as usize];
+            row[i as usize] = 1;
+            rows.push(row);
+            let mut shard = shard;
+            shard.resize(shard_len, 0);
+            values.push(shard);
+        }
+        for (&j, shard) in self.parity.iter().take(missing.len()) {
+            if shard.len() != shard_len {
+                return Err(AegisQError::InvalidLength("FEC shard length mismatch"));
+            }
+            rows.push(cauchy_row(k, j));
+            values.push(shard.clone());
+        }
+
+        let inverse = invert(rows).ok_or(AegisQError::Serialization("Singular FEC system"))?;
+        let mut frames = Vec::with_capacity(missing.len());
+        for &i in &missing {
+            let shard = combine(&inverse[i as usize], &values);
+            let len = u16::from_be_bytes([shard[0], shard[1]]) as usize;
+            let frame = shard.get(2..2 + len).ok_or(AegisQError::Serialization("Corrupt FEC recovery"))?.to_vec();
+            self.data.insert(i, frame.clone());
+            frames.push(frame);
+        }
+        self.complete = true;
+        Ok(Some(frames))
+    }
+}
+
+// GF(2^8) with the polynomial x^8 + x^4 + x^3 + x^2 + 1 (0x11d)
+
+const GF_TABLES: ([u8; 512], [u8; 256]) = {
+    let mut exp = [0u8; 512];
+    let mut log = [0u8; 256];
+    let mut x: u16 = 1;
+    let mut i = 0;
+    while i < 255 {
+        exp[i] = x as u8;
+        exp[i + 255] = x as u8;
+        log[x as usize] = i as u8;
+        x <<= 1;
+        if x & 0x100 != 0 {
+            x ^= 0x11d;
+        }
+        i += 1;
+    }
+    (exp, log)
+};
+
+fn gf_mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        return 0;
+    }
+    let (exp, log) = &GF_TABLES;
+    exp[log[a as usize] as usize + log[b as usize] as usize]
+}
+
+fn gf_inv(a: u8) -> u8 {
+    let (exp, log) = &GF_TABLES;
+    exp[255 - log[a as usize] as usize]
+}
+
+/// Parity row `j` of the Cauchy matrix c[j][i] = 1 / (x_j + y_i), x_j = k + j, y_i = i
+///
+/// Columns are scaled so that row 0 is all ones, making a single parity
+/// shard plain XOR. Column scaling keeps every square submatrix of the
+/// systematic generator invertible, so any k shards still decode.
+fn cauchy_row(k: u8, j: u8) -> Vec<u8> {
+    (0..k)
+        .map(|i| gf_mul(gf_inv((k + j) ^ i), k ^ i))
+        .collect()
+}
+
+/// Gauss-Jordan inverse over GF(2^8)
+fn invert(mut m: Vec<Vec<u8>>) -> Option<Vec<Vec<u8>>> {
+    let n = m.len();
+    let mut inv: Vec<Vec<u8>> = (0..n)
+        .map(|i| {
+            let mut row = vec![0u8; n];
+            row[i] = 1;
+            row
+        })
+        .collect();
+    for col in 0..n {
+        let pivot = (col..n).find(|&r| m[r][col] != 0)?;
+        m.swap(col, pivot);
+        inv.swap(col, pivot);
+        let scale = gf_inv(m[col][col]);
+        for c in 0..n {
+            m[col][c] = gf_mul(m[col][c], scale);
+            inv[col][c] = gf_mul(inv[col][c], scale);
+        }
+        for r in 0..n {
+            let factor = m[r][col];
+            if r == col || factor == 0 {
+                continue;
+            }
+            for c in 0..n {
+                m[r][c] ^= gf_mul(factor, m[col][c]);
+                inv[r][c] ^= gf_mul(factor, inv[col][c]);
+            }
+        }
+    }
+    Some(inv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(n: usize) -> Vec<Vec<u8>> {
+        (0..n).map(|i| vec![i as u8; 10 + i * 7]).collect()
+    }
+
+    fn encode_all(config: FecConfig, frames: &[Vec<u8>]) -> Vec<Vec<u8>> {
+        let mut encoder = FecEncoder::new(config).unwrap();
+        let mut packets: Vec<Vec<u8>> = frames.iter().flat_map(|f| encoder.push(f).unwrap()).collect();
+        packets.extend(encoder.flush());
+        packets
+    }
+
+    #[test]
+    fn test_recovers_any_erasure_pattern() {
+        let config = FecConfig { data_shards: 4, parity_shards: 2 };
+        let input = frames(4);
+        let packets = encode_all(config, &input);
+        assert_eq!(packets.len(), 6);
+
+        // Every way of losing two of the six packets
+        for a in 0..6 {
+            for b in a + 1..6 {
+                let mut decoder = FecDecoder::default();
+                let mut out = Vec::new();
+                for (i, packet) in packets.iter().enumerate() {
+                    if i != a && i != b {
+                        out.extend(decoder.receive(packet).unwrap());
+                    }
+                }
+                out.sort();
+                assert_eq!(out, input, "lost {} and {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_xor_parity_and_partial_group() {
+        let config = FecConfig { data_shards: 8, parity_shards: 1 };
+        let input = frames(3);
+        let packets = encode_all(config, &input);
+        assert_eq!(packets.len(), 4);
+
+        let parity = FecShardWire::from_wire(&packets[3]).unwrap();
+        let expected: Vec<u8> = padded_shards(input.iter().map(Vec::as_slice))
+            .iter()
+            .fold(vec![0u8; parity.shard.len()], |acc, s| acc.iter().zip(s).map(|(a, b)| a ^ b).collect());
+        assert_eq!(parity.shard, expected);
+
+        let mut decoder = FecDecoder::default();
+        assert_eq!(decoder.receive(&packets[0]).unwrap(), vec![input[0].clone()]);
+        assert_eq!(decoder.receive(&packets[2]).unwrap(), vec![input[2].clone()]);
+        assert_eq!(decoder.receive(&packets[3]).unwrap(), vec![input[1].clone()]);
+        assert_eq!(decoder.recovered(), 1);
+        // Late original is not delivered twice
+        assert!(decoder.receive(&packets[1]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_too_much_loss_and_validation() {
+        let config = FecConfig { data_shards: 4, parity_shards: 1 };
+        let packets = encode_all(config, &frames(4));
+        let mut decoder = FecDecoder::default();
+        for packet in &packets[2..] {
+            decoder.receive(packet).unwrap();
+        }
+        assert_eq!(decoder.recovered(), 0);
+
+        assert!(FecEncoder::new(FecConfig { data_shards: 200, parity_shards: 60 }).is_err());
+        assert!(FecEncoder::new(FecConfig { data_shards: 4, parity_shards: 0 }).is_err());
+        assert!((FecConfig::default().recoverable_loss() - 1.0 / 6.0).abs() < 1e-9);
+        assert!(decoder.receive(&[0, 0]).is_err());
+    }
+}
@@ -0,0 +1,276 @@
+//! Fleet Secrets
+//!
+//! Stateless tokens for server fleets behind L4 load balancers. Every
+//! server of a fleet installs the same fleet secrets (by epoch, from the
+//! operator's key distribution), so a retry token or session ticket issued
+//! by one server is accepted by any other without sticky routing or shared
+//! state.
+//!
+//! - Retry tokens prove the client owns its source address before the
+//!   server does handshake work: `epoch || issued_at || MAC`, with the MAC
+//!   over the client address and a connection context. They are valid for
+//!   `token_lifetime_ms`; `MAX_CLOCK_SKEW_MS` absorbs clock differences
+//!   between servers.
+//! - Session tickets use a STEK derived from the current fleet secret
+//!   (`sync_ticket_keys`), so `TicketKeys` on every server hold the same
+//!   keys under the same IDs.
+//!
+//! Rotation: `install` a newer epoch on all servers; up to `max_epochs`
+//! secrets are kept so tokens issued just before a rotation still verify.
+//! Secrets are zeroized when dropped.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::net::SocketAddr;
+
+use aegis_q_core::{AegisQError, AegisQMac};
+use utils::kdf::kdf_shake256;
+use utils::memory::zeroize;
+use utils::wire::{RetryTokenHeaderWire, RetryTokenWire, Wire};
+
+use crate::tickets::{TicketKeyId, TicketKeys, TicketPolicy};
+
+/// Encoded retry token size: epoch (4) || issued_at_ms (8) || MAC (32),
+/// see `utils::wire::RetryTokenWire`
+pub const RETRY_TOKEN_SIZE: usize = 4 + 8 + aegis_q_core::mac::MAC_SIZE;
+
+/// Default retry token lifetime
+pub const DEFAULT_TOKEN_LIFETIME_MS: u64 = 30_000;
+
+/// Tolerated clock difference between servers of a fleet
+pub const MAX_CLOCK_SKEW_MS: u64 = 2_000;
+
+/// Default number of epochs kept
+pub const DEFAULT_MAX_EPOCHS: usize = 2;
+
+struct FleetSecret {
+    epoch: u32,
+    /// Source of the ticket key
+    secret: Vec<u8>,
+    /// Keyed retry token MAC (the state derivation is the expensive part)
+    retry_mac: AegisQMac,
+}
+
+impl Drop for FleetSecret {
+    fn drop(&mut self) {
+        zeroize(&mut self.secret);
+    }
+}
+
+/// Fleet-wide secrets, newest epoch first
+pub struct FleetSecrets {
+    secrets: VecDeque<FleetSecret>,
+    max_epochs: usize,
+    token_lifetime_ms: u64,
+}
+
+impl FleetSecrets {
+    /// Secrets starting at `epoch`
+    pub fn new(epoch: u32, secret: &[u8]) -> Self {
+        let mut secrets = VecDeque::with_capacity(DEFAULT_MAX_EPOCHS);
+        secrets.push_front(FleetSecret::new(epoch, secret));
+        Self {
+            secrets,
+            max_epochs: DEFAULT_MAX_EPOCHS,
+            token_lifetime_ms: DEFAULT_TOKEN_LIFETIME_MS,
+        }
+    }
+
+    /// Keep up to `max_epochs` secrets (at least one)
+    pub fn with_max_epochs(mut self, max_epochs: usize) -> Self {
+        self.max_epochs = max_epochs.max(1);
+        self.secrets.truncate(self.max_epochs);
+        self
+    }
+
+    /// Retry token lifetime
+    pub fn with_token_lifetime(mut self, token_lifetime_ms: u64) -> Self {
+        self.token_lifetime_ms = token_lifetime_ms;
+        self
+    }
+
+    /// Epoch tokens are issued under
+    pub fn current_epoch(&self) -> u32 {
+        self.secrets[0].epoch
+    }
+
+    /// Installed epochs, newest first
+    pub fn epochs(&self) -> Vec<u32> {
+        self.secrets.iter().map(|secret| secret.epoch).collect()
+    }
+
+    /// Install the secret of a newer epoch; it becomes current
+    pub fn install(&mut self, epoch: u32, secret: &[u8]) -> Result<(), AegisQError> {
+        if epoch <= self.current_epoch() {
+            return Err(AegisQError::InvalidInput("Fleet epoch must increase"));
+        }
+        self.secrets.push_front(FleetSecret::new(epoch, secret));
+        self.secrets.truncate(self.max_epochs);
+        Ok(())
+    }
+
+    /// Retry token for `client` under the current epoch
+    ///
+    /// `context` is what the token is bound to besides the address, e.g.
+    /// the connection ID the client first used.
+    pub fn issue_retry_token(&self, client: SocketAddr, context: &[u8], now_ms: u64) -> Vec<u8> {
+        let secret = &self.secrets[0];
+        let header = RetryTokenHeaderWire { epoch: secret.epoch, issued_at_ms: now_ms };
+        let transcript = retry_transcript(&header, client, context);
+        RetryTokenWire { header, tag: secret.retry_mac.mac(&transcript) }
+            .to_wire()
+            .expect("retry token has no length prefixes")
+    }
+
+    /// Check a retry token issued by any server of the fleet
+    pub fn verify_retry_token(&self, token: &[u8], client: SocketAddr, context: &[u8], now_ms: u64) -> Result<(), AegisQError> {
+        let token = RetryTokenWire::from_wire(token).map_err(|_| AegisQError::InvalidLength("Invalid retry token length"))?;
+        let RetryTokenHeaderWire { epoch, issued_at_ms: issued_ms } = token.header;
+
+        let secret = self
+            .secrets
+            .iter()
+            .find(|secret| secret.epoch == epoch)
+            .ok_or(AegisQError::NotFound("Unknown fleet epoch"))?;
+        secret.retry_mac.verify(&retry_transcript(&token.header, client, context), &token.tag)?;
+
+        if issued_ms > now_ms.saturating_add(MAX_CLOCK_SKEW_MS) {
+            return Err(AegisQError::Protocol("Retry token issued in the future"));
+        }
+        if now_ms >= issued_ms.saturating_add(self.token_lifetime_ms + MAX_CLOCK_SKEW_MS) {
+            return Err(AegisQError::Protocol("Retry token expired"));
+        }
+        Ok(())
+    }
+
+    /// Make the current epoch's ticket key active in `tickets`
+    ///
+    /// Every server derives the same STEK from the fleet secret, so tickets
+    /// issued anywhere in the fleet open everywhere. Returns the key ID if
+    /// `tickets` was rotated.
+    pub fn sync_ticket_keys(&self, tickets: &mut TicketKeys, now_ms: u64) -> Result<Option<TicketKeyId>, AegisQError> {
+        let mut stek = self.secrets[0].ticket_stek();
+        let result = if tickets.active() == TicketKeyId::of(&stek) {
+            Ok(None)
+        } else {
+            tickets.rotate(&stek, now_ms).map(Some)
+        };
+        zeroize(&mut stek);
+        result
+    }
+
+    /// Ticket keys for every installed epoch, the current one active
+    ///
+    /// A server (re)started after a rotation still opens tickets issued
+    /// under the previous epochs.
+    pub fn ticket_keys(&self, now_ms: u64, policy: TicketPolicy) -> Result<TicketKeys, AegisQError> {
+        let mut steks: Vec<Vec<u8>> = self.secrets.iter().rev().map(FleetSecret::ticket_stek).collect();
+        let keys = TicketKeys::new(&steks[0], now_ms, policy).and_then(|mut keys| {
+            for stek in &steks[1..] {
+                keys.rotate(stek, now_ms)?;
+            }
+            Ok(keys)
+        });
+        for stek in &mut steks {
+            zeroize(stek);
+        }
+        keys
+    }
+}
+
+impl FleetSecret {
+    fn new(epoch: u32, secret: &[u8]) -> Self {
+        let mut retry_key = kdf_shake256(b"aegis-q-fleet", secret, b"retry-token", 32);
+        let retry_mac = AegisQMac::new(&retry_key);
+        zeroize(&mut retry_key);
+        Self {
+            epoch,
+            secret: secret.to_vec(),
+            retry_mac,
+        }
+    }
+
+    fn ticket_stek(&self) -> Vec<u8> {
+        let mut info = b"session-ticket".to_vec();
+        info.extend_from_slice(&self.epoch.to_be_bytes());
+        kdf_shake256(b"aegis-q-fleet", &self.secret, &info, 32)
+    }
+}
+
+/// MAC input: label || epoch || issued_at || address || context
+fn retry_transcript(header: &RetryTokenHeaderWire, client: SocketAddr, context: &[u8]) -> Vec<u8> {
+    let prefix = header.to_wire().expect("retry token header has no length prefixes");
+    let address = client.to_string();
+    let mut transcript = Vec::with_capacity(32 + prefix.len() + address.len() + context.len());
+    transcript.extend_from_slice(b"aegis-q-retry-token");
+    transcript.extend_from_slice(&prefix);
+    transcript.extend_from_slice(&(address.len() as u16).to_be_bytes());
+    transcript.extend_from_slice(address.as_bytes());
+    transcript.extend_from_slice(context);
+    transcript
+}
+
+impl fmt::Debug for FleetSecrets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FleetSecrets")
+            .field("epochs", &self.epochs())
+            .field("token_lifetime_ms", &self.token_lifetime_ms)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(text: &str) -> SocketAddr {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn test_retry_token_accepted_across_fleet() {
+        let server_a = FleetSecrets::new(1, b"fleet-secret-epoch-1");
+        let mut server_b = FleetSecrets::new(1, b"fleet-secret-epoch-1");
+        let client = addr("198.51.100.7:40000");
+
+        let token = server_a.issue_retry_token(client, b"dcid", 1_000);
+        server_b.verify_retry_token(&token, client, b"dcid", 1_500).unwrap();
+        assert_eq!(server_b.verify_retry_token(&token, addr("198.51.100.7:40001"), b"dcid", 1_500), Err(AegisQError::AuthenticationFailed));
+        assert_eq!(server_b.verify_retry_token(&token, client, b"other", 1_500), Err(AegisQError::AuthenticationFailed));
+        assert_eq!(server_b.verify_retry_token(&token, client, b"dcid", 40_000), Err(AegisQError::Protocol("Retry token expired")));
+
+        // After rotation the previous epoch still verifies, two back does not
+        server_b.install(2, b"fleet-secret-epoch-2").unwrap();
+        server_b.verify_retry_token(&token, client, b"dcid", 2_000).unwrap();
+        server_b.install(3, b"fleet-secret-epoch-3").unwrap();
+        assert_eq!(server_b.verify_retry_token(&token, client, b"dcid", 2_000), Err(AegisQError::NotFound("Unknown fleet epoch")));
+        assert!(server_b.install(3, b"again").is_err());
+    }
+
+    #[test]
+    fn test_tickets_open_on_any_server() {
+        let mut fleet_a = FleetSecrets::new(7, b"fleet-secret-epoch-7");
+        let mut fleet_b = FleetSecrets::new(7, b"fleet-secret-epoch-7");
+        let mut tickets_a = fleet_a.ticket_keys(0, TicketPolicy::default()).unwrap();
+        let mut tickets_b = fleet_b.ticket_keys(0, TicketPolicy::default()).unwrap();
+        assert_eq!(tickets_a.active(), tickets_b.active());
+
+        let ticket = tickets_a.issue(b"resumption state", 10);
+        assert_eq!(tickets_b.open(&ticket, 20).unwrap(), b"resumption state");
+
+        fleet_a.install(8, b"fleet-secret-epoch-8").unwrap();
+        fleet_b.install(8, b"fleet-secret-epoch-8").unwrap();
+        assert!(fleet_a.sync_ticket_keys(&mut tickets_a, 100).unwrap().is_some());
+        assert!(fleet_b.sync_ticket_keys(&mut tickets_b, 100).unwrap().is_some());
+        assert_eq!(fleet_b.sync_ticket_keys(&mut tickets_b, 200).unwrap(), None);
+
+        let newer = tickets_b.issue(b"newer state", 300);
+        assert_eq!(tickets_a.open(&newer, 400).unwrap(), b"newer state");
+        assert_eq!(tickets_a.open(&ticket, 400).unwrap(), b"resumption state");
+
+        // A restarted server rebuilds keys for all installed epochs
+        let restarted = fleet_b.ticket_keys(500, TicketPolicy::default()).unwrap();
+        assert_eq!(restarted.active(), tickets_b.active());
+        assert_eq!(restarted.open(&ticket, 600).unwrap(), b"resumption state");
+    }
+}
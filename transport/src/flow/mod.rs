@@ -0,0 +1,332 @@
+//! Flow Control
+//!
+//! Receive windows for QUIC streams and the whole connection, with
+//! bandwidth-delay-product auto-tuning. A static window caps throughput at
+//! window / RTT, which throttles long-fat networks; instead each window
+//! starts small and grows when the application drains it faster than the
+//! path RTT allows (window updates less than two RTTs apart), or when the
+//! measured throughput × RTT exceeds it. Growth is clamped per session by
+//! `WindowConfig`. The connection window is kept at least
+//! `CONNECTION_WINDOW_FACTOR` × the largest stream window.
+//!
+//! Sans-IO: callers pass time as a `Duration` since session start and
+//! ship the returned limits in their own window update frames.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use aegis_q_core::AegisQError;
+
+/// Connection window relative to the largest stream window
+pub const CONNECTION_WINDOW_FACTOR: f64 = 1.5;
+
+/// Initial RTT assumed before any sample (RFC 9002)
+pub const INITIAL_RTT: Duration = Duration::from_millis(333);
+
+/// Per-session window limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowConfig {
+    /// Starting stream window
+    pub initial_stream_window: u64,
+    /// Lower clamp for stream windows
+    pub min_stream_window: u64,
+    /// Upper clamp for stream windows
+    pub max_stream_window: u64,
+    /// Upper clamp for the connection window
+    pub max_connection_window: u64,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            initial_stream_window: 64 * 1024,
+            min_stream_window: 16 * 1024,
+            max_stream_window: 16 * 1024 * 1024,
+            max_connection_window: 24 * 1024 * 1024,
+        }
+    }
+}
+
+impl WindowConfig {
+    /// Check clamps are ordered
+    pub fn validate(&self) -> Result<(), AegisQError> {
+        if self.min_stream_window == 0
+            || self.min_stream_window > self.initial_stream_window
+            || self.initial_stream_window > self.max_stream_window
+            || self.max_stream_window > self.max_connection_window
+        {
+            return Err(AegisQError::InvalidInput("Invalid window configuration"));
+        }
+        Ok(())
+    }
+}
+
+/// Smoothed RTT estimate (RFC 9002 §5)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RttEstimator {
+    smoothed: Duration,
+    min: Duration,
+    has_sample: bool,
+}
+
+impl Default for RttEstimator {
+    fn default() -> Self {
+        Self {
+            smoothed: INITIAL_RTT,
+            min: INITIAL_RTT,
+            has_sample: false,
+        }
+    }
+}
+
+impl RttEstimator {
+    /// Add an RTT sample
+    pub fn update(&mut self, sample: Duration) {
+        if !self.has_sample {
+            self.smoothed = sample;
+            self.min = sample;
+            self.has_sample = true;
+        } else {
+            self.smoothed = (self.smoothed * 7 + sample) / 8;
+            self.min = self.min.min(sample);
+        }
+    }
+
+    /// Smoothed RTT
+    pub fn smoothed(&self) -> Duration {
+        self.smoothed
+    }
+
+    /// Minimum observed RTT
+    pub fn min(&self) -> Duration {
+        self.min
+    }
+}
+
+/// Auto-tuned receive window
+#[derive(Debug, Clone)]
+pub struct ReceiveWindow {
+    window: u64,
+    min_window: u64,
+    max_window: u64,
+    /// Highest offset received
+    received: u64,
+    /// Bytes consumed by the application
+    consumed: u64,
+    /// Limit advertised to the peer
+    max_data: u64,
+    last_update: Option<(Duration, u64)>,
+}
+
+impl ReceiveWindow {
+    /// Window starting at `initial`, clamped to `[min, max]`
+    pub fn new(initial: u64, min: u64, max: u64) -> Self {
+        let window = initial.clamp(min, max);
+        Self {
+            window,
+            min_window: min,
+            max_window: max,
+            received: 0,
+            consumed: 0,
+            max_data: window,
+            last_update: None,
+        }
+    }
+
+    /// Current window size
+    pub fn window(&self) -> u64 {
+        self.window
+    }
+
+    /// Limit currently advertised to the peer
+    pub fn max_data(&self) -> u64 {
+        self.max_data
+    }
+
+    /// Record data up to `end_offset`; rejects flow-control violations
+    pub fn on_received(&mut self, end_offset: u64) -> Result<(), AegisQError> {
+        if end_offset > self.max_data {
+            return Err(AegisQError::LimitExceeded("Flow control limit exceeded"));
+        }
+        self.received = self.received.max(end_offset);
+        Ok(())
+    }
+
+    /// Record `bytes` consumed by the application
+    ///
+    /// Returns a new limit to advertise once half the window is used.
+    pub fn on_consumed(&mut self, bytes: u64, now: Duration, rtt: &RttEstimator) -> Option<u64> {
+        self.consumed = (self.consumed + bytes).min(self.received);
+        if self.max_data - self.consumed > self.window / 2 {
+            return None;
+        }
+
+        self.autotune(now, rtt);
+        self.max_data = self.consumed + self.window;
+        self.last_update = Some((now, self.consumed));
+        Some(self.max_data)
+    }
+
+    /// Raise the window to at least `target` (within clamps)
+    pub fn ensure_at_least(&mut self, target: u64) {
+        self.window = self.window.max(target).clamp(self.min_window, self.max_window);
+    }
+
+    fn autotune(&mut self, now: Duration, rtt: &RttEstimator) {
+        let Some((last_time, last_consumed)) = self.last_update else {
+            return;
+        };
+        let elapsed = now.saturating_sub(last_time);
+        let srtt = rtt.smoothed();
+
+        let mut target = self.window;
+
+        // Updates closer than two RTTs: the window is the bottleneck
+        if elapsed < srtt * 2 {
+            target = target.saturating_mul(2);
+        }
+
+        // Measured throughput × RTT, with 2× headroom
+        if !elapsed.is_zero() {
+            let rate = (self.consumed - last_consumed) as f64 / elapsed.as_secs_f64();
+            let bdp = (rate * srtt.as_secs_f64()) as u64;
+            target = target.max(bdp.saturating_mul(2));
+        }
+
+        self.window = target.clamp(self.min_window, self.max_window);
+    }
+}
+
+/// Stream and connection windows of one session
+#[derive(Debug, Clone)]
+pub struct SessionWindows {
+    config: WindowConfig,
+    rtt: RttEstimator,
+    connection: ReceiveWindow,
+    streams: HashMap<u32, ReceiveWindow>,
+}
+
+impl SessionWindows {
+    /// Windows with the given limits
+    pub fn new(config: WindowConfig) -> Result<Self, AegisQError> {
+        config.validate()?;
+        let connection_initial = (config.initial_stream_window as f64 * CONNECTION_WINDOW_FACTOR) as u64;
+        Ok(Self {
+            config,
+            rtt: RttEstimator::default(),
+            connection: ReceiveWindow::new(connection_initial, config.min_stream_window, config.max_connection_window),
+            streams: HashMap::new(),
+        })
+    }
+
+    /// Feed an RTT sample
+    pub fn on_rtt_sample(&mut self, sample: Duration) {
+        self.rtt.update(sample);
+    }
+
+    /// RTT estimate
+    pub fn rtt(&self) -> &RttEstimator {
+        &self.rtt
+    }
+
+    /// Connection window
+    pub fn connection(&self) -> &ReceiveWindow {
+        &self.connection
+    }
+
+    /// Window of a stream (created on first use)
+    pub fn stream(&mut self, stream_id: u32) -> &ReceiveWindow {
+        self.stream_mut(stream_id)
+    }
+
+    fn stream_mut(&mut self, stream_id: u32) -> &mut ReceiveWindow {
+        let config = self.config;
+        self.streams.entry(stream_id).or_insert_with(|| {
+            ReceiveWindow::new(config.initial_stream_window, config.min_stream_window, config.max_stream_window)
+        })
+    }
+
+    /// Record stream data up to `end_offset`, adding `new_bytes` to the connection total
+    pub fn on_stream_data(&mut self, stream_id: u32, end_offset: u64, new_bytes: u64) -> Result<(), AegisQError> {
+        self.stream_mut(stream_id).on_received(end_offset)?;
+        let total = self.connection.received + new_bytes;
+        self.connection.on_received(total)
+    }
+
+    /// Record application reads; returns (stream update, connection update) limits to advertise
+    pub fn on_stream_consumed(&mut self, stream_id: u32, bytes: u64, now: Duration) -> (Option<u64>, Option<u64>) {
+        let rtt = self.rtt;
+        let stream_update = self.stream_mut(stream_id).on_consumed(bytes, now, &rtt);
+
+        let largest = self.streams.values().map(|w| w.window).max().unwrap_or(0);
+        self.connection.ensure_at_least((largest as f64 * CONNECTION_WINDOW_FACTOR) as u64);
+        let connection_update = self.connection.on_consumed(bytes, now, &rtt);
+
+        (stream_update, connection_update)
+    }
+
+    /// Forget a closed stream
+    pub fn remove_stream(&mut self, stream_id: u32) {
+        self.streams.remove(&stream_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(value: u64) -> Duration {
+        Duration::from_millis(value)
+    }
+
+    #[test]
+    fn test_window_grows_on_fast_drain() {
+        let mut rtt = RttEstimator::default();
+        rtt.update(ms(100));
+        let mut window = ReceiveWindow::new(64 * 1024, 16 * 1024, 1024 * 1024);
+
+        // Drain the window every 50 ms (faster than 2 RTT): window doubles up to the clamp
+        let mut now = ms(0);
+        let mut offset = 0;
+        for _ in 0..10 {
+            offset += window.window() / 2 + 1;
+            window.on_received(offset).unwrap();
+            now += ms(50);
+            window.on_consumed(window.window() / 2 + 1, now, &rtt);
+        }
+        assert_eq!(window.window(), 1024 * 1024);
+    }
+
+    #[test]
+    fn test_window_stays_on_slow_drain() {
+        let mut rtt = RttEstimator::default();
+        rtt.update(ms(10));
+        let mut window = ReceiveWindow::new(64 * 1024, 16 * 1024, 1024 * 1024);
+
+        // One half-window per second on a 10 ms path: no growth needed
+        let mut now = ms(0);
+        for _ in 0..5 {
+            let half = window.window() / 2 + 1;
+            window.on_received(window.max_data()).unwrap();
+            now += ms(1000);
+            window.on_consumed(half, now, &rtt);
+        }
+        assert_eq!(window.window(), 64 * 1024);
+    }
+
+    #[test]
+    fn test_flow_control_violation_and_connection_window() {
+        let config = WindowConfig::default();
+        let mut windows = SessionWindows::new(config).unwrap();
+        windows.on_rtt_sample(ms(200));
+
+        assert!(windows.on_stream_data(1, config.initial_stream_window + 1, 1).is_err());
+        windows.on_stream_data(1, config.initial_stream_window, config.initial_stream_window).unwrap();
+
+        let (stream_update, _) = windows.on_stream_consumed(1, config.initial_stream_window, ms(10));
+        assert!(stream_update.unwrap() > config.initial_stream_window);
+        assert!(windows.connection().window() as f64 >= windows.stream(1).window() as f64 * CONNECTION_WINDOW_FACTOR);
+
+        assert!(SessionWindows::new(WindowConfig { min_stream_window: 0, ..config }).is_err());
+    }
+}
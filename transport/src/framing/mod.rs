@@ -3,8 +3,10 @@
 //! Frame structure for Aegis-Q transport layer
 //! Replaces TLS framing
 
-use aegis_q_core::{aegis_q_encrypt, aegis_q_decrypt};
-use serde::{Serialize, Deserialize};
+use aegis_q_core::{AegisQError, aegis_q_encrypt, aegis_q_decrypt, aegis_q_decrypt_in_place};
+use utils::memory::zeroize;
+use utils::pool;
+use utils::wire::{FrameHeaderWire, Wire, Writer};
 
 /// Frame header size
 pub const FRAME_HEADER_SIZE: usize = 16;
@@ -16,6 +18,10 @@ pub enum FrameType {
     Data = 0x02,
     Close = 0x03,
     Heartbeat = 0x04,
+    /// Padded path MTU probe
+    Probe = 0x05,
+    /// Acknowledgement (optionally with ECN counts)
+    Ack = 0x06,
 }
 
 impl From<u8> for FrameType {
@@ -25,11 +31,43 @@ impl From<u8> for FrameType {
             0x02 => FrameType::Data,
             0x03 => FrameType::Close,
             0x04 => FrameType::Heartbeat,
+            0x05 => FrameType::Probe,
+            0x06 => FrameType::Ack,
             _ => FrameType::Data, // Default
         }
     }
 }
 
+/// Decoded frame header (payload left in the receive buffer)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    pub frame_type: FrameType,
+    pub sequence: u64,
+    pub payload_len: usize,
+}
+
+impl FrameHeader {
+    /// Decode header and check that the full payload is present
+    pub fn decode(data: &[u8]) -> Result<Self, AegisQError> {
+        let (wire, payload) = FrameHeaderWire::from_wire_prefix(data).map_err(|_| AegisQError::InvalidLength("Frame too short"))?;
+        let payload_len = wire.payload_len as usize;
+        if payload.len() < payload_len {
+            return Err(AegisQError::InvalidLength("Incomplete frame"));
+        }
+        
+        Ok(Self {
+            frame_type: FrameType::from(wire.frame_type),
+            sequence: wire.sequence,
+            payload_len,
+        })
+    }
+    
+    /// Byte range of the payload within the encoded frame
+    pub fn payload_range(&self) -> std::ops::Range<usize> {
+        FRAME_HEADER_SIZE..FRAME_HEADER_SIZE + self.payload_len
+    }
+}
+
 /// Aegis-Q Frame
 #[derive(Debug, Clone)]
 pub struct Frame {
@@ -48,53 +86,46 @@ impl Frame {
         }
     }
     
+    /// Padded probe frame whose encoding is exactly `size` bytes
+    pub fn probe(size: usize, sequence: u64) -> Result<Self, AegisQError> {
+        if size < FRAME_HEADER_SIZE {
+            return Err(AegisQError::InvalidLength("Probe smaller than frame header"));
+        }
+        Ok(Self::new(FrameType::Probe, vec![0u8; size - FRAME_HEADER_SIZE], sequence))
+    }
+    
+    /// Encoded size in bytes
+    pub fn encoded_len(&self) -> usize {
+        FRAME_HEADER_SIZE + self.payload.len()
+    }
+    
     /// Encode frame to bytes
+    /// 
+    /// The output buffer is taken from `utils::pool`; hand it back with
+    /// `utils::pool::recycle` once sent.
     pub fn encode(&self) -> Vec<u8> {
-        let mut result = Vec::new();
-        
-        // Frame type (1 byte)
-        result.push(self.frame_type as u8);
-        
-        // Sequence number (8 bytes)
-        result.extend_from_slice(&self.sequence.to_le_bytes());
-        
-        // Payload length (4 bytes)
-        result.extend_from_slice(&(self.payload.len() as u32).to_le_bytes());
-        
-        // Reserved (3 bytes)
-        result.extend_from_slice(&[0u8; 3]);
-        
-        // Payload
-        result.extend_from_slice(&self.payload);
-        
-        result
+        let header = FrameHeaderWire {
+            frame_type: self.frame_type as u8,
+            sequence: self.sequence,
+            payload_len: self.payload.len() as u32,
+            reserved: [0u8; 3],
+        };
+        let mut writer = Writer::with_buffer(pool::take(FRAME_HEADER_SIZE + self.payload.len()));
+        header.encode_into(&mut writer).expect("fixed-size header");
+        writer.put(&self.payload);
+        writer.finish()
     }
     
     /// Decode frame from bytes
-    pub fn decode(data: &[u8]) -> Result<Self, &'static str> {
-        if data.len() < FRAME_HEADER_SIZE {
-            return Err("Frame too short");
-        }
-        
-        let frame_type = FrameType::from(data[0]);
-        let sequence = u64::from_le_bytes([
-            data[1], data[2], data[3], data[4],
-            data[5], data[6], data[7], data[8],
-        ]);
-        let payload_len = u32::from_le_bytes([
-            data[9], data[10], data[11], data[12],
-        ]) as usize;
-        
-        if data.len() < FRAME_HEADER_SIZE + payload_len {
-            return Err("Incomplete frame");
-        }
-        
-        let payload = data[FRAME_HEADER_SIZE..FRAME_HEADER_SIZE + payload_len].to_vec();
+    pub fn decode(data: &[u8]) -> Result<Self, AegisQError> {
+        let header = FrameHeader::decode(data)?;
+        let mut payload = pool::take(header.payload_len);
+        payload.extend_from_slice(&data[header.payload_range()]);
         
         Ok(Self {
-            frame_type,
+            frame_type: header.frame_type,
             payload,
-            sequence,
+            sequence: header.sequence,
         })
     }
     
@@ -106,11 +137,13 @@ impl Frame {
             n
         };
         
-        self.payload = aegis_q_encrypt(key, &nonce_with_seq, &self.payload);
+        let ciphertext = aegis_q_encrypt(key, &nonce_with_seq, &self.payload);
+        // The plaintext buffer is freed here; don't leave it behind
+        zeroize(&mut std::mem::replace(&mut self.payload, ciphertext));
     }
     
     /// Decrypt frame payload
-    pub fn decrypt(&mut self, key: &[u8], nonce: &[u8]) -> Result<(), &'static str> {
+    pub fn decrypt(&mut self, key: &[u8], nonce: &[u8]) -> Result<(), AegisQError> {
         let nonce_with_seq = {
             let mut n = nonce.to_vec();
             n.extend_from_slice(&self.sequence.to_le_bytes());
@@ -122,6 +155,24 @@ impl Frame {
     }
 }
 
+/// Decrypt an encoded frame's payload in place within the receive buffer
+///
+/// Returns the header and a view of the plaintext inside `data`; no payload
+/// copy is made.
+pub fn decrypt_frame_in_place<'a>(
+    data: &'a mut [u8],
+    key: &[u8],
+    nonce: &[u8],
+) -> Result<(FrameHeader, &'a mut [u8]), AegisQError> {
+    let header = FrameHeader::decode(data)?;
+    
+    let mut nonce_with_seq = nonce.to_vec();
+    nonce_with_seq.extend_from_slice(&header.sequence.to_le_bytes());
+    
+    let plaintext = aegis_q_decrypt_in_place(key, &nonce_with_seq, &mut data[header.payload_range()])?;
+    Ok((header, plaintext))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -141,5 +192,18 @@ mod tests {
         assert_eq!(frame.payload, decoded.payload);
         assert_eq!(frame.sequence, decoded.sequence);
     }
+    
+    #[test]
+    fn test_frame_decrypt_in_place() {
+        let key = b"frame-key-123456789012345678901234567890";
+        let nonce = b"frame-nonce-1234";
+        
+        let mut frame = Frame::new(FrameType::Data, b"in place".to_vec(), 7);
+        frame.encrypt(key, nonce);
+        let mut buffer = frame.encode();
+        
+        let (header, plaintext) = decrypt_frame_in_place(&mut buffer, key, nonce).unwrap();
+        assert_eq!(header.sequence, 7);
+        assert_eq!(plaintext, b"in place");
+    }
 }
-
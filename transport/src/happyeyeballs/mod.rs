@@ -0,0 +1,211 @@
+//! Happy Eyeballs
+//!
+//! Dual-stack connection racing (RFC 8305). Resolved addresses are
+//! interleaved by family, starting with the preferred one (IPv6 by
+//! default), and connection attempts start one after another with a
+//! staggered delay instead of waiting for each to time out. An attempt
+//! that fails early lets the next one start immediately. The first
+//! attempt to connect wins and every other attempt still running is
+//! reported for cancellation, so a broken IPv6 network costs one attempt
+//! delay rather than a full connect timeout.
+//!
+//! Sans-IO: the caller opens and closes the sockets and drives the race
+//! with its own clock (`Duration` since the race began).
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// Default delay between connection attempts (RFC 8305 §5)
+pub const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Lower bound for the attempt delay
+pub const MIN_CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(100);
+
+/// Upper bound for the attempt delay
+pub const MAX_CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_secs(2);
+
+/// Interleave addresses by family, preferred family first (RFC 8305 §4)
+pub fn sort_addresses(addresses: &[SocketAddr], prefer_ipv6: bool) -> Vec<SocketAddr> {
+    let (preferred, other): (Vec<SocketAddr>, Vec<SocketAddr>) = addresses.iter().partition(|a| a.is_ipv6() == prefer_ipv6);
+    let mut sorted = Vec::with_capacity(addresses.len());
+    let mut preferred = preferred.into_iter();
+    let mut other = other.into_iter();
+    loop {
+        match (preferred.next(), other.next()) {
+            (None, None) => break,
+            (a, b) => sorted.extend(a.into_iter().chain(b)),
+        }
+    }
+    sorted
+}
+
+/// Connection attempt the caller should start
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Attempt {
+    pub index: usize,
+    pub address: SocketAddr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AttemptState {
+    Pending,
+    Running,
+    Failed,
+    Cancelled,
+    Connected,
+}
+
+/// State of one connection race
+#[derive(Debug, Clone)]
+pub struct ConnectionRace {
+    addresses: Vec<SocketAddr>,
+    states: Vec<AttemptState>,
+    attempt_delay: Duration,
+    next_start: Duration,
+    winner: Option<usize>,
+}
+
+impl ConnectionRace {
+    /// Race over resolved addresses (IPv6 preferred)
+    pub fn new(addresses: &[SocketAddr]) -> Self {
+        let addresses = sort_addresses(addresses, true);
+        Self {
+            states: vec![AttemptState::Pending; addresses.len()],
+            addresses,
+            attempt_delay: CONNECTION_ATTEMPT_DELAY,
+            next_start: Duration::ZERO,
+            winner: None,
+        }
+    }
+
+    /// Override the attempt delay (clamped to the RFC 8305 bounds)
+    pub fn attempt_delay(mut self, delay: Duration) -> Self {
+        self.attempt_delay = delay.clamp(MIN_CONNECTION_ATTEMPT_DELAY, MAX_CONNECTION_ATTEMPT_DELAY);
+        self
+    }
+
+    /// Addresses in attempt order
+    pub fn addresses(&self) -> &[SocketAddr] {
+        &self.addresses
+    }
+
+    /// Next attempt to start at `now`, if one is due
+    pub fn poll(&mut self, now: Duration) -> Option<Attempt> {
+        if self.winner.is_some() || now < self.next_start {
+            return None;
+        }
+        let index = self.states.iter().position(|s| *s == AttemptState::Pending)?;
+        self.states[index] = AttemptState::Running;
+        self.next_start = now + self.attempt_delay;
+        Some(Attempt {
+            index,
+            address: self.addresses[index],
+        })
+    }
+
+    /// When the next attempt becomes due (for the caller's timer)
+    pub fn next_deadline(&self) -> Option<Duration> {
+        if self.winner.is_some() || !self.states.contains(&AttemptState::Pending) {
+            return None;
+        }
+        Some(self.next_start)
+    }
+
+    /// An attempt failed; the next one may start right away
+    pub fn on_failed(&mut self, index: usize, now: Duration) {
+        if self.states.get(index) == Some(&AttemptState::Running) {
+            self.states[index] = AttemptState::Failed;
+            self.next_start = self.next_start.min(now);
+        }
+    }
+
+    /// An attempt connected; returns the running attempts to cancel
+    pub fn on_connected(&mut self, index: usize) -> Vec<Attempt> {
+        if self.winner.is_some() || self.states.get(index) != Some(&AttemptState::Running) {
+            return Vec::new();
+        }
+        self.winner = Some(index);
+        self.states[index] = AttemptState::Connected;
+
+        let mut losers = Vec::new();
+        for (i, state) in self.states.iter_mut().enumerate() {
+            match *state {
+                AttemptState::Running => {
+                    *state = AttemptState::Cancelled;
+                    losers.push(Attempt {
+                        index: i,
+                        address: self.addresses[i],
+                    });
+                }
+                AttemptState::Pending => *state = AttemptState::Cancelled,
+                _ => {}
+            }
+        }
+        losers
+    }
+
+    /// Winning attempt, once connected
+    pub fn winner(&self) -> Option<Attempt> {
+        self.winner.map(|index| Attempt {
+            index,
+            address: self.addresses[index],
+        })
+    }
+
+    /// Every attempt failed
+    pub fn is_exhausted(&self) -> bool {
+        self.states.iter().all(|s| *s == AttemptState::Failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    fn ms(value: u64) -> Duration {
+        Duration::from_millis(value)
+    }
+
+    #[test]
+    fn test_address_interleaving() {
+        let addresses = [addr("192.0.2.1:443"), addr("192.0.2.2:443"), addr("[2001:db8::1]:443"), addr("[2001:db8::2]:443"), addr("[2001:db8::3]:443")];
+        let sorted = sort_addresses(&addresses, true);
+        let families: Vec<bool> = sorted.iter().map(|a| a.is_ipv6()).collect();
+        assert_eq!(families, vec![true, false, true, false, true]);
+        assert!(!sort_addresses(&addresses, false)[0].is_ipv6());
+    }
+
+    #[test]
+    fn test_broken_ipv6_falls_back_after_delay() {
+        let mut race = ConnectionRace::new(&[addr("192.0.2.1:443"), addr("[2001:db8::1]:443")]);
+        let v6 = race.poll(ms(0)).unwrap();
+        assert!(v6.address.is_ipv6());
+
+        // IPv6 black-holed: IPv4 starts after the attempt delay, not a connect timeout
+        assert!(race.poll(ms(100)).is_none());
+        assert_eq!(race.next_deadline(), Some(CONNECTION_ATTEMPT_DELAY));
+        let v4 = race.poll(CONNECTION_ATTEMPT_DELAY).unwrap();
+        assert!(!v4.address.is_ipv6());
+
+        let losers = race.on_connected(v4.index);
+        assert_eq!(losers, vec![v6]);
+        assert_eq!(race.winner(), Some(v4));
+        assert!(race.poll(ms(1000)).is_none());
+    }
+
+    #[test]
+    fn test_early_failure_starts_next_immediately() {
+        let mut race = ConnectionRace::new(&[addr("[2001:db8::1]:443"), addr("192.0.2.1:443")]).attempt_delay(ms(10));
+        let first = race.poll(ms(0)).unwrap();
+        race.on_failed(first.index, ms(5));
+        let second = race.poll(ms(5)).unwrap();
+        race.on_failed(second.index, ms(6));
+        assert!(race.poll(ms(6)).is_none());
+        assert!(race.is_exhausted());
+        assert!(race.winner().is_none());
+    }
+}
@@ -0,0 +1,303 @@
+//! ClientHello Shaping
+//!
+//! Encoding of the client's first handshake message with anti-fingerprinting
+//! measures, so passive observers cannot pin the protocol (or a client
+//! build) by the exact extension list, order and message length:
+//!
+//! - GREASE: reserved extension types (`0x0a0a`, `0x1a1a`, ... `0xfafa`, as
+//!   in RFC 8701) with short random bodies; servers must ignore them, which
+//!   also keeps them tolerant to extensions they do not know.
+//! - Padding: a padding extension (`0x0015`) of random length.
+//! - Shuffling: extensions are sent in random order.
+//!
+//! `HelloShaping::disabled()` turns all of it off and `with_seed` draws the
+//! random choices from a seed, so test vectors stay deterministic.
+//! `ClientHello::decode` drops GREASE and padding and rejects duplicates.
+//!
+//! The byte layout is `utils::wire::ClientHelloWire`.
+
+use aegis_q_core::AegisQError;
+use utils::kdf::KeyedKdf;
+use utils::rng::random_bytes;
+use utils::wire::{ClientHelloWire, HandshakeExtensionWire, Wire};
+
+/// ClientHello format version
+pub const HELLO_VERSION: u16 = 1;
+
+/// Padding extension type (as in TLS)
+pub const PADDING_EXTENSION_TYPE: u16 = 0x0015;
+
+/// GREASE extension types
+pub const GREASE_VALUES: [u16; 16] = [
+    0x0a0a, 0x1a1a, 0x2a2a, 0x3a3a, 0x4a4a, 0x5a5a, 0x6a6a, 0x7a7a, 0x8a8a, 0x9a9a, 0xaaaa, 0xbaba, 0xcaca, 0xdada, 0xeaea, 0xfafa,
+];
+
+/// Whether `extension_type` is a GREASE value
+pub fn is_grease(extension_type: u16) -> bool {
+    extension_type & 0x0f0f == 0x0a0a && extension_type >> 8 == extension_type & 0xff
+}
+
+/// Anti-fingerprinting toggles
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HelloShaping {
+    /// Number of GREASE extensions inserted
+    pub grease_extensions: usize,
+    /// Random padding length range (inclusive); `None` sends no padding
+    pub padding: Option<(u16, u16)>,
+    /// Send extensions in random order
+    pub shuffle: bool,
+    /// Draw random choices from this seed instead of the system RNG
+    pub seed: Option<[u8; 32]>,
+}
+
+impl Default for HelloShaping {
+    /// Two GREASE extensions, 0..=256 bytes of padding, shuffled
+    fn default() -> Self {
+        Self {
+            grease_extensions: 2,
+            padding: Some((0, 256)),
+            shuffle: true,
+            seed: None,
+        }
+    }
+}
+
+impl HelloShaping {
+    /// No GREASE, padding or shuffling: the canonical encoding
+    pub fn disabled() -> Self {
+        Self {
+            grease_extensions: 0,
+            padding: None,
+            shuffle: false,
+            seed: None,
+        }
+    }
+
+    /// Reproducible random choices (test vectors)
+    pub fn with_seed(mut self, seed: [u8; 32]) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+/// Client's first handshake message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientHello {
+    pub random: [u8; 32],
+    /// `(type, body)` in the order they were added
+    pub extensions: Vec<(u16, Vec<u8>)>,
+}
+
+impl ClientHello {
+    /// Hello with a fresh random value
+    pub fn new() -> Self {
+        let mut random = [0u8; 32];
+        random.copy_from_slice(&random_bytes(32));
+        Self {
+            random,
+            extensions: Vec::new(),
+        }
+    }
+
+    /// Add an extension; GREASE and padding types are reserved for `encode`
+    pub fn push(&mut self, extension_type: u16, body: Vec<u8>) -> Result<(), AegisQError> {
+        if is_grease(extension_type) || extension_type == PADDING_EXTENSION_TYPE {
+            return Err(AegisQError::InvalidInput("Reserved extension type"));
+        }
+        if self.extension(extension_type).is_some() {
+            return Err(AegisQError::InvalidInput("Duplicate extension"));
+        }
+        if body.len() > u16::MAX as usize {
+            return Err(AegisQError::InvalidLength("Extension too long"));
+        }
+        self.extensions.push((extension_type, body));
+        Ok(())
+    }
+
+    /// Add an encoded `type || length || body` extension (e.g. from
+    /// `encode_auth_extension`)
+    pub fn push_encoded(&mut self, extension: &[u8]) -> Result<(), AegisQError> {
+        let extension = HandshakeExtensionWire::from_wire(extension).map_err(AegisQError::Serialization)?;
+        self.push(extension.extension_type, extension.body)
+    }
+
+    /// Body of the extension of `extension_type`
+    pub fn extension(&self, extension_type: u16) -> Option<&[u8]> {
+        self.extensions
+            .iter()
+            .find(|(ty, _)| *ty == extension_type)
+            .map(|(_, body)| body.as_slice())
+    }
+
+    /// Encode with the given anti-fingerprinting measures
+    pub fn encode(&self, shaping: &HelloShaping) -> Vec<u8> {
+        let mut random = ShapingRandom::new(shaping.seed.as_ref());
+        let mut extensions: Vec<HandshakeExtensionWire> = self
+            .extensions
+            .iter()
+            .map(|(ty, body)| HandshakeExtensionWire {
+                extension_type: *ty,
+                body: body.clone(),
+            })
+            .collect();
+
+        for _ in 0..shaping.grease_extensions {
+            let extension_type = GREASE_VALUES[random.below(GREASE_VALUES.len())];
+            let body_len = random.below(5);
+            let body = random.bytes(body_len);
+            extensions.push(HandshakeExtensionWire { extension_type, body });
+        }
+        if shaping.shuffle {
+            for i in (1..extensions.len()).rev() {
+                extensions.swap(i, random.below(i + 1));
+            }
+        }
+        // Padding is always last, like the TLS padding extension
+        if let Some((min, max)) = shaping.padding {
+            let (min, max) = (min.min(max) as usize, min.max(max) as usize);
+            let len = min + random.below(max - min + 1);
+            extensions.push(HandshakeExtensionWire {
+                extension_type: PADDING_EXTENSION_TYPE,
+                body: vec![0u8; len],
+            });
+        }
+
+        ClientHelloWire {
+            version: HELLO_VERSION,
+            random: self.random,
+            extensions,
+        }
+        .to_wire()
+        .expect("extension lengths checked in push")
+    }
+
+    /// Decode, dropping GREASE and padding extensions
+    pub fn decode(bytes: &[u8]) -> Result<Self, AegisQError> {
+        let wire = ClientHelloWire::from_wire(bytes).map_err(AegisQError::Serialization)?;
+        if wire.version != HELLO_VERSION {
+            return Err(AegisQError::Unsupported("Unsupported ClientHello version"));
+        }
+        let mut hello = Self {
+            random: wire.random,
+            extensions: Vec::new(),
+        };
+        for extension in wire.extensions {
+            if is_grease(extension.extension_type) || extension.extension_type == PADDING_EXTENSION_TYPE {
+                continue;
+            }
+            if hello.extension(extension.extension_type).is_some() {
+                return Err(AegisQError::Protocol("Duplicate extension in ClientHello"));
+            }
+            hello.extensions.push((extension.extension_type, extension.body));
+        }
+        Ok(hello)
+    }
+}
+
+impl Default for ClientHello {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Random choices: system RNG, or a SHAKE stream over a seed
+struct ShapingRandom {
+    seeded: Option<KeyedKdf>,
+    counter: u64,
+}
+
+impl ShapingRandom {
+    fn new(seed: Option<&[u8; 32]>) -> Self {
+        Self {
+            seeded: seed.map(|seed| KeyedKdf::new(b"aegis-q-hello-shaping", seed)),
+            counter: 0,
+        }
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        let Some(kdf) = &self.seeded else {
+            return random_bytes(len);
+        };
+        let mut out = vec![0u8; len];
+        kdf.fill(&self.counter.to_be_bytes(), &mut out);
+        self.counter += 1;
+        out
+    }
+
+    /// Uniform value in `0..bound` (`bound` is small, the bias of 64-bit
+    /// reduction is negligible)
+    fn below(&mut self, bound: usize) -> usize {
+        let bytes = self.bytes(8);
+        (u64::from_be_bytes(bytes.try_into().expect("8 bytes")) % bound as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hello() -> ClientHello {
+        let mut hello = ClientHello {
+            random: [7u8; 32],
+            extensions: Vec::new(),
+        };
+        hello.push(0x0001, b"suites".to_vec()).unwrap();
+        hello.push(0x0002, b"key share".to_vec()).unwrap();
+        hello.push(crate::auth::AUTH_EXTENSION_TYPE, b"credentials".to_vec()).unwrap();
+        hello
+    }
+
+    #[test]
+    fn test_shaped_hello_decodes_to_same_extensions() {
+        let hello = hello();
+        let canonical = hello.encode(&HelloShaping::disabled());
+        assert_eq!(ClientHello::decode(&canonical).unwrap(), hello);
+
+        let shaped = hello.encode(&HelloShaping::default());
+        let decoded = ClientHello::decode(&shaped).unwrap();
+        assert_eq!(decoded.random, hello.random);
+        for (ty, body) in &hello.extensions {
+            assert_eq!(decoded.extension(*ty), Some(body.as_slice()));
+        }
+        assert_eq!(decoded.extensions.len(), 3);
+        assert!(shaped.len() > canonical.len());
+    }
+
+    #[test]
+    fn test_seeded_shaping_is_deterministic() {
+        let hello = hello();
+        let shaping = HelloShaping::default().with_seed([1u8; 32]);
+        assert_eq!(hello.encode(&shaping), hello.encode(&shaping));
+        assert_ne!(hello.encode(&shaping), hello.encode(&HelloShaping::default().with_seed([2u8; 32])));
+        assert_eq!(hello.encode(&HelloShaping::disabled()), hello.encode(&HelloShaping::disabled()));
+
+        // Different seeds give different lengths, orders and GREASE values
+        let encodings: std::collections::HashSet<Vec<u8>> =
+            (0u8..8).map(|seed| hello.encode(&HelloShaping::default().with_seed([seed; 32]))).collect();
+        assert_eq!(encodings.len(), 8);
+    }
+
+    #[test]
+    fn test_reserved_and_duplicate_extensions() {
+        assert!(GREASE_VALUES.iter().all(|&value| is_grease(value)));
+        assert!(!is_grease(0x0a1a) && !is_grease(0x0015));
+
+        let mut hello = hello();
+        assert!(hello.push(0x1a1a, Vec::new()).is_err());
+        assert!(hello.push(PADDING_EXTENSION_TYPE, Vec::new()).is_err());
+        assert!(hello.push(0x0001, Vec::new()).is_err());
+
+        let duplicated = ClientHelloWire {
+            version: HELLO_VERSION,
+            random: [0u8; 32],
+            extensions: vec![
+                HandshakeExtensionWire { extension_type: 0x0001, body: Vec::new() },
+                HandshakeExtensionWire { extension_type: 0x0001, body: Vec::new() },
+            ],
+        };
+        assert_eq!(
+            ClientHello::decode(&duplicated.to_wire().unwrap()),
+            Err(AegisQError::Protocol("Duplicate extension in ClientHello"))
+        );
+    }
+}
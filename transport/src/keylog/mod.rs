@@ -0,0 +1,99 @@
+//! Traffic Secret Logging (feature `keylog`)
+//!
+//! SSLKEYLOGFILE-style debug hook: when the crate is built with the
+//! `keylog` feature AND the `AEGISQ_KEYLOGFILE` environment variable names
+//! a file, the traffic secrets of every new `VpnSession` are appended to
+//! it. Default builds contain no trace of this code path.
+//!
+//! Each line is `AEGISQ_TRAFFIC_SECRETS <session_id> <nonce> <send_key>
+//! <recv_key>` (hex, see `capture`), readable by `decrypt-capture` and by
+//! dissectors. Anyone with the file can decrypt the logged sessions: never
+//! enable this outside test environments.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+use crate::capture::SessionSecrets;
+
+/// Environment variable naming the log file
+pub const KEYLOG_ENV: &str = "AEGISQ_KEYLOGFILE";
+
+/// Append-only secrets log
+#[derive(Debug)]
+pub struct KeyLog {
+    file: Mutex<File>,
+}
+
+impl KeyLog {
+    /// Open (or create) a log file for appending
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut options = OpenOptions::new();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        Ok(Self {
+            file: Mutex::new(options.open(path)?),
+        })
+    }
+
+    /// Append one session's secrets
+    pub fn log(&self, secrets: &SessionSecrets) -> io::Result<()> {
+        let mut file = self.file.lock().expect("keylog lock poisoned");
+        writeln!(file, "{}", secrets.to_line())
+    }
+}
+
+/// Process-wide log from `AEGISQ_KEYLOGFILE`, opened on first use
+pub fn global() -> Option<&'static KeyLog> {
+    static GLOBAL: OnceLock<Option<KeyLog>> = OnceLock::new();
+    GLOBAL
+        .get_or_init(|| {
+            let path = std::env::var_os(KEYLOG_ENV)?;
+            match KeyLog::open(&path) {
+                Ok(log) => {
+                    eprintln!("aegis-q: WARNING: logging traffic secrets to {}", Path::new(&path).display());
+                    Some(log)
+                }
+                Err(e) => {
+                    eprintln!("aegis-q: cannot open {}: {}", KEYLOG_ENV, e);
+                    None
+                }
+            }
+        })
+        .as_ref()
+}
+
+/// Log a session's secrets if the global log is enabled
+pub(crate) fn log_session(secrets: &SessionSecrets) {
+    if let Some(log) = global() {
+        // Best effort: a debug aid must not break the session
+        let _ = log.log(secrets);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vpn::VpnSession;
+
+    #[test]
+    fn test_keylog_lines() {
+        let path = std::env::temp_dir().join(format!("aegisq-keylog-{}.txt", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let log = KeyLog::open(&path).unwrap();
+
+        let a = VpnSession::from_keys(b"key-a", b"key-b", b"keylog-nonce-001").export_secrets();
+        let b = VpnSession::from_keys(b"key-c", b"key-d", b"keylog-nonce-002").export_secrets();
+        log.log(&a).unwrap();
+        log.log(&b).unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(SessionSecrets::parse_all(&text).unwrap(), vec![a, b]);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,290 @@
+//! Server Key Rotation
+//!
+//! Holds the server's static handshake keys so a new key can be installed
+//! without restarting. After `rotate`, new handshakes use the new key while
+//! the previous one stays usable for an overlap window, so clients that
+//! cached it can still connect. Established sessions have their own
+//! traffic keys and are unaffected by rotation.
+//!
+//! A retiring key is dropped (and zeroized) once the overlap window has
+//! passed and no open session was established with it. `KeyEvent`s report
+//! rotation and retirement for logging.
+//!
+//! With a KMS, server keys are generated locally and stored only wrapped
+//! (`generate_wrapped_key`); `from_wrapped` and `rotate_wrapped` unwrap
+//! them through the KMS when they are installed.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use aegis_q_core::kms::{generate_data_key, unwrap_data_key, Kms, WrappedKey};
+use aegis_q_core::AegisQError;
+use sha3::{Digest, Sha3_256};
+use utils::keys::TypedKey;
+use utils::memory::zeroize;
+
+use crate::vpn::Handshake;
+
+/// Length of a key identifier
+pub const KEY_ID_SIZE: usize = 8;
+
+/// Default time an old key keeps accepting handshakes
+pub const DEFAULT_OVERLAP_MS: u64 = 24 * 3_600_000;
+
+/// KMS wrap context of server keys
+pub const KMS_CONTEXT: &[u8] = b"aegis-q-server-key";
+
+/// Generate a server key; only its KMS-wrapped form is returned, for storage
+pub fn generate_wrapped_key(kms: &dyn Kms) -> Result<WrappedKey, AegisQError> {
+    generate_data_key(kms, KMS_CONTEXT).map(|(_, wrapped)| wrapped)
+}
+
+/// Short public identifier of a server key
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyId([u8; KEY_ID_SIZE]);
+
+impl KeyId {
+    /// Identifier of a key
+    pub fn of(key: &[u8]) -> Self {
+        let mut hasher = Sha3_256::new();
+        hasher.update(b"aegis-q-transport-key-id");
+        hasher.update(key);
+        let mut id = [0u8; KEY_ID_SIZE];
+        id.copy_from_slice(&hasher.finalize()[..KEY_ID_SIZE]);
+        Self(id)
+    }
+
+    /// Identifier from its wire bytes
+    pub fn from_bytes(bytes: [u8; KEY_ID_SIZE]) -> Self {
+        Self(bytes)
+    }
+
+    /// Raw bytes
+    pub fn as_bytes(&self) -> &[u8; KEY_ID_SIZE] {
+        &self.0
+    }
+}
+
+impl fmt::Display for KeyId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in self.0 {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for KeyId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "KeyId({})", self)
+    }
+}
+
+/// Key lifecycle change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyEvent {
+    /// `new` became active; `old` accepts handshakes until `overlap_until_ms`
+    Rotated { old: KeyId, new: KeyId, overlap_until_ms: u64 },
+    /// Key no longer accepts handshakes and has no sessions left
+    Retired { key: KeyId },
+}
+
+struct ServerKey {
+    material: Vec<u8>,
+    /// None for the active key
+    retiring_at: Option<u64>,
+    sessions: usize,
+}
+
+impl Drop for ServerKey {
+    fn drop(&mut self) {
+        zeroize(&mut self.material);
+    }
+}
+
+/// Active server key plus keys being phased out
+pub struct ServerKeyring {
+    active: KeyId,
+    keys: HashMap<KeyId, ServerKey>,
+    overlap_ms: u64,
+    events: Vec<KeyEvent>,
+}
+
+impl ServerKeyring {
+    /// Keyring with one active key
+    pub fn new(key: &[u8], overlap_ms: u64) -> Self {
+        let active = KeyId::of(key);
+        let mut keys = HashMap::new();
+        keys.insert(active, ServerKey {
+            material: key.to_vec(),
+            retiring_at: None,
+            sessions: 0,
+        });
+        Self {
+            active,
+            keys,
+            overlap_ms,
+            events: Vec::new(),
+        }
+    }
+
+    /// Keyring whose active key is unwrapped by `kms`
+    pub fn from_wrapped(kms: &dyn Kms, wrapped: &WrappedKey, overlap_ms: u64) -> Result<Self, AegisQError> {
+        let key = unwrap_data_key(kms, wrapped, KMS_CONTEXT)?;
+        Ok(Self::new(key.as_bytes(), overlap_ms))
+    }
+
+    /// Key used for new handshakes
+    pub fn active(&self) -> KeyId {
+        self.active
+    }
+
+    /// All keys still accepting handshakes or holding sessions
+    pub fn key_ids(&self) -> Vec<KeyId> {
+        self.keys.keys().copied().collect()
+    }
+
+    /// Install a new active key; the current one starts retiring
+    pub fn rotate(&mut self, key: &[u8], now_ms: u64) -> Result<KeyId, AegisQError> {
+        let new = KeyId::of(key);
+        if self.keys.contains_key(&new) {
+            return Err(AegisQError::InvalidInput("Key already in keyring"));
+        }
+        let old = self.active;
+        let overlap_until_ms = now_ms.saturating_add(self.overlap_ms);
+        if let Some(previous) = self.keys.get_mut(&old) {
+            previous.retiring_at = Some(overlap_until_ms);
+        }
+        self.keys.insert(new, ServerKey {
+            material: key.to_vec(),
+            retiring_at: None,
+            sessions: 0,
+        });
+        self.active = new;
+        self.events.push(KeyEvent::Rotated { old, new, overlap_until_ms });
+        Ok(new)
+    }
+
+    /// `rotate` to a key unwrapped by `kms`
+    pub fn rotate_wrapped(&mut self, kms: &dyn Kms, wrapped: &WrappedKey, now_ms: u64) -> Result<KeyId, AegisQError> {
+        let key = unwrap_data_key(kms, wrapped, KMS_CONTEXT)?;
+        self.rotate(key.as_bytes(), now_ms)
+    }
+
+    /// Server side of a handshake
+    ///
+    /// `requested` is the key the client expects (from its cached server
+    /// key); `None` selects the active key. Retiring keys are accepted only
+    /// within the overlap window. The session is counted against the key
+    /// until `close_session`.
+    pub fn accept(&mut self, client_key: &[u8], requested: Option<KeyId>, now_ms: u64) -> Result<(Handshake, KeyId), AegisQError> {
+        let id = requested.unwrap_or(self.active);
+        let key = self.keys.get_mut(&id).ok_or(AegisQError::NotFound("Unknown server key"))?;
+        if key.retiring_at.is_some_and(|until| now_ms >= until) {
+            return Err(AegisQError::Protocol("Server key retired"));
+        }
+        key.sessions += 1;
+        Ok((Handshake::perform(client_key, &key.material), id))
+    }
+
+    /// A session established with `key` has ended
+    pub fn close_session(&mut self, key: KeyId) {
+        if let Some(key) = self.keys.get_mut(&key) {
+            key.sessions = key.sessions.saturating_sub(1);
+        }
+    }
+
+    /// Drop retiring keys whose window has passed and that have no sessions
+    pub fn expire(&mut self, now_ms: u64) {
+        let events = &mut self.events;
+        self.keys.retain(|id, key| {
+            let retired = key.retiring_at.is_some_and(|until| now_ms >= until) && key.sessions == 0;
+            if retired {
+                events.push(KeyEvent::Retired { key: *id });
+            }
+            !retired
+        });
+    }
+
+    /// Take queued rotation events
+    pub fn drain_events(&mut self) -> Vec<KeyEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+impl fmt::Debug for ServerKeyring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServerKeyring")
+            .field("active", &self.active)
+            .field("keys", &self.keys.len())
+            .field("overlap_ms", &self.overlap_ms)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotation_overlap() {
+        let mut keyring = ServerKeyring::new(b"server-key-1", 1_000);
+        let old = keyring.active();
+
+        // Session on the old key survives rotation
+        let (hs_old, used) = keyring.accept(b"client-a", None, 0).unwrap();
+        assert_eq!(used, old);
+        let new = keyring.rotate(b"server-key-2", 100).unwrap();
+        assert_eq!(keyring.active(), new);
+        assert_eq!(keyring.drain_events(), vec![KeyEvent::Rotated { old, new, overlap_until_ms: 1_100 }]);
+
+        // New handshakes prefer the new key, cached clients may still use the old one
+        let (hs_new, used) = keyring.accept(b"client-a", None, 200).unwrap();
+        assert_eq!(used, new);
+        assert_ne!(hs_new.shared_secret, hs_old.shared_secret);
+        let (hs_cached, _) = keyring.accept(b"client-a", Some(old), 200).unwrap();
+        assert_eq!(hs_cached.shared_secret, hs_old.shared_secret);
+        assert!(keyring.accept(b"client-b", Some(old), 1_100).is_err());
+        assert!(keyring.rotate(b"server-key-2", 300).is_err());
+    }
+
+    #[test]
+    fn test_retires_after_sessions_close() {
+        let mut keyring = ServerKeyring::new(b"server-key-1", 1_000);
+        let old = keyring.active();
+        keyring.accept(b"client", None, 0).unwrap();
+        keyring.rotate(b"server-key-2", 0).unwrap();
+        keyring.drain_events();
+
+        keyring.expire(5_000);
+        assert!(keyring.drain_events().is_empty());
+        assert_eq!(keyring.key_ids().len(), 2);
+
+        keyring.close_session(old);
+        keyring.expire(5_000);
+        assert_eq!(keyring.drain_events(), vec![KeyEvent::Retired { key: old }]);
+        assert_eq!(keyring.key_ids(), vec![keyring.active()]);
+        assert_eq!(KeyId::of(b"server-key-2"), keyring.active());
+    }
+
+    #[test]
+    fn test_kms_wrapped_keys() {
+        use aegis_q_core::LocalKms;
+        use utils::keys::EncryptionKey;
+
+        let kms = LocalKms::new("server-master", EncryptionKey::from_bytes(b"kms-master-key-0123456789abcdef0"));
+        let first = generate_wrapped_key(&kms).unwrap();
+        let second = generate_wrapped_key(&kms).unwrap();
+
+        let mut keyring = ServerKeyring::from_wrapped(&kms, &first, 1_000).unwrap();
+        let key = unwrap_data_key(&kms, &first, KMS_CONTEXT).unwrap();
+        assert_eq!(keyring.active(), KeyId::of(key.as_bytes()));
+        let new = keyring.rotate_wrapped(&kms, &second, 0).unwrap();
+        assert_eq!(keyring.active(), new);
+        assert!(keyring.accept(b"client", None, 0).is_ok());
+
+        // A key wrapped for another purpose does not unwrap as a server key
+        let (_, config_key) = generate_data_key(&kms, b"config").unwrap();
+        assert!(keyring.rotate_wrapped(&kms, &config_key, 0).is_err());
+    }
+}
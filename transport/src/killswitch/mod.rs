@@ -0,0 +1,393 @@
+//! Kill Switch
+//!
+//! Leak protection for the VPN client: while armed, all traffic outside
+//! the tunnel is blocked except loopback, the VPN server endpoints (so the
+//! client can reconnect) and, optionally, the local network. When the
+//! session drops the tunnel interface allowance is withdrawn too, so
+//! nothing leaks until the session is back; on reconnect it is restored.
+//!
+//! Rules are generated for nftables (Linux), pf (macOS/BSD) and the
+//! Windows Filtering Platform. Applying them is platform- and privilege-
+//! specific, so it goes through the `FirewallHooks` trait the VPN binary
+//! or embedder implements (e.g. piping the script to `nft -f -`).
+
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+
+use aegis_q_core::AegisQError;
+
+use crate::splittunnel::{Cidr, LAN_RANGES};
+
+/// nftables table owned by the kill switch
+pub const NFT_TABLE: &str = "aegisq_killswitch";
+
+/// pf anchor owned by the kill switch
+pub const PF_ANCHOR: &str = "aegisq_killswitch";
+
+/// Firewall backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    /// Linux nftables
+    Nftables,
+    /// macOS / BSD packet filter
+    Pf,
+    /// Windows Filtering Platform
+    Wfp,
+}
+
+/// What the kill switch lets through
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KillSwitchConfig {
+    pub tunnel_interface: String,
+    pub server_endpoints: Vec<SocketAddr>,
+    pub allowed_networks: Vec<Cidr>,
+}
+
+impl KillSwitchConfig {
+    /// Block everything but the tunnel and its servers
+    pub fn new(tunnel_interface: impl Into<String>, server_endpoints: Vec<SocketAddr>) -> Self {
+        Self {
+            tunnel_interface: tunnel_interface.into(),
+            server_endpoints,
+            allowed_networks: Vec::new(),
+        }
+    }
+
+    /// Also allow the private LAN ranges
+    pub fn allow_lan(mut self) -> Self {
+        for cidr in LAN_RANGES {
+            self.allowed_networks.push(cidr.parse().expect("valid LAN range"));
+        }
+        self
+    }
+
+    /// Also allow a network
+    pub fn allow_network(mut self, cidr: Cidr) -> Self {
+        self.allowed_networks.push(cidr);
+        self
+    }
+
+    /// Reject values that could break out of generated rules
+    pub fn validate(&self) -> Result<(), AegisQError> {
+        let name = &self.tunnel_interface;
+        if name.is_empty()
+            || name.len() > 15
+            || !name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        {
+            return Err(AegisQError::InvalidInput("Invalid tunnel interface name"));
+        }
+        if self.server_endpoints.is_empty() {
+            return Err(AegisQError::InvalidInput("Kill switch needs at least one server endpoint"));
+        }
+        Ok(())
+    }
+}
+
+/// Generated rules for one platform
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleSet {
+    /// `nft -f` script
+    Nftables(String),
+    /// pf anchor rules (`pfctl -a aegisq_killswitch -f -`)
+    Pf(String),
+    /// Filters for `FwpmFilterAdd0`, highest weight first
+    Wfp(Vec<WfpFilter>),
+}
+
+/// WFP filter layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WfpLayer {
+    AleAuthConnectV4,
+    AleAuthConnectV6,
+}
+
+/// WFP filter action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WfpAction {
+    Permit,
+    Block,
+}
+
+/// WFP filter condition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WfpCondition {
+    /// Matches everything
+    Any,
+    Loopback,
+    InterfaceAlias(String),
+    RemoteEndpoint(SocketAddr),
+    RemoteNetwork(Cidr),
+}
+
+/// One WFP filter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WfpFilter {
+    pub layer: WfpLayer,
+    pub action: WfpAction,
+    pub weight: u8,
+    pub condition: WfpCondition,
+}
+
+impl fmt::Display for WfpFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} weight={} {:?} {:?}", self.layer, self.weight, self.action, self.condition)
+    }
+}
+
+/// Generate rules; `tunnel_up` controls whether the tunnel interface is allowed
+pub fn generate(platform: Platform, config: &KillSwitchConfig, tunnel_up: bool) -> Result<RuleSet, AegisQError> {
+    config.validate()?;
+    Ok(match platform {
+        Platform::Nftables => RuleSet::Nftables(nftables(config, tunnel_up)),
+        Platform::Pf => RuleSet::Pf(pf(config, tunnel_up)),
+        Platform::Wfp => RuleSet::Wfp(wfp(config, tunnel_up)),
+    })
+}
+
+fn ip_keyword(address: IpAddr) -> &'static str {
+    if address.is_ipv4() { "ip" } else { "ip6" }
+}
+
+fn nftables(config: &KillSwitchConfig, tunnel_up: bool) -> String {
+    let mut output = vec!["oif \"lo\" accept".to_string()];
+    let mut input = vec!["iif \"lo\" accept".to_string()];
+    if tunnel_up {
+        output.push(format!("oifname \"{}\" accept", config.tunnel_interface));
+        input.push(format!("iifname \"{}\" accept", config.tunnel_interface));
+    }
+    for endpoint in &config.server_endpoints {
+        let ip = ip_keyword(endpoint.ip());
+        output.push(format!("{} daddr {} meta l4proto {{ tcp, udp }} th dport {} accept", ip, endpoint.ip(), endpoint.port()));
+        input.push(format!("{} saddr {} meta l4proto {{ tcp, udp }} th sport {} accept", ip, endpoint.ip(), endpoint.port()));
+    }
+    for cidr in &config.allowed_networks {
+        let ip = if cidr.is_ipv4() { "ip" } else { "ip6" };
+        output.push(format!("{} daddr {} accept", ip, cidr));
+        input.push(format!("{} saddr {} accept", ip, cidr));
+    }
+
+    let chain = |name: &str, hook: &str, rules: &[String]| {
+        let mut s = format!("  chain {} {{\n    type filter hook {} priority 0; policy drop;\n", name, hook);
+        for rule in rules {
+            s.push_str(&format!("    {}\n", rule));
+        }
+        s.push_str("  }\n");
+        s
+    };
+
+    format!(
+        "table inet {table}\ndelete table inet {table}\ntable inet {table} {{\n{}{}}}\n",
+        chain("output", "output", &output),
+        chain("input", "input", &input),
+        table = NFT_TABLE,
+    )
+}
+
+fn pf(config: &KillSwitchConfig, tunnel_up: bool) -> String {
+    let mut rules = vec!["pass quick on lo0 all".to_string()];
+    if tunnel_up {
+        rules.push(format!("pass quick on {} all", config.tunnel_interface));
+    }
+    for endpoint in &config.server_endpoints {
+        let family = if endpoint.is_ipv4() { "inet" } else { "inet6" };
+        rules.push(format!("pass out quick {} proto {{ tcp udp }} to {} port {} keep state", family, endpoint.ip(), endpoint.port()));
+    }
+    for cidr in &config.allowed_networks {
+        rules.push(format!("pass quick to {} keep state", cidr));
+        rules.push(format!("pass quick from {} keep state", cidr));
+    }
+    rules.push("block drop quick all".to_string());
+    rules.join("\n") + "\n"
+}
+
+fn wfp(config: &KillSwitchConfig, tunnel_up: bool) -> Vec<WfpFilter> {
+    let mut filters = Vec::new();
+    for layer in [WfpLayer::AleAuthConnectV4, WfpLayer::AleAuthConnectV6] {
+        let is_v4 = layer == WfpLayer::AleAuthConnectV4;
+        let permit = |condition| WfpFilter {
+            layer,
+            action: WfpAction::Permit,
+            weight: 15,
+            condition,
+        };
+        filters.push(permit(WfpCondition::Loopback));
+        if tunnel_up {
+            filters.push(permit(WfpCondition::InterfaceAlias(config.tunnel_interface.clone())));
+        }
+        for endpoint in config.server_endpoints.iter().filter(|e| e.is_ipv4() == is_v4) {
+            filters.push(permit(WfpCondition::RemoteEndpoint(*endpoint)));
+        }
+        for cidr in config.allowed_networks.iter().filter(|c| c.is_ipv4() == is_v4) {
+            filters.push(permit(WfpCondition::RemoteNetwork(*cidr)));
+        }
+        filters.push(WfpFilter {
+            layer,
+            action: WfpAction::Block,
+            weight: 0,
+            condition: WfpCondition::Any,
+        });
+    }
+    filters
+}
+
+/// Platform hook that installs and removes generated rules
+pub trait FirewallHooks {
+    /// Install `rules`, replacing any previously installed kill-switch rules
+    fn apply(&mut self, rules: &RuleSet) -> Result<(), AegisQError>;
+
+    /// Remove all kill-switch rules
+    fn remove(&mut self) -> Result<(), AegisQError>;
+}
+
+/// Kill-switch state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KillSwitchState {
+    /// No rules installed
+    Disabled,
+    /// Tunnel up, non-tunnel traffic blocked
+    Armed,
+    /// Session dropped, everything but reconnects blocked
+    Blocking,
+}
+
+/// Kill switch driven by session events
+pub struct KillSwitch<H: FirewallHooks> {
+    platform: Platform,
+    config: KillSwitchConfig,
+    hooks: H,
+    state: KillSwitchState,
+}
+
+impl<H: FirewallHooks> KillSwitch<H> {
+    /// Kill switch for a platform (nothing installed yet)
+    pub fn new(platform: Platform, config: KillSwitchConfig, hooks: H) -> Result<Self, AegisQError> {
+        config.validate()?;
+        Ok(Self {
+            platform,
+            config,
+            hooks,
+            state: KillSwitchState::Disabled,
+        })
+    }
+
+    /// Current state
+    pub fn state(&self) -> KillSwitchState {
+        self.state
+    }
+
+    /// Platform hooks
+    pub fn hooks(&self) -> &H {
+        &self.hooks
+    }
+
+    /// Session is connecting or lost: block everything except the servers
+    pub fn on_disconnected(&mut self) -> Result<(), AegisQError> {
+        self.install(false)?;
+        self.state = KillSwitchState::Blocking;
+        Ok(())
+    }
+
+    /// Session (re)established: allow traffic through the tunnel
+    pub fn on_connected(&mut self) -> Result<(), AegisQError> {
+        self.install(true)?;
+        self.state = KillSwitchState::Armed;
+        Ok(())
+    }
+
+    /// User turned the kill switch off: remove all rules
+    pub fn disable(&mut self) -> Result<(), AegisQError> {
+        if self.state != KillSwitchState::Disabled {
+            self.hooks.remove()?;
+            self.state = KillSwitchState::Disabled;
+        }
+        Ok(())
+    }
+
+    fn install(&mut self, tunnel_up: bool) -> Result<(), AegisQError> {
+        let rules = generate(self.platform, &self.config, tunnel_up)?;
+        self.hooks.apply(&rules)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct RecordingHooks {
+        installed: Option<RuleSet>,
+    }
+
+    impl FirewallHooks for RecordingHooks {
+        fn apply(&mut self, rules: &RuleSet) -> Result<(), AegisQError> {
+            self.installed = Some(rules.clone());
+            Ok(())
+        }
+
+        fn remove(&mut self) -> Result<(), AegisQError> {
+            self.installed = None;
+            Ok(())
+        }
+    }
+
+    fn config() -> KillSwitchConfig {
+        KillSwitchConfig::new("aq0", vec!["203.0.113.10:443".parse().unwrap(), "[2001:db8::10]:443".parse().unwrap()])
+    }
+
+    #[test]
+    fn test_generated_rules() {
+        let RuleSet::Nftables(script) = generate(Platform::Nftables, &config().allow_lan(), true).unwrap() else {
+            panic!("expected nftables");
+        };
+        assert!(script.contains("policy drop;"));
+        assert!(script.contains("oifname \"aq0\" accept"));
+        assert!(script.contains("ip daddr 203.0.113.10 meta l4proto { tcp, udp } th dport 443 accept"));
+        assert!(script.contains("ip6 daddr 2001:db8::10"));
+        assert!(script.contains("ip daddr 192.168.0.0/16 accept"));
+
+        let RuleSet::Pf(rules) = generate(Platform::Pf, &config(), false).unwrap() else {
+            panic!("expected pf");
+        };
+        assert!(!rules.contains("aq0"));
+        assert!(rules.trim_end().ends_with("block drop quick all"));
+
+        let RuleSet::Wfp(filters) = generate(Platform::Wfp, &config(), true).unwrap() else {
+            panic!("expected wfp");
+        };
+        assert_eq!(filters.iter().filter(|f| f.action == WfpAction::Block).count(), 2);
+        assert!(filters.contains(&WfpFilter {
+            layer: WfpLayer::AleAuthConnectV6,
+            action: WfpAction::Permit,
+            weight: 15,
+            condition: WfpCondition::RemoteEndpoint("[2001:db8::10]:443".parse().unwrap()),
+        }));
+    }
+
+    #[test]
+    fn test_rejects_injection() {
+        let mut bad = config();
+        bad.tunnel_interface = "aq0\" accept; flush".to_string();
+        assert!(generate(Platform::Nftables, &bad, true).is_err());
+        assert!(KillSwitchConfig::new("aq0", Vec::new()).validate().is_err());
+    }
+
+    #[test]
+    fn test_session_lifecycle() {
+        let mut switch = KillSwitch::new(Platform::Nftables, config(), RecordingHooks::default()).unwrap();
+        switch.on_connected().unwrap();
+        assert_eq!(switch.state(), KillSwitchState::Armed);
+
+        // Session drops: tunnel interface no longer allowed
+        switch.on_disconnected().unwrap();
+        assert_eq!(switch.state(), KillSwitchState::Blocking);
+        let Some(RuleSet::Nftables(script)) = &switch.hooks().installed else {
+            panic!("rules not installed");
+        };
+        assert!(!script.contains("aq0"));
+
+        switch.on_connected().unwrap();
+        switch.disable().unwrap();
+        assert_eq!(switch.state(), KillSwitchState::Disabled);
+        assert!(switch.hooks().installed.is_none());
+    }
+}
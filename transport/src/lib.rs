@@ -1,4 +1,40 @@
 pub mod vpn;
 pub mod quic;
 pub mod framing;
-
+pub mod tls;
+pub mod session;
+pub mod filetransfer;
+pub mod flow;
+pub mod pmtu;
+pub mod ecn;
+pub mod multipath;
+pub mod happyeyeballs;
+pub mod dns;
+pub mod splittunnel;
+pub mod killswitch;
+pub mod auth;
+pub mod authguard;
+pub mod config;
+pub mod keyring;
+pub mod tickets;
+pub mod fleet;
+pub mod cid;
+pub mod hello;
+pub mod lifecycle;
+pub mod replay;
+pub mod metrics;
+pub mod tenant;
+#[cfg(feature = "capture")]
+pub mod capture;
+pub mod dissect;
+pub mod fec;
+#[cfg(any(test, feature = "testutil"))]
+pub mod testutil;
+#[cfg(unix)]
+pub mod systemd;
+#[cfg(feature = "keylog")]
+pub mod keylog;
+#[cfg(feature = "noise")]
+pub mod noise;
+#[cfg(feature = "entitlements")]
+pub mod entitlement;
@@ -0,0 +1,516 @@
+//! Connection Lifecycle
+//!
+//! Handshake, rekey and close as an explicit state machine. The transport
+//! feeds it events (application sends, received frames, timers, peer
+//! signals) and performs the actions it returns, so the ordering rules live
+//! in one place and can be checked exhaustively:
+//!
+//! - application data is only sent in `Established` or `Rekeying`; data
+//!   submitted earlier is queued and flushed when the handshake completes;
+//! - every sent frame has a fresh `(generation, epoch, sequence)` nonce
+//!   identity: the sequence restarts only with a new key epoch (rekey) or
+//!   key generation (new handshake after a peer restart);
+//! - received frames pass a per-epoch replay window, so duplicates are
+//!   dropped rather than delivered twice;
+//! - `Closed` is terminal.
+//!
+//! Resumed sessions keep their keys across a process restart, so the
+//! replay windows and send sequences must survive it too: `replay_mark`
+//! captures them and `resume` restores them (see `replay::ReplayStore`).
+//!
+//! ```text
+//! Idle --Connect--> Handshaking --HandshakeComplete--> Established <--RekeyAck-- Rekeying
+//!                        ^                                  |  --RekeyDue-->     |
+//!                        +----------PeerRestart-------------+-------------------+
+//! Established/Rekeying --Close/idle Timeout--> Closing --CloseAck/Timeout--> Closed
+//! ```
+
+/// Handshake retransmissions before giving up
+pub const MAX_HANDSHAKE_ATTEMPTS: u32 = 5;
+
+/// Rekey retransmissions before closing
+pub const MAX_REKEY_ATTEMPTS: u32 = 3;
+
+/// Replay window size (frames)
+pub const REPLAY_WINDOW: u64 = 64;
+
+/// Send sequences skipped on `resume`, covering frames sent after the
+/// mark was saved
+pub const SEND_RESERVE: u64 = 1 << 16;
+
+/// Connection phase
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+    Idle,
+    Handshaking,
+    Established,
+    /// Established, new send keys offered and not yet acknowledged
+    Rekeying,
+    Closing,
+    Closed,
+}
+
+impl Phase {
+    /// Whether application data may be sent
+    pub fn can_send(self) -> bool {
+        matches!(self, Phase::Established | Phase::Rekeying)
+    }
+
+    /// Whether `self -> next` is an edge of the state machine
+    pub fn can_transition(self, next: Phase) -> bool {
+        use Phase::*;
+        self == next
+            || matches!(
+                (self, next),
+                (Idle, Handshaking)
+                    | (Idle, Closed)
+                    | (Handshaking, Established)
+                    | (Handshaking, Closed)
+                    | (Established, Rekeying)
+                    | (Rekeying, Established)
+                    | (Established | Rekeying, Handshaking)
+                    | (Established | Rekeying, Closing)
+                    | (Closing, Closed)
+            )
+    }
+}
+
+/// Input to the state machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// Start the handshake
+    Connect,
+    /// Peer's handshake response verified
+    HandshakeComplete,
+    /// Application submits one message
+    Send,
+    /// Data frame received
+    Frame { epoch: u32, sequence: u64 },
+    /// Send keys reached their usage limit (`SessionCipher`)
+    RekeyDue,
+    /// Peer acknowledged the new send keys
+    RekeyAck,
+    /// Retransmission or idle timer fired
+    Timeout,
+    /// Peer lost its state (stateless reset or fresh handshake)
+    PeerRestart,
+    /// Application closes the connection
+    Close,
+    /// Peer acknowledged the close
+    CloseAck,
+}
+
+/// Why an event produced no effect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropReason {
+    /// Data before the handshake completed or after closing started
+    NotEstablished,
+    /// Frame already received
+    Duplicate,
+    /// Frame older than the replay window or previous epoch
+    Stale,
+    /// Frame of an epoch the peer cannot have reached
+    UnknownEpoch,
+    /// Event that does not apply to the current phase
+    Unexpected,
+}
+
+/// Output of the state machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    SendHandshake { attempt: u32 },
+    /// Encrypt and send one queued message under this nonce identity
+    SendData { generation: u32, epoch: u32, sequence: u64 },
+    /// Pass a received frame to the application
+    Deliver { generation: u32, epoch: u32, sequence: u64 },
+    /// Message queued until the handshake completes
+    Queue,
+    SendRekey { epoch: u32, attempt: u32 },
+    SendClose,
+    Drop(DropReason),
+    /// Connection finished; release its resources
+    Closed,
+}
+
+/// Receive and send high-water marks of a connection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReplayMark {
+    /// Newest peer epoch seen
+    pub recv_epoch: u32,
+    /// One past the highest sequence seen in `recv_epoch`
+    pub recv_next: u64,
+    pub send_epoch: u32,
+    /// Next sequence to send in `send_epoch`
+    pub send_next: u64,
+}
+
+impl ReplayMark {
+    /// Later of two marks, per direction
+    pub fn max(self, other: ReplayMark) -> ReplayMark {
+        let (recv_epoch, recv_next) = (self.recv_epoch, self.recv_next).max((other.recv_epoch, other.recv_next));
+        let (send_epoch, send_next) = (self.send_epoch, self.send_next).max((other.send_epoch, other.send_next));
+        ReplayMark { recv_epoch, recv_next, send_epoch, send_next }
+    }
+}
+
+/// Sliding replay window over sequence numbers of one epoch
+#[derive(Debug, Clone, Copy, Default)]
+struct ReplayWindow {
+    /// One past the highest sequence seen
+    next: u64,
+    /// Bit `i` set: sequence `next - 1 - i` seen
+    seen: u64,
+}
+
+impl ReplayWindow {
+    fn accept(&mut self, sequence: u64) -> Result<(), DropReason> {
+        if sequence >= self.next {
+            let shift = sequence - self.next + 1;
+            self.seen = if shift >= REPLAY_WINDOW { 0 } else { self.seen << shift };
+            self.seen |= 1;
+            self.next = sequence + 1;
+            return Ok(());
+        }
+        let age = self.next - 1 - sequence;
+        if age >= REPLAY_WINDOW {
+            return Err(DropReason::Stale);
+        }
+        if self.seen & (1 << age) != 0 {
+            return Err(DropReason::Duplicate);
+        }
+        self.seen |= 1 << age;
+        Ok(())
+    }
+}
+
+/// Receive side of the current and previous peer epoch
+#[derive(Debug, Clone, Default)]
+struct ReceiveWindows {
+    epoch: u32,
+    current: ReplayWindow,
+    previous: Option<ReplayWindow>,
+}
+
+impl ReceiveWindows {
+    fn accept(&mut self, epoch: u32, sequence: u64) -> Result<(), DropReason> {
+        if epoch == self.epoch {
+            self.current.accept(sequence)
+        } else if epoch.checked_add(1) == Some(self.epoch) {
+            self.previous.as_mut().ok_or(DropReason::Stale)?.accept(sequence)
+        } else if Some(epoch) == self.epoch.checked_add(1) {
+            // Peer rekeyed its send direction
+            let mut next = ReplayWindow::default();
+            next.accept(sequence)?;
+            self.previous = Some(std::mem::replace(&mut self.current, next));
+            self.epoch = epoch;
+            Ok(())
+        } else if epoch < self.epoch {
+            Err(DropReason::Stale)
+        } else {
+            Err(DropReason::UnknownEpoch)
+        }
+    }
+}
+
+/// Connection state machine
+#[derive(Debug, Clone)]
+pub struct Lifecycle {
+    phase: Phase,
+    /// Key generation: one per completed handshake
+    generation: u32,
+    /// Send key epoch within the generation
+    epoch: u32,
+    next_sequence: u64,
+    attempts: u32,
+    /// Messages submitted before the handshake completed
+    queued: usize,
+    receive: ReceiveWindows,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lifecycle {
+    pub fn new() -> Self {
+        Self {
+            phase: Phase::Idle,
+            generation: 0,
+            epoch: 0,
+            next_sequence: 0,
+            attempts: 0,
+            queued: 0,
+            receive: ReceiveWindows::default(),
+        }
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// Current key generation and send epoch
+    pub fn keys(&self) -> (u32, u32) {
+        (self.generation, self.epoch)
+    }
+
+    /// Messages waiting for the handshake
+    pub fn queued(&self) -> usize {
+        self.queued
+    }
+
+    /// High-water marks to persist for resumption
+    pub fn replay_mark(&self) -> ReplayMark {
+        ReplayMark {
+            recv_epoch: self.receive.epoch,
+            recv_next: self.receive.current.next,
+            send_epoch: self.epoch,
+            send_next: self.next_sequence,
+        }
+    }
+
+    /// Continue a resumed session from a persisted `mark`
+    ///
+    /// Call after `Connect`, which resets both directions. Every frame up
+    /// to the mark is dropped as a duplicate or stale, and sending resumes
+    /// `SEND_RESERVE` sequences past it.
+    pub fn resume(&mut self, mark: ReplayMark) {
+        self.epoch = mark.send_epoch;
+        self.next_sequence = mark.send_next.saturating_add(SEND_RESERVE);
+        self.receive = ReceiveWindows {
+            epoch: mark.recv_epoch,
+            current: ReplayWindow { next: mark.recv_next, seen: u64::MAX },
+            previous: None,
+        };
+    }
+
+    /// Apply `event`; the returned actions are to be performed in order
+    pub fn handle(&mut self, event: Event) -> Vec<Action> {
+        use Event::*;
+        use Phase::*;
+        let mut actions = Vec::new();
+        match (self.phase, event) {
+            (Closed, _) => {}
+
+            (Idle, Connect) => self.start_handshake(&mut actions),
+            (Idle | Handshaking, Send) => {
+                self.queued += 1;
+                actions.push(Action::Queue);
+            }
+            (Idle, Close) => self.finish(&mut actions),
+            (Handshaking, HandshakeComplete) => {
+                self.phase = Established;
+                for _ in 0..std::mem::take(&mut self.queued) {
+                    actions.push(self.send_data());
+                }
+            }
+            (Handshaking, Timeout) => {
+                if self.attempts >= MAX_HANDSHAKE_ATTEMPTS {
+                    self.finish(&mut actions);
+                } else {
+                    self.attempts += 1;
+                    actions.push(Action::SendHandshake { attempt: self.attempts });
+                }
+            }
+            (Handshaking, Close) => self.finish(&mut actions),
+
+            (Established | Rekeying, Send) => actions.push(self.send_data()),
+            (Established | Rekeying, Frame { epoch, sequence }) => match self.receive.accept(epoch, sequence) {
+                Ok(()) => actions.push(Action::Deliver {
+                    generation: self.generation,
+                    epoch,
+                    sequence,
+                }),
+                Err(reason) => actions.push(Action::Drop(reason)),
+            },
+            (Established, RekeyDue) => {
+                self.phase = Rekeying;
+                self.attempts = 1;
+                actions.push(Action::SendRekey {
+                    epoch: self.epoch + 1,
+                    attempt: self.attempts,
+                });
+            }
+            (Rekeying, RekeyAck) => {
+                self.phase = Established;
+                self.epoch += 1;
+                self.next_sequence = 0;
+            }
+            (Rekeying, Timeout) => {
+                if self.attempts >= MAX_REKEY_ATTEMPTS {
+                    self.start_close(&mut actions);
+                } else {
+                    self.attempts += 1;
+                    actions.push(Action::SendRekey {
+                        epoch: self.epoch + 1,
+                        attempt: self.attempts,
+                    });
+                }
+            }
+            // Idle timeout
+            (Established, Timeout) => self.start_close(&mut actions),
+            (Established | Rekeying, Close) => self.start_close(&mut actions),
+            (Handshaking | Established | Rekeying, PeerRestart) => {
+                self.generation += 1;
+                self.start_handshake(&mut actions);
+            }
+
+            (Closing, CloseAck | Timeout) => self.finish(&mut actions),
+            (Closing, Send) => actions.push(Action::Drop(DropReason::NotEstablished)),
+
+            (Idle | Handshaking, Frame { .. }) => actions.push(Action::Drop(DropReason::NotEstablished)),
+            _ => actions.push(Action::Drop(DropReason::Unexpected)),
+        }
+        actions
+    }
+
+    fn start_handshake(&mut self, actions: &mut Vec<Action>) {
+        self.phase = Phase::Handshaking;
+        self.epoch = 0;
+        self.next_sequence = 0;
+        self.receive = ReceiveWindows::default();
+        self.attempts = 1;
+        actions.push(Action::SendHandshake { attempt: self.attempts });
+    }
+
+    fn send_data(&mut self) -> Action {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        Action::SendData {
+            generation: self.generation,
+            epoch: self.epoch,
+            sequence,
+        }
+    }
+
+    fn start_close(&mut self, actions: &mut Vec<Action>) {
+        self.phase = Phase::Closing;
+        actions.push(Action::SendClose);
+    }
+
+    fn finish(&mut self, actions: &mut Vec<Action>) {
+        self.phase = Phase::Closed;
+        self.queued = 0;
+        actions.push(Action::Closed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queued_data_flushes_on_establish() {
+        let mut lifecycle = Lifecycle::new();
+        assert_eq!(lifecycle.handle(Event::Send), [Action::Queue]);
+        assert_eq!(lifecycle.handle(Event::Connect), [Action::SendHandshake { attempt: 1 }]);
+        assert_eq!(lifecycle.handle(Event::Send), [Action::Queue]);
+        assert_eq!(lifecycle.handle(Event::Frame { epoch: 0, sequence: 0 }), [Action::Drop(DropReason::NotEstablished)]);
+
+        let flushed = lifecycle.handle(Event::HandshakeComplete);
+        assert_eq!(
+            flushed,
+            [
+                Action::SendData { generation: 0, epoch: 0, sequence: 0 },
+                Action::SendData { generation: 0, epoch: 0, sequence: 1 },
+            ]
+        );
+        assert_eq!(lifecycle.queued(), 0);
+
+        lifecycle.handle(Event::RekeyDue);
+        assert_eq!(lifecycle.handle(Event::Send), [Action::SendData { generation: 0, epoch: 0, sequence: 2 }]);
+        lifecycle.handle(Event::RekeyAck);
+        assert_eq!(lifecycle.handle(Event::Send), [Action::SendData { generation: 0, epoch: 1, sequence: 0 }]);
+
+        assert_eq!(lifecycle.handle(Event::Close), [Action::SendClose]);
+        assert_eq!(lifecycle.handle(Event::CloseAck), [Action::Closed]);
+        assert!(lifecycle.handle(Event::Connect).is_empty());
+    }
+
+    #[test]
+    fn test_replay_window_across_epochs() {
+        let mut receive = ReceiveWindows::default();
+        receive.accept(0, 5).unwrap();
+        receive.accept(0, 3).unwrap();
+        assert_eq!(receive.accept(0, 5), Err(DropReason::Duplicate));
+        receive.accept(0, 100).unwrap();
+        assert_eq!(receive.accept(0, 3), Err(DropReason::Stale));
+
+        // Peer rekeys; stragglers of the previous epoch still arrive once
+        receive.accept(1, 0).unwrap();
+        receive.accept(0, 99).unwrap();
+        assert_eq!(receive.accept(0, 100), Err(DropReason::Duplicate));
+        assert_eq!(receive.accept(3, 0), Err(DropReason::UnknownEpoch));
+        receive.accept(2, 0).unwrap();
+        assert_eq!(receive.accept(0, 98), Err(DropReason::Stale));
+    }
+    #[test]
+    fn test_resume_from_mark() {
+        let mut before = Lifecycle::new();
+        before.handle(Event::Connect);
+        before.handle(Event::HandshakeComplete);
+        before.handle(Event::Frame { epoch: 1, sequence: 7 });
+        before.handle(Event::Send);
+        let mark = before.replay_mark();
+        assert_eq!(mark, ReplayMark { recv_epoch: 1, recv_next: 8, send_epoch: 0, send_next: 1 });
+
+        let mut after = Lifecycle::new();
+        after.handle(Event::Connect);
+        after.resume(mark);
+        after.handle(Event::HandshakeComplete);
+        assert_eq!(after.handle(Event::Frame { epoch: 1, sequence: 7 }), [Action::Drop(DropReason::Duplicate)]);
+        assert_eq!(after.handle(Event::Frame { epoch: 0, sequence: 9 }), [Action::Drop(DropReason::Stale)]);
+        assert_eq!(after.handle(Event::Frame { epoch: 1, sequence: 8 }), [Action::Deliver { generation: 0, epoch: 1, sequence: 8 }]);
+        assert_eq!(after.handle(Event::Send), [Action::SendData { generation: 0, epoch: 0, sequence: 1 + SEND_RESERVE }]);
+
+        let older = ReplayMark { recv_epoch: 1, recv_next: 2, send_epoch: 1, send_next: 0 };
+        assert_eq!(mark.max(older), ReplayMark { recv_epoch: 1, recv_next: 8, send_epoch: 1, send_next: 0 });
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use super::*;
+    use loom::sync::{Arc, Mutex};
+    use loom::thread;
+
+    #[test]
+    fn loom_replay_window_accept_and_advance() {
+        loom::model(|| {
+            let mut lifecycle = Lifecycle::new();
+            lifecycle.handle(Event::Connect);
+            lifecycle.handle(Event::HandshakeComplete);
+            let lifecycle = Arc::new(Mutex::new(lifecycle));
+
+            // Two receive workers race the same frame; a third sees the peer's rekey
+            let frames = [
+                Event::Frame { epoch: 0, sequence: 3 },
+                Event::Frame { epoch: 0, sequence: 3 },
+                Event::Frame { epoch: 1, sequence: 0 },
+            ];
+            let workers: Vec<_> = frames
+                .into_iter()
+                .map(|frame| {
+                    let lifecycle = lifecycle.clone();
+                    thread::spawn(move || lifecycle.lock().unwrap().handle(frame))
+                })
+                .collect();
+            let actions: Vec<Action> = workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect();
+
+            let delivered = |epoch, sequence| {
+                actions
+                    .iter()
+                    .filter(|action| matches!(action, Action::Deliver { epoch: e, sequence: s, .. } if *e == epoch && *s == sequence))
+                    .count()
+            };
+            // The replayed frame is delivered once whether it lands before or after the advance
+            assert_eq!(delivered(0, 3), 1);
+            assert_eq!(delivered(1, 0), 1);
+            assert!(actions.contains(&Action::Drop(DropReason::Duplicate)));
+
+            let mark = lifecycle.lock().unwrap().replay_mark();
+            assert_eq!((mark.recv_epoch, mark.recv_next), (1, 1));
+        });
+    }
+}
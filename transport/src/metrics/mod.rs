@@ -0,0 +1,291 @@
+//! Server Metrics
+//!
+//! Lock-free counters for handshakes, sessions, traffic, authentication
+//! failures and rekeys, rendered in the Prometheus text exposition format.
+//! The server shares one `Metrics` (via `Arc`) between its workers and
+//! exports it either through the built-in HTTP endpoint (`serve`) or by
+//! periodically writing a file for node_exporter's textfile collector.
+//! Under `--cfg loom` the counters are loom atomics for model checking.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+#[cfg(loom)]
+use loom::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+#[cfg(not(loom))]
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::fmt::Write as _;
+
+/// Outcome of a server handshake
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandshakeResult {
+    Success,
+    Failure,
+}
+
+/// Server statistics
+#[derive(Debug, Default)]
+pub struct Metrics {
+    handshakes_success: AtomicU64,
+    handshakes_failure: AtomicU64,
+    active_sessions: AtomicI64,
+    sessions_total: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    auth_failures: AtomicU64,
+    rekeys: AtomicU64,
+}
+
+impl Metrics {
+    /// Zeroed counters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a completed or failed handshake
+    pub fn handshake(&self, result: HandshakeResult) {
+        let counter = match result {
+            HandshakeResult::Success => &self.handshakes_success,
+            HandshakeResult::Failure => &self.handshakes_failure,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A session was established
+    pub fn session_opened(&self) {
+        self.active_sessions.fetch_add(1, Ordering::Relaxed);
+        self.sessions_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A session ended
+    pub fn session_closed(&self) {
+        self.active_sessions.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Payload bytes sent
+    pub fn bytes_sent(&self, bytes: u64) {
+        self.bytes_sent.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Payload bytes received
+    pub fn bytes_received(&self, bytes: u64) {
+        self.bytes_received.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// A client failed authentication
+    pub fn auth_failure(&self) {
+        self.auth_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A session rekeyed
+    pub fn rekey(&self) {
+        self.rekeys.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Current number of open sessions
+    pub fn active_sessions(&self) -> i64 {
+        self.active_sessions.load(Ordering::Relaxed)
+    }
+
+    /// Prometheus text exposition
+    pub fn render(&self) -> String {
+        render_sets(&[(None, self)])
+    }
+
+    /// Write the exposition for node_exporter's textfile collector
+    ///
+    /// Written to a temporary file and renamed so the collector never
+    /// reads a partial file.
+    pub fn write_textfile(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let tmp = path.with_extension("prom.tmp");
+        std::fs::write(&tmp, self.render())?;
+        std::fs::rename(&tmp, path)
+    }
+}
+
+/// Exposition of per-tenant counters, each sample labeled `tenant="<name>"`
+///
+/// Every metric family is written once, with one sample per tenant.
+/// Tenant names are used verbatim, so they must not contain `"` or `\\`.
+pub fn render_tenants(tenants: &[(&str, &Metrics)]) -> String {
+    let sets: Vec<(Option<&str>, &Metrics)> = tenants.iter().map(|(tenant, metrics)| (Some(*tenant), *metrics)).collect();
+    render_sets(&sets)
+}
+
+type Counter = fn(&Metrics) -> &AtomicU64;
+
+fn render_sets(sets: &[(Option<&str>, &Metrics)]) -> String {
+    let load = |c: &AtomicU64| c.load(Ordering::Relaxed);
+    let labels = |tenant: Option<&str>, extra: Option<&str>| -> String {
+        let pairs: Vec<String> = tenant
+            .map(|tenant| format!("tenant=\"{}\"", tenant))
+            .into_iter()
+            .chain(extra.map(str::to_string))
+            .collect();
+        if pairs.is_empty() { String::new() } else { format!("{{{}}}", pairs.join(",")) }
+    };
+    let mut out = String::new();
+
+    header(&mut out, "aegisq_handshakes_total", "counter", "Server handshakes by result");
+    for (tenant, metrics) in sets {
+        let _ = writeln!(out, "aegisq_handshakes_total{} {}", labels(*tenant, Some("result=\"success\"")), load(&metrics.handshakes_success));
+        let _ = writeln!(out, "aegisq_handshakes_total{} {}", labels(*tenant, Some("result=\"failure\"")), load(&metrics.handshakes_failure));
+    }
+
+    header(&mut out, "aegisq_active_sessions", "gauge", "Currently open sessions");
+    for (tenant, metrics) in sets {
+        let _ = writeln!(out, "aegisq_active_sessions{} {}", labels(*tenant, None), metrics.active_sessions());
+    }
+
+    let counters: [(&str, &str, Counter); 5] = [
+        ("aegisq_sessions_total", "Sessions established", |m| &m.sessions_total),
+        ("aegisq_bytes_sent_total", "Payload bytes sent", |m| &m.bytes_sent),
+        ("aegisq_bytes_received_total", "Payload bytes received", |m| &m.bytes_received),
+        ("aegisq_auth_failures_total", "Failed client authentications", |m| &m.auth_failures),
+        ("aegisq_rekeys_total", "Session rekeys", |m| &m.rekeys),
+    ];
+    for (name, help, counter) in counters {
+        header(&mut out, name, "counter", help);
+        for (tenant, metrics) in sets {
+            let _ = writeln!(out, "{}{} {}", name, labels(*tenant, None), load(counter(metrics)));
+        }
+    }
+    out
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Serve `GET /metrics` on `listener`
+///
+/// Blocking; run it on a dedicated thread. Other paths get 404. A failed
+/// `accept` (e.g. out of file descriptors) is logged and the loop goes on.
+pub fn serve(listener: TcpListener, metrics: Arc<Metrics>) -> io::Result<()> {
+    for stream in listener.incoming() {
+        match stream {
+            // A misbehaving scraper must not stop the endpoint
+            Ok(stream) => {
+                let _ = respond(stream, &metrics);
+            }
+            Err(e) => {
+                eprintln!("aegis-q: metrics endpoint: accept failed: {}", e);
+                // Back off so a persistent error does not spin
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn respond(mut stream: TcpStream, metrics: &Metrics) -> io::Result<()> {
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(5)))?;
+    let mut request_line = String::new();
+    // Bounded so slow or hostile clients stay cheap
+    BufReader::new(&stream).take(8192).read_line(&mut request_line)?;
+
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", metrics.render()),
+        (Some("GET"), Some(_)) => ("404 Not Found", String::from("not found\n")),
+        _ => ("405 Method Not Allowed", String::from("method not allowed\n")),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::new();
+        metrics.handshake(HandshakeResult::Success);
+        metrics.handshake(HandshakeResult::Failure);
+        metrics.session_opened();
+        metrics.session_opened();
+        metrics.session_closed();
+        metrics.bytes_sent(1500);
+        metrics.auth_failure();
+        metrics.rekey();
+
+        let text = metrics.render();
+        assert!(text.contains("aegisq_handshakes_total{result=\"success\"} 1\n"));
+        assert!(text.contains("# TYPE aegisq_active_sessions gauge\naegisq_active_sessions 1\n"));
+        assert!(text.contains("aegisq_sessions_total 2\n"));
+        assert!(text.contains("aegisq_bytes_sent_total 1500\n"));
+        assert!(text.contains("aegisq_auth_failures_total 1\n"));
+        assert!(text.contains("aegisq_rekeys_total 1\n"));
+
+        let path = std::env::temp_dir().join(format!("aegisq-{}.prom", std::process::id()));
+        metrics.write_textfile(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), text);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_http_endpoint() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let metrics = Arc::new(Metrics::new());
+        metrics.rekey();
+        let shared = metrics.clone();
+        std::thread::spawn(move || serve(listener, shared));
+
+        let get = |path: &str| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        let response = get("/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with(&metrics.render()));
+        assert!(get("/").starts_with("HTTP/1.1 404"));
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use super::*;
+    use loom::thread;
+
+    #[test]
+    fn loom_session_gauge() {
+        loom::model(|| {
+            let metrics = loom::sync::Arc::new(Metrics::new());
+            let workers: Vec<_> = (0..2)
+                .map(|_| {
+                    let metrics = metrics.clone();
+                    thread::spawn(move || {
+                        metrics.session_opened();
+                        metrics.bytes_sent(10);
+                        metrics.session_closed();
+                    })
+                })
+                .collect();
+
+            // A concurrent scrape only ever sees a consistent gauge
+            let active = metrics.active_sessions();
+            assert!((0..=2).contains(&active));
+
+            for worker in workers {
+                worker.join().unwrap();
+            }
+            assert_eq!(metrics.active_sessions(), 0);
+            assert!(metrics.render().contains("aegisq_sessions_total 2\n"));
+            assert!(metrics.render().contains("aegisq_bytes_sent_total 20\n"));
+        });
+    }
+}
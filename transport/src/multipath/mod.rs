@@ -0,0 +1,391 @@
+//! Multipath (experimental)
+//!
+//! Sends tunnel traffic over several network paths at once (e.g. Wi-Fi
+//! and cellular) so a VPN session survives one path degrading or
+//! disappearing. Each path has its own packet sequence space, RTT estimate
+//! and NewReno-style congestion window; a connection-level data sequence
+//! lets the receiver reassemble packets in order whichever path carried
+//! them. The scheduler picks the lowest-RTT path with congestion window
+//! room, preferring unmetered paths; packets lost on one path are handed
+//! back for reinjection on another.
+//!
+//! Packets are sealed with the path ID and path sequence in the nonce, so
+//! sequence spaces never collide. Sans-IO: time is passed as a `Duration`
+//! since session start.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use aegis_q_core::{AegisQError, aegis_q_decrypt, aegis_q_encrypt};
+use utils::wire::{MultipathHeaderWire, Wire, Writer};
+
+use crate::flow::RttEstimator;
+
+/// Packet header: path ID (1) || path sequence (8) || data sequence (8)
+pub const MULTIPATH_HEADER_SIZE: usize = 17;
+
+/// Initial congestion window in bytes
+pub const INITIAL_CWND: u64 = 14_720;
+
+/// Congestion window never shrinks below this
+pub const MIN_CWND: u64 = 2_400;
+
+/// Maximum out-of-order packets buffered by the receiver
+pub const MAX_REORDER_BUFFER: usize = 4096;
+
+/// Path identifier
+pub type PathId = u8;
+
+/// Network the path runs over
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathKind {
+    WiFi,
+    Cellular,
+    Wired,
+}
+
+impl PathKind {
+    /// Whether traffic on this path is metered
+    pub fn is_metered(self) -> bool {
+        matches!(self, PathKind::Cellular)
+    }
+}
+
+/// Per-path sending state
+#[derive(Debug, Clone)]
+pub struct PathState {
+    kind: PathKind,
+    next_sequence: u64,
+    rtt: RttEstimator,
+    cwnd: u64,
+    ssthresh: u64,
+    bytes_in_flight: u64,
+    /// path sequence -> (data sequence, payload, sent at)
+    sent: BTreeMap<u64, (u64, Vec<u8>, Duration)>,
+    active: bool,
+}
+
+impl PathState {
+    fn new(kind: PathKind) -> Self {
+        Self {
+            kind,
+            next_sequence: 0,
+            rtt: RttEstimator::default(),
+            cwnd: INITIAL_CWND,
+            ssthresh: u64::MAX,
+            bytes_in_flight: 0,
+            sent: BTreeMap::new(),
+            active: true,
+        }
+    }
+
+    /// Network kind
+    pub fn kind(&self) -> PathKind {
+        self.kind
+    }
+
+    /// RTT estimate
+    pub fn rtt(&self) -> &RttEstimator {
+        &self.rtt
+    }
+
+    /// Congestion window in bytes
+    pub fn cwnd(&self) -> u64 {
+        self.cwnd
+    }
+
+    /// Unacknowledged bytes
+    pub fn bytes_in_flight(&self) -> u64 {
+        self.bytes_in_flight
+    }
+
+    /// Whether the path is used for new traffic
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn has_room(&self, len: u64) -> bool {
+        self.active && self.bytes_in_flight + len <= self.cwnd
+    }
+}
+
+/// Packet on one path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultipathPacket {
+    pub path_id: PathId,
+    pub path_sequence: u64,
+    pub data_sequence: u64,
+    pub payload: Vec<u8>,
+}
+
+impl MultipathPacket {
+    /// Encode to bytes
+    pub fn encode(&self) -> Vec<u8> {
+        let header = MultipathHeaderWire {
+            path_id: self.path_id,
+            path_sequence: self.path_sequence,
+            data_sequence: self.data_sequence,
+        };
+        let mut writer = Writer::with_buffer(Vec::with_capacity(MULTIPATH_HEADER_SIZE + self.payload.len()));
+        header.encode_into(&mut writer).expect("fixed-size header");
+        writer.put(&self.payload);
+        writer.finish()
+    }
+
+    /// Decode from bytes
+    pub fn decode(data: &[u8]) -> Result<Self, AegisQError> {
+        let (header, payload) = MultipathHeaderWire::from_wire_prefix(data).map_err(|_| AegisQError::InvalidLength("Multipath packet too short"))?;
+        Ok(Self {
+            path_id: header.path_id,
+            path_sequence: header.path_sequence,
+            data_sequence: header.data_sequence,
+            payload: payload.to_vec(),
+        })
+    }
+
+    /// Encrypt the payload (nonce bound to path ID and path sequence)
+    pub fn encrypt(&mut self, key: &[u8], nonce: &[u8]) {
+        self.payload = aegis_q_encrypt(key, &self.nonce(nonce), &self.payload);
+    }
+
+    /// Decrypt the payload
+    pub fn decrypt(&mut self, key: &[u8], nonce: &[u8]) -> Result<(), AegisQError> {
+        self.payload = aegis_q_decrypt(key, &self.nonce(nonce), &self.payload)?;
+        Ok(())
+    }
+
+    fn nonce(&self, nonce: &[u8]) -> Vec<u8> {
+        let mut n = nonce.to_vec();
+        n.push(self.path_id);
+        n.extend_from_slice(&self.path_sequence.to_le_bytes());
+        n.extend_from_slice(&self.data_sequence.to_le_bytes());
+        n
+    }
+}
+
+/// Multipath sender: paths, scheduler and congestion control
+#[derive(Debug, Clone, Default)]
+pub struct MultipathSender {
+    paths: HashMap<PathId, PathState>,
+    next_path_id: PathId,
+    next_data_sequence: u64,
+}
+
+impl MultipathSender {
+    /// Sender with no paths
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a path
+    pub fn add_path(&mut self, kind: PathKind) -> Result<PathId, AegisQError> {
+        let id = self.next_path_id;
+        self.next_path_id = self.next_path_id.checked_add(1).ok_or(AegisQError::LimitExceeded("Too many paths"))?;
+        self.paths.insert(id, PathState::new(kind));
+        Ok(id)
+    }
+
+    /// State of a path
+    pub fn path(&self, id: PathId) -> Option<&PathState> {
+        self.paths.get(&id)
+    }
+
+    /// Stop scheduling on a path; returns its unacknowledged payloads for reinjection
+    pub fn deactivate_path(&mut self, id: PathId) -> Vec<(u64, Vec<u8>)> {
+        let Some(path) = self.paths.get_mut(&id) else {
+            return Vec::new();
+        };
+        path.active = false;
+        path.bytes_in_flight = 0;
+        std::mem::take(&mut path.sent)
+            .into_values()
+            .map(|(data_sequence, payload, _)| (data_sequence, payload))
+            .collect()
+    }
+
+    /// Path the scheduler would use for `len` bytes
+    ///
+    /// Lowest smoothed RTT among paths with window room; unmetered paths
+    /// win over metered ones.
+    pub fn select_path(&self, len: usize) -> Option<PathId> {
+        self.paths
+            .iter()
+            .filter(|(_, path)| path.has_room(len as u64))
+            .min_by_key(|(id, path)| (path.kind.is_metered(), path.rtt.smoothed(), **id))
+            .map(|(id, _)| *id)
+    }
+
+    /// Schedule new data; `None` when every path is congestion-limited
+    pub fn send(&mut self, payload: Vec<u8>, now: Duration) -> Option<MultipathPacket> {
+        let data_sequence = self.next_data_sequence;
+        let packet = self.send_with_sequence(data_sequence, payload, now)?;
+        self.next_data_sequence += 1;
+        Some(packet)
+    }
+
+    /// Resend data (e.g. lost on another path) under its original data sequence
+    pub fn reinject(&mut self, data_sequence: u64, payload: Vec<u8>, now: Duration) -> Option<MultipathPacket> {
+        self.send_with_sequence(data_sequence, payload, now)
+    }
+
+    fn send_with_sequence(&mut self, data_sequence: u64, payload: Vec<u8>, now: Duration) -> Option<MultipathPacket> {
+        let path_id = self.select_path(payload.len())?;
+        let path = self.paths.get_mut(&path_id)?;
+        let path_sequence = path.next_sequence;
+        path.next_sequence += 1;
+        path.bytes_in_flight += payload.len() as u64;
+        path.sent.insert(path_sequence, (data_sequence, payload.clone(), now));
+        Some(MultipathPacket {
+            path_id,
+            path_sequence,
+            data_sequence,
+            payload,
+        })
+    }
+
+    /// Acknowledgement of a packet on a path
+    pub fn on_ack(&mut self, path_id: PathId, path_sequence: u64, now: Duration) {
+        let Some(path) = self.paths.get_mut(&path_id) else {
+            return;
+        };
+        let Some((_, payload, sent_at)) = path.sent.remove(&path_sequence) else {
+            return;
+        };
+        let len = payload.len() as u64;
+        path.bytes_in_flight -= len;
+        path.rtt.update(now.saturating_sub(sent_at));
+        if path.cwnd < path.ssthresh {
+            path.cwnd += len;
+        } else {
+            path.cwnd += (len * len / path.cwnd).max(1);
+        }
+    }
+
+    /// Loss of a packet on a path; returns `(data sequence, payload)` to reinject
+    pub fn on_loss(&mut self, path_id: PathId, path_sequence: u64) -> Option<(u64, Vec<u8>)> {
+        let path = self.paths.get_mut(&path_id)?;
+        let (data_sequence, payload, _) = path.sent.remove(&path_sequence)?;
+        path.bytes_in_flight -= payload.len() as u64;
+        path.ssthresh = (path.cwnd / 2).max(MIN_CWND);
+        path.cwnd = path.ssthresh;
+        Some((data_sequence, payload))
+    }
+}
+
+/// Multipath receiver: per-path tracking and in-order reassembly
+#[derive(Debug, Clone, Default)]
+pub struct MultipathReceiver {
+    next_data_sequence: u64,
+    pending: BTreeMap<u64, Vec<u8>>,
+    largest_received: HashMap<PathId, u64>,
+}
+
+impl MultipathReceiver {
+    /// Empty receiver
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept a packet; returns payloads now deliverable in order
+    pub fn receive(&mut self, packet: MultipathPacket) -> Result<Vec<Vec<u8>>, AegisQError> {
+        let largest = self.largest_received.entry(packet.path_id).or_insert(packet.path_sequence);
+        *largest = (*largest).max(packet.path_sequence);
+
+        if packet.data_sequence < self.next_data_sequence || self.pending.contains_key(&packet.data_sequence) {
+            // Duplicate (e.g. reinjected copy)
+            return Ok(Vec::new());
+        }
+        if self.pending.len() >= MAX_REORDER_BUFFER {
+            return Err(AegisQError::LimitExceeded("Reorder buffer full"));
+        }
+        self.pending.insert(packet.data_sequence, packet.payload);
+
+        let mut delivered = Vec::new();
+        while let Some(payload) = self.pending.remove(&self.next_data_sequence) {
+            delivered.push(payload);
+            self.next_data_sequence += 1;
+        }
+        Ok(delivered)
+    }
+
+    /// Highest path sequence seen on a path (for acknowledgements)
+    pub fn largest_received(&self, path_id: PathId) -> Option<u64> {
+        self.largest_received.get(&path_id).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(value: u64) -> Duration {
+        Duration::from_millis(value)
+    }
+
+    #[test]
+    fn test_scheduler_prefers_fast_unmetered_path() {
+        let mut sender = MultipathSender::new();
+        let wifi = sender.add_path(PathKind::WiFi).unwrap();
+        let cellular = sender.add_path(PathKind::Cellular).unwrap();
+
+        let packet = sender.send(vec![0u8; 1000], ms(0)).unwrap();
+        assert_eq!(packet.path_id, wifi);
+        sender.on_ack(wifi, packet.path_sequence, ms(30));
+
+        // Fill the Wi-Fi window: traffic spills onto cellular
+        let spilled: Vec<_> = (0..25)
+            .map(|i| sender.send(vec![0u8; 1000], ms(40 + i)).unwrap())
+            .filter(|packet| packet.path_id == cellular)
+            .collect();
+        assert!(!spilled.is_empty());
+        // Cellular has its own sequence space
+        assert_eq!(spilled[0].path_sequence, 0);
+        assert!(sender.path(wifi).unwrap().bytes_in_flight() <= sender.path(wifi).unwrap().cwnd());
+    }
+
+    #[test]
+    fn test_loss_reinjection_and_reassembly() {
+        let mut sender = MultipathSender::new();
+        let wifi = sender.add_path(PathKind::WiFi).unwrap();
+        let cellular = sender.add_path(PathKind::Cellular).unwrap();
+        let mut receiver = MultipathReceiver::new();
+
+        let first = sender.send(b"one".to_vec(), ms(0)).unwrap();
+        let second = sender.send(b"two".to_vec(), ms(0)).unwrap();
+
+        // Second arrives first: held until the gap is filled
+        assert!(receiver.receive(second.clone()).unwrap().is_empty());
+
+        // First is lost on Wi-Fi and Wi-Fi goes away; reinject on cellular
+        let cwnd = sender.path(wifi).unwrap().cwnd();
+        let (data_sequence, payload) = sender.on_loss(wifi, first.path_sequence).unwrap();
+        assert!(sender.path(wifi).unwrap().cwnd() < cwnd);
+        sender.deactivate_path(wifi);
+        let resent = sender.reinject(data_sequence, payload, ms(50)).unwrap();
+        assert_eq!(resent.path_id, cellular);
+        assert_eq!(resent.path_sequence, 0);
+
+        let decoded = MultipathPacket::decode(&resent.encode()).unwrap();
+        assert_eq!(receiver.receive(decoded).unwrap(), vec![b"one".to_vec(), b"two".to_vec()]);
+        assert!(receiver.receive(second).unwrap().is_empty());
+        assert_eq!(receiver.largest_received(cellular), Some(0));
+    }
+
+    #[test]
+    fn test_packet_encryption_binds_path() {
+        let key = b"multipath-key-123456789012345678901234567";
+        let nonce = b"multipath-nonce1";
+        let mut packet = MultipathPacket {
+            path_id: 1,
+            path_sequence: 7,
+            data_sequence: 3,
+            payload: b"tunnel data".to_vec(),
+        };
+        packet.encrypt(key, nonce);
+        let mut moved = packet.clone();
+        moved.path_id = 2;
+        assert!(moved.decrypt(key, nonce).is_err());
+        packet.decrypt(key, nonce).unwrap();
+        assert_eq!(packet.payload, b"tunnel data");
+    }
+}
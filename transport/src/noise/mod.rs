@@ -0,0 +1,589 @@
+//! Noise Protocol Framework interop shim
+//!
+//! Maps the Aegis-Q handshake onto KEM-based Noise patterns (pqXX, pqIK):
+//! - AEAD: Aegis-Q, associated data bound through the nonce
+//! - Hash: SHAKE-256 (64-byte output)
+//! - DH: replaced by a caller-supplied post-quantum KEM
+//!
+//! Protocol names follow `Noise_pqXX_<kem>_AegisQ_SHAKE256`, so existing
+//! Noise tooling and analyses of the KEM patterns apply unchanged.
+//!
+//! Before the handshake the initiator may offer algorithm suites
+//! (`AlgorithmSuite::encode_offer`); `select_suite` picks the one whose KEM
+//! both sides then instantiate, subject to the responder's
+//! `SecurityPolicy`. The initiator checks the answer with
+//! `SecurityPolicy::confirm`, and both sides build the prologue with
+//! `suite_prologue` so that an offer edited in transit fails the handshake.
+
+use aegis_q_core::{AegisQError, AlgorithmSuite, HashId, SecurityPolicy, aegis_q_decrypt, aegis_q_encrypt};
+use aegis_q_core::suite::offer_transcript;
+use sha3::{Shake256, digest::{Update, ExtendableOutput, XofReader}};
+use utils::kdf::kdf_shake256;
+use crate::session::SessionId;
+
+/// Hash output length (HASHLEN)
+pub const HASH_LEN: usize = 64;
+
+/// Aegis-Q authentication tag length
+pub const TAG_LEN: usize = 32;
+
+/// Post-quantum KEM used in place of Noise DH
+pub trait Kem {
+    /// KEM name used in the protocol name
+    fn name(&self) -> &'static str;
+    /// Encoded public key length
+    fn public_key_len(&self) -> usize;
+    /// Encapsulation ciphertext length
+    fn ciphertext_len(&self) -> usize;
+    /// Generate a fresh key pair: (public, secret)
+    fn keypair(&self) -> (Vec<u8>, Vec<u8>);
+    /// Encapsulate to a public key: (ciphertext, shared secret)
+    fn encapsulate(&self, public_key: &[u8]) -> Result<(Vec<u8>, Vec<u8>), AegisQError>;
+    /// Decapsulate a ciphertext with a secret key
+    fn decapsulate(&self, secret_key: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AegisQError>;
+}
+
+/// Supported handshake patterns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    /// Mutual authentication, static keys transmitted
+    ///
+    /// ```text
+    /// -> e
+    /// <- ekem, s
+    /// -> skem, s
+    /// <- skem
+    /// ```
+    XX,
+    /// Responder static key known in advance
+    ///
+    /// ```text
+    /// <- s
+    /// ...
+    /// -> skem, e, s
+    /// <- ekem, skem
+    /// ```
+    IK,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    E,
+    S,
+    Ekem,
+    Skem,
+}
+
+impl Pattern {
+    fn name(&self) -> &'static str {
+        match self {
+            Pattern::XX => "pqXX",
+            Pattern::IK => "pqIK",
+        }
+    }
+
+    fn messages(&self) -> &'static [&'static [Token]] {
+        match self {
+            Pattern::XX => &[
+                &[Token::E],
+                &[Token::Ekem, Token::S],
+                &[Token::Skem, Token::S],
+                &[Token::Skem],
+            ],
+            Pattern::IK => &[
+                &[Token::Skem, Token::E, Token::S],
+                &[Token::Ekem, Token::Skem],
+            ],
+        }
+    }
+}
+
+/// Noise hash: SHAKE-256 truncated to HASH_LEN
+fn hash(parts: &[&[u8]]) -> Vec<u8> {
+    let mut hasher = Shake256::default();
+    for part in parts {
+        hasher.update(part);
+    }
+    let mut reader = hasher.finalize_xof();
+    let mut out = vec![0u8; HASH_LEN];
+    reader.read(&mut out);
+    out
+}
+
+/// Noise HKDF replacement: two HASH_LEN outputs from one SHAKE-256 call
+fn hkdf2(chaining_key: &[u8], input_key_material: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let out = kdf_shake256(b"aegis-q-noise-hkdf", chaining_key, input_key_material, 2 * HASH_LEN);
+    (out[..HASH_LEN].to_vec(), out[HASH_LEN..].to_vec())
+}
+
+/// Noise CipherState over Aegis-Q
+pub struct CipherState {
+    key: Option<Vec<u8>>,
+    nonce: u64,
+}
+
+impl CipherState {
+    fn empty() -> Self {
+        Self { key: None, nonce: 0 }
+    }
+
+    fn with_key(key: Vec<u8>) -> Self {
+        Self { key: Some(key), nonce: 0 }
+    }
+
+    /// Whether a key has been set
+    pub fn has_key(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// Nonce: 64-bit counter || SHAKE-256(ad)
+    fn aead_nonce(&self, ad: &[u8]) -> Vec<u8> {
+        let mut nonce = self.nonce.to_le_bytes().to_vec();
+        nonce.extend_from_slice(&hash(&[b"aegis-q-noise-ad", ad])[..32]);
+        nonce
+    }
+
+    /// EncryptWithAd; passes plaintext through when no key is set
+    pub fn encrypt_with_ad(&mut self, ad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        let Some(key) = &self.key else {
+            return Ok(plaintext.to_vec());
+        };
+        if self.nonce == u64::MAX {
+            return Err(AegisQError::LimitExceeded("Nonce exhausted"));
+        }
+
+        let ciphertext = aegis_q_encrypt(key, &self.aead_nonce(ad), plaintext);
+        self.nonce += 1;
+        Ok(ciphertext)
+    }
+
+    /// DecryptWithAd; passes ciphertext through when no key is set
+    pub fn decrypt_with_ad(&mut self, ad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        let Some(key) = &self.key else {
+            return Ok(ciphertext.to_vec());
+        };
+        if self.nonce == u64::MAX {
+            return Err(AegisQError::LimitExceeded("Nonce exhausted"));
+        }
+
+        let plaintext = aegis_q_decrypt(key, &self.aead_nonce(ad), ciphertext)?;
+        self.nonce += 1;
+        Ok(plaintext)
+    }
+}
+
+/// Noise SymmetricState
+struct SymmetricState {
+    cipher: CipherState,
+    chaining_key: Vec<u8>,
+    hash: Vec<u8>,
+}
+
+impl SymmetricState {
+    fn initialize(protocol_name: &str) -> Self {
+        let h = hash(&[protocol_name.as_bytes()]);
+        Self {
+            cipher: CipherState::empty(),
+            chaining_key: h.clone(),
+            hash: h,
+        }
+    }
+
+    fn mix_key(&mut self, input_key_material: &[u8]) {
+        let (ck, k) = hkdf2(&self.chaining_key, input_key_material);
+        self.chaining_key = ck;
+        self.cipher = CipherState::with_key(k);
+    }
+
+    fn mix_hash(&mut self, data: &[u8]) {
+        self.hash = hash(&[&self.hash, data]);
+    }
+
+    fn encrypt_and_hash(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        let ciphertext = self.cipher.encrypt_with_ad(&self.hash, plaintext)?;
+        self.mix_hash(&ciphertext);
+        Ok(ciphertext)
+    }
+
+    fn decrypt_and_hash(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        let plaintext = self.cipher.decrypt_with_ad(&self.hash, ciphertext)?;
+        self.mix_hash(ciphertext);
+        Ok(plaintext)
+    }
+
+    fn split(&self) -> (CipherState, CipherState) {
+        let (k1, k2) = hkdf2(&self.chaining_key, &[]);
+        (CipherState::with_key(k1), CipherState::with_key(k2))
+    }
+}
+
+/// Noise HandshakeState for the KEM patterns
+pub struct HandshakeState<K: Kem> {
+    kem: K,
+    pattern: Pattern,
+    initiator: bool,
+    symmetric: SymmetricState,
+    s: (Vec<u8>, Vec<u8>),
+    e: Option<(Vec<u8>, Vec<u8>)>,
+    rs: Option<Vec<u8>>,
+    re: Option<Vec<u8>>,
+    message_index: usize,
+}
+
+impl<K: Kem> HandshakeState<K> {
+    /// Create handshake state
+    ///
+    /// # Arguments
+    /// * `static_keypair` - Local static KEM key pair (public, secret)
+    /// * `remote_static` - Responder static public key (required for IK initiators)
+    /// * `prologue` - Data both sides must agree on out of band
+    pub fn new(
+        kem: K,
+        pattern: Pattern,
+        initiator: bool,
+        static_keypair: (Vec<u8>, Vec<u8>),
+        remote_static: Option<Vec<u8>>,
+        prologue: &[u8],
+    ) -> Result<Self, AegisQError> {
+        if pattern == Pattern::IK && initiator && remote_static.is_none() {
+            return Err(AegisQError::InvalidInput("IK initiator requires remote static key"));
+        }
+
+        let protocol_name = format!("Noise_{}_{}_AegisQ_SHAKE256", pattern.name(), kem.name());
+        let mut symmetric = SymmetricState::initialize(&protocol_name);
+        symmetric.mix_hash(prologue);
+
+        // Pre-message: <- s
+        if pattern == Pattern::IK {
+            if initiator {
+                symmetric.mix_hash(remote_static.as_deref().unwrap_or_default());
+            } else {
+                symmetric.mix_hash(&static_keypair.0);
+            }
+        }
+
+        Ok(Self {
+            kem,
+            pattern,
+            initiator,
+            symmetric,
+            s: static_keypair,
+            e: None,
+            rs: remote_static,
+            re: None,
+            message_index: 0,
+        })
+    }
+
+    /// Whether all handshake messages have been processed
+    pub fn is_finished(&self) -> bool {
+        self.message_index == self.pattern.messages().len()
+    }
+
+    /// Whether it is our turn to write
+    pub fn is_my_turn(&self) -> bool {
+        self.message_index.is_multiple_of(2) == self.initiator
+    }
+
+    /// Current handshake hash (channel binding value)
+    pub fn handshake_hash(&self) -> &[u8] {
+        &self.symmetric.hash
+    }
+
+    /// Remote static public key, once learned
+    pub fn remote_static(&self) -> Option<&[u8]> {
+        self.rs.as_deref()
+    }
+
+    /// Write the next handshake message carrying `payload`
+    pub fn write_message(&mut self, payload: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        if self.is_finished() {
+            return Err(AegisQError::Protocol("Handshake already finished"));
+        }
+        if !self.is_my_turn() {
+            return Err(AegisQError::Protocol("Not our turn to write"));
+        }
+
+        let mut message = Vec::new();
+        for token in self.pattern.messages()[self.message_index] {
+            match token {
+                Token::E => {
+                    let (public, secret) = self.kem.keypair();
+                    self.symmetric.mix_hash(&public);
+                    message.extend_from_slice(&public);
+                    self.e = Some((public, secret));
+                }
+                Token::S => {
+                    let public = self.s.0.clone();
+                    message.extend_from_slice(&self.symmetric.encrypt_and_hash(&public)?);
+                }
+                Token::Ekem => {
+                    let re = self.re.as_ref().ok_or(AegisQError::Protocol("Missing remote ephemeral key"))?;
+                    let (ciphertext, shared) = self.kem.encapsulate(re)?;
+                    self.symmetric.mix_hash(&ciphertext);
+                    self.symmetric.mix_key(&shared);
+                    message.extend_from_slice(&ciphertext);
+                }
+                Token::Skem => {
+                    let rs = self.rs.as_ref().ok_or(AegisQError::Protocol("Missing remote static key"))?;
+                    let (ciphertext, shared) = self.kem.encapsulate(rs)?;
+                    message.extend_from_slice(&self.symmetric.encrypt_and_hash(&ciphertext)?);
+                    self.symmetric.mix_key(&shared);
+                }
+            }
+        }
+
+        message.extend_from_slice(&self.symmetric.encrypt_and_hash(payload)?);
+        self.message_index += 1;
+        Ok(message)
+    }
+
+    /// Read the next handshake message, returning its payload
+    pub fn read_message(&mut self, message: &[u8]) -> Result<Vec<u8>, AegisQError> {
+        if self.is_finished() {
+            return Err(AegisQError::Protocol("Handshake already finished"));
+        }
+        if self.is_my_turn() {
+            return Err(AegisQError::Protocol("Not our turn to read"));
+        }
+
+        let mut rest = message;
+        for token in self.pattern.messages()[self.message_index] {
+            match token {
+                Token::E => {
+                    let public = take(&mut rest, self.kem.public_key_len())?;
+                    self.symmetric.mix_hash(public);
+                    self.re = Some(public.to_vec());
+                }
+                Token::S => {
+                    let len = self.kem.public_key_len() + self.tag_len();
+                    let encrypted = take(&mut rest, len)?;
+                    self.rs = Some(self.symmetric.decrypt_and_hash(encrypted)?);
+                }
+                Token::Ekem => {
+                    let ciphertext = take(&mut rest, self.kem.ciphertext_len())?;
+                    self.symmetric.mix_hash(ciphertext);
+                    let e = self.e.as_ref().ok_or(AegisQError::Protocol("Missing local ephemeral key"))?;
+                    let shared = self.kem.decapsulate(&e.1, ciphertext)?;
+                    self.symmetric.mix_key(&shared);
+                }
+                Token::Skem => {
+                    let len = self.kem.ciphertext_len() + self.tag_len();
+                    let encrypted = take(&mut rest, len)?;
+                    let ciphertext = self.symmetric.decrypt_and_hash(encrypted)?;
+                    let shared = self.kem.decapsulate(&self.s.1, &ciphertext)?;
+                    self.symmetric.mix_key(&shared);
+                }
+            }
+        }
+
+        let payload = self.symmetric.decrypt_and_hash(rest)?;
+        self.message_index += 1;
+        Ok(payload)
+    }
+
+    /// Session identifier derived from the handshake hash
+    ///
+    /// Only stable once the handshake is finished.
+    pub fn session_id(&self) -> SessionId {
+        SessionId::from_transcript(self.handshake_hash())
+    }
+
+    /// Split into transport cipher states: (send, receive)
+    pub fn into_transport(self) -> Result<(CipherState, CipherState), AegisQError> {
+        if !self.is_finished() {
+            return Err(AegisQError::Protocol("Handshake not finished"));
+        }
+
+        let (c1, c2) = self.symmetric.split();
+        if self.initiator {
+            Ok((c1, c2))
+        } else {
+            Ok((c2, c1))
+        }
+    }
+
+    fn tag_len(&self) -> usize {
+        if self.symmetric.cipher.has_key() {
+            TAG_LEN
+        } else {
+            0
+        }
+    }
+}
+
+/// Responder side of suite negotiation: choose from an encoded offer
+///
+/// Only suites hashing with SHAKE-256, the hash of this shim, and allowed
+/// by `policy` can be selected; the initiator's preference order wins
+/// among the rest. Fails with `DowngradeRejected` if every common suite is
+/// below the policy.
+pub fn select_suite(offer: &[u8], supported: &[AlgorithmSuite], policy: &SecurityPolicy) -> Result<AlgorithmSuite, AegisQError> {
+    let usable: Vec<AlgorithmSuite> = supported.iter().copied().filter(|suite| suite.hash() == HashId::Shake256).collect();
+    policy.negotiate(&AlgorithmSuite::decode_offer(offer)?, &usable)
+}
+
+/// Prologue binding the suite offer and selection, followed by `prologue`
+///
+/// `offer` is the encoded offer exactly as the initiator sent it.
+pub fn suite_prologue(offer: &[u8], selected: &AlgorithmSuite, prologue: &[u8]) -> Vec<u8> {
+    let mut bound = offer_transcript(offer, selected);
+    bound.extend_from_slice(prologue);
+    bound
+}
+
+fn take<'a>(rest: &mut &'a [u8], len: usize) -> Result<&'a [u8], AegisQError> {
+    if rest.len() < len {
+        return Err(AegisQError::InvalidLength("Handshake message too short"));
+    }
+    let (head, tail) = rest.split_at(len);
+    *rest = tail;
+    Ok(head)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::rng::random_bytes;
+
+    /// Insecure stand-in KEM (public key == secret key), for exercising the
+    /// pattern state machine only
+    struct TestKem;
+
+    impl Kem for TestKem {
+        fn name(&self) -> &'static str {
+            "TestKEM"
+        }
+        fn public_key_len(&self) -> usize {
+            32
+        }
+        fn ciphertext_len(&self) -> usize {
+            32
+        }
+        fn keypair(&self) -> (Vec<u8>, Vec<u8>) {
+            let key = random_bytes(32);
+            (key.clone(), key)
+        }
+        fn encapsulate(&self, public_key: &[u8]) -> Result<(Vec<u8>, Vec<u8>), AegisQError> {
+            let ciphertext = random_bytes(32);
+            let shared = hash(&[public_key, &ciphertext]);
+            Ok((ciphertext, shared))
+        }
+        fn decapsulate(&self, secret_key: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AegisQError> {
+            Ok(hash(&[secret_key, ciphertext]))
+        }
+    }
+
+    fn run(pattern: Pattern) {
+        let client_static = TestKem.keypair();
+        let server_static = TestKem.keypair();
+        let remote = (pattern == Pattern::IK).then(|| server_static.0.clone());
+
+        let mut client = HandshakeState::new(TestKem, pattern, true, client_static.clone(), remote, b"prologue").unwrap();
+        let mut server = HandshakeState::new(TestKem, pattern, false, server_static.clone(), None, b"prologue").unwrap();
+
+        let mut turn = 0;
+        while !client.is_finished() {
+            let (writer, reader) = if turn % 2 == 0 { (&mut client, &mut server) } else { (&mut server, &mut client) };
+            let message = writer.write_message(b"payload").unwrap();
+            assert_eq!(reader.read_message(&message).unwrap(), b"payload");
+            turn += 1;
+        }
+
+        assert!(server.is_finished());
+        assert_eq!(client.session_id(), server.session_id());
+        assert_eq!(client.handshake_hash(), server.handshake_hash());
+        assert_eq!(client.remote_static(), Some(server_static.0.as_slice()));
+        assert_eq!(server.remote_static(), Some(client_static.0.as_slice()));
+
+        let (mut client_send, _) = client.into_transport().unwrap();
+        let (_, mut server_recv) = server.into_transport().unwrap();
+        let ciphertext = client_send.encrypt_with_ad(b"", b"Hello, Noise!").unwrap();
+        assert_eq!(server_recv.decrypt_with_ad(b"", &ciphertext).unwrap(), b"Hello, Noise!");
+    }
+
+    #[test]
+    fn test_noise_xx() {
+        run(Pattern::XX);
+    }
+
+    #[test]
+    fn test_noise_ik() {
+        run(Pattern::IK);
+    }
+
+    #[test]
+    fn test_noise_lost_third_message() {
+        use crate::testutil::{FaultSchedule, LinkConfig, Side, SimLink};
+
+        let mut client = HandshakeState::new(TestKem, Pattern::XX, true, TestKem.keypair(), None, b"").unwrap();
+        let mut server = HandshakeState::new(TestKem, Pattern::XX, false, TestKem.keypair(), None, b"").unwrap();
+        let mut link = SimLink::new(LinkConfig::default(), FaultSchedule::new().drop_nth(3));
+
+        link.send(Side::A, &client.write_message(b"").unwrap());
+        server.read_message(&link.drain(Side::B)[0]).unwrap();
+        link.send(Side::B, &server.write_message(b"").unwrap());
+        client.read_message(&link.drain(Side::A)[0]).unwrap();
+        link.send(Side::A, &client.write_message(b"").unwrap());
+
+        // Message 3 is lost: the server cannot answer and neither side may go live
+        assert!(link.drain(Side::B).is_empty());
+        assert!(server.write_message(b"").is_err());
+        assert!(client.into_transport().is_err());
+        assert!(server.into_transport().is_err());
+    }
+
+    #[test]
+    fn test_noise_prologue_mismatch() {
+        let mut client = HandshakeState::new(TestKem, Pattern::XX, true, TestKem.keypair(), None, b"a").unwrap();
+        let mut server = HandshakeState::new(TestKem, Pattern::XX, false, TestKem.keypair(), None, b"b").unwrap();
+
+        let message = client.write_message(b"").unwrap();
+        server.read_message(&message).unwrap();
+        let message = server.write_message(b"").unwrap();
+        assert!(client.read_message(&message).is_err());
+    }
+
+    #[test]
+    fn test_select_suite() {
+        use aegis_q_core::{AeadId, KemId, SignatureId};
+
+        let sha3 = AlgorithmSuite::new(AeadId::AegisQ192, KemId::MlKem768, SignatureId::MlDsa65, HashId::Sha3_512).unwrap();
+        let offer = AlgorithmSuite::encode_offer(&[sha3, AlgorithmSuite::DEFAULT]).unwrap();
+
+        let policy = SecurityPolicy::default();
+        assert_eq!(select_suite(&offer, &[sha3, AlgorithmSuite::DEFAULT], &policy).unwrap(), AlgorithmSuite::DEFAULT);
+        assert!(select_suite(&offer, &[sha3], &policy).is_err());
+        assert!(select_suite(b"garbage", &[AlgorithmSuite::DEFAULT], &policy).is_err());
+    }
+
+    #[test]
+    fn test_stripped_offer_fails_handshake() {
+        use aegis_q_core::{AeadId, KemId, SecurityLevel, SignatureId};
+
+        let weak = AlgorithmSuite::new(AeadId::AegisQ128, KemId::MlKem512, SignatureId::MlDsa44, HashId::Shake256).unwrap();
+        let supported = [AlgorithmSuite::DEFAULT, weak];
+        let offer = AlgorithmSuite::encode_offer(&supported).unwrap();
+        let stripped = AlgorithmSuite::encode_offer(&[weak]).unwrap();
+
+        // The responder's policy refuses the weak suite outright
+        let strict = SecurityPolicy::new(SecurityLevel::L256);
+        assert_eq!(
+            select_suite(&stripped, &supported, &strict),
+            Err(AegisQError::DowngradeRejected("Only suites below policy in common"))
+        );
+
+        // A lenient responder selects it, but the transcripts no longer match
+        let selected = select_suite(&stripped, &supported, &SecurityPolicy::default()).unwrap();
+        assert_eq!(selected, weak);
+        SecurityPolicy::default().confirm(&supported, &selected).unwrap();
+
+        let client_prologue = suite_prologue(&offer, &selected, b"app");
+        let server_prologue = suite_prologue(&stripped, &selected, b"app");
+        let mut client = HandshakeState::new(TestKem, Pattern::XX, true, TestKem.keypair(), None, &client_prologue).unwrap();
+        let mut server = HandshakeState::new(TestKem, Pattern::XX, false, TestKem.keypair(), None, &server_prologue).unwrap();
+        server.read_message(&client.write_message(b"").unwrap()).unwrap();
+        let message = server.write_message(b"").unwrap();
+        assert!(client.read_message(&message).is_err());
+    }
+}
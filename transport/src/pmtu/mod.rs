@@ -0,0 +1,195 @@
+//! Path MTU Discovery
+//!
+//! Datagram packetization-layer PMTU discovery (DPLPMTUD, RFC 8899) for the
+//! UDP path. The tunnel starts at a conservative base size and probes
+//! larger sizes with padded `Probe` frames, binary-searching between the
+//! largest acknowledged and smallest failed size. A probe size is declared
+//! failed after `MAX_PROBES` unacknowledged attempts. Loss of full-sized
+//! packets after the search (a black hole) drops back to the base size and
+//! restarts the search, so the tunnel never relies on IP fragmentation.
+//!
+//! Sans-IO: the caller sends `next_probe()` frames and reports
+//! acknowledgements and losses.
+
+use aegis_q_core::AegisQError;
+
+use crate::framing::Frame;
+
+/// Base datagram size every path must carry (RFC 9000 minimum)
+pub const BASE_PLPMTU: usize = 1200;
+
+/// Default upper bound for the search
+pub const DEFAULT_MAX_PLPMTU: usize = 1500;
+
+/// Unacknowledged probes before a size is considered too large
+pub const MAX_PROBES: u8 = 3;
+
+/// Search stops once the bounds are this close
+pub const SEARCH_GRANULARITY: usize = 8;
+
+/// Discovery phase
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PmtuState {
+    /// Probing larger sizes
+    Searching,
+    /// Largest working size found
+    Complete,
+}
+
+/// DPLPMTUD state for one path
+#[derive(Debug, Clone)]
+pub struct MtuDiscovery {
+    base: usize,
+    max: usize,
+    /// Largest size confirmed by an acknowledgement
+    confirmed: usize,
+    /// Smallest size known (or assumed) not to work, exclusive
+    ceiling: usize,
+    in_flight: Option<usize>,
+    attempts: u8,
+    state: PmtuState,
+}
+
+impl Default for MtuDiscovery {
+    fn default() -> Self {
+        Self::new(BASE_PLPMTU, DEFAULT_MAX_PLPMTU).expect("default bounds are valid")
+    }
+}
+
+impl MtuDiscovery {
+    /// Discovery between `base` and `max` datagram sizes
+    pub fn new(base: usize, max: usize) -> Result<Self, AegisQError> {
+        if base < crate::framing::FRAME_HEADER_SIZE || base > max {
+            return Err(AegisQError::InvalidInput("Invalid PMTU bounds"));
+        }
+        Ok(Self {
+            base,
+            max,
+            confirmed: base,
+            ceiling: max + 1,
+            in_flight: None,
+            attempts: 0,
+            state: PmtuState::Searching,
+        })
+    }
+
+    /// Largest datagram size currently safe to send
+    pub fn current(&self) -> usize {
+        self.confirmed
+    }
+
+    /// Discovery phase
+    pub fn state(&self) -> PmtuState {
+        self.state
+    }
+
+    /// Next probe to send, if the search is still running
+    ///
+    /// While a probe is outstanding the same size is returned again, so
+    /// callers can retransmit it on their probe timer.
+    pub fn next_probe(&mut self, sequence: u64) -> Option<Frame> {
+        if self.state == PmtuState::Complete {
+            return None;
+        }
+        let size = match self.in_flight {
+            Some(size) => size,
+            None => {
+                let size = if self.ceiling > self.max && self.ceiling - self.confirmed <= SEARCH_GRANULARITY + 1 {
+                    // Near an unprobed upper bound: try it directly
+                    self.max
+                } else {
+                    self.confirmed + (self.ceiling - self.confirmed) / 2
+                };
+                self.in_flight = Some(size);
+                self.attempts = 0;
+                size
+            }
+        };
+        self.attempts += 1;
+        Frame::probe(size, sequence).ok()
+    }
+
+    /// A probe of `size` bytes was acknowledged
+    pub fn on_probe_acked(&mut self, size: usize) {
+        if self.in_flight != Some(size) {
+            return;
+        }
+        self.in_flight = None;
+        self.confirmed = self.confirmed.max(size);
+        self.check_complete();
+    }
+
+    /// A probe of `size` bytes timed out
+    pub fn on_probe_lost(&mut self, size: usize) {
+        if self.in_flight != Some(size) || self.attempts < MAX_PROBES {
+            return;
+        }
+        self.in_flight = None;
+        self.ceiling = size;
+        self.check_complete();
+    }
+
+    /// Full-sized packets are being lost: fall back to the base size and search again
+    pub fn on_black_hole(&mut self) {
+        self.confirmed = self.base;
+        self.ceiling = self.max + 1;
+        self.in_flight = None;
+        self.attempts = 0;
+        self.state = PmtuState::Searching;
+    }
+
+    fn check_complete(&mut self) {
+        if self.confirmed >= self.max || (self.ceiling <= self.max && self.ceiling - self.confirmed <= SEARCH_GRANULARITY) {
+            self.state = PmtuState::Complete;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run discovery against a path that drops datagrams above `path_mtu`
+    fn discover(discovery: &mut MtuDiscovery, path_mtu: usize) {
+        let mut sequence = 0;
+        while let Some(probe) = discovery.next_probe(sequence) {
+            sequence += 1;
+            let size = probe.encoded_len();
+            if size <= path_mtu {
+                discovery.on_probe_acked(size);
+            } else {
+                discovery.on_probe_lost(size);
+            }
+        }
+    }
+
+    #[test]
+    fn test_binary_search_converges() {
+        let mut discovery = MtuDiscovery::default();
+        discover(&mut discovery, 1400);
+        assert_eq!(discovery.state(), PmtuState::Complete);
+        assert!(discovery.current() <= 1400);
+        assert!(discovery.current() > 1400 - SEARCH_GRANULARITY);
+
+        let mut discovery = MtuDiscovery::default();
+        discover(&mut discovery, 9000);
+        assert_eq!(discovery.current(), DEFAULT_MAX_PLPMTU);
+    }
+
+    #[test]
+    fn test_probe_retries_and_black_hole() {
+        let mut discovery = MtuDiscovery::default();
+        let size = discovery.next_probe(0).unwrap().encoded_len();
+        discovery.on_probe_lost(size);
+        // One loss is not enough: the same size is retried
+        assert_eq!(discovery.next_probe(1).unwrap().encoded_len(), size);
+
+        discover(&mut discovery, 1300);
+        assert!(discovery.current() <= 1300);
+
+        discovery.on_black_hole();
+        assert_eq!(discovery.current(), BASE_PLPMTU);
+        assert_eq!(discovery.state(), PmtuState::Searching);
+        assert!(MtuDiscovery::new(2000, 1500).is_err());
+    }
+}
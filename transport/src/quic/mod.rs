@@ -3,26 +3,64 @@
 //! QUIC-like protocol using Aegis-Q encryption
 //! Session management and stream handling
 
-use aegis_q_core::{aegis_q_encrypt, aegis_q_decrypt};
+use aegis_q_core::{AegisQError, aegis_q_encrypt, aegis_q_decrypt, aegis_q_decrypt_in_place, aegis_q_init};
+use utils::memory::zeroize;
 use utils::kdf::kdf_shake256_fill;
+use crate::flow::{SessionWindows, WindowConfig};
+use crate::session::SessionId;
 
 /// QUIC session
 pub struct QuicSession {
     session_key: Vec<u8>,
     session_nonce: Vec<u8>,
+    session_id: SessionId,
     stream_ids: Vec<u32>,
+    windows: SessionWindows,
 }
 
 impl QuicSession {
     /// Create new QUIC session
     pub fn new(session_key: Vec<u8>, session_nonce: Vec<u8>) -> Self {
+        // The ID comes from an exporter of the session state, never the raw key
+        let mut export = aegis_q_init(&session_key, &session_nonce)
+            .export_keying_material(b"aegis-q-transport-quic-session-id", b"", 32);
+        let session_id = SessionId::from_transcript(&export);
+        zeroize(&mut export);
         Self {
             session_key,
             session_nonce,
+            session_id,
             stream_ids: Vec::new(),
+            windows: SessionWindows::new(WindowConfig::default()).expect("default window config is valid"),
         }
     }
     
+    /// Use per-session receive window clamps
+    pub fn with_window_config(mut self, config: WindowConfig) -> Result<Self, AegisQError> {
+        self.windows = SessionWindows::new(config)?;
+        Ok(self)
+    }
+    
+    /// Auto-tuned receive windows
+    pub fn windows(&self) -> &SessionWindows {
+        &self.windows
+    }
+    
+    /// Auto-tuned receive windows (mutable)
+    pub fn windows_mut(&mut self) -> &mut SessionWindows {
+        &mut self.windows
+    }
+    
+    /// Authenticated session identifier (same on both peers)
+    pub fn session_id(&self) -> SessionId {
+        self.session_id
+    }
+    
+    /// Check that a presented session ID belongs to this live session
+    pub fn verify_session_id(&self, presented: &[u8]) -> bool {
+        self.session_id.verify(presented)
+    }
+    
     /// Create new stream
     pub fn create_stream(&mut self) -> u32 {
         let stream_id = self.stream_ids.len() as u32;
@@ -32,26 +70,24 @@ impl QuicSession {
     
     /// Encrypt stream data
     pub fn encrypt_stream(&self, stream_id: u32, data: &[u8], sequence: u64) -> Vec<u8> {
-        // Derive stream-specific key
-        let mut stream_key = vec![0u8; 64];
-        kdf_shake256_fill(
-            b"aegis-q-transport-quic-stream-key",
-            &self.session_key,
-            &stream_id.to_le_bytes(),
-            &mut stream_key,
-        );
-        
-        // Create nonce with stream ID and sequence
-        let mut nonce = self.session_nonce.clone();
-        nonce.extend_from_slice(&stream_id.to_le_bytes());
-        nonce.extend_from_slice(&sequence.to_le_bytes());
-        
+        let (stream_key, nonce) = self.stream_keys(stream_id, sequence);
         aegis_q_encrypt(&stream_key, &nonce, data)
     }
     
     /// Decrypt stream data
-    pub fn decrypt_stream(&self, stream_id: u32, ciphertext: &[u8], sequence: u64) -> Result<Vec<u8>, &'static str> {
-        // Derive stream-specific key
+    pub fn decrypt_stream(&self, stream_id: u32, ciphertext: &[u8], sequence: u64) -> Result<Vec<u8>, AegisQError> {
+        let (stream_key, nonce) = self.stream_keys(stream_id, sequence);
+        aegis_q_decrypt(&stream_key, &nonce, ciphertext)
+    }
+    
+    /// Decrypt stream data in place, returning a view of the plaintext
+    pub fn decrypt_stream_in_place<'a>(&self, stream_id: u32, buffer: &'a mut [u8], sequence: u64) -> Result<&'a mut [u8], AegisQError> {
+        let (stream_key, nonce) = self.stream_keys(stream_id, sequence);
+        aegis_q_decrypt_in_place(&stream_key, &nonce, buffer)
+    }
+    
+    /// Derive stream-specific key and per-packet nonce
+    fn stream_keys(&self, stream_id: u32, sequence: u64) -> (Vec<u8>, Vec<u8>) {
         let mut stream_key = vec![0u8; 64];
         kdf_shake256_fill(
             b"aegis-q-transport-quic-stream-key",
@@ -65,7 +101,7 @@ impl QuicSession {
         nonce.extend_from_slice(&stream_id.to_le_bytes());
         nonce.extend_from_slice(&sequence.to_le_bytes());
         
-        aegis_q_decrypt(&stream_key, &nonce, ciphertext)
+        (stream_key, nonce)
     }
 }
 
@@ -0,0 +1,312 @@
+//! Replay State Persistence
+//!
+//! Replay windows and send sequences (`lifecycle`) live in memory. A
+//! session resumed from a ticket gets fresh traffic keys from the ticket's
+//! resumption secret and randoms both peers pick for the resumption, so a
+//! restart, a crash before the first save or a fleet server that never
+//! saw the ticket cannot make two resumptions share a keystream.
+//!
+//! The high-water marks are kept as defense in depth, in case a
+//! resumption's randoms ever repeat. `ReplayStore` keeps a `ReplayMark`
+//! per peer in a file encrypted under an `EncryptionKey`. For a
+//! `VpnSession`, `save_session` records its mark under its session ID and
+//! saves; `resume_session` derives the resumed session and continues it
+//! from that mark if one exists. Other connections record
+//! `Lifecycle::replay_mark` and pass the stored mark to
+//! `Lifecycle::resume`. Save periodically and on shutdown. Marks only move
+//! forward, and the file is synced and replaced atomically, so a crash
+//! leaves the previous save.
+//!
+//! A mark lags the connection by whatever happened since the last save.
+//! The send side covers up to `SEND_RESERVE` frames of that; frames
+//! received after the last save can be replayed once, so save after every
+//! delivered frame where that matters.
+//!
+//! File: `nonce || Aegis-Q(ReplayMarksWire)`, see `utils::wire::ReplayStoreWire`.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use aegis_q_core::{aegis_q_decrypt, aegis_q_try_encrypt, AegisQError};
+use utils::kdf::kdf_shake256_fill;
+use utils::keys::{EncryptionKey, TypedKey};
+use utils::memory::zeroize;
+use utils::rng::random_bytes;
+use utils::wire::{ReplayMarkWire, ReplayMarksWire, ReplayStoreWire, Wire};
+
+use crate::lifecycle::ReplayMark;
+use crate::tls::{session_from_exporter, Role};
+use crate::vpn::VpnSession;
+
+const NONCE_SIZE: usize = 16;
+
+/// Length of the client and server randoms of a resumption
+pub const RESUMPTION_RANDOM_SIZE: usize = 32;
+
+/// Encrypted per-peer replay marks backed by a file
+pub struct ReplayStore {
+    path: PathBuf,
+    key: EncryptionKey,
+    marks: HashMap<Vec<u8>, ReplayMark>,
+}
+
+impl ReplayStore {
+    /// Load the store at `path`; a missing file is an empty store
+    pub fn open(path: impl Into<PathBuf>, key: EncryptionKey) -> Result<Self, AegisQError> {
+        let path = path.into();
+        let marks = match fs::read(&path) {
+            Ok(file) => decode(&file, &key)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(_) => return Err(AegisQError::Io("Cannot read replay store")),
+        };
+        Ok(Self { path, key, marks })
+    }
+
+    /// File backing the store
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Stored mark of `peer` (e.g. the resumption ticket's identity)
+    pub fn get(&self, peer: &[u8]) -> Option<ReplayMark> {
+        self.marks.get(peer).copied()
+    }
+
+    /// Raise the mark of `peer`; an older mark never lowers it
+    pub fn record(&mut self, peer: &[u8], mark: ReplayMark) {
+        let entry = self.marks.entry(peer.to_vec()).or_default();
+        *entry = entry.max(mark);
+    }
+
+    /// Forget `peer`, e.g. once its tickets have expired
+    pub fn remove(&mut self, peer: &[u8]) -> Option<ReplayMark> {
+        self.marks.remove(peer)
+    }
+
+    /// Write all marks, replacing the file atomically
+    ///
+    /// The new file is synced before the rename and the directory after
+    /// it, so a power loss leaves either the old or the new marks.
+    pub fn save(&self) -> Result<(), AegisQError> {
+        let file = encode(&self.marks, &self.key)?;
+        let tmp = self.path.with_extension("tmp");
+        let dir = match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        File::create(&tmp)
+            .and_then(|mut f| f.write_all(&file).and_then(|_| f.sync_all()))
+            .and_then(|_| fs::rename(&tmp, &self.path))
+            .and_then(|_| File::open(dir)?.sync_all())
+            .map_err(|_| AegisQError::Io("Cannot write replay store"))
+    }
+
+    /// Record `session`'s mark under its session ID and save
+    pub fn save_session(&mut self, session: &VpnSession) -> Result<(), AegisQError> {
+        self.record(session.session_id().as_bytes(), session.replay_mark());
+        self.save()
+    }
+
+    /// Derive a ticket-resumed session, continuing from its stored mark
+    ///
+    /// `resumption_secret` is the ticket's state (`TicketKeys::open` on the
+    /// server, the secret kept with the ticket on the client).
+    /// `client_random` and `server_random` are `RESUMPTION_RANDOM_SIZE`
+    /// bytes each side draws from `utils::rng` for this resumption and
+    /// sends to the other; the traffic keys depend on both. A stored mark
+    /// only exists if the same randoms were used before.
+    pub fn resume_session(
+        &self,
+        resumption_secret: &[u8],
+        client_random: &[u8],
+        server_random: &[u8],
+        role: Role,
+    ) -> Result<VpnSession, AegisQError> {
+        let mut secret = resumed_secret(resumption_secret, client_random, server_random)?;
+        let session = session_from_exporter(&secret, role);
+        zeroize(&mut secret);
+        let mut session = session?;
+        if let Some(mark) = self.get(session.session_id().as_bytes()) {
+            session.resume(mark);
+        }
+        Ok(session)
+    }
+}
+
+/// Secret of one resumption, bound to both peers' randoms
+fn resumed_secret(resumption_secret: &[u8], client_random: &[u8], server_random: &[u8]) -> Result<Vec<u8>, AegisQError> {
+    if client_random.len() != RESUMPTION_RANDOM_SIZE || server_random.len() != RESUMPTION_RANDOM_SIZE {
+        return Err(AegisQError::InvalidLength("Resumption random must be 32 bytes"));
+    }
+    let mut randoms = Vec::with_capacity(2 * RESUMPTION_RANDOM_SIZE);
+    randoms.extend_from_slice(client_random);
+    randoms.extend_from_slice(server_random);
+    let mut secret = vec![0u8; 64];
+    kdf_shake256_fill(b"aegis-q-transport-resumption", resumption_secret, &randoms, &mut secret);
+    Ok(secret)
+}
+
+fn encode(marks: &HashMap<Vec<u8>, ReplayMark>, key: &EncryptionKey) -> Result<Vec<u8>, AegisQError> {
+    let marks = marks
+        .iter()
+        .map(|(peer, mark)| ReplayMarkWire {
+            peer: peer.clone(),
+            recv_epoch: mark.recv_epoch,
+            recv_next: mark.recv_next,
+            send_epoch: mark.send_epoch,
+            send_next: mark.send_next,
+        })
+        .collect();
+    let mut plaintext = ReplayMarksWire { marks }.to_wire().map_err(AegisQError::Serialization)?;
+
+    let mut nonce = [0u8; NONCE_SIZE];
+    nonce.copy_from_slice(&random_bytes(NONCE_SIZE));
+    let sealed = aegis_q_try_encrypt(key.as_bytes(), &nonce, &plaintext);
+    zeroize(&mut plaintext);
+    ReplayStoreWire { nonce, sealed: sealed? }.to_wire().map_err(AegisQError::Serialization)
+}
+
+fn decode(file: &[u8], key: &EncryptionKey) -> Result<HashMap<Vec<u8>, ReplayMark>, AegisQError> {
+    let wire = ReplayStoreWire::from_wire(file).map_err(AegisQError::Serialization)?;
+    let mut plaintext = aegis_q_decrypt(key.as_bytes(), &wire.nonce, &wire.sealed)?;
+    let marks = ReplayMarksWire::from_wire(&plaintext);
+    zeroize(&mut plaintext);
+
+    Ok(marks
+        .map_err(AegisQError::Serialization)?
+        .marks
+        .into_iter()
+        .map(|mark| {
+            let replay_mark = ReplayMark {
+                recv_epoch: mark.recv_epoch,
+                recv_next: mark.recv_next,
+                send_epoch: mark.send_epoch,
+                send_next: mark.send_next,
+            };
+            (mark.peer, replay_mark)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framing::Frame;
+    use crate::lifecycle::{Action, DropReason, Event, Lifecycle, SEND_RESERVE};
+
+    fn key(byte: u8) -> EncryptionKey {
+        EncryptionKey::from_bytes(&[byte; 32])
+    }
+
+    #[test]
+    fn test_marks_survive_restart() {
+        let dir = std::env::temp_dir().join(format!("aegis-q-replay-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("replay.state");
+
+        // Before the restart: the peer's frame 3 was delivered
+        let mut lifecycle = Lifecycle::new();
+        lifecycle.handle(Event::Connect);
+        lifecycle.handle(Event::HandshakeComplete);
+        lifecycle.handle(Event::Frame { epoch: 0, sequence: 3 });
+        let mut store = ReplayStore::open(&path, key(1)).unwrap();
+        store.record(b"peer-a", lifecycle.replay_mark());
+        store.record(b"peer-a", ReplayMark::default());
+        store.save().unwrap();
+
+        // After the restart the resumed session rejects the replayed frame
+        let store = ReplayStore::open(&path, key(1)).unwrap();
+        assert_eq!(store.get(b"peer-b"), None);
+        let mut resumed = Lifecycle::new();
+        resumed.handle(Event::Connect);
+        resumed.resume(store.get(b"peer-a").unwrap());
+        resumed.handle(Event::HandshakeComplete);
+        assert_eq!(resumed.handle(Event::Frame { epoch: 0, sequence: 3 }), [Action::Drop(DropReason::Duplicate)]);
+
+        assert!(matches!(ReplayStore::open(&path, key(2)), Err(AegisQError::AuthenticationFailed)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_each_ticket_resumption_gets_fresh_keys() {
+        use crate::tickets::{TicketKeys, TicketPolicy};
+
+        let path = std::env::temp_dir().join(format!("aegis-q-replay-fresh-{}", std::process::id()));
+        let store = ReplayStore::open(&path, key(1)).unwrap();
+        let tickets = TicketKeys::new(&[7u8; 32], 0, TicketPolicy::default()).unwrap();
+        let resumption_secret = [9u8; 32];
+        let ticket = tickets.issue(&resumption_secret, 0);
+
+        // First resumption; the server crashes before saving anything
+        let (client_random, server_random) = (random_bytes(RESUMPTION_RANDOM_SIZE), random_bytes(RESUMPTION_RANDOM_SIZE));
+        let mut client = store.resume_session(&resumption_secret, &client_random, &server_random, Role::Client).unwrap();
+        let mut server = store.resume_session(&tickets.open(&ticket, 1).unwrap(), &client_random, &server_random, Role::Server).unwrap();
+        let first = client.encrypt_data(b"first");
+        assert_eq!(server.decrypt_data(&first).unwrap(), b"first");
+
+        // The next resumption of the same ticket starts at sequence 0 under other keys
+        let (client_random, server_random) = (random_bytes(RESUMPTION_RANDOM_SIZE), random_bytes(RESUMPTION_RANDOM_SIZE));
+        let mut client = store.resume_session(&resumption_secret, &client_random, &server_random, Role::Client).unwrap();
+        let mut server = store.resume_session(&tickets.open(&ticket, 2).unwrap(), &client_random, &server_random, Role::Server).unwrap();
+        let again = client.encrypt_data(b"first");
+        assert_eq!(Frame::decode(&again).unwrap().sequence, 0);
+        assert_ne!(Frame::decode(&again).unwrap().payload, Frame::decode(&first).unwrap().payload);
+        assert_eq!(server.decrypt_data(&first), Err(AegisQError::AuthenticationFailed));
+        assert_eq!(server.decrypt_data(&again).unwrap(), b"first");
+
+        assert!(store.resume_session(&resumption_secret, &client_random[..16], &server_random, Role::Client).is_err());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_repeated_resumption_continues_from_stored_marks() {
+        let dir = std::env::temp_dir().join(format!("aegis-q-replay-ticket-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (client_path, server_path) = (dir.join("client.state"), dir.join("server.state"));
+        let resumption_secret = [9u8; 32];
+        let (client_random, server_random) = ([1u8; RESUMPTION_RANDOM_SIZE], [2u8; RESUMPTION_RANDOM_SIZE]);
+
+        // Before the restart: two client frames delivered, one server frame
+        let mut client_store = ReplayStore::open(&client_path, key(1)).unwrap();
+        let mut server_store = ReplayStore::open(&server_path, key(1)).unwrap();
+        let mut client = client_store.resume_session(&resumption_secret, &client_random, &server_random, Role::Client).unwrap();
+        let mut server = server_store.resume_session(&resumption_secret, &client_random, &server_random, Role::Server).unwrap();
+        let first = client.encrypt_data(b"first");
+        let second = client.encrypt_data(b"second");
+        assert_eq!(server.decrypt_data(&first).unwrap(), b"first");
+        assert_eq!(server.decrypt_data(&second).unwrap(), b"second");
+        let reply = server.encrypt_data(b"reply");
+        assert_eq!(client.decrypt_data(&reply).unwrap(), b"reply");
+        client_store.save_session(&client).unwrap();
+        server_store.save_session(&server).unwrap();
+
+        // The same randoms again: both sides continue past their marks
+        let client_store = ReplayStore::open(&client_path, key(1)).unwrap();
+        let server_store = ReplayStore::open(&server_path, key(1)).unwrap();
+        let mut client = client_store.resume_session(&resumption_secret, &client_random, &server_random, Role::Client).unwrap();
+        let mut server = server_store.resume_session(&resumption_secret, &client_random, &server_random, Role::Server).unwrap();
+        assert_eq!(server.decrypt_data(&second), Err(AegisQError::Protocol("Sequence mismatch")));
+        assert_eq!(client.decrypt_data(&reply), Err(AegisQError::Protocol("Sequence mismatch")));
+
+        let third = client.encrypt_data(b"third");
+        assert_eq!(Frame::decode(&third).unwrap().sequence, 2 + SEND_RESERVE);
+        assert_eq!(server.decrypt_data(&third).unwrap(), b"third");
+        let resumed_reply = server.encrypt_data(b"resumed");
+        assert_eq!(Frame::decode(&resumed_reply).unwrap().sequence, 1 + SEND_RESERVE);
+        assert_eq!(client.decrypt_data(&resumed_reply).unwrap(), b"resumed");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_missing_file_is_empty() {
+        let path = std::env::temp_dir().join(format!("aegis-q-replay-missing-{}", std::process::id()));
+        let mut store = ReplayStore::open(&path, key(1)).unwrap();
+        assert_eq!(store.get(b"peer"), None);
+        store.record(b"peer", ReplayMark { recv_epoch: 0, recv_next: 1, send_epoch: 0, send_next: 0 });
+        assert!(store.remove(b"peer").is_some());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,137 @@
+//! Session identifiers
+//!
+//! Stable, unforgeable session IDs derived from the handshake transcript
+//! hash or from exporter output of the session states. Both peers compute
+//! the same ID; an observer without the session secrets cannot. Safe to
+//! log, correlate and embed into application tokens.
+
+use std::fmt;
+
+use utils::kdf::kdf_shake256_fill;
+use utils::memory::zeroize;
+
+/// Session ID length
+pub const SESSION_ID_SIZE: usize = 32;
+
+/// Authenticated session identifier
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SessionId([u8; SESSION_ID_SIZE]);
+
+impl SessionId {
+    /// Derive from a handshake transcript (or transcript hash)
+    pub fn from_transcript(transcript: &[u8]) -> Self {
+        let mut id = [0u8; SESSION_ID_SIZE];
+        kdf_shake256_fill(b"aegis-q-transport-session-id", transcript, b"", &mut id);
+        Self(id)
+    }
+
+    /// Derive from the exports of a session's two directional states
+    ///
+    /// Both peers hold the states swapped; the exports are combined with
+    /// `ordered_concat` so they arrive at the same ID. The traffic keys
+    /// themselves never enter the ID.
+    pub(crate) fn from_exports(a: &[u8], b: &[u8]) -> Self {
+        let mut combined = ordered_concat(a, b);
+        let id = Self::from_transcript(&combined);
+        zeroize(&mut combined);
+        id
+    }
+
+    /// ID from its raw bytes (as carried on the wire)
+    pub fn from_bytes(bytes: [u8; SESSION_ID_SIZE]) -> Self {
+        Self(bytes)
+    }
+
+    /// Raw ID bytes
+    pub fn as_bytes(&self) -> &[u8; SESSION_ID_SIZE] {
+        &self.0
+    }
+
+    /// Lowercase hex form
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Check a presented ID (raw bytes) against this one in constant time
+    pub fn verify(&self, presented: &[u8]) -> bool {
+        if presented.len() != SESSION_ID_SIZE {
+            return false;
+        }
+        let mut diff = 0u8;
+        for (a, b) in self.0.iter().zip(presented) {
+            diff |= a ^ b;
+        }
+        diff == 0
+    }
+}
+
+/// Concatenate two equal-length secrets, smaller first, in constant time
+///
+/// The comparison and the swap are computed with masks over every byte, so
+/// neither timing nor branches depend on the secret contents.
+pub(crate) fn ordered_concat(a: &[u8], b: &[u8]) -> Vec<u8> {
+    assert_eq!(a.len(), b.len(), "ordered_concat needs equal-length inputs");
+    // Walk backwards so the first differing byte decides: swap = 0xFF iff a > b
+    let mut swap = 0u8;
+    for (&x, &y) in a.iter().zip(b).rev() {
+        let differs = (((x ^ y) as u16).wrapping_neg() >> 8) as u8;
+        let greater = ((y as u16).wrapping_sub(x as u16) >> 8) as u8;
+        swap = greater | (swap & !differs);
+    }
+    let swap = std::hint::black_box(swap);
+    let mut out = vec![0u8; a.len() * 2];
+    let (low, high) = out.split_at_mut(a.len());
+    for (i, (&x, &y)) in a.iter().zip(b).enumerate() {
+        let flip = swap & (x ^ y);
+        low[i] = x ^ flip;
+        high[i] = y ^ flip;
+    }
+    out
+}
+
+impl fmt::Display for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+impl fmt::Debug for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SessionId({})", self.to_hex())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_id_symmetric_and_verifiable() {
+        let a = SessionId::from_exports(b"export-a", b"export-b");
+        let b = SessionId::from_exports(b"export-b", b"export-a");
+        assert_eq!(a, b);
+        assert_ne!(a, SessionId::from_exports(b"export-a", b"export-c"));
+
+        assert!(a.verify(a.as_bytes()));
+        assert!(!a.verify(&[0u8; SESSION_ID_SIZE]));
+        assert!(!a.verify(&a.as_bytes()[..16]));
+        assert_eq!(a.to_hex().len(), SESSION_ID_SIZE * 2);
+    }
+
+    #[test]
+    fn test_ordered_concat_matches_lexicographic_order() {
+        let cases: [(&[u8], &[u8]); 5] = [
+            (b"\x01\x02", b"\x01\x03"),
+            (b"\x02\x00", b"\x01\xff"),
+            (b"\xff\x00", b"\x00\xff"),
+            (b"same", b"same"),
+            (b"", b""),
+        ];
+        for (a, b) in cases {
+            let (low, high) = if a <= b { (a, b) } else { (b, a) };
+            let expected = [low, high].concat();
+            assert_eq!(ordered_concat(a, b), expected);
+            assert_eq!(ordered_concat(b, a), expected);
+        }
+    }
+}
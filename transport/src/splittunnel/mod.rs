@@ -0,0 +1,343 @@
+//! Split Tunneling
+//!
+//! Routing policy evaluated before a packet enters the tunnel: each rule
+//! matches on destination CIDR, port range and (where the platform reports
+//! it) the originating process, and decides whether the packet goes
+//! through the tunnel, bypasses it, or is dropped. Rules are checked in
+//! order and the first match wins; unmatched traffic takes the policy's
+//! default action. Typical use: exclude the LAN or a specific app.
+//!
+//! Policies serialize to JSON (CIDRs as strings) and can be swapped at
+//! runtime through `PolicyEngine` without interrupting the data path.
+
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+
+use aegis_q_core::AegisQError;
+use serde::{Serialize, Deserialize};
+
+/// Private and link-local ranges treated as the local network
+pub const LAN_RANGES: &[&str] = &["10.0.0.0/8", "172.16.0.0/12", "192.168.0.0/16", "169.254.0.0/16", "fc00::/7", "fe80::/10"];
+
+/// Where a packet goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RouteAction {
+    /// Through the VPN tunnel
+    Tunnel,
+    /// Directly via the local network
+    Bypass,
+    /// Dropped
+    Block,
+}
+
+/// IP network in CIDR notation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// Network with the host bits cleared
+    pub fn new(address: IpAddr, prefix: u8) -> Result<Self, AegisQError> {
+        let max = if address.is_ipv4() { 32 } else { 128 };
+        if prefix > max {
+            return Err(AegisQError::InvalidInput("CIDR prefix too long"));
+        }
+        let network = match address {
+            IpAddr::V4(a) => IpAddr::V4((u32::from(a) & mask32(prefix)).into()),
+            IpAddr::V6(a) => IpAddr::V6((u128::from(a) & mask128(prefix)).into()),
+        };
+        Ok(Self { network, prefix })
+    }
+
+    /// Whether this is an IPv4 network
+    pub fn is_ipv4(&self) -> bool {
+        self.network.is_ipv4()
+    }
+
+    /// Whether `address` lies in this network
+    pub fn contains(&self, address: IpAddr) -> bool {
+        match (self.network, address) {
+            (IpAddr::V4(net), IpAddr::V4(a)) => u32::from(a) & mask32(self.prefix) == u32::from(net),
+            (IpAddr::V6(net), IpAddr::V6(a)) => u128::from(a) & mask128(self.prefix) == u128::from(net),
+            _ => false,
+        }
+    }
+}
+
+fn mask32(prefix: u8) -> u32 {
+    u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0)
+}
+
+fn mask128(prefix: u8) -> u128 {
+    u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0)
+}
+
+impl FromStr for Cidr {
+    type Err = AegisQError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix) = match s.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (s, None),
+        };
+        let address: IpAddr = address.parse().map_err(|_| AegisQError::InvalidInput("Invalid CIDR address"))?;
+        let prefix = match prefix {
+            Some(p) => p.parse().map_err(|_| AegisQError::InvalidInput("Invalid CIDR prefix"))?,
+            None if address.is_ipv4() => 32,
+            None => 128,
+        };
+        Self::new(address, prefix)
+    }
+}
+
+impl TryFrom<String> for Cidr {
+    type Error = AegisQError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Cidr> for String {
+    fn from(cidr: Cidr) -> Self {
+        cidr.to_string()
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+/// Inclusive destination port range
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl PortRange {
+    /// Single port
+    pub fn single(port: u16) -> Self {
+        Self { start: port, end: port }
+    }
+
+    /// Whether `port` is in range
+    pub fn contains(&self, port: u16) -> bool {
+        (self.start..=self.end).contains(&port)
+    }
+}
+
+/// Packet attributes the policy looks at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketInfo<'a> {
+    pub destination: IpAddr,
+    pub port: Option<u16>,
+    /// Originating process, if the platform reports it
+    pub process: Option<&'a str>,
+}
+
+/// One routing rule; every condition set must match
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteRule {
+    pub action: RouteAction,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub networks: Vec<Cidr>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ports: Vec<PortRange>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub processes: Vec<String>,
+}
+
+impl RouteRule {
+    /// Rule matching everything
+    pub fn new(action: RouteAction) -> Self {
+        Self {
+            action,
+            networks: Vec::new(),
+            ports: Vec::new(),
+            processes: Vec::new(),
+        }
+    }
+
+    /// Match destinations in a network
+    pub fn network(mut self, cidr: Cidr) -> Self {
+        self.networks.push(cidr);
+        self
+    }
+
+    /// Match destination ports
+    pub fn ports(mut self, range: PortRange) -> Self {
+        self.ports.push(range);
+        self
+    }
+
+    /// Match an originating process (by executable name)
+    pub fn process(mut self, name: impl Into<String>) -> Self {
+        self.processes.push(name.into());
+        self
+    }
+
+    /// Whether the packet matches this rule
+    ///
+    /// Process conditions never match packets without process information.
+    pub fn matches(&self, packet: &PacketInfo<'_>) -> bool {
+        let network = self.networks.is_empty() || self.networks.iter().any(|c| c.contains(packet.destination));
+        let port = self.ports.is_empty() || packet.port.is_some_and(|p| self.ports.iter().any(|r| r.contains(p)));
+        let process = self.processes.is_empty()
+            || packet.process.is_some_and(|p| self.processes.iter().any(|name| name.eq_ignore_ascii_case(p)));
+        network && port && process
+    }
+}
+
+/// Ordered rule set with a default action
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SplitPolicy {
+    pub default_action: RouteAction,
+    #[serde(default)]
+    pub rules: Vec<RouteRule>,
+}
+
+impl Default for SplitPolicy {
+    /// Full tunnel
+    fn default() -> Self {
+        Self {
+            default_action: RouteAction::Tunnel,
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl SplitPolicy {
+    /// Policy with the given default and no rules
+    pub fn new(default_action: RouteAction) -> Self {
+        Self {
+            default_action,
+            rules: Vec::new(),
+        }
+    }
+
+    /// Append a rule (evaluated after existing ones)
+    pub fn rule(mut self, rule: RouteRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Full tunnel except private LAN ranges
+    pub fn exclude_lan() -> Self {
+        let mut rule = RouteRule::new(RouteAction::Bypass);
+        for cidr in LAN_RANGES {
+            rule = rule.network(cidr.parse().expect("valid LAN range"));
+        }
+        Self::default().rule(rule)
+    }
+
+    /// Route for a packet
+    pub fn evaluate(&self, packet: &PacketInfo<'_>) -> RouteAction {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(packet))
+            .map_or(self.default_action, |rule| rule.action)
+    }
+
+    /// Serialize to JSON
+    pub fn to_json(&self) -> Result<String, AegisQError> {
+        serde_json::to_string_pretty(self).map_err(|_| AegisQError::Serialization("Failed to serialize policy"))
+    }
+
+    /// Parse from JSON
+    pub fn from_json(json: &str) -> Result<Self, AegisQError> {
+        serde_json::from_str(json).map_err(|_| AegisQError::InvalidInput("Invalid split tunnel policy"))
+    }
+}
+
+/// Shared, runtime-updatable policy
+#[derive(Debug, Clone, Default)]
+pub struct PolicyEngine {
+    current: Arc<RwLock<Arc<SplitPolicy>>>,
+}
+
+impl PolicyEngine {
+    /// Engine starting with `policy`
+    pub fn new(policy: SplitPolicy) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(Arc::new(policy))),
+        }
+    }
+
+    /// Replace the policy; in-flight evaluations finish on the old one
+    pub fn update(&self, policy: SplitPolicy) {
+        *self.current.write().expect("policy lock poisoned") = Arc::new(policy);
+    }
+
+    /// Snapshot of the active policy
+    pub fn policy(&self) -> Arc<SplitPolicy> {
+        self.current.read().expect("policy lock poisoned").clone()
+    }
+
+    /// Route for a packet under the active policy
+    pub fn evaluate(&self, packet: &PacketInfo<'_>) -> RouteAction {
+        self.policy().evaluate(packet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(destination: &str, port: u16, process: Option<&'static str>) -> PacketInfo<'static> {
+        PacketInfo {
+            destination: destination.parse().unwrap(),
+            port: Some(port),
+            process,
+        }
+    }
+
+    #[test]
+    fn test_cidr() {
+        let cidr: Cidr = "192.168.1.77/24".parse().unwrap();
+        assert_eq!(cidr.to_string(), "192.168.1.0/24");
+        assert!(cidr.contains("192.168.1.200".parse().unwrap()));
+        assert!(!cidr.contains("192.168.2.1".parse().unwrap()));
+        assert!(!cidr.contains("::1".parse().unwrap()));
+        assert!("0.0.0.0/0".parse::<Cidr>().unwrap().contains("8.8.8.8".parse().unwrap()));
+        assert!("fe80::/10".parse::<Cidr>().unwrap().contains("fe80::1".parse().unwrap()));
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn test_first_match_wins() {
+        let policy = SplitPolicy::exclude_lan()
+            .rule(RouteRule::new(RouteAction::Bypass).process("steam"))
+            .rule(RouteRule::new(RouteAction::Block).ports(PortRange::single(25)));
+
+        assert_eq!(policy.evaluate(&packet("192.168.0.10", 443, None)), RouteAction::Bypass);
+        assert_eq!(policy.evaluate(&packet("203.0.113.1", 443, Some("Steam"))), RouteAction::Bypass);
+        assert_eq!(policy.evaluate(&packet("203.0.113.1", 443, None)), RouteAction::Tunnel);
+        assert_eq!(policy.evaluate(&packet("203.0.113.1", 25, None)), RouteAction::Block);
+    }
+
+    #[test]
+    fn test_serialization_and_runtime_update() {
+        let policy = SplitPolicy::new(RouteAction::Bypass)
+            .rule(RouteRule::new(RouteAction::Tunnel).network("10.20.0.0/16".parse().unwrap()).ports(PortRange { start: 8000, end: 8999 }));
+        let json = policy.to_json().unwrap();
+        assert!(json.contains("\"10.20.0.0/16\""));
+        assert_eq!(SplitPolicy::from_json(&json).unwrap(), policy);
+        assert!(SplitPolicy::from_json(r#"{"default_action":"tunnel","rules":[{"action":"bypass","networks":["nope"]}]}"#).is_err());
+
+        let engine = PolicyEngine::default();
+        let target = packet("10.20.1.1", 8080, None);
+        assert_eq!(engine.evaluate(&target), RouteAction::Tunnel);
+        let handle = engine.clone();
+        handle.update(SplitPolicy::new(RouteAction::Block));
+        assert_eq!(engine.evaluate(&target), RouteAction::Block);
+    }
+}
@@ -0,0 +1,248 @@
+//! systemd Integration
+//!
+//! Helpers for running the server under systemd (or as a classic root
+//! daemon):
+//! - socket activation: take over listening sockets passed in via
+//!   `LISTEN_FDS` instead of binding them
+//! - readiness: `sd_notify` messages (`READY=1`, `STATUS=...`, watchdog)
+//! - privilege drop: bind privileged ports as root, then switch to an
+//!   unprivileged user and group for the rest of the process lifetime
+//!
+//! All calls are no-ops (or report "not activated") when the process was
+//! not started by systemd, so the same binary works in both setups.
+//!
+//! These are library helpers for the embedding server binary; none of the
+//! binaries in this workspace (`decrypt-capture`, `dissect`) calls them.
+
+use std::env;
+use std::ffi::CString;
+use std::net::{TcpListener, UdpSocket};
+use std::os::fd::{FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixDatagram;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use aegis_q_core::AegisQError;
+
+/// First file descriptor passed by systemd
+pub const SD_LISTEN_FDS_START: RawFd = 3;
+
+/// Set once `listen_fds` has taken ownership of the inherited descriptors
+static LISTEN_FDS_TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Socket inherited from the service manager
+#[derive(Debug)]
+pub struct ListenFd {
+    fd: OwnedFd,
+    name: Option<String>,
+}
+
+impl ListenFd {
+    /// Name from `FileDescriptorName=` in the socket unit
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Use as a TCP listener (`ListenStream=`)
+    pub fn into_tcp_listener(self) -> TcpListener {
+        TcpListener::from(self.fd)
+    }
+
+    /// Use as a UDP socket (`ListenDatagram=`)
+    pub fn into_udp_socket(self) -> UdpSocket {
+        UdpSocket::from(self.fd)
+    }
+}
+
+/// Parsed socket activation environment: descriptor count and names
+fn parse_listen_env(
+    pid: Option<&str>,
+    fds: Option<&str>,
+    names: Option<&str>,
+    own_pid: u32,
+) -> Result<Option<Vec<Option<String>>>, AegisQError> {
+    let (Some(pid), Some(fds)) = (pid, fds) else {
+        return Ok(None);
+    };
+    if pid.parse::<u32>().map_err(|_| AegisQError::InvalidInput("Invalid LISTEN_PID"))? != own_pid {
+        // Meant for another process (e.g. our parent)
+        return Ok(None);
+    }
+    let count: usize = fds.parse().map_err(|_| AegisQError::InvalidInput("Invalid LISTEN_FDS"))?;
+    let mut parsed: Vec<Option<String>> = names
+        .map(|n| n.split(':').map(|s| Some(s.to_string()).filter(|s| !s.is_empty())).collect())
+        .unwrap_or_default();
+    if !parsed.is_empty() && parsed.len() != count {
+        return Err(AegisQError::InvalidInput("LISTEN_FDNAMES does not match LISTEN_FDS"));
+    }
+    parsed.resize(count, None);
+    Ok(Some(parsed))
+}
+
+/// Take the sockets passed by systemd socket activation
+///
+/// Returns an empty list when the process was not socket-activated, and on
+/// every call after the first, so a descriptor is never owned twice. The
+/// descriptors are marked close-on-exec.
+///
+/// The activation variables are left in the environment: modifying it is
+/// unsound once other threads may read it. A server that spawns children
+/// should unset `LISTEN_PID`, `LISTEN_FDS` and `LISTEN_FDNAMES` at the
+/// start of `main`, before any thread exists (`LISTEN_PID` already keeps
+/// children from taking the sockets).
+pub fn listen_fds() -> Result<Vec<ListenFd>, AegisQError> {
+    if LISTEN_FDS_TAKEN.swap(true, Ordering::SeqCst) {
+        return Ok(Vec::new());
+    }
+    let pid = env::var("LISTEN_PID").ok();
+    let fds = env::var("LISTEN_FDS").ok();
+    let names = env::var("LISTEN_FDNAMES").ok();
+
+    let Some(names) = parse_listen_env(pid.as_deref(), fds.as_deref(), names.as_deref(), std::process::id())? else {
+        return Ok(Vec::new());
+    };
+    let mut sockets = Vec::with_capacity(names.len());
+    for (i, name) in names.into_iter().enumerate() {
+        let raw = SD_LISTEN_FDS_START + i as RawFd;
+        // SAFETY: systemd hands over descriptors 3..3+LISTEN_FDS to this
+        // process (LISTEN_PID matched); LISTEN_FDS_TAKEN makes this the only
+        // call that wraps them, so each is owned exactly once.
+        unsafe {
+            if libc::fcntl(raw, libc::F_SETFD, libc::FD_CLOEXEC) < 0 {
+                return Err(AegisQError::Io("Inherited descriptor is not open"));
+            }
+            sockets.push(ListenFd {
+                fd: OwnedFd::from_raw_fd(raw),
+                name,
+            });
+        }
+    }
+    Ok(sockets)
+}
+
+/// Send a raw `sd_notify` message
+///
+/// Returns `false` without error when `NOTIFY_SOCKET` is not set.
+pub fn notify(state: &str) -> Result<bool, AegisQError> {
+    let Ok(path) = env::var("NOTIFY_SOCKET") else {
+        return Ok(false);
+    };
+    let socket = UnixDatagram::unbound().map_err(|_| AegisQError::Io("Failed to create notify socket"))?;
+    match path.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        Some(abstract_name) => {
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(abstract_name.as_bytes())
+                .map_err(|_| AegisQError::InvalidInput("Invalid NOTIFY_SOCKET"))?;
+            socket.send_to_addr(state.as_bytes(), &addr).map_err(|_| AegisQError::Io("Failed to notify service manager"))?;
+        }
+        #[cfg(not(target_os = "linux"))]
+        Some(_) => return Err(AegisQError::Unsupported("Abstract NOTIFY_SOCKET not supported")),
+        None => {
+            socket.send_to(state.as_bytes(), &path).map_err(|_| AegisQError::Io("Failed to notify service manager"))?;
+        }
+    }
+    Ok(true)
+}
+
+/// Startup finished (`READY=1`)
+pub fn notify_ready() -> Result<bool, AegisQError> {
+    notify("READY=1")
+}
+
+/// Shutdown started (`STOPPING=1`)
+pub fn notify_stopping() -> Result<bool, AegisQError> {
+    notify("STOPPING=1")
+}
+
+/// Free-form status line shown by `systemctl status`
+pub fn notify_status(status: &str) -> Result<bool, AegisQError> {
+    notify(&format!("STATUS={}", status.replace('\n', " ")))
+}
+
+/// Watchdog keepalive (`WATCHDOG=1`)
+pub fn notify_watchdog() -> Result<bool, AegisQError> {
+    notify("WATCHDOG=1")
+}
+
+/// Switch to an unprivileged user (and group) permanently
+///
+/// Call after binding privileged ports and opening key files. The group
+/// defaults to the user's primary group; supplementary groups are cleared.
+/// Fails if not running as root or if root could be regained afterwards.
+pub fn drop_privileges(user: &str, group: Option<&str>) -> Result<(), AegisQError> {
+    // SAFETY: plain libc identity calls; returned passwd/group records are
+    // copied out before the next lookup.
+    unsafe {
+        if libc::geteuid() != 0 {
+            return Err(AegisQError::Io("Not running as root"));
+        }
+        let user_c = CString::new(user).map_err(|_| AegisQError::InvalidInput("Invalid user name"))?;
+        let passwd = libc::getpwnam(user_c.as_ptr());
+        if passwd.is_null() {
+            return Err(AegisQError::NotFound("Unknown user"));
+        }
+        let uid = (*passwd).pw_uid;
+        let mut gid = (*passwd).pw_gid;
+
+        if let Some(group) = group {
+            let group_c = CString::new(group).map_err(|_| AegisQError::InvalidInput("Invalid group name"))?;
+            let entry = libc::getgrnam(group_c.as_ptr());
+            if entry.is_null() {
+                return Err(AegisQError::NotFound("Unknown group"));
+            }
+            gid = (*entry).gr_gid;
+        }
+        if uid == 0 {
+            return Err(AegisQError::InvalidInput("Refusing to drop privileges to root"));
+        }
+
+        if libc::setgroups(0, std::ptr::null()) != 0 {
+            return Err(AegisQError::Io("setgroups failed"));
+        }
+        if libc::setgid(gid) != 0 {
+            return Err(AegisQError::Io("setgid failed"));
+        }
+        if libc::setuid(uid) != 0 {
+            return Err(AegisQError::Io("setuid failed"));
+        }
+        if libc::setuid(0) == 0 {
+            return Err(AegisQError::Io("Privileges could be regained"));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_listen_env() {
+        assert_eq!(parse_listen_env(None, None, None, 42), Ok(None));
+        assert_eq!(parse_listen_env(Some("41"), Some("2"), None, 42), Ok(None));
+        assert_eq!(parse_listen_env(Some("42"), Some("2"), None, 42), Ok(Some(vec![None, None])));
+        assert_eq!(
+            parse_listen_env(Some("42"), Some("2"), Some("quic:"), 42),
+            Ok(Some(vec![Some("quic".to_string()), None]))
+        );
+        assert!(parse_listen_env(Some("42"), Some("2"), Some("quic"), 42).is_err());
+        assert!(parse_listen_env(Some("x"), Some("2"), None, 42).is_err());
+    }
+
+    #[test]
+    fn test_notify() {
+        let dir = env::temp_dir().join(format!("aegisq-notify-{}", std::process::id()));
+        let _ = std::fs::remove_file(&dir);
+        let receiver = UnixDatagram::bind(&dir).unwrap();
+
+        env::set_var("NOTIFY_SOCKET", &dir);
+        assert_eq!(notify_status("serving\n2 listeners"), Ok(true));
+        env::remove_var("NOTIFY_SOCKET");
+        assert_eq!(notify_ready(), Ok(false));
+
+        let mut buf = [0u8; 64];
+        let n = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"STATUS=serving 2 listeners");
+        std::fs::remove_file(&dir).unwrap();
+    }
+}
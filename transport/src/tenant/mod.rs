@@ -0,0 +1,605 @@
+//! Tenant Isolation
+//!
+//! Lets one server process host many customers. Every tenant gets its own
+//! key namespace: a `ServerKeyring` of identity keys, `TicketKeys` for
+//! session tickets, its own `Metrics` and (with `entitlements`) its own
+//! license policy. Nothing is shared between namespaces: a key is
+//! installed for exactly one tenant, and every call names the tenant it
+//! acts for, so a session or ticket of one tenant can not be resumed,
+//! closed or counted through another.
+//!
+//! `SessionManager` tracks which tenant owns each session; closing a
+//! session through the wrong tenant is a `Policy` error. A tenant with an
+//! `AuthProvider` only admits clients whose hello carries a valid auth
+//! extension; every rejection is the same `AuthenticationFailed` to the
+//! peer, and the reason is queued for server logs (`drain_auth_failures`).
+//! An `AuthGuard` shared by all tenants refuses sources that keep failing
+//! handshakes or authentication before any handshake work is done.
+//!
+//! The manager itself is driven through `&mut self` by one thread; the
+//! state it shares with other threads is each tenant's `Arc<Metrics>`
+//! (`metrics`), which the loom tests model-check.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+#[cfg(loom)]
+use loom::sync::Arc;
+#[cfg(not(loom))]
+use std::sync::Arc;
+
+use aegis_q_core::AegisQError;
+
+use crate::auth::{authenticate_hello, AuthError, AuthProvider, AuthenticatedClient};
+use crate::authguard::{AuthGuard, GuardConfig, GuardEvent};
+#[cfg(feature = "entitlements")]
+use crate::entitlement::{EntitlementPolicy, Entitlements};
+use crate::hello::ClientHello;
+use crate::keyring::{KeyId, ServerKeyring};
+use crate::metrics::{self, HandshakeResult, Metrics};
+use crate::session::SessionId;
+use crate::tickets::{TicketKeyId, TicketKeys, TicketPolicy};
+use crate::vpn::Handshake;
+
+/// Maximum length of a tenant identifier
+pub const MAX_TENANT_ID_LEN: usize = 64;
+
+/// Name of a tenant: 1–64 characters of `[A-Za-z0-9._-]`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TenantId(String);
+
+impl TenantId {
+    /// Validate a tenant name
+    pub fn new(name: &str) -> Result<Self, AegisQError> {
+        let valid_char = |c: char| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-');
+        if name.is_empty() || name.len() > MAX_TENANT_ID_LEN || !name.chars().all(valid_char) {
+            return Err(AegisQError::InvalidInput("Invalid tenant ID"));
+        }
+        Ok(Self(name.to_string()))
+    }
+
+    /// Tenant name
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for TenantId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+struct Tenant {
+    keyring: ServerKeyring,
+    tickets: TicketKeys,
+    metrics: Arc<Metrics>,
+    auth: Option<Arc<dyn AuthProvider>>,
+    #[cfg(feature = "entitlements")]
+    entitlements: Option<EntitlementPolicy>,
+}
+
+/// Open session and what it was admitted with
+struct OpenSession {
+    tenant: TenantId,
+    key: KeyId,
+    client: Option<AuthenticatedClient>,
+    #[cfg(feature = "entitlements")]
+    entitlements: Option<Entitlements>,
+}
+
+/// Sessions and keys of all tenants in one process
+pub struct SessionManager {
+    overlap_ms: u64,
+    tenants: HashMap<TenantId, Tenant>,
+    sessions: HashMap<SessionId, OpenSession>,
+    auth_failures: Vec<(TenantId, AuthError)>,
+    guard: AuthGuard,
+}
+
+impl SessionManager {
+    /// No tenants; retiring identity keys stay usable for `overlap_ms`
+    pub fn new(overlap_ms: u64) -> Self {
+        Self {
+            overlap_ms,
+            tenants: HashMap::new(),
+            sessions: HashMap::new(),
+            auth_failures: Vec::new(),
+            guard: AuthGuard::default(),
+        }
+    }
+
+    /// Override the failure thresholds (e.g. `GuardConfig::from(&config.limits)`)
+    pub fn with_guard(mut self, config: GuardConfig) -> Self {
+        self.guard = AuthGuard::new(config);
+        self
+    }
+
+    /// Register a tenant with its identity key and STEK
+    ///
+    /// Fails if the tenant exists or either key is already installed for
+    /// another tenant.
+    pub fn add_tenant(&mut self, tenant: TenantId, identity_key: &[u8], stek: &[u8], ticket_policy: TicketPolicy, now_ms: u64) -> Result<(), AegisQError> {
+        if self.tenants.contains_key(&tenant) {
+            return Err(AegisQError::InvalidInput("Tenant already exists"));
+        }
+        self.check_identity_key_unused(identity_key)?;
+        let stek_id = TicketKeyId::of(stek);
+        if self.tenants.values().any(|other| other.tickets.key_ids().contains(&stek_id)) {
+            return Err(AegisQError::Policy("Ticket key belongs to another tenant"));
+        }
+        let tickets = TicketKeys::new(stek, now_ms, ticket_policy)?;
+        self.tenants.insert(tenant, Tenant {
+            keyring: ServerKeyring::new(identity_key, self.overlap_ms),
+            tickets,
+            metrics: Arc::new(Metrics::new()),
+            auth: None,
+            #[cfg(feature = "entitlements")]
+            entitlements: None,
+        });
+        Ok(())
+    }
+
+    /// Drop a tenant with its keys and sessions
+    pub fn remove_tenant(&mut self, tenant: &TenantId) -> Result<(), AegisQError> {
+        self.tenants.remove(tenant).ok_or(AegisQError::NotFound("Unknown tenant"))?;
+        self.sessions.retain(|_, open| open.tenant != *tenant);
+        Ok(())
+    }
+
+    /// Registered tenants, sorted
+    pub fn tenants(&self) -> Vec<TenantId> {
+        let mut tenants: Vec<TenantId> = self.tenants.keys().cloned().collect();
+        tenants.sort();
+        tenants
+    }
+
+    /// Server side of a handshake from `source` with `tenant`'s identity key
+    ///
+    /// A source the guard refuses gets `LimitExceeded` before any key work;
+    /// every failure below is recorded against `source`.
+    /// `requested` selects one of the tenant's keys, as in
+    /// `ServerKeyring::accept`; keys of other tenants are unknown here.
+    /// With an auth provider set, `hello` must carry an auth extension the
+    /// provider admits, otherwise the result is `AuthenticationFailed`.
+    /// With a license policy set (feature `entitlements`), `hello` must
+    /// carry a license the policy admits; the result is kept as the
+    /// session's `entitlements`.
+    pub fn accept(
+        &mut self,
+        tenant: &TenantId,
+        source: IpAddr,
+        client_key: &[u8],
+        requested: Option<KeyId>,
+        hello: &ClientHello,
+        now_ms: u64,
+    ) -> Result<(Handshake, SessionId), AegisQError> {
+        if self.guard.check(source, now_ms).is_err() {
+            return Err(AegisQError::LimitExceeded("Too many failed handshakes"));
+        }
+        let entry = self.tenants.get_mut(tenant).ok_or(AegisQError::NotFound("Unknown tenant"))?;
+        let (handshake, key) = match entry.keyring.accept(client_key, requested, now_ms) {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                entry.metrics.handshake(HandshakeResult::Failure);
+                self.guard.record_failure(source, now_ms);
+                return Err(e);
+            }
+        };
+
+        let client = match &entry.auth {
+            None => None,
+            Some(provider) => match authenticate_hello(provider.as_ref(), hello, &handshake) {
+                Ok(client) => Some(client),
+                Err(e) => {
+                    entry.keyring.close_session(key);
+                    entry.metrics.handshake(HandshakeResult::Failure);
+                    entry.metrics.auth_failure();
+                    self.auth_failures.push((tenant.clone(), e));
+                    self.guard.record_failure(source, now_ms);
+                    return Err(AegisQError::AuthenticationFailed);
+                }
+            },
+        };
+
+        #[cfg(feature = "entitlements")]
+        let entitlements = match &entry.entitlements {
+            None => None,
+            Some(policy) => match policy.admit_hello(hello, &handshake, now_ms / 1000) {
+                Ok(entitlements) => Some(entitlements),
+                Err(_) => {
+                    entry.keyring.close_session(key);
+                    entry.metrics.handshake(HandshakeResult::Failure);
+                    self.guard.record_failure(source, now_ms);
+                    return Err(AegisQError::Policy("License rejected"));
+                }
+            },
+        };
+        entry.metrics.handshake(HandshakeResult::Success);
+        entry.metrics.session_opened();
+        self.guard.record_success(source);
+        let session = handshake.session_id();
+        let open = OpenSession {
+            tenant: tenant.clone(),
+            key,
+            client,
+            #[cfg(feature = "entitlements")]
+            entitlements,
+        };
+        self.sessions.insert(session, open);
+        Ok((handshake, session))
+    }
+
+    /// Tenant owning an open session
+    pub fn owner(&self, session: &SessionId) -> Option<&TenantId> {
+        self.sessions.get(session).map(|open| &open.tenant)
+    }
+
+    /// A session of `tenant` has ended
+    pub fn close_session(&mut self, tenant: &TenantId, session: &SessionId) -> Result<(), AegisQError> {
+        match self.sessions.get(session) {
+            None => return Err(AegisQError::NotFound("Unknown session")),
+            Some(open) if open.tenant != *tenant => return Err(AegisQError::Policy("Session belongs to another tenant")),
+            Some(_) => {}
+        }
+        let key = self.sessions.remove(session).expect("session checked above").key;
+        let entry = self.tenant_mut(tenant)?;
+        entry.keyring.close_session(key);
+        entry.metrics.session_closed();
+        Ok(())
+    }
+
+    /// Install a new identity key for `tenant`
+    pub fn rotate_identity(&mut self, tenant: &TenantId, key: &[u8], now_ms: u64) -> Result<KeyId, AegisQError> {
+        self.tenant(tenant)?;
+        self.check_identity_key_unused(key)?;
+        self.tenant_mut(tenant)?.keyring.rotate(key, now_ms)
+    }
+
+    /// Active identity key of `tenant`
+    pub fn active_identity(&self, tenant: &TenantId) -> Result<KeyId, AegisQError> {
+        Ok(self.tenant(tenant)?.keyring.active())
+    }
+
+    /// Install a new STEK for `tenant`
+    pub fn rotate_ticket_key(&mut self, tenant: &TenantId, stek: &[u8], now_ms: u64) -> Result<TicketKeyId, AegisQError> {
+        let id = TicketKeyId::of(stek);
+        if self.tenants.iter().any(|(other, entry)| other != tenant && entry.tickets.key_ids().contains(&id)) {
+            return Err(AegisQError::Policy("Ticket key belongs to another tenant"));
+        }
+        self.tenant_mut(tenant)?.tickets.rotate(stek, now_ms)
+    }
+
+    /// Session ticket sealed with `tenant`'s STEK
+    pub fn issue_ticket(&self, tenant: &TenantId, state: &[u8], now_ms: u64) -> Result<Vec<u8>, AegisQError> {
+        Ok(self.tenant(tenant)?.tickets.issue(state, now_ms))
+    }
+
+    /// Open a ticket of `tenant`; tickets of other tenants fail
+    pub fn open_ticket(&self, tenant: &TenantId, ticket: &[u8], now_ms: u64) -> Result<Vec<u8>, AegisQError> {
+        self.tenant(tenant)?.tickets.open(ticket, now_ms)
+    }
+
+    /// Retire expired identity keys, run timed STEK rotation of all
+    /// tenants and expire guard bans
+    pub fn poll(&mut self, now_ms: u64) {
+        for entry in self.tenants.values_mut() {
+            entry.keyring.expire(now_ms);
+            entry.tickets.poll(now_ms);
+        }
+        self.guard.expire(now_ms);
+    }
+
+    /// Take queued guard ban/unban events, for logging
+    pub fn drain_guard_events(&mut self) -> Vec<GuardEvent> {
+        self.guard.drain_events()
+    }
+
+    /// Counters of `tenant`, for sharing with its workers
+    pub fn metrics(&self, tenant: &TenantId) -> Result<Arc<Metrics>, AegisQError> {
+        Ok(Arc::clone(&self.tenant(tenant)?.metrics))
+    }
+
+    /// Prometheus exposition of all tenants, labeled by tenant
+    pub fn render_metrics(&self) -> String {
+        let tenants = self.tenants();
+        let sets: Vec<(&str, &Metrics)> = tenants
+            .iter()
+            .map(|tenant| (tenant.as_str(), self.tenants[tenant].metrics.as_ref()))
+            .collect();
+        metrics::render_tenants(&sets)
+    }
+
+    /// Require clients of `tenant` to authenticate against `provider`
+    pub fn set_auth_provider(&mut self, tenant: &TenantId, provider: Arc<dyn AuthProvider>) -> Result<(), AegisQError> {
+        self.tenant_mut(tenant)?.auth = Some(provider);
+        Ok(())
+    }
+
+    /// Client an open session authenticated as, if its tenant has a provider
+    pub fn session_client(&self, session: &SessionId) -> Option<&AuthenticatedClient> {
+        self.sessions.get(session)?.client.as_ref()
+    }
+
+    /// Take queued authentication failures with their reasons, for logging
+    pub fn drain_auth_failures(&mut self) -> Vec<(TenantId, AuthError)> {
+        std::mem::take(&mut self.auth_failures)
+    }
+
+    /// Set the license policy of `tenant`
+    #[cfg(feature = "entitlements")]
+    pub fn set_entitlements(&mut self, tenant: &TenantId, policy: EntitlementPolicy) -> Result<(), AegisQError> {
+        self.tenant_mut(tenant)?.entitlements = Some(policy);
+        Ok(())
+    }
+
+    /// License policy of `tenant`, if one is set
+    #[cfg(feature = "entitlements")]
+    pub fn entitlements(&self, tenant: &TenantId) -> Result<Option<&EntitlementPolicy>, AegisQError> {
+        Ok(self.tenant(tenant)?.entitlements.as_ref())
+    }
+
+    /// Entitlements an open session was admitted with
+    #[cfg(feature = "entitlements")]
+    pub fn session_entitlements(&self, session: &SessionId) -> Option<&Entitlements> {
+        self.sessions.get(session)?.entitlements.as_ref()
+    }
+
+    fn tenant(&self, tenant: &TenantId) -> Result<&Tenant, AegisQError> {
+        self.tenants.get(tenant).ok_or(AegisQError::NotFound("Unknown tenant"))
+    }
+
+    fn tenant_mut(&mut self, tenant: &TenantId) -> Result<&mut Tenant, AegisQError> {
+        self.tenants.get_mut(tenant).ok_or(AegisQError::NotFound("Unknown tenant"))
+    }
+
+    fn check_identity_key_unused(&self, key: &[u8]) -> Result<(), AegisQError> {
+        let id = KeyId::of(key);
+        if self.tenants.values().any(|entry| entry.keyring.key_ids().contains(&id)) {
+            return Err(AegisQError::Policy("Identity key already installed"));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for SessionManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionManager")
+            .field("tenants", &self.tenants())
+            .field("sessions", &self.sessions.len())
+            .finish()
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::keyring::DEFAULT_OVERLAP_MS;
+
+    const SOURCE: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 1));
+    const OTHER_SOURCE: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 2));
+
+    fn manager() -> (SessionManager, TenantId, TenantId) {
+        let mut manager = SessionManager::new(DEFAULT_OVERLAP_MS);
+        let a = TenantId::new("acme").unwrap();
+        let b = TenantId::new("globex-2").unwrap();
+        manager.add_tenant(a.clone(), b"acme identity", b"acme stek", TicketPolicy::default(), 0).unwrap();
+        manager.add_tenant(b.clone(), b"globex identity", b"globex stek", TicketPolicy::default(), 0).unwrap();
+        (manager, a, b)
+    }
+
+    #[test]
+    fn test_tenant_isolation() {
+        let (mut manager, a, b) = manager();
+
+        let (_, session) = manager.accept(&a, SOURCE, b"client", None, &ClientHello::new(), 10).unwrap();
+        assert_eq!(manager.owner(&session), Some(&a));
+        let b_key = manager.active_identity(&b).unwrap();
+        assert!(matches!(manager.accept(&a, SOURCE, b"client", Some(b_key), &ClientHello::new(), 10), Err(AegisQError::NotFound(_))));
+        assert!(matches!(manager.close_session(&b, &session), Err(AegisQError::Policy(_))));
+        manager.close_session(&a, &session).unwrap();
+
+        let ticket = manager.issue_ticket(&a, b"resume", 20).unwrap();
+        assert_eq!(manager.open_ticket(&a, &ticket, 30).unwrap(), b"resume");
+        assert!(manager.open_ticket(&b, &ticket, 30).is_err());
+
+        let unknown = TenantId::new("initech").unwrap();
+        assert!(matches!(manager.issue_ticket(&unknown, b"x", 0), Err(AegisQError::NotFound(_))));
+        assert!(TenantId::new("").is_err());
+        assert!(TenantId::new("a\"b").is_err());
+    }
+
+    #[test]
+    fn test_keys_not_shared() {
+        let (mut manager, a, b) = manager();
+        let c = TenantId::new("c").unwrap();
+        assert!(matches!(
+            manager.add_tenant(c.clone(), b"acme identity", b"c stek", TicketPolicy::default(), 0),
+            Err(AegisQError::Policy(_))
+        ));
+        assert!(matches!(
+            manager.add_tenant(c, b"c identity", b"globex stek", TicketPolicy::default(), 0),
+            Err(AegisQError::Policy(_))
+        ));
+        assert!(manager.add_tenant(a.clone(), b"other", b"other", TicketPolicy::default(), 0).is_err());
+        assert!(matches!(manager.rotate_identity(&b, b"acme identity", 5), Err(AegisQError::Policy(_))));
+        assert!(matches!(manager.rotate_ticket_key(&b, b"acme stek", 5), Err(AegisQError::Policy(_))));
+        manager.rotate_identity(&b, b"globex identity 2", 5).unwrap();
+
+        manager.remove_tenant(&a).unwrap();
+        assert_eq!(manager.tenants(), vec![b]);
+    }
+
+    #[test]
+    fn test_per_tenant_metrics() {
+        let (mut manager, a, b) = manager();
+        manager.accept(&a, SOURCE, b"client 1", None, &ClientHello::new(), 0).unwrap();
+        manager.accept(&a, SOURCE, b"client 2", None, &ClientHello::new(), 0).unwrap();
+        let _ = manager.accept(&b, SOURCE, b"client 3", Some(KeyId::of(b"missing")), &ClientHello::new(), 0);
+
+        assert_eq!(manager.metrics(&a).unwrap().active_sessions(), 2);
+        assert_eq!(manager.metrics(&b).unwrap().active_sessions(), 0);
+        let text = manager.render_metrics();
+        assert_eq!(text.matches("# TYPE aegisq_active_sessions gauge").count(), 1);
+        assert!(text.contains("aegisq_active_sessions{tenant=\"acme\"} 2"));
+        assert!(text.contains("aegisq_handshakes_total{tenant=\"globex-2\",result=\"failure\"} 1"));
+    }
+
+    #[test]
+    fn test_handshake_requires_auth() {
+        use crate::auth::{encode_auth_extension, ClientRecord, MemoryAuthProvider, RejectReason};
+
+        let (mut manager, a, b) = manager();
+        let provider = MemoryAuthProvider::new();
+        provider.insert(ClientRecord::new("alice", b"alice-psk").entitlement("vpn"));
+        manager.set_auth_provider(&a, Arc::new(provider)).unwrap();
+
+        let client = Handshake::perform(b"client", b"acme identity");
+        let hello = |identity: &str, psk: &[u8]| {
+            let mut hello = ClientHello::new();
+            hello.push_encoded(&encode_auth_extension(identity, psk, &client).unwrap()).unwrap();
+            hello
+        };
+
+        let (_, session) = manager.accept(&a, SOURCE, b"client", None, &hello("alice", b"alice-psk"), 10).unwrap();
+        assert_eq!(manager.session_client(&session).unwrap().identity, "alice");
+
+        // Unknown identity and wrong PSK look the same to the peer
+        for (i, rejected) in [hello("bob", b"bob-psk"), hello("alice", b"guess"), ClientHello::new()].iter().enumerate() {
+            let now_ms = 10 + i as u64 * 1_000;
+            assert!(matches!(manager.accept(&a, SOURCE, b"client", None, rejected, now_ms), Err(AegisQError::AuthenticationFailed)));
+        }
+        let failures = manager.drain_auth_failures();
+        assert_eq!(failures.len(), 3);
+        assert_eq!(failures[0], (a.clone(), AuthError::Rejected(RejectReason::UnknownIdentity)));
+        assert_eq!(failures[1].1, AuthError::Rejected(RejectReason::BadProof));
+        assert_eq!(failures[2].1, AuthError::MalformedExtension);
+        assert!(manager.drain_auth_failures().is_empty());
+        assert_eq!(manager.metrics(&a).unwrap().active_sessions(), 1);
+        assert!(manager.render_metrics().contains("aegisq_auth_failures_total{tenant=\"acme\"} 3"));
+
+        // Tenants without a provider do not ask for credentials
+        let (_, open) = manager.accept(&b, OTHER_SOURCE, b"client", None, &ClientHello::new(), 10).unwrap();
+        assert!(manager.session_client(&open).is_none());
+    }
+
+    #[test]
+    fn test_guard_refuses_failing_sources() {
+        let (manager, a, _) = manager();
+        let mut manager = manager.with_guard(GuardConfig { max_failures: 2, ..GuardConfig::default() });
+        let attacker = IpAddr::V4(std::net::Ipv4Addr::new(198, 51, 100, 9));
+        let missing = Some(KeyId::of(b"missing"));
+
+        assert!(matches!(manager.accept(&a, attacker, b"client", missing, &ClientHello::new(), 0), Err(AegisQError::NotFound(_))));
+        // Backing off: refused before the keyring is asked
+        assert!(matches!(
+            manager.accept(&a, attacker, b"client", None, &ClientHello::new(), 100),
+            Err(AegisQError::LimitExceeded(_))
+        ));
+        assert!(manager.accept(&a, attacker, b"client", missing, &ClientHello::new(), 1_000).is_err());
+        assert!(matches!(manager.drain_guard_events()[..], [GuardEvent::Banned { source, .. }] if source == attacker));
+        assert!(matches!(
+            manager.accept(&a, attacker, b"client", None, &ClientHello::new(), 30_000),
+            Err(AegisQError::LimitExceeded(_))
+        ));
+
+        // Other sources are unaffected; the ban lifts on `poll`
+        manager.accept(&a, SOURCE, b"client", None, &ClientHello::new(), 30_000).unwrap();
+        manager.poll(61_000);
+        assert!(matches!(manager.drain_guard_events()[..], [GuardEvent::Unbanned { .. }]));
+        manager.accept(&a, attacker, b"client", None, &ClientHello::new(), 61_000).unwrap();
+    }
+
+    #[cfg(feature = "entitlements")]
+    #[test]
+    fn test_handshake_requires_license() {
+        use licensing::License;
+        use utils::keys::SigningKey;
+
+        use crate::entitlement::encode_license_extension;
+
+        let (mut manager, a, b) = manager();
+        let vendor = SigningKey::from_bytes(b"vendor-key");
+        manager.set_entitlements(&a, EntitlementPolicy::new(&vendor)).unwrap();
+
+        // The client runs the handshake against acme's identity key
+        let client = Handshake::perform(b"client", b"acme identity");
+        let hello = |license: &License| {
+            let mut hello = ClientHello::new();
+            hello.push_encoded(&encode_license_extension(license, &client).unwrap()).unwrap();
+            hello
+        };
+        let mut licensed = License::new("lic-1".to_string(), vec!["streams".to_string()], 100);
+        licensed.sign(&vendor);
+        let mut forged = licensed.clone();
+        forged.sign(&SigningKey::from_bytes(b"other-vendor"));
+
+        let (_, session) = manager.accept(&a, SOURCE, b"client", None, &hello(&licensed), 10_000).unwrap();
+        assert!(manager.session_entitlements(&session).unwrap().has_feature("streams"));
+
+        for (i, unlicensed) in [ClientHello::new(), hello(&forged)].iter().enumerate() {
+            assert!(matches!(
+                manager.accept(&a, SOURCE, b"client", None, unlicensed, 10_000 + i as u64 * 1_000),
+                Err(AegisQError::Policy("License rejected"))
+            ));
+        }
+        assert!(matches!(manager.accept(&a, SOURCE, b"client", None, &hello(&licensed), 200_000), Err(AegisQError::Policy(_))));
+        assert_eq!(manager.metrics(&a).unwrap().active_sessions(), 1);
+        assert!(manager.render_metrics().contains("aegisq_handshakes_total{tenant=\"acme\",result=\"failure\"} 3"));
+
+        // Tenants without a policy do not ask for a license
+        let (_, open) = manager.accept(&b, OTHER_SOURCE, b"client", None, &ClientHello::new(), 10_000).unwrap();
+        assert!(manager.session_entitlements(&open).is_none());
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use super::*;
+    use crate::keyring::DEFAULT_OVERLAP_MS;
+    use loom::thread;
+
+    const SOURCE: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 1));
+
+    /// `SessionManager` takes `&mut self` and is driven by one control
+    /// thread; what it shares across threads is each tenant's `Metrics`,
+    /// which data-path workers and the scrape endpoint use concurrently.
+    #[test]
+    fn loom_manager_metrics_shared_with_workers() {
+        loom::model(|| {
+            let tenant = TenantId::new("acme").unwrap();
+            let mut manager = SessionManager::new(DEFAULT_OVERLAP_MS);
+            manager.add_tenant(tenant.clone(), b"acme identity", b"acme stek", TicketPolicy::default(), 0).unwrap();
+
+            // Data path: a session worker counting traffic and a rekey
+            let worker = {
+                let metrics = manager.metrics(&tenant).unwrap();
+                thread::spawn(move || {
+                    metrics.bytes_sent(100);
+                    metrics.rekey();
+                })
+            };
+            // Scrape endpoint reading the gauge mid-accept
+            let scraper = {
+                let metrics = manager.metrics(&tenant).unwrap();
+                thread::spawn(move || metrics.active_sessions())
+            };
+
+            let (_, session) = manager.accept(&tenant, SOURCE, b"client", None, &ClientHello::new(), 10).unwrap();
+            assert_eq!(manager.owner(&session), Some(&tenant));
+            manager.close_session(&tenant, &session).unwrap();
+
+            worker.join().unwrap();
+            assert!((0..=1).contains(&scraper.join().unwrap()));
+            let rendered = manager.render_metrics();
+            for line in [
+                "aegisq_active_sessions{tenant=\"acme\"} 0\n",
+                "aegisq_sessions_total{tenant=\"acme\"} 1\n",
+                "aegisq_bytes_sent_total{tenant=\"acme\"} 100\n",
+                "aegisq_rekeys_total{tenant=\"acme\"} 1\n",
+            ] {
+                assert!(rendered.contains(line), "missing {:?}", line);
+            }
+        });
+    }
+}
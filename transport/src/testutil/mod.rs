@@ -0,0 +1,383 @@
+//! Simulated Network (feature `testutil`)
+//!
+//! Deterministic in-memory link between two endpoints for protocol tests.
+//! Packets get a base latency plus seeded jitter (so reordering is
+//! reproducible), and a scriptable `FaultSchedule` drops, duplicates,
+//! delays or corrupts selected packets: "drop the 3rd handshake message",
+//! "duplicate every 10th frame", "corrupt tags from T on". Time only moves
+//! through `advance`, so every run of a scenario is identical.
+
+/// Link endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    A,
+    B,
+}
+
+impl Side {
+    /// The other endpoint
+    pub fn peer(self) -> Self {
+        match self {
+            Side::A => Side::B,
+            Side::B => Side::A,
+        }
+    }
+}
+
+/// Which packets a fault applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    /// The n-th matching packet (1-based)
+    Nth(u64),
+    /// Every n-th matching packet
+    Every(u64),
+    /// Packets sent at or after this time (ms)
+    After(u64),
+    /// Packets sent within [start, end) ms
+    Window(u64, u64),
+    /// Every matching packet
+    Always,
+}
+
+/// Where to flip bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CorruptAt {
+    /// Byte at this offset (ignored if the packet is shorter)
+    Byte(usize),
+    /// Last byte, i.e. the authentication tag of a trailing-tag format
+    Tag,
+}
+
+/// What happens to a selected packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultAction {
+    Drop,
+    Duplicate,
+    /// Extra delay in ms
+    Delay(u64),
+    Corrupt(CorruptAt),
+}
+
+/// One scripted fault
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fault {
+    pub trigger: Trigger,
+    pub action: FaultAction,
+    /// Only packets sent by this side (both directions if `None`)
+    pub from: Option<Side>,
+}
+
+/// Ordered list of faults; every matching fault applies to a packet
+#[derive(Debug, Clone, Default)]
+pub struct FaultSchedule {
+    faults: Vec<Fault>,
+}
+
+impl FaultSchedule {
+    /// Empty schedule (perfect link apart from latency)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an arbitrary fault
+    pub fn with(mut self, fault: Fault) -> Self {
+        self.faults.push(fault);
+        self
+    }
+
+    /// Drop the n-th packet on the link (1-based, both directions)
+    pub fn drop_nth(self, n: u64) -> Self {
+        self.with(Fault {
+            trigger: Trigger::Nth(n),
+            action: FaultAction::Drop,
+            from: None,
+        })
+    }
+
+    /// Duplicate every n-th packet sent by `from`
+    pub fn duplicate_every(self, n: u64, from: Side) -> Self {
+        self.with(Fault {
+            trigger: Trigger::Every(n),
+            action: FaultAction::Duplicate,
+            from: Some(from),
+        })
+    }
+
+    /// Corrupt the tag of every packet sent by `from` at or after `at_ms`
+    pub fn corrupt_tag_after(self, at_ms: u64, from: Side) -> Self {
+        self.with(Fault {
+            trigger: Trigger::After(at_ms),
+            action: FaultAction::Corrupt(CorruptAt::Tag),
+            from: Some(from),
+        })
+    }
+}
+
+/// Latency model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkConfig {
+    pub latency_ms: u64,
+    /// Uniform extra delay in [0, jitter_ms]
+    pub jitter_ms: u64,
+    /// Jitter PRNG seed
+    pub seed: u64,
+}
+
+impl Default for LinkConfig {
+    fn default() -> Self {
+        Self {
+            latency_ms: 10,
+            jitter_ms: 0,
+            seed: 1,
+        }
+    }
+}
+
+/// A fault that fired, for assertions and failure messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FaultEvent {
+    /// Ordinal of the packet on the link (1-based)
+    pub packet: u64,
+    pub from: Side,
+    pub at_ms: u64,
+    pub action: FaultAction,
+}
+
+#[derive(Debug)]
+struct InFlight {
+    deliver_at: u64,
+    order: u64,
+    to: Side,
+    packet: Vec<u8>,
+}
+
+/// Deterministic two-endpoint link
+#[derive(Debug)]
+pub struct SimLink {
+    config: LinkConfig,
+    faults: Vec<(Fault, u64)>,
+    rng: u64,
+    now_ms: u64,
+    sent: u64,
+    order: u64,
+    in_flight: Vec<InFlight>,
+    events: Vec<FaultEvent>,
+}
+
+impl SimLink {
+    /// Link with the given latency model and faults
+    pub fn new(config: LinkConfig, schedule: FaultSchedule) -> Self {
+        Self {
+            config,
+            faults: schedule.faults.into_iter().map(|fault| (fault, 0)).collect(),
+            // xorshift state must be non-zero
+            rng: config.seed | 1,
+            now_ms: 0,
+            sent: 0,
+            order: 0,
+            in_flight: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Current simulated time (ms)
+    pub fn now_ms(&self) -> u64 {
+        self.now_ms
+    }
+
+    /// Move the clock forward
+    pub fn advance(&mut self, ms: u64) {
+        self.now_ms += ms;
+    }
+
+    /// Faults that fired so far
+    pub fn events(&self) -> &[FaultEvent] {
+        &self.events
+    }
+
+    /// Packets not yet received
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Send a packet from `from` to its peer
+    pub fn send(&mut self, from: Side, packet: &[u8]) {
+        self.sent += 1;
+        let mut packet = packet.to_vec();
+        let mut copies = 1;
+        let mut delay = 0;
+
+        for (fault, matched) in &mut self.faults {
+            if fault.from.is_some_and(|side| side != from) {
+                continue;
+            }
+            *matched += 1;
+            let fires = match fault.trigger {
+                Trigger::Nth(n) => *matched == n,
+                Trigger::Every(n) => n != 0 && *matched % n == 0,
+                Trigger::After(at) => self.now_ms >= at,
+                Trigger::Window(start, end) => (start..end).contains(&self.now_ms),
+                Trigger::Always => true,
+            };
+            if !fires {
+                continue;
+            }
+            self.events.push(FaultEvent {
+                packet: self.sent,
+                from,
+                at_ms: self.now_ms,
+                action: fault.action,
+            });
+            match fault.action {
+                FaultAction::Drop => copies = 0,
+                FaultAction::Duplicate if copies > 0 => copies += 1,
+                FaultAction::Duplicate => {}
+                FaultAction::Delay(ms) => delay += ms,
+                FaultAction::Corrupt(at) => {
+                    let index = match at {
+                        CorruptAt::Byte(i) => i,
+                        CorruptAt::Tag => packet.len().wrapping_sub(1),
+                    };
+                    if let Some(byte) = packet.get_mut(index) {
+                        *byte ^= 0x01;
+                    }
+                }
+            }
+        }
+
+        for _ in 0..copies {
+            let deliver_at = self.now_ms + self.config.latency_ms + delay + self.jitter();
+            self.order += 1;
+            self.in_flight.push(InFlight {
+                deliver_at,
+                order: self.order,
+                to: from.peer(),
+                packet: packet.clone(),
+            });
+        }
+    }
+
+    /// Next packet that has arrived at `side`, earliest first
+    pub fn recv(&mut self, side: Side) -> Option<Vec<u8>> {
+        let index = self
+            .in_flight
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.to == side && p.deliver_at <= self.now_ms)
+            .min_by_key(|(_, p)| (p.deliver_at, p.order))
+            .map(|(i, _)| i)?;
+        Some(self.in_flight.remove(index).packet)
+    }
+
+    /// Advance until every in-flight packet has arrived; returns them per side
+    pub fn drain(&mut self, side: Side) -> Vec<Vec<u8>> {
+        if let Some(last) = self.in_flight.iter().filter(|p| p.to == side).map(|p| p.deliver_at).max() {
+            self.now_ms = self.now_ms.max(last);
+        }
+        std::iter::from_fn(|| self.recv(side)).collect()
+    }
+
+    fn jitter(&mut self) -> u64 {
+        if self.config.jitter_ms == 0 {
+            return 0;
+        }
+        // xorshift64
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng % (self.config.jitter_ms + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vpn::VpnSession;
+
+    #[test]
+    fn test_latency_and_deterministic_jitter() {
+        let config = LinkConfig {
+            latency_ms: 20,
+            jitter_ms: 15,
+            seed: 42,
+        };
+        let run = || {
+            let mut link = SimLink::new(config, FaultSchedule::new());
+            for i in 0..20u8 {
+                link.send(Side::A, &[i]);
+                link.advance(1);
+            }
+            assert!(link.recv(Side::B).is_none());
+            link.drain(Side::B)
+        };
+
+        let first = run();
+        assert_eq!(first.len(), 20);
+        assert_eq!(first, run());
+        // Jitter larger than the send interval reorders packets
+        assert_ne!(first, (0..20u8).map(|i| vec![i]).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_fault_schedule() {
+        let schedule = FaultSchedule::new()
+            .drop_nth(3)
+            .duplicate_every(2, Side::A)
+            .with(Fault {
+                trigger: Trigger::Nth(1),
+                action: FaultAction::Corrupt(CorruptAt::Byte(0)),
+                from: Some(Side::B),
+            });
+        let mut link = SimLink::new(LinkConfig::default(), schedule);
+
+        link.send(Side::A, &[1]);
+        link.send(Side::B, &[2]);
+        link.send(Side::A, &[3]); // 3rd packet: dropped (duplicate does not revive it)
+        link.send(Side::A, &[4]);
+        link.send(Side::A, &[5]);
+
+        assert_eq!(link.drain(Side::B), vec![vec![1], vec![4], vec![5], vec![5]]);
+        assert_eq!(link.drain(Side::A), vec![vec![3]]);
+        let actions: Vec<_> = link.events().iter().map(|e| (e.packet, e.action)).collect();
+        assert_eq!(
+            actions,
+            vec![
+                (2, FaultAction::Corrupt(CorruptAt::Byte(0))),
+                (3, FaultAction::Drop),
+                (3, FaultAction::Duplicate),
+                (5, FaultAction::Duplicate),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_vpn_never_releases_bad_plaintext() {
+        let key = [7u8; 64];
+        let mut sender = VpnSession::from_keys(&key, &[0u8; 64], b"sim-nonce-000001");
+        let mut receiver = VpnSession::from_keys(&[0u8; 64], &key, b"sim-nonce-000001");
+        let schedule = FaultSchedule::new()
+            .duplicate_every(10, Side::A)
+            .corrupt_tag_after(250, Side::A);
+        let mut link = SimLink::new(LinkConfig::default(), schedule);
+
+        let messages: Vec<Vec<u8>> = (0..40u32).map(|i| format!("message {}", i).into_bytes()).collect();
+        let mut released = Vec::new();
+        let mut errors = 0;
+        for message in &messages {
+            link.send(Side::A, &sender.encrypt_data(message));
+            link.advance(10);
+            while let Some(packet) = link.recv(Side::B) {
+                match receiver.decrypt_data(&packet) {
+                    Ok(plaintext) => released.push(plaintext),
+                    Err(_) => errors += 1,
+                }
+            }
+        }
+        released.extend(link.drain(Side::B).iter().filter_map(|p| receiver.decrypt_data(p).ok()));
+
+        // Only an in-order prefix of what was sent, each message once, nothing after corruption
+        assert_eq!(released, messages[..25]);
+        // Two duplicates before T, then every remaining frame fails
+        assert!(errors >= 2 + 10);
+    }
+}
@@ -0,0 +1,322 @@
+//! Session Ticket Keys
+//!
+//! Session ticket encryption keys (STEKs) and the ticket envelope. A
+//! ticket carries the server's resumption state encrypted under a STEK,
+//! so the server keeps nothing per client; the ticket contents are opaque
+//! here. Each ticket starts with the ID of the key it was sealed with.
+//!
+//! `TicketKeys` holds up to `max_keys` keys: tickets are issued with the
+//! newest and opened with any of them, so a rotation does not invalidate
+//! tickets already handed out. Rotation is either explicit (`rotate`, with
+//! a key the operator distributes to every server of a cluster) or timer
+//! driven (`poll`, which generates a fresh key once the newest is older
+//! than `rotation_interval_ms`). The oldest key is dropped and zeroized
+//! when a rotation would exceed `max_keys`.
+//!
+//! A ticket stays usable for `ticket_lifetime_ms` after issue; keep it at
+//! most `(max_keys - 1) * rotation_interval_ms` so its key outlives it
+//! (`TicketPolicy::validate`).
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use aegis_q_core::{aegis_q_decrypt, aegis_q_encrypt, AegisQError};
+use sha3::{Digest, Sha3_256};
+use utils::kdf::kdf_shake256;
+use utils::memory::zeroize;
+use utils::rng::random_bytes;
+use utils::wire::{TicketStateWire, TicketWire, Wire};
+
+use crate::config::TicketsSection;
+
+/// Length of a ticket key identifier
+pub const TICKET_KEY_ID_SIZE: usize = 8;
+
+/// Length of the per-ticket nonce
+pub const TICKET_NONCE_SIZE: usize = 16;
+
+/// Length of keys generated by timer-driven rotation
+pub const TICKET_KEY_SIZE: usize = 32;
+
+/// Identifier of a ticket key, sent in the clear at the start of a ticket
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TicketKeyId([u8; TICKET_KEY_ID_SIZE]);
+
+impl TicketKeyId {
+    /// Identifier of a key
+    pub fn of(key: &[u8]) -> Self {
+        let mut hasher = Sha3_256::new();
+        hasher.update(b"aegis-q-ticket-key-id");
+        hasher.update(key);
+        let mut id = [0u8; TICKET_KEY_ID_SIZE];
+        id.copy_from_slice(&hasher.finalize()[..TICKET_KEY_ID_SIZE]);
+        Self(id)
+    }
+
+    /// Raw bytes
+    pub fn as_bytes(&self) -> &[u8; TICKET_KEY_ID_SIZE] {
+        &self.0
+    }
+}
+
+impl fmt::Display for TicketKeyId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in self.0 {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for TicketKeyId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TicketKeyId({})", self)
+    }
+}
+
+/// Key count, rotation interval and ticket lifetime
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TicketPolicy {
+    pub max_keys: usize,
+    pub rotation_interval_ms: u64,
+    pub ticket_lifetime_ms: u64,
+}
+
+impl Default for TicketPolicy {
+    fn default() -> Self {
+        Self {
+            max_keys: 3,
+            rotation_interval_ms: 12 * 3_600_000,
+            ticket_lifetime_ms: 24 * 3_600_000,
+        }
+    }
+}
+
+impl TicketPolicy {
+    /// Check the values and that a ticket expires before its key is dropped
+    pub fn validate(&self) -> Result<(), AegisQError> {
+        if self.max_keys == 0 || self.rotation_interval_ms == 0 || self.ticket_lifetime_ms == 0 {
+            return Err(AegisQError::InvalidInput("Ticket policy values must be greater than zero"));
+        }
+        let key_overlap = (self.max_keys as u64 - 1).saturating_mul(self.rotation_interval_ms);
+        if self.ticket_lifetime_ms > key_overlap {
+            return Err(AegisQError::InvalidInput("Ticket lifetime exceeds how long its key is kept"));
+        }
+        Ok(())
+    }
+}
+
+impl From<&TicketsSection> for TicketPolicy {
+    fn from(section: &TicketsSection) -> Self {
+        Self {
+            max_keys: section.keys,
+            rotation_interval_ms: section.rotation_interval_ms,
+            ticket_lifetime_ms: section.ticket_lifetime_ms,
+        }
+    }
+}
+
+struct TicketKey {
+    id: TicketKeyId,
+    /// Encryption key derived from the STEK
+    key: Vec<u8>,
+    created_ms: u64,
+}
+
+impl TicketKey {
+    fn new(stek: &[u8], created_ms: u64) -> Self {
+        Self {
+            id: TicketKeyId::of(stek),
+            key: kdf_shake256(b"aegis-q-ticket", stek, b"", 32),
+            created_ms,
+        }
+    }
+}
+
+impl Drop for TicketKey {
+    fn drop(&mut self) {
+        zeroize(&mut self.key);
+    }
+}
+
+/// Active session ticket keys, newest first
+pub struct TicketKeys {
+    keys: VecDeque<TicketKey>,
+    policy: TicketPolicy,
+}
+
+impl TicketKeys {
+    /// Key set with one key
+    pub fn new(stek: &[u8], now_ms: u64, policy: TicketPolicy) -> Result<Self, AegisQError> {
+        policy.validate()?;
+        let mut keys = VecDeque::with_capacity(policy.max_keys);
+        keys.push_front(TicketKey::new(stek, now_ms));
+        Ok(Self { keys, policy })
+    }
+
+    /// Key set with a random first key
+    pub fn generate(now_ms: u64, policy: TicketPolicy) -> Result<Self, AegisQError> {
+        let mut stek = random_bytes(TICKET_KEY_SIZE);
+        let keys = Self::new(&stek, now_ms, policy);
+        zeroize(&mut stek);
+        keys
+    }
+
+    /// Key new tickets are issued with
+    pub fn active(&self) -> TicketKeyId {
+        self.keys[0].id
+    }
+
+    /// All keys tickets are accepted from, newest first
+    pub fn key_ids(&self) -> Vec<TicketKeyId> {
+        self.keys.iter().map(|key| key.id).collect()
+    }
+
+    /// Current policy
+    pub fn policy(&self) -> TicketPolicy {
+        self.policy
+    }
+
+    /// Apply a new policy; extra old keys are dropped
+    pub fn set_policy(&mut self, policy: TicketPolicy) -> Result<(), AegisQError> {
+        policy.validate()?;
+        self.policy = policy;
+        self.keys.truncate(policy.max_keys);
+        Ok(())
+    }
+
+    /// Make `stek` the active key
+    pub fn rotate(&mut self, stek: &[u8], now_ms: u64) -> Result<TicketKeyId, AegisQError> {
+        let key = TicketKey::new(stek, now_ms);
+        if self.keys.iter().any(|existing| existing.id == key.id) {
+            return Err(AegisQError::InvalidInput("Ticket key already in use"));
+        }
+        let id = key.id;
+        self.keys.push_front(key);
+        self.keys.truncate(self.policy.max_keys);
+        Ok(id)
+    }
+
+    /// Rotate to a random key once the active one is older than the
+    /// rotation interval; returns the new key if rotated
+    pub fn poll(&mut self, now_ms: u64) -> Option<TicketKeyId> {
+        let due = self.keys[0].created_ms.saturating_add(self.policy.rotation_interval_ms);
+        if now_ms < due {
+            return None;
+        }
+        let mut stek = random_bytes(TICKET_KEY_SIZE);
+        let rotated = self.rotate(&stek, now_ms).ok();
+        zeroize(&mut stek);
+        rotated
+    }
+
+    /// Seal resumption state into a ticket with the active key
+    ///
+    /// Ticket: key ID || nonce || Aegis-Q(issued_at_ms BE8 || state), see
+    /// `utils::wire::TicketWire`
+    pub fn issue(&self, state: &[u8], now_ms: u64) -> Vec<u8> {
+        let key = &self.keys[0];
+        let mut nonce = [0u8; TICKET_NONCE_SIZE];
+        nonce.copy_from_slice(&random_bytes(TICKET_NONCE_SIZE));
+        let mut contents = TicketStateWire { issued_at_ms: now_ms, state: state.to_vec() };
+        let mut plaintext = contents.to_wire().expect("ticket state has no length prefixes");
+        zeroize(&mut contents.state);
+
+        let ticket = TicketWire {
+            key_id: *key.id.as_bytes(),
+            nonce,
+            sealed: aegis_q_encrypt(&key.key, &ticket_nonce(&key.id, &nonce), &plaintext),
+        };
+        zeroize(&mut plaintext);
+        ticket.to_wire().expect("ticket has no length prefixes")
+    }
+
+    /// Open a ticket sealed with any of the keys
+    pub fn open(&self, ticket: &[u8], now_ms: u64) -> Result<Vec<u8>, AegisQError> {
+        let ticket = TicketWire::from_wire(ticket).map_err(|_| AegisQError::InvalidLength("Ticket too short"))?;
+        let key = self
+            .keys
+            .iter()
+            .find(|key| *key.id.as_bytes() == ticket.key_id)
+            .ok_or(AegisQError::NotFound("Unknown ticket key"))?;
+
+        let mut plaintext = aegis_q_decrypt(&key.key, &ticket_nonce(&key.id, &ticket.nonce), &ticket.sealed)?;
+        let contents = TicketStateWire::from_wire(&plaintext);
+        zeroize(&mut plaintext);
+        let mut contents = contents.map_err(|_| AegisQError::InvalidLength("Ticket too short"))?;
+        if now_ms >= contents.issued_at_ms.saturating_add(self.policy.ticket_lifetime_ms) {
+            zeroize(&mut contents.state);
+            return Err(AegisQError::Protocol("Ticket expired"));
+        }
+        Ok(contents.state)
+    }
+}
+
+/// Nonce bound to the key ID
+fn ticket_nonce(id: &TicketKeyId, nonce: &[u8]) -> Vec<u8> {
+    let mut bound = Vec::with_capacity(TICKET_KEY_ID_SIZE + nonce.len());
+    bound.extend_from_slice(id.as_bytes());
+    bound.extend_from_slice(nonce);
+    bound
+}
+
+impl fmt::Debug for TicketKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TicketKeys")
+            .field("active", &self.active())
+            .field("keys", &self.keys.len())
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: u64 = 3_600_000;
+
+    #[test]
+    fn test_rotation_keeps_old_tickets() {
+        let mut keys = TicketKeys::new(b"stek-1", 0, TicketPolicy::default()).unwrap();
+        let first = keys.issue(b"resumption state", 0);
+        assert_eq!(&first[..TICKET_KEY_ID_SIZE], TicketKeyId::of(b"stek-1").as_bytes());
+
+        let second_id = keys.rotate(b"stek-2", HOUR).unwrap();
+        assert_eq!(keys.active(), second_id);
+        let second = keys.issue(b"newer state", HOUR);
+        assert_eq!(keys.open(&first, HOUR).unwrap(), b"resumption state");
+        assert_eq!(keys.open(&second, HOUR).unwrap(), b"newer state");
+        assert!(keys.rotate(b"stek-2", HOUR).is_err());
+
+        // A third rotation drops stek-1 (max_keys = 3)
+        keys.rotate(b"stek-3", 2 * HOUR).unwrap();
+        keys.rotate(b"stek-4", 3 * HOUR).unwrap();
+        assert_eq!(keys.key_ids().len(), 3);
+        assert_eq!(keys.open(&first, 3 * HOUR), Err(AegisQError::NotFound("Unknown ticket key")));
+        assert_eq!(keys.open(&second, 3 * HOUR).unwrap(), b"newer state");
+
+        // Expired or tampered tickets are refused
+        assert_eq!(keys.open(&second, 25 * HOUR), Err(AegisQError::Protocol("Ticket expired")));
+        let mut tampered = second.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert_eq!(keys.open(&tampered, 3 * HOUR), Err(AegisQError::AuthenticationFailed));
+    }
+
+    #[test]
+    fn test_timer_rotation_and_policy() {
+        let policy = TicketPolicy { max_keys: 2, rotation_interval_ms: HOUR, ticket_lifetime_ms: HOUR };
+        let mut keys = TicketKeys::generate(0, policy).unwrap();
+        let first = keys.active();
+        assert_eq!(keys.poll(HOUR - 1), None);
+        let second = keys.poll(HOUR).unwrap();
+        assert_eq!(keys.key_ids(), vec![second, first]);
+        assert_eq!(keys.poll(HOUR + 1), None);
+
+        let longer = TicketPolicy { ticket_lifetime_ms: 2 * HOUR, ..policy };
+        assert!(TicketKeys::generate(0, longer).is_err());
+        keys.set_policy(TicketPolicy { max_keys: 1, ticket_lifetime_ms: 0, ..policy }).unwrap_err();
+        keys.set_policy(TicketPolicy { max_keys: 3, ticket_lifetime_ms: 2 * HOUR, ..policy }).unwrap();
+        assert_eq!(keys.policy().max_keys, 3);
+    }
+}
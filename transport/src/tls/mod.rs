@@ -0,0 +1,88 @@
+//! TLS 1.3 exporter-keyed inner channel
+//!
+//! Derives Aegis-Q session keys from a TLS exporter secret (RFC 8446 §7.5)
+//! instead of the native handshake. TLS stays on the wire; Aegis-Q is the
+//! inner end-to-end layer. With rustls, the caller obtains the secret via
+//!
+//! ```text
+//! conn.export_keying_material([0u8; EXPORTER_SECRET_LEN], EXPORTER_LABEL, Some(context))
+//! ```
+
+use aegis_q_core::AegisQError;
+use utils::kdf::kdf_shake256_fill;
+use utils::memory::zeroize;
+use crate::vpn::VpnSession;
+
+/// Exporter label both endpoints must use
+pub const EXPORTER_LABEL: &[u8] = b"EXPORTER-aegis-q-inner-channel";
+
+/// Requested exporter secret length
+pub const EXPORTER_SECRET_LEN: usize = 64;
+
+/// Minimum accepted exporter secret length
+pub const MIN_EXPORTER_SECRET_LEN: usize = 32;
+
+/// Endpoint role on the TLS connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Client,
+    Server,
+}
+
+/// Create an inner VPN session keyed from a TLS exporter secret
+///
+/// # Arguments
+/// * `exporter_secret` - Output of the TLS exporter for `EXPORTER_LABEL`
+/// * `role` - Our role on the TLS connection (selects send/receive keys)
+pub fn session_from_exporter(exporter_secret: &[u8], role: Role) -> Result<VpnSession, AegisQError> {
+    if exporter_secret.len() < MIN_EXPORTER_SECRET_LEN {
+        return Err(AegisQError::InvalidLength("Exporter secret too short"));
+    }
+
+    let mut client_key = vec![0u8; 64];
+    kdf_shake256_fill(b"aegis-q-transport-tls-client", exporter_secret, EXPORTER_LABEL, &mut client_key);
+
+    let mut server_key = vec![0u8; 64];
+    kdf_shake256_fill(b"aegis-q-transport-tls-server", exporter_secret, EXPORTER_LABEL, &mut server_key);
+
+    let mut nonce = vec![0u8; 16];
+    kdf_shake256_fill(b"aegis-q-transport-tls-nonce", exporter_secret, EXPORTER_LABEL, &mut nonce);
+
+    let session = match role {
+        Role::Client => VpnSession::from_keys(&client_key, &server_key, &nonce),
+        Role::Server => VpnSession::from_keys(&server_key, &client_key, &nonce),
+    };
+    zeroize(&mut client_key);
+    zeroize(&mut server_key);
+    zeroize(&mut nonce);
+    Ok(session)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exporter_session_roundtrip() {
+        let secret = [0x5au8; EXPORTER_SECRET_LEN];
+
+        let mut client = session_from_exporter(&secret, Role::Client).unwrap();
+        let mut server = session_from_exporter(&secret, Role::Server).unwrap();
+
+        let frame = client.encrypt_data(b"Hello, server!");
+        assert_eq!(server.decrypt_data(&frame).unwrap(), b"Hello, server!");
+
+        let frame = server.encrypt_data(b"Hello, client!");
+        assert_eq!(client.decrypt_data(&frame).unwrap(), b"Hello, client!");
+    }
+
+    #[test]
+    fn test_exporter_secret_mismatch() {
+        let mut client = session_from_exporter(&[1u8; 32], Role::Client).unwrap();
+        let mut server = session_from_exporter(&[2u8; 32], Role::Server).unwrap();
+
+        let frame = client.encrypt_data(b"data");
+        assert!(server.decrypt_data(&frame).is_err());
+        assert!(session_from_exporter(&[0u8; 16], Role::Client).is_err());
+    }
+}
@@ -3,10 +3,17 @@
 //! VPN tunnel using Aegis-Q for encryption
 //! Handshake protocol and stream wrapper
 
-use aegis_q_core::{aegis_q_init, State};
+use aegis_q_core::{AegisQError, aegis_q_init, State};
 use utils::kdf::kdf_shake256_fill;
-use crate::framing::{Frame, FrameType};
+use utils::memory::zeroize;
+use crate::framing::{decrypt_frame_in_place, Frame, FrameHeader, FrameType};
+use crate::lifecycle::{ReplayMark, SEND_RESERVE};
+use crate::metrics::Metrics;
+use crate::session::{ordered_concat, SessionId};
+#[cfg(feature = "capture")]
+use crate::capture::SessionSecrets;
 use sha3::{Digest, Sha3_512};
+use std::sync::Arc;
 
 /// VPN session state
 pub struct VpnSession {
@@ -16,6 +23,19 @@ pub struct VpnSession {
     decrypt_nonce: Vec<u8>,
     sequence_send: u64,
     sequence_recv: u64,
+    /// Key epochs, one per `rekey_send` / `rekey_recv`
+    send_epoch: u32,
+    recv_epoch: u32,
+    /// The next received frame may skip ahead (first frame after `resume`)
+    resume_gap: bool,
+    session_id: SessionId,
+    /// Server counters for traffic and rekeys (`with_metrics`)
+    metrics: Option<Arc<Metrics>>,
+    /// Copies of the traffic keys for `export_secrets`
+    #[cfg(feature = "capture")]
+    encrypt_key: Vec<u8>,
+    #[cfg(feature = "capture")]
+    decrypt_key: Vec<u8>,
 }
 
 impl VpnSession {
@@ -28,17 +48,109 @@ impl VpnSession {
         let mut decrypt_key = vec![0u8; 64];
         kdf_shake256_fill(b"aegis-q-transport-vpn-decrypt", shared_secret, nonce, &mut decrypt_key);
         
-        let encrypt_state = aegis_q_init(&encrypt_key, nonce);
-        let decrypt_state = aegis_q_init(&decrypt_key, nonce);
+        let session = Self::from_keys(&encrypt_key, &decrypt_key, nonce);
+        zeroize(&mut encrypt_key);
+        zeroize(&mut decrypt_key);
+        session
+    }
+    
+    /// Create VPN session from already-derived directional keys
+    pub(crate) fn from_keys(encrypt_key: &[u8], decrypt_key: &[u8], nonce: &[u8]) -> Self {
+        let encrypt_state = aegis_q_init(encrypt_key, nonce);
+        let decrypt_state = aegis_q_init(decrypt_key, nonce);
+        let session_id = Self::derive_session_id(&encrypt_state, &decrypt_state);
         
-        Self {
+        let session = Self {
             encrypt_state,
             decrypt_state,
             encrypt_nonce: nonce.to_vec(),
             decrypt_nonce: nonce.to_vec(),
             sequence_send: 0,
             sequence_recv: 0,
+            send_epoch: 0,
+            recv_epoch: 0,
+            resume_gap: false,
+            session_id,
+            metrics: None,
+            #[cfg(feature = "capture")]
+            encrypt_key: encrypt_key.to_vec(),
+            #[cfg(feature = "capture")]
+            decrypt_key: decrypt_key.to_vec(),
+        };
+        #[cfg(feature = "keylog")]
+        crate::keylog::log_session(&session.export_secrets());
+        session
+    }
+    
+    /// Count payload bytes and rekeys of this session in `metrics`
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Session ID from an exporter of each directional state
+    fn derive_session_id(encrypt_state: &State, decrypt_state: &State) -> SessionId {
+        let mut a = encrypt_state.export_keying_material(b"aegis-q-transport-session-id", b"", 32);
+        let mut b = decrypt_state.export_keying_material(b"aegis-q-transport-session-id", b"", 32);
+        let id = SessionId::from_exports(&a, &b);
+        zeroize(&mut a);
+        zeroize(&mut b);
+        id
+    }
+    
+    /// Session ID the given traffic keys produce (for checking a secrets file)
+    #[cfg(feature = "capture")]
+    pub(crate) fn session_id_for_keys(encrypt_key: &[u8], decrypt_key: &[u8], nonce: &[u8]) -> SessionId {
+        Self::derive_session_id(&aegis_q_init(encrypt_key, nonce), &aegis_q_init(decrypt_key, nonce))
+    }
+    
+    /// Traffic secrets for offline decryption of captured frames
+    /// 
+    /// Debugging only (feature `capture`): anyone holding these can read
+    /// the session. They are the keys of the first epoch; frames sent after
+    /// a rekey need the same number of ratchets applied.
+    #[cfg(feature = "capture")]
+    pub fn export_secrets(&self) -> SessionSecrets {
+        SessionSecrets {
+            session_id: self.session_id,
+            nonce: self.encrypt_nonce.clone(),
+            send_key: self.encrypt_key.clone(),
+            recv_key: self.decrypt_key.clone(),
+        }
+    }
+    
+    /// Channel-bound keying material (TLS-style exporter)
+    ///
+    /// Both peers get the same bytes for the same `label` and `context`:
+    /// the exports of the two directional states are ordered in constant
+    /// time before being combined, as their roles are swapped on the other
+    /// side. Use it to key
+    /// application-layer tokens instead of touching the traffic keys.
+    pub fn export_keying_material(&self, label: &[u8], context: &[u8], len: usize) -> Vec<u8> {
+        let mut a = self.encrypt_state.export_keying_material(label, context, 64);
+        let mut b = self.decrypt_state.export_keying_material(label, context, 64);
+        let mut combined = ordered_concat(&a, &b);
+        let output = utils::kdf::kdf_shake256(b"aegis-q-transport-exporter", &combined, self.session_id.as_bytes(), len);
+        for secret in [&mut a, &mut b, &mut combined] {
+            zeroize(secret);
         }
+        output
+    }
+    
+    /// Expect `sequence` as the next received frame (offline decryption of gapped captures)
+    #[cfg(feature = "capture")]
+    pub(crate) fn resync_recv(&mut self, sequence: u64) {
+        self.sequence_recv = sequence;
+    }
+    
+    /// Authenticated session identifier (same on both peers)
+    pub fn session_id(&self) -> SessionId {
+        self.session_id
+    }
+    
+    /// Check that a presented session ID belongs to this live session
+    pub fn verify_session_id(&self, presented: &[u8]) -> bool {
+        self.session_id.verify(presented)
     }
     
     /// Encrypt and frame data
@@ -46,13 +158,7 @@ impl VpnSession {
         let mut frame = Frame::new(FrameType::Data, data.to_vec(), self.sequence_send);
         
         // Derive per-frame key
-        let mut frame_key = vec![0u8; 64];
-        kdf_shake256_fill(
-            b"aegis-q-transport-vpn-frame",
-            &self.encrypt_state.to_bytes(),
-            &self.sequence_send.to_le_bytes(),
-            &mut frame_key,
-        );
+        let mut frame_key = frame_key(&self.encrypt_state, self.sequence_send);
         
         let frame_nonce = {
             let mut n = self.encrypt_nonce.clone();
@@ -61,38 +167,172 @@ impl VpnSession {
         };
         
         frame.encrypt(&frame_key, &frame_nonce);
+        zeroize(&mut frame_key);
         
         self.sequence_send += 1;
+        if let Some(metrics) = &self.metrics {
+            metrics.bytes_sent(data.len() as u64);
+        }
         frame.encode()
     }
     
     /// Decrypt and unframe data
-    pub fn decrypt_data(&mut self, frame_data: &[u8]) -> Result<Vec<u8>, &'static str> {
+    pub fn decrypt_data(&mut self, frame_data: &[u8]) -> Result<Vec<u8>, AegisQError> {
         let mut frame = Frame::decode(frame_data)?;
+        self.check_recv_sequence(frame.sequence)?;
         
-        if frame.sequence != self.sequence_recv {
-            return Err("Sequence mismatch");
-        }
+        let (mut frame_key, frame_nonce) = self.recv_frame_keys(frame.sequence);
+        let result = frame.decrypt(&frame_key, &frame_nonce);
+        zeroize(&mut frame_key);
+        result?;
         
-        // Derive per-frame key
-        let mut frame_key = vec![0u8; 64];
-        kdf_shake256_fill(
-            b"aegis-q-transport-vpn-frame",
-            &self.decrypt_state.to_bytes(),
-            &self.sequence_recv.to_le_bytes(),
-            &mut frame_key,
-        );
+        self.sequence_recv = frame.sequence + 1;
+        self.resume_gap = false;
+        self.count_received(frame.payload.len());
+        Ok(frame.payload)
+    }
+    
+    /// Decrypt a frame in place within the receive buffer
+    /// 
+    /// Returns a view of the plaintext inside `frame_data` (fast path, no copy).
+    pub fn decrypt_data_in_place<'a>(&mut self, frame_data: &'a mut [u8]) -> Result<&'a mut [u8], AegisQError> {
+        let sequence = FrameHeader::decode(frame_data)?.sequence;
+        self.check_recv_sequence(sequence)?;
+        
+        let (mut frame_key, frame_nonce) = self.recv_frame_keys(sequence);
+        let result = decrypt_frame_in_place(frame_data, &frame_key, &frame_nonce);
+        zeroize(&mut frame_key);
+        let (_, plaintext) = result?;
+        
+        self.sequence_recv = sequence + 1;
+        self.resume_gap = false;
+        self.count_received(plaintext.len());
+        Ok(plaintext)
+    }
+    
+    /// Move the send direction to the next key epoch
+    ///
+    /// Perform `lifecycle::Action::SendRekey` with this: the send state is
+    /// advanced with `State::ratchet`, so the keys of earlier epochs cannot
+    /// be recovered from the session, and the sequence restarts at 0.
+    pub fn rekey_send(&mut self) {
+        self.next_send_epoch();
+        self.count_rekey();
+    }
+
+    /// Follow the peer's send direction into its next key epoch
+    ///
+    /// Call when the peer's rekey is received (the first frame of the next
+    /// epoch); mirrors `rekey_send` on the other side.
+    pub fn rekey_recv(&mut self) {
+        self.next_recv_epoch();
+        self.count_rekey();
+    }
+
+    fn next_send_epoch(&mut self) {
+        self.encrypt_state.ratchet();
+        self.send_epoch += 1;
+        self.sequence_send = 0;
+    }
+
+    fn next_recv_epoch(&mut self) {
+        self.decrypt_state.ratchet();
+        self.recv_epoch += 1;
+        self.sequence_recv = 0;
+    }
+
+    /// High-water marks to persist for ticket resumption (`replay::ReplayStore`)
+    pub fn replay_mark(&self) -> ReplayMark {
+        ReplayMark {
+            recv_epoch: self.recv_epoch,
+            recv_next: self.sequence_recv,
+            send_epoch: self.send_epoch,
+            send_next: self.sequence_send,
+        }
+    }
+
+    /// Continue a ticket-resumed session from a persisted `mark`
+    ///
+    /// Each direction is ratcheted forward to the mark's epoch (catching up
+    /// is not counted as a rekey). Frames up
+    /// to the mark are rejected, and sending resumes `SEND_RESERVE`
+    /// sequences past it, as in `Lifecycle::resume`; the first frame
+    /// received afterwards may likewise skip up to `SEND_RESERVE`. A mark
+    /// behind the session changes nothing.
+    pub fn resume(&mut self, mark: ReplayMark) {
+        while self.send_epoch < mark.send_epoch {
+            self.next_send_epoch();
+        }
+        if self.send_epoch == mark.send_epoch {
+            self.sequence_send = self.sequence_send.max(mark.send_next.saturating_add(SEND_RESERVE));
+        }
+        while self.recv_epoch < mark.recv_epoch {
+            self.next_recv_epoch();
+        }
+        if self.recv_epoch == mark.recv_epoch {
+            self.sequence_recv = self.sequence_recv.max(mark.recv_next);
+        }
+        self.resume_gap = true;
+    }
+
+    /// Accept only the next expected sequence
+    ///
+    /// A dropped or reordered frame is an error. The one exception is the
+    /// first frame after `resume`, which may skip up to `SEND_RESERVE`
+    /// sequences because the resumed peer skips that many on its side.
+    fn check_recv_sequence(&self, sequence: u64) -> Result<(), AegisQError> {
+        let expected = sequence == self.sequence_recv;
+        let resume_skip = self.resume_gap
+            && sequence > self.sequence_recv
+            && sequence - self.sequence_recv <= SEND_RESERVE;
+        if !(expected || resume_skip) || sequence == u64::MAX {
+            return Err(AegisQError::Protocol("Sequence mismatch"));
+        }
+        Ok(())
+    }
+
+    fn count_received(&self, bytes: usize) {
+        if let Some(metrics) = &self.metrics {
+            metrics.bytes_received(bytes as u64);
+        }
+    }
+
+    fn count_rekey(&self) {
+        if let Some(metrics) = &self.metrics {
+            metrics.rekey();
+        }
+    }
+
+    /// Derive per-frame key and nonce for a received frame
+    fn recv_frame_keys(&self, sequence: u64) -> (Vec<u8>, Vec<u8>) {
+        let frame_key = frame_key(&self.decrypt_state, sequence);
         
         let frame_nonce = {
             let mut n = self.decrypt_nonce.clone();
-            n.extend_from_slice(&self.sequence_recv.to_le_bytes());
+            n.extend_from_slice(&sequence.to_le_bytes());
             n
         };
         
-        frame.decrypt(&frame_key, &frame_nonce)?;
-        
-        self.sequence_recv += 1;
-        Ok(frame.payload)
+        (frame_key, frame_nonce)
+    }
+}
+
+/// Per-frame key from a directional state
+///
+/// The serialized state is secret and wiped once the key is derived.
+fn frame_key(state: &State, sequence: u64) -> Vec<u8> {
+    let mut state_bytes = state.to_bytes();
+    let mut frame_key = vec![0u8; 64];
+    kdf_shake256_fill(b"aegis-q-transport-vpn-frame", &state_bytes, &sequence.to_le_bytes(), &mut frame_key);
+    zeroize(&mut state_bytes);
+    frame_key
+}
+
+#[cfg(feature = "capture")]
+impl Drop for VpnSession {
+    fn drop(&mut self) {
+        zeroize(&mut self.encrypt_key);
+        zeroize(&mut self.decrypt_key);
     }
 }
 
@@ -125,6 +365,16 @@ impl Handshake {
             shared_secret,
         }
     }
+    
+    /// Session identifier bound to the handshake transcript
+    pub fn session_id(&self) -> SessionId {
+        let mut transcript = Vec::new();
+        for part in [&self.client_hello, &self.server_hello, &self.shared_secret] {
+            transcript.extend_from_slice(&(part.len() as u32).to_le_bytes());
+            transcript.extend_from_slice(part);
+        }
+        SessionId::from_transcript(&transcript)
+    }
 }
 
 #[cfg(test)]
@@ -144,5 +394,120 @@ mod tests {
         
         assert_eq!(data, decrypted.as_slice());
     }
-}
+    
+    #[test]
+    fn test_vpn_decrypt_in_place() {
+        let nonce = b"vpn-nonce-123456";
+        let mut sender = VpnSession::from_keys(b"key-a", b"key-b", nonce);
+        let mut receiver = VpnSession::from_keys(b"key-b", b"key-a", nonce);
+        
+        let mut buffer = sender.encrypt_data(b"Hello, fast path!");
+        let plaintext = receiver.decrypt_data_in_place(&mut buffer).unwrap();
+        assert_eq!(plaintext, b"Hello, fast path!");
+        
+        // Replayed frame is rejected by sequence check
+        let mut replay = sender.encrypt_data(b"next");
+        replay[1] = 0;
+        assert!(receiver.decrypt_data_in_place(&mut replay).is_err());
+    }
+    
+    #[test]
+    fn test_vpn_exporter_matches_on_both_peers() {
+        let nonce = b"vpn-nonce-123456";
+        let client = VpnSession::from_keys(b"key-a", b"key-b", nonce);
+        let server = VpnSession::from_keys(b"key-b", b"key-a", nonce);
+        let other = VpnSession::from_keys(b"key-a", b"key-c", nonce);
+        
+        let token_key = client.export_keying_material(b"app token", b"", 32);
+        assert_eq!(server.export_keying_material(b"app token", b"", 32), token_key);
+        assert_ne!(client.export_keying_material(b"app token", b"ctx", 32), token_key);
+        assert_ne!(other.export_keying_material(b"app token", b"", 32), token_key);
+    }
+    
+    #[test]
+    fn test_vpn_rekey_ratchets_each_direction() {
+        let nonce = b"vpn-nonce-123456";
+        let mut sender = VpnSession::from_keys(b"key-a", b"key-b", nonce);
+        let mut receiver = VpnSession::from_keys(b"key-b", b"key-a", nonce);
+        let before = sender.encrypt_data(b"epoch 0");
+        assert_eq!(receiver.decrypt_data(&before).unwrap(), b"epoch 0");
+
+        sender.rekey_send();
+        let after = sender.encrypt_data(b"epoch 1");
+        assert_eq!(Frame::decode(&after).unwrap().sequence, 0);
+        // Epoch 0 keys no longer open frames of the new epoch
+        let mut stale = VpnSession::from_keys(b"key-b", b"key-a", nonce);
+        assert_eq!(stale.decrypt_data(&after), Err(AegisQError::AuthenticationFailed));
+        receiver.rekey_recv();
+        assert_eq!(receiver.decrypt_data(&after).unwrap(), b"epoch 1");
+
+        // The other direction is unaffected
+        let reply = receiver.encrypt_data(b"reply");
+        assert_eq!(sender.decrypt_data(&reply).unwrap(), b"reply");
+    }
 
+    #[test]
+    fn test_vpn_rejects_gaps_except_after_resume() {
+        let nonce = b"vpn-nonce-123456";
+        let mut sender = VpnSession::from_keys(b"key-a", b"key-b", nonce);
+        let mut receiver = VpnSession::from_keys(b"key-b", b"key-a", nonce);
+
+        // A dropped frame is detected
+        let _dropped = sender.encrypt_data(b"dropped");
+        let next = sender.encrypt_data(b"next");
+        assert_eq!(receiver.decrypt_data(&next), Err(AegisQError::Protocol("Sequence mismatch")));
+
+        // After a resume the peer's first frame may skip the send reserve, once
+        let mark = ReplayMark { recv_epoch: 0, recv_next: 2, send_epoch: 0, send_next: 2 };
+        sender.resume(mark);
+        receiver.resume(mark);
+        let resumed = sender.encrypt_data(b"resumed");
+        assert_eq!(Frame::decode(&resumed).unwrap().sequence, 2 + SEND_RESERVE);
+        assert_eq!(receiver.decrypt_data(&resumed).unwrap(), b"resumed");
+        let _dropped = sender.encrypt_data(b"dropped");
+        let after = sender.encrypt_data(b"after");
+        assert_eq!(receiver.decrypt_data(&after), Err(AegisQError::Protocol("Sequence mismatch")));
+
+        // A skip past the reserve is rejected even right after a resume
+        let mut far = VpnSession::from_keys(b"key-a", b"key-b", nonce);
+        let mut receiver = VpnSession::from_keys(b"key-b", b"key-a", nonce);
+        far.resume(ReplayMark { send_next: 1, ..ReplayMark::default() });
+        receiver.resume(ReplayMark::default());
+        assert_eq!(receiver.decrypt_data(&far.encrypt_data(b"far")), Err(AegisQError::Protocol("Sequence mismatch")));
+    }
+
+    #[test]
+    fn test_vpn_session_id() {
+        let nonce = b"vpn-nonce-123456";
+        let sender = VpnSession::from_keys(b"key-a", b"key-b", nonce);
+        let receiver = VpnSession::from_keys(b"key-b", b"key-a", nonce);
+        let other = VpnSession::from_keys(b"key-a", b"key-c", nonce);
+        
+        assert_eq!(sender.session_id(), receiver.session_id());
+        assert!(receiver.verify_session_id(sender.session_id().as_bytes()));
+        assert!(!other.verify_session_id(sender.session_id().as_bytes()));
+    }
+
+    #[test]
+    fn test_vpn_counts_traffic_and_rekeys() {
+        let nonce = b"vpn-nonce-123456";
+        let metrics = Arc::new(Metrics::new());
+        let mut sender = VpnSession::from_keys(b"key-a", b"key-b", nonce).with_metrics(metrics.clone());
+        let mut receiver = VpnSession::from_keys(b"key-b", b"key-a", nonce).with_metrics(metrics.clone());
+
+        let frame = sender.encrypt_data(b"12345");
+        receiver.decrypt_data(&frame).unwrap();
+        let mut frame = sender.encrypt_data(b"678");
+        receiver.decrypt_data_in_place(&mut frame).unwrap();
+        assert!(receiver.decrypt_data(&sender.encrypt_data(b"x")[..4]).is_err());
+        sender.rekey_send();
+        receiver.rekey_recv();
+        // Catching up on resume is not a rekey
+        receiver.resume(ReplayMark { recv_epoch: 2, ..ReplayMark::default() });
+
+        let text = metrics.render();
+        assert!(text.contains("aegisq_bytes_sent_total 9\n"));
+        assert!(text.contains("aegisq_bytes_received_total 8\n"));
+        assert!(text.contains("aegisq_rekeys_total 2\n"));
+    }
+}
@@ -0,0 +1,101 @@
+//! Property-based tests of the connection lifecycle state machine
+//!
+//! Random interleavings of application sends, handshake and rekey
+//! signals, timeouts, duplicated and reordered frames and peer restarts.
+
+use std::collections::HashSet;
+
+use proptest::prelude::*;
+use transport::lifecycle::{Action, Event, Lifecycle, Phase};
+
+fn event() -> impl Strategy<Value = Event> {
+    prop_oneof![
+        1 => Just(Event::Connect),
+        2 => Just(Event::HandshakeComplete),
+        6 => Just(Event::Send),
+        // Small ranges so duplicates and reordering are frequent
+        6 => (0u32..4, 0u64..80).prop_map(|(epoch, sequence)| Event::Frame { epoch, sequence }),
+        1 => Just(Event::RekeyDue),
+        1 => Just(Event::RekeyAck),
+        2 => Just(Event::Timeout),
+        1 => Just(Event::PeerRestart),
+        1 => Just(Event::Close),
+        1 => Just(Event::CloseAck),
+    ]
+}
+
+/// Events of a connection that gets established before anything else
+fn established_run() -> impl Strategy<Value = Vec<Event>> {
+    prop::collection::vec(event(), 0..200).prop_map(|events| {
+        let mut run = vec![Event::Connect, Event::HandshakeComplete];
+        run.extend(events);
+        run
+    })
+}
+
+proptest! {
+    #[test]
+    fn never_sends_data_before_established(events in prop::collection::vec(event(), 0..200)) {
+        let mut lifecycle = Lifecycle::new();
+        let mut handshake_done = false;
+        for event in events {
+            let before = lifecycle.phase();
+            let actions = lifecycle.handle(event);
+            if matches!(event, Event::Connect | Event::PeerRestart) && lifecycle.phase() == Phase::Handshaking {
+                handshake_done = false;
+            }
+            if event == Event::HandshakeComplete && before == Phase::Handshaking {
+                handshake_done = true;
+            }
+            for action in actions {
+                if let Action::SendData { .. } = action {
+                    prop_assert!(handshake_done, "data sent before handshake completed");
+                    prop_assert!(lifecycle.phase().can_send());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn never_reuses_a_nonce(events in established_run()) {
+        let mut lifecycle = Lifecycle::new();
+        let mut used = HashSet::new();
+        for event in events {
+            for action in lifecycle.handle(event) {
+                if let Action::SendData { generation, epoch, sequence } = action {
+                    prop_assert!(used.insert((generation, epoch, sequence)), "nonce reused: {:?}", action);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn never_delivers_a_frame_twice(events in established_run()) {
+        let mut lifecycle = Lifecycle::new();
+        let mut delivered = HashSet::new();
+        for event in events {
+            for action in lifecycle.handle(event) {
+                if let Action::Deliver { generation, epoch, sequence } = action {
+                    prop_assert!(delivered.insert((generation, epoch, sequence)), "duplicate delivered: {:?}", action);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn follows_transitions_and_closed_is_terminal(events in prop::collection::vec(event(), 0..200)) {
+        let mut lifecycle = Lifecycle::new();
+        for event in events {
+            let before = lifecycle.phase();
+            let actions = lifecycle.handle(event);
+            prop_assert!(before.can_transition(lifecycle.phase()), "{:?} -> {:?} on {:?}", before, lifecycle.phase(), event);
+            if before == Phase::Closed {
+                prop_assert!(actions.is_empty());
+            }
+            if actions.contains(&Action::Closed) {
+                prop_assert_eq!(lifecycle.phase(), Phase::Closed);
+                prop_assert_eq!(lifecycle.queued(), 0);
+            }
+        }
+    }
+}
@@ -0,0 +1,33 @@
+//! Zeroization audit: VPN session teardown
+
+use transport::vpn::VpnSession;
+use utils::allocaudit::{assert_no_residual, TrackingAllocator};
+use utils::kdf::kdf_shake256;
+
+#[global_allocator]
+static ALLOC: TrackingAllocator = TrackingAllocator;
+
+const SHARED_SECRET: [u8; 32] = *b"vpn-shared-secret-for-audit-0001";
+const NONCE: &[u8] = b"residual-nonce01";
+const PLAINTEXT: [u8; 34] = *b"audited plaintext that must vanish";
+
+#[test]
+fn session_keys_wiped_on_teardown() {
+    let encrypt_key = kdf_shake256(b"aegis-q-transport-vpn-encrypt", &SHARED_SECRET, NONCE, 64);
+    let decrypt_key = kdf_shake256(b"aegis-q-transport-vpn-decrypt", &SHARED_SECRET, NONCE, 64);
+
+    for key in [&encrypt_key, &decrypt_key] {
+        assert_no_residual(&key[..32], || {
+            let mut session = VpnSession::from_handshake(&SHARED_SECRET, NONCE);
+            let frame = session.encrypt_data(&PLAINTEXT);
+            drop(session);
+            frame
+        });
+    }
+}
+
+#[test]
+fn plaintext_not_left_in_freed_buffers() {
+    let mut sender = VpnSession::from_handshake(&SHARED_SECRET, NONCE);
+    assert_no_residual(&PLAINTEXT, || drop(sender.encrypt_data(&PLAINTEXT)));
+}
@@ -0,0 +1,133 @@
+//! Allocation audit (feature `allocaudit`, tests only)
+//!
+//! `TrackingAllocator` wraps the system allocator and, while a pattern is
+//! being watched, scans every block as it is freed. A hit means secret
+//! bytes were still in memory when it went back to the allocator, i.e. a
+//! key, chain key or plaintext was not zeroized before teardown.
+//!
+//! Install it in a test binary and wrap the scenario:
+//!
+//! ```ignore
+//! #[global_allocator]
+//! static ALLOC: TrackingAllocator = TrackingAllocator;
+//!
+//! assert_no_residual(&secret, || drop(session_holding(secret)));
+//! ```
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+/// Longest pattern that can be watched
+pub const MAX_PATTERN: usize = 64;
+
+/// Shortest pattern accepted (shorter ones match by accident)
+pub const MIN_PATTERN: usize = 8;
+
+static PATTERN: [AtomicU8; MAX_PATTERN] = [const { AtomicU8::new(0) }; MAX_PATTERN];
+static PATTERN_LEN: AtomicUsize = AtomicUsize::new(0);
+static HITS: AtomicUsize = AtomicUsize::new(0);
+static WATCH_LOCK: Mutex<()> = Mutex::new(());
+
+/// System allocator that scans freed blocks for the watched pattern
+pub struct TrackingAllocator;
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        scan(ptr, layout.size());
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        // Never let the system allocator move a block behind our back:
+        // the old copy is a freed region like any other
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        let new = System.alloc(new_layout);
+        if !new.is_null() {
+            std::ptr::copy_nonoverlapping(ptr, new, layout.size().min(new_size));
+            self.dealloc(ptr, layout);
+        }
+        new
+    }
+}
+
+/// Count occurrences of the watched pattern in a block being freed
+///
+/// # Safety
+/// `ptr` must be valid for reads of `len` initialized-or-not bytes; reading
+/// them as `u8` through raw pointers never creates a reference.
+unsafe fn scan(ptr: *const u8, len: usize) {
+    let pattern_len = PATTERN_LEN.load(Ordering::Acquire);
+    if pattern_len == 0 || len < pattern_len {
+        return;
+    }
+    for start in 0..=len - pattern_len {
+        let matched = (0..pattern_len)
+            .all(|i| std::ptr::read_volatile(ptr.add(start + i)) == PATTERN[i].load(Ordering::Relaxed));
+        if matched {
+            HITS.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+    }
+}
+
+/// Active watch on a pattern; stops watching when dropped
+///
+/// Only one watch exists at a time, so audited tests in one binary are
+/// serialized.
+pub struct Watch {
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl Watch {
+    /// Freed blocks that still contained the pattern
+    pub fn residual_frees(&self) -> usize {
+        HITS.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        PATTERN_LEN.store(0, Ordering::Release);
+    }
+}
+
+/// Start scanning freed blocks for `pattern`
+///
+/// Panics unless `MIN_PATTERN <= pattern.len() <= MAX_PATTERN`.
+pub fn watch(pattern: &[u8]) -> Watch {
+    assert!(
+        (MIN_PATTERN..=MAX_PATTERN).contains(&pattern.len()),
+        "watched pattern must be {}..={} bytes",
+        MIN_PATTERN,
+        MAX_PATTERN
+    );
+    // A panicking audited test must not wedge the others
+    let lock = WATCH_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    for (slot, &byte) in PATTERN.iter().zip(pattern) {
+        slot.store(byte, Ordering::Relaxed);
+    }
+    HITS.store(0, Ordering::Relaxed);
+    PATTERN_LEN.store(pattern.len(), Ordering::Release);
+    Watch { _lock: lock }
+}
+
+/// Run `scenario` and assert no freed block contained `secret_pattern`
+///
+/// Requires `TrackingAllocator` as the binary's global allocator.
+pub fn assert_no_residual<R>(secret_pattern: &[u8], scenario: impl FnOnce() -> R) -> R {
+    let watch = watch(secret_pattern);
+    let result = scenario();
+    let hits = watch.residual_frees();
+    drop(watch);
+    assert_eq!(hits, 0, "{} freed block(s) still held the secret", hits);
+    result
+}
@@ -2,6 +2,9 @@
 //!
 //! All key derivation in the system should go through these helpers.
 
+use std::ptr;
+use std::sync::atomic::{compiler_fence, Ordering};
+
 use sha3::{Shake256, digest::{Update, ExtendableOutput, XofReader}};
 
 /// Derive `out_len` bytes from `key_material` and optional `info`, under a domain label.
@@ -32,3 +35,58 @@ pub fn kdf_shake256_fill(domain: &[u8], key_material: &[u8], info: &[u8], out: &
 }
 
 
+
+/// KDF with the domain and key material absorbed once
+///
+/// `fill(info, out)` gives the same output as
+/// `kdf_shake256_fill(domain, key_material, info, out)`, without absorbing
+/// the key again for every `info`. The absorbed state is key material and
+/// is overwritten when dropped.
+#[derive(Clone)]
+pub struct KeyedKdf {
+    hasher: Shake256,
+}
+
+impl KeyedKdf {
+    /// Absorb the domain label and key material
+    pub fn new(domain: &[u8], key_material: &[u8]) -> Self {
+        let mut hasher = Shake256::default();
+        hasher.update(b"aegis-q-kdf");
+        hasher.update(domain);
+        hasher.update(key_material);
+        Self { hasher }
+    }
+
+    /// Fill `out` with KDF output for `info`
+    pub fn fill(&self, info: &[u8], out: &mut [u8]) {
+        let mut hasher = self.hasher.clone();
+        hasher.update(info);
+        hasher.finalize_xof().read(out);
+    }
+}
+
+impl Drop for KeyedKdf {
+    fn drop(&mut self) {
+        // SAFETY: `self.hasher` is a valid, aligned, exclusive reference; the
+        // sponge state owns no heap memory, so not dropping it leaks nothing
+        unsafe { ptr::write_volatile(&mut self.hasher, Shake256::default()) };
+        compiler_fence(Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyed_kdf_matches_fill() {
+        let kdf = KeyedKdf::new(b"domain", b"key-material");
+        for info in [&b""[..], b"nonce-1", b"nonce-2"] {
+            let mut expected = [0u8; 200];
+            let mut actual = [0u8; 200];
+            kdf_shake256_fill(b"domain", b"key-material", info, &mut expected);
+            kdf.fill(info, &mut actual);
+            assert_eq!(expected, actual);
+        }
+    }
+}
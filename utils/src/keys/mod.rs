@@ -0,0 +1,163 @@
+//! Typed keys
+//!
+//! One newtype per key purpose, so a storage key cannot be handed to the
+//! ratchet or a MAC key used for encryption. Raw bytes enter only through
+//! `from_bytes` at trust boundaries (loading a key file, a KEM secret);
+//! moving between purposes always goes through the KDF (`TypedKey::derive`),
+//! never through a cast. Key bytes are wiped on drop and never printed.
+
+use std::fmt;
+
+use crate::kdf::kdf_shake256;
+use crate::memory::zeroize;
+
+/// Length of keys produced by `TypedKey::derive`
+pub const DERIVED_KEY_LEN: usize = 64;
+
+mod sealed {
+    pub trait FromKdf {
+        fn from_kdf(bytes: Vec<u8>) -> Self;
+    }
+}
+
+/// Common behaviour of the purpose-specific key types
+pub trait TypedKey: sealed::FromKdf {
+    /// Purpose label mixed into derivations of this type
+    const PURPOSE: &'static str;
+
+    /// Raw key bytes, for the primitive that consumes them
+    fn as_bytes(&self) -> &[u8];
+
+    /// Derive a key of type `T` for `context`
+    ///
+    /// K = KDF("aegis-q-typed-key", self, source purpose || 0 || target purpose || 0 || context)
+    fn derive<T: TypedKey>(&self, context: &str) -> T {
+        let mut info = Vec::with_capacity(Self::PURPOSE.len() + T::PURPOSE.len() + context.len() + 2);
+        for part in [Self::PURPOSE, T::PURPOSE] {
+            info.extend_from_slice(part.as_bytes());
+            info.push(0);
+        }
+        info.extend_from_slice(context.as_bytes());
+        T::from_kdf(kdf_shake256(b"aegis-q-typed-key", self.as_bytes(), &info, DERIVED_KEY_LEN))
+    }
+}
+
+macro_rules! typed_key {
+    ($(#[$doc:meta])* $name:ident, $purpose:literal) => {
+        $(#[$doc])*
+        #[derive(Clone)]
+        pub struct $name {
+            bytes: Vec<u8>,
+        }
+
+        impl $name {
+            /// Import raw key material (trust boundary: key files, KEM secrets)
+            pub fn from_bytes(bytes: &[u8]) -> Self {
+                Self { bytes: bytes.to_vec() }
+            }
+
+            /// Key length in bytes
+            pub fn len(&self) -> usize {
+                self.bytes.len()
+            }
+
+            /// Whether the key is empty
+            pub fn is_empty(&self) -> bool {
+                self.bytes.is_empty()
+            }
+        }
+
+        impl sealed::FromKdf for $name {
+            fn from_kdf(bytes: Vec<u8>) -> Self {
+                Self { bytes }
+            }
+        }
+
+        impl TypedKey for $name {
+            const PURPOSE: &'static str = $purpose;
+
+            fn as_bytes(&self) -> &[u8] {
+                &self.bytes
+            }
+        }
+
+        impl PartialEq for $name {
+            /// Constant-time in the key contents
+            fn eq(&self, other: &Self) -> bool {
+                self.bytes.len() == other.bytes.len()
+                    && self.bytes.iter().zip(&other.bytes).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+            }
+        }
+
+        impl Eq for $name {}
+
+        impl Drop for $name {
+            fn drop(&mut self) {
+                zeroize(&mut self.bytes);
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, concat!(stringify!($name), "(<{} bytes>)"), self.bytes.len())
+            }
+        }
+    };
+}
+
+typed_key!(
+    /// Key for an AEAD (message keys, storage keys)
+    EncryptionKey,
+    "encryption"
+);
+
+typed_key!(
+    /// Key for message authentication tags
+    MacKey,
+    "mac"
+);
+
+typed_key!(
+    /// Root of a key hierarchy (ratchet root, storage master key)
+    RootKey,
+    "root"
+);
+
+typed_key!(
+    /// Key-wrapping key for envelopes around other keys or licenses
+    EnvelopeKey,
+    "envelope"
+);
+
+typed_key!(
+    /// License and log signing key
+    SigningKey,
+    "signing"
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derivation_is_purpose_bound() {
+        let root = RootKey::from_bytes(b"root-key-material");
+        let enc: EncryptionKey = root.derive("storage");
+        let mac: MacKey = root.derive("storage");
+        let other: EncryptionKey = root.derive("media");
+
+        assert_eq!(enc.len(), DERIVED_KEY_LEN);
+        assert_ne!(enc.as_bytes(), mac.as_bytes());
+        assert_ne!(enc, other);
+        assert_eq!(enc, root.derive::<EncryptionKey>("storage"));
+        // Same bytes imported as another type derive differently
+        let as_mac = MacKey::from_bytes(root.as_bytes());
+        assert_ne!(as_mac.derive::<EncryptionKey>("storage"), enc);
+    }
+
+    #[test]
+    fn test_debug_redacted() {
+        let key = SigningKey::from_bytes(b"secret");
+        assert_eq!(format!("{:?}", key), "SigningKey(<6 bytes>)");
+    }
+}
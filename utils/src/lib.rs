@@ -1,3 +1,11 @@
 pub mod rng;
 pub mod memory;
 pub mod kdf;
+pub mod keys;
+pub mod shamir;
+pub mod pool;
+pub mod mnemonic;
+pub mod merkle;
+pub mod wire;
+#[cfg(feature = "allocaudit")]
+pub mod allocaudit;
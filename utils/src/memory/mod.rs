@@ -1,7 +1,16 @@
 //! Memory management utilities
 //! Secure memory allocation and zeroization
+//!
+//! Wiping uses one volatile write per element followed by a compiler fence,
+//! so the stores cannot be elided as dead even though the memory is about
+//! to be freed. All unsafe code here is exercised by the tests under Miri
+//! (`cargo +nightly miri test -p utils memory`).
 
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::ops::{Deref, DerefMut};
 use std::ptr;
+use std::sync::atomic::{compiler_fence, Ordering};
 
 /// Secure memory arena for cryptographic operations
 pub struct SecureArena {
@@ -30,27 +39,207 @@ impl SecureArena {
 impl Drop for SecureArena {
     fn drop(&mut self) {
         // Zeroize memory on drop
-        unsafe {
-            ptr::write_bytes(self.data.as_mut_ptr(), 0, self.data.len());
-        }
+        self.data.wipe();
     }
 }
 
 /// Zeroize a slice in constant time
 pub fn zeroize(slice: &mut [u8]) {
-    // Constant-time zeroization
     for byte in slice.iter_mut() {
-        *byte = 0;
-    }
-    // Compiler barrier to prevent optimization
-    unsafe {
-        core::ptr::read_volatile(slice.as_ptr());
+        // SAFETY: `byte` is a valid, aligned, exclusive reference
+        unsafe { ptr::write_volatile(byte, 0) };
     }
+    // Keep later code from being reordered before the wipe
+    compiler_fence(Ordering::SeqCst);
 }
 
-/// Zeroize a vector
+/// Zeroize a vector, including spare capacity
 pub fn zeroize_vec(mut vec: Vec<u8>) {
-    zeroize(&mut vec);
+    vec.wipe();
     vec.clear();
 }
 
+/// Zero uninitialized memory (e.g. a vector's spare capacity)
+fn zeroize_uninit<T>(slice: &mut [MaybeUninit<T>]) {
+    for element in slice.iter_mut() {
+        // SAFETY: writing a `MaybeUninit<T>` through an exclusive reference is always valid
+        unsafe { ptr::write_volatile(element, MaybeUninit::zeroed()) };
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// Types whose secret contents can be wiped in place
+pub trait Wipe {
+    /// Overwrite the value's secret bytes with zeros
+    fn wipe(&mut self);
+}
+
+impl Wipe for [u8] {
+    fn wipe(&mut self) {
+        zeroize(self);
+    }
+}
+
+impl<const N: usize> Wipe for [u8; N] {
+    fn wipe(&mut self) {
+        zeroize(self);
+    }
+}
+
+impl Wipe for Vec<u8> {
+    /// Wipes the contents and any spare capacity (stale data from earlier truncation)
+    fn wipe(&mut self) {
+        zeroize(self);
+        zeroize_uninit(self.spare_capacity_mut());
+    }
+}
+
+impl Wipe for String {
+    fn wipe(&mut self) {
+        // SAFETY: all zero bytes are valid UTF-8
+        unsafe { self.as_mut_vec() }.wipe();
+    }
+}
+
+macro_rules! wipe_int {
+    ($($t:ty),*) => {$(
+        impl Wipe for $t {
+            fn wipe(&mut self) {
+                // SAFETY: `self` is a valid, aligned, exclusive reference
+                unsafe { ptr::write_volatile(self, 0) };
+                compiler_fence(Ordering::SeqCst);
+            }
+        }
+    )*};
+}
+
+wipe_int!(u8, u16, u32, u64, u128, usize);
+
+macro_rules! wipe_words {
+    ($($t:ty),*) => {$(
+        impl Wipe for [$t] {
+            fn wipe(&mut self) {
+                for word in self.iter_mut() {
+                    // SAFETY: `word` is a valid, aligned, exclusive reference
+                    unsafe { ptr::write_volatile(word, 0) };
+                }
+                compiler_fence(Ordering::SeqCst);
+            }
+        }
+
+        impl Wipe for Vec<$t> {
+            /// Wipes the contents and any spare capacity
+            fn wipe(&mut self) {
+                self.as_mut_slice().wipe();
+                zeroize_uninit(self.spare_capacity_mut());
+            }
+        }
+    )*};
+}
+
+wipe_words!(u32, u64, usize);
+
+/// Heap-allocated secret, wiped on drop
+///
+/// Boxing keeps the value at one address for its whole life, so moving a
+/// `SecureBox` never leaves stray copies of the secret on the stack. A
+/// boxed `Vec` that grows still frees its old block unwiped, so reserve
+/// the final size up front.
+pub struct SecureBox<T: Wipe> {
+    inner: Box<T>,
+}
+
+impl<T: Wipe> SecureBox<T> {
+    /// Move a value into a secure box
+    ///
+    /// The caller's original (if it was a copy type) is not wiped; prefer
+    /// building the secret in place through `DerefMut`.
+    pub fn new(value: T) -> Self {
+        Self { inner: Box::new(value) }
+    }
+}
+
+impl<T: Wipe + Default> Default for SecureBox<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: Wipe> Deref for SecureBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T: Wipe> DerefMut for SecureBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+impl<T: Wipe> Drop for SecureBox<T> {
+    fn drop(&mut self) {
+        self.inner.wipe();
+    }
+}
+
+impl<T: Wipe> fmt::Debug for SecureBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecureBox(<redacted>)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zeroize_slices_and_spare_capacity() {
+        let mut buffer = [0xAAu8; 33];
+        zeroize(&mut buffer[1..]);
+        assert_eq!(buffer[0], 0xAA);
+        assert!(buffer[1..].iter().all(|&b| b == 0));
+
+        // Truncated secret bytes in spare capacity are wiped too
+        let mut vec = vec![0x55u8; 64];
+        vec.truncate(8);
+        vec.wipe();
+        unsafe { vec.set_len(64) };
+        assert!(vec.iter().all(|&b| b == 0));
+
+        let mut text = String::from("hunter2");
+        text.wipe();
+        assert!(text.bytes().all(|b| b == 0));
+
+        let mut word = u64::MAX;
+        word.wipe();
+        assert_eq!(word, 0);
+
+        let mut words = vec![u32::MAX; 16];
+        words.truncate(4);
+        words.wipe();
+        unsafe { words.set_len(16) };
+        assert!(words.iter().all(|&w| w == 0));
+    }
+
+    #[test]
+    fn test_secure_box() {
+        let mut key: SecureBox<[u8; 32]> = SecureBox::default();
+        key.copy_from_slice(&[7u8; 32]);
+        assert_eq!(key[31], 7);
+        assert_eq!(format!("{:?}", key), "SecureBox(<redacted>)");
+
+        let mut moved = key;
+        moved.wipe();
+        assert_eq!(*moved, [0u8; 32]);
+
+        let arena = {
+            let mut arena = SecureArena::new(16);
+            arena.as_mut_slice().fill(9);
+            arena
+        };
+        assert_eq!(arena.as_slice(), &[9u8; 16]);
+    }
+}
@@ -0,0 +1,326 @@
+//! Merkle trees
+//!
+//! Append-only SHA3-256 Merkle tree with inclusion and consistency proofs,
+//! hashed as in RFC 6962/9162: leaf = H(0x00 || data),
+//! node = H(0x01 || left || right). Used for the license transparency log,
+//! audit log snapshots and chunked transfer manifests.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+
+/// Hash output size
+pub const HASH_SIZE: usize = 32;
+
+/// Tree node hash
+pub type Hash = [u8; HASH_SIZE];
+
+/// Inclusion proof for one leaf
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct InclusionProof {
+    pub leaf_index: u64,
+    pub tree_size: u64,
+    pub path: Vec<Hash>,
+}
+
+impl InclusionProof {
+    /// Verify that `leaf_hash` is included under `root`
+    pub fn verify(&self, leaf_hash: &Hash, root: &Hash) -> bool {
+        verify_inclusion(leaf_hash, self.leaf_index, self.tree_size, &self.path, root)
+    }
+}
+
+/// Append-only Merkle tree over leaf hashes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MerkleTree {
+    leaves: Vec<Hash>,
+}
+
+impl MerkleTree {
+    /// Empty tree
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tree over already-hashed leaves
+    pub fn from_leaf_hashes(leaves: Vec<Hash>) -> Self {
+        Self { leaves }
+    }
+
+    /// Append leaf data, returning its index
+    pub fn push(&mut self, data: &[u8]) -> u64 {
+        self.push_leaf_hash(leaf_hash(data))
+    }
+
+    /// Append an already-hashed leaf, returning its index
+    pub fn push_leaf_hash(&mut self, hash: Hash) -> u64 {
+        self.leaves.push(hash);
+        (self.leaves.len() - 1) as u64
+    }
+
+    /// Number of leaves
+    pub fn len(&self) -> u64 {
+        self.leaves.len() as u64
+    }
+
+    /// Whether the tree has no leaves
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Leaf hashes, in order
+    pub fn leaf_hashes(&self) -> &[Hash] {
+        &self.leaves
+    }
+
+    /// Current root
+    pub fn root(&self) -> Hash {
+        subtree_root(&self.leaves)
+    }
+
+    /// Root of the first `tree_size` leaves
+    pub fn root_at(&self, tree_size: u64) -> Result<Hash, &'static str> {
+        if tree_size > self.len() {
+            return Err("Tree size beyond log");
+        }
+        Ok(subtree_root(&self.leaves[..tree_size as usize]))
+    }
+
+    /// Inclusion proof for `leaf_index` in the tree of size `tree_size`
+    pub fn inclusion_proof(&self, leaf_index: u64, tree_size: u64) -> Result<InclusionProof, &'static str> {
+        if tree_size > self.len() || leaf_index >= tree_size {
+            return Err("Index outside tree");
+        }
+        Ok(InclusionProof {
+            leaf_index,
+            tree_size,
+            path: inclusion_path(leaf_index as usize, &self.leaves[..tree_size as usize]),
+        })
+    }
+
+    /// Consistency proof between an older tree size and a newer one
+    pub fn consistency_proof(&self, old_size: u64, new_size: u64) -> Result<Vec<Hash>, &'static str> {
+        if old_size > new_size || new_size > self.len() {
+            return Err("Invalid tree sizes");
+        }
+        if old_size == 0 || old_size == new_size {
+            return Ok(Vec::new());
+        }
+        Ok(consistency_path(old_size as usize, &self.leaves[..new_size as usize], true))
+    }
+}
+
+/// Verify an inclusion path for `leaf_hash` at `index` in a tree of `tree_size`
+pub fn verify_inclusion(leaf_hash: &Hash, index: u64, tree_size: u64, path: &[Hash], root: &Hash) -> bool {
+    if index >= tree_size {
+        return false;
+    }
+
+    let mut fn_ = index;
+    let mut sn = tree_size - 1;
+    let mut r = *leaf_hash;
+    for p in path {
+        if sn == 0 {
+            return false;
+        }
+        if fn_ & 1 == 1 || fn_ == sn {
+            r = node_hash(p, &r);
+            if fn_ & 1 == 0 {
+                while fn_ & 1 == 0 && fn_ != 0 {
+                    fn_ >>= 1;
+                    sn >>= 1;
+                }
+            }
+        } else {
+            r = node_hash(&r, p);
+        }
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+    sn == 0 && &r == root
+}
+
+/// Verify that the tree `(new_size, new_root)` extends `(old_size, old_root)`
+pub fn verify_consistency(old_size: u64, old_root: &Hash, new_size: u64, new_root: &Hash, proof: &[Hash]) -> bool {
+    if old_size > new_size {
+        return false;
+    }
+    if old_size == new_size {
+        return proof.is_empty() && old_root == new_root;
+    }
+    if old_size == 0 {
+        return proof.is_empty();
+    }
+    if proof.is_empty() {
+        return false;
+    }
+
+    let mut path = proof.to_vec();
+    if old_size.is_power_of_two() {
+        path.insert(0, *old_root);
+    }
+
+    let mut fn_ = old_size - 1;
+    let mut sn = new_size - 1;
+    while fn_ & 1 == 1 {
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+
+    let mut fr = path[0];
+    let mut sr = path[0];
+    for c in &path[1..] {
+        if sn == 0 {
+            return false;
+        }
+        if fn_ & 1 == 1 || fn_ == sn {
+            fr = node_hash(c, &fr);
+            sr = node_hash(c, &sr);
+            if fn_ & 1 == 0 {
+                while fn_ & 1 == 0 && fn_ != 0 {
+                    fn_ >>= 1;
+                    sn >>= 1;
+                }
+            }
+        } else {
+            sr = node_hash(&sr, c);
+        }
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+    &fr == old_root && &sr == new_root && sn == 0
+}
+
+/// Leaf hash H(0x00 || data)
+pub fn leaf_hash(data: &[u8]) -> Hash {
+    let mut hasher = Sha3_256::new();
+    hasher.update([0x00]);
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+/// Interior node hash H(0x01 || left || right)
+pub fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha3_256::new();
+    hasher.update([0x01]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Largest power of two strictly less than `n` (n >= 2)
+fn split_point(n: usize) -> usize {
+    1 << (usize::BITS - 1 - (n - 1).leading_zeros())
+}
+
+fn subtree_root(leaves: &[Hash]) -> Hash {
+    match leaves.len() {
+        0 => Sha3_256::digest([]).into(),
+        1 => leaves[0],
+        n => {
+            let k = split_point(n);
+            node_hash(&subtree_root(&leaves[..k]), &subtree_root(&leaves[k..]))
+        }
+    }
+}
+
+fn inclusion_path(index: usize, leaves: &[Hash]) -> Vec<Hash> {
+    let n = leaves.len();
+    if n <= 1 {
+        return Vec::new();
+    }
+    let k = split_point(n);
+    if index < k {
+        let mut path = inclusion_path(index, &leaves[..k]);
+        path.push(subtree_root(&leaves[k..]));
+        path
+    } else {
+        let mut path = inclusion_path(index - k, &leaves[k..]);
+        path.push(subtree_root(&leaves[..k]));
+        path
+    }
+}
+
+fn consistency_path(m: usize, leaves: &[Hash], complete: bool) -> Vec<Hash> {
+    let n = leaves.len();
+    if m == n {
+        return if complete { Vec::new() } else { vec![subtree_root(leaves)] };
+    }
+    let k = split_point(n);
+    if m <= k {
+        let mut path = consistency_path(m, &leaves[..k], complete);
+        path.push(subtree_root(&leaves[k..]));
+        path
+    } else {
+        let mut path = consistency_path(m - k, &leaves[k..], false);
+        path.push(subtree_root(&leaves[..k]));
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(size: usize) -> MerkleTree {
+        let mut tree = MerkleTree::new();
+        for i in 0..size {
+            tree.push(format!("leaf-{}", i).as_bytes());
+        }
+        tree
+    }
+
+    #[test]
+    fn test_split_point() {
+        for (n, k) in [(2, 1), (3, 2), (4, 2), (5, 4), (8, 4), (9, 8), (17, 16)] {
+            assert_eq!(split_point(n), k);
+        }
+    }
+
+    #[test]
+    fn test_known_roots() {
+        assert_eq!(tree(1).root(), leaf_hash(b"leaf-0"));
+        let expected = node_hash(
+            &node_hash(&leaf_hash(b"leaf-0"), &leaf_hash(b"leaf-1")),
+            &leaf_hash(b"leaf-2"),
+        );
+        assert_eq!(tree(3).root(), expected);
+    }
+
+    #[test]
+    fn test_inclusion_proofs_all_sizes() {
+        let tree = tree(20);
+        for size in 1..=20u64 {
+            let root = tree.root_at(size).unwrap();
+            for index in 0..size {
+                let proof = tree.inclusion_proof(index, size).unwrap();
+                let leaf = &tree.leaf_hashes()[index as usize];
+                assert!(proof.verify(leaf, &root));
+
+                let mut wrong = proof.clone();
+                wrong.leaf_index = (index + 1) % size;
+                assert!(size == 1 || !wrong.verify(leaf, &root));
+            }
+        }
+    }
+
+    #[test]
+    fn test_consistency_proofs_all_sizes() {
+        let tree = tree(20);
+        for new_size in 1..=20u64 {
+            let new_root = tree.root_at(new_size).unwrap();
+            for old_size in 1..=new_size {
+                let old_root = tree.root_at(old_size).unwrap();
+                let proof = tree.consistency_proof(old_size, new_size).unwrap();
+                assert!(verify_consistency(old_size, &old_root, new_size, &new_root, &proof));
+
+                if old_size < new_size {
+                    let forged = [0xAAu8; HASH_SIZE];
+                    assert!(!verify_consistency(old_size, &forged, new_size, &new_root, &proof));
+                }
+            }
+        }
+    }
+}
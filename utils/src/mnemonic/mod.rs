@@ -0,0 +1,186 @@
+//! Mnemonic seed phrases
+//!
+//! BIP39-style encoding of master keys and seeds as word phrases for
+//! backup. Uses the BIP39 English wordlist and bit layout (11 bits per
+//! word, entropy followed by ENT/32 checksum bits), but the checksum is
+//! taken from SHA3-256 rather than SHA-256: phrases are not interchangeable
+//! with BIP39 wallets, and a BIP39 phrase is rejected by checksum.
+//!
+//! Phrases and intermediate buffers are zeroized when dropped.
+
+use std::sync::OnceLock;
+
+use sha3::{Digest, Sha3_256};
+
+use crate::memory::zeroize;
+use crate::rng::random_bytes;
+
+/// Number of words in the wordlist
+pub const WORDLIST_SIZE: usize = 2048;
+
+/// Supported phrase lengths
+pub const WORD_COUNTS: [usize; 5] = [12, 15, 18, 21, 24];
+
+const BITS_PER_WORD: usize = 11;
+
+static WORDLIST: OnceLock<Vec<&'static str>> = OnceLock::new();
+
+/// BIP39 English wordlist (sorted)
+pub fn wordlist() -> &'static [&'static str] {
+    WORDLIST.get_or_init(|| include_str!("english.txt").lines().collect())
+}
+
+/// Mnemonic phrase (zeroized on drop)
+pub struct Mnemonic {
+    phrase: String,
+}
+
+impl Mnemonic {
+    /// Generate a phrase with fresh random entropy
+    pub fn generate(word_count: usize) -> Result<Self, &'static str> {
+        if !WORD_COUNTS.contains(&word_count) {
+            return Err("Unsupported word count");
+        }
+        let mut entropy = random_bytes(word_count * BITS_PER_WORD * 32 / 33 / 8);
+        let mnemonic = Self::from_entropy(&entropy);
+        zeroize(&mut entropy);
+        mnemonic
+    }
+
+    /// Encode 16, 20, 24, 28 or 32 bytes of entropy
+    pub fn from_entropy(entropy: &[u8]) -> Result<Self, &'static str> {
+        if !(16..=32).contains(&entropy.len()) || !entropy.len().is_multiple_of(4) {
+            return Err("Unsupported entropy length");
+        }
+
+        let mut bits = to_bits(entropy);
+        let mut checksum = checksum(entropy);
+        bits.extend(to_bits(&checksum).into_iter().take(entropy.len() / 4));
+        zeroize(&mut checksum);
+
+        let words = wordlist();
+        let mut phrase = String::new();
+        for chunk in bits.chunks(BITS_PER_WORD) {
+            let index = chunk.iter().fold(0usize, |acc, &bit| (acc << 1) | bit as usize);
+            if !phrase.is_empty() {
+                phrase.push(' ');
+            }
+            phrase.push_str(words[index]);
+        }
+        zeroize(&mut bits);
+
+        Ok(Self { phrase })
+    }
+
+    /// Parse and validate a phrase (case and extra whitespace are tolerated)
+    pub fn parse(text: &str) -> Result<Self, &'static str> {
+        let mut phrase = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        phrase.make_ascii_lowercase();
+        let mnemonic = Self { phrase };
+
+        let mut entropy = mnemonic.to_entropy()?;
+        zeroize(&mut entropy);
+        Ok(mnemonic)
+    }
+
+    /// Phrase as space-separated words
+    pub fn phrase(&self) -> &str {
+        &self.phrase
+    }
+
+    /// Number of words
+    pub fn word_count(&self) -> usize {
+        self.phrase.split(' ').count()
+    }
+
+    /// Decode back to entropy, verifying the checksum
+    pub fn to_entropy(&self) -> Result<Vec<u8>, &'static str> {
+        let word_count = self.word_count();
+        if !WORD_COUNTS.contains(&word_count) {
+            return Err("Unsupported word count");
+        }
+
+        let words = wordlist();
+        let mut bits = Vec::with_capacity(word_count * BITS_PER_WORD);
+        for word in self.phrase.split(' ') {
+            let index = words.binary_search(&word).map_err(|_| "Unknown word")?;
+            bits.extend((0..BITS_PER_WORD).rev().map(|i| ((index >> i) & 1) as u8));
+        }
+
+        let checksum_bits = word_count * BITS_PER_WORD / 33;
+        let entropy_bits = bits.len() - checksum_bits;
+        let entropy: Vec<u8> = bits[..entropy_bits]
+            .chunks(8)
+            .map(|byte| byte.iter().fold(0u8, |acc, &bit| (acc << 1) | bit))
+            .collect();
+
+        let mut expected = checksum(&entropy);
+        let mut expected_bits = to_bits(&expected);
+        let valid = expected_bits[..checksum_bits] == bits[entropy_bits..];
+        zeroize(&mut expected);
+        zeroize(&mut expected_bits);
+        zeroize(&mut bits);
+
+        if !valid {
+            let mut entropy = entropy;
+            zeroize(&mut entropy);
+            return Err("Checksum mismatch");
+        }
+        Ok(entropy)
+    }
+}
+
+impl Drop for Mnemonic {
+    fn drop(&mut self) {
+        // SAFETY: zero bytes are valid UTF-8
+        zeroize(unsafe { self.phrase.as_bytes_mut() });
+    }
+}
+
+fn checksum(entropy: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha3_256::new();
+    hasher.update(entropy);
+    hasher.finalize().to_vec()
+}
+
+fn to_bits(bytes: &[u8]) -> Vec<u8> {
+    bytes
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wordlist_shape() {
+        let words = wordlist();
+        assert_eq!(words.len(), WORDLIST_SIZE);
+        assert!(words.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(words[0], "abandon");
+        assert_eq!(words[WORDLIST_SIZE - 1], "zoo");
+    }
+
+    #[test]
+    fn test_mnemonic_roundtrip_and_checksum() {
+        for word_count in WORD_COUNTS {
+            let mnemonic = Mnemonic::generate(word_count).unwrap();
+            assert_eq!(mnemonic.word_count(), word_count);
+
+            let parsed = Mnemonic::parse(&format!("  {}  ", mnemonic.phrase().to_uppercase())).unwrap();
+            assert_eq!(parsed.to_entropy().unwrap(), mnemonic.to_entropy().unwrap());
+        }
+
+        let mnemonic = Mnemonic::from_entropy(&[0u8; 16]).unwrap();
+        let mut words: Vec<&str> = mnemonic.phrase().split(' ').collect();
+        let last = wordlist().binary_search(&words[11]).unwrap();
+        words[11] = wordlist()[last ^ 1]; // flips a checksum bit only
+        assert_eq!(Mnemonic::parse(&words.join(" ")).err(), Some("Checksum mismatch"));
+
+        assert!(Mnemonic::parse("abandon abandon notaword").is_err());
+        assert!(Mnemonic::from_entropy(&[0u8; 15]).is_err());
+        assert!(Mnemonic::generate(13).is_err());
+    }
+}
@@ -0,0 +1,160 @@
+//! Shamir secret sharing over GF(2^8)
+//!
+//! Byte-wise sharing with the AES field polynomial (x^8 + x^4 + x^3 + x + 1).
+//! Field arithmetic is branch-free and table-free.
+
+use crate::rng::random_bytes;
+
+/// One share of a split secret
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Share {
+    /// Evaluation point (1..=255, never 0)
+    pub index: u8,
+    /// Threshold needed to reconstruct
+    pub threshold: u8,
+    /// Share bytes (same length as the secret)
+    pub value: Vec<u8>,
+}
+
+/// Multiply in GF(2^8), constant time
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut result = 0u8;
+    for _ in 0..8 {
+        result ^= a & 0u8.wrapping_sub(b & 1);
+        let carry = 0u8.wrapping_sub(a >> 7);
+        a = (a << 1) ^ (0x1b & carry);
+        b >>= 1;
+    }
+    result
+}
+
+/// Inverse in GF(2^8): a^254, constant time (0 maps to 0)
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1u8;
+    let mut base = a;
+    let mut exp = 254u8;
+    for _ in 0..8 {
+        let factor = gf_mul(result, base);
+        let mask = 0u8.wrapping_sub(exp & 1);
+        result = (factor & mask) | (result & !mask);
+        base = gf_mul(base, base);
+        exp >>= 1;
+    }
+    result
+}
+
+/// Split `secret` into `shares` shares, any `threshold` (at least 2) of which reconstruct it
+pub fn split(secret: &[u8], threshold: u8, shares: u8) -> Result<Vec<Share>, &'static str> {
+    if threshold < 2 || shares < threshold {
+        return Err("Invalid threshold");
+    }
+
+    // coefficients[k][i]: degree-k coefficient for secret byte i
+    let mut coefficients = vec![secret.to_vec()];
+    for _ in 1..threshold {
+        coefficients.push(random_bytes(secret.len()));
+    }
+
+    let result = (1..=shares)
+        .map(|x| {
+            let value = (0..secret.len())
+                .map(|i| {
+                    // Horner evaluation at x
+                    coefficients
+                        .iter()
+                        .rev()
+                        .fold(0u8, |acc, c| gf_mul(acc, x) ^ c[i])
+                })
+                .collect();
+            Share { index: x, threshold, value }
+        })
+        .collect();
+
+    for c in coefficients.iter_mut() {
+        crate::memory::zeroize(c);
+    }
+
+    Ok(result)
+}
+
+/// Reconstruct the secret from at least `threshold` shares
+///
+/// The threshold field is not authenticated, so every share given must
+/// agree on it and it must be at least 2; callers should still check the
+/// result against something recorded at split time.
+pub fn combine(shares: &[Share]) -> Result<Vec<u8>, &'static str> {
+    let first = shares.first().ok_or("No shares")?;
+    if first.threshold < 2 {
+        return Err("Invalid threshold");
+    }
+    for (i, share) in shares.iter().enumerate() {
+        if share.index == 0 || share.threshold != first.threshold || share.value.len() != first.value.len() {
+            return Err("Inconsistent shares");
+        }
+        if shares[..i].iter().any(|s| s.index == share.index) {
+            return Err("Duplicate share");
+        }
+    }
+
+    let threshold = first.threshold as usize;
+    if shares.len() < threshold {
+        return Err("Not enough shares");
+    }
+    let shares = &shares[..threshold];
+
+    let mut secret = vec![0u8; first.value.len()];
+    for (i, share_i) in shares.iter().enumerate() {
+        // Lagrange basis at 0: prod_{j != i} x_j / (x_j - x_i); subtraction is XOR
+        let mut basis = 1u8;
+        for (j, share_j) in shares.iter().enumerate() {
+            if i != j {
+                basis = gf_mul(basis, gf_mul(share_j.index, gf_inv(share_j.index ^ share_i.index)));
+            }
+        }
+        for (out, &y) in secret.iter_mut().zip(share_i.value.iter()) {
+            *out ^= gf_mul(y, basis);
+        }
+    }
+
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gf_inverse() {
+        for a in 1..=255u8 {
+            assert_eq!(gf_mul(a, gf_inv(a)), 1);
+        }
+    }
+
+    #[test]
+    fn test_split_combine() {
+        let secret = b"vendor-root-key-0123456789abcdef".to_vec();
+        let shares = split(&secret, 3, 5).unwrap();
+
+        assert_eq!(combine(&shares[..3]).unwrap(), secret);
+        assert_eq!(combine(&[shares[4].clone(), shares[0].clone(), shares[2].clone()]).unwrap(), secret);
+        assert!(combine(&shares[..2]).is_err());
+        assert!(combine(&[shares[0].clone(), shares[0].clone(), shares[1].clone()]).is_err());
+        assert!(split(&secret, 1, 3).is_err());
+    }
+
+    #[test]
+    fn test_combine_rejects_forged_threshold() {
+        let secret = b"vendor-root-key-0123456789abcdef".to_vec();
+        let shares = split(&secret, 2, 3).unwrap();
+
+        for threshold in [0, 1] {
+            let forged = Share { threshold, ..shares[0].clone() };
+            assert_eq!(combine(&[forged.clone(), shares[1].clone()]), Err("Invalid threshold"));
+            assert_eq!(combine(&[shares[1].clone(), forged]), Err("Inconsistent shares"));
+        }
+
+        // Shares past the threshold must agree too
+        let extra = Share { threshold: 3, ..shares[2].clone() };
+        assert_eq!(combine(&[shares[0].clone(), shares[1].clone(), extra]), Err("Inconsistent shares"));
+    }
+}
@@ -0,0 +1,353 @@
+//! Message definitions
+//!
+//! Byte layouts of every binary format in the workspace. Semantic checks
+//! (known versions, flag combinations, MACs) stay with the owning crate.
+
+use crate::merkle::Hash;
+
+crate::wire_struct! {
+    /// Ciphertext header (core): magic || version || mode || flags || padding block log2
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct CiphertextHeaderWire {
+        pub magic: [u8; 4] => super::Fixed,
+        pub version: u8 => super::U8,
+        pub mode: u8 => super::U8,
+        pub flags: u8 => super::U8,
+        pub block_log2: u8 => super::U8,
+    }
+}
+
+crate::wire_struct! {
+    /// Nonce with the ciphertext header bound in (core): length-prefixed nonce || header
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct BoundNonceWire {
+        pub nonce: Vec<u8> => super::Bytes16Be,
+        pub header: Vec<u8> => super::Rest,
+    }
+}
+
+crate::wire_struct! {
+    /// Transport frame header (16 bytes, payload follows)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct FrameHeaderWire {
+        pub frame_type: u8 => super::U8,
+        pub sequence: u64 => super::U64Le,
+        pub payload_len: u32 => super::U32Le,
+        pub reserved: [u8; 3] => super::Fixed,
+    }
+}
+
+crate::wire_struct! {
+    /// Handshake extension: type || length-prefixed encrypted body
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct HandshakeExtensionWire {
+        pub extension_type: u16 => super::U16Be,
+        pub body: Vec<u8> => super::Bytes16Be,
+    }
+}
+
+crate::wire_struct! {
+    /// Client authentication extension body (decrypted)
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct AuthBodyWire {
+        pub identity: String => super::Bytes8,
+        pub proof: Vec<u8> => super::Rest,
+    }
+}
+
+crate::wire_struct! {
+    /// ECN receive counters
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct EcnCountsWire {
+        pub ect0: u64 => super::U64Le,
+        pub ect1: u64 => super::U64Le,
+        pub ce: u64 => super::U64Le,
+    }
+}
+
+crate::wire_struct! {
+    /// ACK frame payload
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct AckWire {
+        pub largest_acked: u64 => super::U64Le,
+        pub ecn: Option<EcnCountsWire> => super::Optional<super::Nested>,
+    }
+}
+
+crate::wire_struct! {
+    /// Multipath packet header: path ID || path sequence || data sequence (payload follows)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct MultipathHeaderWire {
+        pub path_id: u8 => super::U8,
+        pub path_sequence: u64 => super::U64Le,
+        pub data_sequence: u64 => super::U64Le,
+    }
+}
+
+crate::wire_struct! {
+    /// File transfer manifest: sizes || Merkle root || chunk hashes
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct ManifestWire {
+        pub file_size: u64 => super::U64Le,
+        pub chunk_size: u32 => super::U32Le,
+        pub root: Hash => super::Fixed,
+        pub chunk_hashes: Vec<Hash> => super::Repeated<super::Fixed>,
+    }
+}
+
+crate::wire_struct! {
+    /// File transfer chunk header: stream ID || chunk index (ciphertext follows)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct ChunkHeaderWire {
+        pub stream_id: u32 => super::U32Le,
+        pub index: u64 => super::U64Le,
+    }
+}
+
+crate::wire_struct! {
+    /// Debug capture record body (before operator encryption)
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct CaptureRecordWire {
+        pub session_id: [u8; 32] => super::Fixed,
+        pub direction: u8 => super::U8,
+        pub timestamp_ms: u64 => super::U64Be,
+        pub frame: Vec<u8> => super::Rest,
+    }
+}
+
+crate::wire_struct! {
+    /// Messenger QR payload (checksum appended separately)
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct QrPayloadWire {
+        pub version: u8 => super::U8,
+        pub kind: u8 => super::U8,
+        pub body: Vec<u8> => super::Bytes16Be,
+    }
+}
+
+crate::wire_struct! {
+    /// Prekey bundle inside a QR payload
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct PrekeyBundleWire {
+        pub identity_key: Vec<u8> => super::Bytes16Be,
+        pub signed_prekey: Vec<u8> => super::Bytes16Be,
+        pub prekey_signature: Vec<u8> => super::Bytes16Be,
+        pub one_time_prekey: Option<Vec<u8>> => super::Optional<super::Bytes16Be>,
+    }
+}
+
+crate::wire_struct! {
+    /// NAT traversal probe (MAC tag appended separately)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct NatProbeWire {
+        pub magic: [u8; 4] => super::Fixed,
+        pub kind: u8 => super::U8,
+        pub transaction: u64 => super::U64Be,
+    }
+}
+
+crate::wire_struct! {
+    /// Forward error correction shard: group || kind || index || data count || shard
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct FecShardWire {
+        pub group: u32 => super::U32Be,
+        /// 0 = data, 1 = parity
+        pub kind: u8 => super::U8,
+        pub index: u8 => super::U8,
+        /// Data shards in the group (parity shards only, 0 in data shards)
+        pub data_count: u8 => super::U8,
+        pub shard: Vec<u8> => super::Rest,
+    }
+}
+
+crate::wire_struct! {
+    /// License envelope (licensing): version || algorithm || nonce || ciphertext
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct LicenseEnvelopeWire {
+        pub version: u8 => super::U8,
+        pub algorithm: u8 => super::U8,
+        pub nonce: Vec<u8> => super::Bytes8,
+        pub ciphertext: Vec<u8> => super::Rest,
+    }
+}
+
+crate::wire_struct! {
+    /// Algorithm suite (core): AEAD || KEM || signature || hash registry IDs
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct AlgorithmSuiteWire {
+        pub aead: u16 => super::U16Be,
+        pub kem: u16 => super::U16Be,
+        pub signature: u16 => super::U16Be,
+        pub hash: u16 => super::U16Be,
+    }
+}
+
+crate::wire_struct! {
+    /// Offered algorithm suites, most preferred first
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct SuiteOfferWire {
+        pub suites: Vec<AlgorithmSuiteWire> => super::Repeated<super::Nested>,
+    }
+}
+
+crate::wire_struct! {
+    /// State encoding header (core): version || lattice and code dimensions || ZK and mask lengths
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct StateHeaderWire {
+        pub version: u8 => super::U8,
+        pub lattice_n: u16 => super::U16Be,
+        pub code_n: u16 => super::U16Be,
+        pub zk_len: u16 => super::U16Be,
+        pub mask_len: u16 => super::U16Be,
+    }
+}
+
+crate::wire_struct! {
+    /// Client's first handshake message: version || random || extensions (GREASE and padding included)
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct ClientHelloWire {
+        pub version: u16 => super::U16Be,
+        pub random: [u8; 32] => super::Fixed,
+        pub extensions: Vec<HandshakeExtensionWire> => super::Repeated<super::Nested>,
+    }
+}
+
+crate::wire_struct! {
+    /// KMS-wrapped data key (core): version || master key ID || wrapped blob
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct WrappedKeyWire {
+        pub version: u8 => super::U8,
+        pub kms_key_id: String => super::Bytes16Be,
+        pub blob: Vec<u8> => super::Bytes16Be,
+    }
+}
+
+crate::wire_struct! {
+    /// Session ticket (transport): STEK ID || nonce || sealed `TicketStateWire`
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct TicketWire {
+        pub key_id: [u8; 8] => super::Fixed,
+        pub nonce: [u8; 16] => super::Fixed,
+        pub sealed: Vec<u8> => super::Rest,
+    }
+}
+
+crate::wire_struct! {
+    /// Ticket plaintext: issue time || resumption state
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct TicketStateWire {
+        pub issued_at_ms: u64 => super::U64Be,
+        pub state: Vec<u8> => super::Rest,
+    }
+}
+
+crate::wire_struct! {
+    /// Fleet retry token fields covered by the MAC: epoch || issue time
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct RetryTokenHeaderWire {
+        pub epoch: u32 => super::U32Be,
+        pub issued_at_ms: u64 => super::U64Be,
+    }
+}
+
+crate::wire_struct! {
+    /// Fleet retry token (transport): header || MAC
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct RetryTokenWire {
+        pub header: RetryTokenHeaderWire => super::Nested,
+        pub tag: [u8; 32] => super::Fixed,
+    }
+}
+
+crate::wire_struct! {
+    /// Replay high-water mark of one peer (transport)
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct ReplayMarkWire {
+        pub peer: Vec<u8> => super::Bytes16Be,
+        pub recv_epoch: u32 => super::U32Be,
+        pub recv_next: u64 => super::U64Be,
+        pub send_epoch: u32 => super::U32Be,
+        pub send_next: u64 => super::U64Be,
+    }
+}
+
+crate::wire_struct! {
+    /// Replay marks of all peers
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct ReplayMarksWire {
+        pub marks: Vec<ReplayMarkWire> => super::Repeated<super::Nested>,
+    }
+}
+
+crate::wire_struct! {
+    /// Replay state file: nonce || Aegis-Q(`ReplayMarksWire`)
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct ReplayStoreWire {
+        pub nonce: [u8; 16] => super::Fixed,
+        pub sealed: Vec<u8> => super::Rest,
+    }
+}
+
+crate::wire_struct! {
+    /// One licensed feature name
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct LicenseFeatureWire {
+        pub name: String => super::Bytes32Le,
+    }
+}
+
+crate::wire_struct! {
+    /// One license constraint: kind (1 countries, 2 IP ranges, 3 platforms) and its allowed values
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct LicenseConstraintWire {
+        pub kind: u8 => super::U8,
+        pub values: Vec<String> => super::Counted<super::Bytes32Le>,
+    }
+}
+
+crate::wire_struct! {
+    /// License fields covered by a signature or approval (licensing): every
+    /// field length-prefixed; `issuer`/`previous` are empty for the vendor signature
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct LicenseSignedWire {
+        pub domain: Vec<u8> => super::Bytes8,
+        pub issuer: String => super::Bytes32Le,
+        pub previous: Vec<u8> => super::Bytes32Le,
+        pub license_id: String => super::Bytes32Le,
+        pub expiry: u64 => super::U64Le,
+        pub seats: Option<u32> => super::Optional<super::U32Le>,
+        pub constraints: Vec<LicenseConstraintWire> => super::Counted<super::Nested>,
+        pub features: Vec<LicenseFeatureWire> => super::Repeated<super::Nested>,
+    }
+}
+
+crate::wire_struct! {
+    /// Encrypted blob embedded in a binary (licensing): magic || version || nonce || sealed data
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct EmbeddedBlobWire {
+        pub magic: [u8; 12] => super::Fixed,
+        pub version: u8 => super::U8,
+        pub nonce: [u8; 16] => super::Fixed,
+        pub sealed: Vec<u8> => super::Bytes32Le,
+    }
+}
+
+crate::wire_struct! {
+    /// Multi-recipient envelope header (core): version || KEM ID || slot count; slots and ciphertext follow
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct RecipientEnvelopeHeaderWire {
+        pub version: u8 => super::U8,
+        pub kem: u16 => super::U16Be,
+        pub slot_count: u16 => super::U16Be,
+    }
+}
+
+crate::wire_struct! {
+    /// Content key wrapped for one envelope recipient: recipient ID || KEM ciphertext || wrapped key
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct EnvelopeSlotWire {
+        pub recipient: [u8; 16] => super::Fixed,
+        pub kem_ciphertext: Vec<u8> => super::Bytes16Be,
+        pub wrapped_key: Vec<u8> => super::Bytes16Be,
+    }
+}
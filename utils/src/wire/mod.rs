@@ -0,0 +1,490 @@
+//! Wire formats
+//!
+//! Declarative binary message definitions shared by all crates. A message
+//! is declared once with `wire_struct!`, naming a codec for each field
+//! (integer width and byte order, fixed arrays, length-prefixed or
+//! trailing byte strings, optional and repeated values); encoding and
+//! decoding are derived from that declaration. Crates keep their own
+//! semantic types and convert to and from the wire structs in `messages`,
+//! so every byte layout lives in one place.
+//!
+//! Decoding is strict: truncated input, oversized length prefixes and
+//! invalid option flags are errors, and `Wire::from_wire` rejects trailing
+//! bytes.
+
+use std::marker::PhantomData;
+
+mod messages;
+pub use messages::*;
+
+/// Append-only encoder
+#[derive(Debug, Default)]
+pub struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    /// Empty writer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writer appending to an existing buffer (e.g. from `pool::take`)
+    pub fn with_buffer(buf: Vec<u8>) -> Self {
+        Self { buf }
+    }
+
+    /// Append raw bytes
+    pub fn put(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Bytes written so far
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Whether nothing was written
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Encoded bytes
+    pub fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+/// Cursor over encoded bytes
+#[derive(Debug, Clone)]
+pub struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    /// Reader at the start of `data`
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    /// Bytes not yet consumed
+    pub fn remaining(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Whether all input was consumed
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Consume `len` bytes
+    pub fn take(&mut self, len: usize) -> Result<&'a [u8], &'static str> {
+        if self.data.len() < len {
+            return Err("Truncated message");
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    /// Consume a fixed-size array
+    pub fn array<const N: usize>(&mut self) -> Result<[u8; N], &'static str> {
+        Ok(self.take(N)?.try_into().expect("length checked"))
+    }
+
+    /// Consume everything left
+    pub fn rest(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.data)
+    }
+
+    /// Fail if input remains
+    pub fn finish(&self) -> Result<(), &'static str> {
+        if self.data.is_empty() {
+            Ok(())
+        } else {
+            Err("Trailing bytes in message")
+        }
+    }
+}
+
+/// Field encoding for values of type `T`
+pub trait Codec<T> {
+    fn encode(value: &T, w: &mut Writer) -> Result<(), &'static str>;
+    fn decode(r: &mut Reader<'_>) -> Result<T, &'static str>;
+}
+
+/// Binary message
+pub trait Wire: Sized {
+    /// Append the encoding to `w`
+    fn encode_into(&self, w: &mut Writer) -> Result<(), &'static str>;
+
+    /// Decode from the front of `r`, leaving the rest
+    fn decode_from(r: &mut Reader<'_>) -> Result<Self, &'static str>;
+
+    /// Encode to a new buffer
+    fn to_wire(&self) -> Result<Vec<u8>, &'static str> {
+        let mut w = Writer::new();
+        self.encode_into(&mut w)?;
+        Ok(w.finish())
+    }
+
+    /// Decode exactly `bytes` (no trailing data)
+    fn from_wire(bytes: &[u8]) -> Result<Self, &'static str> {
+        let mut r = Reader::new(bytes);
+        let value = Self::decode_from(&mut r)?;
+        r.finish()?;
+        Ok(value)
+    }
+
+    /// Decode from the front of `bytes`; returns the value and the rest
+    fn from_wire_prefix(bytes: &[u8]) -> Result<(Self, &[u8]), &'static str> {
+        let mut r = Reader::new(bytes);
+        let value = Self::decode_from(&mut r)?;
+        Ok((value, r.remaining()))
+    }
+}
+
+macro_rules! int_codec {
+    ($($(#[$meta:meta])* $name:ident: $ty:ty, $to:ident, $from:ident;)*) => {
+        $(
+            $(#[$meta])*
+            #[derive(Debug)]
+            pub struct $name;
+
+            impl Codec<$ty> for $name {
+                fn encode(value: &$ty, w: &mut Writer) -> Result<(), &'static str> {
+                    w.put(&value.$to());
+                    Ok(())
+                }
+
+                fn decode(r: &mut Reader<'_>) -> Result<$ty, &'static str> {
+                    Ok(<$ty>::$from(r.array()?))
+                }
+            }
+        )*
+    };
+}
+
+int_codec! {
+    /// Single byte
+    U8: u8, to_be_bytes, from_be_bytes;
+    /// u16, big-endian
+    U16Be: u16, to_be_bytes, from_be_bytes;
+    /// u16, little-endian
+    U16Le: u16, to_le_bytes, from_le_bytes;
+    /// u32, big-endian
+    U32Be: u32, to_be_bytes, from_be_bytes;
+    /// u32, little-endian
+    U32Le: u32, to_le_bytes, from_le_bytes;
+    /// u64, big-endian
+    U64Be: u64, to_be_bytes, from_be_bytes;
+    /// u64, little-endian
+    U64Le: u64, to_le_bytes, from_le_bytes;
+}
+
+/// Fixed-size byte array
+#[derive(Debug)]
+pub struct Fixed;
+
+impl<const N: usize> Codec<[u8; N]> for Fixed {
+    fn encode(value: &[u8; N], w: &mut Writer) -> Result<(), &'static str> {
+        w.put(value);
+        Ok(())
+    }
+
+    fn decode(r: &mut Reader<'_>) -> Result<[u8; N], &'static str> {
+        r.array()
+    }
+}
+
+macro_rules! prefixed_codec {
+    ($($(#[$meta:meta])* $name:ident: $len:ty, $to:ident, $from:ident;)*) => {
+        $(
+            $(#[$meta])*
+            #[derive(Debug)]
+            pub struct $name;
+
+            impl Codec<Vec<u8>> for $name {
+                fn encode(value: &Vec<u8>, w: &mut Writer) -> Result<(), &'static str> {
+                    let len = <$len>::try_from(value.len()).map_err(|_| "Field too large")?;
+                    w.put(&len.$to());
+                    w.put(value);
+                    Ok(())
+                }
+
+                fn decode(r: &mut Reader<'_>) -> Result<Vec<u8>, &'static str> {
+                    let len = <$len>::$from(r.array()?) as usize;
+                    Ok(r.take(len)?.to_vec())
+                }
+            }
+
+            impl Codec<String> for $name {
+                fn encode(value: &String, w: &mut Writer) -> Result<(), &'static str> {
+                    let len = <$len>::try_from(value.len()).map_err(|_| "Field too large")?;
+                    w.put(&len.$to());
+                    w.put(value.as_bytes());
+                    Ok(())
+                }
+
+                fn decode(r: &mut Reader<'_>) -> Result<String, &'static str> {
+                    let bytes = <$name as Codec<Vec<u8>>>::decode(r)?;
+                    String::from_utf8(bytes).map_err(|_| "Invalid UTF-8 in message")
+                }
+            }
+        )*
+    };
+}
+
+prefixed_codec! {
+    /// Byte string with a 1-byte length
+    Bytes8: u8, to_be_bytes, from_be_bytes;
+    /// Byte string with a big-endian u16 length
+    Bytes16Be: u16, to_be_bytes, from_be_bytes;
+    /// Byte string with a little-endian u32 length
+    Bytes32Le: u32, to_le_bytes, from_le_bytes;
+}
+
+/// Byte string running to the end of the message (must be the last field)
+#[derive(Debug)]
+pub struct Rest;
+
+impl Codec<Vec<u8>> for Rest {
+    fn encode(value: &Vec<u8>, w: &mut Writer) -> Result<(), &'static str> {
+        w.put(value);
+        Ok(())
+    }
+
+    fn decode(r: &mut Reader<'_>) -> Result<Vec<u8>, &'static str> {
+        Ok(r.rest().to_vec())
+    }
+}
+
+/// Nested message
+#[derive(Debug)]
+pub struct Nested;
+
+impl<T: Wire> Codec<T> for Nested {
+    fn encode(value: &T, w: &mut Writer) -> Result<(), &'static str> {
+        value.encode_into(w)
+    }
+
+    fn decode(r: &mut Reader<'_>) -> Result<T, &'static str> {
+        T::decode_from(r)
+    }
+}
+
+/// Presence byte (0 or 1) followed by the value when present
+#[derive(Debug)]
+pub struct Optional<C>(PhantomData<C>);
+
+impl<T, C: Codec<T>> Codec<Option<T>> for Optional<C> {
+    fn encode(value: &Option<T>, w: &mut Writer) -> Result<(), &'static str> {
+        match value {
+            None => w.put(&[0]),
+            Some(inner) => {
+                w.put(&[1]);
+                C::encode(inner, w)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn decode(r: &mut Reader<'_>) -> Result<Option<T>, &'static str> {
+        match r.array::<1>()? {
+            [0] => Ok(None),
+            [1] => Ok(Some(C::decode(r)?)),
+            _ => Err("Invalid presence flag"),
+        }
+    }
+}
+
+/// Values repeated to the end of the message (must be the last field)
+#[derive(Debug)]
+pub struct Repeated<C>(PhantomData<C>);
+
+impl<T, C: Codec<T>> Codec<Vec<T>> for Repeated<C> {
+    fn encode(value: &Vec<T>, w: &mut Writer) -> Result<(), &'static str> {
+        value.iter().try_for_each(|item| C::encode(item, w))
+    }
+
+    fn decode(r: &mut Reader<'_>) -> Result<Vec<T>, &'static str> {
+        let mut items = Vec::new();
+        while !r.is_empty() {
+            items.push(C::decode(r)?);
+        }
+        Ok(items)
+    }
+}
+
+/// Little-endian u32 count followed by the values (usable mid-message)
+#[derive(Debug)]
+pub struct Counted<C>(PhantomData<C>);
+
+impl<T, C: Codec<T>> Codec<Vec<T>> for Counted<C> {
+    fn encode(value: &Vec<T>, w: &mut Writer) -> Result<(), &'static str> {
+        let count = u32::try_from(value.len()).map_err(|_| "Field too large")?;
+        w.put(&count.to_le_bytes());
+        value.iter().try_for_each(|item| C::encode(item, w))
+    }
+
+    fn decode(r: &mut Reader<'_>) -> Result<Vec<T>, &'static str> {
+        let count = u32::from_le_bytes(r.array()?);
+        // No preallocation: the count is untrusted, running out of input stops it
+        let mut items = Vec::new();
+        for _ in 0..count {
+            items.push(C::decode(r)?);
+        }
+        Ok(items)
+    }
+}
+
+/// Declare a wire message: a struct plus derived `Wire` encode/decode
+///
+/// ```
+/// utils::wire_struct! {
+///     /// Example header
+///     #[derive(Debug, PartialEq)]
+///     pub struct Example {
+///         pub kind: u8 => utils::wire::U8,
+///         pub body: Vec<u8> => utils::wire::Bytes16Be,
+///     }
+/// }
+/// use utils::wire::Wire;
+/// let bytes = Example { kind: 7, body: b"hi".to_vec() }.to_wire().unwrap();
+/// assert_eq!(bytes, [7, 0, 2, b'h', b'i']);
+/// ```
+#[macro_export]
+macro_rules! wire_struct {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$fmeta:meta])* $fvis:vis $field:ident : $ty:ty => $codec:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($(#[$fmeta])* $fvis $field: $ty,)*
+        }
+
+        impl $crate::wire::Wire for $name {
+            fn encode_into(&self, w: &mut $crate::wire::Writer) -> Result<(), &'static str> {
+                $(<$codec as $crate::wire::Codec<$ty>>::encode(&self.$field, w)?;)*
+                Ok(())
+            }
+
+            fn decode_from(r: &mut $crate::wire::Reader<'_>) -> Result<Self, &'static str> {
+                Ok(Self {
+                    $($field: <$codec as $crate::wire::Codec<$ty>>::decode(r)?,)*
+                })
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    crate::wire_struct! {
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        struct Sample {
+            kind: u8 => U8,
+            id: u32 => U32Le,
+            seq: u64 => U64Be,
+            tag: [u8; 4] => Fixed,
+            name: String => Bytes8,
+            extra: Option<Vec<u8>> => Optional<Bytes16Be>,
+            tail: Vec<u64> => Repeated<U16LeAsU64>,
+        }
+    }
+
+    /// Custom codec: u64 values stored as u16
+    struct U16LeAsU64;
+
+    impl Codec<u64> for U16LeAsU64 {
+        fn encode(value: &u64, w: &mut Writer) -> Result<(), &'static str> {
+            U16Le::encode(&u16::try_from(*value).map_err(|_| "Value too large")?, w)
+        }
+
+        fn decode(r: &mut Reader<'_>) -> Result<u64, &'static str> {
+            Ok(U16Le::decode(r)? as u64)
+        }
+    }
+
+    fn sample() -> Sample {
+        Sample {
+            kind: 2,
+            id: 0x01020304,
+            seq: 5,
+            tag: *b"AQ01",
+            name: "ab".to_string(),
+            extra: Some(vec![9]),
+            tail: vec![1, 258],
+        }
+    }
+
+    #[test]
+    fn test_layout_and_roundtrip() {
+        let bytes = sample().to_wire().unwrap();
+        let expected: Vec<u8> = [
+            &[2][..],
+            &[4, 3, 2, 1],
+            &[0, 0, 0, 0, 0, 0, 0, 5],
+            b"AQ01",
+            &[2, b'a', b'b'],
+            &[1, 0, 1, 9],
+            &[1, 0, 2, 1],
+        ]
+        .concat();
+        assert_eq!(bytes, expected);
+        assert_eq!(Sample::from_wire(&bytes).unwrap(), sample());
+
+        let none = Sample { extra: None, tail: vec![], ..sample() };
+        assert_eq!(Sample::from_wire(&none.to_wire().unwrap()).unwrap(), none);
+    }
+
+    #[test]
+    fn test_strict_decoding() {
+        let bytes = sample().to_wire().unwrap();
+        // Every truncation point fails or leaves the repeated tail short
+        for len in 0..bytes.len() - 4 {
+            assert!(Sample::from_wire(&bytes[..len]).is_err(), "prefix {} accepted", len);
+        }
+        let mut bad_flag = bytes.clone();
+        bad_flag[20] = 2;
+        assert_eq!(Sample::from_wire(&bad_flag), Err("Invalid presence flag"));
+
+        let oversized = Sample { name: "x".repeat(256), ..sample() };
+        assert_eq!(oversized.to_wire(), Err("Field too large"));
+
+        let (header, rest) = Header::from_wire_prefix(&[1, 2, 3]).unwrap();
+        assert_eq!((header.a, rest), (1, &[2, 3][..]));
+        assert_eq!(Header::from_wire(&[1, 2]), Err("Trailing bytes in message"));
+    }
+
+    #[test]
+    fn test_counted_values_are_self_delimiting() {
+        let list = Listed { names: vec!["a".to_string(), "bc".to_string()], tail: 7 };
+        let bytes = list.to_wire().unwrap();
+        assert_eq!(bytes, [&[2, 0, 0, 0][..], &[1, 0, 0, 0, b'a'], &[2, 0, 0, 0, b'b', b'c'], &[7]].concat());
+        assert_eq!(Listed::from_wire(&bytes).unwrap(), list);
+
+        // A count larger than the input fails instead of allocating
+        assert!(Listed::from_wire(&[0xff, 0xff, 0xff, 0xff, 7]).is_err());
+    }
+
+    crate::wire_struct! {
+        #[derive(Debug, PartialEq)]
+        struct Header {
+            a: u8 => U8,
+        }
+    }
+
+    crate::wire_struct! {
+        #[derive(Debug, PartialEq)]
+        struct Listed {
+            names: Vec<String> => Counted<Bytes32Le>,
+            tail: u8 => U8,
+        }
+    }
+}
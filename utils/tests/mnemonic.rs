@@ -0,0 +1,35 @@
+//! Property-based tests for mnemonic round-tripping
+
+use proptest::prelude::*;
+use utils::mnemonic::Mnemonic;
+
+proptest! {
+    #[test]
+    fn entropy_mnemonic_roundtrip(
+        words in 4usize..=8,
+        seed in prop::collection::vec(any::<u8>(), 32)
+    ) {
+        let entropy = &seed[..words * 4];
+        let mnemonic = Mnemonic::from_entropy(entropy).unwrap();
+        prop_assert_eq!(mnemonic.word_count(), words * 3);
+
+        let parsed = Mnemonic::parse(mnemonic.phrase()).unwrap();
+        prop_assert_eq!(parsed.to_entropy().unwrap(), entropy.to_vec());
+    }
+
+    #[test]
+    fn single_word_substitution_is_detected_or_changes_entropy(
+        seed in prop::collection::vec(any::<u8>(), 16),
+        position in 0usize..12,
+        replacement in 0usize..2048
+    ) {
+        let mnemonic = Mnemonic::from_entropy(&seed).unwrap();
+        let mut words: Vec<&str> = mnemonic.phrase().split(' ').collect();
+        prop_assume!(words[position] != utils::mnemonic::wordlist()[replacement]);
+        words[position] = utils::mnemonic::wordlist()[replacement];
+
+        if let Ok(altered) = Mnemonic::parse(&words.join(" ")) {
+            prop_assert_ne!(altered.to_entropy().unwrap(), seed);
+        }
+    }
+}
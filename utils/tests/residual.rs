@@ -0,0 +1,48 @@
+//! Zeroization audit of the secret-holding containers
+
+use utils::allocaudit::{assert_no_residual, watch, TrackingAllocator};
+use utils::memory::{zeroize_vec, SecureArena, SecureBox};
+use utils::pool::PooledBuffer;
+
+#[global_allocator]
+static ALLOC: TrackingAllocator = TrackingAllocator;
+
+const SECRET: [u8; 32] = *b"residual-audit-secret-0123456789";
+
+#[test]
+fn detects_unwiped_buffer() {
+    let watch = watch(&SECRET);
+    drop(SECRET.to_vec());
+    assert_eq!(watch.residual_frees(), 1);
+}
+
+#[test]
+fn secret_containers_leave_no_residue() {
+    assert_no_residual(&SECRET, || {
+        let mut arena = SecureArena::new(SECRET.len());
+        arena.as_mut_slice().copy_from_slice(&SECRET);
+
+        let mut boxed: SecureBox<Vec<u8>> = SecureBox::default();
+        boxed.extend_from_slice(&SECRET);
+
+        let mut pooled = PooledBuffer::with_capacity(64);
+        pooled.extend_from_slice(&SECRET);
+        drop(pooled.into_vec().into_iter().map(|b| b ^ 0xFF).collect::<Vec<_>>());
+
+        let mut plain = Vec::with_capacity(64);
+        plain.extend_from_slice(&SECRET);
+        zeroize_vec(plain);
+    });
+}
+
+#[test]
+fn vec_growth_leaves_old_block() {
+    // Reallocation frees the old block without wiping it: size secret
+    // buffers up front
+    let watch = watch(&SECRET);
+    let mut boxed: SecureBox<Vec<u8>> = SecureBox::new(Vec::with_capacity(SECRET.len()));
+    boxed.extend_from_slice(&SECRET);
+    boxed.reserve(4096);
+    drop(boxed);
+    assert_eq!(watch.residual_frees(), 1);
+}